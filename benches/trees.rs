@@ -0,0 +1,212 @@
+//! Benchmarks comparing the jtree types with each other and with `std::collections::BTreeSet`.
+//! 
+//! Run with `cargo bench`, or e.g. `cargo bench -- contains` to run a subset.
+
+use std::collections::BTreeSet;
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use jtree::{Javlt, Jblst, Jbst};
+use jtree::linked::LinkedJavlt;
+use jtree::radix::RadixSet;
+use jtree::IntSet;
+
+const SIZES: [u32; 3] = [100, 1_000, 10_000];
+
+/// The order in which the values are inserted, which matters a lot to the unbalanced trees.
+#[derive(Clone, Copy, Debug)]
+enum Order {
+    Ascending,
+    Shuffled,
+}
+
+impl Order {
+    fn values(self, size: u32) -> Vec<u32> {
+        let mut values: Vec<u32> = (0..size).collect();
+        if let Order::Shuffled = self {
+            // Fisher-Yates with a fixed xorshift seed, so every run sees the same sequence
+            let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+            for i in (1..values.len()).rev() {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                values.swap(i, (state % (i as u64 + 1)) as usize);
+            }
+        }
+        values
+    }
+}
+
+const ORDERS: [Order; 2] = [Order::Ascending, Order::Shuffled];
+
+/// A Jbst of the values, without a depth limit, so values in ascending order make it as deep as it is big.
+fn jbst(values: &[u32]) -> Jbst<u32> {
+    let mut tree = Jbst::with_depth_limit(usize::MAX);
+    let _ = tree.add_all(values.iter().copied());
+    tree
+}
+
+/// A Jblst of the values, without a depth limit, like `jbst`.
+fn jblst(values: &[u32]) -> Jblst<u32> {
+    let mut tree = Jblst::with_depth_limit(usize::MAX);
+    let _ = tree.add_all(values.iter().copied());
+    tree
+}
+
+fn bench_insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert");
+    group.sample_size(20);
+    for order in ORDERS {
+        for size in SIZES {
+            let values = order.values(size);
+            let id = |name: &str| BenchmarkId::new(format!("{name}/{order:?}"), size);
+            group.bench_with_input(id("Jbst"), &values, |b, values| {
+                b.iter(|| jbst(values))
+            });
+            group.bench_with_input(id("Jblst"), &values, |b, values| {
+                b.iter(|| jblst(values))
+            });
+            group.bench_with_input(id("Javlt"), &values, |b, values| {
+                b.iter(|| Javlt::from_collection(values.iter().copied()))
+            });
+            group.bench_with_input(id("BTreeSet"), &values, |b, values| {
+                b.iter(|| values.iter().copied().collect::<BTreeSet<u32>>())
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_contains(c: &mut Criterion) {
+    let mut group = c.benchmark_group("contains");
+    group.sample_size(20);
+    for order in ORDERS {
+        for size in SIZES {
+            let values = order.values(size);
+            let probes = Order::Shuffled.values(size * 2); // about half of these are misses
+            let id = |name: &str| BenchmarkId::new(format!("{name}/{order:?}"), size);
+            let jbst = jbst(&values);
+            group.bench_with_input(id("Jbst"), &probes, |b, probes| {
+                b.iter(|| probes.iter().filter(|p| jbst.contains(p)).count())
+            });
+            let jblst = jblst(&values);
+            group.bench_with_input(id("Jblst"), &probes, |b, probes| {
+                b.iter(|| probes.iter().filter(|p| jblst.contains(p)).count())
+            });
+            let javlt = Javlt::from_collection(values.iter().copied());
+            group.bench_with_input(id("Javlt"), &probes, |b, probes| {
+                b.iter(|| probes.iter().filter(|p| javlt.contains(p)).count())
+            });
+            let btree: BTreeSet<u32> = values.iter().copied().collect();
+            group.bench_with_input(id("BTreeSet"), &probes, |b, probes| {
+                b.iter(|| probes.iter().filter(|p| btree.contains(p)).count())
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_remove(c: &mut Criterion) {
+    let mut group = c.benchmark_group("remove");
+    group.sample_size(20);
+    for order in ORDERS {
+        for size in SIZES {
+            let values = order.values(size);
+            let removals = Order::Shuffled.values(size);
+            let id = |name: &str| BenchmarkId::new(format!("{name}/{order:?}"), size);
+            group.bench_with_input(id("Jbst"), &removals, |b, removals| {
+                b.iter_batched(|| jbst(&values), |mut tree| {
+                    for value in removals {
+                        let _ = tree.drop_value(*value);
+                    }
+                    tree
+                }, criterion::BatchSize::LargeInput)
+            });
+            group.bench_with_input(id("Jblst"), &removals, |b, removals| {
+                b.iter_batched(|| jblst(&values), |mut tree| {
+                    for value in removals {
+                        let _ = tree.drop_value(*value);
+                    }
+                    tree
+                }, criterion::BatchSize::LargeInput)
+            });
+            group.bench_with_input(id("Javlt"), &removals, |b, removals| {
+                b.iter_batched(|| Javlt::from_collection(values.iter().copied()), |mut tree| {
+                    for value in removals {
+                        let _ = tree.drop_value(*value);
+                    }
+                    tree
+                }, criterion::BatchSize::LargeInput)
+            });
+            group.bench_with_input(id("BTreeSet"), &removals, |b, removals| {
+                b.iter_batched(|| values.iter().copied().collect::<BTreeSet<u32>>(), |mut tree| {
+                    for value in removals {
+                        tree.remove(value);
+                    }
+                    tree
+                }, criterion::BatchSize::LargeInput)
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_iterate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("iterate");
+    group.sample_size(20);
+    for size in SIZES {
+        let values = Order::Shuffled.values(size);
+        let jbst = Jbst::from_collection(values.iter().copied());
+        group.bench_function(BenchmarkId::new("Jbst", size), |b| {
+            b.iter(|| black_box(jbst.as_vec()))
+        });
+        let jblst = Jblst::from_collection(values.iter().copied());
+        group.bench_function(BenchmarkId::new("Jblst", size), |b| {
+            b.iter(|| black_box(jblst.as_vec()))
+        });
+        let javlt = Javlt::from_collection(values.iter().copied());
+        group.bench_function(BenchmarkId::new("Javlt", size), |b| {
+            b.iter(|| black_box(javlt.as_vec()))
+        });
+        let linked = LinkedJavlt::from_collection(values.iter().copied());
+        group.bench_function(BenchmarkId::new("LinkedJavlt", size), |b| {
+            b.iter(|| black_box(linked.as_vec()))
+        });
+        let btree: BTreeSet<u32> = values.iter().copied().collect();
+        group.bench_function(BenchmarkId::new("BTreeSet", size), |b| {
+            b.iter(|| black_box(btree.iter().copied().collect::<Vec<u32>>()))
+        });
+    }
+    group.finish();
+}
+
+/// Lookups of random `u64` IDs, spread over the whole range, which is what `RadixSet` is for.
+fn bench_contains_ids(c: &mut Criterion) {
+    let mut group = c.benchmark_group("contains_ids");
+    group.sample_size(20);
+    for size in [1_000, 100_000] {
+        // spread the shuffled values over the whole u64 range with a multiplicative hash
+        let ids: Vec<u64> = Order::Shuffled.values(size * 2).into_iter().map(|n| u64::from(n).wrapping_mul(0x9E37_79B9_7F4A_7C15)).collect();
+        let (stored, probes) = (&ids[..size as usize], &ids[..]); // half of the probes are misses
+        let javlt = Javlt::from_collection(stored.iter().copied());
+        group.bench_with_input(BenchmarkId::new("Javlt", size), probes, |b, probes| {
+            b.iter(|| probes.iter().filter(|p| javlt.contains(p)).count())
+        });
+        let int_set = IntSet::from_collection(stored.iter().copied());
+        group.bench_with_input(BenchmarkId::new("IntSet", size), probes, |b, probes| {
+            b.iter(|| probes.iter().filter(|p| int_set.contains(**p)).count())
+        });
+        let radix = RadixSet::from_collection(stored.iter().copied());
+        group.bench_with_input(BenchmarkId::new("RadixSet", size), probes, |b, probes| {
+            b.iter(|| probes.iter().filter(|p| radix.contains(**p)).count())
+        });
+        let btree: BTreeSet<u64> = stored.iter().copied().collect();
+        group.bench_with_input(BenchmarkId::new("BTreeSet", size), probes, |b, probes| {
+            b.iter(|| probes.iter().filter(|p| btree.contains(p)).count())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_insert, bench_contains, bench_remove, bench_iterate, bench_contains_ids);
+criterion_main!(benches);
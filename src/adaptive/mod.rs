@@ -0,0 +1,271 @@
+use std::{fmt, mem, ops::{Bound, RangeBounds}};
+
+use crate::bounds::is_above;
+use crate::errors::TreeError;
+use crate::javlt::Javlt;
+use crate::ordered::OrderedSet;
+
+
+
+/// # Adaptive Set
+///
+/// An ordered set of unique values that starts out as a sorted `Vec`, which is compact and quick to
+/// search while it's small, and turns itself into a `Javlt` once it isn't: when it holds more than
+/// `max_len` values, or when its changes have shifted more than `max_shifted` values along the Vec in all
+/// (inserting or removing near the front of a long Vec moves everything after it).  The change is made
+/// once, in O(n), and the set stays a tree after that.  The methods are the same either way.
+///
+///     use jtree::adaptive::AdaptiveSet;
+///
+///     let mut set = AdaptiveSet::with_thresholds(4, 100);
+///     for value in [30, 10, 20] {
+///         set.add(value).unwrap();
+///     }
+///     assert!( !set.is_tree() );
+///     set.add(40).unwrap();
+///     set.add(50).unwrap();
+///     assert!( set.is_tree() );
+///     assert_eq!( vec!(&10,&20,&30,&40,&50), set.values() );
+pub struct AdaptiveSet<T: PartialEq + PartialOrd> {
+    repr: Repr<T>,
+    max_len: usize,
+    max_shifted: usize,
+    shifted: usize, // values moved along the Vec by changes so far
+}
+
+enum Repr<T: PartialEq + PartialOrd> {
+    Sorted(Vec<T>),
+    Tree(Javlt<T>),
+}
+
+/// Sets made with `new` become trees beyond this many values...
+const DEFAULT_MAX_LEN: usize = 64;
+/// ...or once their changes have shifted this many values along the Vec.
+const DEFAULT_MAX_SHIFTED: usize = 4096;
+
+impl <T: PartialEq + PartialOrd> AdaptiveSet<T> {
+
+    /// Create a new, empty set that becomes a tree beyond 64 values, or once its changes have shifted
+    /// 4096 values along the Vec.
+    pub fn new() -> Self {
+        Self::with_thresholds(DEFAULT_MAX_LEN, DEFAULT_MAX_SHIFTED)
+    }
+
+    /// Create a new, empty set that becomes a tree when it holds more than `max_len` values, or when its
+    /// changes have shifted more than `max_shifted` values along the Vec.
+    pub fn with_thresholds(max_len: usize, max_shifted: usize) -> Self {
+        Self {
+            repr: Repr::Sorted(Vec::new()),
+            max_len,
+            max_shifted,
+            shifted: 0,
+        }
+    }
+
+    /// Create a new set from a collection (vector, array, or whatever), skipping duplicates.
+    pub fn from_collection<U: IntoIterator<Item = T>>(collection: U) -> Self {
+        let mut new_set = Self::new();
+        for value in collection {
+            let _ = new_set.add(value);
+        }
+        new_set
+    }
+
+    /// Insert a value, returning a TreeError::ValueAlreadyStored if it's already in the set.
+    pub fn add(&mut self, value: T) -> Result<(),TreeError<T>> {
+        match &mut self.repr {
+            Repr::Tree(tree) => return tree.add(value),
+            Repr::Sorted(values) => {
+                let index = values.partition_point(|stored| *stored < value);
+                if values.get(index) == Some(&value) {
+                    return Err(TreeError::ValueAlreadyStored { value });
+                }
+                if values.len() == u32::MAX as usize {
+                    return Err(TreeError::CapacityExceeded { value });
+                }
+                self.shifted += values.len() - index;
+                values.insert(index, value);
+            },
+        }
+        self.adapt();
+        Ok(())
+    }
+
+    /// If the value is in the set, delete it.  Otherwise a TreeError::ValueNotFound will be returned.
+    pub fn drop_value(&mut self, value: T) -> Result<(),TreeError<T>> {
+        match &mut self.repr {
+            Repr::Tree(tree) => return tree.drop_value(value),
+            Repr::Sorted(values) => {
+                let index = values.partition_point(|stored| *stored < value);
+                if values.get(index) != Some(&value) {
+                    return Err(TreeError::ValueNotFound { value });
+                }
+                values.remove(index);
+                self.shifted += values.len() - index;
+            },
+        }
+        self.adapt();
+        Ok(())
+    }
+
+    /// Returns true if the value is in the set
+    pub fn contains(&self, value: &T) -> bool {
+        return match &self.repr {
+            Repr::Tree(tree) => tree.contains(value),
+            Repr::Sorted(values) => values.get(values.partition_point(|stored| stored < value)) == Some(value),
+        };
+    }
+
+    /// Get the number of values in the set
+    pub fn get_size(&self) -> u32 {
+        return match &self.repr {
+            Repr::Tree(tree) => tree.get_size(),
+            Repr::Sorted(values) => values.len() as u32,
+        };
+    }
+
+    /// Returns true once the set has become a `Javlt`.
+    pub fn is_tree(&self) -> bool {
+        matches!(self.repr, Repr::Tree(_))
+    }
+
+    /// Returns references to all the values in the set as an ordered Vec from least to greatest.
+    pub fn values(&self) -> Vec<&T> {
+        OrderedSet::iter(self).collect()
+    }
+
+    /// Consume the set, moving its values into a Vec from least to greatest.
+    pub fn into_sorted_vec(self) -> Vec<T> {
+        return match self.repr {
+            Repr::Tree(tree) => tree.into_sorted_vec(),
+            Repr::Sorted(values) => values,
+        };
+    }
+
+    /// Become a tree, if the Vec has grown too long or been shifted too much.
+    fn adapt(&mut self) {
+        let values = match &mut self.repr {
+            Repr::Sorted(values) if values.len() > self.max_len || self.shifted > self.max_shifted => mem::take(values),
+            _ => return,
+        };
+        self.repr = Repr::Tree(Javlt::from_sorted_vec(values));
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd + Clone> AdaptiveSet<T> {
+
+    /// Returns all the values in the set as an ordered Vec from least to greatest.
+    pub fn as_vec(&self) -> Vec<T> {
+        OrderedSet::iter(self).cloned().collect()
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd> OrderedSet<T> for AdaptiveSet<T> {
+    fn add(&mut self, value: T) -> Result<(),TreeError<T>> { AdaptiveSet::add(self, value) }
+    fn remove(&mut self, value: T) -> Result<(),TreeError<T>> { self.drop_value(value) }
+    fn contains(&self, value: &T) -> bool { AdaptiveSet::contains(self, value) }
+    fn len(&self) -> usize { self.get_size() as usize }
+    fn iter<'a>(&'a self) -> impl Iterator<Item = &'a T> where T: 'a { OrderedSet::range(self, ..) }
+    fn range<'a, R: RangeBounds<T>>(&'a self, range: R) -> impl Iterator<Item = &'a T> where T: 'a {
+        return match &self.repr {
+            Repr::Tree(tree) => Either::Tree(OrderedSet::range(tree, range)),
+            Repr::Sorted(values) => {
+                let start = match range.start_bound() {
+                    Bound::Unbounded => 0,
+                    Bound::Included(lower) => values.partition_point(|stored| stored < lower),
+                    Bound::Excluded(lower) => values.partition_point(|stored| stored <= lower),
+                };
+                Either::Sorted(values[start..].iter().take_while(move |stored| !is_above(*stored, range.end_bound())))
+            },
+        };
+    }
+    fn least(&self) -> Option<&T> { OrderedSet::iter(self).next() }
+    fn greatest(&self) -> Option<&T> {
+        return match &self.repr {
+            Repr::Tree(tree) => OrderedSet::greatest(tree),
+            Repr::Sorted(values) => values.last(),
+        };
+    }
+}
+
+/// An iterator over either representation's values.
+enum Either<S, T> {
+    Sorted(S),
+    Tree(T),
+}
+
+impl <'a, V: 'a, S: Iterator<Item = &'a V>, T: Iterator<Item = &'a V>> Iterator for Either<S, T> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        return match self {
+            Either::Sorted(values) => values.next(),
+            Either::Tree(values) => values.next(),
+        };
+    }
+}
+
+impl <T: PartialEq + PartialOrd> Default for AdaptiveSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <T: PartialEq + PartialOrd + fmt::Debug> fmt::Debug for AdaptiveSet<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("AdaptiveSet")
+            .field("is_tree", &self.is_tree())
+            .field("values", &self.values())
+            .finish()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_answers_before_and_after_becoming_a_tree() {
+        let mut set = AdaptiveSet::with_thresholds(50, usize::MAX);
+        for value in (0..50).map(|n| n * 7 % 50) {
+            set.add(value).unwrap();
+        }
+        assert!( !set.is_tree() );
+        let before: Vec<u32> = OrderedSet::range(&set, 10..=20).cloned().collect();
+        assert_eq!( Err(TreeError::ValueAlreadyStored { value: 3 }), set.add(3) );
+        set.add(50).unwrap();
+        assert!( set.is_tree() );
+        assert_eq!( before, OrderedSet::range(&set, 10..=20).cloned().collect::<Vec<_>>() );
+        assert_eq!( (0..=50).collect::<Vec<_>>(), set.as_vec() );
+        assert_eq!( Some(&50), set.greatest() );
+        set.drop_value(0).unwrap();
+        assert_eq!( Err(TreeError::ValueNotFound { value: 0 }), set.drop_value(0) );
+        assert_eq!( Some(&1), set.least() );
+    }
+
+    #[test]
+    fn churn_at_the_front_makes_a_tree() {
+        let mut set = AdaptiveSet::with_thresholds(1000, 500);
+        set.add(1000).unwrap();
+        for value in (0..40).rev() {
+            set.add(value).unwrap();
+        }
+        // each add went in front of every value added before it
+        assert!( set.is_tree() );
+        assert_eq!( 41, set.get_size() );
+
+        let mut set = AdaptiveSet::with_thresholds(1000, 500);
+        for value in 0..40 {
+            set.add(value).unwrap();
+        }
+        // adding at the back shifts nothing
+        assert!( !set.is_tree() );
+        assert!( set.contains(&39) && !set.contains(&40) );
+        assert_eq!( vec!(&38,&39), OrderedSet::range(&set, (Bound::Excluded(37), Bound::Unbounded)).collect::<Vec<_>>() );
+        assert_eq!( (0..40).collect::<Vec<_>>(), set.into_sorted_vec() );
+    }
+
+}
@@ -0,0 +1,699 @@
+use std::{cmp::max, fmt, marker::PhantomData, ops::{Add, Bound, RangeBounds}};
+
+use crate::bounds::{is_above, is_below};
+use crate::errors::TreeError;
+
+
+
+/// A way of summarizing values (a monoid): a summary of each single value, an `empty` summary of no values,
+/// and a `combine` that joins the summaries of two neighboring runs of values.  `combine` must be associative,
+/// with `empty` as its identity, but needn't be commutative: the left run always comes first.  Summaries are
+/// compared by `check_invariants`, to find any that weren't kept up to date.
+pub trait Aggregate<T> {
+    type Summary: Clone + PartialEq;
+    /// The summary of no values at all.
+    fn empty() -> Self::Summary;
+    /// The summary of a single value.
+    fn single(value: &T) -> Self::Summary;
+    /// Join the summaries of two neighboring runs of values, `left` being the run of lesser values.
+    fn combine(left: &Self::Summary, right: &Self::Summary) -> Self::Summary;
+}
+
+/// Sums the values, starting from their type's default (zero, for the number types).
+pub struct Sum;
+
+impl <T: Clone + Default + PartialEq + Add<Output = T>> Aggregate<T> for Sum {
+    type Summary = T;
+    fn empty() -> T {
+        T::default()
+    }
+    fn single(value: &T) -> T {
+        value.clone()
+    }
+    fn combine(left: &T, right: &T) -> T {
+        left.clone() + right.clone()
+    }
+}
+
+/// Counts the values.
+pub struct Count;
+
+impl <T> Aggregate<T> for Count {
+    type Summary = usize;
+    fn empty() -> usize {
+        0
+    }
+    fn single(_: &T) -> usize {
+        1
+    }
+    fn combine(left: &usize, right: &usize) -> usize {
+        left + right
+    }
+}
+
+/// Picks out the part of a value that `Min` and `Max` compare, for values that are ordered in the tree by
+/// something else, such as records ordered by id.
+pub trait Field<T> {
+    type Output: PartialOrd + Clone;
+    /// The field of this value.
+    fn of(value: &T) -> Self::Output;
+}
+
+/// The whole value, as its own field: `Min` and `Max` compare the values themselves unless given another `Field`.
+pub struct Whole;
+
+impl <T: PartialOrd + Clone> Field<T> for Whole {
+    type Output = T;
+    fn of(value: &T) -> T {
+        value.clone()
+    }
+}
+
+/// Finds the least value's field (or the least value, by default), or None for no values.  Where two are
+/// equal, or can't be compared, the first is kept.
+///
+///     use jtree::aggregate::{AggregateJavlt, Field, Min};
+///
+///     // jobs ordered by id, each with a deadline
+///     #[derive(PartialEq, PartialOrd, Clone)]
+///     struct Job { id: u32, deadline: u32 }
+///
+///     struct Deadline;
+///     impl Field<Job> for Deadline {
+///         type Output = u32;
+///         fn of(job: &Job) -> u32 { job.deadline }
+///     }
+///
+///     let jobs: AggregateJavlt<Job, Min<Deadline>> = AggregateJavlt::from_collection([
+///         Job { id: 1, deadline: 40 }, Job { id: 2, deadline: 15 }, Job { id: 3, deadline: 30 },
+///     ]);
+///     let soonest = jobs.aggregate_range(Job { id: 3, deadline: 0 }..);
+///     assert_eq!( Some(30), soonest );
+pub struct Min<F = Whole>(PhantomData<F>);
+
+impl <T, F: Field<T>> Aggregate<T> for Min<F> {
+    type Summary = Option<F::Output>;
+    fn empty() -> Self::Summary {
+        None
+    }
+    fn single(value: &T) -> Self::Summary {
+        Some(F::of(value))
+    }
+    fn combine(left: &Self::Summary, right: &Self::Summary) -> Self::Summary {
+        return match (left, right) {
+            (Some(l), Some(r)) if r < l => right.clone(),
+            (None, _) => right.clone(),
+            _ => left.clone(),
+        };
+    }
+}
+
+/// Finds the greatest value's field (or the greatest value, by default), or None for no values.  Where two
+/// are equal, or can't be compared, the first is kept.
+pub struct Max<F = Whole>(PhantomData<F>);
+
+impl <T, F: Field<T>> Aggregate<T> for Max<F> {
+    type Summary = Option<F::Output>;
+    fn empty() -> Self::Summary {
+        None
+    }
+    fn single(value: &T) -> Self::Summary {
+        Some(F::of(value))
+    }
+    fn combine(left: &Self::Summary, right: &Self::Summary) -> Self::Summary {
+        return match (left, right) {
+            (Some(l), Some(r)) if r > l => right.clone(),
+            (None, _) => right.clone(),
+            _ => left.clone(),
+        };
+    }
+}
+
+/// # Aggregate AVL Tree
+///
+/// An AVL tree (like `Javlt`) in which every node also keeps a summary of its subtree's values, kept up
+/// to date through adds, deletes and rotations.  The summary is defined by an `Aggregate`, such as `Sum`,
+/// `Count`, `Min`, `Max`, or one of your own, and `aggregate_range` can then summarize any range of values in O(log(n)).
+///
+///     use jtree::aggregate::{AggregateJavlt, Sum};
+///
+///     let mut tree: AggregateJavlt<u64, Sum> = AggregateJavlt::from_collection([5, 1, 8, 3, 9]);
+///     assert_eq!( 26, tree.aggregate() );
+///     assert_eq!( 16, tree.aggregate_range(2..9) ); // 3 + 5 + 8
+///     tree.drop_value(5).unwrap();
+///     assert_eq!( 11, tree.aggregate_range(2..=8) );
+///
+/// A custom aggregate, here the number of values and their total so that an average can be worked out:
+///
+///     use jtree::aggregate::{Aggregate, AggregateJavlt};
+///
+///     struct CountAndTotal;
+///     impl Aggregate<f64> for CountAndTotal {
+///         type Summary = (usize, f64);
+///         fn empty() -> (usize, f64) { (0, 0.0) }
+///         fn single(value: &f64) -> (usize, f64) { (1, *value) }
+///         fn combine(left: &(usize, f64), right: &(usize, f64)) -> (usize, f64) { (left.0 + right.0, left.1 + right.1) }
+///     }
+///
+///     let prices: AggregateJavlt<f64, CountAndTotal> = AggregateJavlt::from_collection([9.5, 12.0, 10.5, 30.0]);
+///     let (count, total) = prices.aggregate_range(..20.0);
+///     assert_eq!( 32.0 / 3.0, total / count as f64 );
+pub struct AggregateJavlt<T: PartialEq + PartialOrd + Clone, A: Aggregate<T>> {
+    size: u32,
+    root: Link<T, A>,
+    aggregate: PhantomData<A>,
+}
+
+type Link<T, A> = Option<Box<Node<T, A>>>;
+
+impl <T: PartialEq + PartialOrd + Clone, A: Aggregate<T>> AggregateJavlt<T, A> {
+
+    /// Create a new tree with no data
+    pub fn new() -> Self {
+        Self {
+            size: 0,
+            root: None,
+            aggregate: PhantomData,
+        }
+    }
+
+    /// Create a new tree from a collection (vector, array, or whatever), skipping duplicates.
+    pub fn from_collection<U: IntoIterator<Item = T>>(collection: U) -> Self {
+        let mut new_tree = Self::new();
+        for value in collection {
+            let _ = new_tree.add(value);
+        }
+        new_tree
+    }
+
+    /// Insert a value, returning a TreeError::ValueAlreadyStored if it's already in the tree.
+    pub fn add(&mut self, value: T) -> Result<(),TreeError<T>> {
+        if self.size == u32::MAX {
+            return Err(TreeError::CapacityExceeded { value });
+        }
+        Node::add(&mut self.root, value)?;
+        self.size += 1;
+        #[cfg(feature = "paranoid")]
+        self.paranoid_check("add");
+        Ok(())
+    }
+
+    /// If the value is in the tree, delete it.  Otherwise a TreeError::ValueNotFound will be returned.
+    pub fn drop_value(&mut self, value: T) -> Result<(),TreeError<T>> {
+        if !Node::drop_value(&mut self.root, &value) {
+            return Err(TreeError::ValueNotFound { value });
+        }
+        self.size -= 1;
+        #[cfg(feature = "paranoid")]
+        self.paranoid_check("drop_value");
+        Ok(())
+    }
+
+    /// Get the number of values in the tree
+    pub fn get_size(&self) -> u32 {
+        self.size
+    }
+
+    /// Returns true if the value is currently a member of the tree
+    pub fn contains(&self, value: &T) -> bool {
+        let mut link = &self.root;
+        while let Some(node) = link {
+            if *value == node.value {
+                return true;
+            }
+            link = if *value < node.value { &node.left } else { &node.right };
+        }
+        false
+    }
+
+    /// Returns all the values in the tree as an ordered Vec from least to greatest.
+    pub fn as_vec(&self) -> Vec<T> {
+        let mut vals = Vec::with_capacity(self.size as usize);
+        match &self.root {
+            None => (),
+            Some(branch) => branch.collect_values(&mut vals),
+        }
+        vals
+    }
+
+    /// The summary of all of the values, in O(1).
+    pub fn aggregate(&self) -> A::Summary {
+        Node::summary_of(&self.root)
+    }
+
+    /// The summary of the values within the range, in O(log(n)).
+    pub fn aggregate_range<R: RangeBounds<T>>(&self, range: R) -> A::Summary {
+        Node::range_summary(&self.root, range.start_bound(), range.end_bound())
+    }
+
+    /// Verify the tree's internal consistency: that values are in order, every node's height and summary are
+    /// correct and its subtrees are balanced, and the size matches the number of nodes.  Returns a
+    /// TreeError::InvariantViolation describing the first problem found, if any.
+    pub fn check_invariants(&self) -> Result<(),TreeError<T>> {
+        let nodes = match &self.root {
+            None => 0,
+            Some(branch) => branch.check_invariants(&mut String::from("root"), None, None)?,
+        };
+        if nodes != self.size {
+            return Err(TreeError::InvariantViolation(format!("size is {} but the tree holds {} values", self.size, nodes)));
+        }
+        Ok(())
+    }
+
+    /// With the `paranoid` feature, every change to the tree is followed by this check, which panics
+    /// with a description of the problem if the change broke an invariant.
+    #[cfg(feature = "paranoid")]
+    fn paranoid_check(&self, operation: &str) {
+        if let Err(TreeError::InvariantViolation(problem)) = self.check_invariants() {
+            panic!("AggregateJavlt::{operation} broke the tree: {problem} (size {})", self.size);
+        }
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd + Clone> AggregateJavlt<T, Count> {
+
+    /// Returns the value at a position in order (0 being the least value), or None if there aren't that
+    /// many values.  The subtrees' counts lead straight to it, in O(log(n)).
+    ///
+    ///     use jtree::aggregate::{AggregateJavlt, Count};
+    ///
+    ///     let tree: AggregateJavlt<char, Count> = AggregateJavlt::from_collection("jtree".chars());
+    ///     assert_eq!( Some(&'j'), tree.nth(1) );
+    ///     assert_eq!( None, tree.nth(4) );
+    pub fn nth(&self, index: usize) -> Option<&T> {
+        let mut index = index;
+        let mut link = &self.root;
+        while let Some(node) = link {
+            let left = Node::summary_of(&node.left);
+            if index < left {
+                link = &node.left;
+            } else if index == left {
+                return Some(&node.value);
+            } else {
+                index -= left + 1;
+                link = &node.right;
+            }
+        }
+        None
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd + Clone, A: Aggregate<T>> Default for AggregateJavlt<T, A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <T: PartialEq + PartialOrd + Clone + fmt::Debug, A: Aggregate<T>> fmt::Debug for AggregateJavlt<T, A> where A::Summary: fmt::Debug {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("AggregateJavlt")
+            .field("size", &self.get_size())
+            .field("aggregate", &self.aggregate())
+            .field("values", &self.as_vec())
+            .finish()
+    }
+}
+
+
+struct Node<T: PartialEq + PartialOrd + Clone, A: Aggregate<T>> {
+    value: T,
+    height: u32,
+    summary: A::Summary, // of this node's whole subtree
+    left: Link<T, A>,
+    right: Link<T, A>,
+}
+
+// The changing functions take the link that owns a node, rather than the node itself, so that a rotation
+// can put a different node in its place.  Every node whose children change has its height and summary
+// brought up to date by `update`.
+impl <T: PartialEq + PartialOrd + Clone, A: Aggregate<T>> Node<T, A> {
+
+    fn new(value: T) -> Self {
+        Self {
+            summary: A::single(&value),
+            value,
+            height: 1,
+            left: None,
+            right: None,
+        }
+    }
+
+    /// Insert a value, then rebalance the (sub)tree.
+    fn add(link: &mut Link<T, A>, value: T) -> Result<(),TreeError<T>> {
+        let node = match link {
+            None => {
+                *link = Some(Box::new(Node::new(value)));
+                return Ok(());
+            },
+            Some(node) => node,
+        };
+        if value == node.value {
+            return Err(TreeError::ValueAlreadyStored { value });
+        }
+        if value < node.value {
+            Self::add(&mut node.left, value)?;
+        } else {
+            Self::add(&mut node.right, value)?;
+        }
+        Self::rebalance(link);
+        Ok(())
+    }
+
+    /// Delete a value from the (sub)tree, then rebalance it.  Returns false if the value wasn't there.
+    fn drop_value(link: &mut Link<T, A>, value: &T) -> bool {
+        let node = match link {
+            Some(node) => node,
+            None => return false,
+        };
+        if *value < node.value {
+            if !Self::drop_value(&mut node.left, value) {
+                return false;
+            }
+        } else if *value > node.value {
+            if !Self::drop_value(&mut node.right, value) {
+                return false;
+            }
+        } else if node.left.is_none() {
+            // replace the node with its only child (or nothing)
+            let right = node.right.take();
+            *link = right;
+            return true;
+        } else if node.right.is_none() {
+            let left = node.left.take();
+            *link = left;
+            return true;
+        } else {
+            // both children are branches, so take the value's immediate successor out of the right branch
+            node.value = Self::take_least(&mut node.right);
+        }
+        Self::rebalance(link);
+        true
+    }
+
+    /// Delete the least value in a non-empty (sub)tree and return it.
+    fn take_least(link: &mut Link<T, A>) -> T {
+        let node = link.as_mut().unwrap();
+        if node.left.is_some() {
+            let least = Self::take_least(&mut node.left);
+            Self::rebalance(link);
+            return least;
+        }
+        let mut node = link.take().unwrap();
+        *link = node.right.take();
+        node.value
+    }
+
+    /// Bring the node up to date, and rotate the (sub)tree if it has become unbalanced.
+    fn rebalance(link: &mut Link<T, A>) {
+        let node = match link {
+            Some(node) => node,
+            None => return,
+        };
+        node.update();
+        let bf = node.compute_balancing_factor();
+        if bf > 1 {
+            // right-heavy; if the right child is left-heavy, this is a Right Left situation needing two rotations
+            if node.right.as_ref().unwrap().compute_balancing_factor() < 0 {
+                Self::rotate_right(&mut node.right);
+            }
+            Self::rotate_left(link);
+        } else if bf < -1 {
+            // left-heavy; if the left child is right-heavy, this is a Left Right situation needing two rotations
+            if node.left.as_ref().unwrap().compute_balancing_factor() > 0 {
+                Self::rotate_left(&mut node.left);
+            }
+            Self::rotate_right(link);
+        }
+    }
+
+    /// Make the node's right child the root of its (sub)tree.
+    fn rotate_left(link: &mut Link<T, A>) {
+        let mut old_root = link.take().unwrap();
+        let mut new_root = old_root.right.take().unwrap();
+        old_root.right = new_root.left.take();
+        old_root.update();
+        new_root.left = Some(old_root);
+        new_root.update();
+        *link = Some(new_root);
+    }
+
+    /// Make the node's left child the root of its (sub)tree.
+    fn rotate_right(link: &mut Link<T, A>) {
+        let mut old_root = link.take().unwrap();
+        let mut new_root = old_root.left.take().unwrap();
+        old_root.left = new_root.right.take();
+        old_root.update();
+        new_root.right = Some(old_root);
+        new_root.update();
+        *link = Some(new_root);
+    }
+
+    /// Recompute the node's height and summary from its children's.
+    fn update(&mut self) {
+        self.height = self.compute_height();
+        self.summary = self.compute_summary();
+    }
+
+    /// Height of a subtree is the height of its largest child subtree, plus 1.
+    fn compute_height(&self) -> u32 {
+        let left_height = self.left.as_ref().map_or(0, |node| node.height);
+        let right_height = self.right.as_ref().map_or(0, |node| node.height);
+        max(left_height, right_height).saturating_add(1)
+    }
+
+    /// Balancing factor is the height of the right subtree minus the height of the left subtree.
+    fn compute_balancing_factor(&self) -> i64 {
+        let left_height = self.left.as_ref().map_or(0, |node| node.height);
+        let right_height = self.right.as_ref().map_or(0, |node| node.height);
+        i64::from(right_height) - i64::from(left_height)
+    }
+
+    /// The summary of the left subtree, this value, and the right subtree, in that order.
+    fn compute_summary(&self) -> A::Summary {
+        let left = A::combine(&Self::summary_of(&self.left), &A::single(&self.value));
+        A::combine(&left, &Self::summary_of(&self.right))
+    }
+
+    /// The summary of a (possibly empty) subtree.
+    fn summary_of(link: &Link<T, A>) -> A::Summary {
+        return match link {
+            None => A::empty(),
+            Some(node) => node.summary.clone(),
+        };
+    }
+
+    /// The summary of the subtree's values between the bounds.  Once the search splits at a value within
+    /// the bounds, each side only has one bound left to check, so it visits O(log(n)) nodes.
+    fn range_summary(link: &Link<T, A>, lower: Bound<&T>, upper: Bound<&T>) -> A::Summary {
+        let node = match link {
+            None => return A::empty(),
+            Some(node) => node,
+        };
+        if is_below(&node.value, lower) {
+            return Self::range_summary(&node.right, lower, upper);
+        }
+        if is_above(&node.value, upper) {
+            return Self::range_summary(&node.left, lower, upper);
+        }
+        let left = A::combine(&Self::summary_from(&node.left, lower), &A::single(&node.value));
+        A::combine(&left, &Self::summary_to(&node.right, upper))
+    }
+
+    /// The summary of the subtree's values above the lower bound.
+    fn summary_from(link: &Link<T, A>, lower: Bound<&T>) -> A::Summary {
+        let node = match link {
+            None => return A::empty(),
+            Some(node) => node,
+        };
+        if is_below(&node.value, lower) {
+            return Self::summary_from(&node.right, lower);
+        }
+        let left = A::combine(&Self::summary_from(&node.left, lower), &A::single(&node.value));
+        A::combine(&left, &Self::summary_of(&node.right))
+    }
+
+    /// The summary of the subtree's values below the upper bound.
+    fn summary_to(link: &Link<T, A>, upper: Bound<&T>) -> A::Summary {
+        let node = match link {
+            None => return A::empty(),
+            Some(node) => node,
+        };
+        if is_above(&node.value, upper) {
+            return Self::summary_to(&node.left, upper);
+        }
+        let left = A::combine(&Self::summary_of(&node.left), &A::single(&node.value));
+        A::combine(&left, &Self::summary_to(&node.right, upper))
+    }
+
+    /// Recursively add values to the borrowed vector, from least to greatest.
+    fn collect_values(&self, value_vector: &mut Vec<T>) {
+        if let Some(node) = &self.left {
+            node.collect_values(value_vector);
+        }
+        value_vector.push(self.value.clone());
+        if let Some(node) = &self.right {
+            node.collect_values(value_vector);
+        }
+    }
+
+    /// Recursively verify that this (sub)tree's values are in order and strictly between the bounds
+    /// set by its ancestors, and that its heights, summaries and balance are right.  Returns the number of nodes.
+    /// The path (e.g. "root.left.right") is only used to describe where a problem was found.
+    fn check_invariants(&self, path: &mut String, lower: Option<&T>, upper: Option<&T>) -> Result<u32,TreeError<T>> {
+        if lower.is_some_and(|lower| self.value <= *lower) || upper.is_some_and(|upper| self.value >= *upper) {
+            return Err(TreeError::InvariantViolation(format!("value at {path} is out of order")));
+        }
+        let mut nodes = 1;
+        let path_len = path.len();
+        if let Some(node) = &self.left {
+            path.push_str(".left");
+            nodes += node.check_invariants(path, lower, Some(&self.value))?;
+            path.truncate(path_len);
+        }
+        if let Some(node) = &self.right {
+            path.push_str(".right");
+            nodes += node.check_invariants(path, Some(&self.value), upper)?;
+            path.truncate(path_len);
+        }
+        if self.height != self.compute_height() {
+            return Err(TreeError::InvariantViolation(format!("node at {path} has height {} but should be {}", self.height, self.compute_height())));
+        }
+        // a summary that isn't equal even to itself (such as a sum that's NaN) can't be checked
+        let summary = self.compute_summary();
+        #[allow(clippy::eq_op)]
+        if summary == summary && self.summary != summary {
+            return Err(TreeError::InvariantViolation(format!("node at {path} has a summary that doesn't match its subtree")));
+        }
+        let balancing_factor = self.compute_balancing_factor();
+        if !(-1..=1).contains(&balancing_factor) {
+            return Err(TreeError::InvariantViolation(format!("node at {path} is unbalanced, with a balancing factor of {balancing_factor}")));
+        }
+        Ok(nodes)
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Concatenates the values, to check that summaries are combined in order
+    struct Concat;
+
+    impl Aggregate<char> for Concat {
+        type Summary = String;
+        fn empty() -> String {
+            String::new()
+        }
+        fn single(value: &char) -> String {
+            value.to_string()
+        }
+        fn combine(left: &String, right: &String) -> String {
+            format!("{left}{right}")
+        }
+    }
+
+    #[test]
+    fn ranges_match_a_brute_force_sum() {
+        // a pseudo-random mix of adds and drops, checking every range against the values in a Vec
+        let mut tree: AggregateJavlt<i64, Sum> = AggregateJavlt::new();
+        let mut state: u64 = 7;
+        for _ in 0..300 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let value = (state % 60) as i64;
+            if state & 64 == 0 {
+                let _ = tree.add(value);
+            } else {
+                let _ = tree.drop_value(value);
+            }
+            assert_eq!( Ok(()), tree.check_invariants() );
+        }
+        let values = tree.as_vec();
+        for lo in -1..62 {
+            for hi in lo..62 {
+                let expected: i64 = values.iter().filter(|&&value| lo <= value && value < hi).sum();
+                assert_eq!( expected, tree.aggregate_range(lo..hi) );
+            }
+        }
+        assert_eq!( values.iter().sum::<i64>(), tree.aggregate() );
+        assert_eq!( values.iter().filter(|&&value| value > 30).sum::<i64>(), tree.aggregate_range((Bound::Excluded(30), Bound::Unbounded)) );
+    }
+
+    #[test]
+    fn summaries_combine_in_order() {
+        let mut tree: AggregateJavlt<char, Concat> = AggregateJavlt::from_collection("the quick brown fox".chars());
+        assert_eq!( " bcefhiknoqrtuwx", tree.aggregate() );
+        assert_eq!( "cefhik", tree.aggregate_range('c'..='k') );
+        tree.drop_value('h').unwrap();
+        assert_eq!( Err(TreeError::ValueNotFound { value: 'h' }), tree.drop_value('h') );
+        assert_eq!( Err(TreeError::ValueAlreadyStored { value: 'q' }), tree.add('q') );
+        assert_eq!( "cefik", tree.aggregate_range('c'..='k') );
+        let counted: AggregateJavlt<char, Count> = AggregateJavlt::from_collection('a'..='z');
+        assert_eq!( 10, counted.aggregate_range('e'..'o') );
+    }
+
+    #[test]
+    fn nth_matches_the_ordered_values() {
+        let mut tree: AggregateJavlt<u32, Count> = AggregateJavlt::from_collection((0..200).map(|n| (n * 67) % 211));
+        for n in (0..200).step_by(3) {
+            tree.drop_value((n * 67) % 211).unwrap();
+        }
+        let values = tree.as_vec();
+        for (index, value) in values.iter().enumerate() {
+            assert_eq!( Some(value), tree.nth(index) );
+        }
+        assert_eq!( None, tree.nth(values.len()) );
+        assert_eq!( None, AggregateJavlt::<u32, Count>::new().nth(0) );
+    }
+
+    #[test]
+    fn stale_summaries_are_found() {
+        let mut tree: AggregateJavlt<u32, Sum> = AggregateJavlt::from_collection(1..=7);
+        assert_eq!( Ok(()), tree.check_invariants() );
+        tree.root.as_mut().unwrap().left.as_mut().unwrap().summary += 1;
+        let expected = TreeError::InvariantViolation(String::from("node at root.left has a summary that doesn't match its subtree"));
+        assert_eq!( Err(expected), tree.check_invariants() );
+        // a sum of infinities that cancel out is NaN, which can't be checked but isn't reported
+        let infinite: AggregateJavlt<f64, Sum> = AggregateJavlt::from_collection([f64::NEG_INFINITY, 0.0, f64::INFINITY]);
+        assert!( infinite.aggregate().is_nan() );
+        assert_eq!( Ok(()), infinite.check_invariants() );
+    }
+
+    /// The second of a pair, which the tree doesn't order by
+    struct Second;
+
+    impl Field<(u32, i32)> for Second {
+        type Output = i32;
+        fn of(value: &(u32, i32)) -> i32 {
+            value.1
+        }
+    }
+
+    #[test]
+    fn min_and_max_of_a_field_match_a_brute_force_scan() {
+        let pairs: Vec<(u32, i32)> = (0..80).map(|n| (n, ((n * 37) % 101) as i32 - 50)).collect();
+        let mut mins: AggregateJavlt<(u32, i32), Min<Second>> = AggregateJavlt::from_collection(pairs.clone());
+        let mut maxes: AggregateJavlt<(u32, i32), Max<Second>> = AggregateJavlt::from_collection(pairs.clone());
+        for n in (0..80).step_by(7) {
+            mins.drop_value(pairs[n]).unwrap();
+            maxes.drop_value(pairs[n]).unwrap();
+        }
+        let values = mins.as_vec();
+        for lo in (0..82).step_by(3) {
+            for hi in lo..82 {
+                let in_range = values.iter().filter(|pair| lo <= pair.0 && pair.0 < hi).map(|pair| pair.1);
+                assert_eq!( in_range.clone().min(), mins.aggregate_range((lo, i32::MIN)..(hi, i32::MIN)) );
+                assert_eq!( in_range.max(), maxes.aggregate_range((lo, i32::MIN)..(hi, i32::MIN)) );
+            }
+        }
+        let whole: AggregateJavlt<i32, Max> = AggregateJavlt::from_collection([4, 9, 2]);
+        assert_eq!( Some(9), whole.aggregate() );
+        assert_eq!( None, whole.aggregate_range(10..) );
+    }
+
+}
@@ -0,0 +1,223 @@
+use std::{fmt, hash::{DefaultHasher, Hash, Hasher}};
+
+use crate::errors::TreeError;
+use crate::javlt::Javlt;
+
+
+
+/// # Filtered AVL Tree
+///
+/// A `Javlt` with a Bloom filter in front of it, for workloads where most lookups are misses.  The filter
+/// can say that a value is "definitely not present" after hashing it, without descending the tree; only
+/// lookups that pass the filter (the hits, plus about 1% of the misses) go on to search the tree.
+///
+///     use jtree::bloom::FilteredJavlt;
+///
+///     let mut tree = FilteredJavlt::from_collection(0..1000);
+///     assert!( tree.contains(&500) );
+///     assert!( !tree.contains(&5000) );
+///     tree.drop_value(500).unwrap();
+///     assert!( !tree.contains(&500) );
+///
+/// The filter is kept up to date as values are added.  A Bloom filter can't forget a value, so deleted
+/// values stay in it (costing only a wasted search when they're looked up) until enough have been deleted
+/// that the filter is rebuilt from the tree's values.  It's also rebuilt, twice as big, when the tree
+/// outgrows it.
+pub struct FilteredJavlt<T: PartialEq + PartialOrd + Clone + Hash> {
+    tree: Javlt<T>,
+    filter: BloomFilter,
+    deletions: u32, // values deleted since the filter was built, which it still claims to hold
+}
+
+impl <T: PartialEq + PartialOrd + Clone + Hash> FilteredJavlt<T> {
+
+    /// Create a new tree with no data
+    pub fn new() -> Self {
+        Self::from(Javlt::new())
+    }
+
+    /// Create a new tree from a collection (vector, array, or whatever), skipping duplicates.
+    pub fn from_collection<U: IntoIterator<Item = T>>(collection: U) -> Self {
+        Self::from(Javlt::from_collection(collection))
+    }
+
+    /// Insert a value
+    pub fn add(&mut self, value: T) -> Result<(),TreeError<T>> {
+        let hash = hash_of(&value);
+        self.tree.add(value)?;
+        if self.tree.get_size() > self.filter.capacity {
+            self.rebuild();
+        } else {
+            self.filter.insert(hash);
+        }
+        Ok(())
+    }
+
+    /// If the value is in the tree, delete it.  Otherwise a TreeError::ValueNotFound will be returned.
+    pub fn drop_value(&mut self, value: T) -> Result<(),TreeError<T>> {
+        self.tree.drop_value(value)?;
+        self.deletions += 1;
+        if self.deletions > self.filter.capacity / 4 {
+            self.rebuild();
+        }
+        Ok(())
+    }
+
+    /// Returns true if the value is in the tree.  Most values that aren't are turned away by the filter
+    /// without searching the tree.
+    pub fn contains(&self, value: &T) -> bool {
+        if !self.filter.might_contain(hash_of(value)) {
+            return false;
+        }
+        self.tree.contains(value)
+    }
+
+    /// Returns false if the filter says the value is definitely not in the tree.  A true answer means
+    /// only that the value may be there.
+    pub fn might_contain(&self, value: &T) -> bool {
+        self.filter.might_contain(hash_of(value))
+    }
+
+    /// Get the number of values in the tree
+    pub fn get_size(&self) -> u32 {
+        self.tree.get_size()
+    }
+
+    /// Returns all the values in the tree as an ordered Vec from least to greatest.
+    pub fn as_vec(&self) -> Vec<T> {
+        self.tree.as_vec()
+    }
+
+    /// The tree behind the filter, for reading.
+    pub fn tree(&self) -> &Javlt<T> {
+        &self.tree
+    }
+
+    /// Take the tree back out of the filter.
+    pub fn into_tree(self) -> Javlt<T> {
+        self.tree
+    }
+
+    /// Build a new filter from the tree's values, sized for twice as many as it holds now.
+    fn rebuild(&mut self) {
+        self.filter = BloomFilter::new(self.tree.get_size().saturating_mul(2));
+        let filter = &mut self.filter;
+        self.tree.visit_in_order(|value| filter.insert(hash_of(value)));
+        self.deletions = 0;
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd + Clone + Hash> From<Javlt<T>> for FilteredJavlt<T> {
+    fn from(tree: Javlt<T>) -> Self {
+        let mut filtered = Self {
+            tree,
+            filter: BloomFilter::new(0),
+            deletions: 0,
+        };
+        filtered.rebuild();
+        filtered
+    }
+}
+
+impl <T: PartialEq + PartialOrd + Clone + Hash> Default for FilteredJavlt<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <T: PartialEq + PartialOrd + Clone + Hash + fmt::Debug> fmt::Debug for FilteredJavlt<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("FilteredJavlt")
+            .field("size", &self.tree.get_size())
+            .field("filter_capacity", &self.filter.capacity)
+            .field("values", &self.tree.as_vec())
+            .finish()
+    }
+}
+
+/// A Bloom filter with about 10 bits per value and 7 hash functions, for a false positive rate near 1%
+/// while it holds no more than its capacity.  The hash functions are made from the two halves of one
+/// 64-bit hash (as suggested by Kirsch and Mitzenmacher).
+struct BloomFilter {
+    bits: Vec<u64>,
+    capacity: u32,
+}
+
+const BITS_PER_VALUE: usize = 10;
+const HASHES: u64 = 7;
+
+impl BloomFilter {
+
+    fn new(capacity: u32) -> Self {
+        let capacity = capacity.max(64);
+        Self {
+            bits: vec![0; (capacity as usize * BITS_PER_VALUE).div_ceil(64)],
+            capacity,
+        }
+    }
+
+    fn insert(&mut self, hash: u64) {
+        for bit in self.bit_indices(hash) {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    fn might_contain(&self, hash: u64) -> bool {
+        self.bit_indices(hash).all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    fn bit_indices(&self, hash: u64) -> impl Iterator<Item = usize> + use<> {
+        let len = self.bits.len() as u64 * 64;
+        let first = hash & 0xffff_ffff;
+        let step = (hash >> 32) | 1;
+        (0..HASHES).map(move |i| (first.wrapping_add(i.wrapping_mul(step)) % len) as usize)
+    }
+
+}
+
+fn hash_of<T: Hash>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_turns_away_most_misses() {
+        let mut tree = FilteredJavlt::new();
+        for n in 0..2000 {
+            tree.add(n * 2).unwrap();
+        }
+        assert_eq!( Err(TreeError::ValueAlreadyStored { value: 4 }), tree.add(4) );
+        for n in 0..2000 {
+            assert!( tree.contains(&(n * 2)) );
+        }
+        let passed = (0..2000).filter(|n| tree.might_contain(&(n * 2 + 1))).count();
+        assert!( passed < 60, "{passed} of 2000 misses passed the filter" );
+        assert!( (0..2000).all(|n| !tree.contains(&(n * 2 + 1))) );
+    }
+
+    #[test]
+    fn deletions_rebuild_the_filter() {
+        let mut tree = FilteredJavlt::from_collection(0..1000);
+        let capacity = tree.filter.capacity;
+        for n in 0..600 {
+            tree.drop_value(n).unwrap();
+            assert!( !tree.contains(&n) );
+        }
+        assert_eq!( Err(TreeError::ValueNotFound { value: 0 }), tree.drop_value(0) );
+        // the filter was rebuilt without the deleted values, at least once
+        assert!( tree.filter.capacity < capacity );
+        // so only the values deleted since then (and a few false positives) still pass it
+        let passed = (0..600).filter(|n| tree.might_contain(n)).count();
+        assert!( passed < 150, "{passed} of 600 deleted values passed the filter" );
+        assert!( (600..1000).all(|n| tree.contains(&n)) );
+        assert_eq!( (600..1000).collect::<Vec<_>>(), tree.into_tree().as_vec() );
+    }
+
+}
@@ -0,0 +1,189 @@
+use std::fmt;
+
+use crate::errors::TreeError;
+use crate::javlt::Javlt;
+
+
+
+/// Which end of the ordering a `BoundedJavlt` keeps.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Keep {
+    /// Keep the greatest values, evicting the least (e.g. a high-score leaderboard).
+    Greatest,
+    /// Keep the least values, evicting the greatest (e.g. the fastest times).
+    Least,
+}
+
+/// # Bounded AVL Tree
+///
+/// A `Javlt` holding at most `max_size` values: once it's full, adding a value evicts one from the far
+/// end, so that only the greatest (or least) values are kept.  Each add is still O(log(n)).
+///
+///     use jtree::BoundedJavlt;
+///
+///     let mut top_three = BoundedJavlt::keep_greatest(3);
+///     for score in [40, 75, 10, 90] {
+///         let _ = top_three.add(score);
+///     }
+///     assert_eq!( vec!(40, 75, 90), top_three.as_vec() );
+///     // the evicted value is handed back, whether it was already in the tree or the one being added
+///     assert_eq!( Ok(Some(40)), top_three.add(80) );
+///     assert_eq!( Ok(Some(5)), top_three.add(5) );
+///     assert_eq!( vec!(75, 80, 90), top_three.as_vec() );
+pub struct BoundedJavlt<T: PartialEq + PartialOrd + Clone> {
+    tree: Javlt<T>,
+    max_size: u32,
+    keep: Keep,
+}
+
+impl <T: PartialEq + PartialOrd + Clone> BoundedJavlt<T> {
+
+    /// Create a new, empty tree that will keep the given end of the ordering, up to `max_size` values.
+    pub fn new(max_size: u32, keep: Keep) -> Self {
+        Self {
+            tree: Javlt::new(),
+            max_size,
+            keep,
+        }
+    }
+
+    /// Create a new, empty tree that will keep the `max_size` greatest values added to it.
+    pub fn keep_greatest(max_size: u32) -> Self {
+        Self::new(max_size, Keep::Greatest)
+    }
+
+    /// Create a new, empty tree that will keep the `max_size` least values added to it.
+    pub fn keep_least(max_size: u32) -> Self {
+        Self::new(max_size, Keep::Least)
+    }
+
+    /// Insert a value.  If the tree was already full, one value is evicted and returned: the value at the far
+    /// end, or the new value itself if it would have been the one at the far end.  A value that's already in
+    /// the tree is refused with a TreeError::ValueAlreadyStored.
+    pub fn add(&mut self, value: T) -> Result<Option<T>,TreeError<T>> {
+        if self.tree.contains(&value) {
+            return Err(TreeError::ValueAlreadyStored { value });
+        }
+        if self.tree.get_size() < self.max_size {
+            self.tree.add(value)?;
+            return Ok(None);
+        }
+        let far_end = match self.keep {
+            Keep::Greatest => self.tree.least_value(),
+            Keep::Least => self.tree.greatest_value(),
+        };
+        let far_end = match far_end {
+            // a tree with no room at all keeps nothing
+            None => return Ok(Some(value)),
+            Some(far_end) => far_end,
+        };
+        let beyond_far_end = match self.keep {
+            Keep::Greatest => value < far_end,
+            Keep::Least => value > far_end,
+        };
+        if beyond_far_end {
+            return Ok(Some(value));
+        }
+        self.tree.drop_value(far_end.clone())?;
+        self.tree.add(value)?;
+        Ok(Some(far_end))
+    }
+
+    /// If the value is in the tree, delete it.  Otherwise a TreeError::ValueNotFound will be returned.
+    pub fn drop_value(&mut self, value: T) -> Result<(),TreeError<T>> {
+        self.tree.drop_value(value)
+    }
+
+    /// Get the number of values in the tree
+    pub fn get_size(&self) -> u32 {
+        self.tree.get_size()
+    }
+
+    /// The most values the tree will hold
+    pub fn max_size(&self) -> u32 {
+        self.max_size
+    }
+
+    /// Which end of the ordering the tree keeps
+    pub fn keep(&self) -> Keep {
+        self.keep
+    }
+
+    /// Returns true if the value is currently a member of the tree
+    pub fn contains(&self, value: &T) -> bool {
+        self.tree.contains(value)
+    }
+
+    /// Returns all the values in the tree as an ordered Vec from least to greatest.
+    pub fn as_vec(&self) -> Vec<T> {
+        self.tree.as_vec()
+    }
+
+    /// Returns the values kept, best first: greatest first if keeping the greatest, least first if keeping the least.
+    pub fn ranked(&self) -> Vec<T> {
+        return match self.keep {
+            Keep::Greatest => self.tree.as_vec_r_to_l(),
+            Keep::Least => self.tree.as_vec_l_to_r(),
+        };
+    }
+
+    /// Borrow the underlying tree, to use any of `Javlt`'s other read-only methods.
+    pub fn as_tree(&self) -> &Javlt<T> {
+        &self.tree
+    }
+
+    /// Unwrap the underlying tree.
+    pub fn into_inner(self) -> Javlt<T> {
+        self.tree
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd + Clone + std::fmt::Debug> fmt::Debug for BoundedJavlt<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("BoundedJavlt")
+            .field("max_size", &self.max_size)
+            .field("keep", &self.keep)
+            .field("values", &self.as_vec())
+            .finish()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keep_the_least() {
+        let mut fastest = BoundedJavlt::keep_least(2);
+        assert_eq!( Ok(None), fastest.add(9.5) );
+        assert_eq!( Ok(None), fastest.add(9.9) );
+        assert_eq!( Ok(Some(9.9)), fastest.add(9.7) );
+        assert_eq!( Ok(Some(10.1)), fastest.add(10.1) );
+        assert_eq!( Err(TreeError::ValueAlreadyStored { value: 9.5 }), fastest.add(9.5) );
+        assert_eq!( vec!(9.5, 9.7), fastest.ranked() );
+        assert_eq!( 2, fastest.get_size() );
+        assert_eq!( Ok(()), fastest.drop_value(9.5) );
+        assert_eq!( Ok(None), fastest.add(9.8) );
+        assert_eq!( vec!(9.7, 9.8), fastest.as_vec() );
+    }
+
+    #[test]
+    fn keep_the_greatest_of_many() {
+        let mut top = BoundedJavlt::keep_greatest(10);
+        for n in 0..1000 {
+            let _ = top.add((n * 37) % 1000);
+        }
+        assert_eq!( (990..1000).rev().collect::<Vec<_>>(), top.ranked() );
+        assert_eq!( Ok(()), top.as_tree().check_invariants() );
+    }
+
+    #[test]
+    fn no_room_at_all() {
+        let mut none = BoundedJavlt::keep_greatest(0);
+        assert_eq!( Ok(Some(1)), none.add(1) );
+        assert_eq!( 0, none.get_size() );
+    }
+
+}
@@ -0,0 +1,156 @@
+use std::{collections::VecDeque, ops::{Bound, RangeBounds}};
+
+/// Returns true if the value is below the range's lower bound
+pub(crate) fn is_below<T: PartialOrd>(value: &T, lower: Bound<&T>) -> bool {
+    return match lower {
+        Bound::Included(lower) => value < lower,
+        Bound::Excluded(lower) => value <= lower,
+        Bound::Unbounded => false,
+    };
+}
+
+/// Returns true if the value is above the range's upper bound
+pub(crate) fn is_above<T: PartialOrd>(value: &T, upper: Bound<&T>) -> bool {
+    return match upper {
+        Bound::Included(upper) => value > upper,
+        Bound::Excluded(upper) => value >= upper,
+        Bound::Unbounded => false,
+    };
+}
+
+/// A node of one of the binary search trees, as far as `queue_within` needs to see it.
+pub(crate) trait SearchNode {
+    type Value: PartialOrd;
+    fn value(&self) -> &Self::Value;
+    fn left(&self) -> Option<&Self>;
+    fn right(&self) -> Option<&Self>;
+}
+
+/// Queue just the nodes whose values are within the range, for a tree's `range`.  Going down from the root to
+/// the first node within it, then down each side of that node to the range's bounds, it queues each node found
+/// within the range along with its subtree on the inner side, which is all within the range too.  Each node is
+/// queued with its depth and whether its left and right subtrees come with it, as the trees' `Nodes` iterators
+/// expect.
+pub(crate) fn queue_within<'a, N: SearchNode, R: RangeBounds<N::Value>>(root: Option<&'a N>, range: &R) -> VecDeque<(&'a N, usize, bool, bool)> {
+    let mut queue = VecDeque::new();
+    let (mut link, mut depth) = (root, 0);
+    while let Some(node) = link {
+        if is_below(node.value(), range.start_bound()) {
+            (link, depth) = (node.right(), depth + 1);
+        } else if is_above(node.value(), range.end_bound()) {
+            (link, depth) = (node.left(), depth + 1);
+        } else {
+            queue.push_back((node, depth, false, false));
+            let (mut left, mut left_depth) = (node.left(), depth + 1);
+            while let Some(node) = left {
+                if is_below(node.value(), range.start_bound()) {
+                    left = node.right();
+                } else {
+                    queue.push_front((node, left_depth, false, true));
+                    left = node.left();
+                }
+                left_depth += 1;
+            }
+            let (mut right, mut right_depth) = (node.right(), depth + 1);
+            while let Some(node) = right {
+                if is_above(node.value(), range.end_bound()) {
+                    right = node.left();
+                } else {
+                    queue.push_back((node, right_depth, true, false));
+                    right = node.right();
+                }
+                right_depth += 1;
+            }
+            break;
+        }
+    }
+    queue
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A plain binary search tree node, to search with `queue_within`
+    struct TestNode {
+        value: i32,
+        left: Option<Box<TestNode>>,
+        right: Option<Box<TestNode>>,
+    }
+
+    impl SearchNode for TestNode {
+        type Value = i32;
+        fn value(&self) -> &i32 { &self.value }
+        fn left(&self) -> Option<&Self> { self.left.as_deref() }
+        fn right(&self) -> Option<&Self> { self.right.as_deref() }
+    }
+
+    /// A balanced tree of sorted values
+    fn build(values: &[i32]) -> Option<Box<TestNode>> {
+        if values.is_empty() {
+            return None;
+        }
+        let middle = values.len() / 2;
+        Some(Box::new(TestNode { value: values[middle], left: build(&values[..middle]), right: build(&values[middle + 1..]) }))
+    }
+
+    fn collect(node: Option<&TestNode>, values: &mut Vec<i32>) {
+        if let Some(node) = node {
+            collect(node.left(), values);
+            values.push(node.value);
+            collect(node.right(), values);
+        }
+    }
+
+    fn depth_of(root: Option<&TestNode>, value: i32) -> usize {
+        let (mut link, mut depth) = (root, 0);
+        while let Some(node) = link {
+            if value == node.value {
+                return depth;
+            }
+            (link, depth) = (if value < node.value { node.left() } else { node.right() }, depth + 1);
+        }
+        panic!("{value} isn't in the tree");
+    }
+
+    #[test]
+    fn bounds_of_each_kind() {
+        assert!( is_below(&1, Bound::Included(&2)) && !is_below(&2, Bound::Included(&2)) );
+        assert!( is_below(&2, Bound::Excluded(&2)) && !is_below(&3, Bound::Excluded(&2)) );
+        assert!( !is_below(&i32::MIN, Bound::Unbounded) );
+        assert!( is_above(&3, Bound::Included(&2)) && !is_above(&2, Bound::Included(&2)) );
+        assert!( is_above(&2, Bound::Excluded(&2)) && !is_above(&1, Bound::Excluded(&2)) );
+        assert!( !is_above(&i32::MAX, Bound::Unbounded) );
+        // a value that can't be compared is neither below nor above an included bound
+        assert!( !is_below(&f64::NAN, Bound::Included(&0.0)) && !is_above(&f64::NAN, Bound::Included(&0.0)) );
+    }
+
+    #[test]
+    fn queued_nodes_cover_the_range_in_order() {
+        let values: Vec<i32> = (0..40).map(|n| n * 2).collect();
+        let root = build(&values);
+        let root = root.as_deref();
+        let bounds = [Bound::Unbounded, Bound::Included(-1), Bound::Included(10), Bound::Excluded(10), Bound::Included(33), Bound::Excluded(78), Bound::Included(90)];
+        for lower in bounds {
+            for upper in bounds {
+                let range = (lower, upper);
+                let mut found = Vec::new();
+                for (node, depth, with_left, with_right) in queue_within(root, &range) {
+                    assert_eq!( depth_of(root, node.value), depth );
+                    if with_left {
+                        collect(node.left(), &mut found);
+                    }
+                    found.push(node.value);
+                    if with_right {
+                        collect(node.right(), &mut found);
+                    }
+                }
+                let expected: Vec<i32> = values.iter().copied().filter(|value| range.contains(value)).collect();
+                assert_eq!( expected, found, "range {range:?}" );
+            }
+        }
+        assert!( queue_within::<TestNode, _>(None, &(..)).is_empty() );
+    }
+
+}
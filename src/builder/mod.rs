@@ -0,0 +1,183 @@
+//! # Tree builder
+//!
+//! `Javlt::builder()` gathers the options for making a tree in one place, rather than a constructor for
+//! each combination of them:
+//!
+//!     use jtree::Javlt;
+//!     use jtree::bounded::Keep;
+//!     use jtree::builder::DuplicatePolicy;
+//!     use jtree::errors::TreeError;
+//!
+//!     let tree = Javlt::builder().values([3,1,2,1]).build().unwrap();
+//!     assert_eq!( vec!(1,2,3), tree.as_vec() );
+//!
+//!     let strict = Javlt::builder().values([3,1,2,1]).duplicate_policy(DuplicatePolicy::Reject).build();
+//!     assert_eq!( Err(TreeError::DuplicateAt { index: 3, value: 1 }), strict.map(|tree| tree.get_size()) );
+//!
+//!     let top_two = Javlt::builder().values([5,9,2,7]).capacity(2, Keep::Greatest).build().unwrap();
+//!     assert_eq!( vec!(7,9), top_two.as_vec() );
+//!
+//! A `Javlt` is ordered by its values' `PartialOrd`, so there's no comparator to configure: for the reverse
+//! order, or ordering by a key, store the values in a wrapper (such as `std::cmp::Reverse`) that compares them
+//! that way.
+
+use crate::bounded::{BoundedJavlt, Keep};
+use crate::errors::TreeError;
+use crate::javlt::Javlt;
+
+/// What building a tree does with a value equal to one given before it.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum DuplicatePolicy {
+    /// Keep the first and skip the rest, as `from_collection` does.
+    #[default]
+    Skip,
+    /// Fail with a TreeError::DuplicateAt, holding the index and value of the first duplicate.
+    Reject,
+}
+
+/// Options for building a `Javlt`; get one with `Javlt::builder()`.
+pub struct JavltBuilder<T: PartialEq + PartialOrd> {
+    values: Vec<T>,
+    duplicates: DuplicatePolicy,
+}
+
+impl <T: PartialEq + PartialOrd> Javlt<T> {
+
+    /// Start building a tree, with the options of a `JavltBuilder`.
+    pub fn builder() -> JavltBuilder<T> {
+        JavltBuilder {
+            values: Vec::new(),
+            duplicates: DuplicatePolicy::default(),
+        }
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd> JavltBuilder<T> {
+
+    /// Values for the tree to start with, after any given already.
+    pub fn values<U: IntoIterator<Item = T>>(mut self, collection: U) -> Self {
+        self.values.extend(collection);
+        self
+    }
+
+    /// What to do with duplicate values (by default, skip them).
+    pub fn duplicate_policy(mut self, duplicates: DuplicatePolicy) -> Self {
+        self.duplicates = duplicates;
+        self
+    }
+
+    /// Build a `BoundedJavlt`, which holds at most `max_size` values, keeping the given end of the ordering.
+    pub fn capacity(self, max_size: u32, keep: Keep) -> BoundedJavltBuilder<T> where T: Clone {
+        BoundedJavltBuilder {
+            options: self,
+            max_size,
+            keep,
+        }
+    }
+
+    /// Build the tree, adding the values in the order they were given.
+    pub fn build(self) -> Result<Javlt<T>,TreeError<T>> {
+        let mut tree = Javlt::new();
+        match self.duplicates {
+            DuplicatePolicy::Skip => {
+                let _ = tree.add_all_skipping_duplicates(self.values);
+            },
+            DuplicatePolicy::Reject => tree.add_all_strict(self.values)?,
+        }
+        Ok(tree)
+    }
+
+}
+
+/// Options for building a `BoundedJavlt`; get one with `Javlt::builder().capacity(..)`.
+pub struct BoundedJavltBuilder<T: PartialEq + PartialOrd + Clone> {
+    options: JavltBuilder<T>,
+    max_size: u32,
+    keep: Keep,
+}
+
+impl <T: PartialEq + PartialOrd + Clone> BoundedJavltBuilder<T> {
+
+    /// Values for the tree to start with, after any given already.
+    pub fn values<U: IntoIterator<Item = T>>(mut self, collection: U) -> Self {
+        self.options = self.options.values(collection);
+        self
+    }
+
+    /// What to do with duplicate values (by default, skip them).
+    pub fn duplicate_policy(mut self, duplicates: DuplicatePolicy) -> Self {
+        self.options = self.options.duplicate_policy(duplicates);
+        self
+    }
+
+    /// Build the tree, adding the values in the order they were given; values beyond the capacity are
+    /// evicted as they would be by `BoundedJavlt::add`.  With `DuplicatePolicy::Reject`, a duplicate is found
+    /// even if the value it repeats was evicted before it came.
+    pub fn build(self) -> Result<BoundedJavlt<T>,TreeError<T>> {
+        if self.options.duplicates == DuplicatePolicy::Reject {
+            // the tree only holds what it hasn't evicted, so look for duplicates among all the values given
+            let mut seen = Javlt::new();
+            for (index, value) in self.options.values.iter().enumerate() {
+                if let Err(TreeError::ValueAlreadyStored { .. }) = seen.add(value) {
+                    return Err(TreeError::DuplicateAt { index, value: value.clone() });
+                }
+            }
+        }
+        let mut tree = BoundedJavlt::new(self.max_size, self.keep);
+        for (index, value) in self.options.values.into_iter().enumerate() {
+            match (tree.add(value), self.options.duplicates) {
+                (Err(TreeError::ValueAlreadyStored { value }), DuplicatePolicy::Reject) => return Err(TreeError::DuplicateAt { index, value }),
+                (Err(TreeError::ValueAlreadyStored { .. }), DuplicatePolicy::Skip) => {},
+                (Err(other), _) => return Err(other),
+                (Ok(_), _) => {},
+            }
+        }
+        Ok(tree)
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options_combine() {
+        let tree = Javlt::<u32>::builder().build().unwrap();
+        assert_eq!( 0, tree.get_size() );
+        let tree = Javlt::builder().values(0..50).values((0..100).rev()).build().unwrap();
+        assert_eq!( 100, tree.get_size() );
+        assert!( tree.check_invariants().is_ok() );
+        let rejected = Javlt::builder().values(0..50).values([7]).duplicate_policy(DuplicatePolicy::Reject).build();
+        assert_eq!( Err(TreeError::DuplicateAt { index: 50, value: 7 }), rejected.map(|tree| tree.get_size()) );
+
+        let least = Javlt::builder().values((0..100).rev()).capacity(3, Keep::Least).build().unwrap();
+        assert_eq!( vec!(0,1,2), least.as_vec() );
+        let skipped = Javlt::builder().capacity(5, Keep::Greatest).values([1,2,2,3]).build().unwrap();
+        assert_eq!( vec!(1,2,3), skipped.as_vec() );
+        let rejected = Javlt::builder().capacity(5, Keep::Greatest).duplicate_policy(DuplicatePolicy::Reject).values([1,2,2,3]).build();
+        assert_eq!( Err(TreeError::DuplicateAt { index: 2, value: 2 }), rejected.map(|tree| tree.get_size()) );
+        // 1 is evicted by the time it comes round again, but it's still a duplicate
+        let rejected = Javlt::builder().capacity(2, Keep::Greatest).duplicate_policy(DuplicatePolicy::Reject).values([1,5,9,1]).build();
+        assert_eq!( Err(TreeError::DuplicateAt { index: 3, value: 1 }), rejected.map(|tree| tree.get_size()) );
+        let skipped = Javlt::builder().capacity(2, Keep::Greatest).values([1,5,9,1]).build().unwrap();
+        assert_eq!( vec!(5,9), skipped.as_vec() );
+    }
+
+    #[test]
+    fn skipping_keeps_the_first_of_equal_values() {
+        use crate::folded::Folded;
+
+        assert_eq!( DuplicatePolicy::Skip, DuplicatePolicy::default() );
+        let words = Javlt::builder().values(["Apple", "banana", "APPLE", "Banana"].map(Folded::new)).build().unwrap();
+        assert_eq!( vec!("Apple", "banana"), words.iter().map(|word| *word.value()).collect::<Vec<_>>() );
+        // the last policy given is the one that counts
+        let rejected = Javlt::builder().duplicate_policy(DuplicatePolicy::Skip).values([4,4]).duplicate_policy(DuplicatePolicy::Reject).build();
+        assert_eq!( Err(TreeError::DuplicateAt { index: 1, value: 4 }), rejected.map(|tree| tree.get_size()) );
+        let skipped = Javlt::builder().duplicate_policy(DuplicatePolicy::Reject).values([4,4]).duplicate_policy(DuplicatePolicy::Skip).build().unwrap();
+        assert_eq!( vec!(4), skipped.as_vec() );
+    }
+
+}
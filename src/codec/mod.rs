@@ -0,0 +1,367 @@
+use crate::errors::TreeError;
+
+/// # Binary format
+///
+/// The trees can be saved as bytes (with `to_bytes` or `save`) and loaded again (with `from_bytes` or `load`)
+/// if their values implement `Codec`.  Every tree's bytes start with a header:
+///
+/// - the magic bytes `JTREE`
+/// - a format version byte (currently 1)
+/// - a byte naming the kind of tree: `S` for `Jbst`, `L` for `Jblst`, or `A` for `Javlt`
+///
+/// followed by the tree's size and a record for each node, in pre-order (parent, left subtree, right subtree).
+/// Each record starts with a flags byte (see `HAS_LEFT` and friends) followed by the node's value, so the tree
+/// is rebuilt with exactly the same shape.  Numbers are little-endian.
+///
+/// A `Javlt` can also be saved in a compact format (with `to_compact_bytes`), which leaves out the flags bytes.
+/// Its kind byte is `a`, and the size is followed by the tree's shape, packed into two bits per node (the
+/// `HAS_LEFT` and `HAS_RIGHT` flags, in pre-order, starting from each byte's lowest bits), and then by all of the
+/// values in order from least to greatest.  For small values, that's a much smaller encoding.
+///
+/// A `DurableJavlt`'s write-ahead log starts with the same header, with the kind byte `W`, followed by a record
+/// for each change (see the `durable` module).
+///
+/// `Codec` is implemented for the primitive number types, `bool`, `char` and `String`.  To store your own type,
+/// implement it using the implementations of its parts, e.g.:
+///
+///     use jtree::Jbst;
+///     use jtree::codec::{Codec, Reader};
+///
+///     #[derive(PartialEq, PartialOrd, Clone, Debug)]
+///     struct Point { x: i32, y: i32 }
+///
+///     impl Codec for Point {
+///         fn encode(&self, bytes: &mut Vec<u8>) {
+///             self.x.encode(bytes);
+///             self.y.encode(bytes);
+///         }
+///         fn decode(reader: &mut Reader) -> Result<Self, String> {
+///             Ok(Point { x: i32::decode(reader)?, y: i32::decode(reader)? })
+///         }
+///     }
+///
+///     let tree = Jbst::from_collection([Point { x: 1, y: 2 }, Point { x: 0, y: 5 }]);
+///     let copy = Jbst::<Point>::from_bytes(&tree.to_bytes()).unwrap();
+///     assert_eq!( tree.as_vec(), copy.as_vec() );
+pub trait Codec: Sized {
+    /// Append this value's encoding to the bytes.
+    fn encode(&self, bytes: &mut Vec<u8>);
+    /// Read a value from the reader, or describe why the bytes there aren't a valid encoding.
+    fn decode(reader: &mut Reader) -> Result<Self, String>;
+}
+
+pub(crate) const MAGIC: &[u8] = b"JTREE";
+pub(crate) const VERSION: u8 = 1;
+
+/// Node record flag: the node has a left child, whose record follows this node's.
+pub(crate) const HAS_LEFT: u8 = 1;
+/// Node record flag: the node has a right child, whose record follows the left subtree's records.
+pub(crate) const HAS_RIGHT: u8 = 2;
+/// Node record flag: the node is a `Jbst` tombstone, left by lazy deletion.
+pub(crate) const DELETED: u8 = 4;
+
+/// Packs the node flags (`HAS_LEFT` and `HAS_RIGHT`) of the compact format's shape section, two bits per node.
+pub(crate) struct ShapeWriter {
+    bytes: Vec<u8>,
+    nodes: usize,
+}
+
+impl ShapeWriter {
+
+    pub(crate) fn new() -> Self {
+        Self { bytes: Vec::new(), nodes: 0 }
+    }
+
+    /// Add the next node's flags.
+    pub(crate) fn push(&mut self, flags: u8) {
+        if self.nodes.is_multiple_of(4) {
+            self.bytes.push(0);
+        }
+        *self.bytes.last_mut().unwrap() |= flags << (2 * (self.nodes % 4));
+        self.nodes += 1;
+    }
+
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+}
+
+/// Reads back the node flags packed by a `ShapeWriter`, for a tree of a known size.
+pub(crate) struct ShapeReader<'a> {
+    bytes: &'a [u8],
+    offset: usize, // where the shape section starts in the tree's bytes
+    nodes: usize, // how many nodes' flags have been read
+    size: usize,
+}
+
+impl <'a> ShapeReader<'a> {
+
+    /// Take the shape section for a tree of `size` nodes from the reader.
+    pub(crate) fn new<T>(reader: &mut Reader<'a>, size: usize) -> Result<Self, TreeError<T>> {
+        let offset = reader.offset();
+        return match reader.take(size.div_ceil(4)) {
+            Ok(bytes) => Ok(Self { bytes, offset, nodes: 0, size }),
+            Err(reason) => Err(TreeError::DeserializationCorrupt { offset, reason }),
+        };
+    }
+
+    /// Read the next node's flags.
+    pub(crate) fn next<T>(&mut self) -> Result<u8, TreeError<T>> {
+        if self.nodes == self.size {
+            return Err(self.corrupt(format!("the shape has more than the tree's {} nodes", self.size)));
+        }
+        let flags = (self.bytes[self.nodes / 4] >> (2 * (self.nodes % 4))) & (HAS_LEFT | HAS_RIGHT);
+        self.nodes += 1;
+        Ok(flags)
+    }
+
+    /// Check that the shape held exactly the tree's nodes.
+    pub(crate) fn finish<T>(&self) -> Result<(), TreeError<T>> {
+        if self.nodes != self.size {
+            return Err(self.corrupt(format!("the shape has {} nodes but the tree has {}", self.nodes, self.size)));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn corrupt<T>(&self, reason: String) -> TreeError<T> {
+        TreeError::DeserializationCorrupt { offset: self.offset + self.nodes / 4, reason }
+    }
+
+}
+
+/// Start a tree's bytes with the header for the given kind of tree.
+pub(crate) fn write_header(bytes: &mut Vec<u8>, kind: u8) {
+    bytes.extend_from_slice(MAGIC);
+    bytes.push(VERSION);
+    bytes.push(kind);
+}
+
+/// Reads values from a tree's bytes, keeping track of how far it has got so that errors can say where
+/// the data went wrong.
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl <'a> Reader<'a> {
+
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, offset: 0 }
+    }
+
+    /// Position of the next byte to be read.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Read the next `count` bytes.
+    pub fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        if count > self.bytes.len() - self.offset {
+            return Err(format!("expected {count} more bytes but only {} remain", self.bytes.len() - self.offset));
+        }
+        let taken = &self.bytes[self.offset..self.offset + count];
+        self.offset += count;
+        Ok(taken)
+    }
+
+    /// Read the next `N` bytes as an array.
+    pub fn take_array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    /// Decode a value (or a tree's size or flags), turning any problem into a TreeError::DeserializationCorrupt
+    /// that points at where the value started.
+    pub(crate) fn read<V: Codec, T>(&mut self) -> Result<V, TreeError<T>> {
+        let offset = self.offset;
+        return match V::decode(self) {
+            Ok(value) => Ok(value),
+            Err(reason) => Err(TreeError::DeserializationCorrupt { offset, reason }),
+        };
+    }
+
+    /// Check the header written by `write_header`.
+    pub(crate) fn read_header<T>(&mut self, kind: u8, tree_name: &str) -> Result<(), TreeError<T>> {
+        let corrupt = |offset: usize, reason: String| TreeError::DeserializationCorrupt { offset, reason };
+        if self.take(MAGIC.len()).ok() != Some(MAGIC) {
+            return Err(corrupt(0, String::from("this isn't jtree data")));
+        }
+        let version: u8 = self.read()?;
+        if version != VERSION {
+            return Err(corrupt(MAGIC.len(), format!("format version {version} isn't supported")));
+        }
+        let found: u8 = self.read()?;
+        if found != kind {
+            return Err(corrupt(MAGIC.len() + 1, format!("this is a '{}' tree, not a {tree_name}", char::from(found))));
+        }
+        Ok(())
+    }
+
+    /// Turn a decoded tree's failed invariant check into a TreeError::DeserializationCorrupt, pointing at
+    /// the start of the tree's data (just after the header).
+    pub(crate) fn validate<T>(&self, checked: Result<(),TreeError<T>>) -> Result<(), TreeError<T>> {
+        return match checked {
+            Err(TreeError::InvariantViolation(problem)) => Err(TreeError::DeserializationCorrupt {
+                offset: MAGIC.len() + 2,
+                reason: format!("the data holds an invalid tree: {problem}"),
+            }),
+            other => other,
+        };
+    }
+
+    /// Check that all of the data has been read.
+    pub(crate) fn finish<T>(&self) -> Result<(), TreeError<T>> {
+        if self.offset != self.bytes.len() {
+            return Err(TreeError::DeserializationCorrupt {
+                offset: self.offset,
+                reason: format!("{} unexpected bytes after the tree", self.bytes.len() - self.offset),
+            });
+        }
+        Ok(())
+    }
+
+}
+
+macro_rules! impl_codec_for_numbers {
+    ($($number:ty),*) => {
+        $(
+            impl Codec for $number {
+                fn encode(&self, bytes: &mut Vec<u8>) {
+                    bytes.extend_from_slice(&self.to_le_bytes());
+                }
+                fn decode(reader: &mut Reader) -> Result<Self, String> {
+                    Ok(Self::from_le_bytes(reader.take_array()?))
+                }
+            }
+        )*
+    };
+}
+
+impl_codec_for_numbers!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+
+// pointer-sized integers are stored as 64 bits, so the data can move between platforms
+impl Codec for usize {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        (*self as u64).encode(bytes);
+    }
+    fn decode(reader: &mut Reader) -> Result<Self, String> {
+        let value = u64::decode(reader)?;
+        return match usize::try_from(value) {
+            Ok(value) => Ok(value),
+            Err(_) => Err(format!("{value} is too large for a usize on this platform")),
+        };
+    }
+}
+
+impl Codec for isize {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        (*self as i64).encode(bytes);
+    }
+    fn decode(reader: &mut Reader) -> Result<Self, String> {
+        let value = i64::decode(reader)?;
+        return match isize::try_from(value) {
+            Ok(value) => Ok(value),
+            Err(_) => Err(format!("{value} doesn't fit in an isize on this platform")),
+        };
+    }
+}
+
+impl Codec for bool {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        bytes.push(u8::from(*self));
+    }
+    fn decode(reader: &mut Reader) -> Result<Self, String> {
+        return match u8::decode(reader)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            other => Err(format!("{other} isn't a bool")),
+        };
+    }
+}
+
+impl Codec for char {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        u32::from(*self).encode(bytes);
+    }
+    fn decode(reader: &mut Reader) -> Result<Self, String> {
+        let code = u32::decode(reader)?;
+        return match char::from_u32(code) {
+            Some(value) => Ok(value),
+            None => Err(format!("{code:#x} isn't a valid char")),
+        };
+    }
+}
+
+// strings are stored as their length in bytes, then their UTF-8 bytes
+impl Codec for String {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        self.len().encode(bytes);
+        bytes.extend_from_slice(self.as_bytes());
+    }
+    fn decode(reader: &mut Reader) -> Result<Self, String> {
+        let len = usize::decode(reader)?;
+        return match String::from_utf8(reader.take(len)?.to_vec()) {
+            Ok(value) => Ok(value),
+            Err(_) => Err(String::from("string isn't valid UTF-8")),
+        };
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip<V: Codec>(value: &V) -> V {
+        let mut bytes = Vec::new();
+        value.encode(&mut bytes);
+        let mut reader = Reader::new(&bytes);
+        let decoded = V::decode(&mut reader).unwrap();
+        assert_eq!( bytes.len(), reader.offset() );
+        decoded
+    }
+
+    #[test]
+    fn values_round_trip() {
+        assert_eq!( 258u16, round_trip(&258u16) );
+        assert_eq!( -7i64, round_trip(&-7i64) );
+        assert_eq!( 1.5f64, round_trip(&1.5f64) );
+        assert_eq!( usize::MAX, round_trip(&usize::MAX) );
+        assert!( round_trip(&true) );
+        assert_eq!( 'ß', round_trip(&'ß') );
+        assert_eq!( String::from("héllo"), round_trip(&String::from("héllo")) );
+    }
+
+    #[test]
+    fn bad_bytes_are_reported() {
+        let mut reader = Reader::new(&[1, 2, 3]);
+        assert_eq!( Err(String::from("expected 4 more bytes but only 3 remain")), u32::decode(&mut reader) );
+        assert_eq!( Err(String::from("3 isn't a bool")), bool::decode(&mut Reader::new(&[3])) );
+        let mut reader = Reader::new(&[9, 9, 1, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!( Ok(9), reader.read::<u8, u8>() );
+        // the string's length says 265 bytes follow, but there are only 2
+        assert_eq!( Err(TreeError::DeserializationCorrupt { offset: 1, reason: String::from("expected 265 more bytes but only 2 remain") }),
+            reader.read::<String, u8>() );
+    }
+
+    #[test]
+    fn shapes_pack_four_nodes_to_a_byte() {
+        let flags = [HAS_LEFT | HAS_RIGHT, HAS_LEFT, 0, HAS_RIGHT, 0];
+        let mut writer = ShapeWriter::new();
+        for node_flags in flags {
+            writer.push(node_flags);
+        }
+        let bytes = writer.into_bytes();
+        assert_eq!( vec!(0b10_00_01_11, 0), bytes );
+        let mut reader = Reader::new(&bytes);
+        let mut shape = ShapeReader::new::<u8>(&mut reader, 5).unwrap();
+        for node_flags in flags {
+            assert_eq!( Ok(node_flags), shape.next::<u8>() );
+        }
+        assert_eq!( Ok(()), shape.finish::<u8>() );
+        assert!( matches!( shape.next::<u8>(), Err(TreeError::DeserializationCorrupt { offset: 1, .. }) ) );
+    }
+
+}
@@ -0,0 +1,183 @@
+//! # Collated strings
+//!
+//! Strings normally compare by their bytes, which puts "Zebra" before "apple" and "é" after "z".  A `Collator`
+//! compares them by some other rules instead, such as a language's alphabetical order, and the strings it
+//! wraps (`Collated` strings) compare by those rules, so a tree of them is ordered that way: lookups, `range`,
+//! `floor` and `ceiling` all follow the collation.
+//!
+//!     use jtree::Javlt;
+//!     use jtree::collation::Collator;
+//!
+//!     // a simple collation: alphabetical, ignoring case, with the capitalized spelling first
+//!     let collator = Collator::new(|a, b| a.to_lowercase().cmp(&b.to_lowercase()).then(a.cmp(b)));
+//!     let words = Javlt::collated(["apple", "Zebra", "Apple", "mango"], &collator);
+//!     let listed: Vec<&str> = words.iter().map(|word| *word.value()).collect();
+//!     assert_eq!( vec!("Apple", "apple", "mango", "Zebra"), listed );
+//!     assert_eq!( Some(&"apple"), words.floor(&collator.wrap("banana")).map(|word| word.value()) );
+//!
+//! Any comparison can be supplied as a closure.  With the `icu` feature, `Collator::for_locale` makes one that
+//! follows a locale's collation rules, using ICU4X.  Every value in a tree (and every value used to look one up)
+//! should be wrapped by the same collator.  `Folded` strings (see the `folded` module) are compared by a collator
+//! too, after normalizing them.
+
+use std::{cmp::Ordering, fmt, sync::{Arc, LazyLock}};
+
+use crate::javlt::Javlt;
+
+/// The comparison a `Collator` makes.
+type CompareFn = dyn Fn(&str, &str) -> Ordering + Send + Sync;
+
+/// A way of comparing strings, shared (cheaply cloned) by the `Collated` strings it wraps.
+#[derive(Clone)]
+pub struct Collator {
+    compare: Arc<CompareFn>,
+}
+
+/// The one bytewise collator, shared by everything that uses `Collator::bytewise`.
+static BYTEWISE: LazyLock<Collator> = LazyLock::new(|| Collator::new(|a, b| a.cmp(b)));
+
+impl Collator {
+
+    /// Make a collator that compares strings with the given function.  It must be a total order: consistent,
+    /// and transitive.
+    pub fn new<F: Fn(&str, &str) -> Ordering + Send + Sync + 'static>(compare: F) -> Self {
+        Self {
+            compare: Arc::new(compare),
+        }
+    }
+
+    /// A collator that compares strings by their bytes, as `str` does.
+    pub fn bytewise() -> Self {
+        BYTEWISE.clone()
+    }
+
+    /// Compare two strings by the collation.
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        (self.compare)(a, b)
+    }
+
+    /// Wrap a string, to be compared by this collator.
+    pub fn wrap<S: AsRef<str>>(&self, value: S) -> Collated<S> {
+        Collated {
+            value,
+            collator: self.clone(),
+        }
+    }
+
+}
+
+#[cfg(feature = "icu")]
+impl Collator {
+
+    /// With the `icu` feature, make a collator that follows the collation rules of the locale with the given
+    /// BCP 47 tag (such as "sv" or "de-u-co-phonebk"), with ICU4X's default options.
+    ///
+    ///     use jtree::collation::Collator;
+    ///
+    ///     let swedish = Collator::for_locale("sv").unwrap();
+    ///     assert!( swedish.compare("ö", "z").is_gt() ); // ö comes after z in Swedish...
+    ///     let german = Collator::for_locale("de").unwrap();
+    ///     assert!( german.compare("ö", "z").is_lt() ); // ...but not in German
+    pub fn for_locale(tag: &str) -> Result<Self, icu_collator::CollatorError> {
+        let locale: icu_provider::DataLocale = tag.parse()?;
+        let collator = icu_collator::Collator::try_new(&locale, icu_collator::CollatorOptions::new())?;
+        Ok(Self::from_icu(collator))
+    }
+
+    /// With the `icu` feature, make a collator from an ICU4X collator, for control over its options.
+    pub fn from_icu(collator: icu_collator::Collator) -> Self {
+        Self::new(move |a, b| collator.compare(a, b))
+    }
+
+}
+
+impl fmt::Debug for Collator {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str("Collator")
+    }
+}
+
+/// A string (a `String`, `&str`, or anything else that is `AsRef<str>`), compared by a `Collator`.  Make one
+/// with `Collator::wrap`.
+#[derive(Clone)]
+pub struct Collated<S: AsRef<str>> {
+    value: S,
+    collator: Collator,
+}
+
+impl <S: AsRef<str>> Collated<S> {
+
+    /// The string.
+    pub fn value(&self) -> &S {
+        &self.value
+    }
+
+    /// Unwrap the string.
+    pub fn into_inner(self) -> S {
+        self.value
+    }
+
+}
+
+impl <S: AsRef<str>> PartialEq for Collated<S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.collator.compare(self.value.as_ref(), other.value.as_ref()) == Ordering::Equal
+    }
+}
+
+impl <S: AsRef<str>> PartialOrd for Collated<S> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.collator.compare(self.value.as_ref(), other.value.as_ref()))
+    }
+}
+
+impl <S: AsRef<str>> fmt::Debug for Collated<S> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "{:?}", self.value.as_ref())
+    }
+}
+
+impl <S: AsRef<str>> Javlt<Collated<S>> {
+
+    /// Create a tree of strings ordered by the collator, skipping any that it finds equal to one already added.
+    pub fn collated<U: IntoIterator<Item = S>>(collection: U, collator: &Collator) -> Self {
+        Self::from_collection(collection.into_iter().map(|value| collator.wrap(value)))
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::ordered::OrderedSet;
+
+    use super::*;
+
+    #[test]
+    fn queries_follow_the_collation() {
+        // order by length, then bytes
+        let collator = Collator::new(|a, b| a.len().cmp(&b.len()).then(a.cmp(b)));
+        let words = Javlt::collated(["ccc", "a", "bb", "dddd", "ab", "c"], &collator);
+        let listed: Vec<&str> = words.iter().map(|word| *word.value()).collect();
+        assert_eq!( vec!("a", "c", "ab", "bb", "ccc", "dddd"), listed );
+        let value = |word: Option<&Collated<&'static str>>| word.map(|word| *word.value());
+        assert_eq!( Some("bb"), value(words.floor(&collator.wrap("zz"))) );
+        assert_eq!( Some("ccc"), value(words.ceiling(&collator.wrap("aaa"))) );
+        assert_eq!( None, value(words.ceiling(&collator.wrap("eeeee"))) );
+        let two_letters: Vec<&str> = OrderedSet::range(&words, collator.wrap("aa")..collator.wrap("aaa")).map(|word| *word.value()).collect();
+        assert_eq!( vec!("ab", "bb"), two_letters );
+    }
+
+    #[cfg(feature = "icu")]
+    #[test]
+    fn locales_order_differently() {
+        let words = ["zebra", "öl", "ost", "Apfel"];
+        let order = |tag: &str| -> Vec<&str> {
+            let tree = Javlt::collated(words, &Collator::for_locale(tag).unwrap());
+            tree.iter().map(|word| *word.value()).collect()
+        };
+        assert_eq!( vec!("Apfel", "öl", "ost", "zebra"), order("de") );
+        assert_eq!( vec!("Apfel", "ost", "zebra", "öl"), order("sv") );
+    }
+
+}
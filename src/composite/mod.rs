@@ -0,0 +1,64 @@
+//! # Composite keys
+//!
+//! Tuples are ordered field by field, so a tree of tuples such as `(priority, timestamp, id)` is already
+//! ordered by priority, then by timestamp among equal priorities, and so on.  `HasPrefix` compares a
+//! tuple's leading fields with a shorter tuple (a prefix), so that `Javlt::range_by_prefix` can find all
+//! of the values that start with it in O(log(n)) plus the number found, without a full scan:
+//!
+//!     use jtree::Javlt;
+//!
+//!     let tasks = Javlt::from_collection([(1, 50, "a"), (3, 10, "b"), (3, 20, "c"), (2, 5, "d"), (3, 20, "e")]);
+//!     let priority_3: Vec<_> = tasks.range_by_prefix(&(3,)).map(|task| task.2).collect();
+//!     assert_eq!( vec!("b", "c", "e"), priority_3 );
+//!     assert_eq!( 2, tasks.range_by_prefix(&(3, 20)).count() );
+//!
+//! It's implemented for tuples of two to four fields, with every shorter prefix.  For a struct, implement
+//! it by comparing the fields that lead the struct's ordering.
+
+use std::cmp::Ordering;
+
+/// A value whose ordering starts with the fields of `P`.
+pub trait HasPrefix<P> {
+    /// Compare this value's leading fields with the prefix: Less or Greater if the value sorts before or
+    /// after every value starting with the prefix, Equal if it starts with it, or None if they can't be compared.
+    fn cmp_prefix(&self, prefix: &P) -> Option<Ordering>;
+}
+
+macro_rules! impl_has_prefix {
+    (($($t:ident),+) => ($($i:tt : $p:ident),+)) => {
+        impl <$($t: PartialOrd),+> HasPrefix<($($p,)+)> for ($($t,)+) {
+            fn cmp_prefix(&self, prefix: &($($p,)+)) -> Option<Ordering> {
+                $(
+                    match self.$i.partial_cmp(&prefix.$i) {
+                        Some(Ordering::Equal) => (),
+                        other => return other,
+                    }
+                )+
+                Some(Ordering::Equal)
+            }
+        }
+    };
+}
+
+impl_has_prefix!((A, B) => (0: A));
+impl_has_prefix!((A, B, C) => (0: A));
+impl_has_prefix!((A, B, C) => (0: A, 1: B));
+impl_has_prefix!((A, B, C, D) => (0: A));
+impl_has_prefix!((A, B, C, D) => (0: A, 1: B));
+impl_has_prefix!((A, B, C, D) => (0: A, 1: B, 2: C));
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_leading_fields_only() {
+        assert_eq!( Some(Ordering::Equal), (3, 7, 'x').cmp_prefix(&(3,)) );
+        assert_eq!( Some(Ordering::Less), (3, 7, 'x').cmp_prefix(&(3, 8)) );
+        assert_eq!( Some(Ordering::Greater), (4, 0).cmp_prefix(&(3,)) );
+        assert_eq!( Some(Ordering::Equal), (1, 2, 3, 4).cmp_prefix(&(1, 2, 3)) );
+        assert_eq!( None, (f64::NAN, 1).cmp_prefix(&(1.0,)) );
+    }
+
+}
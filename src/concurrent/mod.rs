@@ -0,0 +1,519 @@
+use std::{fmt, ops::ControlFlow, sync::{PoisonError, atomic::Ordering}};
+
+use crate::errors::TreeError;
+
+mod sync;
+
+use sync::{Arc, AtomicBool, AtomicLink, AtomicU64, AtomicUsize, Loaded, Mutex, MutexGuard, into_full, yield_now};
+
+
+
+/// The most levels a node can have; with each level holding about half the nodes of the one below,
+/// this is plenty for sets of millions of values.
+#[cfg(not(loom))]
+const MAX_LEVEL: usize = 24;
+
+/// Under loom, a few levels are enough to exercise the express lanes, and keep the interleavings to try
+/// within reach.
+#[cfg(loom)]
+const MAX_LEVEL: usize = 3;
+
+/// # Concurrent Set
+///
+/// An ordered set that threads can add to, look up and delete from at the same time, for workloads where
+/// a single lock around the whole set (as in `JavltSync`) is the bottleneck.
+///
+/// It's a "lazy" skip list (Herlihy, Lev, Luchangco and Shavit, 2006): a sorted linked list with extra
+/// express lanes, where each node has its own lock.  A change only locks the handful of nodes just before
+/// the value being added or deleted, so changes in different parts of the set don't wait for each other.
+/// Deleting first marks the node as deleted and then unlinks it, and every operation takes effect at a
+/// single moment (it is linearizable), so an `insert` or `remove` that succeeds is seen by every later call.
+/// Unlinked nodes are freed by reference counting once no thread is still passing through them.
+///
+///     use std::{sync::Arc, thread};
+///     use jtree::ConcurrentSet;
+///
+///     let shared = Arc::new(ConcurrentSet::new());
+///     let handles: Vec<_> = (0..4).map(|t| {
+///         let shared = Arc::clone(&shared);
+///         thread::spawn(move || {
+///             for n in 0..100 {
+///                 let _ = shared.insert(n * 4 + t);
+///             }
+///         })
+///     }).collect();
+///     for handle in handles {
+///         handle.join().unwrap();
+///     }
+///     assert_eq!( 400, shared.get_size() );
+///     assert_eq!( (0..400).collect::<Vec<_>>(), shared.snapshot_vec() );
+///
+/// Lookups (`contains` and `snapshot_vec`) are lock-free: the links are read atomically, through
+/// `arc-swap`, so a lookup never waits for another thread.  Changes aren't: one can wait a moment for
+/// another that holds a lock on the same nodes.  No operation allocates anything but the node it adds, and
+/// the head of the list is never shared by reference count, so threads don't all contend to count it.
+pub struct ConcurrentSet<T: PartialEq + PartialOrd + Clone> {
+    head: Node<T>,
+    size: AtomicUsize,
+    seed: AtomicU64, // for choosing the new nodes' levels
+}
+
+type Link<T> = Option<Arc<Node<T>>>;
+
+/// A link on each level, as found by `find`: the last node before a value (None standing for the head),
+/// or the node after that.  Kept on the stack.
+type Links<T> = [Link<T>; MAX_LEVEL];
+
+struct Node<T> {
+    value: Option<T>, // None only for the head, which comes before every value
+    next: Vec<AtomicLink<Node<T>>>, // one link per level, from the bottom (which links every node) up
+    lock: Mutex<()>, // held while changing the links that lead out of this node
+    marked: AtomicBool, // set when the node is being deleted
+    fully_linked: AtomicBool, // set when the node has been linked in at every level
+}
+
+/// Lock a mutex, carrying on if another thread panicked while holding it: the set's operations never
+/// leave a link half-written, so the data is still good.
+fn lock<G>(mutex: &Mutex<G>) -> MutexGuard<'_, G> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A fresh set of links for `find` to fill in.
+fn no_links<T>() -> Links<T> {
+    std::array::from_fn(|_| None)
+}
+
+impl <T: PartialEq + PartialOrd + Clone> ConcurrentSet<T> {
+
+    /// Create a new set with no data
+    pub fn new() -> Self {
+        Self {
+            head: Node::new(None, MAX_LEVEL),
+            size: AtomicUsize::new(0),
+            seed: AtomicU64::new(0),
+        }
+    }
+
+    /// Create a new set from a collection (vector, array, or whatever), skipping duplicates.
+    pub fn from_collection<U: IntoIterator<Item = T>>(collection: U) -> Self {
+        let set = Self::new();
+        for value in collection {
+            let _ = set.insert(value);
+        }
+        set
+    }
+
+    /// Insert a value, returning a TreeError::ValueAlreadyStored if it's already in the set.
+    pub fn insert(&self, value: T) -> Result<(),TreeError<T>> {
+        let height = self.random_height();
+        let (mut preds, mut succs) = (no_links(), no_links());
+        loop {
+            match self.find(&value, &mut preds, &mut succs) {
+                Some(level) => {
+                    let found = succs[level].as_ref().unwrap();
+                    if !found.marked.load(Ordering::SeqCst) {
+                        // another thread may still be linking it in; it isn't stored until that's finished
+                        while !found.fully_linked.load(Ordering::SeqCst) {
+                            yield_now();
+                        }
+                        return Err(TreeError::ValueAlreadyStored { value });
+                    }
+                    // it's being deleted, so try again once it's gone
+                    yield_now();
+                    continue;
+                },
+                None => {},
+            }
+            let guards = self.lock_preds(&preds, height, |level, pred| {
+                match &succs[level] {
+                    Some(succ) if succ.marked.load(Ordering::SeqCst) => false,
+                    succ => pred.links_to(level, succ.as_ref()),
+                }
+            });
+            if guards.is_none() {
+                // a neighbor changed since we looked, so look again
+                continue;
+            }
+            let node = Arc::new(Node::new(Some(value), height));
+            for (link, succ) in node.next.iter().zip(&succs) {
+                link.store(succ.clone());
+            }
+            for (level, pred) in preds.iter().enumerate().take(height) {
+                self.or_head(pred.as_deref()).next[level].store(Some(Arc::clone(&node)));
+            }
+            node.fully_linked.store(true, Ordering::SeqCst);
+            self.size.fetch_add(1, Ordering::SeqCst);
+            return Ok(());
+        }
+    }
+
+    /// Returns true if the value is currently a member of the set.  This takes no locks.
+    pub fn contains(&self, value: &T) -> bool {
+        let found = self.descend(value, |_, _, current| {
+            return match &*current {
+                Some(node) if node.value.as_ref() == Some(value) => {
+                    ControlFlow::Break(node.fully_linked.load(Ordering::SeqCst) && !node.marked.load(Ordering::SeqCst))
+                },
+                _ => ControlFlow::Continue(()),
+            };
+        });
+        found.unwrap_or(false)
+    }
+
+    /// If the value is in the set, delete it.  Otherwise a TreeError::ValueNotFound will be returned.
+    pub fn remove(&self, value: T) -> Result<(),TreeError<T>> {
+        let (mut preds, mut succs) = (no_links(), no_links());
+        let victim = match self.find(&value, &mut preds, &mut succs) {
+            // a node that isn't yet linked at its top level is still being inserted, so it isn't stored yet
+            Some(level) => succs[level].take().filter(|node| {
+                node.fully_linked.load(Ordering::SeqCst) && node.next.len() == level + 1 && !node.marked.load(Ordering::SeqCst)
+            }),
+            None => None,
+        };
+        let victim = match victim {
+            Some(node) => node,
+            None => return Err(TreeError::ValueNotFound { value }),
+        };
+        {
+            // marking is the moment of deletion; if another thread got there first, it deleted the value
+            let _guard = lock(&victim.lock);
+            if victim.marked.load(Ordering::SeqCst) {
+                return Err(TreeError::ValueNotFound { value });
+            }
+            victim.marked.store(true, Ordering::SeqCst);
+        }
+        // now unlink it; nobody changes a marked node's links, so they can be read without its lock
+        let height = victim.next.len();
+        loop {
+            let guards = self.lock_preds(&preds, height, |level, pred| pred.links_to(level, Some(&victim)));
+            if guards.is_some() {
+                for level in (0..height).rev() {
+                    self.or_head(preds[level].as_deref()).next[level].store(victim.next[level].load_full());
+                }
+                self.size.fetch_sub(1, Ordering::SeqCst);
+                return Ok(());
+            }
+            drop(guards);
+            yield_now();
+            self.find(&value, &mut preds, &mut succs);
+        }
+    }
+
+    /// Get the number of values in the set
+    pub fn get_size(&self) -> usize {
+        self.size.load(Ordering::SeqCst)
+    }
+
+    /// Returns true if the set holds no values
+    pub fn is_empty(&self) -> bool {
+        self.get_size() == 0
+    }
+
+    /// Copy the values into an ordered Vec, from least to greatest.  While other threads are changing
+    /// the set, this sees each value as it is at the moment the walk passes it.  This takes no locks.
+    pub fn snapshot_vec(&self) -> Vec<T> {
+        let mut values = Vec::new();
+        let mut current = self.head.next[0].load();
+        while let Some(node) = &*current {
+            if node.fully_linked.load(Ordering::SeqCst) && !node.marked.load(Ordering::SeqCst) {
+                match &node.value {
+                    Some(value) => values.push(value.clone()),
+                    None => {},
+                }
+            }
+            let next = node.next[0].load();
+            current = next;
+        }
+        values
+    }
+
+    /// The node, or the head if it's None.
+    fn or_head<'a>(&'a self, node: Option<&'a Node<T>>) -> &'a Node<T> {
+        node.unwrap_or(&self.head)
+    }
+
+    /// Walk down the levels towards the value, calling `f` on each level with the last node before the value
+    /// (None for the head) and the node after that, until it says to stop.  Returns what `f` stopped with.
+    fn descend<B, F>(&self, value: &T, mut f: F) -> Option<B>
+        where F: FnMut(usize, Option<&Arc<Node<T>>>, Loaded<Node<T>>) -> ControlFlow<B> {
+        let mut pred: Option<Loaded<Node<T>>> = None;
+        for level in (0..MAX_LEVEL).rev() {
+            let mut current = self.or_head(pred.as_ref().and_then(|loaded| loaded.as_deref())).next[level].load();
+            loop {
+                let next = match &*current {
+                    Some(node) if node.is_before(value) => node.next[level].load(),
+                    _ => break,
+                };
+                pred = Some(std::mem::replace(&mut current, next));
+            }
+            match f(level, pred.as_ref().and_then(|loaded| loaded.as_ref()), current) {
+                ControlFlow::Break(result) => return Some(result),
+                ControlFlow::Continue(()) => {},
+            }
+        }
+        None
+    }
+
+    /// Walk down the levels towards the value, filling in each level's last node before the value (`preds`)
+    /// and the node after that (`succs`).  Returns the highest level on which the value was found, if any.
+    fn find(&self, value: &T, preds: &mut Links<T>, succs: &mut Links<T>) -> Option<usize> {
+        let mut found = None;
+        let _ = self.descend(value, |level, pred, current| {
+            if found.is_none() && current.as_ref().is_some_and(|node| node.value.as_ref() == Some(value)) {
+                found = Some(level);
+            }
+            preds[level] = pred.cloned();
+            succs[level] = into_full(current);
+            ControlFlow::<()>::Continue(())
+        });
+        found
+    }
+
+    /// Lock the predecessors on the bottom `height` levels, from the bottom up (so that every thread takes
+    /// the locks in the same order, from later in the list to earlier), and check that each is unmarked
+    /// and passes the `valid` test.  If any isn't, the locks are released and None is returned.
+    fn lock_preds<'a, F: Fn(usize, &Node<T>) -> bool>(&'a self, preds: &'a Links<T>, height: usize, valid: F) -> Option<[Option<MutexGuard<'a, ()>>; MAX_LEVEL]> {
+        let mut guards = std::array::from_fn(|_| None);
+        for level in 0..height {
+            let pred = self.or_head(preds[level].as_deref());
+            // a node can be the predecessor on several levels, but it's only locked once
+            if level == 0 || !std::ptr::eq(pred, self.or_head(preds[level - 1].as_deref())) {
+                guards[level] = Some(lock(&pred.lock));
+            }
+            if pred.marked.load(Ordering::SeqCst) || !valid(level, pred) {
+                return None;
+            }
+        }
+        Some(guards)
+    }
+
+    /// Choose how many levels a new node gets: one, plus one more for each coin flip that comes up heads.
+    fn random_height(&self) -> usize {
+        // splitmix64, stepped atomically so that threads don't share a random number
+        let mut z = self.seed.fetch_add(0x9e3779b97f4a7c15, Ordering::Relaxed).wrapping_add(0x9e3779b97f4a7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^= z >> 31;
+        return (z.trailing_ones() as usize + 1).min(MAX_LEVEL);
+    }
+
+}
+
+impl <T> Node<T> {
+
+    fn new(value: Option<T>, height: usize) -> Self {
+        Self {
+            value,
+            next: (0..height).map(|_| AtomicLink::new()).collect(),
+            lock: Mutex::new(()),
+            marked: AtomicBool::new(false),
+            fully_linked: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns true if this node's link on the given level points to `succ` (or nowhere, if `succ` is None)
+    fn links_to(&self, level: usize, succ: Option<&Arc<Node<T>>>) -> bool {
+        return match (&*self.next[level].load(), succ) {
+            (Some(next), Some(succ)) => Arc::ptr_eq(next, succ),
+            (None, None) => true,
+            _ => false,
+        };
+    }
+
+}
+
+impl <T: PartialOrd> Node<T> {
+
+    /// Returns true if this node comes before the value in the list
+    fn is_before(&self, value: &T) -> bool {
+        return match &self.value {
+            Some(own) => own < value,
+            None => true,
+        };
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd + Clone> Default for ConcurrentSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <T: PartialEq + PartialOrd + Clone + fmt::Debug> fmt::Debug for ConcurrentSet<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_set().entries(self.snapshot_vec()).finish()
+    }
+}
+
+impl <T: PartialEq + PartialOrd + Clone> Drop for ConcurrentSet<T> {
+    fn drop(&mut self) {
+        // unlink the nodes one at a time; letting the Arcs drop each other would recurse down the whole list
+        for level in 1..MAX_LEVEL {
+            self.head.next[level].take();
+        }
+        let mut current = self.head.next[0].take();
+        while let Some(node) = current {
+            for level in 1..node.next.len() {
+                node.next[level].take();
+            }
+            current = node.next[0].take();
+        }
+    }
+}
+
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn add_find_and_remove() {
+        let set = ConcurrentSet::from_collection([5,3,8,1,3,9]);
+        assert_eq!( 5, set.get_size() );
+        assert_eq!( vec!(1,3,5,8,9), set.snapshot_vec() );
+        assert!( set.contains(&8) );
+        assert!( !set.contains(&4) );
+        assert_eq!( Err(TreeError::ValueAlreadyStored { value: 5 }), set.insert(5) );
+        assert_eq!( Ok(()), set.remove(5) );
+        assert_eq!( Err(TreeError::ValueNotFound { value: 5 }), set.remove(5) );
+        assert!( !set.contains(&5) );
+        assert_eq!( vec!(1,3,8,9), set.snapshot_vec() );
+        assert_eq!( "{1, 3, 8, 9}", format!("{:?}", set) );
+    }
+
+    #[test]
+    fn drop_a_long_list() {
+        let set = ConcurrentSet::from_collection(0..50_000);
+        assert_eq!( 50_000, set.get_size() );
+    }
+
+    #[test]
+    fn stress_with_contending_threads() {
+        // every thread inserts and removes the same small range of values, so they constantly collide;
+        // each thread tallies its successful inserts minus removes, value by value
+        let set = ConcurrentSet::new();
+        let tallies: Vec<Vec<i64>> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..8u64).map(|t| {
+                let set = &set;
+                scope.spawn(move || {
+                    let mut tally = vec![0i64; 64];
+                    let mut state = t + 1;
+                    for _ in 0..5_000 {
+                        state ^= state << 13;
+                        state ^= state >> 7;
+                        state ^= state << 17;
+                        let value = (state % 64) as usize;
+                        if state & 64 == 0 {
+                            if set.insert(value).is_ok() {
+                                tally[value] += 1;
+                            }
+                        } else if set.remove(value).is_ok() {
+                            tally[value] -= 1;
+                        }
+                        let _ = set.contains(&value);
+                    }
+                    tally
+                })
+            }).collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+        // every successful insert must be matched by a successful remove, except for the values left in the set
+        let expected: Vec<usize> = (0..64).filter(|&value| {
+            let net: i64 = tallies.iter().map(|tally| tally[value]).sum();
+            assert!( net == 0 || net == 1 );
+            net == 1
+        }).collect();
+        assert_eq!( expected, set.snapshot_vec() );
+        assert_eq!( expected.len(), set.get_size() );
+        for value in 0..64 {
+            assert_eq!( expected.contains(&value), set.contains(&value) );
+        }
+    }
+
+    #[test]
+    fn iterate_while_removing() {
+        // walkers pass through nodes as they are unlinked; those nodes must stay alive (and their links
+        // usable) until the walkers have moved on, and every walk must still come out in order
+        let set = ConcurrentSet::from_collection(0..2000);
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                for value in (0..2000).rev() {
+                    set.remove(value).unwrap();
+                }
+            });
+            for _ in 0..2 {
+                scope.spawn(|| {
+                    loop {
+                        let values = set.snapshot_vec();
+                        assert!( values.windows(2).all(|pair| pair[0] < pair[1]) );
+                        if values.is_empty() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        assert!( set.is_empty() );
+    }
+
+}
+
+/// Run with `RUSTFLAGS="--cfg loom" LOOM_MAX_PREEMPTIONS=2 cargo test --release --lib concurrent`.  Each test
+/// tries every way its threads' steps can interleave with up to two preemptions, which is where nearly all
+/// concurrency bugs show up; unbounded, the search takes far too long.
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use loom::thread;
+
+    use super::*;
+
+    #[test]
+    fn racing_inserts_of_one_value_store_it_once() {
+        loom::model(|| {
+            let set = Arc::new(ConcurrentSet::new());
+            let other = Arc::clone(&set);
+            let handle = thread::spawn(move || other.insert(1).is_ok());
+            let here = set.insert(1).is_ok();
+            let there = handle.join().unwrap();
+            assert!( here != there );
+            assert_eq!( 1, set.get_size() );
+            assert_eq!( vec!(1), set.snapshot_vec() );
+        });
+    }
+
+    #[test]
+    fn racing_removes_of_one_value_delete_it_once() {
+        loom::model(|| {
+            let set = Arc::new(ConcurrentSet::from_collection([1, 2]));
+            let other = Arc::clone(&set);
+            let handle = thread::spawn(move || other.remove(2).is_ok());
+            let here = set.remove(2).is_ok();
+            let there = handle.join().unwrap();
+            assert!( here != there );
+            assert_eq!( vec!(1), set.snapshot_vec() );
+        });
+    }
+
+    #[test]
+    fn lookups_see_a_change_whole_or_not_at_all() {
+        loom::model(|| {
+            let set = Arc::new(ConcurrentSet::from_collection([2]));
+            let other = Arc::clone(&set);
+            let handle = thread::spawn(move || {
+                other.insert(1).unwrap();
+                other.remove(2).unwrap();
+            });
+            // 1 is added before 2 is deleted, so once 2 is seen to be gone, 1 must be found
+            let two = set.contains(&2);
+            let one = set.contains(&1);
+            assert!( two || one );
+            handle.join().unwrap();
+            assert_eq!( vec!(1), set.snapshot_vec() );
+            assert_eq!( 1, set.get_size() );
+        });
+    }
+
+}
@@ -0,0 +1,149 @@
+//! The synchronization types `ConcurrentSet` is built from: the standard library's, with `arc-swap` for the
+//! links, or loom's stand-ins when the crate is built with `--cfg loom`, so that loom can try every way the
+//! set's operations might interleave.  Loom can't look inside `arc-swap`, so there a link is a plain mutex;
+//! that checks the set's own locking and marking, which is where its subtleties lie.
+
+#[cfg(not(loom))]
+pub(super) use std::{sync::{Arc, Mutex, MutexGuard, atomic::{AtomicBool, AtomicU64, AtomicUsize}}, thread::yield_now};
+
+#[cfg(loom)]
+pub(super) use loom::{sync::{Arc, Mutex, MutexGuard, atomic::{AtomicBool, AtomicU64, AtomicUsize}}, thread::yield_now};
+
+/// A link that can be read without taking a lock.  Reading it gives a guard that keeps the node it points
+/// to alive (usually without touching the node's reference count, which would have every thread writing
+/// to the same few counters near the head of the list).
+#[cfg(not(loom))]
+pub(super) struct AtomicLink<T>(arc_swap::ArcSwapOption<T>);
+
+/// What reading an `AtomicLink` gives: the link, kept alive for as long as it's held.
+#[cfg(not(loom))]
+pub(super) type Loaded<T> = arc_swap::Guard<Option<Arc<T>>>;
+
+#[cfg(not(loom))]
+impl <T> AtomicLink<T> {
+
+    pub(super) fn new() -> Self {
+        Self(arc_swap::ArcSwapOption::empty())
+    }
+
+    pub(super) fn load(&self) -> Loaded<T> {
+        self.0.load()
+    }
+
+    /// Read the link into a reference of its own, for holding on to.
+    pub(super) fn load_full(&self) -> Option<Arc<T>> {
+        self.0.load_full()
+    }
+
+    pub(super) fn store(&self, link: Option<Arc<T>>) {
+        self.0.store(link);
+    }
+
+    pub(super) fn take(&self) -> Option<Arc<T>> {
+        self.0.swap(None)
+    }
+
+}
+
+/// Turn what was read from a link into a reference of its own.
+#[cfg(not(loom))]
+pub(super) fn into_full<T>(loaded: Loaded<T>) -> Option<Arc<T>> {
+    arc_swap::Guard::into_inner(loaded)
+}
+
+#[cfg(loom)]
+pub(super) struct AtomicLink<T>(Mutex<Option<Arc<T>>>);
+
+#[cfg(loom)]
+pub(super) struct Loaded<T>(Option<Arc<T>>);
+
+#[cfg(loom)]
+impl <T> std::ops::Deref for Loaded<T> {
+    type Target = Option<Arc<T>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(loom)]
+impl <T> AtomicLink<T> {
+
+    pub(super) fn new() -> Self {
+        Self(Mutex::new(None))
+    }
+
+    pub(super) fn load(&self) -> Loaded<T> {
+        Loaded(self.load_full())
+    }
+
+    pub(super) fn load_full(&self) -> Option<Arc<T>> {
+        self.0.lock().unwrap().clone()
+    }
+
+    pub(super) fn store(&self, link: Option<Arc<T>>) {
+        *self.0.lock().unwrap() = link;
+    }
+
+    pub(super) fn take(&self) -> Option<Arc<T>> {
+        self.0.lock().unwrap().take()
+    }
+
+}
+
+#[cfg(loom)]
+pub(super) fn into_full<T>(loaded: Loaded<T>) -> Option<Arc<T>> {
+    loaded.0
+}
+
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(loom))]
+    use std::thread;
+
+    #[cfg(loom)]
+    use loom::thread;
+
+    use super::*;
+
+    /// Run a test as it is, or under loom, every way its threads could interleave.
+    fn model<F: Fn() + Sync + Send + 'static>(test: F) {
+        #[cfg(loom)]
+        loom::model(test);
+        #[cfg(not(loom))]
+        test();
+    }
+
+    #[test]
+    fn links_hold_what_was_stored() {
+        model(|| {
+            let link = AtomicLink::new();
+            assert!( link.load().is_none() );
+            link.store(Some(Arc::new(5)));
+            assert_eq!( Some(5), link.load().as_deref().copied() );
+            assert_eq!( Some(5), into_full(link.load()).as_deref().copied() );
+            let held = link.load_full();
+            link.store(Some(Arc::new(6)));
+            // what was read stays alive after the link moves on
+            assert_eq!( Some(5), held.as_deref().copied() );
+            assert_eq!( Some(6), link.take().as_deref().copied() );
+            assert!( link.take().is_none() && link.load_full().is_none() );
+        });
+    }
+
+    #[test]
+    fn readers_see_a_whole_store() {
+        model(|| {
+            let link = Arc::new(AtomicLink::new());
+            link.store(Some(Arc::new(String::from("old"))));
+            let writer = Arc::clone(&link);
+            let handle = thread::spawn(move || writer.store(Some(Arc::new(String::from("new")))));
+            let seen = link.load_full().unwrap();
+            assert!( *seen == "old" || *seen == "new" );
+            handle.join().unwrap();
+            assert_eq!( Some("new"), link.load().as_deref().map(String::as_str) );
+        });
+    }
+
+}
@@ -0,0 +1,363 @@
+use std::{ffi::OsString, fmt, fs::{self, File, OpenOptions}, io::Write, path::{Path, PathBuf}};
+
+use crate::codec::{self, Codec, Reader};
+use crate::errors::TreeError;
+use crate::javlt::Javlt;
+
+
+
+/// # Durable AVL Tree
+///
+/// A `Javlt` kept in a file that survives crashes.  The file at `path` holds a checkpoint of the tree (written
+/// by `Javlt::save`), and every change since then is appended to a write-ahead log beside it (at `path.wal`)
+/// before the change is made in memory.  `recover(path)` loads the checkpoint and replays the log onto it, so
+/// a reopened tree holds every change that `add` or `drop_value` returned from, even if the program stopped
+/// without warning.
+///
+///     use jtree::durable::DurableJavlt;
+///
+///     let path = std::env::temp_dir().join(format!("jtree_durable_doc_{}", std::process::id()));
+///     let mut tree = DurableJavlt::recover(&path).unwrap();
+///     tree.add(3).unwrap();
+///     tree.add(1).unwrap();
+///     tree.drop_value(3).unwrap();
+///     drop(tree);
+///     let tree = DurableJavlt::<i32>::recover(&path).unwrap();
+///     assert_eq!( vec!(1), tree.as_vec() );
+///     # tree.destroy().unwrap();
+///
+/// Once the log holds `checkpoint_every` changes (1000 by default), the whole tree is saved as a new
+/// checkpoint and the log is emptied.  The checkpoint is written to a temporary file and renamed over the
+/// old one, so a crash part-way through leaves the old checkpoint in place; the directory is synced after the
+/// rename, so the log isn't emptied until the new checkpoint is sure to be the one found by `recover`.
+///
+/// Each log record is a `+` (added) or `-` (dropped) byte, the length of the value's encoding, the encoding
+/// itself, and a checksum.  A crash in the middle of an append leaves an incomplete record at the end of the
+/// log, which `recover` discards; an append that fails with an error is cut back off the log straight away,
+/// so the records that follow it stay readable.  A complete record with the wrong checksum is reported as a
+/// TreeError::DeserializationCorrupt.  Replaying a record that was already in the checkpoint (as happens when
+/// a crash comes between saving a checkpoint and emptying the log) changes nothing, because the last change
+/// to each value decides whether it's in the tree.
+pub struct DurableJavlt<T: PartialEq + PartialOrd + Clone + Codec> {
+    tree: Javlt<T>,
+    path: PathBuf,
+    log: File,
+    log_end: u64, // the length of the log up to the end of its last complete record
+    logged: u32, // changes in the log since the last checkpoint
+    checkpoint_every: u32,
+}
+
+const ADDED: u8 = b'+';
+const DROPPED: u8 = b'-';
+const LOG_HEADER_LEN: usize = codec::MAGIC.len() + 2;
+
+impl <T: PartialEq + PartialOrd + Clone + Codec> DurableJavlt<T> {
+
+    /// Open the tree kept at `path`, replaying its log onto its last checkpoint, or start a new, empty tree
+    /// there if there's no checkpoint or log yet.
+    pub fn recover<P: AsRef<Path>>(path: P) -> Result<Self,TreeError<T>> {
+        let path = path.as_ref().to_path_buf();
+        let mut tree = match fs::exists(&path)? {
+            true => Javlt::load(&path)?,
+            false => Javlt::new(),
+        };
+        let log_path = sibling(&path, ".wal");
+        let bytes = match fs::exists(&log_path)? {
+            true => fs::read(&log_path)?,
+            false => Vec::new(),
+        };
+        let mut log = OpenOptions::new().create(true).append(true).open(&log_path)?;
+        let (logged, kept) = match bytes.len() < LOG_HEADER_LEN {
+            true => (0, 0),
+            false => replay(&mut tree, &bytes)?,
+        };
+        if kept < bytes.len() || kept == 0 {
+            // drop an incomplete last record, or write the header of a new log
+            log.set_len(kept as u64)?;
+            if kept == 0 {
+                let mut header = Vec::new();
+                codec::write_header(&mut header, b'W');
+                log.write_all(&header)?;
+            }
+            log.sync_data()?;
+            sync_directory(&log_path)?;
+        }
+        let log_end = kept.max(LOG_HEADER_LEN) as u64;
+        Ok(Self { tree, path, log, log_end, logged, checkpoint_every: 1000 })
+    }
+
+    /// Save a checkpoint whenever the log holds this many changes (at least 1).
+    pub fn with_checkpoint_every(mut self, changes: u32) -> Self {
+        self.checkpoint_every = changes.max(1);
+        self
+    }
+
+    /// Add a value, returning a TreeError::ValueAlreadyStored if it's already there.  The change is in the
+    /// log, on disk, before this returns.
+    pub fn add(&mut self, value: T) -> Result<(),TreeError<T>> {
+        if self.tree.contains(&value) {
+            return Err(TreeError::ValueAlreadyStored { value });
+        }
+        self.append(ADDED, &value)?;
+        self.tree.add(value)?;
+        self.after_change()
+    }
+
+    /// Delete a value, returning a TreeError::ValueNotFound if it isn't there.  The change is in the log, on
+    /// disk, before this returns.
+    pub fn drop_value(&mut self, value: T) -> Result<(),TreeError<T>> {
+        if !self.tree.contains(&value) {
+            return Err(TreeError::ValueNotFound { value });
+        }
+        self.append(DROPPED, &value)?;
+        self.tree.drop_value(value)?;
+        self.after_change()
+    }
+
+    /// Save the whole tree as the new checkpoint and empty the log.
+    pub fn checkpoint(&mut self) -> Result<(),TreeError<T>> {
+        let temporary = sibling(&self.path, ".tmp");
+        let mut file = File::create(&temporary)?;
+        file.write_all(&self.tree.to_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temporary, &self.path)?;
+        sync_directory(&self.path)?;
+        self.log.set_len(LOG_HEADER_LEN as u64)?;
+        self.log.sync_data()?;
+        self.log_end = LOG_HEADER_LEN as u64;
+        self.logged = 0;
+        Ok(())
+    }
+
+    /// Delete the checkpoint and the log from disk, consuming the tree.
+    pub fn destroy(self) -> Result<(),TreeError<T>> {
+        let log_path = sibling(&self.path, ".wal");
+        drop(self.log);
+        if fs::exists(&self.path)? {
+            fs::remove_file(&self.path)?;
+        }
+        fs::remove_file(log_path)?;
+        Ok(())
+    }
+
+    /// Returns true if the value is in the tree
+    pub fn contains(&self, value: &T) -> bool {
+        self.tree.contains(value)
+    }
+
+    /// Get the number of values in the tree
+    pub fn get_size(&self) -> u32 {
+        self.tree.get_size()
+    }
+
+    /// Returns all the values in the tree as an ordered Vec from least to greatest.
+    pub fn as_vec(&self) -> Vec<T> {
+        self.tree.as_vec()
+    }
+
+    /// The tree as it is in memory, for reading.
+    pub fn tree(&self) -> &Javlt<T> {
+        &self.tree
+    }
+
+    /// Get the number of changes in the log since the last checkpoint
+    pub fn log_len(&self) -> u32 {
+        self.logged
+    }
+
+    /// Append a change's record to the log and wait for it to reach the disk.  If that fails, whatever part of
+    /// the record was written is cut off again, so that it can't come between the records before and after it.
+    fn append(&mut self, op: u8, value: &T) -> Result<(),TreeError<T>> {
+        let mut encoded = Vec::new();
+        value.encode(&mut encoded);
+        let mut record = vec!(op);
+        (encoded.len() as u32).encode(&mut record);
+        record.extend_from_slice(&encoded);
+        checksum(op, &encoded).encode(&mut record);
+        if let Err(error) = self.log.write_all(&record).and_then(|()| self.log.sync_data()) {
+            // if even this fails, `recover` still finds the torn record at the end of the log, and discards it
+            let _ = self.log.set_len(self.log_end);
+            return Err(error.into());
+        }
+        self.log_end += record.len() as u64;
+        Ok(())
+    }
+
+    fn after_change(&mut self) -> Result<(),TreeError<T>> {
+        self.logged += 1;
+        if self.logged >= self.checkpoint_every {
+            self.checkpoint()?;
+        }
+        Ok(())
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd + Clone + Codec + fmt::Debug> fmt::Debug for DurableJavlt<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("DurableJavlt")
+            .field("path", &self.path)
+            .field("size", &self.tree.get_size())
+            .field("logged", &self.logged)
+            .field("values", &self.tree.as_vec())
+            .finish()
+    }
+}
+
+/// Replay the log's records onto the tree, returning how many there were and how many of the bytes they
+/// took up (the rest being an incomplete last record).
+fn replay<T: PartialEq + PartialOrd + Clone + Codec>(tree: &mut Javlt<T>, bytes: &[u8]) -> Result<(u32, usize),TreeError<T>> {
+    let mut reader = Reader::new(bytes);
+    reader.read_header(b'W', "write-ahead log")?;
+    let mut logged = 0;
+    loop {
+        let start = reader.offset();
+        if start == bytes.len() {
+            return Ok((logged, start));
+        }
+        let (op, encoded, stored) = match read_record(&mut reader) {
+            Some(record) => record,
+            None => return Ok((logged, start)),
+        };
+        let corrupt = |reason: &str| TreeError::DeserializationCorrupt { offset: start, reason: String::from(reason) };
+        if stored != checksum(op, encoded) {
+            return Err(corrupt("the log record's checksum doesn't match"));
+        }
+        let mut value_reader = Reader::new(encoded);
+        let value: T = value_reader.read()?;
+        value_reader.finish()?;
+        // a change that's already in the checkpoint fails harmlessly
+        let _ = match op {
+            ADDED => tree.add(value),
+            DROPPED => tree.drop_value(value),
+            _ => return Err(corrupt("the log record isn't an addition or a deletion")),
+        };
+        logged += 1;
+    }
+}
+
+/// Read a record's operation byte, value encoding and checksum, or None if the log ends first.
+fn read_record<'a>(reader: &mut Reader<'a>) -> Option<(u8, &'a [u8], u32)> {
+    let op = reader.take(1).ok()?[0];
+    let len = u32::from_le_bytes(reader.take_array().ok()?);
+    let encoded = reader.take(len as usize).ok()?;
+    let stored = u32::from_le_bytes(reader.take_array().ok()?);
+    Some((op, encoded, stored))
+}
+
+/// FNV-1a hash of a log record's contents
+fn checksum(op: u8, encoded: &[u8]) -> u32 {
+    let mut hash: u32 = 0x811c9dc5;
+    for byte in std::iter::once(&op).chain(encoded) {
+        hash ^= *byte as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    hash
+}
+
+/// Wait for the directory holding the file to reach the disk, so that a file just created or renamed there is
+/// sure to be found under its new name after a crash.  (Only Unix-like systems let a directory be synced.)
+fn sync_directory(path: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        File::open(directory)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+/// The path with a suffix added to its file name, e.g. `tree.bin` to `tree.bin.wal`.
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("jtree_durable_{name}_{}", std::process::id()))
+    }
+
+    #[test]
+    fn recovers_from_checkpoint_and_log() {
+        let path = test_path("recover");
+        let mut tree = DurableJavlt::recover(&path).unwrap().with_checkpoint_every(7);
+        for n in 0..20 {
+            tree.add(n).unwrap();
+        }
+        for n in (0..20).step_by(3) {
+            tree.drop_value(n).unwrap();
+        }
+        assert_eq!( Err(TreeError::ValueAlreadyStored { value: 1 }), tree.add(1) );
+        assert_eq!( Err(TreeError::ValueNotFound { value: 0 }), tree.drop_value(0) );
+        assert_eq!( 27 % 7, tree.log_len() );
+        // a failed append would be cut back to here
+        assert_eq!( fs::metadata(sibling(&path, ".wal")).unwrap().len(), tree.log_end );
+        let expected = tree.as_vec();
+        drop(tree);
+        let tree = DurableJavlt::<i32>::recover(&path).unwrap();
+        assert_eq!( expected, tree.as_vec() );
+        assert_eq!( 27 % 7, tree.log_len() );
+        assert_eq!( Ok(()), tree.tree().check_invariants() );
+        tree.destroy().unwrap();
+        assert!( !fs::exists(&path).unwrap() );
+    }
+
+    #[test]
+    fn replays_log_onto_newer_checkpoint() {
+        // as if the program stopped after saving a checkpoint but before emptying the log
+        let path = test_path("stale_log");
+        let mut tree = DurableJavlt::recover(&path).unwrap();
+        tree.add(1).unwrap();
+        tree.add(2).unwrap();
+        tree.drop_value(1).unwrap();
+        tree.add(1).unwrap();
+        tree.drop_value(2).unwrap();
+        let log = fs::read(sibling(&path, ".wal")).unwrap();
+        tree.checkpoint().unwrap();
+        drop(tree);
+        fs::write(sibling(&path, ".wal"), log).unwrap();
+        let tree = DurableJavlt::<i32>::recover(&path).unwrap();
+        assert_eq!( vec!(1), tree.as_vec() );
+        tree.destroy().unwrap();
+    }
+
+    #[test]
+    fn discards_torn_record() {
+        let path = test_path("torn");
+        let mut tree = DurableJavlt::recover(&path).unwrap();
+        tree.add(String::from("kept")).unwrap();
+        tree.add(String::from("torn")).unwrap();
+        drop(tree);
+        let log_path = sibling(&path, ".wal");
+        let mut log = fs::read(&log_path).unwrap();
+        log.truncate(log.len() - 3);
+        fs::write(&log_path, &log).unwrap();
+        let mut tree = DurableJavlt::<String>::recover(&path).unwrap();
+        assert_eq!( vec!(String::from("kept")), tree.as_vec() );
+        // the torn record is gone from the log, so later records follow the good ones
+        tree.add(String::from("later")).unwrap();
+        drop(tree);
+        let tree = DurableJavlt::<String>::recover(&path).unwrap();
+        assert_eq!( 2, tree.log_len() );
+        assert_eq!( vec!(String::from("kept"), String::from("later")), tree.as_vec() );
+        // a complete record that's been damaged is reported
+        let mut log = fs::read(&log_path).unwrap();
+        let last = log.len() - 6;
+        log[last] ^= 0xff;
+        fs::write(&log_path, &log).unwrap();
+        let error = DurableJavlt::<String>::recover(&path).unwrap_err();
+        assert!( matches!( error, TreeError::DeserializationCorrupt { .. } ) );
+        tree.destroy().unwrap();
+    }
+
+}
@@ -1,3 +1,5 @@
 pub mod tree_error;
+pub mod occupied_error;
 
-pub use tree_error::TreeError;
\ No newline at end of file
+pub use tree_error::TreeError;
+pub use occupied_error::OccupiedError;
\ No newline at end of file
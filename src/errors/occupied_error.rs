@@ -0,0 +1,37 @@
+use std::{error::Error, fmt};
+
+
+/// Returned by `try_insert` when the tree already holds a value equal to the one given: the stored value
+/// (which the tree keeps), and the value that wasn't inserted, handed back to the caller.
+pub struct OccupiedError<'a, T> {
+    /// The value already in the tree.
+    pub existing: &'a T,
+    /// The value that wasn't inserted.
+    pub value: T,
+}
+
+impl <T: PartialEq> PartialEq for OccupiedError<'_, T> {
+
+    fn eq(&self, other: &Self) -> bool {
+        self.existing == other.existing && self.value == other.value
+    }
+}
+
+impl <T: fmt::Debug> fmt::Debug for OccupiedError<'_, T> {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OccupiedError")
+            .field("existing", self.existing)
+            .field("value", &self.value)
+            .finish()
+    }
+}
+
+impl <T: fmt::Debug> fmt::Display for OccupiedError<'_, T> {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "OccupiedError: {:?} wasn't inserted, because the tree already holds {:?}.", self.value, self.existing)
+    }
+}
+
+impl <T: fmt::Debug> Error for OccupiedError<'_, T> {}
@@ -1,22 +1,96 @@
-use std::{error::Error, fmt};
+use std::{error::Error, fmt, io};
 
 
-#[derive(PartialEq, Debug)]
-pub enum TreeError {
+/// Errors returned by the trees' operations.  Where an operation consumed a value that it couldn't
+/// store or find, the error hands that value back to the caller.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum TreeError<T> {
     /// Caller attempted to add a duplicate value to a tree that only accepts unique values.
-    ValueAlreadyStored,
-    ValueNotFound,
+    ValueAlreadyStored { value: T },
+    /// Caller attempted to drop a value that isn't in the tree.
+    ValueNotFound { value: T },
+    /// An element of a batch was a duplicate (of a value in the tree or earlier in the batch); `index` is its position in the batch.
+    DuplicateAt { index: usize, value: T },
+    /// A tree's internal structure is inconsistent; the description says what was wrong, and where.
+    InvariantViolation(String),
+    /// The operation needs at least one value, but the tree is empty.
+    EmptyTree,
+    /// A position-based lookup asked for `index`, but the tree only holds `len` values.
+    IndexOutOfRange { index: usize, len: usize },
+    /// The bounds of a range are in the wrong order (the start is after the end).
+    InvalidRange,
+    /// The value can't be ordered relative to the tree's other values (such as a floating point NaN).
+    Incomparable { value: T },
+    /// Serialized tree data couldn't be decoded; `offset` is the position (in bytes) where the problem was found.
+    DeserializationCorrupt { offset: usize, reason: String },
+    /// A value inserted at a cursor doesn't belong between the values on either side of it.
+    OutOfOrder { value: T },
+    /// Reading or writing a saved tree failed; the underlying error is the `source()`.
+    Io(io::Error),
+    /// The tree already holds as many values as its size can count (`u32::MAX`), so the value wasn't added.
+    CapacityExceeded { value: T },
+    /// The value would have gone deeper in the tree than its depth limit allows, so it wasn't added.
+    DepthLimitExceeded { value: T, limit: usize },
 }
 
-impl fmt::Display for TreeError {
+// io::Error can't be compared, so two Io errors are equal if they're the same kind with the same message
+impl <T: PartialEq> PartialEq for TreeError<T> {
+
+    fn eq(&self, other: &Self) -> bool {
+        return match (self, other) {
+            (TreeError::ValueAlreadyStored { value: a }, TreeError::ValueAlreadyStored { value: b }) => a == b,
+            (TreeError::ValueNotFound { value: a }, TreeError::ValueNotFound { value: b }) => a == b,
+            (TreeError::DuplicateAt { index: i, value: a }, TreeError::DuplicateAt { index: j, value: b }) => i == j && a == b,
+            (TreeError::InvariantViolation(a), TreeError::InvariantViolation(b)) => a == b,
+            (TreeError::EmptyTree, TreeError::EmptyTree) => true,
+            (TreeError::IndexOutOfRange { index: i, len: m }, TreeError::IndexOutOfRange { index: j, len: n }) => i == j && m == n,
+            (TreeError::InvalidRange, TreeError::InvalidRange) => true,
+            (TreeError::Incomparable { value: a }, TreeError::Incomparable { value: b }) => a == b,
+            (TreeError::DeserializationCorrupt { offset: i, reason: a }, TreeError::DeserializationCorrupt { offset: j, reason: b }) => i == j && a == b,
+            (TreeError::OutOfOrder { value: a }, TreeError::OutOfOrder { value: b }) => a == b,
+            (TreeError::Io(a), TreeError::Io(b)) => a.kind() == b.kind() && a.to_string() == b.to_string(),
+            (TreeError::CapacityExceeded { value: a }, TreeError::CapacityExceeded { value: b }) => a == b,
+            (TreeError::DepthLimitExceeded { value: a, limit: m }, TreeError::DepthLimitExceeded { value: b, limit: n }) => a == b && m == n,
+            _ => false,
+        };
+    }
+}
+
+impl <T> From<io::Error> for TreeError<T> {
+    fn from(error: io::Error) -> Self {
+        TreeError::Io(error)
+    }
+}
+
+impl <T: fmt::Debug> fmt::Display for TreeError<T> {
 
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let description = match self {
-            TreeError::ValueAlreadyStored => "Caller attempted to add a duplicate value to a tree that only accepts unique values.",
-            TreeError::ValueNotFound => "Specified value was not found in the tree.",
-        }.to_string();        
+            TreeError::ValueAlreadyStored { value } => format!("Caller attempted to add a duplicate value ({value:?}) to a tree that only accepts unique values."),
+            TreeError::ValueNotFound { value } => format!("Specified value ({value:?}) was not found in the tree."),
+            TreeError::DuplicateAt { index, value } => format!("Element {index} of the batch ({value:?}) is a duplicate."),
+            TreeError::InvariantViolation(problem) => format!("Tree invariant violated: {problem}."),
+            TreeError::EmptyTree => "The tree is empty.".to_string(),
+            TreeError::IndexOutOfRange { index, len } => format!("Index {index} is out of range for a tree holding {len} values."),
+            TreeError::InvalidRange => "The start of the range is after its end.".to_string(),
+            TreeError::Incomparable { value } => format!("Value ({value:?}) can't be ordered relative to the values in the tree."),
+            TreeError::DeserializationCorrupt { offset, reason } => format!("Serialized tree data is corrupt at byte {offset}: {reason}."),
+            TreeError::OutOfOrder { value } => format!("Value ({value:?}) doesn't belong at the cursor's position."),
+            TreeError::Io(error) => format!("Couldn't read or write the tree's file: {error}."),
+            TreeError::CapacityExceeded { value } => format!("Value ({value:?}) wasn't added: the tree already holds as many values as it can count."),
+            TreeError::DepthLimitExceeded { value, limit } => format!("Value ({value:?}) wasn't added: it would be deeper in the tree than its depth limit ({limit})."),
+        };
         write!(f, "TreeError: {description}")
     }
 }
 
-impl Error for TreeError {}
+impl <T: fmt::Debug> Error for TreeError<T> {
+
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        return match self {
+            TreeError::Io(error) => Some(error),
+            _ => None,
+        };
+    }
+}
@@ -0,0 +1,369 @@
+use std::{cmp::Ordering, fmt, time::Instant};
+
+use crate::javlt::{Cursor, Javlt};
+
+
+
+/// # Expiring Map
+///
+/// An ordered map whose entries each carry an expiry time, for small in-process caches that also need
+/// to scan their keys in order.  The entries are kept in a `Javlt` ordered by key, and a second `Javlt`
+/// orders them by expiry time, so `purge_expired` only visits the entries it removes: O(log(n)) each.
+///
+/// Expired entries are hidden from lookups and scans straight away, but they only go (freeing their
+/// memory) when `purge_expired` is called.  All of the methods take the current time as `now`, so you
+/// choose the clock (and tests can control it).
+///
+///     use std::time::{Duration, Instant};
+///     use jtree::ExpiringMap;
+///
+///     let start = Instant::now();
+///     let mut cache = ExpiringMap::new();
+///     cache.insert("b", 2, start + Duration::from_secs(10));
+///     cache.insert("a", 1, start + Duration::from_secs(5));
+///     cache.insert("c", 3, start + Duration::from_secs(60));
+///     assert_eq!( Some(&1), cache.get(&"a", start) );
+///
+///     let later = start + Duration::from_secs(30);
+///     assert_eq!( None, cache.get(&"a", later) );
+///     assert_eq!( vec!((&"c", &3)), cache.iter(later).collect::<Vec<_>>() );
+///     assert_eq!( vec!(("a", 1), ("b", 2)), cache.purge_expired(later) );
+///     assert_eq!( 1, cache.get_size() );
+///
+/// A key that can't be ordered even against itself (such as a floating point NaN) can't be stored, and is
+/// never found.
+pub struct ExpiringMap<K: PartialEq + PartialOrd + Clone, V: Clone> {
+    entries: Javlt<Entry<K, V>>, // ordered by key
+    expiries: Javlt<Expiry<K>>, // ordered by expiry time, then key
+}
+
+/// A map entry, compared by its key alone so that an entry with no payload can be used to look one up.
+#[derive(Clone)]
+struct Entry<K, V> {
+    key: K,
+    payload: Option<(V, Instant)>, // the value and its expiry time; None only in a lookup
+}
+
+impl <K: PartialEq, V> PartialEq for Entry<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl <K: PartialOrd, V> PartialOrd for Entry<K, V> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.key.partial_cmp(&other.key)
+    }
+}
+
+#[derive(PartialEq, PartialOrd, Clone)]
+struct Expiry<K> {
+    at: Instant,
+    key: K,
+}
+
+impl <K: PartialEq + PartialOrd + Clone, V: Clone> ExpiringMap<K, V> {
+
+    /// Create a new map with no entries
+    pub fn new() -> Self {
+        Self {
+            entries: Javlt::new(),
+            expiries: Javlt::new(),
+        }
+    }
+
+    /// Store a value under the key until the given time, replacing (and returning) any value the key
+    /// already had, even if that one has expired.
+    ///
+    /// Panics if the key can't be ordered even against itself (such as a floating point NaN): it could be
+    /// stored, but never found again, not even by `purge_expired`.
+    pub fn insert(&mut self, key: K, value: V, expires_at: Instant) -> Option<V> {
+        assert!( key.partial_cmp(&key).is_some(), "a map key must be comparable with itself" );
+        let old = self.take(&key);
+        let _ = self.expiries.add(Expiry { at: expires_at, key: key.clone() });
+        let _ = self.entries.add(Entry { key, payload: Some((value, expires_at)) });
+        return match old {
+            None => None,
+            Some((value, _)) => Some(value),
+        };
+    }
+
+    /// Look up the key's value, if it has one that hasn't expired by `now`.
+    pub fn get(&self, key: &K, now: Instant) -> Option<&V> {
+        return match self.find(key) {
+            Some((value, expires_at)) if *expires_at > now => Some(value),
+            _ => None,
+        };
+    }
+
+    /// Returns true if the key has a value that hasn't expired by `now`.
+    pub fn contains_key(&self, key: &K, now: Instant) -> bool {
+        self.get(key, now).is_some()
+    }
+
+    /// The time at which the key's value expires (or expired), if the map still holds one.
+    pub fn expires_at(&self, key: &K) -> Option<Instant> {
+        return match self.find(key) {
+            Some((_, expires_at)) => Some(*expires_at),
+            None => None,
+        };
+    }
+
+    /// Delete the key's entry and return its value, if the map still holds one (expired or not).
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        return match self.take(key) {
+            Some((value, _)) => Some(value),
+            None => None,
+        };
+    }
+
+    /// Delete every entry that has expired by `now`, returning their keys and values in the order they expired.
+    pub fn purge_expired(&mut self, now: Instant) -> Vec<(K, V)> {
+        let mut purged = Vec::new();
+        while let Some(expiry) = self.expiries.least_value() {
+            if expiry.at > now {
+                break;
+            }
+            // the expiry goes whether or not its entry is found, so the loop always moves on
+            let _ = self.expiries.drop_value(expiry.clone());
+            let taken = self.take(&expiry.key);
+            debug_assert!(taken.as_ref().is_some_and(|(_, at)| *at == expiry.at), "ExpiringMap has an expiry with no matching entry");
+            match taken {
+                Some((value, _)) => purged.push((expiry.key, value)),
+                None => {},
+            }
+        }
+        purged
+    }
+
+    /// Get the number of entries held, including any that have expired but haven't been purged.
+    pub fn get_size(&self) -> u32 {
+        self.entries.get_size()
+    }
+
+    /// Iterate over the entries that haven't expired by `now`, in order of their keys.
+    pub fn iter(&self, now: Instant) -> impl Iterator<Item = (&K, &V)> {
+        Self::live(self.entries.cursor_front(), now)
+    }
+
+    /// Iterate over the entries that haven't expired by `now`, in order of their keys, starting from the
+    /// first key at least as great as `key`.
+    pub fn iter_from(&self, key: &K, now: Instant) -> impl Iterator<Item = (&K, &V)> {
+        Self::live(self.entries.cursor_at(&Self::lookup(key)), now)
+    }
+
+    /// Consume the map, moving the keys and values of the entries that haven't expired by `now` into a Vec,
+    /// in order of their keys.  Unlike collecting `iter`, nothing is cloned.
+    pub fn into_sorted_vec(self, now: Instant) -> Vec<(K, V)> {
+        self.entries.into_sorted_vec().into_iter().filter_map(|entry| {
+            return match entry.payload {
+                Some((value, expires_at)) if expires_at > now => Some((entry.key, value)),
+                _ => None,
+            };
+        }).collect()
+    }
+
+    /// Call `f` on each key and a mutable reference to its value, in order of the keys, to change the values in
+    /// place in one traversal.  Entries that have expired but haven't been purged are visited too.
+    ///
+    ///     use std::time::{Duration, Instant};
+    ///     use jtree::ExpiringMap;
+    ///
+    ///     let now = Instant::now();
+    ///     let mut prices = ExpiringMap::new();
+    ///     prices.insert("jam", 250, now + Duration::from_secs(60));
+    ///     prices.insert("tea", 400, now + Duration::from_secs(60));
+    ///     prices.for_each_mut(|_, price| *price = *price * 11 / 10);
+    ///     assert_eq!( Some(&440), prices.get(&"tea", now) );
+    pub fn for_each_mut<F: FnMut(&K, &mut V)>(&mut self, mut f: F) {
+        self.entries.visit_in_order_mut(|entry| {
+            match &mut entry.payload {
+                Some((value, _)) => f(&entry.key, value),
+                None => {},
+            }
+        });
+    }
+
+    /// Build a new map with the same keys and expiry times, and each value replaced by what `f` makes of it.
+    /// If `f` fails for any entry, its error is returned and no map is built.  The keys are already in order,
+    /// so the new map is built in O(n) rather than by inserting each entry.
+    ///
+    ///     use std::time::{Duration, Instant};
+    ///     use jtree::ExpiringMap;
+    ///
+    ///     let now = Instant::now();
+    ///     let mut counts = ExpiringMap::new();
+    ///     counts.insert('a', "12", now + Duration::from_secs(60));
+    ///     counts.insert('b', "7", now + Duration::from_secs(60));
+    ///     let parsed = counts.map_values(|_, count| count.parse::<u32>()).unwrap();
+    ///     assert_eq!( Some(&12), parsed.get(&'a', now) );
+    ///     counts.insert('c', "many", now + Duration::from_secs(60));
+    ///     assert!( counts.map_values(|_, count| count.parse::<u32>()).is_err() );
+    pub fn map_values<U: Clone, E, F: FnMut(&K, &V) -> Result<U, E>>(&self, mut f: F) -> Result<ExpiringMap<K, U>, E> {
+        let mut entries = Vec::with_capacity(self.entries.get_size() as usize);
+        for entry in self.entries.values() {
+            match &entry.payload {
+                Some((value, expires_at)) => entries.push(Entry { key: entry.key.clone(), payload: Some((f(&entry.key, value)?, *expires_at)) }),
+                None => {},
+            }
+        }
+        Ok(ExpiringMap {
+            entries: Javlt::from_sorted_vec(entries),
+            expiries: Javlt::from_sorted_vec(self.expiries.as_vec()),
+        })
+    }
+
+    /// The entries from the cursor's position onwards (none, if it's at the ghost position), skipping expired ones.
+    fn live(cursor: Cursor<'_, Entry<K, V>>, now: Instant) -> impl Iterator<Item = (&K, &V)> {
+        cursor.peek().into_iter().chain(cursor).filter_map(move |entry| {
+            return match &entry.payload {
+                Some((value, expires_at)) if *expires_at > now => Some((&entry.key, value)),
+                _ => None,
+            };
+        })
+    }
+
+    /// An entry with no payload, to look up the entry with the same key.
+    fn lookup(key: &K) -> Entry<K, V> {
+        Entry { key: key.clone(), payload: None }
+    }
+
+    /// The key's stored value and expiry time, expired or not.
+    fn find(&self, key: &K) -> Option<&(V, Instant)> {
+        return match self.entries.cursor_at(&Self::lookup(key)).peek() {
+            Some(entry) if entry.key == *key => entry.payload.as_ref(),
+            _ => None,
+        };
+    }
+
+    /// Delete the key's entry from both indexes, returning its value and expiry time.
+    fn take(&mut self, key: &K) -> Option<(V, Instant)> {
+        let (value, expires_at) = self.find(key)?.clone();
+        let _ = self.entries.drop_value(Self::lookup(key));
+        let _ = self.expiries.drop_value(Expiry { at: expires_at, key: key.clone() });
+        Some((value, expires_at))
+    }
+
+}
+
+impl <K: PartialEq + PartialOrd + Clone, V: Clone> Default for ExpiringMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <K: PartialEq + PartialOrd + Clone + fmt::Debug, V: Clone + fmt::Debug> fmt::Debug for ExpiringMap<K, V> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = fmt.debug_map();
+        self.entries.visit_in_order(|entry| {
+            match &entry.payload {
+                Some((value, expires_at)) => {
+                    map.entry(&entry.key, &(value, expires_at));
+                },
+                None => {},
+            }
+        });
+        map.finish()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn replace_and_remove() {
+        let start = Instant::now();
+        let mut cache = ExpiringMap::new();
+        assert_eq!( None, cache.insert(1, "one", start + Duration::from_secs(1)) );
+        assert_eq!( Some("one"), cache.insert(1, "uno", start + Duration::from_secs(100)) );
+        // the replacement's expiry time counts, not the old one's
+        assert_eq!( Some(&"uno"), cache.get(&1, start + Duration::from_secs(50)) );
+        assert_eq!( Some(start + Duration::from_secs(100)), cache.expires_at(&1) );
+        assert!( cache.purge_expired(start + Duration::from_secs(50)).is_empty() );
+        assert_eq!( Some("uno"), cache.remove(&1) );
+        assert_eq!( None, cache.remove(&1) );
+        assert_eq!( 0, cache.get_size() );
+        assert!( cache.expiries.check_invariants().is_ok() );
+        assert_eq!( 0, cache.expiries.get_size() );
+    }
+
+    #[test]
+    #[cfg_attr(debug_assertions, should_panic(expected = "ExpiringMap has an expiry with no matching entry"))]
+    fn purging_an_orphaned_expiry_moves_on() {
+        let start = Instant::now();
+        let mut cache = ExpiringMap::new();
+        cache.insert(2, "two", start);
+        assert!( cache.expiries.add(Expiry { at: start, key: 1 }).is_ok() );
+        assert_eq!( vec!((2, "two")), cache.purge_expired(start + Duration::from_secs(1)) );
+        assert_eq!( 0, cache.expiries.get_size() );
+    }
+
+    #[test]
+    fn incomparable_keys_are_refused() {
+        let start = Instant::now();
+        let mut cache = ExpiringMap::new();
+        cache.insert(1.5, "kept", start);
+        assert!( std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| cache.insert(f64::NAN, "lost", start))).is_err() );
+        assert_eq!( None, cache.get(&f64::NAN, start) );
+        assert_eq!( vec!((1.5, "kept")), cache.purge_expired(start + Duration::from_secs(1)) );
+        assert_eq!( 0, cache.get_size() );
+    }
+
+    #[test]
+    fn scan_and_purge_in_order() {
+        let start = Instant::now();
+        let mut cache = ExpiringMap::new();
+        // keys 0..100, each expiring after (key * 7 % 100) seconds
+        for key in 0..100u64 {
+            cache.insert(key, key * 10, start + Duration::from_secs(key * 7 % 100));
+        }
+        let now = start + Duration::from_secs(50);
+        let live: Vec<u64> = cache.iter(now).map(|(key, _)| *key).collect();
+        assert_eq!( (0..100).filter(|key| key * 7 % 100 > 50).collect::<Vec<_>>(), live );
+        assert_eq!( Some((&65, &650)), cache.iter_from(&58, now).next() );
+        let purged = cache.purge_expired(now);
+        assert_eq!( 51, purged.len() );
+        assert!( purged.windows(2).all(|pair| pair[0].0 * 7 % 100 < pair[1].0 * 7 % 100) );
+        assert_eq!( 49, cache.get_size() );
+        assert_eq!( live, cache.iter(now).map(|(key, _)| *key).collect::<Vec<_>>() );
+        assert_eq!( 0, cache.iter_from(&200, now).count() );
+    }
+
+    #[test]
+    fn into_sorted_vec_drops_expired_entries() {
+        let start = Instant::now();
+        let mut cache = ExpiringMap::new();
+        cache.insert(String::from("b"), 2, start + Duration::from_secs(10));
+        cache.insert(String::from("a"), 1, start + Duration::from_secs(5));
+        cache.insert(String::from("c"), 3, start + Duration::from_secs(60));
+        assert_eq!( vec!((String::from("b"), 2), (String::from("c"), 3)), cache.into_sorted_vec(start + Duration::from_secs(7)) );
+    }
+
+    #[test]
+    fn values_change_in_place_or_into_a_new_map() {
+        let start = Instant::now();
+        let mut cache = ExpiringMap::new();
+        for key in 0..50u64 {
+            cache.insert(key, key, start + Duration::from_secs(key));
+        }
+        cache.for_each_mut(|key, value| *value += key * 100);
+        assert_eq!( Some(&4040), cache.get(&40, start) );
+        let halves = cache.map_values(|_, value| match value % 2 {
+            0 => Ok(value / 2),
+            _ => Err(*value),
+        });
+        assert_eq!( Err(101), halves.map(|map| map.get_size()) );
+        let strings = cache.map_values(|_, value| Ok::<String, ()>(value.to_string())).unwrap();
+        assert!( strings.entries.check_invariants().is_ok() );
+        assert!( strings.expiries.check_invariants().is_ok() );
+        assert_eq!( Some(start + Duration::from_secs(30)), strings.expires_at(&30) );
+        let mut strings = strings;
+        assert_eq!( 25, strings.purge_expired(start + Duration::from_secs(24)).len() );
+        assert_eq!( vec!((String::from("2525"))), strings.iter(start + Duration::from_secs(24)).take(1).map(|(_, value)| value.clone()).collect::<Vec<_>>() );
+    }
+
+}
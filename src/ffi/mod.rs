@@ -0,0 +1,149 @@
+//! With the `ffi` feature, a `Javlt<u64>` can be used from C through the `extern "C"` functions below.  The
+//! tree is an opaque pointer, made by `jtree_javlt_u64_new` and freed by `jtree_javlt_u64_destroy`.  Build a
+//! library to link against with e.g. `cargo rustc --release --features ffi --crate-type staticlib`, and
+//! declare the functions in C as:
+//!
+//! ```text
+//! typedef struct JavltU64 JavltU64;
+//!
+//! JavltU64 *jtree_javlt_u64_new(void);
+//! void      jtree_javlt_u64_destroy(JavltU64 *tree);
+//! bool      jtree_javlt_u64_insert(JavltU64 *tree, uint64_t value);
+//! bool      jtree_javlt_u64_contains(const JavltU64 *tree, uint64_t value);
+//! bool      jtree_javlt_u64_remove(JavltU64 *tree, uint64_t value);
+//! uint32_t  jtree_javlt_u64_size(const JavltU64 *tree);
+//! size_t    jtree_javlt_u64_dump(const JavltU64 *tree, uint64_t *out, size_t capacity);
+//! ```
+//!
+//! A null tree pointer is treated as an empty tree that can't be changed.
+
+use std::{ops::ControlFlow, ptr};
+
+use crate::javlt::Javlt;
+
+/// The tree behind the opaque pointer handed to C.
+pub struct JavltU64 {
+    tree: Javlt<u64>,
+}
+
+/// Create a new, empty tree.  Free it with `jtree_javlt_u64_destroy`.
+#[unsafe(no_mangle)]
+pub extern "C" fn jtree_javlt_u64_new() -> *mut JavltU64 {
+    Box::into_raw(Box::new(JavltU64 { tree: Javlt::new() }))
+}
+
+/// Free a tree.  A null pointer is ignored.
+///
+/// # Safety
+/// `tree` must be null or a pointer from `jtree_javlt_u64_new` that hasn't been destroyed already.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn jtree_javlt_u64_destroy(tree: *mut JavltU64) {
+    if !tree.is_null() {
+        drop(unsafe { Box::from_raw(tree) });
+    }
+}
+
+/// Insert a value, returning false if it was already in the tree.
+///
+/// # Safety
+/// `tree` must be null or a live pointer from `jtree_javlt_u64_new`, not in use by another thread.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn jtree_javlt_u64_insert(tree: *mut JavltU64, value: u64) -> bool {
+    return match unsafe { tree.as_mut() } {
+        Some(handle) => handle.tree.add(value).is_ok(),
+        None => false,
+    };
+}
+
+/// Returns true if the value is in the tree.
+///
+/// # Safety
+/// `tree` must be null or a live pointer from `jtree_javlt_u64_new`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn jtree_javlt_u64_contains(tree: *const JavltU64, value: u64) -> bool {
+    return match unsafe { tree.as_ref() } {
+        Some(handle) => handle.tree.contains(&value),
+        None => false,
+    };
+}
+
+/// Delete a value, returning false if it wasn't in the tree.
+///
+/// # Safety
+/// `tree` must be null or a live pointer from `jtree_javlt_u64_new`, not in use by another thread.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn jtree_javlt_u64_remove(tree: *mut JavltU64, value: u64) -> bool {
+    return match unsafe { tree.as_mut() } {
+        Some(handle) => handle.tree.drop_value(value).is_ok(),
+        None => false,
+    };
+}
+
+/// The number of values in the tree.
+///
+/// # Safety
+/// `tree` must be null or a live pointer from `jtree_javlt_u64_new`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn jtree_javlt_u64_size(tree: *const JavltU64) -> u32 {
+    return match unsafe { tree.as_ref() } {
+        Some(handle) => handle.tree.get_size(),
+        None => 0,
+    };
+}
+
+/// Copy the values, least first, into `out`, stopping after `capacity` of them.  Returns the number copied.
+/// Call it with a capacity of at least `jtree_javlt_u64_size` to get them all.
+///
+/// # Safety
+/// `tree` must be null or a live pointer from `jtree_javlt_u64_new`, and `out` must be null or point to
+/// room for `capacity` values.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn jtree_javlt_u64_dump(tree: *const JavltU64, out: *mut u64, capacity: usize) -> usize {
+    let handle = match unsafe { tree.as_ref() } {
+        Some(handle) => handle,
+        None => return 0,
+    };
+    if out.is_null() {
+        return 0;
+    }
+    let mut copied = 0;
+    let _ = handle.tree.try_visit_in_order(|value| {
+        if copied == capacity {
+            return ControlFlow::Break(());
+        }
+        unsafe { ptr::write(out.add(copied), *value) };
+        copied += 1;
+        ControlFlow::Continue(())
+    });
+    copied
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calls_as_c_would_make_them() {
+        unsafe {
+            let tree = jtree_javlt_u64_new();
+            for value in [50, 10, 40, 20, 30, 10] {
+                jtree_javlt_u64_insert(tree, value);
+            }
+            assert_eq!( 5, jtree_javlt_u64_size(tree) );
+            assert!( jtree_javlt_u64_contains(tree, 40) );
+            assert!( jtree_javlt_u64_remove(tree, 40) );
+            assert!( !jtree_javlt_u64_remove(tree, 40) );
+            let mut out = [0u64; 8];
+            assert_eq!( 4, jtree_javlt_u64_dump(tree, out.as_mut_ptr(), out.len()) );
+            assert_eq!( [10, 20, 30, 50], out[..4] );
+            assert_eq!( 2, jtree_javlt_u64_dump(tree, out.as_mut_ptr(), 2) );
+            jtree_javlt_u64_destroy(tree);
+
+            assert!( !jtree_javlt_u64_insert(ptr::null_mut(), 1) );
+            assert_eq!( 0, jtree_javlt_u64_size(ptr::null()) );
+            jtree_javlt_u64_destroy(ptr::null_mut());
+        }
+    }
+
+}
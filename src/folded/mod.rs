@@ -0,0 +1,184 @@
+//! # Case-insensitive strings
+//!
+//! The trees order values by their own `PartialOrd`, so to order strings some other way they need a wrapper
+//! that compares them that way.  `Folded` is one ready-made for case-insensitive trees: it keeps a string along
+//! with a normalized form of it (lower case, by default), and compares by the normalized form alone, so
+//! "Apple" and "apple" are the same value to a tree.  Whichever spelling is added first is the one kept.
+//!
+//!     use jtree::Javlt;
+//!     use jtree::folded::Folded;
+//!
+//!     let mut fruit = Javlt::case_insensitive(["banana", "Apple", "cherry"]);
+//!     assert!( fruit.add(Folded::new("APPLE")).is_err() );
+//!     assert!( fruit.contains(&Folded::new("BANANA")) );
+//!     let spellings: Vec<&str> = fruit.iter().map(|word| *word.value()).collect();
+//!     assert_eq!( vec!("Apple", "banana", "cherry"), spellings );
+//!
+//! Other normalizations (trimming, Unicode case folding, stripping accents) can be plugged in with
+//! `Folded::with_normalizer` and `Javlt::with_normalizer`.  The normalized forms are compared by their bytes,
+//! unless `Folded::with_collator` gives a `Collator` (see the `collation` module) to compare them by.  Values
+//! compared with each other must all be made with the same normalizer and collator, including those used only
+//! to look values up.
+
+use std::{cmp::Ordering, fmt};
+
+use crate::collation::{Collated, Collator};
+use crate::javlt::Javlt;
+
+/// Makes the normalized form of a string, by which `Folded` strings are compared.
+pub type Normalizer = fn(&str) -> String;
+
+/// A string (a `String`, `&str`, or anything else that is `AsRef<str>`), compared by a normalized form of it.
+#[derive(Clone)]
+pub struct Folded<S: AsRef<str>> {
+    key: Collated<String>, // the normalized form, made once and compared by the collator
+    value: S,
+}
+
+impl <S: AsRef<str>> Folded<S> {
+
+    /// Wrap a string, to be compared case-insensitively (by its lower case form).
+    pub fn new(value: S) -> Self {
+        Self::with_normalizer(value, str::to_lowercase)
+    }
+
+    /// Wrap a string, to be compared by the form `normalize` makes of it.
+    ///
+    ///     use jtree::folded::Folded;
+    ///
+    ///     let trimmed = |name: &str| name.trim().to_lowercase();
+    ///     assert!( Folded::with_normalizer(" Ada ", trimmed) == Folded::with_normalizer("ada", trimmed) );
+    pub fn with_normalizer(value: S, normalize: Normalizer) -> Self {
+        Self::with_collator(value, normalize, &Collator::bytewise())
+    }
+
+    /// Wrap a string, to be compared by the form `normalize` makes of it, with the normalized forms compared
+    /// by `collator`.
+    ///
+    ///     use std::cmp::Ordering;
+    ///     use jtree::collation::Collator;
+    ///     use jtree::folded::Folded;
+    ///
+    ///     // case-insensitive, with shorter strings first
+    ///     let by_length = Collator::new(|a, b| a.len().cmp(&b.len()).then(a.cmp(b)));
+    ///     let fold = |word| Folded::with_collator(word, str::to_lowercase, &by_length);
+    ///     assert_eq!( Some(Ordering::Less), fold("Zoo").partial_cmp(&fold("apple")) );
+    ///     assert!( fold("ZOO") == fold("zoo") );
+    pub fn with_collator(value: S, normalize: Normalizer, collator: &Collator) -> Self {
+        Self {
+            key: collator.wrap(normalize(value.as_ref())),
+            value,
+        }
+    }
+
+    /// The string as it was given.
+    pub fn value(&self) -> &S {
+        &self.value
+    }
+
+    /// The normalized form of the string, by which it's compared.
+    pub fn key(&self) -> &str {
+        self.key.value()
+    }
+
+    /// Unwrap the string as it was given.
+    pub fn into_inner(self) -> S {
+        self.value
+    }
+
+}
+
+impl <S: AsRef<str>> PartialEq for Folded<S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl <S: AsRef<str>> PartialOrd for Folded<S> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.key.partial_cmp(&other.key)
+    }
+}
+
+impl <S: AsRef<str>> fmt::Debug for Folded<S> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "{:?}", self.value.as_ref())
+    }
+}
+
+impl <S: AsRef<str>> Javlt<Folded<S>> {
+
+    /// Create a tree of strings ordered and deduplicated case-insensitively, keeping the first spelling of each.
+    pub fn case_insensitive<U: IntoIterator<Item = S>>(collection: U) -> Self {
+        Self::from_collection(collection.into_iter().map(Folded::new))
+    }
+
+    /// Create a tree of strings ordered and deduplicated by the form `normalize` makes of them, keeping the
+    /// first of each that are the same once normalized.
+    pub fn with_normalizer<U: IntoIterator<Item = S>>(collection: U, normalize: Normalizer) -> Self {
+        Self::from_collection(collection.into_iter().map(|value| Folded::with_normalizer(value, normalize)))
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spellings_collide() {
+        let words = vec!(String::from("Zebra"), String::from("apple"), String::from("ZEBRA"), String::from("Apple"), String::from("mango"));
+        let tree = Javlt::case_insensitive(words);
+        assert_eq!( 3, tree.get_size() );
+        assert_eq!( vec!("apple", "mango", "Zebra"), tree.iter().map(|word| word.value().as_str()).collect::<Vec<_>>() );
+        assert_eq!( Some("Zebra"), tree.get(&Folded::new(String::from("zebra"))).map(|word| word.value().as_str()) );
+
+        // a custom normalizer: ignore surrounding spaces and underscores as well as case
+        let squash: Normalizer = |name| name.trim().replace('_', "").to_lowercase();
+        let tree = Javlt::with_normalizer(["user_id", " UserId", "name"], squash);
+        assert_eq!( 2, tree.get_size() );
+        assert!( tree.contains(&Folded::with_normalizer("USERID ", squash)) );
+        assert_eq!( vec!("name", "userid"), tree.iter().map(Folded::key).collect::<Vec<_>>() );
+        assert_eq!( "user_id", tree.into_sorted_vec().pop().unwrap().into_inner() );
+    }
+
+    #[test]
+    fn comparisons_ignore_case() {
+        assert!( Folded::new("Hello") == Folded::new("hELLO") );
+        assert!( Folded::new("Hello") != Folded::new("Help") );
+        assert_eq!( Some(Ordering::Less), Folded::new("apple").partial_cmp(&Folded::new("Banana")) );
+        assert_eq!( Some(Ordering::Greater), Folded::new("Zebra").partial_cmp(&Folded::new("apple")) );
+        assert_eq!( Some(Ordering::Equal), Folded::new("MiXeD").partial_cmp(&Folded::new("mixed")) );
+        // bytewise, "Zebra" would come before "apple"
+        assert!( "Zebra" < "apple" );
+        assert!( Folded::new("Zebra") > Folded::new("apple") );
+        assert_eq!( "mixed", Folded::new("MiXeD").key() );
+        assert_eq!( "\"MiXeD\"", format!("{:?}", Folded::new("MiXeD")) );
+    }
+
+    #[test]
+    fn trees_are_ordered_by_the_folded_form() {
+        let tree = Javlt::case_insensitive(["delta", "Charlie", "ALPHA", "bravo", "Echo"]);
+        assert_eq!( vec!("ALPHA", "bravo", "Charlie", "delta", "Echo"), tree.iter().map(|word| *word.value()).collect::<Vec<_>>() );
+        assert_eq!( Some(&"Charlie"), tree.floor(&Folded::new("CHEESE")).map(Folded::value) );
+        assert_eq!( Some(&"delta"), tree.ceiling(&Folded::new("D")).map(Folded::value) );
+        assert_eq!( Some(&"ALPHA"), tree.least().map(Folded::value) );
+        assert_eq!( Some(&"Echo"), tree.greatest().map(Folded::value) );
+    }
+
+    #[test]
+    fn lookups_by_another_spelling() {
+        let mut tree = Javlt::case_insensitive([String::from("Rust"), String::from("Go")]);
+        assert!( tree.contains(&Folded::new(String::from("RUST"))) );
+        assert!( tree.contains(&Folded::new(String::from("go"))) );
+        assert!( !tree.contains(&Folded::new(String::from("rusty"))) );
+        assert_eq!( Some("Go"), tree.get(&Folded::new(String::from("gO"))).map(|word| word.value().as_str()) );
+        // the first spelling stays, and a differently-cased key removes it
+        assert!( tree.add(Folded::new(String::from("rust"))).is_err() );
+        assert!( tree.drop_value(Folded::new(String::from("rUsT"))).is_ok() );
+        assert!( !tree.contains(&Folded::new(String::from("Rust"))) );
+        assert_eq!( 1, tree.get_size() );
+    }
+
+}
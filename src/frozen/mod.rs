@@ -0,0 +1,142 @@
+use std::fmt;
+
+
+
+/// # Frozen Set
+/// 
+/// A read-only ordered set, built from any of the trees with `freeze()`.  The values are stored
+/// in a single array in "Eytzinger" order (the order of a breadth-first walk over a perfectly 
+/// balanced BST), so a lookup is a branch-free walk down an implicit tree with no pointers to chase
+/// and good cache behavior near the top of the tree.
+///
+///     use jtree::Javlt;
+/// 
+///     let my_tree = Javlt::from_collection([5,1,4,2,3]);
+///     let frozen = my_tree.freeze();
+///     assert_eq!( 5, frozen.get_size() );
+///     assert!( frozen.contains(&4) );
+///     assert!( !frozen.contains(&6) );
+///     assert_eq!( vec!(1,2,3,4,5), frozen.as_vec() );
+/// 
+/// Values from a tree that allows duplicates (`Jblst`) are de-duplicated when frozen.
+pub struct FrozenSet<T: PartialEq + PartialOrd + Clone> {
+    values: Vec<T>, // values[k-1] holds the k-th node of the implicit tree, counting from 1
+}
+
+impl <T: PartialEq + PartialOrd + Clone> FrozenSet<T> {
+
+    /// Build the set from a Vec of values that is already sorted from least to greatest.
+    /// Duplicate values are skipped.
+    pub(crate) fn from_sorted_vec(mut sorted_values: Vec<T>) -> Self {
+        sorted_values.dedup();
+        let mut slots: Vec<Option<T>> = vec![None; sorted_values.len()];
+        let mut sorted_iter = sorted_values.into_iter();
+        Self::fill(&mut slots, &mut sorted_iter, 1);
+        Self {
+            values: slots.into_iter().map(|slot| slot.unwrap()).collect(),
+        }
+    }
+
+    /// Recursively place the sorted values into the implicit tree by an in-order walk over its slots.
+    fn fill<I: Iterator<Item = T>>(slots: &mut Vec<Option<T>>, sorted_iter: &mut I, k: usize) {
+        if k > slots.len() {
+            return;
+        }
+        Self::fill(slots, sorted_iter, 2 * k);
+        slots[k - 1] = sorted_iter.next();
+        Self::fill(slots, sorted_iter, 2 * k + 1);
+    }
+
+    /// Get the number of values in the set
+    pub fn get_size(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns true if the set holds no values
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Returns true if the value is a member of the set
+    pub fn contains(&self, value: &T) -> bool {
+        let size = self.values.len();
+        let mut k = 1;
+        while k <= size {
+            // go left if the node is >= the value, right if it's smaller, without a branch
+            k = 2 * k + usize::from(self.values[k - 1] < *value);
+        }
+        // undo the trailing right-turns (and the final left-turn) to find the last node that was >= the value
+        k >>= k.trailing_ones() + 1;
+        return k != 0 && self.values[k - 1] == *value;
+    }
+
+    /// Returns all the values in the set as an ordered Vec from least to greatest.
+    pub fn as_vec(&self) -> Vec<T> {
+        let mut vals = Vec::with_capacity(self.values.len());
+        self.collect_values(1, &mut vals);
+        vals
+    }
+
+    /// Recursively add values to the borrowed vector, traversing the implicit tree from left to right.
+    fn collect_values(&self, k: usize, value_vector: &mut Vec<T>) {
+        if k > self.values.len() {
+            return;
+        }
+        self.collect_values(2 * k, value_vector);
+        value_vector.push(self.values[k - 1].clone());
+        self.collect_values(2 * k + 1, value_vector);
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd + Clone + std::fmt::Debug> fmt::Debug for FrozenSet<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("FrozenSet")
+            .field("size", &self.get_size())
+            .field("values", &self.as_vec())
+            .finish()
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::{Javlt, Jblst, Jbst};
+
+    #[test]
+    fn freeze_each_tree_type() {
+        let values = [8,6,7,5,3,0,9,12,11];
+        let frozen_sets = [
+            Jbst::from_collection(values).freeze(),
+            Jblst::from_collection(values).freeze(),
+            Javlt::from_collection(values).freeze(),
+        ];
+        for frozen in frozen_sets {
+            assert_eq!( vec!(0,3,5,6,7,8,9,11,12), frozen.as_vec() );
+            for value in values {
+                assert!( frozen.contains(&value) );
+            }
+            for value in [-1,1,2,4,10,13] {
+                assert!( !frozen.contains(&value) );
+            }
+        }
+    }
+
+    #[test]
+    fn freeze_with_duplicates() {
+        let frozen = Jblst::from_collection(['b','a','b','c','a']).freeze();
+        assert_eq!( 3, frozen.get_size() );
+        assert_eq!( vec!('a','b','c'), frozen.as_vec() );
+        assert!( frozen.contains(&'c') );
+    }
+
+    #[test]
+    fn freeze_empty_tree() {
+        let frozen = Javlt::<u32>::new().freeze();
+        assert!( frozen.is_empty() );
+        assert!( !frozen.contains(&1) );
+        assert_eq!( Vec::<u32>::new(), frozen.as_vec() );
+    }
+
+}
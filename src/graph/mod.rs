@@ -0,0 +1,21 @@
+//! With the `petgraph` feature, the trees can be exported as a petgraph `DiGraph` with `to_petgraph()`, to run
+//! graph algorithms on their structure or draw them with petgraph's `Dot` output.  Each node of the tree becomes
+//! a node of the graph weighted with (a copy of) its value, with the root added first (so its index is 0), and
+//! each edge runs from a parent to a child, weighted with which branch the child is on.
+//!
+//!     use jtree::Javlt;
+//!     use jtree::graph::Branch;
+//!     use petgraph::graph::NodeIndex;
+//!
+//!     let graph = Javlt::from_collection([1,2,3]).to_petgraph();
+//!     assert_eq!( 3, graph.node_count() );
+//!     assert_eq!( 2, graph[NodeIndex::new(0)] ); // the root
+//!     let edge = graph.find_edge(NodeIndex::new(0), NodeIndex::new(1)).unwrap();
+//!     assert_eq!( Branch::Left, graph[edge] );
+
+/// Which of its parent's branches a node hangs from; the weight of each edge in the graph.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Branch {
+    Left,
+    Right,
+}
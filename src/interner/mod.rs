@@ -0,0 +1,177 @@
+use std::{cmp::Ordering, fmt, sync::Arc};
+
+use crate::javlt::Javlt;
+
+
+
+/// # Interner
+///
+/// Keeps one copy of each distinct string it's given, and hands out a small `Symbol` for each: the same
+/// string always gets the same symbol, so strings can be compared (or hashed, or stored) as symbols, and
+/// turned back into strings when they're needed.  The strings are kept in a `Javlt`, so they can also be
+/// listed in order.
+///
+///     use jtree::interner::Interner;
+///
+///     let mut names = Interner::new();
+///     let x = names.intern("x");
+///     let y = names.intern("y");
+///     assert_eq!( x, names.intern("x") );
+///     assert_ne!( x, y );
+///     assert_eq!( "y", names.resolve(y) );
+///     assert_eq!( None, names.get("z") );
+pub struct Interner {
+    names: Javlt<Entry>, // ordered by name
+    symbols: Vec<Arc<str>>, // the names, indexed by symbol
+}
+
+/// Stands for a string interned by an `Interner`.  Symbols are numbered from 0 in the order their strings were
+/// first interned, so they're only meaningful to the interner that made them.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug)]
+pub struct Symbol(u32);
+
+impl Symbol {
+
+    /// The symbol's number, which is its string's position in the order they were interned.
+    pub fn as_u32(self) -> u32 {
+        self.0
+    }
+
+}
+
+/// An interned name and its symbol, compared by the name alone.
+struct Entry {
+    name: Arc<str>,
+    symbol: Symbol,
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.name.partial_cmp(&other.name)
+    }
+}
+
+impl Interner {
+
+    /// Create a new interner with no strings
+    pub fn new() -> Self {
+        Self {
+            names: Javlt::new(),
+            symbols: Vec::new(),
+        }
+    }
+
+    /// Returns the string's symbol, interning the string first if it's new.  Panics if the interner already
+    /// holds as many strings as a symbol can count (`u32::MAX`).
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(symbol) = self.get(name) {
+            return symbol;
+        }
+        let symbol = match u32::try_from(self.symbols.len()) {
+            Ok(number) if number < u32::MAX => Symbol(number),
+            _ => panic!("the interner already holds as many strings as it can count"),
+        };
+        let name: Arc<str> = Arc::from(name);
+        self.symbols.push(Arc::clone(&name));
+        let _ = self.names.add(Entry { name, symbol });
+        symbol
+    }
+
+    /// Returns the string's symbol, if it has been interned, without interning it.
+    pub fn get(&self, name: &str) -> Option<Symbol> {
+        self.names.get_by(|entry| Some((*entry.name).cmp(name))).map(|entry| entry.symbol)
+    }
+
+    /// Returns the string a symbol stands for.  Panics if the symbol was made by a different interner (one
+    /// that has interned more strings than this one).
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        return match self.symbols.get(symbol.0 as usize) {
+            Some(name) => name,
+            None => panic!("symbol {} wasn't made by this interner", symbol.0),
+        };
+    }
+
+    /// Returns the string a symbol stands for, or None if this interner never made the symbol.
+    pub fn try_resolve(&self, symbol: Symbol) -> Option<&str> {
+        self.symbols.get(symbol.0 as usize).map(|name| &**name)
+    }
+
+    /// Get the number of strings interned
+    pub fn get_size(&self) -> u32 {
+        self.names.get_size()
+    }
+
+    /// Returns each interned string with its symbol, in order of the strings (not of the symbols).
+    ///
+    ///     use jtree::interner::Interner;
+    ///
+    ///     let mut words = Interner::new();
+    ///     for word in ["pear", "apple", "fig", "apple"] {
+    ///         words.intern(word);
+    ///     }
+    ///     let listed: Vec<(&str, u32)> = words.iter().map(|(name, symbol)| (name, symbol.as_u32())).collect();
+    ///     assert_eq!( vec!(("apple",1), ("fig",2), ("pear",0)), listed );
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Symbol)> {
+        self.names.iter().map(|entry| (&*entry.name, entry.symbol))
+    }
+
+}
+
+impl Default for Interner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Interner {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_map().entries(self.iter().map(|(name, symbol)| (name, symbol.0))).finish()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symbols_are_stable() {
+        let mut interner = Interner::new();
+        let words: Vec<String> = (0..500).map(|n| format!("word{}", n * 37 % 500)).collect();
+        let symbols: Vec<Symbol> = words.iter().map(|word| interner.intern(word)).collect();
+        assert_eq!( 500, interner.get_size() );
+        assert!( symbols.iter().enumerate().all(|(index, symbol)| symbol.as_u32() == index as u32) );
+        // interning again, or looking up, gives the same symbols, whatever else was interned in between
+        interner.intern("");
+        for (word, symbol) in words.iter().zip(&symbols) {
+            assert_eq!( *symbol, interner.intern(word) );
+            assert_eq!( Some(*symbol), interner.get(word) );
+            assert_eq!( word, interner.resolve(*symbol) );
+        }
+        assert_eq!( Some(""), interner.try_resolve(Symbol(500)) );
+        assert_eq!( None, interner.try_resolve(Symbol(501)) );
+        let listed: Vec<&str> = interner.iter().map(|(name, _)| name).collect();
+        let mut sorted = listed.clone();
+        sorted.sort();
+        assert_eq!( sorted, listed );
+        assert_eq!( 501, listed.len() );
+    }
+
+    #[test]
+    #[should_panic(expected = "wasn't made by this interner")]
+    fn foreign_symbols_are_refused() {
+        let mut big = Interner::new();
+        big.intern("a");
+        let b = big.intern("b");
+        let mut small = Interner::new();
+        small.intern("a");
+        small.resolve(b);
+    }
+
+}
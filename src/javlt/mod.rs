@@ -1,6 +1,22 @@
-use std::{cmp::max, fmt, i32::MAX};
-
-use crate::errors::TreeError;
+use std::{cmp::{max, min, Ordering}, collections::VecDeque, fmt, fs, hash::{Hash, Hasher}, iter::FusedIterator, ops::{ControlFlow, RangeBounds}, path::Path};
+
+use crate::composite::HasPrefix;
+use crate::bounds::{queue_within, SearchNode};
+use crate::errors::{OccupiedError, TreeError};
+use crate::reports::{AddReport, TreeDiff, TreeStats};
+use crate::frozen::FrozenSet;
+use crate::jblst::Jblst;
+#[cfg(feature = "petgraph")]
+use petgraph::graph::{DiGraph, NodeIndex};
+#[cfg(feature = "petgraph")]
+use crate::graph::Branch;
+use crate::merge::{Duplicates, MergeIter};
+use crate::visit::Traversal;
+#[cfg(feature = "rayon")]
+use rayon::{iter::{IntoParallelRefIterator, ParallelIterator}, slice::{ParallelSlice, ParallelSliceMut}};
+use crate::codec::{self, Codec, Reader, ShapeReader, ShapeWriter, HAS_LEFT, HAS_RIGHT};
+#[cfg(feature = "metrics")]
+use crate::metrics;
 
 /// # Joe's AVL Tree
 /// 
@@ -11,18 +27,41 @@ use crate::errors::TreeError;
 /// order in which values are inserted.  A self-balancing structure modifies its
 /// structure when inserts or deletions would make it lopsided.  This guarantees
 /// that lookups will remain O(log(n)) complexity.
-pub struct Javlt<T: PartialEq + PartialOrd + Clone> {
+///
+/// The values don't have to be `Clone`: rebalancing moves nodes rather than copying values, so a tree can
+/// hold values that own resources, such as files.  Only the methods that hand back copies of values (like
+/// `as_vec` and `least_value`) need `Clone`.
+///
+/// `least` and `greatest` borrow the extreme values by walking down the tree's left or right edge, which makes
+/// no comparisons and copies nothing.  That's O(log(n)) steps, not O(1): the tree doesn't cache its extremes,
+/// because a cached reference would have to be a raw pointer that every rotation and removal could leave
+/// dangling, and a cached copy would need `Clone`, which the tree doesn't require.
+///
+/// With the `tracing` feature, `add` and `drop_value` run in trace-level spans, each rotation is a trace-level
+/// event, a change that needed rotations ends with a debug-level event counting them (one change can set off
+/// rotations all the way up to the root), and `partition` and `append` report the split or merge at debug
+/// level.  The events describe the tree's shape (heights and sizes) but not its values, which don't have to be
+/// `Debug`; for values that are, `trace_values` has the rotations reported at debug level with the values at
+/// the top of the rotated subtrees, and the rebalancing events with the root's.
+pub struct Javlt<T: PartialEq + PartialOrd> {
     size: u32,
     root: Option<Box<Node<T>>>,
+    rotations: Rotations<T>,
 }
 
-impl <T: PartialEq + PartialOrd + Clone> Javlt<T> {
+impl <T: PartialEq + PartialOrd> Javlt<T> {
 
     /// Create a new tree with no data
     pub fn new() -> Self {
+        Self::from_root(None, 0)
+    }
+
+    /// A tree with the given nodes, which hold `size` values.
+    fn from_root(root: Option<Box<Node<T>>>, size: u32) -> Self {
         Self {
-            root: None,
-            size: 0,
+            root,
+            size,
+            rotations: Rotations::new(),
         }
     }
 
@@ -34,26 +73,93 @@ impl <T: PartialEq + PartialOrd + Clone> Javlt<T> {
         new_tree
     }
 
+    /// Create a new tree from a collection, like `from_collection`, but for input that shouldn't hold duplicates:
+    /// the first duplicate is returned in a TreeError::DuplicateAt (with its index in the collection) instead.
+    ///
+    ///     use jtree::Javlt;
+    ///     use jtree::errors::TreeError;
+    ///
+    ///     assert_eq!( vec!(1,2,3), Javlt::try_from_collection([3,1,2]).unwrap().as_vec() );
+    ///     assert_eq!( Err(TreeError::DuplicateAt { index: 3, value: 1 }), Javlt::try_from_collection([3,1,2,1]).map(|tree| tree.as_vec()) );
+    pub fn try_from_collection<U: IntoIterator<Item = T>>(collection: U) -> Result<Self,TreeError<T>> {
+        let mut new_tree = Self::new();
+        new_tree.add_all_strict(collection)?;
+        Ok(new_tree)
+    }
+
     /// Insert a value
-    pub fn add(&mut self, value: T) -> Result<(),TreeError> {
-        match &mut self.root {
-            None => self.root = Some(Box::new(Node::new(value))),
-            Some(branch) => branch.add(value)?, // TODO: handle errors if any are possible
+    pub fn add(&mut self, value: T) -> Result<(),TreeError<T>> {
+        if self.size == u32::MAX {
+            return Err(TreeError::CapacityExceeded { value });
         }
+        return match self.place(value) {
+            (_, None) => Ok(()),
+            (_, Some(value)) => Err(TreeError::ValueAlreadyStored { value }),
+        };
+    }
+
+    /// Add the value unless an equal value is already stored, in a single search of the tree, and return the
+    /// route to the stored value either way (so it can be found again after rebalancing without comparing
+    /// values), along with the value given back if it wasn't added.  The caller makes sure there's room.
+    fn place<V: Arriving<T>>(&mut self, value: V) -> (Route, Option<V>) {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("Javlt::add").entered();
+        #[cfg(any(feature = "tracing", feature = "metrics"))]
+        let rotations_before = self.rotations.count;
+        #[cfg(feature = "metrics")]
+        metrics::start_operation();
+        let route = match &mut self.root {
+            None => {
+                self.root = Some(Box::new(Node::new(value.make())));
+                Route::default()
+            },
+            Some(branch) => match branch.add(value, &mut self.rotations) {
+                Ok(route) => route,
+                Err(refused) => return (refused.0, Some(refused.1)),
+            },
+        };
         self.size += 1;
-        Ok(())
+        #[cfg(any(feature = "tracing", feature = "metrics"))]
+        self.report_rebalancing("add", rotations_before);
+        #[cfg(feature = "paranoid")]
+        self.paranoid_check("add");
+        (route, None)
+    }
+
+    /// The stored value at the end of a route returned by `place`.
+    fn value_at(&self, route: Route) -> &T {
+        self.root.as_deref().unwrap().value_at(route)
     }
 
     /// Alias for add_all_skipping_duplicates. Adds all members of a collection (vector, array, or whatever) to the tree.
-    pub fn add_all<U: IntoIterator<Item = T>>(&mut self, collection: U) -> Result<(),TreeError> {
+    pub fn add_all<U: IntoIterator<Item = T>>(&mut self, collection: U) -> Result<AddReport,TreeError<T>> {
         self.add_all_skipping_duplicates(collection)
     }
 
     /// Adds all members of a collection (vector, array, or whatever) to the tree,
     /// skipping over any that would be duplicates, so no error will stop the batch.
-    pub fn add_all_skipping_duplicates<U: IntoIterator<Item = T>>(&mut self, collection: U) -> Result<(),TreeError> {
+    /// The returned report says how many values were inserted and how many were skipped.
+    pub fn add_all_skipping_duplicates<U: IntoIterator<Item = T>>(&mut self, collection: U) -> Result<AddReport,TreeError<T>> {
+        let mut report = AddReport::default();
         for elem in collection.into_iter() {
-            let _ = self.add(elem);
+            match self.add(elem) {
+                Ok(()) => report.inserted += 1,
+                Err(_) => report.skipped += 1,
+            }
+        }
+        Ok(report)
+    }
+
+    /// Adds all members of a collection (vector, array, or whatever) to the tree, stopping at the first
+    /// duplicate.  Unlike `try_add_all`, the values added before it stay in the tree; the TreeError::DuplicateAt
+    /// error holds the index and value of the offending element, and the rest of the collection isn't added.
+    pub fn add_all_strict<U: IntoIterator<Item = T>>(&mut self, collection: U) -> Result<(),TreeError<T>> {
+        for (index, elem) in collection.into_iter().enumerate() {
+            match self.add(elem) {
+                Ok(()) => {},
+                Err(TreeError::ValueAlreadyStored { value }) => return Err(TreeError::DuplicateAt { index, value }),
+                Err(other) => return Err(other),
+            }
         }
         Ok(())
     }
@@ -63,6 +169,592 @@ impl <T: PartialEq + PartialOrd + Clone> Javlt<T> {
         self.size
     }
 
+    /// Returns true if the value is currently a member of the tree
+    pub fn contains(&self, value: &T) -> bool {
+        #[cfg(feature = "metrics")]
+        metrics::start_operation();
+        return match &self.root {
+            None => false,
+            Some(branch) => branch.contains(value), 
+        };
+    }
+
+    /// Returns the stored value equal to `value`, if there is one.  This is the tree's own copy, which
+    /// matters for values that compare equal without being identical.
+    pub fn get(&self, value: &T) -> Option<&T> {
+        return match &self.root {
+            None => None,
+            Some(branch) => branch.get(value),
+        };
+    }
+
+    /// Like `get`, but searches with `compare` in place of the values' ordering, for looking values up by a key
+    /// that isn't a whole value.  `compare` says how each stored value is ordered relative to the one sought,
+    /// and must agree with the values' own ordering; if it can't say (returning None), nothing is found.
+    pub(crate) fn get_by<F: FnMut(&T) -> Option<Ordering>>(&self, mut compare: F) -> Option<&T> {
+        let mut link = &self.root;
+        while let Some(node) = link {
+            link = match compare(&node.value)? {
+                Ordering::Equal => return Some(&node.value),
+                Ordering::Greater => &node.left,
+                Ordering::Less => &node.right,
+            };
+        }
+        None
+    }
+
+    /// Returns the stored value equal to `value`, adding `value` first if there's none.  Handy for interning
+    /// values.  It takes a single search of the tree, as `try_insert` does.  Panics if the value is new and the
+    /// tree already holds as many values as it can count (`u32::MAX`).
+    ///
+    ///     use jtree::Javlt;
+    ///
+    ///     let mut names = Javlt::new();
+    ///     assert_eq!( "ada", names.get_or_insert(String::from("ada")) );
+    ///     assert_eq!( "ada", names.get_or_insert(String::from("ada")) );
+    ///     assert_eq!( 1, names.get_size() );
+    pub fn get_or_insert(&mut self, value: T) -> &T {
+        assert!( self.size < u32::MAX || self.contains(&value), "the tree already holds as many values as it can count" );
+        let (route, _) = self.place(value);
+        self.value_at(route)
+    }
+
+    /// Like `get_or_insert`, but looks for a value equal to `probe`, and only calls `make` to create the value
+    /// to add if there's none, once the search has found its place.  Panics if `make` returns a value that
+    /// doesn't equal the probe.
+    pub fn get_or_insert_with<F: FnOnce() -> T>(&mut self, probe: &T, make: F) -> &T {
+        assert!( self.size < u32::MAX || self.contains(probe), "the tree already holds as many values as it can count" );
+        let made = Made { probe, make: || {
+            let value = make();
+            assert!( value == *probe, "get_or_insert_with: the value made must equal the probe" );
+            value
+        } };
+        let (route, _) = self.place(made);
+        self.value_at(route)
+    }
+
+    /// Insert a value and return the tree's copy of it, unless an equal value is already stored, in which case
+    /// an OccupiedError holds both the stored value and the one given back.  It takes a single search of the
+    /// tree: adding a value notes the route to it, which rebalancing adjusts as it goes.  Panics if the value is
+    /// new and the tree already holds as many values as it can count (`u32::MAX`).
+    ///
+    ///     use jtree::Javlt;
+    ///
+    ///     let mut names = Javlt::new();
+    ///     assert_eq!( "ada", names.try_insert(String::from("ada")).unwrap() );
+    ///     let occupied = names.try_insert(String::from("ada")).unwrap_err();
+    ///     assert_eq!( ("ada", String::from("ada")), (occupied.existing.as_str(), occupied.value) );
+    pub fn try_insert(&mut self, value: T) -> Result<&T,OccupiedError<'_, T>> {
+        assert!( self.size < u32::MAX || self.contains(&value), "the tree already holds as many values as it can count" );
+        let (route, refused) = self.place(value);
+        let stored = self.value_at(route);
+        return match refused {
+            None => Ok(stored),
+            Some(value) => Err(OccupiedError { existing: stored, value }),
+        };
+    }
+
+    /// Insert a value, replacing any equal value already stored, like `BTreeMap::insert` does for keys.
+    /// Returns the value that was replaced, or None if the value is new to the tree.  Unlike `add`, this doesn't
+    /// return errors; it panics only if the value is new and the tree already holds as many values as it can
+    /// count (`u32::MAX`).  Replacing a value doesn't change the tree's shape, so no rebalancing is needed.
+    ///
+    ///     use jtree::Javlt;
+    ///
+    ///     let mut tree = Javlt::from_collection([1,2,3]);
+    ///     assert_eq!( Some(2), tree.insert(2) );
+    ///     assert_eq!( None, tree.insert(4) );
+    ///     assert_eq!( 4, tree.get_size() );
+    pub fn insert(&mut self, value: T) -> Option<T> {
+        let value = match &mut self.root {
+            None => value,
+            Some(branch) => match branch.replace(value) {
+                Ok(displaced) => return Some(displaced),
+                Err(value) => value,
+            },
+        };
+        assert!( self.add(value).is_ok(), "the tree already holds as many values as it can count" );
+        None
+    }
+
+    /// Returns references to all the values in the tree as an ordered Vec from least to greatest, for when
+    /// cloning them with `as_vec` would be wasteful.
+    ///
+    ///     use jtree::Javlt;
+    ///
+    ///     let my_tree = Javlt::from_collection([2,1,3]);
+    ///     assert_eq!( vec!(&1,&2,&3), my_tree.values() );
+    pub fn values(&self) -> Vec<&T> {
+        let mut vals = Vec::with_capacity(self.size as usize);
+        vals.extend(self.iter_values());
+        vals
+    }
+
+    /// Consume the tree, moving its values into a Vec from least to greatest.  Unlike `as_vec`, nothing is cloned.
+    pub fn into_sorted_vec(self) -> Vec<T> {
+        self.into_iter().collect()
+    }
+
+    /// Call `f` on each value in order, from least to greatest, without copying the values into a Vec.
+    ///
+    ///     use jtree::Javlt;
+    ///
+    ///     let my_tree = Javlt::from_collection([2,1,3]);
+    ///     let mut total = 0;
+    ///     my_tree.visit_in_order(|value| total += value);
+    ///     assert_eq!( 6, total );
+    pub fn visit_in_order<F: FnMut(&T)>(&self, mut f: F) {
+        let _ = self.try_visit(Traversal::In, |value| {
+            f(value);
+            ControlFlow::<()>::Continue(())
+        });
+    }
+
+    /// Call `f` on each value in pre-order: each node's value before the values in its left subtree, then its right subtree.
+    pub fn visit_pre_order<F: FnMut(&T)>(&self, mut f: F) {
+        let _ = self.try_visit(Traversal::Pre, |value| {
+            f(value);
+            ControlFlow::<()>::Continue(())
+        });
+    }
+
+    /// Call `f` on each value in post-order: the values in each node's left subtree, then its right subtree, then its own value.
+    pub fn visit_post_order<F: FnMut(&T)>(&self, mut f: F) {
+        let _ = self.try_visit(Traversal::Post, |value| {
+            f(value);
+            ControlFlow::<()>::Continue(())
+        });
+    }
+
+    /// Like `visit_in_order`, but `f` can stop the visit early by returning `ControlFlow::Break`, which is passed
+    /// back to the caller.
+    ///
+    ///     use std::ops::ControlFlow;
+    ///     use jtree::Javlt;
+    ///
+    ///     let my_tree = Javlt::from_collection([5,1,8,3]);
+    ///     let first_over_2 = my_tree.try_visit_in_order(|value| match *value > 2 {
+    ///         true => ControlFlow::Break(*value),
+    ///         false => ControlFlow::Continue(()),
+    ///     });
+    ///     assert_eq!( ControlFlow::Break(3), first_over_2 );
+    pub fn try_visit_in_order<B, F: FnMut(&T) -> ControlFlow<B>>(&self, f: F) -> ControlFlow<B> {
+        self.try_visit(Traversal::In, f)
+    }
+
+    /// Like `visit_pre_order`, but `f` can stop the visit early by returning `ControlFlow::Break`.
+    pub fn try_visit_pre_order<B, F: FnMut(&T) -> ControlFlow<B>>(&self, f: F) -> ControlFlow<B> {
+        self.try_visit(Traversal::Pre, f)
+    }
+
+    /// Like `visit_post_order`, but `f` can stop the visit early by returning `ControlFlow::Break`.
+    pub fn try_visit_post_order<B, F: FnMut(&T) -> ControlFlow<B>>(&self, f: F) -> ControlFlow<B> {
+        self.try_visit(Traversal::Post, f)
+    }
+
+    fn try_visit<B, F: FnMut(&T) -> ControlFlow<B>>(&self, order: Traversal, mut f: F) -> ControlFlow<B> {
+        return match &self.root {
+            None => ControlFlow::Continue(()),
+            Some(branch) => branch.try_visit(order, &mut f),
+        };
+    }
+
+    /// Returns the values stored in leaf nodes (those with no children), in order from least to greatest.
+    ///
+    ///     use jtree::Javlt;
+    ///
+    ///     let my_tree = Javlt::from_collection(1..=7);
+    ///     assert_eq!( vec!(&1,&3,&5,&7), my_tree.leaves().collect::<Vec<_>>() );
+    pub fn leaves(&self) -> impl Iterator<Item = &T> {
+        Nodes::new(&self.root).filter(|(node, _)| node.is_leaf()).map(|(node, _)| &node.value)
+    }
+
+    /// Returns each value with its depth in the tree (the root is at depth 0), in order from least to greatest.
+    ///
+    ///     use jtree::Javlt;
+    ///
+    ///     let my_tree = Javlt::from_collection([1,2,3]);
+    ///     assert_eq!( vec!((&1,1), (&2,0), (&3,1)), my_tree.iter_with_depth().collect::<Vec<_>>() );
+    pub fn iter_with_depth(&self) -> impl Iterator<Item = (&T, usize)> {
+        Nodes::new(&self.root).map(|(node, depth)| (&node.value, depth))
+    }
+
+    /// Returns an iterator over the values in order, from least to greatest, which can be `split` into two
+    /// iterators over the earlier and later values: handy for sharing a traversal out among threads.
+    ///
+    ///     use jtree::Javlt;
+    ///
+    ///     let my_tree = Javlt::from_collection(1..=100);
+    ///     let (front, back) = my_tree.iter().split();
+    ///     let (front_sum, back_sum) = std::thread::scope(|scope| {
+    ///         let front_sum = scope.spawn(|| front.sum::<u32>());
+    ///         (front_sum.join().unwrap(), back.sum::<u32>())
+    ///     });
+    ///     assert_eq!( 5050, front_sum + back_sum );
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { nodes: Nodes::new(&self.root) }
+    }
+
+    /// Returns an iterator over the values within a range (such as `2..5`, `..=7` or `(Bound::Excluded(1),
+    /// Bound::Unbounded)`), in order, from either end, in O(log(n)) time plus O(1) for each value yielded: only
+    /// the paths down to the range's bounds are searched to find them.  It can be `split` like `iter`'s.
+    ///
+    ///     use std::ops::Bound;
+    ///     use jtree::Javlt;
+    ///
+    ///     let my_tree = Javlt::from_collection(1..=100);
+    ///     assert_eq!( vec!(&40,&41,&42), my_tree.range(40..43).collect::<Vec<_>>() );
+    ///     assert_eq!( Some(&99), my_tree.range((Bound::Excluded(97), Bound::Excluded(100))).next_back() );
+    ///     assert_eq!( 0, my_tree.range(200..).count() );
+    pub fn range<R: RangeBounds<T>>(&self, range: R) -> Iter<'_, T> {
+        Iter { nodes: Nodes::within(&self.root, &range) }
+    }
+
+    /// Returns the values of this tree and another together, in order from least to greatest, without copying
+    /// them or sorting them again.  `duplicates` says whether a value in both trees is yielded twice or once.
+    ///
+    ///     use jtree::Javlt;
+    ///     use jtree::merge::Duplicates;
+    ///
+    ///     let tree_a = Javlt::from_collection([1,3,5]);
+    ///     let tree_b = Javlt::from_collection([2,3,4]);
+    ///     assert_eq!( vec!(&1,&2,&3,&4,&5), tree_a.merge_iter(&tree_b, Duplicates::Union).collect::<Vec<_>>() );
+    pub fn merge_iter<'a>(&'a self, other: &'a Self, duplicates: Duplicates) -> impl Iterator<Item = &'a T> {
+        MergeIter::new(self.iter_values(), other.iter_values(), duplicates)
+    }
+
+    /// Returns a cursor positioned at the value, or if it's not in the tree, at the least value greater than it
+    /// (or the ghost position past the end, if there's none).  From there, the cursor can walk to neighbouring values
+    /// in either direction.
+    ///
+    ///     use jtree::Javlt;
+    ///
+    ///     let my_tree = Javlt::from_collection([10,20,30,40]);
+    ///     let mut cursor = my_tree.cursor_at(&25);
+    ///     assert_eq!( Some(&30), cursor.peek() );
+    ///     assert_eq!( Some(&40), cursor.next() );
+    ///     assert_eq!( None, cursor.next() ); // past the end
+    ///     assert_eq!( Some(&40), cursor.prev() );
+    pub fn cursor_at(&self, value: &T) -> Cursor<'_, T> {
+        let mut cursor = Cursor::new(&self.root);
+        cursor.seek(value);
+        cursor
+    }
+
+    /// Returns the greatest value in the tree that's less than or equal to `value`, if there is one.
+    ///
+    ///     use jtree::Javlt;
+    ///
+    ///     let my_tree = Javlt::from_collection([10,20,30]);
+    ///     assert_eq!( Some(&20), my_tree.floor(&25) );
+    ///     assert_eq!( Some(&20), my_tree.floor(&20) );
+    ///     assert_eq!( None, my_tree.floor(&5) );
+    pub fn floor(&self, value: &T) -> Option<&T> {
+        let mut cursor = self.cursor_at(value);
+        return match cursor.peek() {
+            Some(found) if found == value => Some(found),
+            _ => cursor.prev(),
+        };
+    }
+
+    /// Returns the least value in the tree that's greater than or equal to `value`, if there is one.
+    ///
+    ///     use jtree::Javlt;
+    ///
+    ///     let my_tree = Javlt::from_collection([10,20,30]);
+    ///     assert_eq!( Some(&30), my_tree.ceiling(&25) );
+    ///     assert_eq!( None, my_tree.ceiling(&35) );
+    pub fn ceiling(&self, value: &T) -> Option<&T> {
+        self.cursor_at(value).peek()
+    }
+
+    /// Returns a cursor positioned at the least value (or the ghost position, if the tree is empty).
+    pub fn cursor_front(&self) -> Cursor<'_, T> {
+        Cursor::front(&self.root)
+    }
+
+    /// Returns a cursor positioned at the greatest value (or the ghost position, if the tree is empty).
+    pub fn cursor_back(&self) -> Cursor<'_, T> {
+        let mut cursor = Cursor::new(&self.root);
+        cursor.prev();
+        cursor
+    }
+
+    /// Returns the values that start with the prefix, such as all of the `(priority, timestamp, id)` tuples
+    /// with a given priority, in order from least to greatest (see the `composite` module).  Finding the first
+    /// takes O(log(n)), and the rest follow in order.
+    pub fn range_by_prefix<'a, P>(&'a self, prefix: &'a P) -> impl Iterator<Item = &'a T> where T: HasPrefix<P> {
+        let mut cursor = Cursor::new(&self.root);
+        cursor.seek_by(|value| value.cmp_prefix(prefix) != Some(Ordering::Less));
+        let first = cursor.peek();
+        first.into_iter().chain(cursor).take_while(move |value| value.cmp_prefix(prefix) == Some(Ordering::Equal))
+    }
+
+    /// Take all of the values out of the tree, in order from least to greatest, leaving it empty (and ready
+    /// to be reused) without copying the values.  The tree is emptied straight away, so any values
+    /// the iterator isn't asked for are dropped with it.
+    ///
+    ///     use jtree::Javlt;
+    ///
+    ///     let mut my_tree = Javlt::from_collection([2,1,3]);
+    ///     assert_eq!( vec!(1,2,3), my_tree.drain().collect::<Vec<_>>() );
+    ///     assert_eq!( 0, my_tree.get_size() );
+    pub fn drain(&mut self) -> impl DoubleEndedIterator<Item = T> + use<T> {
+        self.take_nodes()
+    }
+
+    /// Empty the tree, handing its nodes to a `Drain` to take the values out of them, for `drain` and `into_iter`.
+    fn take_nodes(&mut self) -> Drain<T> {
+        #[cfg(feature = "metrics")]
+        metrics::record(|metrics| metrics.frees += u64::from(self.size));
+        self.size = 0;
+        Drain::new(self.root.take())
+    }
+
+    /// Build a new tree from what `f` makes of each value.  The mapped values are sorted again (the mapping may
+    /// not keep their order) and the tree is built from them in one go, rather than by adding each one.  Mapped
+    /// values that turn out equal are kept once, the first of them in the old tree's order; any that can't be
+    /// ordered even against themselves (such as a floating point NaN) are left out.
+    ///
+    ///     use jtree::Javlt;
+    ///
+    ///     let my_tree = Javlt::from_collection([-2,-1,0,1,3]);
+    ///     let squares = my_tree.map(|value| value * value);
+    ///     assert_eq!( vec!(0,1,4,9), squares.as_vec() );
+    pub fn map<U: PartialEq + PartialOrd, F: FnMut(&T) -> U>(&self, f: F) -> Javlt<U> {
+        let mut mapped: Vec<U> = self.iter_values()
+            .map(f)
+            .filter(|value| value.partial_cmp(value).is_some())
+            .collect();
+        mapped.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        mapped.dedup_by(|later, earlier| later == earlier);
+        Javlt::from_sorted_vec(mapped)
+    }
+
+    /// Consume the tree, moving each value into one of two new trees: the first holds the values for which
+    /// `f` returns true, the second the rest.  Both are built in one go from values already in order.
+    ///
+    ///     use jtree::Javlt;
+    ///
+    ///     let ids = Javlt::from_collection([4,8,15,16,23,42]);
+    ///     let (even, odd) = ids.partition(|id| id % 2 == 0);
+    ///     assert_eq!( vec!(4,8,16,42), even.into_sorted_vec() );
+    ///     assert_eq!( vec!(15,23), odd.into_sorted_vec() );
+    pub fn partition<F: FnMut(&T) -> bool>(self, f: F) -> (Self, Self) {
+        let (matching, rest): (Vec<T>, Vec<T>) = self.into_sorted_vec().into_iter().partition(f);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(matching = matching.len(), rest = rest.len(), "Javlt split in two");
+        (Self::from_sorted_vec(matching), Self::from_sorted_vec(rest))
+    }
+
+    /// Move all of `other`'s values into this tree, leaving `other` empty.  Where both trees hold equal values,
+    /// `other`'s takes the place of this tree's, as with `insert`.  The values are merged in order and the tree
+    /// rebuilt from them in one go, in O(n + m) time.
+    ///
+    ///     use jtree::Javlt;
+    ///
+    ///     let mut low = Javlt::from_collection([1,3,5]);
+    ///     let mut high = Javlt::from_collection([3,4,6]);
+    ///     low.append(&mut high);
+    ///     assert_eq!( vec!(1,3,4,5,6), low.values().into_iter().copied().collect::<Vec<_>>() );
+    ///     assert_eq!( 0, high.get_size() );
+    pub fn append(&mut self, other: &mut Self) {
+        let (appended, size) = (other.size, self.size);
+        let mut merged = Vec::with_capacity(self.size as usize + other.size as usize);
+        let (mut mine, mut theirs) = (self.drain().peekable(), other.drain().peekable());
+        loop {
+            let next = match (mine.peek(), theirs.peek()) {
+                (None, None) => break,
+                (Some(_), None) => mine.next(),
+                (Some(value), Some(other_value)) if value < other_value => mine.next(),
+                (Some(value), Some(other_value)) if value == other_value => {
+                    mine.next();
+                    theirs.next()
+                },
+                _ => theirs.next(),
+            };
+            merged.extend(next);
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(size, appended, merged = merged.len(), "Javlt merged with another");
+        #[cfg(not(feature = "tracing"))]
+        let _ = (appended, size);
+        let tree = Self::from_sorted_vec(merged);
+        self.root = tree.root;
+        self.size = tree.size;
+    }
+
+    /// If the value is in the tree, delete it.  Otherwise a TreeError::ValueNotFound will be returned.
+    pub fn drop_value(&mut self, value: T) -> Result<(),TreeError<T>> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("Javlt::drop_value").entered();
+        return match self.remove_by("drop_value", |stored| stored.partial_cmp(&value)) {
+            Some(_) => Ok(()),
+            None => Err(TreeError::ValueNotFound { value }),
+        };
+    }
+
+    /// Remove the stored value equal to `value` and return it (the tree's own copy, which matters for values
+    /// that compare equal without being identical), or None if there's none.
+    ///
+    ///     use jtree::Javlt;
+    ///
+    ///     let mut words = Javlt::from_collection([String::from("ant"), String::from("bee")]);
+    ///     assert_eq!( Some(String::from("ant")), words.take(&String::from("ant")) );
+    ///     assert_eq!( None, words.take(&String::from("ant")) );
+    ///     assert_eq!( 1, words.get_size() );
+    pub fn take(&mut self, value: &T) -> Option<T> {
+        self.take_by(|stored| stored.partial_cmp(value))
+    }
+
+    /// Like `take`, but searches with `compare` in place of the values' ordering, as `get_by` does.
+    pub(crate) fn take_by<F: FnMut(&T) -> Option<Ordering>>(&mut self, compare: F) -> Option<T> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("Javlt::take").entered();
+        self.remove_by("take", compare)
+    }
+
+    /// Remove the value that `compare` finds (see `get_by`) and return it, for `drop_value` and `take_by`, which
+    /// name the `operation` in reports.
+    fn remove_by<F: FnMut(&T) -> Option<Ordering>>(&mut self, operation: &str, mut compare: F) -> Option<T> {
+        #[cfg(any(feature = "tracing", feature = "metrics"))]
+        let rotations_before = self.rotations.count;
+        #[cfg(feature = "metrics")]
+        metrics::start_operation();
+        let (removed, new_root) = self.root.take()?.take_by(&mut compare, &mut self.rotations);
+        self.root = new_root;
+        let removed = removed?;
+        self.size -= 1;
+        #[cfg(feature = "metrics")]
+        metrics::record(|metrics| metrics.frees += 1);
+        #[cfg(any(feature = "tracing", feature = "metrics"))]
+        self.report_rebalancing(operation, rotations_before);
+        #[cfg(feature = "paranoid")]
+        self.paranoid_check(operation);
+        #[cfg(not(any(feature = "tracing", feature = "metrics", feature = "paranoid")))]
+        let _ = operation;
+        Some(removed)
+    }
+
+    /// Measure the tree's shape: node count, height, leaf depths, and the number of rotations
+    /// made to keep it balanced since it was created.
+    pub fn stats(&self) -> TreeStats {
+        let mut stats = TreeStats { rotations: Some(self.rotations.count), ..TreeStats::default() };
+        match &self.root {
+            None => (),
+            Some(branch) => branch.collect_stats(0, &mut stats),
+        }
+        stats
+    }
+
+    /// Build a perfectly balanced tree from values that are already sorted from least to greatest, with no
+    /// duplicates, moving them into place in O(n).
+    pub(crate) fn from_sorted_vec(values: Vec<T>) -> Self {
+        let size = values.len();
+        let count = u32::try_from(size).expect("more values than a tree can count");
+        let tree = Self::from_root(Node::from_sorted(&mut values.into_iter(), size), count);
+        #[cfg(feature = "paranoid")]
+        tree.paranoid_check("from_sorted_vec");
+        tree
+    }
+
+    /// Call `f` on a mutable reference to each value, from least to greatest.  Only for values whose ordering
+    /// `f` leaves alone (such as an entry compared by its key, when `f` changes its payload), so it isn't public.
+    pub(crate) fn visit_in_order_mut<F: FnMut(&mut T)>(&mut self, mut f: F) {
+        match &mut self.root {
+            None => (),
+            Some(branch) => branch.visit_in_order_mut(&mut f),
+        }
+    }
+
+    /// Returns the least value in the tree, if any, found by walking down its left edge: O(log(n)) steps, with no
+    /// comparisons.
+    ///
+    ///     use jtree::Javlt;
+    ///
+    ///     let mut tasks = Javlt::from_collection([30, 10, 20]);
+    ///     assert_eq!( Some(&10), tasks.least() );
+    ///     tasks.drop_value(10).unwrap();
+    ///     assert_eq!( Some(&20), tasks.least() );
+    pub fn least(&self) -> Option<&T> {
+        self.root.as_deref().map(Node::least)
+    }
+
+    /// Returns the greatest value in the tree, if any, found by walking down its right edge.
+    pub fn greatest(&self) -> Option<&T> {
+        self.root.as_deref().map(Node::greatest)
+    }
+
+    /// The tree's values in order, without copying them.
+    pub(crate) fn iter_values(&self) -> impl Iterator<Item = &T> {
+        Nodes::new(&self.root).map(|(node, _)| &node.value)
+    }
+
+    /// Verify the tree's internal consistency: that values are in order, every node's height is correct
+    /// and its subtrees are balanced, and the size matches the number of nodes.  Returns a
+    /// TreeError::InvariantViolation describing the first problem found, if any.
+    pub fn check_invariants(&self) -> Result<(),TreeError<T>> {
+        let nodes = match &self.root {
+            None => 0,
+            Some(branch) => branch.check_invariants(&mut String::from("root"), None, None)?,
+        };
+        if nodes != self.size {
+            return Err(TreeError::InvariantViolation(format!("size is {} but the tree holds {} values", self.size, nodes)));
+        }
+        Ok(())
+    }
+
+    /// With the `paranoid` feature, every change to the tree is followed by this check, which panics
+    /// with a description of the problem and the tree's shape if the change broke an invariant.
+    #[cfg(feature = "paranoid")]
+    fn paranoid_check(&self, operation: &str) {
+        if let Err(TreeError::InvariantViolation(problem)) = self.check_invariants() {
+            let shape = self.root.as_ref().map_or(String::from("."), |branch| branch.outline());
+            panic!("Javlt::{operation} broke the tree: {problem} (size {}, shape {shape})", self.size);
+        }
+    }
+
+    /// With the `tracing` or `metrics` feature, report the rotations a change needed to rebalance the tree, if
+    /// it needed any.
+    #[cfg(any(feature = "tracing", feature = "metrics"))]
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    fn report_rebalancing(&self, operation: &str, rotations_before: u64) {
+        let rotations = self.rotations.count - rotations_before;
+        if rotations > 0 {
+            #[cfg(feature = "tracing")]
+            {
+                let height = self.root.as_ref().map_or(0, |root| root.height);
+                match self.rotations.describe {
+                    None => tracing::debug!(operation, rotations, size = self.size, height, "Javlt rebalanced"),
+                    Some(describe) => {
+                        let root = self.root.as_deref().map(|root| describe(&root.value));
+                        tracing::debug!(operation, rotations, size = self.size, height, root, "Javlt rebalanced");
+                    },
+                }
+            }
+            #[cfg(feature = "metrics")]
+            metrics::record(|metrics| metrics.rotations += rotations);
+        }
+    }
+
+    /// With the `tracing` feature, have the tree's events show the values involved, at debug level: the value
+    /// at the top of each rotated subtree, and the root's value after a change that needed rotations.
+    ///
+    ///     use jtree::Javlt;
+    ///
+    ///     let mut my_tree = Javlt::new();
+    ///     my_tree.trace_values();
+    ///     my_tree.add_all([1,2,3]).unwrap(); // reports that 2 was rotated to the top
+    #[cfg(feature = "tracing")]
+    pub fn trace_values(&mut self) where T: fmt::Debug {
+        self.rotations.describe = Some(|value| format!("{value:?}"));
+    }
+
+}
+
+// The methods below copy values out of the tree (or, for `try_add_all` and `apply`, keep copies to undo with).
+impl <T: PartialEq + PartialOrd + Clone> Javlt<T> {
+
     /// Returns the 'value' field of the root node; used for automated tests only
     #[cfg(test)]
     fn get_root_value(&self) -> Option<T> {
@@ -72,12 +764,47 @@ impl <T: PartialEq + PartialOrd + Clone> Javlt<T> {
         }
     }
 
-    /// Returns true if the value is currently a member of the tree
-    pub fn contains(&self, value: &T) -> bool {
-        return match &self.root {
-            None => false,
-            Some(branch) => branch.contains(value), 
-        };
+    /// Adds all members of a collection (vector, array, or whatever) to the tree as a single transaction:
+    /// if any value is a duplicate, or can't be added for another reason (such as the tree being full), the values
+    /// already added from this batch are removed again so the tree holds the same values as before.  A duplicate
+    /// is reported as a TreeError::DuplicateAt, holding the index and value of the offending element; any other
+    /// error is returned as `add` gave it.
+    pub fn try_add_all<U: IntoIterator<Item = T>>(&mut self, collection: U) -> Result<(),TreeError<T>> {
+        let mut added = Vec::new();
+        for (index, elem) in collection.into_iter().enumerate() {
+            let copy = elem.clone();
+            let error = match self.add(elem) {
+                Ok(()) => {
+                    added.push(copy);
+                    continue;
+                },
+                Err(TreeError::ValueAlreadyStored { value }) => TreeError::DuplicateAt { index, value },
+                Err(other) => other,
+            };
+            for value in added.into_iter().rev() {
+                let _ = self.drop_value(value);
+            }
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    /// Build a new tree of copies of the values for which `f` returns true.  They're already in order, so the
+    /// tree is built from them in one go, rather than by adding each one.
+    ///
+    ///     use jtree::Javlt;
+    ///
+    ///     let my_tree = Javlt::from_collection(1..=10);
+    ///     assert_eq!( vec!(3,6,9), my_tree.filtered(|value| value % 3 == 0).as_vec() );
+    pub fn filtered<F: FnMut(&T) -> bool>(&self, mut f: F) -> Self {
+        let kept: Vec<T> = self.iter_values().filter(|value| f(value)).cloned().collect();
+        Self::from_sorted_vec(kept)
+    }
+
+    /// Returns true if this tree holds the same distinct values as the `Jblst`, however many times each was
+    /// added to that; the same as `jblst.distinct_eq(&javlt)`.
+    pub fn distinct_eq(&self, other: &Jblst<T>) -> bool {
+        other.distinct_eq(self)
     }
 
     /// Short for `as_vec_l_to_r`, this method returns all the values in the tree as an ordered Vec
@@ -91,92 +818,364 @@ impl <T: PartialEq + PartialOrd + Clone> Javlt<T> {
         return match &self.root {
             None => Vec::new(),
             Some(branch) => {
-                let mut vals = Vec::new();
+                let mut vals = Vec::with_capacity(self.size as usize);
                 branch.collect_values_l_to_r(&mut vals);
                 vals 
             }
         };
     }
 
-    /// Returns all the values in the tree as an ordered Vec from greatest to least  (right to left).
+    /// Returns the `n` least values in the tree (or all of them, if there are fewer), from least to greatest.
+    /// Only the part of the tree holding them is traversed, so this is much cheaper than slicing `as_vec`
+    /// when `n` is small.
+    ///
+    ///     use jtree::Javlt;
+    ///
+    ///     let my_tree = Javlt::from_collection([5,1,8,3,9]);
+    ///     assert_eq!( vec!(1,3), my_tree.first_n(2) );
+    ///     assert_eq!( vec!(8,9), my_tree.last_n(2) );
+    pub fn first_n(&self, n: usize) -> Vec<T> {
+        let mut vals = Vec::with_capacity(min(n, self.size as usize));
+        match &self.root {
+            None => (),
+            Some(branch) => branch.collect_first_n(n, &mut vals),
+        }
+        vals
+    }
+
+    /// Returns the `n` greatest values in the tree (or all of them, if there are fewer), from least to greatest,
+    /// like the end of `as_vec`; but only the part of the tree holding them is traversed.
+    pub fn last_n(&self, n: usize) -> Vec<T> {
+        let mut vals = Vec::with_capacity(min(n, self.size as usize));
+        match &self.root {
+            None => (),
+            Some(branch) => branch.collect_last_n(n, &mut vals),
+        }
+        vals.reverse();
+        vals
+    }
+
+    /// Returns all the values in the tree as an ordered Vec from greatest to least  (right to left).  To walk them
+    /// in that order without cloning them, use `iter().rev()`.
     pub fn as_vec_r_to_l(&self) -> Vec<T> {
         return match &self.root {
             None => Vec::new(),
             Some(branch) => {
-                let mut vals = Vec::new();
+                let mut vals = Vec::with_capacity(self.size as usize);
                 branch.collect_values_r_to_l(&mut vals);
                 vals 
             }
         };
     }
 
-    /// Returns the smallest/lowest value in the tree, if any.
-    pub fn least_value(&self) -> Option<T> {
-        return match &self.root {
-            None => None,
-            Some(subtree) => Some(subtree.least_value()),
+    /// Returns a cursor that can remove values as it walks the tree, positioned like `cursor_at`.
+    pub fn cursor_mut_at(&mut self, value: &T) -> CursorMut<'_, T> {
+        let current = self.cursor_at(value).peek().cloned();
+        CursorMut::new(self, current)
+    }
+
+    /// Returns a cursor that can remove values as it walks the tree, positioned at the least value.
+    pub fn cursor_mut_front(&mut self) -> CursorMut<'_, T> {
+        let current = self.cursor_front().peek().cloned();
+        CursorMut::new(self, current)
+    }
+
+    /// Returns a cursor that can remove values as it walks the tree, positioned at the greatest value.
+    pub fn cursor_mut_back(&mut self) -> CursorMut<'_, T> {
+        let current = self.cursor_back().peek().cloned();
+        CursorMut::new(self, current)
+    }
+
+    /// Copy the values into a read-only `FrozenSet`, which is faster to search.
+    pub fn freeze(&self) -> FrozenSet<T> {
+        FrozenSet::from_sorted_vec(self.as_vec())
+    }
+
+    /// Compare the values in this tree with another's, in a single pass over both in order, listing the values
+    /// found in only one of them.
+    ///
+    ///     use jtree::Javlt;
+    ///
+    ///     let old_tree = Javlt::from_collection([1,2,3]);
+    ///     let new_tree = Javlt::from_collection([2,3,4]);
+    ///     let diff = old_tree.diff(&new_tree);
+    ///     assert_eq!( vec!(1), diff.only_in_self );
+    ///     assert_eq!( vec!(4), diff.only_in_other );
+    pub fn diff(&self, other: &Self) -> TreeDiff<T> {
+        TreeDiff::between(self.iter_values(), other.iter_values())
+    }
+
+    /// Patch this tree with a diff, removing its `only_in_self` values and adding its `only_in_other` values,
+    /// so that after `tree_a.apply(&tree_a.diff(&tree_b))`, `tree_a` holds the same values as `tree_b`.  The
+    /// patch is all-or-nothing: if a value to remove isn't in the tree, or a value to add already is, the changes
+    /// already made are undone and the error is returned.
+    pub fn apply(&mut self, diff: &TreeDiff<T>) -> Result<(),TreeError<T>> {
+        for (index, value) in diff.only_in_self.iter().enumerate() {
+            if let Err(error) = self.drop_value(value.clone()) {
+                for removed in diff.only_in_self[..index].iter() {
+                    let _ = self.add(removed.clone());
+                }
+                return Err(error);
+            }
+        }
+        for (index, value) in diff.only_in_other.iter().enumerate() {
+            if let Err(error) = self.add(value.clone()) {
+                for added in diff.only_in_other[..index].iter() {
+                    let _ = self.drop_value(added.clone());
+                }
+                for removed in diff.only_in_self.iter() {
+                    let _ = self.add(removed.clone());
+                }
+                return Err(error);
+            }
         }
+        Ok(())
+    }
+
+    /// Returns a copy of the smallest/lowest value in the tree, if any.  Use `least` to borrow it instead.
+    pub fn least_value(&self) -> Option<T> {
+        self.least().cloned()
     }
 
-    /// Returns the largest/highest value in the tree, if any.
+    /// Returns a copy of the largest/highest value in the tree, if any.  Use `greatest` to borrow it instead.
     pub fn greatest_value(&self) -> Option<T> {
-        return match &self.root {
-            None => None,
-            Some(subtree) => Some(subtree.greatest_value()),
-        }
+        self.greatest().cloned()
     }
 
-        /// If the value is in the tree, delete it.  Otherwise a TreeError::ValueNotFound will be returned.
-    pub fn drop_value(&mut self, value: T) -> Result<(),TreeError> {
-        match self.root.take() {
-            None => {
-                self.root = None;
-                return Err(TreeError::ValueNotFound);
-            },
-            Some(child) => {
-                match child.drop_value(value) {
-                    (Err(_), new_node) => {
-                        self.root = new_node;
-                        return Err(TreeError::ValueNotFound);
-                    },
-                    (Ok(_), new_node) => {
-                        self.root = new_node;
-                        self.size -= 1;
-                        return Ok(());
-                    }
-                }
+    /// Export the tree's structure as a petgraph `DiGraph` (see the `graph` module).
+    #[cfg(feature = "petgraph")]
+    pub fn to_petgraph(&self) -> DiGraph<T, Branch> {
+        let mut graph = DiGraph::new();
+        match &self.root {
+            None => (),
+            Some(branch) => {
+                branch.add_to_graph(&mut graph);
             },
         }
+        graph
+    }
+
+}
+
+#[cfg(feature = "rayon")]
+impl <T: PartialEq + PartialOrd + Clone + Send + Sync> Javlt<T> {
+
+    /// With the `rayon` feature, build a tree from a (large) slice of values using all of the CPU's cores: the values
+    /// are sorted in parallel, duplicates are skipped, and then the left and right halves of each big subtree are
+    /// built at the same time.  The result is perfectly balanced.  Values that can't be ordered, even against
+    /// themselves (such as a floating point NaN), would have no place in the tree, so they cause a TreeError::Incomparable;
+    /// so do two values that can't be ordered against each other.
+    ///
+    ///     use jtree::Javlt;
+    ///
+    ///     let values: Vec<u64> = (0..100_000).rev().collect();
+    ///     let my_tree = Javlt::from_slice_parallel(&values).unwrap();
+    ///     assert_eq!( 100_000, my_tree.get_size() );
+    pub fn from_slice_parallel(values: &[T]) -> Result<Self,TreeError<T>> {
+        // the sort needs values that can be ordered, so any that can't be, even against themselves, are turned away first
+        if let Some(value) = values.par_iter().find_first(|value| value.partial_cmp(value).is_none()) {
+            return Err(TreeError::Incomparable { value: value.clone() });
+        }
+        let mut sorted: Vec<&T> = values.iter().collect();
+        sorted.par_sort_unstable_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+        // two values that can't be ordered against each other leave a pair of neighbours out of order
+        let out_of_order = sorted.par_windows(2).find_first(|pair| pair[0].partial_cmp(pair[1]).is_none_or(|order| order == Ordering::Greater));
+        if let Some(pair) = out_of_order {
+            return Err(TreeError::Incomparable { value: pair[1].clone() });
+        }
+        sorted.dedup_by(|a, b| a == b);
+        let size = u32::try_from(sorted.len()).expect("more values than a tree can count");
+        let tree = Self::from_root(Node::from_sorted_parallel(&sorted), size);
+        #[cfg(feature = "paranoid")]
+        tree.paranoid_check("from_slice_parallel");
+        Ok(tree)
     }
 
 }
 
-impl <T: PartialEq + PartialOrd + Clone> Default for Javlt<T> {
+impl <T: PartialEq + PartialOrd> Default for Javlt<T> {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl <T: PartialEq + PartialOrd + Clone + std::fmt::Debug> fmt::Debug for Javlt<T> {
+impl <T: PartialEq + PartialOrd + std::fmt::Debug> fmt::Debug for Javlt<T> {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt.debug_struct("Javlt")
             .field("size", &self.get_size())
-            .field("values", &self.as_vec())
+            .field("values", &self.values())
             .finish()
     }
 }
 
 
-struct Node<T: PartialEq + PartialOrd + Clone> {
+impl <T: PartialEq + PartialOrd + std::fmt::Debug> Javlt<T> {
+
+    /// Draw the tree sideways as ASCII art, one node per line, with the root at the left margin and
+    /// each right subtree drawn above its parent.
+    /// Each value is followed by the height of its subtree, e.g. `3 (h2)`.  An empty tree is drawn as an empty string.
+    ///
+    ///     use jtree::Javlt;
+    ///
+    ///     let my_tree = Javlt::from_collection([3,1,5,4,6]);
+    ///     assert_eq!( "    /-- 6 (h1)\n/-- 5 (h2)\n|   \\-- 4 (h1)\n3 (h3)\n\\-- 1 (h1)\n", my_tree.to_ascii() );
+    ///
+    /// where the drawing looks like:
+    ///
+    /// ```text
+    ///     /-- 6 (h1)
+    /// /-- 5 (h2)
+    /// |   \-- 4 (h1)
+    /// 3 (h3)
+    /// \-- 1 (h1)
+    /// ```
+    pub fn to_ascii(&self) -> String {
+        let mut drawing = String::new();
+        match &self.root {
+            None => (),
+            Some(branch) => branch.draw(&mut drawing, "", ""),
+        }
+        drawing
+    }
+
+    /// Print the tree's structure to stdout, as drawn by `to_ascii`.
+    pub fn print_tree(&self) {
+        print!("{}", self.to_ascii());
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd + Codec> Javlt<T> {
+
+    /// Encode the tree in jtree's binary format (see the `codec` module), keeping its exact shape.  Heights are worked out again as the tree is loaded, and the loaded tree's rotation count starts from zero.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        codec::write_header(&mut bytes, b'A');
+        self.size.encode(&mut bytes);
+        match &self.root {
+            None => (),
+            Some(branch) => branch.encode(&mut bytes),
+        }
+        bytes
+    }
+
+    /// Decode a tree encoded by `to_bytes`.  Returns a TreeError::DeserializationCorrupt if the bytes
+    /// aren't a valid Javlt, including as soon as they run deeper than a balanced tree of their size could, so
+    /// that untrusted bytes can't make the decoder recurse without bound.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self,TreeError<T>> {
+        let mut reader = Reader::new(bytes);
+        reader.read_header(b'A', "Javlt")?;
+        let size: u32 = reader.read()?;
+        let root = match size {
+            0 => None,
+            _ => Some(Node::decode(&mut reader, 1, Node::<T>::height_limit(size))?),
+        };
+        reader.finish()?;
+        let tree = Self::from_root(root, size);
+        reader.validate(tree.check_invariants())?;
+        Ok(tree)
+    }
+
+    /// Encode the tree in jtree's compact binary format (see the `codec` module), which stores the tree's shape in
+    /// two bits per node followed by the values in order, rather than a flags byte beside each value.  The exact
+    /// shape is kept, as with `to_bytes`.
+    ///
+    ///     use jtree::Javlt;
+    ///
+    ///     let my_tree = Javlt::from_collection(0..100u8);
+    ///     let bytes = my_tree.to_compact_bytes();
+    ///     assert!( bytes.len() < my_tree.to_bytes().len() * 2 / 3 );
+    ///     assert_eq!( my_tree.to_ascii(), Javlt::<u8>::from_compact_bytes(&bytes).unwrap().to_ascii() );
+    pub fn to_compact_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        codec::write_header(&mut bytes, b'a');
+        self.size.encode(&mut bytes);
+        let mut shape = ShapeWriter::new();
+        match &self.root {
+            None => (),
+            Some(branch) => branch.encode_shape(&mut shape),
+        }
+        bytes.extend(shape.into_bytes());
+        self.visit_in_order(|value| value.encode(&mut bytes));
+        bytes
+    }
+
+    /// Decode a tree encoded by `to_compact_bytes`.  Returns a TreeError::DeserializationCorrupt if the bytes
+    /// aren't a valid compact Javlt, bounding the depth as `from_bytes` does.
+    pub fn from_compact_bytes(bytes: &[u8]) -> Result<Self,TreeError<T>> {
+        let mut reader = Reader::new(bytes);
+        reader.read_header(b'a', "compact Javlt")?;
+        let size: u32 = reader.read()?;
+        let mut shape = ShapeReader::new(&mut reader, size as usize)?;
+        let root = match size {
+            0 => None,
+            _ => Some(Node::decode_shape(&mut shape, &mut reader, 1, Node::<T>::height_limit(size))?),
+        };
+        shape.finish()?;
+        reader.finish()?;
+        let tree = Self::from_root(root, size);
+        reader.validate(tree.check_invariants())?;
+        Ok(tree)
+    }
+
+    /// Save the tree to a file, in the format written by `to_bytes`, replacing the file if it exists.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(),TreeError<T>> {
+        fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+
+    /// Load a tree from a file written by `save`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self,TreeError<T>> {
+        let bytes = fs::read(path)?;
+        Self::from_bytes(&bytes)
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd + Hash> Javlt<T> {
+
+    /// Hash the tree's contents: its size and its values in order, so two trees holding the same values
+    /// have the same digest however they're shaped.  The digest is only as stable as the hasher (for example,
+    /// `DefaultHasher::new()` may change between Rust releases), so use the same one on both sides when
+    /// comparing trees in different places.
+    ///
+    ///     use std::hash::DefaultHasher;
+    ///     use jtree::Javlt;
+    ///
+    ///     let tree_a = Javlt::from_collection([1,2,3]);
+    ///     let tree_b = Javlt::from_collection([3,2,1]);
+    ///     assert_eq!( tree_a.digest::<DefaultHasher>(), tree_b.digest::<DefaultHasher>() );
+    pub fn digest<H: Hasher + Default>(&self) -> u64 {
+        let mut hasher = H::default();
+        self.size.hash(&mut hasher);
+        match &self.root {
+            None => (),
+            Some(branch) => branch.hash_values(&mut hasher),
+        }
+        hasher.finish()
+    }
+
+}
+
+struct Node<T: PartialEq + PartialOrd> {
     value: T,
     height: u32,
     left: Option<Box<Node<T>>>,
     right: Option<Box<Node<T>>>,
 }
 
-impl <T: PartialEq + PartialOrd + Clone> Node<T> {
+impl <T: PartialEq + PartialOrd> SearchNode for Node<T> {
+    type Value = T;
+    fn value(&self) -> &T { &self.value }
+    fn left(&self) -> Option<&Self> { self.left.as_deref() }
+    fn right(&self) -> Option<&Self> { self.right.as_deref() }
+}
+
+impl <T: PartialEq + PartialOrd> Node<T> {
 
     pub fn new(value: T) -> Self {
+        #[cfg(feature = "metrics")]
+        metrics::record(|metrics| metrics.allocations += 1);
         Self {
             value,
             height: 1,
@@ -185,137 +1184,178 @@ impl <T: PartialEq + PartialOrd + Clone> Node<T> {
         }
     }
 
-    /// Insert a value
-    pub fn add(&mut self, value: T) -> Result<(),TreeError> {
-        if value == self.value {
-            // no duplicates allowed in this kind of tree
-            return Err(TreeError::ValueAlreadyStored)
+    /// Returns the smallest/lowest value in this (sub)tree.
+    pub fn least(&self) -> &T {
+        return match &self.left {
+            None => &self.value,
+            Some(left_child) => left_child.least(),
         }
+    }
 
-        if value < self.value {
-            // add to the left branch
-            match &mut self.left {
-                None => self.left = Some(Box::new(Node::new(value))),
-                Some(branch) => branch.add(value)?,
-            }
-            self.rebalance();
-            self.height = self.compute_height();
-            return Ok(())
-        } else {
-            // add it to the right branch
-            match &mut self.right {
-                None => self.right = Some(Box::new(Node::new(value))),
-                Some(branch) => branch.add(value)?,
-            }
-            self.rebalance();
-            self.height = self.compute_height();
-            return Ok(())
+    /// Returns the largest/highest value in this (sub)tree.
+    pub fn greatest(&self) -> &T {
+        return match &self.right {
+            None => &self.value,
+            Some(right_child) => right_child.greatest(),
         }
     }
 
+    /// Insert a value (or one made on arrival), adding any rotations needed to rebalance the (sub)tree to the
+    /// `rotations` tally, and return the route from this node to the new one.  If an equal value is already stored, the value is
+    /// given back along with the route to the stored one.
+    pub fn add<V: Arriving<T>>(&mut self, value: V, rotations: &mut Rotations<T>) -> Result<Route,(Route, V)> {
+        #[cfg(feature = "metrics")]
+        metrics::visit();
+        if *value.probe() == self.value {
+            // no duplicates allowed in this kind of tree
+            return Err((Route::default(), value))
+        }
+
+        // add to the left branch if the value is less than this one, or the right branch if it's greater
+        let right = *value.probe() > self.value;
+        let child = if right { &mut self.right } else { &mut self.left };
+        let route = match child {
+            None => {
+                *child = Some(Box::new(Node::new(value.make())));
+                Route::default()
+            },
+            Some(branch) => branch.add(value, rotations).map_err(|(route, value)| (route.via(right), value))?,
+        };
+        let rotation = self.rebalance(rotations);
+        self.height = self.compute_height();
+        Ok(route.via(right).after(rotation))
+    }
+
+    /// The value at the end of the route from this node.
+    fn value_at(&self, route: Route) -> &T {
+        return match route.first() {
+            None => &self.value,
+            Some((false, rest)) => self.left.as_deref().unwrap().value_at(rest),
+            Some((true, rest)) => self.right.as_deref().unwrap().value_at(rest),
+        };
+    }
+
     /// Height of a subtree is the height of its largest child subtree, plus 1.
     fn compute_height(&self) -> u32 {
-        let left_height = if self.left.is_none() {0} else {self.left.as_ref().unwrap().height};
-        let right_height = if self.right.is_none() {0} else {self.right.as_ref().unwrap().height};
-        max(left_height, right_height) + 1
+        let left_height = self.left.as_ref().map_or(0, |node| node.height);
+        let right_height = self.right.as_ref().map_or(0, |node| node.height);
+        max(left_height, right_height).saturating_add(1)
     }
 
     /// Balancing factor is the height of the right subtree minus the height of the left subtree.
     /// Although this will never be outside the range -2 to +2, we use i64 for safe type casting.
     fn compute_balancing_factor(&self) -> i64 {
-        let left_height = if self.left.is_none() {0} else {self.left.as_ref().unwrap().height};
-        let right_height = if self.right.is_none() {0} else {self.right.as_ref().unwrap().height};
+        let left_height = self.left.as_ref().map_or(0, |node| node.height);
+        let right_height = self.right.as_ref().map_or(0, |node| node.height);
         i64::from(right_height) - i64::from(left_height)
     }
 
-    fn rebalance(&mut self) {
+    /// Rotate this (sub)tree if it has become unbalanced, adding the number of rotations made
+    /// (one for the single rotation cases, two for the double rotation cases) to the `rotations` tally, and
+    /// return which case it was.
+    fn rebalance(&mut self, rotations: &mut Rotations<T>) -> Rotation {
         let bf = self.compute_balancing_factor();
-        if bf >= -1 && bf <= 1 {
+        if (-1..=1).contains(&bf) {
             // tree is balanced, do nothing
-            return;
+            return Rotation::None;
         }
+        let rotation;
         if bf > 1 {
             // tree is right-heavy
-            if self.right.as_ref().unwrap().compute_balancing_factor() > 0 {
-                // right child is right-heavy, this is a Right Right rotation
-                let mut new_left_node = Node::new(self.value.clone());
-                new_left_node.left = self.left.take();
-                new_left_node.right = self.right.as_mut().unwrap().left.take();
-                new_left_node.height = new_left_node.compute_height();
-                self.left = Some(Box::new(new_left_node));
-                self.value = self.right.as_ref().unwrap().value.clone();
-                let new_right_node = self.right.as_mut().unwrap().right.take();
-                self.right = new_right_node;
-                self.height = self.compute_height();
+            if self.right.as_ref().unwrap().compute_balancing_factor() >= 0 {
+                // right child is right-heavy (or, after a deletion, balanced), this is a Right Right rotation
+                rotations.tally(1);
+                self.rotate_left();
+                #[cfg(feature = "tracing")]
+                rotations.trace("right right", self);
+                rotation = Rotation::RightRight;
             } else {
                 // right child is left-heavy, this is a Right Left situation
-                // step 1: rotate the right child's subtree right
-                let mut new_right_right = Node::new(self.right.as_ref().unwrap().value.clone());
-                new_right_right.right = self.right.as_mut().unwrap().right.take();
-                new_right_right.left = self.right.as_mut().unwrap().left.as_mut().unwrap().right.take();
-                new_right_right.height = new_right_right.compute_height();
-
-                let mut new_right = Node::new(self.right.as_ref().unwrap().left.as_ref().unwrap().value.clone());
-                new_right.right = Some(Box::new(new_right_right));
-                new_right.left = self.right.as_mut().unwrap().left.as_mut().unwrap().left.take();
-                new_right.height = new_right.compute_height();
-
-                self.right = Some(Box::new(new_right));
-                // step 2: rotate our subtree left (as in the above Right Right case)
-                let mut new_left_node = Node::new(self.value.clone());
-                new_left_node.left = self.left.take();
-                new_left_node.right = self.right.as_mut().unwrap().left.take();
-                new_left_node.height = new_left_node.compute_height();
-                self.left = Some(Box::new(new_left_node));
-                self.value = self.right.as_ref().unwrap().value.clone();
-                let final_right_node = self.right.as_mut().unwrap().right.take();
-                self.right = final_right_node;
-                self.height = self.compute_height();
+                rotations.tally(2);
+                // step 1: rotate the right child's subtree right; step 2: rotate our subtree left
+                self.right.as_mut().unwrap().rotate_right();
+                self.rotate_left();
+                #[cfg(feature = "tracing")]
+                rotations.trace("right left", self);
+                rotation = Rotation::RightLeft;
             }
         } else {
             // tree is left-heavy
-            if self.left.as_ref().unwrap().compute_balancing_factor() < 0 {
-                // left child is left-heavy, this is a Left Left rotation
-                let mut new_right_node = Node::new(self.value.clone());
-                new_right_node.right = self.right.take();
-                new_right_node.left = self.left.as_mut().unwrap().right.take();
-                new_right_node.height = new_right_node.compute_height();
-                self.right = Some(Box::new(new_right_node));
-                self.value = self.left.as_ref().unwrap().value.clone();
-                let new_left_node = self.left.as_mut().unwrap().left.take();
-                self.left = new_left_node;
-                self.height = self.compute_height();
+            if self.left.as_ref().unwrap().compute_balancing_factor() <= 0 {
+                // left child is left-heavy (or, after a deletion, balanced), this is a Left Left rotation
+                rotations.tally(1);
+                self.rotate_right();
+                #[cfg(feature = "tracing")]
+                rotations.trace("left left", self);
+                rotation = Rotation::LeftLeft;
             } else {
-                // left child is right-heavy, this is a Right Left rotation
-                // step 1: rotate the left child's subtree left
-                let mut new_left_left = Node::new(self.left.as_ref().unwrap().value.clone());
-                new_left_left.left = self.left.as_mut().unwrap().left.take();
-                new_left_left.right = self.left.as_mut().unwrap().right.as_mut().unwrap().left.take();
-                new_left_left.height = new_left_left.compute_height();
-
-                let mut new_left = Node::new(self.left.as_ref().unwrap().right.as_ref().unwrap().value.clone());
-                new_left.left = Some(Box::new(new_left_left));
-                new_left.right = self.left.as_mut().unwrap().right.as_mut().unwrap().right.take();
-                new_left.height = new_left.compute_height();
-                
-                self.left = Some(Box::new(new_left));
-                // step 2: rotate our subtree right (as in the above Left Left case)
-                let mut new_right_node = Node::new(self.value.clone());
-                new_right_node.right = self.right.take();
-                new_right_node.left = self.left.as_mut().unwrap().right.take();
-                new_right_node.height = new_right_node.compute_height();
-
-                self.right = Some(Box::new(new_right_node));
-                self.value = self.left.as_ref().unwrap().value.clone();
-                let final_left_node = self.left.as_mut().unwrap().left.take();
-                self.left = final_left_node;
-                self.height = self.compute_height();
+                // left child is right-heavy, this is a Left Right rotation
+                rotations.tally(2);
+                // step 1: rotate the left child's subtree left; step 2: rotate our subtree right
+                self.left.as_mut().unwrap().rotate_left();
+                self.rotate_right();
+                #[cfg(feature = "tracing")]
+                rotations.trace("left right", self);
+                rotation = Rotation::LeftRight;
             }
         }
+        #[cfg(feature = "paranoid")]
+        if let Err(TreeError::InvariantViolation(problem)) = self.check_invariants(&mut String::from("rotated subtree"), None, None) {
+            panic!("Javlt rotation broke the tree: {problem} (shape {})", self.outline());
+        }
+        rotation
+    }
+
+    /// Rotate this (sub)tree left: the right child takes this node's place, and this node becomes its left child.
+    /// The nodes themselves are moved, so no value is copied.
+    fn rotate_left(&mut self) {
+        let mut pivot = self.right.take().unwrap();
+        self.right = pivot.left.take();
+        self.height = self.compute_height();
+        std::mem::swap(self, &mut pivot);
+        self.left = Some(pivot);
+        self.height = self.compute_height();
+    }
+
+    /// Rotate this (sub)tree right: the left child takes this node's place, and this node becomes its right child.
+    fn rotate_right(&mut self) {
+        let mut pivot = self.left.take().unwrap();
+        self.left = pivot.right.take();
+        self.height = self.compute_height();
+        std::mem::swap(self, &mut pivot);
+        self.right = Some(pivot);
+        self.height = self.compute_height();
+    }
+
+    /// Swap `value` in for the equal value stored in the (sub)tree, returning the old one; or hand `value`
+    /// back if there's no equal value to replace.
+    pub fn replace(&mut self, value: T) -> Result<T,T> {
+        if value == self.value {
+            return Ok(std::mem::replace(&mut self.value, value));
+        }
+        let child = if value < self.value { &mut self.left } else { &mut self.right };
+        return match child {
+            None => Err(value),
+            Some(node) => node.replace(value),
+        }
+    }
+
+    /// Returns the stored value equal to `value`, if it's in the (sub)tree
+    pub fn get(&self, value: &T) -> Option<&T> {
+        if *value == self.value {
+            return Some(&self.value);
+        }
+        let child = if *value < self.value { &self.left } else { &self.right };
+        return match child {
+            None => None,
+            Some(node) => node.get(value),
+        }
     }
 
     /// Returns true if the value is currently a member of the (sub)tree
     pub fn contains(&self, value: &T) -> bool {
+        #[cfg(feature = "metrics")]
+        metrics::visit();
         if *value == self.value {
             return true;
         }
@@ -332,144 +1372,1041 @@ impl <T: PartialEq + PartialOrd + Clone> Node<T> {
         }
     }
 
-    /// Returns true if the node is a leaf or terminal node, with no child nodes of its own.
-    pub fn is_leaf(&self) -> bool {
-        self.left.is_none() && self.right.is_none()
-    }
-
-    /// Returns the smallest/lowest value in this (sub)tree.
-    pub fn least_value(&self) -> T {
-        return match &self.left {
-            None => self.value.clone(),
-            Some(left_child) => left_child.least_value(),
+    /// Recursively verify that this (sub)tree's values are in order and strictly between the bounds
+    /// set by its ancestors, and that its heights and balance are right.  Returns the number of nodes.
+    /// The path (e.g. "root.left.right") is only used to describe where a problem was found.
+    pub fn check_invariants(&self, path: &mut String, lower: Option<&T>, upper: Option<&T>) -> Result<u32,TreeError<T>> {
+        if lower.is_some_and(|lower| self.value <= *lower) || upper.is_some_and(|upper| self.value >= *upper) {
+            return Err(TreeError::InvariantViolation(format!("value at {path} is out of order")));
+        }
+        let mut nodes = 1;
+        let path_len = path.len();
+        if let Some(node) = &self.left {
+            path.push_str(".left");
+            nodes += node.check_invariants(path, lower, Some(&self.value))?;
+            path.truncate(path_len);
+        }
+        if let Some(node) = &self.right {
+            path.push_str(".right");
+            nodes += node.check_invariants(path, Some(&self.value), upper)?;
+            path.truncate(path_len);
+        }
+        if self.height != self.compute_height() {
+            return Err(TreeError::InvariantViolation(format!("node at {path} has height {} but should be {}", self.height, self.compute_height())));
+        }
+        let balancing_factor = self.compute_balancing_factor();
+        if !(-1..=1).contains(&balancing_factor) {
+            return Err(TreeError::InvariantViolation(format!("node at {path} is unbalanced, with a balancing factor of {balancing_factor}")));
+        }
+        Ok(nodes)
+    }
+
+    /// Sketch the shape of this (sub)tree, for the `paranoid` feature's diagnostics, without needing to print the values.
+    /// Nodes are shown by their heights, e.g. `(. 2 (. 1 .))`.
+    #[cfg(feature = "paranoid")]
+    fn outline(&self) -> String {
+        let left = self.left.as_ref().map_or(String::from("."), |node| node.outline());
+        let right = self.right.as_ref().map_or(String::from("."), |node| node.outline());
+        format!("({left} {} {right})", self.height)
+    }
+
+    /// Recursively tally this (sub)tree's nodes, at the given depth, into the stats.
+    pub fn collect_stats(&self, depth: usize, stats: &mut TreeStats) {
+        stats.record_node(depth, self.is_leaf());
+        match &self.left {
+            Some(node) => node.collect_stats(depth + 1, stats),
+            None => (),
+        }
+        match &self.right {
+            Some(node) => node.collect_stats(depth + 1, stats),
+            None => (),
+        }
+    }
+
+    /// Returns true if the node is a leaf or terminal node, with no child nodes of its own.
+    pub fn is_leaf(&self) -> bool {
+        self.left.is_none() && self.right.is_none()
+    }
+
+    /// Recursively call `f` on this (sub)tree's values in the given order, stopping as soon as it returns `ControlFlow::Break`.
+    pub fn try_visit<B, F: FnMut(&T) -> ControlFlow<B>>(&self, order: Traversal, f: &mut F) -> ControlFlow<B> {
+        if order == Traversal::Pre {
+            self.visit_value(f)?;
+        }
+        if let Some(node) = &self.left {
+            node.try_visit(order, f)?;
+        }
+        if order == Traversal::In {
+            self.visit_value(f)?;
+        }
+        if let Some(node) = &self.right {
+            node.try_visit(order, f)?;
+        }
+        if order == Traversal::Post {
+            self.visit_value(f)?;
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Recursively call `f` on a mutable reference to each of this (sub)tree's values, from least to greatest.
+    fn visit_in_order_mut<F: FnMut(&mut T)>(&mut self, f: &mut F) {
+        if let Some(node) = &mut self.left {
+            node.visit_in_order_mut(f);
+        }
+        f(&mut self.value);
+        if let Some(node) = &mut self.right {
+            node.visit_in_order_mut(f);
+        }
+    }
+
+    /// Recursively build a perfectly balanced (sub)tree from the next `len` of the sorted, unique values.
+    fn from_sorted<I: Iterator<Item = T>>(values: &mut I, len: usize) -> Option<Box<Node<T>>> {
+        if len == 0 {
+            return None;
+        }
+        let left = Node::from_sorted(values, len / 2);
+        let mut node = Node::new(values.next().unwrap());
+        node.left = left;
+        node.right = Node::from_sorted(values, len - len / 2 - 1);
+        node.height = node.compute_height();
+        Some(Box::new(node))
+    }
+
+    /// Call `f` on this node's value.
+    fn visit_value<B, F: FnMut(&T) -> ControlFlow<B>>(&self, f: &mut F) -> ControlFlow<B> {
+        f(&self.value)
+    }
+
+    /// Remove the value that `compare` finds (see `Javlt::get_by`), rebalancing on the way back up, and return
+    /// it (or None, if there's no such value) along with the node that takes this one's place.
+    fn take_by<F: FnMut(&T) -> Option<Ordering>>(mut self, compare: &mut F, rotations: &mut Rotations<T>) -> (Option<T>, Option<Box<Node<T>>>) {
+        #[cfg(feature = "metrics")]
+        metrics::visit();
+
+        let child = match compare(&self.value) {
+            None => return (None, Some(Box::new(self))),
+            // if this node's value is greater than the one sought, look in the left subtree; if less, the right
+            Some(Ordering::Greater) => &mut self.left,
+            Some(Ordering::Less) => &mut self.right,
+            // if this node has the value:
+            Some(Ordering::Equal) => {
+                // - if it has no children, just replace it with None
+                if self.is_leaf() {
+                    return (Some(self.value), None);
+                }
+                // - if it has no left branch, replace it with its right child (and subtree)
+                if self.left.is_none() {
+                    return (Some(self.value), self.right);
+                }
+                // - if it has no right branch, replace it with its left child (and subtree)
+                if self.right.is_none() {
+                    return (Some(self.value), self.left);
+                }
+                let replacement = if self.right.as_ref().unwrap().is_leaf() {
+                    // - if its right child is a leaf, move that leaf's value into this node (and drop the leaf)
+                    self.right.take().unwrap().value
+                } else if self.left.as_ref().unwrap().is_leaf() {
+                    // - otherwise, if its left child is a leaf, move that leaf's value in (and drop the leaf)
+                    self.left.take().unwrap().value
+                } else {
+                    // - if we get to this point, both children are branches. Take the immediate successor out of the
+                    //   right branch, and move it into this node in place of the removed value
+                    let (successor, new_right) = self.right.take().unwrap().take_least(rotations);
+                    self.right = new_right;
+                    successor
+                };
+                let removed = std::mem::replace(&mut self.value, replacement);
+                self.rebalance(rotations);
+                self.height = self.compute_height();
+                return (Some(removed), Some(Box::new(self)));
+            },
+        };
+        let removed = match child.take() {
+            None => return (None, Some(Box::new(self))),
+            Some(node) => {
+                let (removed, new_child) = node.take_by(compare, rotations);
+                *child = new_child;
+                removed
+            },
+        };
+        if removed.is_some() {
+            self.rebalance(rotations);
+            self.height = self.compute_height();
+        }
+        (removed, Some(Box::new(self)))
+    }
+
+    /// Remove the least value from this (sub)tree, rebalancing on the way back up, and return it along with
+    /// the node that takes this one's place.
+    fn take_least(mut self, rotations: &mut Rotations<T>) -> (T, Option<Box<Node<T>>>) {
+        return match self.left.take() {
+            None => (self.value, self.right),
+            Some(left_child) => {
+                let (least, new_left) = left_child.take_least(rotations);
+                self.left = new_left;
+                self.rebalance(rotations);
+                self.height = self.compute_height();
+                (least, Some(Box::new(self)))
+            },
+        };
+    }
+
+}
+
+/// A value on its way into the tree, as `Node::add` sees it: something to compare with the stored values on
+/// the way down, to be made into the value to store once its place is found.
+trait Arriving<T> {
+    fn probe(&self) -> &T;
+    fn make(self) -> T;
+}
+
+impl <T: PartialEq + PartialOrd> Arriving<T> for T {
+    fn probe(&self) -> &T {
+        self
+    }
+
+    fn make(self) -> T {
+        self
+    }
+}
+
+/// A probe to search with, and a function to make the value equal to it only once it's known to be needed.
+struct Made<'a, T, F: FnOnce() -> T> {
+    probe: &'a T,
+    make: F,
+}
+
+impl <T, F: FnOnce() -> T> Arriving<T> for Made<'_, T, F> {
+    fn probe(&self) -> &T {
+        self.probe
+    }
+
+    fn make(self) -> T {
+        (self.make)()
+    }
+}
+
+/// Which of the four cases, if any, `Node::rebalance` rotated a (sub)tree for, named as in its trace events.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Rotation {
+    None,
+    RightRight,
+    RightLeft,
+    LeftLeft,
+    LeftRight,
+}
+
+/// The rotations made to rebalance a tree since it was created, and (with the `tracing` feature, once
+/// `Javlt::trace_values` has been called) how to describe values in its events.
+struct Rotations<T> {
+    count: u64,
+    #[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+    describe: Option<fn(&T) -> String>,
+}
+
+impl <T: PartialEq + PartialOrd> Rotations<T> {
+
+    fn new() -> Self {
+        Self {
+            count: 0,
+            describe: None,
+        }
+    }
+
+    /// Count `rotations` more rotations.
+    fn tally(&mut self, rotations: u64) {
+        self.count = self.count.saturating_add(rotations);
+    }
+
+    /// Report a rotation of the subtree now topped by `node`: at trace level, or at debug level with the
+    /// node's value if values are being described.
+    #[cfg(feature = "tracing")]
+    fn trace(&self, rotation: &str, node: &Node<T>) {
+        match self.describe {
+            None => tracing::trace!(rotation, height = node.height, "Javlt rotated a subtree"),
+            Some(describe) => tracing::debug!(rotation, height = node.height, value = describe(&node.value), "Javlt rotated a subtree"),
+        }
+    }
+
+}
+
+/// The turns taken down from a (sub)tree's root to one of its nodes, so that a value just added can be found
+/// again after rebalancing without comparing values.  Bit `i` of `turns` is set if step `i` goes right; a
+/// tree of `u32::MAX` values is under 48 nodes high, so the turns always fit.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Route {
+    turns: u64,
+    steps: u32,
+}
+
+impl Route {
+
+    /// The route to the same node from its parent, on whose right (or left) it is.
+    fn via(self, right: bool) -> Self {
+        Self { turns: self.turns << 1 | u64::from(right), steps: self.steps + 1 }
+    }
+
+    /// Split off the first turn (true for right), or None for the route to the root itself.
+    fn first(self) -> Option<(bool, Self)> {
+        return match self.steps {
+            0 => None,
+            _ => Some((self.turns & 1 == 1, Self { turns: self.turns >> 1, steps: self.steps - 1 })),
+        };
+    }
+
+    /// The route to the same node after `rebalance` rotated the (sub)tree.
+    fn after(self, rotation: Rotation) -> Self {
+        return match rotation {
+            Rotation::None => self,
+            Rotation::RightRight => self.rotated(true),
+            Rotation::RightLeft => self.child_rotated(true, false).rotated(true),
+            Rotation::LeftLeft => self.rotated(false),
+            Rotation::LeftRight => self.child_rotated(false, true).rotated(false),
+        };
+    }
+
+    /// The route to the same node after the (sub)tree is rotated so that its right (or left, if `rising` is
+    /// false) child takes the root's place: the root moves down to the other side, taking the rising child's
+    /// inner subtree with it.
+    fn rotated(self, rising: bool) -> Self {
+        return match self.first() {
+            None => Self::default().via(!rising),
+            Some((turn, rest)) if turn != rising => rest.via(!rising).via(!rising),
+            Some((_, rest)) => match rest.first() {
+                None => rest,
+                Some((turn, inner)) if turn != rising => inner.via(rising).via(!rising),
+                Some((_, outer)) => outer.via(rising),
+            },
+        };
+    }
+
+    /// The route to the same node after the root's child on the `side` given is rotated (see `rotated`).
+    fn child_rotated(self, side: bool, rising: bool) -> Self {
+        return match self.first() {
+            Some((turn, rest)) if turn == side => rest.rotated(rising).via(side),
+            _ => self,
+        };
+    }
+
+}
+
+/// Visits the nodes of a tree in order, from least to greatest value, along with their depths, from either end.
+/// It keeps a queue of the nodes still to come, in order, so it only holds about one path's worth of nodes for
+/// each end at a time.  Each one queued stands for itself along with its left and right subtrees, unless the
+/// nodes of a subtree have been queued separately, or a `split` has handed them to another iterator.
+struct Nodes<'a, T: PartialEq + PartialOrd> {
+    queue: VecDeque<Pending<'a, T>>,
+}
+
+/// A node queued by `Nodes`, its depth, and whether its left and right subtrees are still to come with it.
+type Pending<'a, T> = (&'a Node<T>, usize, bool, bool);
+
+impl <'a, T: PartialEq + PartialOrd> Nodes<'a, T> {
+
+    fn new(root: &'a Option<Box<Node<T>>>) -> Self {
+        Self { queue: root.iter().map(|node| (&**node, 0, true, true)).collect() }
+    }
+
+    /// Queue just the nodes whose values are within the range, for `range`.
+    fn within<R: RangeBounds<T>>(root: &'a Option<Box<Node<T>>>, range: &R) -> Self {
+        Self { queue: queue_within(root.as_deref(), range) }
+    }
+
+    /// The number of nodes a queued node stands for, roughly: a subtree of height h holds up to 2^h of them.
+    fn weight((node, _, with_left, with_right): &Pending<'a, T>) -> u64 {
+        let subtree = |with: bool, link: &Option<Box<Node<T>>>| match (with, link) {
+            (true, Some(child)) => 1u64 << child.height.min(61),
+            _ => 0,
+        };
+        1 + subtree(*with_left, &node.left) + subtree(*with_right, &node.right)
+    }
+
+    /// Replace the queued node at `index` with its subtrees (those still to come with it) and itself alone.
+    fn separate(&mut self, index: usize) {
+        let (node, depth, with_left, with_right) = self.queue[index];
+        self.queue[index] = (node, depth, false, false);
+        if let (true, Some(right)) = (with_right, &node.right) {
+            self.queue.insert(index + 1, (right, depth + 1, true, true));
+        }
+        if let (true, Some(left)) = (with_left, &node.left) {
+            self.queue.insert(index, (left, depth + 1, true, true));
+        }
+    }
+
+    /// Divide the nodes still to come between two iterators, the first taking the earlier ones, at a boundary
+    /// between subtrees chosen so that (judging by their heights) each gets about half.  If there's at most one
+    /// node left, the second iterator gets none.
+    fn split(mut self) -> (Self, Self) {
+        // with fewer than three nodes queued, queue the heaviest one's subtrees separately, to divide them more evenly
+        while self.queue.len() < 3 {
+            let heaviest = (0..self.queue.len()).max_by_key(|&index| Self::weight(&self.queue[index]));
+            match heaviest {
+                Some(index) if Self::weight(&self.queue[index]) > 1 => self.separate(index),
+                _ => break,
+            }
+        }
+        if self.queue.len() < 2 {
+            return (self, Self { queue: VecDeque::new() });
+        }
+        let total: u64 = self.queue.iter().map(Self::weight).fold(0, u64::saturating_add);
+        let mut front_weight = 0;
+        let mut front_len = 0;
+        while front_len < self.queue.len() - 1 && front_weight < total / 2 {
+            front_weight += Self::weight(&self.queue[front_len]);
+            front_len += 1;
+        }
+        let back = Self { queue: self.queue.split_off(front_len) };
+        (self, back)
+    }
+
+}
+
+impl <'a, T: PartialEq + PartialOrd> Iterator for Nodes<'a, T> {
+    type Item = (&'a Node<T>, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (node, depth, with_left, with_right) = self.queue.pop_front()?;
+            if let (true, Some(left)) = (with_left, &node.left) {
+                // the left subtree comes first
+                self.queue.push_front((node, depth, false, with_right));
+                self.queue.push_front((left, depth + 1, true, true));
+                continue;
+            }
+            if let (true, Some(right)) = (with_right, &node.right) {
+                self.queue.push_front((right, depth + 1, true, true));
+            }
+            return Some((node, depth));
+        }
+    }
+}
+
+impl <'a, T: PartialEq + PartialOrd> DoubleEndedIterator for Nodes<'a, T> {
+
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            let (node, depth, with_left, with_right) = self.queue.pop_back()?;
+            if let (true, Some(right)) = (with_right, &node.right) {
+                // the right subtree comes last
+                self.queue.push_back((node, depth, with_left, false));
+                self.queue.push_back((right, depth + 1, true, true));
+                continue;
+            }
+            if let (true, Some(left)) = (with_left, &node.left) {
+                self.queue.push_back((left, depth + 1, true, true));
+            }
+            return Some((node, depth));
+        }
+    }
+}
+
+/// An iterator over a Javlt's values, from least to greatest or (with `rev` or `next_back`) from greatest to
+/// least, or from both ends at once; get one with `iter`.
+pub struct Iter<'a, T: PartialEq + PartialOrd> {
+    nodes: Nodes<'a, T>,
+}
+
+impl <'a, T: PartialEq + PartialOrd> Iter<'a, T> {
+
+    /// Divide the values still to come between two iterators over disjoint halves of them, at a boundary between
+    /// subtrees: the first iterator yields the earlier values and the second the later ones, so that
+    /// running the first and then the second gives the same values as this iterator would.  The halves are
+    /// roughly equal, and each can be split again.  Once there's at most one value left, the second
+    /// iterator is empty.
+    pub fn split(self) -> (Self, Self) {
+        let (front, back) = self.nodes.split();
+        (Self { nodes: front }, Self { nodes: back })
+    }
+
+}
+
+impl <'a, T: PartialEq + PartialOrd> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.nodes.next().map(|(node, _)| &node.value)
+    }
+}
+
+impl <'a, T: PartialEq + PartialOrd> DoubleEndedIterator for Iter<'a, T> {
+
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.nodes.next_back().map(|(node, _)| &node.value)
+    }
+}
+
+impl <'a, T: PartialEq + PartialOrd> IntoIterator for &'a Javlt<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator that moves the values out of a Javlt in order (from either end), taking the tree apart as it
+/// goes.  Made by `into_iter`, as in `for value in tree`.
+pub struct IntoIter<T: PartialEq + PartialOrd> {
+    drain: Drain<T>,
+}
+
+impl <T: PartialEq + PartialOrd> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.drain.next()
+    }
+}
+
+impl <T: PartialEq + PartialOrd> DoubleEndedIterator for IntoIter<T> {
+
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.drain.next_back()
+    }
+}
+
+impl <T: PartialEq + PartialOrd> IntoIterator for Javlt<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        IntoIter { drain: self.take_nodes() }
+    }
+}
+
+/// Takes the values out of a tree's nodes in order, from either end, for `drain` and `into_iter`.  Like `Nodes`, it
+/// keeps a queue of the nodes still to come, each standing for itself and the children it still holds; children
+/// are taken from their parent as they're queued.
+struct Drain<T: PartialEq + PartialOrd> {
+    queue: VecDeque<Box<Node<T>>>,
+}
+
+impl <T: PartialEq + PartialOrd> Drain<T> {
+
+    fn new(root: Option<Box<Node<T>>>) -> Self {
+        Self { queue: root.into_iter().collect() }
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd> Iterator for Drain<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let mut node = self.queue.pop_front()?;
+            if let Some(left) = node.left.take() {
+                self.queue.push_front(node);
+                self.queue.push_front(left);
+                continue;
+            }
+            if let Some(right) = node.right.take() {
+                self.queue.push_front(right);
+            }
+            return Some(node.value);
+        }
+    }
+}
+
+impl <T: PartialEq + PartialOrd> DoubleEndedIterator for Drain<T> {
+
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            let mut node = self.queue.pop_back()?;
+            if let Some(right) = node.right.take() {
+                self.queue.push_back(node);
+                self.queue.push_back(right);
+                continue;
+            }
+            if let Some(left) = node.left.take() {
+                self.queue.push_back(left);
+            }
+            return Some(node.value);
+        }
+    }
+}
+
+/// A position among a Javlt's values, which can step to the next or previous value in amortized O(1) time.
+/// Get one with `cursor_at`, `cursor_front` or `cursor_back`.  Besides the values, there's a "ghost" position
+/// past both ends, where `peek` returns None: stepping back from it goes to the greatest value.  As an `Iterator`,
+/// the cursor yields the values after its position, and stops for good at the ghost position.
+pub struct Cursor<'a, T: PartialEq + PartialOrd> {
+    root: &'a Option<Box<Node<T>>>,
+    path: Vec<&'a Node<T>>, // from the root down to the current node; empty at the ghost position
+}
+
+impl <'a, T: PartialEq + PartialOrd> Cursor<'a, T> {
+
+    fn new(root: &'a Option<Box<Node<T>>>) -> Self {
+        Self { root, path: Vec::new() }
+    }
+
+    /// A cursor positioned at the least value (or the ghost position, if there are none).
+    fn front(root: &'a Option<Box<Node<T>>>) -> Self {
+        let mut cursor = Self::new(root);
+        cursor.push_left_edge(root);
+        cursor
+    }
+
+    /// Returns the value at the cursor's position, without moving.
+    pub fn peek(&self) -> Option<&'a T> {
+        return match self.path.last() {
+            None => None,
+            Some(node) => Some(&node.value),
+        };
+    }
+
+    /// Move to the previous value and return it, or None on reaching the ghost position.
+    pub fn prev(&mut self) -> Option<&'a T> {
+        self.step_back();
+        self.peek()
+    }
+
+    /// Position the cursor on the least node whose value is at least `value`, or the ghost position if there's none.
+    fn seek(&mut self, value: &T) {
+        let mut link = self.root;
+        let mut found = 0;
+        while let Some(node) = link {
+            self.path.push(node);
+            if *value == node.value {
+                found = self.path.len();
+                break;
+            }
+            if *value < node.value {
+                found = self.path.len();
+                link = &node.left;
+            } else {
+                link = &node.right;
+            }
+        }
+        self.path.truncate(found);
+    }
+
+    /// Position the cursor on the least node whose value is `at_or_after` the place being sought, or the ghost
+    /// position if there's none.  `at_or_after` must be false for a run of the least values and true for the rest.
+    fn seek_by<F: Fn(&T) -> bool>(&mut self, at_or_after: F) {
+        let mut link = self.root;
+        let mut found = 0;
+        while let Some(node) = link {
+            self.path.push(node);
+            if at_or_after(&node.value) {
+                found = self.path.len();
+                link = &node.left;
+            } else {
+                link = &node.right;
+            }
+        }
+        self.path.truncate(found);
+    }
+
+    /// Move to the next node in order, or from the last node to the ghost position, or from the ghost
+    /// position to the first node.
+    fn step_forward(&mut self) {
+        match self.path.last().copied() {
+            None => self.push_left_edge(self.root),
+            Some(node) if node.right.is_some() => self.push_left_edge(&node.right),
+            Some(_) => {
+                // climb until we come up out of a left subtree; that parent is next
+                let mut child = self.path.pop();
+                while let (Some(parent), Some(from)) = (self.path.last(), child) {
+                    if parent.left.as_deref().is_some_and(|left| std::ptr::eq(left, from)) {
+                        return;
+                    }
+                    child = self.path.pop();
+                }
+            },
+        }
+    }
+
+    /// Move to the previous node in order, or from the first node to the ghost position, or from the ghost
+    /// position to the last node.
+    fn step_back(&mut self) {
+        match self.path.last().copied() {
+            None => self.push_right_edge(self.root),
+            Some(node) if node.left.is_some() => self.push_right_edge(&node.left),
+            Some(_) => {
+                // climb until we come up out of a right subtree; that parent is previous
+                let mut child = self.path.pop();
+                while let (Some(parent), Some(from)) = (self.path.last(), child) {
+                    if parent.right.as_deref().is_some_and(|right| std::ptr::eq(right, from)) {
+                        return;
+                    }
+                    child = self.path.pop();
+                }
+            },
+        }
+    }
+
+    /// Descend from the node to the least node in its subtree.
+    fn push_left_edge(&mut self, mut link: &'a Option<Box<Node<T>>>) {
+        while let Some(node) = link {
+            self.path.push(node);
+            link = &node.left;
+        }
+    }
+
+    /// Descend from the node to the greatest node in its subtree.
+    fn push_right_edge(&mut self, mut link: &'a Option<Box<Node<T>>>) {
+        while let Some(node) = link {
+            self.path.push(node);
+            link = &node.right;
+        }
+    }
+
+}
+
+impl <'a, T: PartialEq + PartialOrd> Iterator for Cursor<'a, T> {
+    type Item = &'a T;
+
+    /// Move to the next value and return it, or None on reaching (or being at) the ghost position.
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.path.is_empty() {
+            return None;
+        }
+        self.step_forward();
+        self.peek()
+    }
+}
+
+impl <'a, T: PartialEq + PartialOrd> FusedIterator for Cursor<'a, T> {}
+
+/// A cursor like `Cursor` that can also remove the value at its position, for editing a Javlt in one pass.
+/// Get one with `cursor_mut_at`, `cursor_mut_front` or `cursor_mut_back`.  Because it borrows the tree mutably
+/// while changing its structure, it remembers its position by value, so each step is a search from the root.
+///
+///     use jtree::Javlt;
+///
+///     let mut my_tree = Javlt::from_collection(1..=10);
+///     let mut cursor = my_tree.cursor_mut_at(&4);
+///     cursor.remove_current();
+///     cursor.remove_current();
+///     assert_eq!( Some(&6), cursor.peek() );
+///     assert_eq!( Some(&3), cursor.move_prev() );
+///     assert_eq!( vec!(1,2,3,6,7,8,9,10), my_tree.as_vec() );
+pub struct CursorMut<'a, T: PartialEq + PartialOrd + Clone> {
+    tree: &'a mut Javlt<T>,
+    current: Option<T>, // the value at the cursor's position, or None at the ghost position
+}
+
+impl <'a, T: PartialEq + PartialOrd + Clone> CursorMut<'a, T> {
+
+    fn new(tree: &'a mut Javlt<T>, current: Option<T>) -> Self {
+        Self { tree, current }
+    }
+
+    /// Returns the value at the cursor's position, without moving.
+    pub fn peek(&self) -> Option<&T> {
+        self.current.as_ref()
+    }
+
+    /// Move to the next value and return it, or None on reaching the ghost position.
+    pub fn move_next(&mut self) -> Option<&T> {
+        self.current = self.value_after().cloned();
+        self.current.as_ref()
+    }
+
+    /// Move to the previous value and return it, or None on reaching the ghost position.
+    pub fn move_prev(&mut self) -> Option<&T> {
+        self.current = self.value_before().cloned();
+        self.current.as_ref()
+    }
+
+    /// Remove the value at the cursor's position and return it, moving the cursor on to the next value.
+    /// At the ghost position, nothing is removed and None is returned.
+    pub fn remove_current(&mut self) -> Option<T> {
+        let removed = self.current.take()?;
+        self.current = self.successor_of(&removed).cloned();
+        let _ = self.tree.drop_value(removed.clone());
+        Some(removed)
+    }
+
+    /// The value after the cursor's position.
+    fn value_after(&self) -> Option<&T> {
+        return match &self.current {
+            None => Cursor::front(&self.tree.root).peek(),
+            Some(value) => self.successor_of(value),
+        };
+    }
+
+    /// The value after the given one, which is in the tree.
+    fn successor_of(&self, value: &T) -> Option<&T> {
+        let mut cursor = Cursor::new(&self.tree.root);
+        cursor.seek(value);
+        cursor.next()
+    }
+
+    /// The value before the cursor's position.
+    fn value_before(&self) -> Option<&T> {
+        let mut cursor = Cursor::new(&self.tree.root);
+        match &self.current {
+            None => (),
+            Some(value) => cursor.seek(value),
+        }
+        cursor.prev()
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd + Clone> Node<T> {
+
+    /// Recursively add this (sub)tree's nodes to the graph, with edges from each node to its children.
+    /// Returns the index of this node in the graph.
+    #[cfg(feature = "petgraph")]
+    pub fn add_to_graph(&self, graph: &mut DiGraph<T, Branch>) -> NodeIndex {
+        let index = graph.add_node(self.value.clone());
+        match &self.left {
+            Some(node) => {
+                let child = node.add_to_graph(graph);
+                graph.add_edge(index, child, Branch::Left);
+            },
+            None => (),
+        }
+        match &self.right {
+            Some(node) => {
+                let child = node.add_to_graph(graph);
+                graph.add_edge(index, child, Branch::Right);
+            },
+            None => (),
+        }
+        index
+    }
+
+    /// Recursively add values to the borrowed vector, traversing the tree from left to right.
+    pub fn collect_values_l_to_r(&self, value_vector: &mut Vec<T>) {
+        match &self.left {
+            Some(node) => node.collect_values_l_to_r(value_vector),
+            None => (),
+        }
+        value_vector.push(self.value.clone());
+        match &self.right {
+            Some(node) => node.collect_values_l_to_r(value_vector),
+            None => (),
+        }
+    }
+
+    /// Recursively add values to the borrowed vector, traversing the tree from left to right, until it holds `n` values.
+    pub fn collect_first_n(&self, n: usize, value_vector: &mut Vec<T>) {
+        if value_vector.len() >= n {
+            return;
+        }
+        match &self.left {
+            Some(node) => node.collect_first_n(n, value_vector),
+            None => (),
+        }
+        if value_vector.len() >= n {
+            return;
+        }
+        value_vector.push(self.value.clone());
+        match &self.right {
+            Some(node) => node.collect_first_n(n, value_vector),
+            None => (),
+        }
+    }
+
+    /// Recursively add values to the borrowed vector, traversing the tree from right to left, until it holds `n` values.
+    pub fn collect_last_n(&self, n: usize, value_vector: &mut Vec<T>) {
+        if value_vector.len() >= n {
+            return;
+        }
+        match &self.right {
+            Some(node) => node.collect_last_n(n, value_vector),
+            None => (),
+        }
+        if value_vector.len() >= n {
+            return;
+        }
+        value_vector.push(self.value.clone());
+        match &self.left {
+            Some(node) => node.collect_last_n(n, value_vector),
+            None => (),
+        }
+    }
+
+    /// Recursively add values to the borrowed vector, traversing the tree from right to left.
+    pub fn collect_values_r_to_l(&self, value_vector: &mut Vec<T>) {
+        match &self.right {
+            Some(node) => node.collect_values_r_to_l(value_vector),
+            None => (),
+        }
+        value_vector.push(self.value.clone());
+        match &self.left {
+            Some(node) => node.collect_values_r_to_l(value_vector),
+            None => (),
         }
     }
 
-    /// Returns the largest/highest value in this (sub)tree.
-    pub fn greatest_value(&self) -> T {
-        return match &self.right {
-            None => self.value.clone(),
-            Some(right_child) => right_child.greatest_value(),
+}
+
+#[cfg(feature = "rayon")]
+impl <T: PartialEq + PartialOrd + Clone + Send + Sync> Node<T> {
+
+    /// Recursively build a perfectly balanced (sub)tree from sorted, unique values, building the two halves of
+    /// subtrees bigger than `PARALLEL_BUILD_THRESHOLD` on separate threads.
+    fn from_sorted_parallel(values: &[&T]) -> Option<Box<Node<T>>> {
+        if values.is_empty() {
+            return None;
         }
+        let middle = values.len() / 2;
+        let (left, right) = match values.len() > PARALLEL_BUILD_THRESHOLD {
+            true => rayon::join(|| Node::from_sorted_parallel(&values[..middle]), || Node::from_sorted_parallel(&values[middle + 1..])),
+            false => (Node::from_sorted_parallel(&values[..middle]), Node::from_sorted_parallel(&values[middle + 1..])),
+        };
+        let mut node = Node::new(values[middle].clone());
+        node.left = left;
+        node.right = right;
+        node.height = node.compute_height();
+        Some(Box::new(node))
     }
 
-    /// Recursively add values to the borrowed vector, traversing the tree from left to right.
-    pub fn collect_values_l_to_r(&self, value_vector: &mut Vec<T>) {
+}
+
+/// Below this many values, `from_slice_parallel` builds a subtree on the current thread, as handing it to
+/// another would cost more than it saves.
+#[cfg(feature = "rayon")]
+const PARALLEL_BUILD_THRESHOLD: usize = 4096;
+
+impl <T: PartialEq + PartialOrd + Hash> Node<T> {
+
+    /// Recursively feed this (sub)tree's values to the hasher, from left to right.
+    fn hash_values<H: Hasher>(&self, hasher: &mut H) {
         match &self.left {
-            Some(node) => node.collect_values_l_to_r(value_vector),
+            Some(node) => node.hash_values(hasher),
             None => (),
         }
-        value_vector.push(self.value.clone());
+        self.value.hash(hasher);
         match &self.right {
-            Some(node) => node.collect_values_l_to_r(value_vector),
+            Some(node) => node.hash_values(hasher),
             None => (),
         }
     }
 
-    /// Recursively add values to the borrowed vector, traversing the tree from right to left.
-    pub fn collect_values_r_to_l(&self, value_vector: &mut Vec<T>) {
+}
+
+impl <T: PartialEq + PartialOrd + Codec> Node<T> {
+
+    /// Recursively encode this (sub)tree's node records, in pre-order.
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        let mut flags = 0;
+        if self.left.is_some() {
+            flags |= HAS_LEFT;
+        }
+        if self.right.is_some() {
+            flags |= HAS_RIGHT;
+        }
+        flags.encode(bytes);
+        self.value.encode(bytes);
+        match &self.left {
+            Some(node) => node.encode(bytes),
+            None => (),
+        }
         match &self.right {
-            Some(node) => node.collect_values_r_to_l(value_vector),
+            Some(node) => node.encode(bytes),
             None => (),
         }
-        value_vector.push(self.value.clone());
+    }
+
+    /// Recursively add this (sub)tree's node flags to the compact format's shape, in pre-order.
+    fn encode_shape(&self, shape: &mut ShapeWriter) {
+        let mut flags = 0;
+        if self.left.is_some() {
+            flags |= HAS_LEFT;
+        }
+        if self.right.is_some() {
+            flags |= HAS_RIGHT;
+        }
+        shape.push(flags);
         match &self.left {
-            Some(node) => node.collect_values_r_to_l(value_vector),
+            Some(node) => node.encode_shape(shape),
+            None => (),
+        }
+        match &self.right {
+            Some(node) => node.encode_shape(shape),
             None => (),
         }
     }
 
-    /// If the value exists in this sub-tree, drop it, returning to the parent
-    /// a pointer to the Node that replaces this one, or None if this node
-    /// is removed by the change.  Called recursively.
-    /// 
-    /// Because 'self' is consumed, we need to return a node to replace it
-    /// even in case of error, hence we're returning a tuple of Result (to be interpreted)
-    /// and Option<Box<Node>> to replace the current node in the parent.
-    /// 
-    pub fn drop_value(mut self, value: T) -> (Result<(),TreeError>, Option<Box<Node<T>>>) {
-
-        // if the value is less than this node's value, and we have a left child, call 'drop_value' on the left child
-        if value < self.value {
-            match self.left {
-                None => return (Err(TreeError::ValueNotFound), Some(Box::new(self))),
-                Some(left_child) => {
-                    match left_child.drop_value(value) {
-                        (Err(_), new_node) => {
-                            self.left = new_node;
-                            return (Err(TreeError::ValueNotFound), Some(Box::new(self)));
-                        },
-                        (Ok(_), new_node) => {
-                            self.left = new_node;
-                            self.rebalance();
-                            self.height = self.compute_height();
-                            return (Ok(()), Some(Box::new(self)));
-                        } 
-                    }
-                }
-            }
+    /// The greatest height an AVL tree of `size` nodes can have, which bounds how deep decoding goes.  The
+    /// sparsest tree of each height has one node more than the sparsest trees of the two heights below it put
+    /// together, so this grows as about 1.44·log2(size).
+    fn height_limit(size: u32) -> usize {
+        let (mut height, mut fewest, mut fewest_taller) = (0, 0u64, 1u64);
+        while fewest_taller <= size as u64 {
+            (fewest, fewest_taller) = (fewest_taller, fewest_taller + fewest + 1);
+            height += 1;
         }
-        // if the value is greater than this node's value, and we have a right child, call 'drop_value' on the right child
-        else if value > self.value {
-            match self.right {
-                None => return (Err(TreeError::ValueNotFound), Some(Box::new(self))),
-                Some(right_child) => {
-                    match right_child.drop_value(value) {
-                        (Err(_), new_node) => {
-                            self.right = new_node;
-                            return (Err(TreeError::ValueNotFound), Some(Box::new(self)));
-                        },
-                        (Ok(_), new_node) => {
-                            self.right = new_node;
-                            self.rebalance();
-                            self.height = self.compute_height();
-                            return (Ok(()), Some(Box::new(self)));
-                        } 
-                    }
-                }
-            }
+        height
+    }
+
+    /// Recursively rebuild a (sub)tree from the compact format's shape, reading its flags in pre-order and its
+    /// values in order: the left subtree's, then this node's, then the right subtree's.  The node is at `depth`,
+    /// which mustn't pass `depth_limit`.
+    fn decode_shape(shape: &mut ShapeReader, reader: &mut Reader, depth: usize, depth_limit: usize) -> Result<Box<Node<T>>,TreeError<T>> {
+        if depth > depth_limit {
+            return Err(shape.corrupt(format!("the tree is deeper than a balanced tree of its size can be ({depth_limit})")));
         }
-        // if this node has the exact value:
-        else {
-            // - if it has no children, just replace it with None
-            if self.is_leaf() {
-                return (Ok(()), None);
-            }
-            // - if it has no left branch, replace it with its right child (and subtree)
-            if self.left.is_none() {
-                return (Ok(()), self.right);
-            }
-            // - if it has no right branch, replace it with its left child (and subtree)
-            if self.right.is_none() {
-                return (Ok(()), self.left);
-            }
-            // - if the root's right child is a leaf, replace its value with its right leaf (and drop that leaf)
-            let right_child = self.right.as_ref().unwrap();
-            if right_child.is_leaf() {
-                self.value = right_child.value.clone();
-                self.right = None;
-                self.rebalance();
-                self.height = self.compute_height();
-                return (Ok(()), Some(Box::new(self)));
-            }
-            // - otherwise, if the root's left child is a leaf, replace its value with its left leaf (and drop that leaf)
-            let left_child = self.left.as_ref().unwrap();
-            if left_child.is_leaf() {
-                self.value = left_child.value.clone();
-                self.left = None;
-                self.rebalance();
-                self.height = self.compute_height();
-                return (Ok(()), Some(Box::new(self)));
-            }
-            // - if we get to this point, both children are branches. Replace the root's value with its immediate successor, 
-            //   then recursively tell its right branch to remove that successor
-            self.value = right_child.least_value();
-            self.right = self.right.unwrap().drop_value(self.value.clone()).1;
-            self.rebalance();
-            self.height = self.compute_height();
-            return (Ok(()), Some(Box::new(self)));
+        let flags = shape.next()?;
+        let left = match flags & HAS_LEFT {
+            0 => None,
+            _ => Some(Node::decode_shape(shape, reader, depth + 1, depth_limit)?),
+        };
+        let mut node = Node::new(reader.read()?);
+        node.left = left;
+        if flags & HAS_RIGHT != 0 {
+            node.right = Some(Node::decode_shape(shape, reader, depth + 1, depth_limit)?);
+        }
+        node.height = node.compute_height();
+        Ok(Box::new(node))
+    }
+
+    /// Recursively decode a (sub)tree encoded by `encode`, whose root is at `depth`, which mustn't pass
+    /// `depth_limit`.
+    fn decode(reader: &mut Reader, depth: usize, depth_limit: usize) -> Result<Box<Node<T>>,TreeError<T>> {
+        let offset = reader.offset();
+        if depth > depth_limit {
+            return Err(TreeError::DeserializationCorrupt { offset, reason: format!("the tree is deeper than a balanced tree of its size can be ({depth_limit})") });
+        }
+        let flags: u8 = reader.read()?;
+        if flags & !(HAS_LEFT | HAS_RIGHT) != 0 {
+            return Err(TreeError::DeserializationCorrupt { offset, reason: format!("{flags:#x} isn't a valid node record") });
+        }
+        let mut node = Node::new(reader.read()?);
+        if flags & HAS_LEFT != 0 {
+            node.left = Some(Node::decode(reader, depth + 1, depth_limit)?);
+        }
+        if flags & HAS_RIGHT != 0 {
+            node.right = Some(Node::decode(reader, depth + 1, depth_limit)?);
+        }
+        node.height = node.compute_height();
+        Ok(Box::new(node))
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd + std::fmt::Debug> Node<T> {
+
+    /// Recursively draw this (sub)tree for `to_ascii`: first the right subtree, then this node, then the left subtree.
+    /// The `prefix` holds the indentation and `|` lines carried down from ancestors, and the `link` is the
+    /// branch leading up (`/-- `) or down (`\-- `) to this node from its parent, or empty for the root.
+    fn draw(&self, drawing: &mut String, prefix: &str, link: &str) {
+        let (above, below) = match link {
+            "" => (String::from(prefix), String::from(prefix)),
+            "/-- " => (format!("{prefix}    "), format!("{prefix}|   ")),
+            _ => (format!("{prefix}|   "), format!("{prefix}    ")),
+        };
+        match &self.right {
+            Some(node) => node.draw(drawing, &above, "/-- "),
+            None => (),
         }
+        drawing.push_str(&format!("{prefix}{link}{}\n", self.label()));
+        match &self.left {
+            Some(node) => node.draw(drawing, &below, "\\-- "),
+            None => (),
+        }
+    }
 
+    /// How this node is shown by `to_ascii`: its value and the height of its subtree.
+    fn label(&self) -> String {
+        format!("{:?} (h{})", self.value, self.height)
     }
 
 }
@@ -495,7 +2432,7 @@ mod tests {
 
         assert_eq!( 3, my_tree.get_size() );
         assert_eq!(
-            Err(TreeError::ValueAlreadyStored),
+            Err(TreeError::ValueAlreadyStored { value: 5 }),
             my_tree.add(5) // can't add duplicates
         );
     }
@@ -515,7 +2452,7 @@ mod tests {
 
         assert_eq!( 3, my_tree.get_size() );
         assert_eq!(
-            Err(TreeError::ValueAlreadyStored),
+            Err(TreeError::ValueAlreadyStored { value: 4 }),
             my_tree.add(4) // can't add duplicates
         );
     }
@@ -538,7 +2475,7 @@ mod tests {
 
         assert_eq!( 6, my_tree.get_size() );
         assert_eq!(
-            Err(TreeError::ValueAlreadyStored),
+            Err(TreeError::ValueAlreadyStored { value: 7 }),
             my_tree.add(7) // can't add duplicates
         );
     }
@@ -561,7 +2498,7 @@ mod tests {
 
         assert_eq!( 6, my_tree.get_size() );
         assert_eq!(
-            Err(TreeError::ValueAlreadyStored),
+            Err(TreeError::ValueAlreadyStored { value: 7 }),
             my_tree.add(7) // can't add duplicates
         );
     }
@@ -569,17 +2506,17 @@ mod tests {
     #[test]
     fn add_collection() {
         let mut my_tree = Javlt::new();
-        assert_eq!( Ok(()), my_tree.add_all_skipping_duplicates(vec!(1,2,3,4,5)));
-        assert_eq!( Ok(()), my_tree.add_all([6,7,8,9,10])); // alias for add_all_skipping_duplicates
+        assert_eq!( Ok(AddReport { inserted: 5, skipped: 0 }), my_tree.add_all_skipping_duplicates(vec!(1,2,3,4,5)));
+        assert_eq!( Ok(AddReport { inserted: 5, skipped: 0 }), my_tree.add_all([6,7,8,9,10])); // alias for add_all_skipping_duplicates
         assert_eq!( 10, my_tree.get_size() );
-        assert_eq!( Ok(()), my_tree.add_all_skipping_duplicates([5,10,15,20])); // duplicates should NOT cause a panic
+        assert_eq!( Ok(AddReport { inserted: 2, skipped: 2 }), my_tree.add_all_skipping_duplicates([5,10,15,20])); // duplicates should NOT cause a panic
         assert_eq!( 12, my_tree.get_size() ); // duplicates were skipped
     }
 
     #[test]
     fn test_contains() {
         let mut my_tree: Javlt<i32> = Javlt::new();
-        assert_eq!( Ok(()), my_tree.add_all_skipping_duplicates(vec!(8,6,7,5,3,0,9)));
+        assert_eq!( Ok(AddReport { inserted: 7, skipped: 0 }), my_tree.add_all_skipping_duplicates(vec!(8,6,7,5,3,0,9)));
         assert_eq!( 7, my_tree.get_size() );
         assert!( my_tree.contains(&7) );
         assert!( my_tree.contains(&8) );
@@ -588,7 +2525,7 @@ mod tests {
     #[test]
     fn collect_values_l_to_r() {
         let mut my_tree = Javlt::new();
-        assert_eq!( Ok(()), my_tree.add_all_skipping_duplicates(vec!(6,3,7,2,4,5))); // this sequence should trigger a rebalance
+        assert_eq!( Ok(AddReport { inserted: 6, skipped: 0 }), my_tree.add_all_skipping_duplicates(vec!(6,3,7,2,4,5))); // this sequence should trigger a rebalance
         let output = my_tree.as_vec();
         println!("{:?}", output);
         assert_eq!(vec!(2,3,4,5,6,7), output);
@@ -597,7 +2534,7 @@ mod tests {
     #[test]
     fn collect_values_r_to_l() {
         let mut my_tree = Javlt::new();
-        assert_eq!( Ok(()), my_tree.add_all_skipping_duplicates(vec!(2,1,6,4,7,3))); // this sequence should trigger a rebalance
+        assert_eq!( Ok(AddReport { inserted: 6, skipped: 0 }), my_tree.add_all_skipping_duplicates(vec!(2,1,6,4,7,3))); // this sequence should trigger a rebalance
         let output = my_tree.as_vec_r_to_l();
         println!("{:?}", output);
         assert_eq!(vec!(7,6,4,3,2,1), output);
@@ -609,13 +2546,13 @@ mod tests {
         // an empty tree
         let mut my_tree = Javlt::new();
         assert_eq!( 0, my_tree.get_size() );
-        assert_eq!( Err(TreeError::ValueNotFound), my_tree.drop_value(1) );
+        assert_eq!( Err(TreeError::ValueNotFound { value: 1 }), my_tree.drop_value(1) );
 
         // a tree with only a root node
         let mut my_tree = Javlt::new();
         let _ = my_tree.add(1);
         assert_eq!( 1, my_tree.get_size() );
-        assert_eq!( Err(TreeError::ValueNotFound), my_tree.drop_value(4) );
+        assert_eq!( Err(TreeError::ValueNotFound { value: 4 }), my_tree.drop_value(4) );
         assert_eq!( Ok(()), my_tree.drop_value(1) );
         assert_eq!( 0, my_tree.get_size() );
 
@@ -624,7 +2561,7 @@ mod tests {
         let _ = my_tree.add_all_skipping_duplicates(['A','B','C']);
         assert_eq!( Some('B'), my_tree.get_root_value() ); // root is B because of right right rebalancing
         assert_eq!( 3, my_tree.get_size() );
-        assert_eq!( Err(TreeError::ValueNotFound), my_tree.drop_value('Z') );
+        assert_eq!( Err(TreeError::ValueNotFound { value: 'Z' }), my_tree.drop_value('Z') );
         assert_eq!( Ok(()), my_tree.drop_value('A') );
         assert_eq!( vec!('B','C'), my_tree.as_vec_l_to_r() );
         assert_eq!( 2, my_tree.get_size() );
@@ -634,7 +2571,7 @@ mod tests {
         let _ = my_tree.add_all_skipping_duplicates([3,1,2]);
         assert_eq!( Some(2), my_tree.get_root_value() ); // root is 2 because of left right rebalancing
         assert_eq!( 3, my_tree.get_size() );
-        assert_eq!( Err(TreeError::ValueNotFound), my_tree.drop_value(4) );
+        assert_eq!( Err(TreeError::ValueNotFound { value: 4 }), my_tree.drop_value(4) );
         assert_eq!( Ok(()), my_tree.drop_value(3) );
         assert_eq!( vec!(1,2), my_tree.as_vec_l_to_r() );
         assert_eq!( 2, my_tree.get_size() );
@@ -644,7 +2581,7 @@ mod tests {
         let _ = my_tree.add_all_skipping_duplicates([2,1,3]);
         assert_eq!( Some(2), my_tree.get_root_value() ); // root is 2
         assert_eq!( 3, my_tree.get_size() );
-        assert_eq!( Err(TreeError::ValueNotFound), my_tree.drop_value(4) );
+        assert_eq!( Err(TreeError::ValueNotFound { value: 4 }), my_tree.drop_value(4) );
         assert_eq!( Ok(()), my_tree.drop_value(2) );
         assert_eq!( vec!(1,3), my_tree.as_vec_l_to_r() );
         assert_eq!( 2, my_tree.get_size() );
@@ -654,7 +2591,7 @@ mod tests {
         let _ = my_tree.add_all_skipping_duplicates([2,1,5,3,7]);
         assert_eq!( Some(2), my_tree.get_root_value() ); // root is 2
         assert_eq!( 5, my_tree.get_size() );
-        assert_eq!( Err(TreeError::ValueNotFound), my_tree.drop_value(4) );
+        assert_eq!( Err(TreeError::ValueNotFound { value: 4 }), my_tree.drop_value(4) );
         assert_eq!( Ok(()), my_tree.drop_value(2) );
         assert_eq!( vec!(1,3,5,7), my_tree.as_vec_l_to_r() );
         assert_eq!( 4, my_tree.get_size() );
@@ -664,7 +2601,7 @@ mod tests {
         let _ = my_tree.add_all_skipping_duplicates([5,3,8,1,2,7,9]);
         assert_eq!( Some(5), my_tree.get_root_value() ); // root is 5
         assert_eq!( 7, my_tree.get_size() );
-        assert_eq!( Err(TreeError::ValueNotFound), my_tree.drop_value(4) );
+        assert_eq!( Err(TreeError::ValueNotFound { value: 4 }), my_tree.drop_value(4) );
         assert!( my_tree.contains(&5) );
         assert_eq!( Ok(()), my_tree.drop_value(5) );
         assert!( !my_tree.contains(&5) );
@@ -687,4 +2624,690 @@ mod tests {
 
     }
 
+    #[test]
+    fn test_try_add_all() {
+        let mut my_tree = Javlt::from_collection([5,3,8]);
+        assert_eq!( Ok(()), my_tree.try_add_all([1,9]) );
+        assert_eq!( vec!(1,3,5,8,9), my_tree.as_vec() );
+        assert_eq!( Err(TreeError::DuplicateAt { index: 2, value: 8 }), my_tree.try_add_all([4,7,8,2]) ); // 8 is already stored
+        assert_eq!( Err(TreeError::DuplicateAt { index: 2, value: 6 }), my_tree.try_add_all([6,0,6]) ); // duplicates within the batch count too
+        assert_eq!( vec!(1,3,5,8,9), my_tree.as_vec() ); // the failed batches were rolled back
+        assert_eq!( 5, my_tree.get_size() );
+        assert_eq!( Some(5), my_tree.get_root_value() );
+    }
+
+    #[test]
+    fn test_add_all_strict() {
+        let mut my_tree = Javlt::from_collection([5,3,8]);
+        assert_eq!( Ok(()), my_tree.add_all_strict([1,9]) );
+        assert_eq!( Err(TreeError::DuplicateAt { index: 2, value: 8 }), my_tree.add_all_strict([4,7,8,2]) ); // 8 is already stored
+        assert_eq!( vec!(1,3,4,5,7,8,9), my_tree.as_vec() ); // 4 and 7 stay, and 2 was never reached
+        assert_eq!( Err(TreeError::DuplicateAt { index: 2, value: 6 }), my_tree.add_all_strict([6,0,6]) ); // duplicates within the batch count too
+        assert_eq!( vec!(0,1,3,4,5,6,7,8,9), my_tree.as_vec() );
+        assert_eq!( 9, my_tree.get_size() );
+    }
+
+    #[test]
+    fn test_check_invariants() {
+        let mut my_tree = Javlt::new();
+        assert_eq!( Ok(()), my_tree.check_invariants() );
+        // a pseudo-random mix of adds and drops, checking the invariants after every change
+        let mut state: u32 = 12345;
+        for _ in 0..2000 {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let value = state % 64;
+            if state.is_multiple_of(3) {
+                let _ = my_tree.drop_value(value);
+            } else {
+                let _ = my_tree.add(value);
+            }
+            assert_eq!( Ok(()), my_tree.check_invariants() );
+        }
+
+        // break a height on purpose
+        let mut my_tree = Javlt::from_collection([2,1,3]);
+        my_tree.root.as_mut().unwrap().left.as_mut().unwrap().height = 2;
+        assert_eq!( Err(TreeError::InvariantViolation("node at root.left has height 2 but should be 1".to_string())), my_tree.check_invariants() );
+    }
+
+    #[test]
+    #[cfg(feature = "paranoid")]
+    #[should_panic(expected = "Javlt::add broke the tree: size is 5 but the tree holds 4 values (size 5, shape (((. 1 .) 2 .) 3 (. 1 .)))")]
+    fn paranoid_mode_panics_on_broken_invariants() {
+        let mut my_tree = Javlt::from_collection([2,1,3]);
+        my_tree.size = 4; // break the size bookkeeping, so the next change trips the check
+        let _ = my_tree.add(0);
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut my_tree = Javlt::<u32>::new();
+        assert_eq!( TreeStats { rotations: Some(0), ..TreeStats::default() }, my_tree.stats() );
+        // ascending inserts force a single rotation after each of 3, 5, 6 and 7, leaving a perfect tree
+        let _ = my_tree.add_all(1..=7);
+        let stats = my_tree.stats();
+        assert_eq!( 7, stats.node_count );
+        assert_eq!( 3, stats.height );
+        assert_eq!( 4, stats.leaf_count );
+        assert_eq!( 3, stats.internal_count );
+        assert_eq!( 2, stats.min_leaf_depth );
+        assert_eq!( 2, stats.max_leaf_depth );
+        assert_eq!( 2.0, stats.avg_leaf_depth );
+        assert_eq!( Some(4), stats.rotations );
+        // a Right Left case counts as two rotations
+        let mut zigzag = Javlt::from_collection([1,3,2]);
+        assert_eq!( Some(2), zigzag.stats().rotations );
+        // rotations made by deletions are counted too
+        let _ = zigzag.add(4);
+        let _ = zigzag.drop_value(1);
+        assert_eq!( Some(3), zigzag.stats().rotations );
+    }
+
+    #[test]
+    fn test_to_ascii() {
+        let mut my_tree = Javlt::<u32>::new();
+        assert_eq!( "", my_tree.to_ascii() );
+        let _ = my_tree.add_all([1,2,3,4]);
+        assert_eq!( "    /-- 4 (h1)\n/-- 3 (h2)\n2 (h3)\n\\-- 1 (h1)\n", my_tree.to_ascii() );
+    }
+
+    #[test]
+    fn test_to_and_from_bytes() {
+        let my_tree = Javlt::from_collection(1..=20u64);
+        let copy = Javlt::<u64>::from_bytes(&my_tree.to_bytes()).unwrap();
+        assert_eq!( my_tree.to_ascii(), copy.to_ascii() );
+        assert_eq!( Some(0), copy.stats().rotations );
+
+        // a tree that isn't balanced is rejected: this is the 1-byte records for 2, 3 and 4, chained to the right
+        let mut bytes = Javlt::from_collection([2u8,1,3,4]).to_bytes();
+        bytes[7] = 3; // the size
+        bytes[11] = HAS_RIGHT; // the root's flags
+        bytes.drain(13..15); // the record for 1
+        assert!( matches!( Javlt::<u8>::from_bytes(&bytes), Err(TreeError::DeserializationCorrupt { .. }) ) );
+
+        // nor is a chain deeper than a tree of its size could be, however long: decoding stops at its third record
+        let mut chain = Javlt::from_collection([1u8]).to_bytes();
+        chain[7] = 3;
+        chain.truncate(11);
+        for value in 0..100_000u32 {
+            chain.extend([HAS_LEFT, value as u8]);
+        }
+        assert_eq!( Some(TreeError::DeserializationCorrupt { offset: 15, reason: String::from("the tree is deeper than a balanced tree of its size can be (2)") }),
+            Javlt::<u8>::from_bytes(&chain).err() );
+        assert_eq!( vec!(0, 1, 2, 2, 3, 3, 3, 4, 4), (0..9).map(Node::<u8>::height_limit).collect::<Vec<_>>() );
+        assert_eq!( 45, Node::<u8>::height_limit(u32::MAX) );
+    }
+
+    #[test]
+    fn test_to_and_from_compact_bytes() {
+        let my_tree = Javlt::from_collection((0..1000u32).map(|n| n * 7919 % 1000));
+        let bytes = my_tree.to_compact_bytes();
+        assert_eq!( 7 + 4 + 250 + 4000, bytes.len() ); // header, size, shape, values
+        let copy = Javlt::<u32>::from_compact_bytes(&bytes).unwrap();
+        assert_eq!( my_tree.to_ascii(), copy.to_ascii() );
+        assert_eq!( 0, Javlt::<u32>::from_compact_bytes(&Javlt::<u32>::new().to_compact_bytes()).unwrap().get_size() );
+
+        // a shape with fewer nodes than the size says is rejected: the root of [2,1,3] loses its right child
+        let mut bytes = Javlt::from_collection([2u8,1,3]).to_compact_bytes();
+        bytes[11] = HAS_LEFT;
+        assert_eq!( Some(TreeError::DeserializationCorrupt { offset: 11, reason: String::from("the shape has 2 nodes but the tree has 3") }),
+            Javlt::<u8>::from_compact_bytes(&bytes).err() );
+        // as is a shape that runs deeper than a tree of its size could: here, a chain of 1000 left children
+        let mut chain = Javlt::from_collection(0..1000u16).to_compact_bytes();
+        chain[11..261].fill(HAS_LEFT * 0b0101_0101);
+        assert!( matches!( Javlt::<u16>::from_compact_bytes(&chain), Err(TreeError::DeserializationCorrupt { offset: 14, .. }) ) );
+        // and the ordinary format isn't mistaken for the compact one
+        assert!( matches!( Javlt::<u8>::from_compact_bytes(&Javlt::from_collection([1u8]).to_bytes()), Err(TreeError::DeserializationCorrupt { offset: 6, .. }) ) );
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!("jtree_javlt_test_{}.bin", std::process::id()));
+        let my_tree = Javlt::from_collection(["b", "a", "c"].map(String::from));
+        assert_eq!( Ok(()), my_tree.save(&path) );
+        let loaded = Javlt::<String>::load(&path).unwrap();
+        assert_eq!( my_tree.as_vec(), loaded.as_vec() );
+        let _ = fs::remove_file(&path);
+        // I/O problems are passed along, with the io::Error as the source
+        let error = Javlt::<String>::load(&path).unwrap_err();
+        assert!( matches!( &error, TreeError::Io(io_error) if io_error.kind() == std::io::ErrorKind::NotFound ) );
+        assert!( std::error::Error::source(&error).is_some() );
+    }
+
+    #[test]
+    fn test_digest() {
+        use std::hash::DefaultHasher;
+        let my_tree = Javlt::from_collection(1..=10);
+        let same_values = Javlt::from_collection((1..=10).rev()); // rotated into a different shape
+        assert_eq!( my_tree.digest::<DefaultHasher>(), same_values.digest::<DefaultHasher>() );
+        assert_ne!( my_tree.digest::<DefaultHasher>(), Javlt::from_collection(2..=11).digest::<DefaultHasher>() );
+        assert_ne!( Javlt::<u32>::new().digest::<DefaultHasher>(), Javlt::from_collection([0]).digest::<DefaultHasher>() );
+    }
+
+    #[test]
+    fn test_diff() {
+        let before = Javlt::from_collection(1..=100);
+        let after = Javlt::from_collection((50..=150).rev());
+        let diff = before.diff(&after);
+        assert_eq!( (1..50).collect::<Vec<_>>(), diff.only_in_self );
+        assert_eq!( (101..=150).collect::<Vec<_>>(), diff.only_in_other );
+        assert_eq!( diff.only_in_other, after.diff(&before).only_in_self );
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut replica = Javlt::from_collection(1..=10);
+        let target = Javlt::from_collection(5..=15);
+        assert_eq!( Ok(()), replica.apply(&replica.diff(&target)) );
+        assert_eq!( target.as_vec(), replica.as_vec() );
+        // a diff that doesn't fit the tree changes nothing
+        let stale = TreeDiff { only_in_self: vec!(5,6), only_in_other: vec!(1,2,15) };
+        assert_eq!( Err(TreeError::ValueAlreadyStored { value: 15 }), replica.apply(&stale) );
+        assert_eq!( target.as_vec(), replica.as_vec() );
+        assert_eq!( Ok(()), replica.check_invariants() );
+    }
+
+    #[test]
+    #[cfg(feature = "petgraph")]
+    fn test_to_petgraph() {
+        let graph = Javlt::from_collection(1..=7).to_petgraph();
+        assert_eq!( 7, graph.node_count() );
+        assert_eq!( 6, graph.edge_count() );
+        assert_eq!( 4, graph[NodeIndex::new(0)] );
+        // the deepest nodes are two edges from the root
+        let depths = petgraph::algo::dijkstra(&graph, NodeIndex::new(0), None, |_| 1);
+        assert_eq!( Some(&2), depths.values().max() );
+    }
+
+    #[test]
+    fn test_visits() {
+        let my_tree = Javlt::from_collection(1..=7);
+        let mut values = Vec::new();
+        my_tree.visit_in_order(|value| values.push(*value));
+        assert_eq!( my_tree.as_vec(), values );
+        values.clear();
+        my_tree.visit_pre_order(|value| values.push(*value));
+        assert_eq!( vec!(4,2,1,3,6,5,7), values );
+        values.clear();
+        my_tree.visit_post_order(|value| values.push(*value));
+        assert_eq!( vec!(1,3,2,5,7,6,4), values );
+        // stop at the first value over 4, having only visited the ones before it
+        values.clear();
+        let found = my_tree.try_visit_in_order(|value| {
+            if *value > 4 {
+                return ControlFlow::Break(*value);
+            }
+            values.push(*value);
+            ControlFlow::Continue(())
+        });
+        assert_eq!( ControlFlow::Break(5), found );
+        assert_eq!( vec!(1,2,3,4), values );
+        assert_eq!( ControlFlow::<u32>::Continue(()), Javlt::new().try_visit_post_order(|_: &u32| ControlFlow::Break(0)) );
+    }
+
+    #[test]
+    fn test_leaves() {
+        let mut my_tree = Javlt::from_collection(1..=7);
+        let _ = my_tree.drop_value(1);
+        let _ = my_tree.drop_value(3);
+        assert_eq!( vec!(&2,&5,&7), my_tree.leaves().collect::<Vec<_>>() );
+        assert_eq!( my_tree.stats().leaf_count, my_tree.leaves().count() );
+    }
+
+    #[test]
+    fn test_iter_with_depth() {
+        let my_tree = Javlt::from_collection(1..=15);
+        let depths: Vec<usize> = my_tree.iter_with_depth().map(|(_, depth)| depth).collect();
+        assert_eq!( vec!(3,2,3,1,3,2,3,0,3,2,3,1,3,2,3), depths );
+        assert_eq!( Some(my_tree.stats().max_leaf_depth), depths.into_iter().max() );
+    }
+
+    #[test]
+    fn test_iter_split() {
+        let my_tree = Javlt::from_collection(0..1000);
+        let (front, back) = my_tree.iter().split();
+        let (front, back): (Vec<u32>, Vec<u32>) = (front.cloned().collect(), back.cloned().collect());
+        assert_eq!( (0..1000).collect::<Vec<_>>(), [front.clone(), back.clone()].concat() );
+        assert!( front.len() > 250 && back.len() > 250 );
+
+        // split every piece again and again, starting partway through: the pieces still hold the values in order
+        fn split_into_pieces<'a>(iter: Iter<'a, u32>, times: usize, pieces: &mut Vec<Vec<&'a u32>>) {
+            if times == 0 {
+                pieces.push(iter.collect());
+                return;
+            }
+            let (front, back) = iter.split();
+            split_into_pieces(front, times - 1, pieces);
+            split_into_pieces(back, times - 1, pieces);
+        }
+        let mut iter = my_tree.iter();
+        iter.nth(9);
+        let mut pieces = Vec::new();
+        split_into_pieces(iter, 16, &mut pieces);
+        assert!( pieces.iter().all(|piece| piece.len() <= 1) );
+        assert_eq!( (10..1000).collect::<Vec<_>>(), pieces.into_iter().flatten().cloned().collect::<Vec<_>>() );
+        assert_eq!( 0, Javlt::<u32>::new().iter().split().1.count() );
+    }
+
+    #[test]
+    fn test_cursor() {
+        let my_tree = Javlt::from_collection((0..100).map(|n| n * 2));
+        // walk outwards from a probe point
+        let forward = my_tree.cursor_at(&51);
+        let mut backward = my_tree.cursor_at(&51);
+        assert_eq!( Some(&52), forward.peek() );
+        assert_eq!( Some(&50), backward.prev() );
+        assert_eq!( vec!(&54,&56), forward.take(2).collect::<Vec<_>>() );
+        assert_eq!( Some(&48), backward.prev() );
+        // every value in both directions
+        assert_eq!( my_tree.as_vec()[1..], my_tree.cursor_front().copied().collect::<Vec<_>>() );
+        let mut cursor = my_tree.cursor_back();
+        let mut count = 1;
+        while cursor.prev().is_some() {
+            count += 1;
+        }
+        assert_eq!( 100, count );
+    }
+
+    #[test]
+    fn test_cursor_mut() {
+        // remove every other value in one pass, keeping the tree balanced throughout
+        let mut my_tree = Javlt::from_collection(0..100);
+        let mut cursor = my_tree.cursor_mut_front();
+        while cursor.peek().is_some() {
+            cursor.remove_current();
+            cursor.move_next();
+        }
+        assert_eq!( (0..100).filter(|n| n % 2 == 1).collect::<Vec<_>>(), my_tree.as_vec() );
+        assert_eq!( Ok(()), my_tree.check_invariants() );
+        let mut cursor = my_tree.cursor_mut_back();
+        assert_eq!( Some(99), cursor.remove_current() );
+        assert_eq!( None, cursor.peek() );
+        assert_eq!( Some(&1), cursor.move_next() );
+        assert_eq!( None, Javlt::<u32>::new().cursor_mut_front().remove_current() );
+    }
+
+    #[test]
+    fn test_drain() {
+        let mut my_tree = Javlt::from_collection((1..=50).map(|n| n.to_string()));
+        let expected = my_tree.as_vec();
+        assert_eq!( expected, my_tree.drain().collect::<Vec<_>>() );
+        assert_eq!( 0, my_tree.get_size() );
+        // values not taken from the iterator are dropped along with it
+        let _ = my_tree.add_all(["x".to_string(), "y".to_string()]);
+        assert_eq!( Some("x".to_string()), my_tree.drain().next() );
+        assert_eq!( None, my_tree.least_value() );
+        assert_eq!( Ok(()), my_tree.check_invariants() );
+    }
+
+    #[test]
+    fn test_merge_iter() {
+        let tree_a = Javlt::from_collection((0..20).filter(|n| n % 2 == 0));
+        let tree_b = Javlt::from_collection((0..20).filter(|n| n % 3 == 0));
+        let union: Vec<u32> = tree_a.merge_iter(&tree_b, Duplicates::Union).copied().collect();
+        assert_eq!( (0..20).filter(|n| n % 2 == 0 || n % 3 == 0).collect::<Vec<_>>(), union );
+        assert_eq!( 17, tree_a.merge_iter(&tree_b, Duplicates::KeepBoth).count() );
+        assert_eq!( 10, tree_a.merge_iter(&Javlt::new(), Duplicates::Union).count() );
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn test_from_slice_parallel() {
+        // big enough for the halves to be built on separate threads, with every value twice
+        let values: Vec<u32> = (0..20_000).map(|n| (n * 7919) % 10_000).collect();
+        let my_tree = Javlt::from_slice_parallel(&values).unwrap();
+        assert_eq!( Ok(()), my_tree.check_invariants() );
+        assert_eq!( 10_000, my_tree.get_size() );
+        assert_eq!( (0..10_000).collect::<Vec<_>>(), my_tree.as_vec() );
+        assert_eq!( 14, my_tree.stats().height );
+        assert_eq!( 0, Javlt::<u32>::from_slice_parallel(&[]).unwrap().get_size() );
+        assert!( matches!( Javlt::from_slice_parallel(&[1.0, f64::NAN]), Err(TreeError::Incomparable { .. }) ) );
+        // odd and even numbers can't be ordered against each other, though each can be against itself
+        #[derive(Clone, Debug, PartialEq)]
+        struct Parity(u32);
+        impl PartialOrd for Parity {
+            fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+                (self.0 % 2 == other.0 % 2).then(|| self.0.cmp(&other.0))
+            }
+        }
+        assert!( matches!( Javlt::from_slice_parallel(&[Parity(3), Parity(2), Parity(1)]), Err(TreeError::Incomparable { .. }) ) );
+        assert_eq!( 2, Javlt::from_slice_parallel(&[Parity(3), Parity(1)]).unwrap().get_size() );
+    }
+
+    #[test]
+    fn get_or_insert_keeps_the_stored_copy() {
+        // values compare by the first field only, so the stored copy can be told apart by the second
+        #[derive(Debug)]
+        struct Tagged(u32, &'static str);
+        impl PartialEq for Tagged {
+            fn eq(&self, other: &Self) -> bool { self.0 == other.0 }
+        }
+        impl PartialOrd for Tagged {
+            fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> { self.0.partial_cmp(&other.0) }
+        }
+        let mut tree = Javlt::from_collection((0..20).map(|n| Tagged(n, "first")));
+        assert_eq!( "first", tree.get_or_insert(Tagged(7, "second")).1 );
+        assert_eq!( "new", tree.get_or_insert(Tagged(20, "new")).1 );
+        assert_eq!( "made", tree.get_or_insert_with(&Tagged(21, "probe"), || Tagged(21, "made")).1 );
+        assert_eq!( "first", tree.get_or_insert_with(&Tagged(3, "probe"), || panic!("3 is already stored")).1 );
+        // a run of new values, each found again after the rotations it causes
+        for n in (22..100).rev() {
+            assert_eq!( n, tree.get_or_insert_with(&Tagged(n, "probe"), || Tagged(n, "made")).0 );
+        }
+        assert_eq!( 100, tree.get_size() );
+        assert!( tree.get(&Tagged(100, "")).is_none() );
+        assert!( tree.check_invariants().is_ok() );
+    }
+
+    #[test]
+    fn try_insert_finds_values_moved_by_rebalancing() {
+        // values compare by the first field only, so the stored copy can be told apart by the second
+        #[derive(Debug)]
+        struct Tagged(u32, &'static str);
+        impl PartialEq for Tagged {
+            fn eq(&self, other: &Self) -> bool { self.0 == other.0 }
+        }
+        impl PartialOrd for Tagged {
+            fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> { self.0.partial_cmp(&other.0) }
+        }
+        // rising, falling and scattered runs, between them rotating in each of the four ways
+        let runs = [(0..300).collect::<Vec<u32>>(), (0..300).rev().collect(), (0..300).map(|n| n * 7919 % 300).collect()];
+        for run in runs {
+            let mut tree = Javlt::new();
+            for &n in &run {
+                let stored = tree.try_insert(Tagged(n, "first")).unwrap();
+                assert_eq!( (n, "first"), (stored.0, stored.1) );
+            }
+            for &n in &run {
+                let occupied = tree.try_insert(Tagged(n, "second")).unwrap_err();
+                assert_eq!( (n, "first", "second"), (occupied.existing.0, occupied.existing.1, occupied.value.1) );
+            }
+            assert_eq!( 300, tree.get_size() );
+            assert!( tree.check_invariants().is_ok() );
+            assert!( tree.stats().rotations > Some(0) );
+        }
+    }
+
+    #[test]
+    fn insert_returns_the_displaced_value() {
+        #[derive(Clone, Debug)]
+        struct Tagged(u32, &'static str);
+        impl PartialEq for Tagged {
+            fn eq(&self, other: &Self) -> bool { self.0 == other.0 }
+        }
+        impl PartialOrd for Tagged {
+            fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> { self.0.partial_cmp(&other.0) }
+        }
+        let mut tree = Javlt::new();
+        for n in 0..20 {
+            assert!( tree.insert(Tagged(n, "old")).is_none() );
+        }
+        assert_eq!( "old", tree.insert(Tagged(7, "new")).unwrap().1 );
+        assert_eq!( "new", tree.get(&Tagged(7, "")).unwrap().1 );
+        assert_eq!( 20, tree.get_size() );
+        assert!( tree.check_invariants().is_ok() );
+    }
+
+    #[test]
+    fn first_n_and_last_n() {
+        let my_tree = Javlt::from_collection(0..1000);
+        assert_eq!( (0..10).collect::<Vec<_>>(), my_tree.first_n(10) );
+        assert_eq!( (990..1000).collect::<Vec<_>>(), my_tree.last_n(10) );
+        assert_eq!( my_tree.as_vec(), my_tree.last_n(5000) );
+        assert!( my_tree.last_n(0).is_empty() );
+    }
+
+    #[test]
+    fn into_sorted_vec_moves_the_values() {
+        let my_tree = Javlt::from_collection(["pear", "apple", "fig", "kiwi", "date"].map(String::from));
+        let mut addresses = Vec::new();
+        my_tree.visit_in_order(|value| addresses.push(value.as_ptr()));
+        let values = my_tree.into_sorted_vec();
+        assert_eq!( vec!("apple", "date", "fig", "kiwi", "pear"), values );
+        // each string still has the buffer it had in the tree, so none was cloned
+        assert_eq!( addresses, values.iter().map(|value| value.as_ptr()).collect::<Vec<_>>() );
+    }
+
+    #[test]
+    fn values_need_not_be_clone() {
+        #[derive(PartialEq, PartialOrd, Debug)]
+        struct Handle(u32);
+        let mut my_tree = Javlt::new();
+        for n in [5, 2, 8, 1, 3, 9, 7, 4, 6] {
+            assert_eq!( Ok(()), my_tree.add(Handle(n)) );
+        }
+        assert_eq!( Err(TreeError::ValueAlreadyStored { value: Handle(4) }), my_tree.add(Handle(4)) );
+        assert_eq!( Ok(()), my_tree.drop_value(Handle(5)) );
+        assert_eq!( Ok(()), my_tree.drop_value(Handle(2)) );
+        assert_eq!( Ok(()), my_tree.check_invariants() );
+        assert!( my_tree.contains(&Handle(8)) );
+        assert_eq!( Some(&Handle(6)), my_tree.get(&Handle(6)) );
+        assert_eq!( Some(Handle(9)), my_tree.insert(Handle(9)) );
+        assert_eq!( vec!(1,3,4,6,7,8,9), my_tree.into_sorted_vec().into_iter().map(|handle| handle.0).collect::<Vec<_>>() );
+    }
+
+    #[test]
+    fn test_range_by_prefix() {
+        let mut my_tree = Javlt::new();
+        for priority in 0..10u32 {
+            for stamp in 0..(priority % 4) {
+                my_tree.add((priority, stamp, priority * 100 + stamp)).unwrap();
+            }
+        }
+        for priority in 0..11 {
+            let expected: Vec<_> = my_tree.values().into_iter().filter(|entry| entry.0 == priority).collect();
+            assert_eq!( expected, my_tree.range_by_prefix(&(priority,)).collect::<Vec<_>>() );
+        }
+        assert_eq!( vec!(&(7, 2, 702)), my_tree.range_by_prefix(&(7, 2)).collect::<Vec<_>>() );
+        assert_eq!( 0, my_tree.range_by_prefix(&(7, 3)).count() );
+        assert_eq!( 0, Javlt::<(u32, u32)>::new().range_by_prefix(&(1,)).count() );
+    }
+
+    #[test]
+    fn map_and_filtered_build_new_trees() {
+        let my_tree = Javlt::from_collection(0..200);
+        let reversed = my_tree.map(|value| 1000 - value * 2);
+        assert_eq!( 200, reversed.get_size() );
+        assert_eq!( Some(602), reversed.least_value() );
+        assert!( reversed.check_invariants().is_ok() );
+        assert_eq!( 8, reversed.stats().height );
+        let buckets = my_tree.map(|value| value / 10);
+        assert_eq!( (0..20).collect::<Vec<_>>(), buckets.as_vec() );
+        let ratios = my_tree.map(|value| 1.0 / (*value as f64 - 100.0) * 0.0);
+        assert_eq!( vec!(0.0), ratios.as_vec() );
+        let evens = my_tree.filtered(|value| value % 2 == 0);
+        assert_eq!( 100, evens.get_size() );
+        assert!( evens.check_invariants().is_ok() );
+        assert_eq!( 0, my_tree.filtered(|_| false).get_size() );
+        assert_eq!( 200, my_tree.get_size() );
+    }
+
+    #[test]
+    fn partition_moves_every_value() {
+        let my_tree = Javlt::from_collection((0..300).map(|id| id.to_string()));
+        let mut addresses = Vec::new();
+        my_tree.visit_in_order(|value| addresses.push(value.as_ptr()));
+        let (archived, active) = my_tree.partition(|id| id.len() < 3);
+        assert_eq!( 100, archived.get_size() );
+        assert_eq!( 200, active.get_size() );
+        assert!( archived.check_invariants().is_ok() );
+        assert!( active.check_invariants().is_ok() );
+        assert!( active.values().iter().all(|id| id.len() == 3) );
+        // the strings were moved, not cloned
+        let mut moved = Vec::new();
+        archived.visit_in_order(|value| moved.push(value.as_ptr()));
+        active.visit_in_order(|value| moved.push(value.as_ptr()));
+        moved.sort();
+        addresses.sort();
+        assert_eq!( addresses, moved );
+    }
+
+    #[test]
+    fn test_full_tree_refuses_values() {
+        let mut my_tree = Javlt::from_collection([1,2,3]);
+        my_tree.size = u32::MAX; // standing in for four billion values
+        assert_eq!( Err(TreeError::CapacityExceeded { value: 4 }), my_tree.add(4) );
+        assert_eq!( Some(2), my_tree.insert(2) ); // replacing a value needs no room
+        my_tree.size = 3;
+        assert_eq!( vec!(1,2,3), my_tree.as_vec() );
+        assert!( my_tree.check_invariants().is_ok() );
+    }
+
+    #[test]
+    #[should_panic(expected = "as many values as it can count")]
+    fn test_full_tree_panics_on_insert() {
+        let mut my_tree = Javlt::from_collection([1,2,3]);
+        my_tree.size = u32::MAX;
+        my_tree.insert(4);
+    }
+
+    #[test]
+    fn test_extremes_follow_changes() {
+        let mut my_tree = Javlt::new();
+        assert_eq!( (None, None), (my_tree.least(), my_tree.greatest()) );
+        // values added in an order that rotates the least and greatest through many nodes
+        for n in [50, 40, 60, 30, 70, 20, 80, 10, 90, 45, 55, 5, 95] {
+            my_tree.add(n).unwrap();
+            assert_eq!( Ok(()), my_tree.check_invariants() );
+        }
+        assert_eq!( (Some(&5), Some(&95)), (my_tree.least(), my_tree.greatest()) );
+        assert!( my_tree.add(5).is_err() );
+        assert_eq!( Some(5), my_tree.insert(5) );
+        assert_eq!( Some(&5), my_tree.least() );
+        for n in [5, 10, 95, 20] {
+            my_tree.drop_value(n).unwrap();
+            assert_eq!( Ok(()), my_tree.check_invariants() );
+        }
+        assert_eq!( (Some(&30), Some(&90)), (my_tree.least(), my_tree.greatest()) );
+        assert_eq!( (Some(30), Some(90)), (my_tree.least_value(), my_tree.greatest_value()) );
+        let _ = my_tree.drain();
+        assert_eq!( None, my_tree.least() );
+        assert_eq!( Some(&7), Javlt::from_sorted_vec(vec!(7, 8, 9)).least() );
+    }
+
+    #[test]
+    fn test_extremes_after_a_panicking_comparison() {
+        // a value whose comparisons panic, which must leave the tree safe to read
+        #[derive(PartialEq)]
+        struct Touchy(String);
+        impl PartialOrd for Touchy {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                assert!( self.0 != "boom" && other.0 != "boom", "comparison refused" );
+                self.0.partial_cmp(&other.0)
+            }
+        }
+        let mut my_tree = Javlt::from_collection(["b", "a", "c", "d"].map(|word| Touchy(String::from(word))));
+        let dropped = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| my_tree.drop_value(Touchy(String::from("boom")))));
+        assert!( dropped.is_err() );
+        let least = my_tree.least().map(|word| word.0.clone());
+        let greatest = my_tree.greatest().map(|word| word.0.clone());
+        assert_eq!( least.is_some(), greatest.is_some() );
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_rotations_are_traced() {
+        use std::sync::{Arc, Mutex};
+        use tracing::{span, subscriber, Event, Metadata, Subscriber};
+
+        // a subscriber that keeps the messages of the events it's sent, along with any values they show
+        struct Messages(Arc<Mutex<Vec<String>>>);
+        struct Message(String);
+        impl tracing::field::Visit for Message {
+            fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn fmt::Debug) {
+                match field.name() {
+                    "message" => self.0.insert_str(0, &format!("{value:?}")),
+                    "value" | "root" => self.0.push_str(&format!(" ({} {value:?})", field.name())),
+                    _ => (),
+                }
+            }
+        }
+        impl Subscriber for Messages {
+            fn enabled(&self, _: &Metadata<'_>) -> bool { true }
+            fn new_span(&self, _: &span::Attributes<'_>) -> span::Id { span::Id::from_u64(1) }
+            fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+            fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+            fn event(&self, event: &Event<'_>) {
+                let mut message = Message(String::new());
+                event.record(&mut message);
+                self.0.lock().unwrap().push(message.0);
+            }
+            fn enter(&self, _: &span::Id) {}
+            fn exit(&self, _: &span::Id) {}
+        }
+
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        subscriber::with_default(Messages(Arc::clone(&messages)), || {
+            let mut my_tree = Javlt::new();
+            let _ = my_tree.add_all([1,2,3]); // one rotation, on adding 3
+            let (mut low, mut high) = my_tree.partition(|value| *value < 2);
+            low.append(&mut high);
+            low.trace_values();
+            let _ = low.add_all([4,5]); // one rotation, on adding 5
+        });
+        let expected = vec!(
+            "Javlt rotated a subtree", "Javlt rebalanced", "Javlt split in two", "Javlt merged with another",
+            "Javlt rotated a subtree (value \"4\")", "Javlt rebalanced (root \"2\")",
+        );
+        assert_eq!( expected, *messages.lock().unwrap() );
+    }
+
+    #[test]
+    fn into_iter_moves_values_out_in_order() {
+        let my_tree = Javlt::from_collection(["pear", "fig", "kiwi", "apple"].map(String::from));
+        let mut moved = Vec::new();
+        for value in my_tree {
+            moved.push(value);
+        }
+        assert_eq!( vec!("apple", "fig", "kiwi", "pear"), moved );
+        // stopping early drops the rest along with the iterator
+        let mut values = Javlt::from_collection((0..100).map(|n| n.to_string())).into_iter();
+        assert_eq!( Some(String::from("0")), values.next() );
+        assert_eq!( Some(String::from("1")), values.next() );
+        drop(values);
+        assert_eq!( Vec::<u32>::new(), Javlt::<u32>::new().into_iter().collect::<Vec<_>>() );
+    }
+
+    #[test]
+    fn iterators_run_from_both_ends() {
+        let my_tree = Javlt::from_collection(0..100);
+        assert_eq!( (0..100).rev().collect::<Vec<_>>(), my_tree.iter().rev().cloned().collect::<Vec<_>>() );
+        let mut values = my_tree.iter();
+        let (mut front, mut back): (Vec<u32>, Vec<u32>) = (Vec::new(), Vec::new());
+        while let Some(value) = values.next() {
+            front.push(*value);
+            back.extend(values.next_back().into_iter().chain(values.next_back()).cloned());
+        }
+        assert_eq!( (0..34).collect::<Vec<_>>(), front );
+        assert_eq!( (34..100).rev().collect::<Vec<_>>(), back );
+        // the halves of a split run backwards too
+        let (first, second) = my_tree.iter().split();
+        let joined: Vec<u32> = second.rev().chain(first.rev()).cloned().collect();
+        assert_eq!( (0..100).rev().collect::<Vec<_>>(), joined );
+        let mut owned = my_tree.into_iter();
+        assert_eq!( (Some(99), Some(0), Some(98)), (owned.next_back(), owned.next(), owned.next_back()) );
+        assert_eq!( 97, owned.count() );
+    }
+
+    #[test]
+    fn range_matches_filtering() {
+        use std::ops::{Bound, RangeBounds};
+        let my_tree = Javlt::from_collection((0..60).map(|n| n * 2));
+        let bounds = |n: u32| [Bound::Included(n), Bound::Excluded(n), Bound::Unbounded];
+        for (lower, upper) in (0..=121).step_by(3).flat_map(|a| (0..=121).step_by(5).map(move |b| (a, b))) {
+            for range in bounds(lower).into_iter().flat_map(|start| bounds(upper).map(|end| (start, end))) {
+                let expected: Vec<&u32> = my_tree.iter().filter(|value| range.contains(value)).collect();
+                assert_eq!( expected, my_tree.range(range).collect::<Vec<_>>() );
+                assert_eq!( expected.into_iter().rev().collect::<Vec<_>>(), my_tree.range(range).rev().collect::<Vec<_>>() );
+            }
+        }
+        let (front, back) = my_tree.range(10..=100).split();
+        assert_eq!( (5..=50).map(|n| n * 2).collect::<Vec<_>>(), front.chain(back).cloned().collect::<Vec<_>>() );
+        // only the paths to the bounds are searched, and the depths stay right
+        assert_eq!( my_tree.iter_with_depth().filter(|(value, _)| (30..40).contains(*value)).map(|(_, depth)| depth).collect::<Vec<_>>(),
+            Nodes::within(&my_tree.root, &(30..40)).map(|(_, depth)| depth).collect::<Vec<_>>() );
+    }
+
 }
\ No newline at end of file
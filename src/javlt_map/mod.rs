@@ -0,0 +1,209 @@
+use std::{cmp::Ordering, fmt};
+
+use crate::javlt::{self, Javlt};
+
+
+
+/// # Javlt Map
+///
+/// An ordered map: each key holds one value, and the entries are kept in a `Javlt` ordered by key, so
+/// lookups, insertions and removals take O(log(n)) time and iteration visits the keys from least to greatest.
+///
+///     use jtree::JavltMap;
+///
+///     let mut stock = JavltMap::new();
+///     assert_eq!( None, stock.insert("pears", 4) );
+///     assert_eq!( None, stock.insert("apples", 10) );
+///     assert_eq!( Some(4), stock.insert("pears", 6) );
+///     assert_eq!( Some(&6), stock.get(&"pears") );
+///     assert_eq!( vec!((&"apples", &10), (&"pears", &6)), stock.iter().collect::<Vec<_>>() );
+///     assert_eq!( Some(10), stock.remove(&"apples") );
+///     assert_eq!( None, stock.remove(&"apples") );
+///     assert_eq!( 1, stock.get_size() );
+///
+/// A key that can't be ordered even against itself (such as a floating point NaN) can't be stored, and is
+/// never found.
+pub struct JavltMap<K: PartialEq + PartialOrd, V> {
+    entries: Javlt<Entry<K, V>>, // ordered by key
+}
+
+/// A map entry, compared by its key alone.
+struct Entry<K, V> {
+    key: K,
+    value: V,
+}
+
+impl <K: PartialEq, V> PartialEq for Entry<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl <K: PartialOrd, V> PartialOrd for Entry<K, V> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.key.partial_cmp(&other.key)
+    }
+}
+
+impl <K: PartialEq + PartialOrd, V> JavltMap<K, V> {
+
+    /// Create a new map with no entries
+    pub fn new() -> Self {
+        Self {
+            entries: Javlt::new(),
+        }
+    }
+
+    /// Store a value under the key, replacing (and returning) any value the key already had.
+    ///
+    /// Panics if the key can't be ordered even against itself (such as a floating point NaN), since there's no
+    /// place for it among the others.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        assert!( key.partial_cmp(&key).is_some(), "a map key must be comparable with itself" );
+        self.entries.insert(Entry { key, value }).map(|entry| entry.value)
+    }
+
+    /// Look up the key's value, if it has one.
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.entries.get_by(|entry| entry.key.partial_cmp(key)).map(|entry| &entry.value)
+    }
+
+    /// Returns true if the key has a value in the map.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Delete the key's entry and return its value, if it has one.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.entries.take_by(|entry| entry.key.partial_cmp(key)).map(|entry| entry.value)
+    }
+
+    /// Get the number of entries held
+    pub fn get_size(&self) -> u32 {
+        self.entries.get_size()
+    }
+
+    /// Iterate over the keys and their values, in order of the keys (or, with `rev`, in reverse order).
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter { entries: self.entries.iter() }
+    }
+
+}
+
+impl <K: PartialEq + PartialOrd, V> Default for JavltMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <K: PartialEq + PartialOrd + fmt::Debug, V: fmt::Debug> fmt::Debug for JavltMap<K, V> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// An iterator over a JavltMap's keys and values, in order of the keys (from either end); get one with `iter`.
+pub struct Iter<'a, K: PartialEq + PartialOrd, V> {
+    entries: javlt::Iter<'a, Entry<K, V>>,
+}
+
+impl <'a, K: PartialEq + PartialOrd, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next().map(|entry| (&entry.key, &entry.value))
+    }
+}
+
+impl <'a, K: PartialEq + PartialOrd, V> DoubleEndedIterator for Iter<'a, K, V> {
+
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.entries.next_back().map(|entry| (&entry.key, &entry.value))
+    }
+}
+
+impl <'a, K: PartialEq + PartialOrd, V> IntoIterator for &'a JavltMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator that moves the keys and values out of a JavltMap in order of the keys (from either end).  Made by
+/// `into_iter`, as in `for (key, value) in map`.
+pub struct IntoIter<K: PartialEq + PartialOrd, V> {
+    entries: javlt::IntoIter<Entry<K, V>>,
+}
+
+impl <K: PartialEq + PartialOrd, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next().map(|entry| (entry.key, entry.value))
+    }
+}
+
+impl <K: PartialEq + PartialOrd, V> DoubleEndedIterator for IntoIter<K, V> {
+
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.entries.next_back().map(|entry| (entry.key, entry.value))
+    }
+}
+
+impl <K: PartialEq + PartialOrd, V> IntoIterator for JavltMap<K, V> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter { entries: self.entries.into_iter() }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A value that can't be cloned.
+    #[derive(Debug, PartialEq)]
+    struct Crate(u32);
+
+    #[test]
+    fn entries_are_kept_in_key_order() {
+        let mut map = JavltMap::new();
+        for key in [50, 20, 80, 10, 30, 70, 90] {
+            assert_eq!( None, map.insert(key, Crate(key)) );
+        }
+        assert_eq!( Some(Crate(30)), map.insert(30, Crate(33)) );
+        assert_eq!( Some(&Crate(33)), map.get(&30) );
+        assert_eq!( None, map.get(&40) );
+        assert_eq!( Some(Crate(50)), map.remove(&50) );
+        assert_eq!( None, map.remove(&50) );
+        assert!( !map.contains_key(&50) && map.contains_key(&90) );
+        assert_eq!( 6, map.get_size() );
+
+        let keys: Vec<u32> = map.iter().map(|(key, _)| *key).collect();
+        assert_eq!( vec!(10, 20, 30, 70, 80, 90), keys );
+        assert_eq!( Some((&90, &Crate(90))), map.iter().next_back() );
+        let mut owned = map.into_iter();
+        assert_eq!( Some((90, Crate(90))), owned.next_back() );
+        assert_eq!( Some((10, Crate(10))), owned.next() );
+        assert_eq!( 4, owned.count() );
+    }
+
+    #[test]
+    fn incomparable_keys_are_never_found() {
+        let mut map = JavltMap::new();
+        for key in [1.0, 2.0, 3.0] {
+            map.insert(key, key as u32 * 10);
+        }
+        assert_eq!( None, map.get(&f64::NAN) );
+        assert_eq!( None, map.remove(&f64::NAN) );
+        assert_eq!( 3, map.get_size() );
+        assert_eq!( vec!(10, 20, 30), map.iter().map(|(_, value)| *value).collect::<Vec<_>>() );
+        assert!( std::panic::catch_unwind(move || map.insert(f64::NAN, 0)).is_err() );
+    }
+
+}
@@ -0,0 +1,175 @@
+use std::{fmt, sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard}};
+
+use crate::errors::TreeError;
+use crate::javlt::Javlt;
+
+
+
+/// # Shared AVL Tree
+///
+/// A `Javlt` behind a read-write lock, so that threads can share one ordered set without doing their own
+/// locking: any number of threads can look values up at once, while adding or removing a value waits
+/// for exclusive access.  Share it between threads with an `Arc` (or a scoped thread's borrow).
+///
+///     use std::{sync::Arc, thread};
+///     use jtree::JavltSync;
+///
+///     let shared = Arc::new(JavltSync::new());
+///     let handles: Vec<_> = (0..4).map(|t| {
+///         let shared = Arc::clone(&shared);
+///         thread::spawn(move || {
+///             for n in 0..100 {
+///                 let _ = shared.insert(t * 100 + n);
+///             }
+///         })
+///     }).collect();
+///     for handle in handles {
+///         handle.join().unwrap();
+///     }
+///     assert_eq!( 400, shared.get_size() );
+///     assert!( shared.contains(&250) );
+///
+/// If a thread panics while holding the lock, the tree is still used afterwards: its own operations
+/// leave it consistent, and a panicking `with_tree` closure only had read access.
+pub struct JavltSync<T: PartialEq + PartialOrd + Clone> {
+    tree: RwLock<Javlt<T>>,
+}
+
+impl <T: PartialEq + PartialOrd + Clone> JavltSync<T> {
+
+    /// Create a new shared tree with no data
+    pub fn new() -> Self {
+        Self {
+            tree: RwLock::new(Javlt::new()),
+        }
+    }
+
+    /// Create a new shared tree from a collection (vector, array, or whatever), skipping duplicates.
+    pub fn from_collection<U: IntoIterator<Item = T>>(collection: U) -> Self {
+        Self {
+            tree: RwLock::new(Javlt::from_collection(collection)),
+        }
+    }
+
+    /// Insert a value, returning a TreeError::ValueAlreadyStored if it's already in the tree.
+    pub fn insert(&self, value: T) -> Result<(),TreeError<T>> {
+        self.write().add(value)
+    }
+
+    /// Returns true if the value is currently a member of the tree
+    pub fn contains(&self, value: &T) -> bool {
+        self.read().contains(value)
+    }
+
+    /// If the value is in the tree, delete it.  Otherwise a TreeError::ValueNotFound will be returned.
+    pub fn remove(&self, value: T) -> Result<(),TreeError<T>> {
+        self.write().drop_value(value)
+    }
+
+    /// Get the number of values in the tree
+    pub fn get_size(&self) -> u32 {
+        self.read().get_size()
+    }
+
+    /// Copy the values into an ordered Vec, from least to greatest, as they are at this moment.
+    pub fn snapshot_vec(&self) -> Vec<T> {
+        self.read().as_vec()
+    }
+
+    /// Run `f` with read access to the tree, to use any of `Javlt`'s methods while the tree can't change.
+    /// Other threads can read at the same time, but writers wait until `f` returns.
+    ///
+    ///     use jtree::JavltSync;
+    ///
+    ///     let shared = JavltSync::from_collection([3,1,2]);
+    ///     assert_eq!( Some(3), shared.with_tree(|tree| tree.greatest_value()) );
+    pub fn with_tree<R, F: FnOnce(&Javlt<T>) -> R>(&self, f: F) -> R {
+        f(&self.read())
+    }
+
+    /// Unwrap the tree, once it's no longer shared.
+    pub fn into_inner(self) -> Javlt<T> {
+        self.tree.into_inner().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Javlt<T>> {
+        self.tree.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Javlt<T>> {
+        self.tree.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd + Clone> Default for JavltSync<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <T: PartialEq + PartialOrd + Clone> From<Javlt<T>> for JavltSync<T> {
+    fn from(tree: Javlt<T>) -> Self {
+        Self {
+            tree: RwLock::new(tree),
+        }
+    }
+}
+
+impl <T: PartialEq + PartialOrd + Clone + std::fmt::Debug> fmt::Debug for JavltSync<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tree = self.read();
+        fmt.debug_struct("JavltSync")
+            .field("size", &tree.get_size())
+            .field("values", &tree.as_vec())
+            .finish()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn share_between_threads() {
+        let shared = JavltSync::from_collection(0..1000);
+        thread::scope(|scope| {
+            // writers remove the even values and add 1000..2000, while readers look values up
+            for t in 0..4 {
+                let shared = &shared;
+                scope.spawn(move || {
+                    for n in (t * 250..(t + 1) * 250).filter(|n| n % 2 == 0) {
+                        assert_eq!( Ok(()), shared.remove(n) );
+                        assert_eq!( Ok(()), shared.insert(n + 1000) );
+                    }
+                });
+                scope.spawn(move || {
+                    for n in 0..1000 {
+                        let _ = shared.contains(&n);
+                    }
+                });
+            }
+        });
+        assert_eq!( 1000, shared.get_size() );
+        assert_eq!( Err(TreeError::ValueAlreadyStored { value: 1 }), shared.insert(1) );
+        assert_eq!( Err(TreeError::ValueNotFound { value: 0 }), shared.remove(0) );
+        let tree = shared.into_inner();
+        assert_eq!( Ok(()), tree.check_invariants() );
+        assert_eq!( (0..1000).filter(|n| n % 2 == 1).chain((1000..2000).step_by(2)).collect::<Vec<_>>(), tree.as_vec() );
+    }
+
+    #[test]
+    fn survive_a_panicking_reader() {
+        let shared = JavltSync::from_collection([1,2,3]);
+        let result = thread::scope(|scope| {
+            scope.spawn(|| shared.with_tree(|_| panic!("reader failed"))).join()
+        });
+        assert!( result.is_err() );
+        assert_eq!( Ok(()), shared.insert(4) );
+        assert_eq!( vec!(1,2,3,4), shared.snapshot_vec() );
+    }
+
+}
@@ -1,6 +1,18 @@
-use std::fmt;
+use std::{cmp::min, collections::{BTreeMap, HashMap, VecDeque}, fmt, fs, hash::{Hash, Hasher}, iter::{self, FusedIterator}, ops::{ControlFlow, RangeBounds}, path::Path};
 
+use crate::bounds::{queue_within, SearchNode};
 use crate::errors::TreeError;
+use crate::reports::{AddReport, TreeDiff, TreeStats};
+use crate::frozen::FrozenSet;
+use crate::javlt::Javlt;
+use crate::jbst::{DECODE_DEPTH_LIMIT, DEFAULT_DEPTH_LIMIT};
+#[cfg(feature = "petgraph")]
+use petgraph::graph::{DiGraph, NodeIndex};
+#[cfg(feature = "petgraph")]
+use crate::graph::Branch;
+use crate::merge::{Duplicates, MergeIter};
+use crate::visit::Traversal;
+use crate::codec::{self, Codec, Reader, HAS_LEFT, HAS_RIGHT};
 
 
 
@@ -23,45 +35,103 @@ use crate::errors::TreeError;
 ///     assert_eq!( vec!(1,1,2,2,3,3), tree_b.as_vec() ); // the list was ordered and duplicates preserved
 ///     assert!( tree_b.contains(&2) ); // fast test for set membership
 /// 
-/// Can hold any data type that supports PartialEq + PartialOrd + Clone.
-pub struct Jblst<T: PartialEq + PartialOrd + Clone> {
+/// Can hold any data type that supports PartialEq + PartialOrd.  Equal values are counted rather than
+/// stored separately, so if they're distinct items compared by a key, only the first of them is kept; to keep
+/// every item, use a `StableJblst` (in the `stable` module).
+///
+/// The values don't have to be `Clone`, since each is stored once however many times it's counted.  The
+/// methods that hand back values rather than references (like `as_vec`, `drain`, and moving out with
+/// `into_iter`) need `Clone`, because a value counted n times comes out as n copies.
+pub struct Jblst<T: PartialEq + PartialOrd> {
     root: Option<Box<Node<T>>>,
     size: u32,
+    depth_limit: usize, // the most nodes allowed on a path down from the root
 }
 
-impl <T: PartialEq + PartialOrd + Clone> Jblst<T> {
+impl <T: PartialEq + PartialOrd> Jblst<T> {
 
     /// Create a new tree with no data
     pub fn new() -> Self {
         Self {
             root: None,
             size: 0,
+            depth_limit: DEFAULT_DEPTH_LIMIT,
         }
     }
 
-    /// Create a new tree from a collection (vector, array, or whatever).
+    /// Create a new tree with no data, whose values may go no deeper than `depth_limit` nodes (at least 1)
+    /// down from the root.  As with a `Jbst`, a value that would go deeper isn't added, and a
+    /// TreeError::DepthLimitExceeded is returned instead.
+    pub fn with_depth_limit(depth_limit: usize) -> Self {
+        let mut new_tree = Self::new();
+        new_tree.set_depth_limit(depth_limit);
+        new_tree
+    }
+
+    /// Create a new tree from a collection (vector, array, or whatever).  Panics if the values would make the
+    /// tree deeper than `DEFAULT_DEPTH_LIMIT`, as more distinct values than that in sorted order do; such a
+    /// collection can be added to a tree made with `with_depth_limit`.
     pub fn from_collection<U: IntoIterator<Item = T>>(collection: U) -> Self {
         let mut new_tree = Self::new();
-        let _ = new_tree.add_all(collection);
+        if new_tree.add_all(collection).is_err() {
+            panic!("Jblst::from_collection: the values would make the tree deeper than its depth limit ({DEFAULT_DEPTH_LIMIT})");
+        }
         new_tree
     }
 
     /// Insert a value
-    pub fn add(&mut self, value: T) -> Result<(),TreeError> {
+    pub fn add(&mut self, value: T) -> Result<(),TreeError<T>> {
+        if self.size == u32::MAX {
+            return Err(TreeError::CapacityExceeded { value });
+        }
+        self.add_n(value, 1)?;
+        #[cfg(feature = "paranoid")]
+        self.paranoid_check("add");
+        Ok(())
+    }
+
+    /// Insert `n` copies of a value in one pass, as if it were added `n` times.  Returns a
+    /// TreeError::CapacityExceeded (and adds none of them) if the tree can't count that many more values, or a
+    /// TreeError::DepthLimitExceeded if the value would go deeper than the tree's depth limit.
+    ///
+    ///     use jtree::Jblst;
+    ///
+    ///     let mut words = Jblst::new();
+    ///     words.insert_n("the", 5000).unwrap();
+    ///     words.insert_n("of", 3000).unwrap();
+    ///     assert_eq!( 5000, words.count(&"the") );
+    ///     assert_eq!( 8000, words.get_size() );
+    pub fn insert_n(&mut self, value: T, n: usize) -> Result<(),TreeError<T>> {
+        if u32::try_from(n).ok().and_then(|n| self.size.checked_add(n)).is_none() {
+            return Err(TreeError::CapacityExceeded { value });
+        }
+        if n > 0 {
+            self.add_n(value, n)?;
+        }
+        #[cfg(feature = "paranoid")]
+        self.paranoid_check("insert_n");
+        Ok(())
+    }
+
+    /// Insert `n` (at least one) copies of a value, which the tree has room to count.
+    fn add_n(&mut self, value: T, n: usize) -> Result<(),TreeError<T>> {
         match &mut self.root {
-            None => self.root = Some(Box::new(Node::new(value))),
-            Some(branch) => branch.add(value)?, // TODO: handle errors if any are possible
+            None => self.root = Some(Box::new(Node::new(value, n))),
+            Some(branch) => branch.add(value, n, 1, self.depth_limit)?,
         }
-        self.size += 1;
+        self.size += n as u32;
         Ok(())
     }
 
     /// Adds all members of a collection (vector, array, or whatever) to the tree.
-    pub fn add_all<U: IntoIterator<Item = T>>(&mut self, collection: U) -> Result<(),TreeError> {
+    /// Since duplicates are allowed, the returned report never counts any values as skipped.
+    pub fn add_all<U: IntoIterator<Item = T>>(&mut self, collection: U) -> Result<AddReport,TreeError<T>> {
+        let mut report = AddReport::default();
         for elem in collection.into_iter() {
-            let _ = self.add(elem);
+            self.add(elem)?;
+            report.inserted += 1;
         }
-        Ok(())
+        Ok(report)
     }
 
     /// Get the number of values in the tree
@@ -69,6 +139,326 @@ impl <T: PartialEq + PartialOrd + Clone> Jblst<T> {
         self.size
     }
 
+    /// Get the most nodes a path down from the root may go through: values that would go deeper aren't added.
+    pub fn get_depth_limit(&self) -> usize {
+        self.depth_limit
+    }
+
+    /// Change the tree's depth limit (to at least 1).  Values already deeper than a lowered limit stay where
+    /// they are; only values added from now on are held to it.
+    pub fn set_depth_limit(&mut self, depth_limit: usize) {
+        self.depth_limit = depth_limit.max(1);
+    }
+
+    /// Returns true if the value is currently a member of the tree
+    pub fn contains(&self, value: &T) -> bool {
+        return match &self.root {
+            None => false,
+            Some(branch) => branch.contains(value), 
+        };
+    }
+
+    /// Returns the number of times the value was added (and not since dropped), or 0 if it's not in the tree.
+    ///
+    ///     use jtree::Jblst;
+    ///
+    ///     let my_tree = Jblst::from_collection([2,1,2]);
+    ///     assert_eq!( 2, my_tree.count(&2) );
+    ///     assert_eq!( 0, my_tree.count(&3) );
+    pub fn count(&self, value: &T) -> usize {
+        return match &self.root {
+            None => 0,
+            Some(branch) => branch.count(value),
+        };
+    }
+
+    /// Returns references to all the values in the tree as an ordered Vec from least to greatest, for when
+    /// cloning them with `as_vec` would be wasteful.  Duplicates are repeated once for each time they were added.
+    ///
+    ///     use jtree::Jblst;
+    ///
+    ///     let my_tree = Jblst::from_collection([2,1,2]);
+    ///     assert_eq!( vec!(&1,&2,&2), my_tree.values() );
+    pub fn values(&self) -> Vec<&T> {
+        let mut vals = Vec::with_capacity(self.size as usize);
+        vals.extend(self.iter());
+        vals
+    }
+
+    /// Call `f` on each value in order, from least to greatest, without copying the values into a Vec.  Duplicates are visited once for each time they were added.
+    ///
+    ///     use jtree::Jblst;
+    ///
+    ///     let my_tree = Jblst::from_collection([2,1,3]);
+    ///     let mut total = 0;
+    ///     my_tree.visit_in_order(|value| total += value);
+    ///     assert_eq!( 6, total );
+    pub fn visit_in_order<F: FnMut(&T)>(&self, mut f: F) {
+        let _ = self.try_visit(Traversal::In, |value| {
+            f(value);
+            ControlFlow::<()>::Continue(())
+        });
+    }
+
+    /// Call `f` on each value in pre-order: each node's value before the values in its left subtree, then its right subtree.
+    pub fn visit_pre_order<F: FnMut(&T)>(&self, mut f: F) {
+        let _ = self.try_visit(Traversal::Pre, |value| {
+            f(value);
+            ControlFlow::<()>::Continue(())
+        });
+    }
+
+    /// Call `f` on each value in post-order: the values in each node's left subtree, then its right subtree, then its own value.
+    pub fn visit_post_order<F: FnMut(&T)>(&self, mut f: F) {
+        let _ = self.try_visit(Traversal::Post, |value| {
+            f(value);
+            ControlFlow::<()>::Continue(())
+        });
+    }
+
+    /// Like `visit_in_order`, but `f` can stop the visit early by returning `ControlFlow::Break`, which is passed
+    /// back to the caller.
+    ///
+    ///     use std::ops::ControlFlow;
+    ///     use jtree::Jblst;
+    ///
+    ///     let my_tree = Jblst::from_collection([5,1,8,3]);
+    ///     let first_over_2 = my_tree.try_visit_in_order(|value| match *value > 2 {
+    ///         true => ControlFlow::Break(*value),
+    ///         false => ControlFlow::Continue(()),
+    ///     });
+    ///     assert_eq!( ControlFlow::Break(3), first_over_2 );
+    pub fn try_visit_in_order<B, F: FnMut(&T) -> ControlFlow<B>>(&self, f: F) -> ControlFlow<B> {
+        self.try_visit(Traversal::In, f)
+    }
+
+    /// Like `visit_pre_order`, but `f` can stop the visit early by returning `ControlFlow::Break`.
+    pub fn try_visit_pre_order<B, F: FnMut(&T) -> ControlFlow<B>>(&self, f: F) -> ControlFlow<B> {
+        self.try_visit(Traversal::Pre, f)
+    }
+
+    /// Like `visit_post_order`, but `f` can stop the visit early by returning `ControlFlow::Break`.
+    pub fn try_visit_post_order<B, F: FnMut(&T) -> ControlFlow<B>>(&self, f: F) -> ControlFlow<B> {
+        self.try_visit(Traversal::Post, f)
+    }
+
+    fn try_visit<B, F: FnMut(&T) -> ControlFlow<B>>(&self, order: Traversal, mut f: F) -> ControlFlow<B> {
+        return match &self.root {
+            None => ControlFlow::Continue(()),
+            Some(branch) => branch.try_visit(order, &mut f),
+        };
+    }
+
+    /// Returns an iterator over the values in order, from least to greatest, which borrows them rather than
+    /// cloning them into a Vec as `as_vec` does.  Duplicates are repeated once for each time they were added.
+    ///
+    ///     use jtree::Jblst;
+    ///
+    ///     let my_tree = Jblst::from_collection([3,1,3,2]);
+    ///     assert_eq!( vec!(&1,&2,&3,&3), my_tree.iter().collect::<Vec<_>>() );
+    ///     assert_eq!( vec!(&3,&3,&2,&1), my_tree.iter().rev().collect::<Vec<_>>() );
+    ///     assert_eq!( 9, (&my_tree).into_iter().sum::<u32>() );
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { nodes: Nodes::new(&self.root), front_repeats: None, back_repeats: None }
+    }
+
+    /// Returns an iterator over the values within a range (such as `2..5` or `..=7`), in order, from either end,
+    /// with duplicates repeated.  Only the paths down to the range's bounds are searched to find them, so on a
+    /// well-shaped tree it takes O(log(n)) time, plus O(1) for each value yielded.
+    ///
+    ///     use jtree::Jblst;
+    ///
+    ///     let my_tree = Jblst::from_collection([5,2,8,2,5,9]);
+    ///     assert_eq!( vec!(&2,&2,&5,&5), my_tree.range(..=5).collect::<Vec<_>>() );
+    pub fn range<R: RangeBounds<T>>(&self, range: R) -> Iter<'_, T> {
+        Iter { nodes: Nodes::within(&self.root, &range), front_repeats: None, back_repeats: None }
+    }
+
+    /// Returns the values stored in leaf nodes (those with no children), in order from least to greatest.
+    /// Duplicates are repeated once for each time they were added.
+    ///
+    ///     use jtree::Jblst;
+    ///
+    ///     let my_tree = Jblst::from_collection([4,2,6,1,3,3]);
+    ///     assert_eq!( vec!(&1,&3,&3,&6), my_tree.leaves().collect::<Vec<_>>() );
+    pub fn leaves(&self) -> impl Iterator<Item = &T> {
+        Nodes::new(&self.root).filter(|(node, _)| node.is_leaf()).flat_map(|(node, _)| iter::repeat_n(&node.value, node.count))
+    }
+
+    /// Returns each distinct value once, however many times it was added, in order from least to greatest;
+    /// a set view of the tree's values.
+    ///
+    ///     use jtree::Jblst;
+    ///
+    ///     let my_tree = Jblst::from_collection([2,1,2,3,3,3]);
+    ///     assert_eq!( vec!(&1,&2,&3), my_tree.iter_unique().collect::<Vec<_>>() );
+    pub fn iter_unique(&self) -> impl Iterator<Item = &T> {
+        Nodes::new(&self.root).map(|(node, _)| &node.value)
+    }
+
+    /// Returns each distinct value with the number of times it was added, in order from least to greatest.
+    ///
+    ///     use jtree::Jblst;
+    ///
+    ///     let my_tree = Jblst::from_collection(["to", "be", "or", "not", "to", "be"]);
+    ///     let counts: Vec<(&&str, usize)> = my_tree.iter_counts().collect();
+    ///     assert_eq!( vec!((&"be",2), (&"not",1), (&"or",1), (&"to",2)), counts );
+    pub fn iter_counts(&self) -> impl Iterator<Item = (&T, usize)> {
+        Nodes::new(&self.root).map(|(node, _)| (&node.value, node.count))
+    }
+
+    /// Returns true if this tree and the `Javlt` hold the same distinct values, ignoring how many times each
+    /// was added here.  Both trees are walked in order together, stopping at the first difference, so
+    /// neither is copied into a Vec.
+    ///
+    ///     use jtree::{Javlt, Jblst};
+    ///
+    ///     let with_repeats = Jblst::from_collection([3,1,3,2,1]);
+    ///     assert!( with_repeats.distinct_eq(&Javlt::from_collection([1,2,3])) );
+    ///     assert!( !with_repeats.distinct_eq(&Javlt::from_collection([1,2])) );
+    pub fn distinct_eq(&self, other: &Javlt<T>) -> bool {
+        self.iter_unique().eq(other.iter())
+    }
+
+    /// Returns true if this tree and the other hold the same values, each added the same number of times,
+    /// however the trees are shaped.
+    ///
+    ///     use jtree::Jblst;
+    ///
+    ///     let tree_a = Jblst::from_collection([1,2,2,3]);
+    ///     assert!( tree_a.multiset_eq(&Jblst::from_collection([2,3,2,1])) );
+    ///     assert!( !tree_a.multiset_eq(&Jblst::from_collection([1,2,3,3])) );
+    pub fn multiset_eq(&self, other: &Self) -> bool {
+        self.get_size() == other.get_size() && self.iter_counts().eq(other.iter_counts())
+    }
+
+    /// Returns each value with its depth in the tree (the root is at depth 0), in order from least to greatest.
+    /// Duplicates are repeated once for each time they were added.
+    ///
+    ///     use jtree::Jblst;
+    ///
+    ///     let my_tree = Jblst::from_collection([2,1,2]);
+    ///     assert_eq!( vec!((&1,1), (&2,0), (&2,0)), my_tree.iter_with_depth().collect::<Vec<_>>() );
+    pub fn iter_with_depth(&self) -> impl Iterator<Item = (&T, usize)> {
+        Nodes::new(&self.root).flat_map(|(node, depth)| iter::repeat_n((&node.value, depth), node.count))
+    }
+
+    /// Returns the values of this tree and another together, in order from least to greatest, without copying
+    /// them or sorting them again.  `duplicates` says whether a value in both trees is yielded twice or once.
+    ///
+    ///     use jtree::Jblst;
+    ///     use jtree::merge::Duplicates;
+    ///
+    ///     let tree_a = Jblst::from_collection([1,3,5]);
+    ///     let tree_b = Jblst::from_collection([2,3,4]);
+    ///     assert_eq!( vec!(&1,&2,&3,&4,&5), tree_a.merge_iter(&tree_b, Duplicates::Union).collect::<Vec<_>>() );
+    pub fn merge_iter<'a>(&'a self, other: &'a Self, duplicates: Duplicates) -> impl Iterator<Item = &'a T> {
+        MergeIter::new(self.iter(), other.iter(), duplicates)
+    }
+
+    /// Returns a cursor positioned at the value, or if it's not in the tree, at the least value greater than it
+    /// (or the ghost position past the end, if there's none).  From there, the cursor can walk to neighbouring values
+    /// in either direction.  Duplicates are visited once for each time they were added.
+    ///
+    ///     use jtree::Jblst;
+    ///
+    ///     let my_tree = Jblst::from_collection([10,20,30,40]);
+    ///     let mut cursor = my_tree.cursor_at(&25);
+    ///     assert_eq!( Some(&30), cursor.peek() );
+    ///     assert_eq!( Some(&40), cursor.next() );
+    ///     assert_eq!( None, cursor.next() ); // past the end
+    ///     assert_eq!( Some(&40), cursor.prev() );
+    pub fn cursor_at(&self, value: &T) -> Cursor<'_, T> {
+        let mut cursor = Cursor::new(&self.root);
+        cursor.seek(value);
+        cursor
+    }
+
+    /// Returns a cursor positioned at the least value (or the ghost position, if the tree is empty).
+    pub fn cursor_front(&self) -> Cursor<'_, T> {
+        Cursor::front(&self.root)
+    }
+
+    /// Returns a cursor positioned at the greatest value (or the ghost position, if the tree is empty).
+    pub fn cursor_back(&self) -> Cursor<'_, T> {
+        let mut cursor = Cursor::new(&self.root);
+        cursor.prev();
+        cursor
+    }
+
+    /// If the value is in the tree, delete it (once, if it was added more than once).  Otherwise a
+    /// TreeError::ValueNotFound will be returned.
+    pub fn drop_value(&mut self, value: T) -> Result<(),TreeError<T>> {
+        self.drop_up_to(value, 1)?;
+        #[cfg(feature = "paranoid")]
+        self.paranoid_check("drop_value");
+        Ok(())
+    }
+
+    /// Delete up to `n` of the value's duplicates, returning how many were deleted.
+    fn drop_up_to(&mut self, value: T, n: usize) -> Result<usize,TreeError<T>> {
+        match self.root.take() {
+            None => {
+                self.root = None;
+                return Err(TreeError::ValueNotFound { value });
+            },
+            Some(child) => {
+                match child.drop_value(value, n) {
+                    (Err(error), new_node) => {
+                        self.root = new_node;
+                        return Err(error);
+                    },
+                    (Ok(dropped), new_node) => {
+                        self.root = new_node;
+                        self.size -= dropped as u32;
+                        return Ok(dropped);
+                    }
+                }
+            },
+        }
+    }
+
+    /// Measure the tree's shape: node count, height, and leaf depths.  Duplicates share a node,
+    /// so the node count can be less than the size.
+    pub fn stats(&self) -> TreeStats {
+        let mut stats = TreeStats::default();
+        match &self.root {
+            None => (),
+            Some(branch) => branch.collect_stats(0, &mut stats),
+        }
+        stats
+    }
+
+    /// Verify the tree's internal consistency: that values are in order, each distinct value is stored in
+    /// exactly one node with a positive count, and the size matches the counts.  Returns a 
+    /// TreeError::InvariantViolation describing the first problem found, if any.
+    pub fn check_invariants(&self) -> Result<(),TreeError<T>> {
+        let total = match &self.root {
+            None => 0,
+            Some(branch) => branch.check_invariants(&mut String::from("root"), None, None)?,
+        };
+        if total != self.size as usize {
+            return Err(TreeError::InvariantViolation(format!("size is {} but the tree holds {} values", self.size, total)));
+        }
+        Ok(())
+    }
+
+    /// With the `paranoid` feature, every change to the tree is followed by this check, which panics
+    /// with a description of the problem and the tree's shape if the change broke an invariant.
+    #[cfg(feature = "paranoid")]
+    fn paranoid_check(&self, operation: &str) {
+        if let Err(TreeError::InvariantViolation(problem)) = self.check_invariants() {
+            let shape = self.root.as_ref().map_or(String::from("."), |branch| branch.outline());
+            panic!("Jblst::{operation} broke the tree: {problem} (size {}, shape {shape})", self.size);
+        }
+    }
+
+}
+
+// The methods below copy values out of the tree (a value held n times comes out as n copies), or keep copies of
+// values to undo with or to look up again.
+impl <T: PartialEq + PartialOrd + Clone> Jblst<T> {
+
     /// Returns the 'value' field of the root node; used for automated tests only
     #[cfg(test)]
     fn get_root_value(&self) -> Option<T> {
@@ -78,12 +468,25 @@ impl <T: PartialEq + PartialOrd + Clone> Jblst<T> {
         }
     }
 
-    /// Returns true if the value is currently a member of the tree
-    pub fn contains(&self, value: &T) -> bool {
-        return match &self.root {
-            None => false,
-            Some(branch) => branch.contains(value), 
-        };
+    /// Change the number of times a value is counted in the tree to `n`, adding or deleting copies of it (or
+    /// deleting it altogether if `n` is 0), and return how many times it was counted before.  Returns a
+    /// TreeError::CapacityExceeded (and changes nothing) if the tree can't count that many values.
+    ///
+    ///     use jtree::Jblst;
+    ///
+    ///     let mut my_tree = Jblst::from_collection([1,2,2,3]);
+    ///     assert_eq!( Ok(2), my_tree.set_count(&2, 4) );
+    ///     assert_eq!( Ok(1), my_tree.set_count(&3, 0) );
+    ///     assert_eq!( Ok(0), my_tree.set_count(&5, 1) );
+    ///     assert_eq!( vec!(1,2,2,2,2,5), my_tree.as_vec() );
+    pub fn set_count(&mut self, value: &T, n: usize) -> Result<usize,TreeError<T>> {
+        let current = self.count(value);
+        if n > current {
+            self.insert_n(value.clone(), n - current)?;
+        } else if n < current {
+            self.drop_n(value, current - n)?;
+        }
+        Ok(current)
     }
 
     /// Short for `as_vec_l_to_r`, this method returns all the values in the tree as an ordered Vec
@@ -97,25 +500,151 @@ impl <T: PartialEq + PartialOrd + Clone> Jblst<T> {
         return match &self.root {
             None => Vec::new(),
             Some(branch) => {
-                let mut vals = Vec::new();
+                let mut vals = Vec::with_capacity(self.size as usize);
                 branch.collect_values_l_to_r(&mut vals);
                 vals 
             }
         };
     }
 
-    /// Returns all the values in the tree as an ordered Vec from greatest to least  (right to left).
+    /// Consume the tree, moving its values into a Vec from least to greatest.  Unlike `as_vec`, only the extra copies
+    /// of duplicated values are cloned.
+    pub fn into_sorted_vec(self) -> Vec<T> {
+        self.into_iter().collect()
+    }
+
+    /// Returns the `n` least values in the tree (or all of them, if there are fewer), from least to greatest.
+    /// Only the part of the tree holding them is traversed, so this is much cheaper than slicing `as_vec`
+    /// when `n` is small.  Duplicates count once for each time they were added.
+    ///
+    ///     use jtree::Jblst;
+    ///
+    ///     let my_tree = Jblst::from_collection([5,1,8,3,9]);
+    ///     assert_eq!( vec!(1,3), my_tree.first_n(2) );
+    ///     assert_eq!( vec!(8,9), my_tree.last_n(2) );
+    pub fn first_n(&self, n: usize) -> Vec<T> {
+        let mut vals = Vec::with_capacity(min(n, self.size as usize));
+        match &self.root {
+            None => (),
+            Some(branch) => branch.collect_first_n(n, &mut vals),
+        }
+        vals
+    }
+
+    /// Returns the `n` greatest values in the tree (or all of them, if there are fewer), from least to greatest,
+    /// like the end of `as_vec`; but only the part of the tree holding them is traversed.
+    pub fn last_n(&self, n: usize) -> Vec<T> {
+        let mut vals = Vec::with_capacity(min(n, self.size as usize));
+        match &self.root {
+            None => (),
+            Some(branch) => branch.collect_last_n(n, &mut vals),
+        }
+        vals.reverse();
+        vals
+    }
+
+    /// Returns all the values in the tree as an ordered Vec from greatest to least  (right to left).  To walk them
+    /// in that order without cloning them, use `iter().rev()`.
     pub fn as_vec_r_to_l(&self) -> Vec<T> {
         return match &self.root {
             None => Vec::new(),
             Some(branch) => {
-                let mut vals = Vec::new();
+                let mut vals = Vec::with_capacity(self.size as usize);
                 branch.collect_values_r_to_l(&mut vals);
                 vals 
             }
         };
     }
 
+    /// Returns a cursor that can remove values as it walks the tree, positioned like `cursor_at`.
+    pub fn cursor_mut_at(&mut self, value: &T) -> CursorMut<'_, T> {
+        let current = self.cursor_at(value).peek().cloned();
+        CursorMut::new(self, current)
+    }
+
+    /// Returns a cursor that can remove values as it walks the tree, positioned at the least value.
+    pub fn cursor_mut_front(&mut self) -> CursorMut<'_, T> {
+        let current = self.cursor_front().peek().cloned();
+        CursorMut::new(self, current)
+    }
+
+    /// Returns a cursor that can remove values as it walks the tree, positioned at the greatest value.
+    pub fn cursor_mut_back(&mut self) -> CursorMut<'_, T> {
+        let current = self.cursor_back().peek().cloned();
+        CursorMut::new(self, current)
+    }
+
+    /// Take all of the values out of the tree, in order from least to greatest, leaving it empty (and ready
+    /// to be reused) without copying the values.  Duplicates are yielded once for each time they were added.  The tree is emptied straight away, so any values
+    /// the iterator isn't asked for are dropped with it.
+    ///
+    ///     use jtree::Jblst;
+    ///
+    ///     let mut my_tree = Jblst::from_collection([2,1,3]);
+    ///     assert_eq!( vec!(1,2,3), my_tree.drain().collect::<Vec<_>>() );
+    ///     assert_eq!( 0, my_tree.get_size() );
+    pub fn drain(&mut self) -> impl DoubleEndedIterator<Item = T> + use<T> {
+        self.take_nodes()
+    }
+
+    /// Empty the tree, handing its nodes to a `Drain` to take the values out of them, for `drain` and `into_iter`.
+    fn take_nodes(&mut self) -> Drain<T> {
+        self.size = 0;
+        Drain::new(self.root.take())
+    }
+
+    /// Copy the values into a read-only `FrozenSet`, which is faster to search.
+    pub fn freeze(&self) -> FrozenSet<T> {
+        FrozenSet::from_sorted_vec(self.as_vec())
+    }
+
+    /// Compare the values in this tree with another's, in a single pass over both in order, listing the values
+    /// found in only one of them.
+    ///
+    ///     use jtree::Jblst;
+    ///
+    ///     let old_tree = Jblst::from_collection([1,2,3]);
+    ///     let new_tree = Jblst::from_collection([2,3,4]);
+    ///     let diff = old_tree.diff(&new_tree);
+    ///     assert_eq!( vec!(1), diff.only_in_self );
+    ///     assert_eq!( vec!(4), diff.only_in_other );
+    pub fn diff(&self, other: &Self) -> TreeDiff<T> {
+        TreeDiff::between(self.iter(), other.iter())
+    }
+
+    /// Patch this tree with a diff, removing its `only_in_self` values and adding its `only_in_other` values,
+    /// so that after `tree_a.apply(&tree_a.diff(&tree_b))`, `tree_a` holds the same values as `tree_b`.  The
+    /// patch is all-or-nothing: if a value to remove isn't in the tree, or a value to add would go deeper than the
+    /// depth limit, the changes already made are undone and the error is returned.
+    pub fn apply(&mut self, diff: &TreeDiff<T>) -> Result<(),TreeError<T>> {
+        for (index, value) in diff.only_in_self.iter().enumerate() {
+            if let Err(error) = self.drop_value(value.clone()) {
+                self.restore(&diff.only_in_self[..index]);
+                return Err(error);
+            }
+        }
+        for (index, value) in diff.only_in_other.iter().enumerate() {
+            if let Err(error) = self.add(value.clone()) {
+                for added in diff.only_in_other[..index].iter() {
+                    let _ = self.drop_value(added.clone());
+                }
+                self.restore(&diff.only_in_self);
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    /// Add back the values a change being undone has removed.  They were in the tree a moment ago, so they go
+    /// back even where the change has left them deeper than the depth limit.
+    fn restore(&mut self, values: &[T]) {
+        let depth_limit = std::mem::replace(&mut self.depth_limit, usize::MAX);
+        for value in values {
+            let _ = self.add(value.clone());
+        }
+        self.depth_limit = depth_limit;
+    }
+
     /// Returns the smallest/lowest value in the tree, if any.
     pub fn least_value(&self) -> Option<T> {
         return match &self.root {
@@ -132,239 +661,1187 @@ impl <T: PartialEq + PartialOrd + Clone> Jblst<T> {
         }
     }
 
-    /// If the value is in the tree, delete it.  Otherwise a TreeError::ValueNotFound will be returned.
-    pub fn drop_value(&mut self, value: T) -> Result<(),TreeError> {
-        match self.root.take() {
-            None => {
-                self.root = None;
-                return Err(TreeError::ValueNotFound);
-            },
-            Some(child) => {
-                match child.drop_value(value) {
-                    (Err(_), new_node) => {
-                        self.root = new_node;
-                        return Err(TreeError::ValueNotFound);
-                    },
-                    (Ok(_), new_node) => {
-                        self.root = new_node;
-                        self.size -= 1;
-                        return Ok(());
-                    }
-                }
+    /// Delete `n` of the value's duplicates in one pass, or all of them if there are fewer than `n`, returning
+    /// how many were deleted.  If the value isn't in the tree a TreeError::ValueNotFound will be returned.
+    ///
+    ///     use jtree::Jblst;
+    ///
+    ///     let mut my_tree = Jblst::from_collection([1,2,2,2,2,3]);
+    ///     assert_eq!( Ok(3), my_tree.drop_n(&2, 3) );
+    ///     assert_eq!( Ok(1), my_tree.drop_n(&2, 3) );
+    ///     assert_eq!( vec!(1,3), my_tree.as_vec() );
+    pub fn drop_n(&mut self, value: &T, n: usize) -> Result<usize,TreeError<T>> {
+        let dropped = self.drop_up_to(value.clone(), n)?;
+        #[cfg(feature = "paranoid")]
+        self.paranoid_check("drop_n");
+        Ok(dropped)
+    }
+
+    /// Export the tree's structure as a petgraph `DiGraph` (see the `graph` module).  Duplicates share a node, so each value appears once.
+    #[cfg(feature = "petgraph")]
+    pub fn to_petgraph(&self) -> DiGraph<T, Branch> {
+        let mut graph = DiGraph::new();
+        match &self.root {
+            None => (),
+            Some(branch) => {
+                branch.add_to_graph(&mut graph);
             },
         }
+        graph
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd> Default for Jblst<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <T: PartialEq + PartialOrd + std::fmt::Debug> fmt::Debug for Jblst<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("Jblst")
+            .field("size", &self.get_size())
+            .field("values", &self.values())
+            .finish()
+    }
+}
+
+impl <T: PartialEq + PartialOrd + std::fmt::Debug> Jblst<T> {
+
+    /// Draw the tree sideways as ASCII art, one node per line, with the root at the left margin and
+    /// each right subtree drawn above its parent.
+    /// Values stored more than once show their count, e.g. `3 x2`.  An empty tree is drawn as an empty string.
+    ///
+    ///     use jtree::Jblst;
+    ///
+    ///     let my_tree = Jblst::from_collection([3,1,5,4,6,3]);
+    ///     assert_eq!( "    /-- 6\n/-- 5\n|   \\-- 4\n3 x2\n\\-- 1\n", my_tree.to_ascii() );
+    ///
+    /// where the drawing looks like:
+    ///
+    /// ```text
+    ///     /-- 6
+    /// /-- 5
+    /// |   \-- 4
+    /// 3 x2
+    /// \-- 1
+    /// ```
+    pub fn to_ascii(&self) -> String {
+        let mut drawing = String::new();
+        match &self.root {
+            None => (),
+            Some(branch) => branch.draw(&mut drawing, "", ""),
+        }
+        drawing
+    }
+
+    /// Print the tree's structure to stdout, as drawn by `to_ascii`.
+    pub fn print_tree(&self) {
+        print!("{}", self.to_ascii());
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd + Codec> Jblst<T> {
+
+    /// Encode the tree in jtree's binary format (see the `codec` module), keeping its exact shape.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        codec::write_header(&mut bytes, b'L');
+        self.size.encode(&mut bytes);
+        match &self.root {
+            None => (),
+            Some(branch) => branch.encode(&mut bytes),
+        }
+        bytes
     }
 
+    /// Decode a tree encoded by `to_bytes`.  Returns a TreeError::DeserializationCorrupt if the bytes
+    /// aren't a valid Jblst, or if the tree is deeper than `DECODE_DEPTH_LIMIT`.  Like a new tree, the
+    /// decoded one has the default depth limit.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self,TreeError<T>> {
+        let mut tree = Self::from_bytes_with_depth_limit(bytes, DECODE_DEPTH_LIMIT)?;
+        tree.depth_limit = DEFAULT_DEPTH_LIMIT;
+        Ok(tree)
+    }
+
+    /// Decode a tree encoded by `to_bytes`, like `from_bytes`, but refusing trees deeper than the given depth
+    /// limit (at least 1) rather than `DECODE_DEPTH_LIMIT`, and giving the decoded tree that limit.  (The
+    /// limit isn't saved.)
+    pub fn from_bytes_with_depth_limit(bytes: &[u8], depth_limit: usize) -> Result<Self,TreeError<T>> {
+        let depth_limit = depth_limit.max(1);
+        let mut reader = Reader::new(bytes);
+        reader.read_header(b'L', "Jblst")?;
+        let size: u32 = reader.read()?;
+        let root = match size {
+            0 => None,
+            _ => Some(Node::decode(&mut reader, 1, depth_limit)?),
+        };
+        reader.finish()?;
+        let tree = Self { root, size, depth_limit };
+        reader.validate(tree.check_invariants())?;
+        Ok(tree)
+    }
+
+    /// Save the tree to a file, in the format written by `to_bytes`, replacing the file if it exists.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(),TreeError<T>> {
+        fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+
+    /// Load a tree from a file written by `save`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self,TreeError<T>> {
+        let bytes = fs::read(path)?;
+        Self::from_bytes(&bytes)
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd + Hash> Jblst<T> {
+
+    /// Hash the tree's contents: its size and its values in order, so two trees holding the same values
+    /// have the same digest however they're shaped.  Duplicates count, so trees holding the same values a different number of times have different digests.
+    /// The digest is only as stable as the hasher (for example, `DefaultHasher::new()` may change between
+    /// Rust releases), so use the same one on both sides when comparing trees in different places.
+    ///
+    ///     use std::hash::DefaultHasher;
+    ///     use jtree::Jblst;
+    ///
+    ///     let tree_a = Jblst::from_collection([1,2,3]);
+    ///     let tree_b = Jblst::from_collection([3,2,1]);
+    ///     assert_eq!( tree_a.digest::<DefaultHasher>(), tree_b.digest::<DefaultHasher>() );
+    pub fn digest<H: Hasher + Default>(&self) -> u64 {
+        let mut hasher = H::default();
+        self.size.hash(&mut hasher);
+        match &self.root {
+            None => (),
+            Some(branch) => branch.hash_values(&mut hasher),
+        }
+        hasher.finish()
+    }
+
+    /// Copy the tree's counts into a `HashMap` from each distinct value to the number of times it was added,
+    /// for code that expects the standard collections.
+    ///
+    ///     use jtree::Jblst;
+    ///
+    ///     let words = Jblst::from_collection(["to", "be", "or", "not", "to", "be"]);
+    ///     let frequencies = words.to_frequency_map();
+    ///     assert_eq!( Some(&2), frequencies.get("be") );
+    ///     assert_eq!( 4, frequencies.len() );
+    pub fn to_frequency_map(&self) -> HashMap<T, usize> where T: Eq + Clone {
+        self.iter_counts().map(|(value, count)| (value.clone(), count)).collect()
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd + Clone + Ord> Jblst<T> {
+
+    /// Copy the tree's counts into a `BTreeMap` from each distinct value to the number of times it was added,
+    /// which keeps the tree's order.
+    ///
+    ///     use jtree::Jblst;
+    ///
+    ///     let rolls = Jblst::from_collection([3,6,3,1,6,6]);
+    ///     let frequencies: Vec<(u32, usize)> = rolls.to_frequency_btree_map().into_iter().collect();
+    ///     assert_eq!( vec!((1,1), (3,2), (6,3)), frequencies );
+    pub fn to_frequency_btree_map(&self) -> BTreeMap<T, usize> {
+        self.iter_counts().map(|(value, count)| (value.clone(), count)).collect()
+    }
+
+}
+
+/// What `Node::drop_value` hands back to its parent: the outcome, and the node that takes the dropped node's place.
+/// The outcome counts the duplicates dropped.
+type DropOutcome<T> = (Result<usize,TreeError<T>>, Option<Box<Node<T>>>);
+
+struct Node<T: PartialEq + PartialOrd> {
+    value: T,
+    count: usize, // duplicate values are counted, rather than getting new nodes
+    left: Option<Box<Node<T>>>,
+    right: Option<Box<Node<T>>>,
+}
+
+impl <T: PartialEq + PartialOrd> SearchNode for Node<T> {
+    type Value = T;
+    fn value(&self) -> &T { &self.value }
+    fn left(&self) -> Option<&Self> { self.left.as_deref() }
+    fn right(&self) -> Option<&Self> { self.right.as_deref() }
+}
+
+impl <T:PartialEq + PartialOrd> Node<T> {
+
+    pub fn new(value: T, count: usize) -> Self {
+        Self {
+            value,
+            count,
+            left: None,
+            right: None,
+        }
+    }
+
+    /// Insert `n` copies of a value.  `depth` is this node's (1 for the root), and no new node may go deeper
+    /// than `depth_limit`.
+    pub fn add(&mut self, value: T, n: usize, depth: usize, depth_limit: usize) -> Result<(),TreeError<T>> {
+        if value == self.value {
+            // increment the count
+            self.count += n;
+            return Ok(());
+        }
+        if value < self.value {
+            // add to the left branch
+            match &mut self.left {
+                None if depth == depth_limit => return Err(TreeError::DepthLimitExceeded { value, limit: depth_limit }),
+                None => self.left = Some(Box::new(Node::new(value, n))),
+                Some(branch) => return branch.add(value, n, depth + 1, depth_limit),
+            }
+        } else {
+            // add it to the right branch
+            match &mut self.right {
+                None if depth == depth_limit => return Err(TreeError::DepthLimitExceeded { value, limit: depth_limit }),
+                None => self.right = Some(Box::new(Node::new(value, n))),
+                Some(branch) => return branch.add(value, n, depth + 1, depth_limit),
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns true if the value is currently a member of the (sub)tree
+    pub fn contains(&self, value: &T) -> bool {
+        if *value == self.value {
+            return true;
+        }
+        if *value < self.value {
+            match &self.left {
+                Some(node) => node.contains(value),
+                None => return false
+            }
+        } else {
+            match &self.right {
+                Some(node) => node.contains(value),
+                None => return false
+            }
+        }
+    }
+
+    /// Returns the count of the node holding the value in this (sub)tree, or 0 if there's none.
+    pub fn count(&self, value: &T) -> usize {
+        if *value == self.value {
+            return self.count;
+        }
+        let child = match *value < self.value {
+            true => &self.left,
+            false => &self.right,
+        };
+        return match child {
+            Some(node) => node.count(value),
+            None => 0,
+        };
+    }
+
+    /// Recursively verify that this (sub)tree's values are in order and strictly between the bounds
+    /// set by its ancestors, and that every count is positive.  Returns the total of the counts.
+    /// The path (e.g. "root.left.right") is only used to describe where a problem was found.
+    pub fn check_invariants(&self, path: &mut String, lower: Option<&T>, upper: Option<&T>) -> Result<usize,TreeError<T>> {
+        if lower.is_some_and(|lower| self.value <= *lower) || upper.is_some_and(|upper| self.value >= *upper) {
+            return Err(TreeError::InvariantViolation(format!("value at {path} is out of order or stored in more than one node")));
+        }
+        if self.count == 0 {
+            return Err(TreeError::InvariantViolation(format!("node at {path} has a count of zero")));
+        }
+        let mut total = self.count;
+        let path_len = path.len();
+        if let Some(node) = &self.left {
+            path.push_str(".left");
+            total += node.check_invariants(path, lower, Some(&self.value))?;
+            path.truncate(path_len);
+        }
+        if let Some(node) = &self.right {
+            path.push_str(".right");
+            total += node.check_invariants(path, Some(&self.value), upper)?;
+            path.truncate(path_len);
+        }
+        Ok(total)
+    }
+
+    /// Sketch the shape of this (sub)tree, for the `paranoid` feature's diagnostics, without needing to print the values.
+    /// Nodes are shown by their counts, e.g. `(. 1 (. 3 .))`.
+    #[cfg(feature = "paranoid")]
+    fn outline(&self) -> String {
+        let left = self.left.as_ref().map_or(String::from("."), |node| node.outline());
+        let right = self.right.as_ref().map_or(String::from("."), |node| node.outline());
+        format!("({left} {} {right})", self.count)
+    }
+
+    /// Recursively tally this (sub)tree's nodes, at the given depth, into the stats.
+    pub fn collect_stats(&self, depth: usize, stats: &mut TreeStats) {
+        stats.record_node(depth, self.is_leaf());
+        match &self.left {
+            Some(node) => node.collect_stats(depth + 1, stats),
+            None => (),
+        }
+        match &self.right {
+            Some(node) => node.collect_stats(depth + 1, stats),
+            None => (),
+        }
+    }
+
+    /// Returns true if the node is a leaf or terminal node, with no child nodes of its own.
+    pub fn is_leaf(&self) -> bool {
+        self.left.is_none() && self.right.is_none()
+    }
+
+    /// Recursively call `f` on this (sub)tree's values in the given order, stopping as soon as it returns `ControlFlow::Break`.
+    pub fn try_visit<B, F: FnMut(&T) -> ControlFlow<B>>(&self, order: Traversal, f: &mut F) -> ControlFlow<B> {
+        if order == Traversal::Pre {
+            self.visit_value(f)?;
+        }
+        if let Some(node) = &self.left {
+            node.try_visit(order, f)?;
+        }
+        if order == Traversal::In {
+            self.visit_value(f)?;
+        }
+        if let Some(node) = &self.right {
+            node.try_visit(order, f)?;
+        }
+        if order == Traversal::Post {
+            self.visit_value(f)?;
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Call `f` on this node's value, once for each time it was added.
+    fn visit_value<B, F: FnMut(&T) -> ControlFlow<B>>(&self, f: &mut F) -> ControlFlow<B> {
+        for _ in 0..self.count {
+            f(&self.value)?;
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Remove the node with the smallest value from this (sub)tree, whatever its count, returning
+    /// the node that replaces this one along with the removed node's value and count.
+    fn take_least(mut self: Box<Self>) -> (Option<Box<Node<T>>>, T, usize) {
+        return match self.left.take() {
+            None => (self.right, self.value, self.count),
+            Some(left_child) => {
+                let (new_left, value, count) = left_child.take_least();
+                self.left = new_left;
+                (Some(self), value, count)
+            },
+        }
+    }
+
+    /// If the value exists in this sub-tree, drop up to `n` of its duplicates, returning to the parent
+    /// a pointer to the Node that replaces this one, or None if this node
+    /// is removed by the change.  Called recursively.
+    /// 
+    /// Because 'self' is consumed, we need to return a node to replace it
+    /// even in case of error, hence we're returning a tuple of Result (to be interpreted)
+    /// and Option<Box<Node>> to replace the current node in the parent.
+    /// 
+    pub fn drop_value(mut self, value: T, n: usize) -> DropOutcome<T> {
+
+        // if the value is less than this node's value, and we have a left child, call 'drop_value' on the left child
+        if value < self.value {
+            match self.left {
+                None => return (Err(TreeError::ValueNotFound { value }), Some(Box::new(self))),
+                Some(left_child) => {
+                    match left_child.drop_value(value, n) {
+                        (Err(error), new_node) => {
+                            self.left = new_node;
+                            return (Err(error), Some(Box::new(self)));
+                        },
+                        (Ok(dropped), new_node) => {
+                            self.left = new_node;
+                            return (Ok(dropped), Some(Box::new(self)));
+                        } 
+                    }
+                }
+            }
+        }
+        // if the value is greater than this node's value, and we have a right child, call 'drop_value' on the right child
+        else if value > self.value {
+            match self.right {
+                None => return (Err(TreeError::ValueNotFound { value }), Some(Box::new(self))),
+                Some(right_child) => {
+                    match right_child.drop_value(value, n) {
+                        (Err(error), new_node) => {
+                            self.right = new_node;
+                            return (Err(error), Some(Box::new(self)));
+                        },
+                        (Ok(dropped), new_node) => {
+                            self.right = new_node;
+                            return (Ok(dropped), Some(Box::new(self)));
+                        } 
+                    }
+                }
+            }
+        }
+        // if this node has the exact value:
+        else {
+            // - if more duplicates than are to be dropped remain, just decrement the count
+            if self.count > n {
+                self.count -= n;
+                return ( Ok(n), Some(Box::new(self)) );
+            }
+            // - otherwise the node goes, with all its duplicates
+            let dropped = self.count;
+            // - if it has no children, just replace it with None
+            if self.is_leaf() {
+                return (Ok(dropped), None);
+            }
+            // - if it has no left branch, replace it with its right child (and subtree)
+            if self.left.is_none() {
+                return (Ok(dropped), self.right);
+            }
+            // - if it has no right branch, replace it with its left child (and subtree)
+            if self.right.is_none() {
+                return (Ok(dropped), self.left);
+            }
+            // - if the root's right child is a leaf, replace its value (and count) with its right leaf (and drop that leaf)
+            if self.right.as_ref().unwrap().is_leaf() {
+                let right_child = self.right.take().unwrap();
+                self.value = right_child.value;
+                self.count = right_child.count;
+                return (Ok(dropped), Some(Box::new(self)));
+            }
+            // - otherwise, if the root's left child is a leaf, replace its value (and count) with its left leaf (and drop that leaf)
+            if self.left.as_ref().unwrap().is_leaf() {
+                let left_child = self.left.take().unwrap();
+                self.value = left_child.value;
+                self.count = left_child.count;
+                return (Ok(dropped), Some(Box::new(self)));
+            }
+            // - if we get to this point, both children are branches. Move the node holding the immediate successor
+            //   (with all its duplicates) out of the right branch, and take its value and count
+            let (new_right, successor_value, successor_count) = self.right.take().unwrap().take_least();
+            self.right = new_right;
+            self.value = successor_value;
+            self.count = successor_count;
+            return (Ok(dropped), Some(Box::new(self)));
+        }
+
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd + Clone> Node<T> {
+
+    /// Recursively add this (sub)tree's nodes to the graph, with edges from each node to its children.
+    /// Returns the index of this node in the graph.
+    #[cfg(feature = "petgraph")]
+    pub fn add_to_graph(&self, graph: &mut DiGraph<T, Branch>) -> NodeIndex {
+        let index = graph.add_node(self.value.clone());
+        match &self.left {
+            Some(node) => {
+                let child = node.add_to_graph(graph);
+                graph.add_edge(index, child, Branch::Left);
+            },
+            None => (),
+        }
+        match &self.right {
+            Some(node) => {
+                let child = node.add_to_graph(graph);
+                graph.add_edge(index, child, Branch::Right);
+            },
+            None => (),
+        }
+        index
+    }
+
+    /// Returns the smallest/lowest value in this (sub)tree.
+    pub fn least_value(&self) -> T {
+        return match &self.left {
+            None => self.value.clone(),
+            Some(left_child) => left_child.least_value(),
+        }
+    }
+
+    /// Returns the largest/highest value in this (sub)tree.
+    pub fn greatest_value(&self) -> T {
+        return match &self.right {
+            None => self.value.clone(),
+            Some(right_child) => right_child.greatest_value(),
+        }
+    }
+
+    /// Recursively add values to the borrowed vector, traversing the tree from left to right.
+    pub fn collect_values_l_to_r(&self, value_vector: &mut Vec<T>) {
+        match &self.left {
+            Some(node) => node.collect_values_l_to_r(value_vector),
+            None => (),
+        }
+        value_vector.extend(vec![self.value.clone(); self.count]);
+        match &self.right {
+            Some(node) => node.collect_values_l_to_r(value_vector),
+            None => (),
+        }
+    }
+
+    /// Recursively add values to the borrowed vector, traversing the tree from left to right, until it holds `n` values.
+    pub fn collect_first_n(&self, n: usize, value_vector: &mut Vec<T>) {
+        if value_vector.len() >= n {
+            return;
+        }
+        match &self.left {
+            Some(node) => node.collect_first_n(n, value_vector),
+            None => (),
+        }
+        if value_vector.len() >= n {
+            return;
+        }
+        let wanted = min(self.count, n - value_vector.len());
+        value_vector.extend(vec![self.value.clone(); wanted]);
+        match &self.right {
+            Some(node) => node.collect_first_n(n, value_vector),
+            None => (),
+        }
+    }
+
+    /// Recursively add values to the borrowed vector, traversing the tree from right to left, until it holds `n` values.
+    pub fn collect_last_n(&self, n: usize, value_vector: &mut Vec<T>) {
+        if value_vector.len() >= n {
+            return;
+        }
+        match &self.right {
+            Some(node) => node.collect_last_n(n, value_vector),
+            None => (),
+        }
+        if value_vector.len() >= n {
+            return;
+        }
+        let wanted = min(self.count, n - value_vector.len());
+        value_vector.extend(vec![self.value.clone(); wanted]);
+        match &self.left {
+            Some(node) => node.collect_last_n(n, value_vector),
+            None => (),
+        }
+    }
+
+    /// Recursively add values to the borrowed vector, traversing the tree from right to left.
+    pub fn collect_values_r_to_l(&self, value_vector: &mut Vec<T>) {
+        match &self.right {
+            Some(node) => node.collect_values_r_to_l(value_vector),
+            None => (),
+        }
+        value_vector.extend(vec![self.value.clone(); self.count]);
+        match &self.left {
+            Some(node) => node.collect_values_r_to_l(value_vector),
+            None => (),
+        }
+    }
+
+}
+
+/// Visits the nodes of a tree in order, from least to greatest value, along with their depths, from either end.
+/// It keeps a queue of the nodes still to come, in order, so it only holds about one path's worth of nodes for
+/// each end at a time.  Each one queued stands for itself along with its left and right subtrees, unless the
+/// nodes of a subtree have been queued separately.
+struct Nodes<'a, T: PartialEq + PartialOrd> {
+    queue: VecDeque<(&'a Node<T>, usize, bool, bool)>, // a node, its depth, and whether its left and right subtrees come with it
+}
+
+impl <'a, T: PartialEq + PartialOrd> Nodes<'a, T> {
+
+    fn new(root: &'a Option<Box<Node<T>>>) -> Self {
+        Self { queue: root.iter().map(|node| (&**node, 0, true, true)).collect() }
+    }
+
+    /// Queue just the nodes whose values are within the range, for `range`.
+    fn within<R: RangeBounds<T>>(root: &'a Option<Box<Node<T>>>, range: &R) -> Self {
+        Self { queue: queue_within(root.as_deref(), range) }
+    }
+
+}
+
+impl <'a, T: PartialEq + PartialOrd> Iterator for Nodes<'a, T> {
+    type Item = (&'a Node<T>, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (node, depth, with_left, with_right) = self.queue.pop_front()?;
+            if let (true, Some(left)) = (with_left, &node.left) {
+                // the left subtree comes first
+                self.queue.push_front((node, depth, false, with_right));
+                self.queue.push_front((left, depth + 1, true, true));
+                continue;
+            }
+            if let (true, Some(right)) = (with_right, &node.right) {
+                self.queue.push_front((right, depth + 1, true, true));
+            }
+            return Some((node, depth));
+        }
+    }
+}
+
+impl <'a, T: PartialEq + PartialOrd> DoubleEndedIterator for Nodes<'a, T> {
+
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            let (node, depth, with_left, with_right) = self.queue.pop_back()?;
+            if let (true, Some(right)) = (with_right, &node.right) {
+                // the right subtree comes last
+                self.queue.push_back((node, depth, with_left, false));
+                self.queue.push_back((right, depth + 1, true, true));
+                continue;
+            }
+            if let (true, Some(left)) = (with_left, &node.left) {
+                self.queue.push_back((left, depth + 1, true, true));
+            }
+            return Some((node, depth));
+        }
+    }
+}
+
+/// An iterator over a Jblst's values in order, with each value repeated once for each time it was added, from
+/// least to greatest or (with `rev` or `next_back`) from greatest to least, or from both ends at once.  Get one
+/// with `iter`.
+pub struct Iter<'a, T: PartialEq + PartialOrd> {
+    nodes: Nodes<'a, T>,
+    front_repeats: Option<(&'a T, usize)>, // a value still to be yielded from the front, and how many more times
+    back_repeats: Option<(&'a T, usize)>, // likewise from the back
+}
+
+impl <'a, T: PartialEq + PartialOrd> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(value) = take_repeat(&mut self.front_repeats) {
+            return Some(value);
+        }
+        match self.nodes.next() {
+            // once the nodes run out, the last value the back took may still have copies to come
+            None => take_repeat(&mut self.back_repeats),
+            Some((node, _)) => {
+                self.front_repeats = Some((&node.value, node.count));
+                take_repeat(&mut self.front_repeats)
+            },
+        }
+    }
+}
+
+impl <'a, T: PartialEq + PartialOrd> DoubleEndedIterator for Iter<'a, T> {
+
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if let Some(value) = take_repeat(&mut self.back_repeats) {
+            return Some(value);
+        }
+        match self.nodes.next_back() {
+            None => take_repeat(&mut self.front_repeats),
+            Some((node, _)) => {
+                self.back_repeats = Some((&node.value, node.count));
+                take_repeat(&mut self.back_repeats)
+            },
+        }
+    }
+}
+
+impl <'a, T: PartialEq + PartialOrd> IntoIterator for &'a Jblst<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator that moves the values out of a Jblst in order (from either end), taking the tree apart as it
+/// goes (duplicates are yielded once for each time they were added).  Made by `into_iter`, as in `for value in tree`.
+pub struct IntoIter<T: PartialEq + PartialOrd + Clone> {
+    drain: Drain<T>,
+}
+
+impl <T: PartialEq + PartialOrd + Clone> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.drain.next()
+    }
+}
+
+impl <T: PartialEq + PartialOrd + Clone> DoubleEndedIterator for IntoIter<T> {
+
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.drain.next_back()
+    }
+}
+
+impl <T: PartialEq + PartialOrd + Clone> IntoIterator for Jblst<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        IntoIter { drain: self.take_nodes() }
+    }
+}
+
+/// Takes the values out of a tree's nodes in order, from either end, for `drain` and `into_iter`.  Like `Nodes`, it
+/// keeps a queue of the nodes still to come, each standing for itself and the children it still holds; children
+/// are taken from their parent as they're queued.
+struct Drain<T: PartialEq + PartialOrd + Clone> {
+    queue: VecDeque<Box<Node<T>>>,
+    front_repeats: Option<(T, usize)>, // a value still to be yielded again from the front, and how many more times
+    back_repeats: Option<(T, usize)>, // likewise from the back
+}
+
+impl <T: PartialEq + PartialOrd + Clone> Drain<T> {
+
+    fn new(root: Option<Box<Node<T>>>) -> Self {
+        Self { queue: root.into_iter().collect(), front_repeats: None, back_repeats: None }
+    }
+
+    /// Take the least node still to come out of the queue.
+    fn pop_least(&mut self) -> Option<Box<Node<T>>> {
+        loop {
+            let mut node = self.queue.pop_front()?;
+            if let Some(left) = node.left.take() {
+                self.queue.push_front(node);
+                self.queue.push_front(left);
+                continue;
+            }
+            if let Some(right) = node.right.take() {
+                self.queue.push_front(right);
+            }
+            return Some(node);
+        }
+    }
+
+    /// Take the greatest node still to come out of the queue.
+    fn pop_greatest(&mut self) -> Option<Box<Node<T>>> {
+        loop {
+            let mut node = self.queue.pop_back()?;
+            if let Some(right) = node.right.take() {
+                self.queue.push_back(node);
+                self.queue.push_back(right);
+                continue;
+            }
+            if let Some(left) = node.left.take() {
+                self.queue.push_back(left);
+            }
+            return Some(node);
+        }
+    }
+
+}
+
+/// Yield one of a value's repeats (cloning it if there'll be more, which for the references `Iter` holds is
+/// cheap), or None if there are none left.
+fn take_repeat<T: Clone>(repeats: &mut Option<(T, usize)>) -> Option<T> {
+    return match repeats.take() {
+        None => None,
+        Some((value, 1)) => Some(value),
+        Some((value, remaining)) => {
+            *repeats = Some((value.clone(), remaining - 1));
+            Some(value)
+        },
+    };
+}
+
+impl <T: PartialEq + PartialOrd + Clone> Iterator for Drain<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(value) = take_repeat(&mut self.front_repeats) {
+            return Some(value);
+        }
+        match self.pop_least() {
+            // once the nodes run out, the last value the back took may still have copies to come
+            None => take_repeat(&mut self.back_repeats),
+            Some(node) => {
+                self.front_repeats = Some((node.value, node.count));
+                take_repeat(&mut self.front_repeats)
+            },
+        }
+    }
+}
+
+impl <T: PartialEq + PartialOrd + Clone> DoubleEndedIterator for Drain<T> {
+
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if let Some(value) = take_repeat(&mut self.back_repeats) {
+            return Some(value);
+        }
+        match self.pop_greatest() {
+            None => take_repeat(&mut self.front_repeats),
+            Some(node) => {
+                self.back_repeats = Some((node.value, node.count));
+                take_repeat(&mut self.back_repeats)
+            },
+        }
+    }
+}
+
+/// A position among a Jblst's values, which can step to the next or previous value in amortized O(1) time.
+/// Get one with `cursor_at`, `cursor_front` or `cursor_back`.  Besides the values, there's a "ghost" position
+/// past both ends, where `peek` returns None: stepping back from it goes to the greatest value.  As an `Iterator`,
+/// the cursor yields the values after its position, and stops for good at the ghost position.
+/// Duplicates are visited once for each time they were added.
+pub struct Cursor<'a, T: PartialEq + PartialOrd> {
+    root: &'a Option<Box<Node<T>>>,
+    path: Vec<&'a Node<T>>, // from the root down to the current node; empty at the ghost position
+    copy: usize, // which of the current node's duplicates the cursor is on, counting from 0
+}
+
+impl <'a, T: PartialEq + PartialOrd> Cursor<'a, T> {
+
+    fn new(root: &'a Option<Box<Node<T>>>) -> Self {
+        Self { root, path: Vec::new(), copy: 0 }
+    }
+
+    /// A cursor positioned at the least value (or the ghost position, if there are none).
+    fn front(root: &'a Option<Box<Node<T>>>) -> Self {
+        let mut cursor = Self::new(root);
+        cursor.push_left_edge(root);
+        cursor
+    }
+
+    /// Returns the value at the cursor's position, without moving.
+    pub fn peek(&self) -> Option<&'a T> {
+        return match self.path.last() {
+            None => None,
+            Some(node) => Some(&node.value),
+        };
+    }
+
+    /// Move to the previous value and return it, or None on reaching the ghost position.
+    pub fn prev(&mut self) -> Option<&'a T> {
+        if self.copy > 0 {
+            self.copy -= 1;
+        } else {
+            self.step_back();
+            self.copy = self.path.last().map_or(0, |node| node.count - 1);
+        }
+        self.peek()
+    }
+
+    /// Position the cursor on the least node whose value is at least `value`, or the ghost position if there's none.
+    fn seek(&mut self, value: &T) {
+        let mut link = self.root;
+        let mut found = 0;
+        while let Some(node) = link {
+            self.path.push(node);
+            if *value == node.value {
+                found = self.path.len();
+                break;
+            }
+            if *value < node.value {
+                found = self.path.len();
+                link = &node.left;
+            } else {
+                link = &node.right;
+            }
+        }
+        self.path.truncate(found);
+    }
+
+    /// Move to the next node in order, or from the last node to the ghost position, or from the ghost
+    /// position to the first node.
+    fn step_forward(&mut self) {
+        match self.path.last().copied() {
+            None => self.push_left_edge(self.root),
+            Some(node) if node.right.is_some() => self.push_left_edge(&node.right),
+            Some(_) => {
+                // climb until we come up out of a left subtree; that parent is next
+                let mut child = self.path.pop();
+                while let (Some(parent), Some(from)) = (self.path.last(), child) {
+                    if parent.left.as_deref().is_some_and(|left| std::ptr::eq(left, from)) {
+                        return;
+                    }
+                    child = self.path.pop();
+                }
+            },
+        }
+    }
+
+    /// Move to the previous node in order, or from the first node to the ghost position, or from the ghost
+    /// position to the last node.
+    fn step_back(&mut self) {
+        match self.path.last().copied() {
+            None => self.push_right_edge(self.root),
+            Some(node) if node.left.is_some() => self.push_right_edge(&node.left),
+            Some(_) => {
+                // climb until we come up out of a right subtree; that parent is previous
+                let mut child = self.path.pop();
+                while let (Some(parent), Some(from)) = (self.path.last(), child) {
+                    if parent.right.as_deref().is_some_and(|right| std::ptr::eq(right, from)) {
+                        return;
+                    }
+                    child = self.path.pop();
+                }
+            },
+        }
+    }
+
+    /// Descend from the node to the least node in its subtree.
+    fn push_left_edge(&mut self, mut link: &'a Option<Box<Node<T>>>) {
+        while let Some(node) = link {
+            self.path.push(node);
+            link = &node.left;
+        }
+    }
+
+    /// Descend from the node to the greatest node in its subtree.
+    fn push_right_edge(&mut self, mut link: &'a Option<Box<Node<T>>>) {
+        while let Some(node) = link {
+            self.path.push(node);
+            link = &node.right;
+        }
+    }
+
+}
+
+impl <'a, T: PartialEq + PartialOrd> Iterator for Cursor<'a, T> {
+    type Item = &'a T;
+
+    /// Move to the next value and return it, or None on reaching (or being at) the ghost position.
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.path.is_empty() {
+            return None;
+        }
+        if self.path.last().is_some_and(|node| self.copy + 1 < node.count) {
+            self.copy += 1;
+        } else {
+            self.step_forward();
+            self.copy = 0;
+        }
+        self.peek()
+    }
+}
+
+impl <'a, T: PartialEq + PartialOrd> FusedIterator for Cursor<'a, T> {}
+
+/// A cursor like `Cursor` that can also remove the value at its position, or insert values next to it, for
+/// editing a Jblst in one pass.  Get one with `cursor_mut_at`, `cursor_mut_front` or `cursor_mut_back`.  Because it
+/// borrows the tree mutably while changing its structure, it remembers its position by value (and which of the
+/// value's duplicates it's on), so each step is a search from the root.
+///
+///     use jtree::Jblst;
+///
+///     let mut my_tree = Jblst::from_collection([1,2,4,4,6]);
+///     let mut cursor = my_tree.cursor_mut_at(&4);
+///     assert_eq!( Some(4), cursor.remove_current() );
+///     assert_eq!( Ok(()), cursor.insert_before(3) );
+///     assert_eq!( Ok(()), cursor.insert_after(5) );
+///     assert!( cursor.insert_after(7).is_err() ); // it wouldn't be next to the cursor
+///     assert_eq!( vec!(1,2,3,4,5,6), my_tree.as_vec() );
+pub struct CursorMut<'a, T: PartialEq + PartialOrd + Clone> {
+    tree: &'a mut Jblst<T>,
+    current: Option<T>, // the value at the cursor's position, or None at the ghost position
+    copy: usize, // which of the current value's duplicates the cursor is on, counting from 0
 }
 
-impl <T: PartialEq + PartialOrd + Clone> Default for Jblst<T> {
-    fn default() -> Self {
-        Self::new()
+impl <'a, T: PartialEq + PartialOrd + Clone> CursorMut<'a, T> {
+
+    fn new(tree: &'a mut Jblst<T>, current: Option<T>) -> Self {
+        Self { tree, current, copy: 0 }
     }
-}
 
-impl <T: PartialEq + PartialOrd + Clone + std::fmt::Debug> fmt::Debug for Jblst<T> {
-    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
-        fmt.debug_struct("Jblst")
-            .field("size", &self.get_size())
-            .field("values", &self.as_vec())
-            .finish()
+    /// Returns the value at the cursor's position, without moving.
+    pub fn peek(&self) -> Option<&T> {
+        self.current.as_ref()
     }
-}
 
-struct Node<T: PartialEq + PartialOrd + Clone> {
-    value: T,
-    count: usize, // duplicate values are counted, rather than getting new nodes
-    left: Option<Box<Node<T>>>,
-    right: Option<Box<Node<T>>>,
-}
+    /// Move to the next value and return it, or None on reaching the ghost position.
+    pub fn move_next(&mut self) -> Option<&T> {
+        if self.copy + 1 < self.current_count() {
+            self.copy += 1;
+        } else {
+            self.current = self.next_distinct().cloned();
+            self.copy = 0;
+        }
+        self.current.as_ref()
+    }
 
-impl <T:PartialEq + PartialOrd + Clone> Node<T> {
+    /// Move to the previous value and return it, or None on reaching the ghost position.
+    pub fn move_prev(&mut self) -> Option<&T> {
+        if self.copy > 0 {
+            self.copy -= 1;
+        } else {
+            self.current = self.prev_distinct().cloned();
+            self.copy = self.current_count().saturating_sub(1);
+        }
+        self.current.as_ref()
+    }
 
-    pub fn new(value: T) -> Self {
-        Self {
-            value,
-            count: 1,
-            left: None,
-            right: None,
+    /// Remove the value at the cursor's position and return it, moving the cursor on to the next value.
+    /// At the ghost position, nothing is removed and None is returned.
+    pub fn remove_current(&mut self) -> Option<T> {
+        let removed = self.current.clone()?;
+        if self.copy + 1 == self.current_count() {
+            // that was the last copy the cursor could be on, so it moves to the next distinct value
+            self.current = self.next_distinct().cloned();
+            self.copy = 0;
         }
+        let _ = self.tree.drop_value(removed.clone());
+        Some(removed)
     }
 
-    /// Insert a value
-    pub fn add(&mut self, value: T) -> Result<(),TreeError> {
-        if value == self.value {
-            // increment the count
-            self.count += 1;
-            return Ok(());
+    /// Insert a value just before the cursor's position (or at the end of the tree, from the ghost position), leaving
+    /// the cursor where it was.  The value must belong there: no less than the previous value and no greater than the
+    /// current one, otherwise a TreeError::OutOfOrder is returned.
+    pub fn insert_before(&mut self, value: T) -> Result<(),TreeError<T>> {
+        let lower = match self.copy {
+            0 => self.prev_distinct(),
+            _ => self.current.as_ref(),
+        };
+        let upper = self.current.as_ref();
+        if !(lower.is_none_or(|lower| *lower <= value) && upper.is_none_or(|upper| value <= *upper)) {
+            return Err(TreeError::OutOfOrder { value });
         }
-        if value < self.value {
-            // add to the left branch
-            match &mut self.left {
-                None => self.left = Some(Box::new(Node::new(value))),
-                Some(branch) => branch.add(value)?,
-            }
-            return Ok(());
-        } else {
-            // add it to the right branch
-            match &mut self.right {
-                None => self.right = Some(Box::new(Node::new(value))),
-                Some(branch) => branch.add(value)?,
-            }
-            return Ok(());
+        if self.current.as_ref() == Some(&value) {
+            // the new copy comes before the one the cursor is on
+            self.copy += 1;
         }
+        self.tree.add(value)
     }
 
-    /// Returns true if the value is currently a member of the (sub)tree
-    pub fn contains(&self, value: &T) -> bool {
-        if *value == self.value {
-            return true;
-        }
-        if *value < self.value {
-            match &self.left {
-                Some(node) => node.contains(value),
-                None => return false
-            }
-        } else {
-            match &self.right {
-                Some(node) => node.contains(value),
-                None => return false
-            }
+    /// Insert a value just after the cursor's position (or at the start of the tree, from the ghost position), leaving
+    /// the cursor where it was.  The value must belong there: no less than the current value and no greater than the
+    /// next one, otherwise a TreeError::OutOfOrder is returned.
+    pub fn insert_after(&mut self, value: T) -> Result<(),TreeError<T>> {
+        let lower = self.current.as_ref();
+        let upper = match self.copy + 1 < self.current_count() {
+            true => self.current.as_ref(),
+            false => self.next_distinct(),
+        };
+        if !(lower.is_none_or(|lower| *lower <= value) && upper.is_none_or(|upper| value <= *upper)) {
+            return Err(TreeError::OutOfOrder { value });
         }
+        self.tree.add(value)
     }
 
-    /// Returns true if the node is a leaf or terminal node, with no child nodes of its own.
-    pub fn is_leaf(&self) -> bool {
-        self.left.is_none() && self.right.is_none()
+    /// How many times the value at the cursor's position is stored (0 at the ghost position).
+    fn current_count(&self) -> usize {
+        return match &self.current {
+            None => 0,
+            Some(value) => {
+                let mut cursor = Cursor::new(&self.tree.root);
+                cursor.seek(value);
+                cursor.path.last().map_or(0, |node| node.count)
+            },
+        };
     }
 
-    /// Returns the smallest/lowest value in this (sub)tree.
-    pub fn least_value(&self) -> T {
-        return match &self.left {
-            None => self.value.clone(),
-            Some(left_child) => left_child.least_value(),
+    /// The least value greater than the cursor's (or the least value in the tree, from the ghost position).
+    fn next_distinct(&self) -> Option<&T> {
+        let mut cursor = Cursor::new(&self.tree.root);
+        match &self.current {
+            None => (),
+            Some(value) => cursor.seek(value),
         }
+        cursor.step_forward();
+        cursor.peek()
     }
 
-    /// Returns the largest/highest value in this (sub)tree.
-    pub fn greatest_value(&self) -> T {
-        return match &self.right {
-            None => self.value.clone(),
-            Some(right_child) => right_child.greatest_value(),
+    /// The greatest value less than the cursor's (or the greatest value in the tree, from the ghost position).
+    fn prev_distinct(&self) -> Option<&T> {
+        let mut cursor = Cursor::new(&self.tree.root);
+        match &self.current {
+            None => (),
+            Some(value) => cursor.seek(value),
         }
+        cursor.step_back();
+        cursor.peek()
     }
 
-    /// Recursively add values to the borrowed vector, traversing the tree from left to right.
-    pub fn collect_values_l_to_r(&self, value_vector: &mut Vec<T>) {
+}
+
+impl <T: PartialEq + PartialOrd + Hash> Node<T> {
+
+    /// Recursively feed this (sub)tree's values to the hasher, from left to right.
+    fn hash_values<H: Hasher>(&self, hasher: &mut H) {
         match &self.left {
-            Some(node) => node.collect_values_l_to_r(value_vector),
+            Some(node) => node.hash_values(hasher),
             None => (),
         }
-        value_vector.extend(vec![self.value.clone(); self.count]);
+        self.value.hash(hasher);
+        self.count.hash(hasher);
         match &self.right {
-            Some(node) => node.collect_values_l_to_r(value_vector),
+            Some(node) => node.hash_values(hasher),
             None => (),
         }
     }
 
-    /// Recursively add values to the borrowed vector, traversing the tree from right to left.
-    pub fn collect_values_r_to_l(&self, value_vector: &mut Vec<T>) {
-        match &self.right {
-            Some(node) => node.collect_values_r_to_l(value_vector),
-            None => (),
+}
+
+impl <T: PartialEq + PartialOrd + Codec> Node<T> {
+
+    /// Recursively encode this (sub)tree's node records, in pre-order.
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        let mut flags = 0;
+        if self.left.is_some() {
+            flags |= HAS_LEFT;
         }
-        value_vector.extend(vec![self.value.clone(); self.count]);
+        if self.right.is_some() {
+            flags |= HAS_RIGHT;
+        }
+        flags.encode(bytes);
+        self.count.encode(bytes);
+        self.value.encode(bytes);
         match &self.left {
-            Some(node) => node.collect_values_r_to_l(value_vector),
+            Some(node) => node.encode(bytes),
+            None => (),
+        }
+        match &self.right {
+            Some(node) => node.encode(bytes),
             None => (),
         }
     }
 
-    /// If the value exists in this sub-tree, drop it, returning to the parent
-    /// a pointer to the Node that replaces this one, or None if this node
-    /// is removed by the change.  Called recursively.
-    /// 
-    /// Because 'self' is consumed, we need to return a node to replace it
-    /// even in case of error, hence we're returning a tuple of Result (to be interpreted)
-    /// and Option<Box<Node>> to replace the current node in the parent.
-    /// 
-    pub fn drop_value(mut self, value: T) -> (Result<(),TreeError>, Option<Box<Node<T>>>) {
-
-        // if the value is less than this node's value, and we have a left child, call 'drop_value' on the left child
-        if value < self.value {
-            match self.left {
-                None => return (Err(TreeError::ValueNotFound), Some(Box::new(self))),
-                Some(left_child) => {
-                    match left_child.drop_value(value) {
-                        (Err(_), new_node) => {
-                            self.left = new_node;
-                            return (Err(TreeError::ValueNotFound), Some(Box::new(self)));
-                        },
-                        (Ok(_), new_node) => {
-                            self.left = new_node;
-                            return (Ok(()), Some(Box::new(self)));
-                        } 
-                    }
-                }
-            }
+    /// Recursively decode a (sub)tree encoded by `encode`, whose root is at the given depth, refusing to
+    /// go deeper than `depth_limit`.
+    fn decode(reader: &mut Reader, depth: usize, depth_limit: usize) -> Result<Box<Node<T>>,TreeError<T>> {
+        let offset = reader.offset();
+        if depth > depth_limit {
+            return Err(TreeError::DeserializationCorrupt { offset, reason: format!("the tree is deeper than its depth limit ({depth_limit})") });
         }
-        // if the value is greater than this node's value, and we have a right child, call 'drop_value' on the right child
-        else if value > self.value {
-            match self.right {
-                None => return (Err(TreeError::ValueNotFound), Some(Box::new(self))),
-                Some(right_child) => {
-                    match right_child.drop_value(value) {
-                        (Err(_), new_node) => {
-                            self.right = new_node;
-                            return (Err(TreeError::ValueNotFound), Some(Box::new(self)));
-                        },
-                        (Ok(_), new_node) => {
-                            self.right = new_node;
-                            return (Ok(()), Some(Box::new(self)));
-                        } 
-                    }
-                }
-            }
+        let flags: u8 = reader.read()?;
+        if flags & !(HAS_LEFT | HAS_RIGHT) != 0 {
+            return Err(TreeError::DeserializationCorrupt { offset, reason: format!("{flags:#x} isn't a valid node record") });
         }
-        // if this node has the exact value:
-        else {
-            // - if it's a duplicate (count >= 2), just decrement the count
-            if self.count > 1 {
-                self.count -= 1;
-                return ( Ok(()), Some(Box::new(self)) );
-            }
-            // - if it has no children, just replace it with None
-            if self.is_leaf() {
-                return (Ok(()), None);
-            }
-            // - if it has no left branch, replace it with its right child (and subtree)
-            if self.left.is_none() {
-                return (Ok(()), self.right);
-            }
-            // - if it has no right branch, replace it with its left child (and subtree)
-            if self.right.is_none() {
-                return (Ok(()), self.left);
-            }
-            // - if the root's right child is a leaf, replace its value with its right leaf (and drop that leaf)
-            let right_child = self.right.as_ref().unwrap();
-            if right_child.is_leaf() {
-                self.value = right_child.value.clone();
-                self.right = None;
-                return (Ok(()), Some(Box::new(self)));
-            }
-            // - otherwise, if the root's left child is a leaf, replace its value with its left leaf (and drop that leaf)
-            let left_child = self.left.as_ref().unwrap();
-            if left_child.is_leaf() {
-                self.value = left_child.value.clone();
-                self.left = None;
-                return (Ok(()), Some(Box::new(self)));
-            }
-            // - if we get to this point, both children are branches. Replace the root's value with its immediate successor, 
-            //   then recursively tell its right branch to remove that successor
-            self.value = right_child.least_value();
-            self.right = self.right.unwrap().drop_value(self.value.clone()).1;
-            return (Ok(()), Some(Box::new(self)));
+        let count: usize = reader.read()?;
+        let mut node = Node::new(reader.read()?, count);
+        if flags & HAS_LEFT != 0 {
+            node.left = Some(Node::decode(reader, depth + 1, depth_limit)?);
+        }
+        if flags & HAS_RIGHT != 0 {
+            node.right = Some(Node::decode(reader, depth + 1, depth_limit)?);
+        }
+        Ok(Box::new(node))
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd + std::fmt::Debug> Node<T> {
+
+    /// Recursively draw this (sub)tree for `to_ascii`: first the right subtree, then this node, then the left subtree.
+    /// The `prefix` holds the indentation and `|` lines carried down from ancestors, and the `link` is the
+    /// branch leading up (`/-- `) or down (`\-- `) to this node from its parent, or empty for the root.
+    fn draw(&self, drawing: &mut String, prefix: &str, link: &str) {
+        let (above, below) = match link {
+            "" => (String::from(prefix), String::from(prefix)),
+            "/-- " => (format!("{prefix}    "), format!("{prefix}|   ")),
+            _ => (format!("{prefix}|   "), format!("{prefix}    ")),
+        };
+        match &self.right {
+            Some(node) => node.draw(drawing, &above, "/-- "),
+            None => (),
+        }
+        drawing.push_str(&format!("{prefix}{link}{}\n", self.label()));
+        match &self.left {
+            Some(node) => node.draw(drawing, &below, "\\-- "),
+            None => (),
         }
+    }
 
+    /// How this node is shown by `to_ascii`: its value, and its count if it holds duplicates.
+    fn label(&self) -> String {
+        if self.count > 1 {
+            return format!("{:?} x{}", self.value, self.count);
+        }
+        format!("{:?}", self.value)
     }
 
 }
@@ -390,17 +1867,17 @@ mod tests {
     #[test]
     fn add_collection() {
         let mut my_tree = Jblst::new();
-        assert_eq!( Ok(()), my_tree.add_all(vec!(1,2,3,4,5)));
-        assert_eq!( Ok(()), my_tree.add_all([6,7,8,9,10]));
+        assert_eq!( Ok(AddReport { inserted: 5, skipped: 0 }), my_tree.add_all(vec!(1,2,3,4,5)));
+        assert_eq!( Ok(AddReport { inserted: 5, skipped: 0 }), my_tree.add_all([6,7,8,9,10]));
         assert_eq!( 10, my_tree.get_size() );
-        assert_eq!( Ok(()), my_tree.add_all([5,10,15,20]));
+        assert_eq!( Ok(AddReport { inserted: 4, skipped: 0 }), my_tree.add_all([5,10,15,20]));
         assert_eq!( 14, my_tree.get_size() ); // duplicates were inlcuded
     }
 
     #[test]
     fn test_contains() {
         let mut my_tree = Jblst::new();
-        assert_eq!( Ok(()), my_tree.add_all(vec!(8,6,7,5,3,0,9)));
+        assert_eq!( Ok(AddReport { inserted: 7, skipped: 0 }), my_tree.add_all(vec!(8,6,7,5,3,0,9)));
         assert_eq!( 7, my_tree.get_size() );
         assert!( my_tree.contains(&7) );
         assert!( !my_tree.contains(&1) );
@@ -436,13 +1913,13 @@ mod tests {
         // an empty tree
         let mut my_tree = Jblst::new();
         assert_eq!( 0, my_tree.get_size() );
-        assert_eq!( Err(TreeError::ValueNotFound), my_tree.drop_value(1) );
+        assert_eq!( Err(TreeError::ValueNotFound { value: 1 }), my_tree.drop_value(1) );
 
         // a tree with only a root node
         let mut my_tree = Jblst::new();
         let _ = my_tree.add(1);
         assert_eq!( 1, my_tree.get_size() );
-        assert_eq!( Err(TreeError::ValueNotFound), my_tree.drop_value(4) );
+        assert_eq!( Err(TreeError::ValueNotFound { value: 4 }), my_tree.drop_value(4) );
         assert_eq!( Ok(()), my_tree.drop_value(1) );
         assert_eq!( 0, my_tree.get_size() );
 
@@ -451,7 +1928,7 @@ mod tests {
         let _ = my_tree.add_all(['A','B','C']);
         assert_eq!( Some('A'), my_tree.get_root_value() ); // root is 1
         assert_eq!( 3, my_tree.get_size() );
-        assert_eq!( Err(TreeError::ValueNotFound), my_tree.drop_value('Z') );
+        assert_eq!( Err(TreeError::ValueNotFound { value: 'Z' }), my_tree.drop_value('Z') );
         assert_eq!( Ok(()), my_tree.drop_value('A') );
         assert_eq!( vec!('B','C'), my_tree.as_vec_l_to_r() );
         assert_eq!( 2, my_tree.get_size() );
@@ -461,7 +1938,7 @@ mod tests {
         let _ = my_tree.add_all([3,1,2]);
         assert_eq!( Some(3), my_tree.get_root_value() ); // root is 3
         assert_eq!( 3, my_tree.get_size() );
-        assert_eq!( Err(TreeError::ValueNotFound), my_tree.drop_value(4) );
+        assert_eq!( Err(TreeError::ValueNotFound { value: 4 }), my_tree.drop_value(4) );
         assert_eq!( Ok(()), my_tree.drop_value(3) );
         assert_eq!( vec!(1,2), my_tree.as_vec_l_to_r() );
         assert_eq!( 2, my_tree.get_size() );
@@ -471,7 +1948,7 @@ mod tests {
         let _ = my_tree.add_all([2,1,3]);
         assert_eq!( Some(2), my_tree.get_root_value() ); // root is 2
         assert_eq!( 3, my_tree.get_size() );
-        assert_eq!( Err(TreeError::ValueNotFound), my_tree.drop_value(4) );
+        assert_eq!( Err(TreeError::ValueNotFound { value: 4 }), my_tree.drop_value(4) );
         assert_eq!( Ok(()), my_tree.drop_value(2) );
         assert_eq!( vec!(1,3), my_tree.as_vec_l_to_r() );
         assert_eq!( 2, my_tree.get_size() );
@@ -481,7 +1958,7 @@ mod tests {
         let _ = my_tree.add_all([2,1,5,3,7]);
         assert_eq!( Some(2), my_tree.get_root_value() ); // root is 2
         assert_eq!( 5, my_tree.get_size() );
-        assert_eq!( Err(TreeError::ValueNotFound), my_tree.drop_value(4) );
+        assert_eq!( Err(TreeError::ValueNotFound { value: 4 }), my_tree.drop_value(4) );
         assert_eq!( Ok(()), my_tree.drop_value(2) );
         assert_eq!( vec!(1,3,5,7), my_tree.as_vec_l_to_r() );
         assert_eq!( 4, my_tree.get_size() );
@@ -491,7 +1968,7 @@ mod tests {
         let _ = my_tree.add_all([5,3,8,1,2,7,9]);
         assert_eq!( Some(5), my_tree.get_root_value() ); // root is 5
         assert_eq!( 7, my_tree.get_size() );
-        assert_eq!( Err(TreeError::ValueNotFound), my_tree.drop_value(4) );
+        assert_eq!( Err(TreeError::ValueNotFound { value: 4 }), my_tree.drop_value(4) );
         assert_eq!( Ok(()), my_tree.drop_value(5) );
         assert_eq!( Some(7), my_tree.get_root_value() ); // root is now 7
         assert_eq!( vec!(1,2,3,7,8,9), my_tree.as_vec_l_to_r() );
@@ -509,4 +1986,370 @@ mod tests {
         assert_eq!( Some(9), my_tree.greatest_value() );
     }
 
+    #[test]
+    fn test_check_invariants() {
+        let mut my_tree = Jblst::new();
+        assert_eq!( Ok(()), my_tree.check_invariants() );
+        // a pseudo-random mix of adds and drops, checking the invariants after every change
+        let mut state: u32 = 12345;
+        for _ in 0..2000 {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let value = state % 64;
+            if state.is_multiple_of(3) {
+                let _ = my_tree.drop_value(value);
+            } else {
+                let _ = my_tree.add(value);
+            }
+            assert_eq!( Ok(()), my_tree.check_invariants() );
+        }
+
+        // break the size on purpose
+        let mut my_tree = Jblst::from_collection([2,2,1,3]);
+        my_tree.size = 3;
+        assert_eq!( Err(TreeError::InvariantViolation("size is 3 but the tree holds 4 values".to_string())), my_tree.check_invariants() );
+    }
+
+    #[test]
+    fn test_stats() {
+        let my_tree = Jblst::from_collection([1,2,2,3,3,3]);
+        // duplicates share a node, and ascending inserts make a chain
+        let stats = my_tree.stats();
+        assert_eq!( 3, stats.node_count );
+        assert_eq!( 3, stats.height );
+        assert_eq!( 1, stats.leaf_count );
+        assert_eq!( 2, stats.internal_count );
+        assert_eq!( 2, stats.min_leaf_depth );
+        assert_eq!( 2, stats.max_leaf_depth );
+        assert_eq!( 2.0, stats.avg_leaf_depth );
+        assert_eq!( None, stats.rotations );
+    }
+
+    #[test]
+    fn test_to_ascii() {
+        // the `|` lines connect a left child back up to a parent that is itself a right child, and vice versa
+        let my_tree = Jblst::from_collection([50,20,80,30,25,70,75,75]);
+        let expected = concat!(
+            "/-- 80\n",
+            "|   |   /-- 75 x2\n",
+            "|   \\-- 70\n",
+            "50\n",
+            "|   /-- 30\n",
+            "|   |   \\-- 25\n",
+            "\\-- 20\n",
+        );
+        assert_eq!( expected, my_tree.to_ascii() );
+    }
+
+    #[test]
+    fn test_to_and_from_bytes() {
+        let my_tree = Jblst::from_collection([String::from("b"), String::from("a"), String::from("b")]);
+        let copy = Jblst::<String>::from_bytes(&my_tree.to_bytes()).unwrap();
+        assert_eq!( my_tree.to_ascii(), copy.to_ascii() );
+        assert_eq!( 3, copy.get_size() );
+
+        // counts that don't add up to the size are rejected
+        let mut bytes = my_tree.to_bytes();
+        bytes[12] = 5; // the root's count follows the header, the size, and its flags byte
+        assert!( matches!( Jblst::<String>::from_bytes(&bytes), Err(TreeError::DeserializationCorrupt { .. }) ) );
+        // so are truncated data and trailing bytes
+        let bytes = my_tree.to_bytes();
+        assert!( matches!( Jblst::<String>::from_bytes(&bytes[..bytes.len() - 1]), Err(TreeError::DeserializationCorrupt { .. }) ) );
+        let mut bytes = my_tree.to_bytes();
+        bytes.push(0);
+        assert_eq!( Err(TreeError::DeserializationCorrupt { offset: bytes.len() - 1, reason: String::from("1 unexpected bytes after the tree") }),
+            Jblst::<String>::from_bytes(&bytes).map(|tree| tree.get_size()) );
+    }
+
+    #[test]
+    fn test_digest() {
+        use std::hash::DefaultHasher;
+        let my_tree = Jblst::from_collection([2,1,2,3]);
+        let same_values = Jblst::from_collection([1,2,3,2]); // a different shape
+        assert_eq!( my_tree.digest::<DefaultHasher>(), same_values.digest::<DefaultHasher>() );
+        assert_ne!( my_tree.digest::<DefaultHasher>(), Jblst::from_collection([1,2,3]).digest::<DefaultHasher>() );
+    }
+
+    #[test]
+    fn test_diff() {
+        let before = Jblst::from_collection([1,2,2,3,3,3]);
+        let after = Jblst::from_collection([3,2,4,3,1]);
+        let diff = before.diff(&after);
+        assert_eq!( vec!(2,3), diff.only_in_self );
+        assert_eq!( vec!(4), diff.only_in_other );
+        assert!( after.diff(&Jblst::from_collection([1,2,3,3,4])).is_empty() );
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut replica = Jblst::from_collection([1,2,2,3]);
+        let target = Jblst::from_collection([2,3,3,4]);
+        assert_eq!( Ok(()), replica.apply(&replica.diff(&target)) );
+        assert_eq!( vec!(2,3,3,4), replica.as_vec() );
+        // removing more copies of a value than the tree holds changes nothing
+        let stale = TreeDiff { only_in_self: vec!(3,3,3), only_in_other: vec!(5) };
+        assert_eq!( Err(TreeError::ValueNotFound { value: 3 }), replica.apply(&stale) );
+        assert_eq!( vec!(2,3,3,4), replica.as_vec() );
+        assert_eq!( Ok(()), replica.check_invariants() );
+    }
+
+    #[test]
+    #[cfg(feature = "petgraph")]
+    fn test_to_petgraph() {
+        let graph = Jblst::from_collection([2,2,1]).to_petgraph();
+        assert_eq!( 2, graph.node_count() );
+        let edge = graph.find_edge(NodeIndex::new(0), NodeIndex::new(1)).unwrap();
+        assert_eq!( Branch::Left, graph[edge] );
+        assert_eq!( 1, graph[NodeIndex::new(1)] );
+    }
+
+    #[test]
+    fn test_visits() {
+        let my_tree = Jblst::from_collection([2,1,3,3]);
+        let mut values = Vec::new();
+        my_tree.visit_post_order(|value| values.push(*value));
+        assert_eq!( vec!(1,3,3,2), values );
+        // stopping partway through a node's duplicates
+        let mut seen = 0;
+        assert_eq!( ControlFlow::Break(3), my_tree.try_visit_in_order(|value| {
+            seen += 1;
+            if seen == 3 { ControlFlow::Break(*value) } else { ControlFlow::Continue(()) }
+        }) );
+    }
+
+    #[test]
+    fn test_leaves() {
+        let my_tree = Jblst::from_collection([2,2,1,3]);
+        assert_eq!( vec!(&1,&3), my_tree.leaves().collect::<Vec<_>>() );
+        let chain = Jblst::from_collection([1,2,3,3]);
+        assert_eq!( vec!(&3,&3), chain.leaves().collect::<Vec<_>>() );
+    }
+
+    #[test]
+    fn test_iter_with_depth() {
+        let my_tree = Jblst::from_collection([1,2,2,3]);
+        assert_eq!( vec!((&1,0), (&2,1), (&2,1), (&3,2)), my_tree.iter_with_depth().collect::<Vec<_>>() );
+    }
+
+    #[test]
+    fn test_cursor() {
+        let my_tree = Jblst::from_collection([3,1,2,2,5,4,4,4]);
+        let mut cursor = my_tree.cursor_at(&4);
+        assert_eq!( vec!(&4,&4,&5), cursor.by_ref().collect::<Vec<_>>() );
+        assert_eq!( None, cursor.peek() );
+        let mut backwards = Vec::new();
+        while let Some(value) = cursor.prev() {
+            backwards.push(*value);
+        }
+        assert_eq!( vec!(5,4,4,4,3,2,2,1), backwards );
+        assert_eq!( Some(&2), my_tree.cursor_front().nth(1) );
+    }
+
+    #[test]
+    fn test_cursor_mut() {
+        let mut my_tree = Jblst::from_collection([1,3,3,5]);
+        let mut cursor = my_tree.cursor_mut_at(&3);
+        assert_eq!( Ok(()), cursor.insert_before(2) );
+        assert_eq!( Ok(()), cursor.insert_before(3) ); // a copy of 3 before the cursor's
+        assert_eq!( Some(&3), cursor.move_prev() );
+        assert_eq!( Some(&2), cursor.move_prev() );
+        assert_eq!( Some(&3), cursor.move_next() );
+        assert_eq!( Some(&3), cursor.move_next() );
+        assert_eq!( Some(&3), cursor.move_next() );
+        assert_eq!( Err(TreeError::OutOfOrder { value: 2 }), cursor.insert_after(2) );
+        assert_eq!( Err(TreeError::OutOfOrder { value: 6 }), cursor.insert_after(6) );
+        assert_eq!( Ok(()), cursor.insert_after(4) );
+        assert_eq!( Some(3), cursor.remove_current() ); // the last copy, so the cursor moves on
+        assert_eq!( Some(&4), cursor.peek() );
+        assert_eq!( Some(&5), cursor.move_next() );
+        assert_eq!( None, cursor.move_next() );
+        assert_eq!( Err(TreeError::OutOfOrder { value: 4 }), cursor.insert_before(4) );
+        assert_eq!( Ok(()), cursor.insert_before(9) ); // at the end
+        assert_eq!( Ok(()), cursor.insert_after(0) ); // at the start
+        assert_eq!( vec!(0,1,2,3,3,4,5,9), my_tree.as_vec() );
+        assert_eq!( Ok(()), my_tree.check_invariants() );
+    }
+
+    #[test]
+    fn test_drain() {
+        let mut my_tree = Jblst::from_collection(["b", "a", "b", "c", "b"]);
+        let mut drain = my_tree.drain();
+        assert_eq!( Some("a"), drain.next() );
+        assert_eq!( vec!("b","b","b","c"), drain.collect::<Vec<_>>() );
+        assert_eq!( 0, my_tree.get_size() );
+        assert_eq!( None, my_tree.drain().next() );
+    }
+
+    #[test]
+    fn test_merge_iter() {
+        let tree_a = Jblst::from_collection([1,2,2,4]);
+        let tree_b = Jblst::from_collection([2,2,2,3]);
+        assert_eq!( vec!(&1,&2,&2,&2,&3,&4), tree_a.merge_iter(&tree_b, Duplicates::Union).collect::<Vec<_>>() );
+        assert_eq!( 8, tree_a.merge_iter(&tree_b, Duplicates::KeepBoth).count() );
+    }
+
+    #[test]
+    fn test_iter_unique() {
+        let mut my_tree = Jblst::from_collection(["b","a","b","c","a","b"]);
+        assert_eq!( vec!(&"a",&"b",&"c"), my_tree.iter_unique().collect::<Vec<_>>() );
+        let _ = my_tree.drop_value("c");
+        assert_eq!( 2, my_tree.iter_unique().count() );
+        assert_eq!( None, Jblst::<u32>::new().iter_unique().next() );
+    }
+
+    #[test]
+    fn test_iter_counts() {
+        let mut my_tree = Jblst::from_collection([3,1,3,2,3,1]);
+        assert_eq!( vec!((&1,2), (&2,1), (&3,3)), my_tree.iter_counts().collect::<Vec<_>>() );
+        let _ = my_tree.drop_value(3);
+        let _ = my_tree.drop_value(2);
+        assert_eq!( vec!((&1,2), (&3,2)), my_tree.iter_counts().collect::<Vec<_>>() );
+        assert_eq!( my_tree.get_size() as usize, my_tree.iter_counts().map(|(_, count)| count).sum::<usize>() );
+    }
+
+    #[test]
+    fn first_n_and_last_n() {
+        let my_tree = Jblst::from_collection([4,1,4,4,9,1]);
+        assert_eq!( vec!(1,1,4), my_tree.first_n(3) );
+        assert_eq!( vec!(4,4,9), my_tree.last_n(3) );
+        assert_eq!( vec!(1,1,4,4,4,9), my_tree.last_n(7) );
+    }
+
+    #[test]
+    fn into_sorted_vec_keeps_duplicates() {
+        let my_tree = Jblst::from_collection([3,1,3,2,3]);
+        assert_eq!( vec!(1,2,3,3,3), my_tree.into_sorted_vec() );
+    }
+
+    #[test]
+    fn compare_distinct_values_and_multisets() {
+        let values: Vec<u32> = (0..500).map(|n| n * 7 % 100).collect();
+        let with_repeats = Jblst::from_collection(values.clone());
+        let set = Javlt::from_collection(values.clone());
+        assert!( with_repeats.distinct_eq(&set) );
+        assert!( set.distinct_eq(&with_repeats) );
+        assert!( !with_repeats.distinct_eq(&Javlt::from_collection(1..100)) );
+        assert!( !with_repeats.distinct_eq(&Javlt::new()) );
+        let shuffled = Jblst::from_collection(values.iter().rev().cloned());
+        assert!( with_repeats.multiset_eq(&shuffled) );
+        let mut one_more = Jblst::from_collection(values);
+        one_more.add(50).unwrap();
+        assert!( !with_repeats.multiset_eq(&one_more) );
+        one_more.drop_value(50).unwrap();
+        one_more.drop_value(51).unwrap();
+        one_more.add(52).unwrap();
+        assert!( !with_repeats.multiset_eq(&one_more) );
+        assert!( one_more.distinct_eq(&set) );
+    }
+
+    #[test]
+    fn frequency_maps_match_the_counts() {
+        let my_tree = Jblst::from_collection((0..1000u32).map(|n| n * n % 37));
+        let hashed = my_tree.to_frequency_map();
+        let ordered = my_tree.to_frequency_btree_map();
+        assert_eq!( my_tree.iter_unique().count(), hashed.len() );
+        assert_eq!( 1000, hashed.values().sum::<usize>() );
+        assert!( ordered.iter().all(|(value, count)| hashed[value] == *count) );
+        assert!( ordered.keys().eq(my_tree.iter_unique()) );
+        assert!( Jblst::<u32>::new().to_frequency_map().is_empty() );
+    }
+
+    #[test]
+    fn drop_n_counts_what_it_removed() {
+        let mut my_tree = Jblst::from_collection([5,3,8,3,3,8,1,4,9,5,5,5]);
+        assert_eq!( Ok(2), my_tree.drop_n(&5, 2) );
+        assert_eq!( 2, my_tree.count(&5) );
+        assert_eq!( Ok(0), my_tree.drop_n(&5, 0) );
+        assert_eq!( Ok(2), my_tree.drop_n(&5, 10) ); // the root goes, with both its remaining duplicates
+        assert_eq!( Ok(3), my_tree.drop_n(&3, 3) );
+        assert_eq!( Err(TreeError::ValueNotFound { value: 7 }), my_tree.drop_n(&7, 1) );
+        assert_eq!( vec!(1,4,8,8,9), my_tree.as_vec() );
+        assert_eq!( 5, my_tree.get_size() );
+        assert_eq!( Ok(()), my_tree.check_invariants() );
+    }
+
+    #[test]
+    fn counts_set_in_bulk() {
+        let mut my_tree = Jblst::new();
+        assert_eq!( Ok(()), my_tree.insert_n('m', 3) );
+        assert_eq!( Ok(()), my_tree.insert_n('c', 2) );
+        assert_eq!( Ok(()), my_tree.insert_n('x', 0) );
+        assert_eq!( Ok(()), my_tree.insert_n('m', 1) );
+        assert_eq!( (4, 0, 6), (my_tree.count(&'m'), my_tree.count(&'x'), my_tree.get_size()) );
+        assert_eq!( Ok(4), my_tree.set_count(&'m', 1) );
+        assert_eq!( Ok(0), my_tree.set_count(&'t', 5) );
+        assert_eq!( Ok(2), my_tree.set_count(&'c', 0) );
+        assert_eq!( Ok(0), my_tree.set_count(&'q', 0) );
+        assert_eq!( vec!('m','t','t','t','t','t'), my_tree.as_vec() );
+        assert_eq!( Ok(()), my_tree.check_invariants() );
+        // a count the tree's size can't hold is refused, leaving the tree as it was
+        assert_eq!( Err(TreeError::CapacityExceeded { value: 'z' }), my_tree.insert_n('z', u32::MAX as usize) );
+        assert_eq!( Err(TreeError::CapacityExceeded { value: 't' }), my_tree.set_count(&'t', usize::MAX) );
+        assert_eq!( 6, my_tree.get_size() );
+        assert_eq!( Ok(()), my_tree.check_invariants() );
+    }
+
+    #[test]
+    fn depth_limit_stops_deep_values() {
+        let mut my_tree = Jblst::with_depth_limit(2);
+        let _ = my_tree.add_all([1,2,2]);
+        assert_eq!( Err(TreeError::DepthLimitExceeded { value: 3, limit: 2 }), my_tree.insert_n(3, 4) );
+        assert_eq!( Ok(()), my_tree.add(2) ); // duplicates are counted, not stored deeper
+        assert_eq!( vec!(1,2,2,2), my_tree.as_vec() );
+        my_tree.set_depth_limit(3);
+        assert_eq!( Ok(()), my_tree.add(3) );
+        assert!( matches!( Jblst::<i32>::from_bytes(&my_tree.to_bytes()), Ok(tree) if tree.get_depth_limit() == DEFAULT_DEPTH_LIMIT ) );
+    }
+
+    #[test]
+    fn values_need_not_be_clone() {
+        #[derive(PartialEq, PartialOrd, Debug)]
+        struct Handle(u32);
+        let mut my_tree = Jblst::new();
+        for n in [5, 2, 8, 2, 1, 5, 3] {
+            assert_eq!( Ok(()), my_tree.add(Handle(n)) );
+        }
+        assert_eq!( Ok(()), my_tree.insert_n(Handle(8), 2) );
+        assert_eq!( (2, 3), (my_tree.count(&Handle(5)), my_tree.count(&Handle(8))) );
+        assert_eq!( Ok(()), my_tree.drop_value(Handle(5)) );
+        assert_eq!( Ok(()), my_tree.drop_value(Handle(1)) );
+        assert_eq!( Ok(()), my_tree.check_invariants() );
+        assert_eq!( 7, my_tree.get_size() );
+        assert_eq!( vec!(2,2,3,5,8,8,8), my_tree.iter().map(|handle| handle.0).collect::<Vec<_>>() );
+    }
+
+    #[test]
+    fn iter_repeats_duplicates() {
+        let my_tree = Jblst::from_collection([2,5,2,1,5,2]);
+        assert_eq!( my_tree.as_vec().iter().collect::<Vec<_>>(), my_tree.iter().collect::<Vec<_>>() );
+        assert_eq!( 6, my_tree.iter().count() );
+        assert_eq!( Some(&5), (&my_tree).into_iter().next_back() );
+    }
+
+    #[test]
+    fn into_iter_moves_every_copy_out() {
+        let my_tree = Jblst::from_collection([3,1,3,2,3]);
+        assert_eq!( vec!(1,2,3,3,3), my_tree.into_iter().collect::<Vec<_>>() );
+    }
+
+    #[test]
+    fn iterators_meet_in_the_middle() {
+        let my_tree = Jblst::from_collection([4,1,4,2,4,3,1]);
+        let mut values = my_tree.iter();
+        let mut both_ends: Vec<&i32> = Vec::new();
+        loop {
+            match (values.next(), values.next_back()) {
+                (None, None) => break,
+                (front, back) => both_ends.extend(front.into_iter().chain(back)),
+            }
+        }
+        assert_eq!( vec!(&1,&4,&1,&4,&2,&4,&3), both_ends );
+        let mut drained = Jblst::from_collection(my_tree.iter().cloned());
+        let mut values = drained.drain();
+        assert_eq!( (Some(4), Some(1), Some(4), Some(1)), (values.next_back(), values.next(), values.next_back(), values.next()) );
+        assert_eq!( vec!(2,3,4), values.collect::<Vec<_>>() );
+        assert_eq!( vec!(4,4,4,3,2,1,1), my_tree.into_iter().rev().collect::<Vec<_>>() );
+    }
+
 }
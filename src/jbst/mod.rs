@@ -1,6 +1,18 @@
-use std::fmt;
+use std::{cmp::min, collections::VecDeque, fmt, fs, hash::{Hash, Hasher}, iter::FusedIterator, ops::{ControlFlow, RangeBounds}, path::Path};
 
-use crate::errors::TreeError;
+use crate::bounds::{queue_within, SearchNode};
+use crate::errors::{OccupiedError, TreeError};
+use crate::reports::{AddReport, TreeDiff, TreeStats};
+use crate::frozen::FrozenSet;
+#[cfg(feature = "petgraph")]
+use petgraph::graph::{DiGraph, NodeIndex};
+#[cfg(feature = "petgraph")]
+use crate::graph::Branch;
+use crate::merge::{Duplicates, MergeIter};
+use crate::visit::Traversal;
+use crate::codec::{self, Codec, Reader, HAS_LEFT, HAS_RIGHT, DELETED};
+#[cfg(feature = "metrics")]
+use crate::metrics;
 
 
 
@@ -18,56 +30,177 @@ use crate::errors::TreeError;
 ///     let _ = my_tree.add(3);
 ///     assert_eq!( 3, my_tree.get_size() );
 ///     assert_eq!( vec!(1,2,3), my_tree.as_vec() );
-///     assert_eq!( Err(TreeError::ValueAlreadyStored), my_tree.add(1) ); // unique values only!
+///     assert_eq!( Err(TreeError::ValueAlreadyStored { value: 1 }), my_tree.add(1) ); // unique values only!
 /// 
 ///     let mut tree_b = Jbst::from_collection([1,1,2,3,5]); // duplicate values are ignored but no error is thrown
 ///     assert_eq!( vec!(1,2,3,5), tree_b.as_vec() ); // the array was effectively converted into a set
 ///     assert!( tree_b.contains(&5) ); // fast test for set membership
 /// 
-/// Can hold any data type that supports PartialEq + PartialOrd + Clone.
-pub struct Jbst<T: PartialEq + PartialOrd + Clone> {
+/// Can hold any data type that supports PartialEq + PartialOrd.  The values don't have to be `Clone`: removing
+/// a node moves its successor's value into its place rather than copying it, so a tree can hold values that own
+/// resources, such as files.  Only the methods that hand back copies of values (like `as_vec` and `least_value`)
+/// need `Clone`.
+/// 
+/// For delete-heavy workloads, a tree created with `Jbst::with_lazy_deletion` only marks
+/// dropped values as deleted ("tombstones") instead of restructuring the tree.  Tombstoned
+/// nodes are removed for real once there are more of them than the given limit, or when
+/// `purge()` is called.
+///
+///     use jtree::Jbst;
+/// 
+///     let mut lazy_tree = Jbst::with_lazy_deletion(2);
+///     let _ = lazy_tree.add_all([4,2,6,1,3]);
+///     let _ = lazy_tree.drop_value(2); // cheap: 2 is only marked as deleted
+///     assert!( !lazy_tree.contains(&2) );
+///     assert_eq!( 1, lazy_tree.get_tombstone_count() );
+///     lazy_tree.purge(); // force the tombstoned nodes to be removed
+///     assert_eq!( 0, lazy_tree.get_tombstone_count() );
+///     assert_eq!( vec!(1,3,4,6), lazy_tree.as_vec() );
+///
+/// Since the tree isn't balanced, values added in order make it as deep as it is big, and its recursive
+/// operations could overflow the stack.  So no value is added deeper than the tree's depth limit
+/// (`DEFAULT_DEPTH_LIMIT` unless it's changed); `add` returns a TreeError::DepthLimitExceeded instead.
+///
+///     use jtree::Jbst;
+///     use jtree::errors::TreeError;
+///
+///     let mut sorted = Jbst::with_depth_limit(3);
+///     let _ = sorted.add_all([1,2,3]);
+///     assert_eq!( Err(TreeError::DepthLimitExceeded { value: 4, limit: 3 }), sorted.add(4) );
+///     sorted.set_depth_limit(4);
+///     assert_eq!( Ok(()), sorted.add(4) );
+pub struct Jbst<T: PartialEq + PartialOrd> {
     root: Option<Box<Node<T>>>,
     size: u32,
+    tombstones: u32,
+    tombstone_limit: Option<u32>, // None means values are deleted eagerly
+    depth_limit: usize, // the most nodes allowed on a path down from the root
 }
 
-impl <T: PartialEq + PartialOrd + Clone> Jbst<T> {
+/// The depth limit of a new `Jbst`, `Jblst` or `StableJblst` (the most nodes a path down from the root may go
+/// through).  The trees' operations go down them recursively, and this leaves plenty of room on a 2 MiB stack
+/// (the size of a spawned thread's, and of a test's) for small values, even in a debug build.  A tree on a
+/// bigger stack can be given a higher limit with `set_depth_limit`.
+pub const DEFAULT_DEPTH_LIMIT: usize = 512;
+
+/// How deep a tree `from_bytes` will decode.  The bytes could come from anywhere, and decoding (like the tree's
+/// other operations) goes down the tree recursively, so a deeper tree is taken to be corrupt rather than risk
+/// the stack.  Trees saved deeper than this can be loaded with `from_bytes_with_depth_limit`.
+pub const DECODE_DEPTH_LIMIT: usize = DEFAULT_DEPTH_LIMIT;
+
+impl <T: PartialEq + PartialOrd> Jbst<T> {
 
     /// Create a new tree with no data
     pub fn new() -> Self {
         Self {
             root: None,
             size: 0,
+            tombstones: 0,
+            tombstone_limit: None,
+            depth_limit: DEFAULT_DEPTH_LIMIT,
+        }
+    }
+
+    /// Create a new tree with no data, in lazy-deletion mode.  Dropped values are marked as deleted
+    /// rather than removed, and the tree is compacted automatically once the number of these 
+    /// tombstones exceeds `tombstone_limit`.
+    pub fn with_lazy_deletion(tombstone_limit: u32) -> Self {
+        Self {
+            root: None,
+            size: 0,
+            tombstones: 0,
+            tombstone_limit: Some(tombstone_limit),
+            depth_limit: DEFAULT_DEPTH_LIMIT,
         }
     }
 
+    /// Create a new tree with no data, whose values may go no deeper than `depth_limit` nodes (at least 1)
+    /// down from the root.
+    pub fn with_depth_limit(depth_limit: usize) -> Self {
+        let mut new_tree = Self::new();
+        new_tree.set_depth_limit(depth_limit);
+        new_tree
+    }
+
     /// Create a new tree from a collection (vector, array, or whatever), skipping duplicates, effectively 
-    /// turning a list into an ordered set of unique values.
+    /// turning a list into an ordered set of unique values.  Panics if the values would make the tree
+    /// deeper than `DEFAULT_DEPTH_LIMIT`, as more values than that in sorted order do; such a collection
+    /// can be added to a tree made with `with_depth_limit`.
     pub fn from_collection<U: IntoIterator<Item = T>>(collection: U) -> Self {
         let mut new_tree = Self::new();
-        let _ = new_tree.add_all_skipping_duplicates(collection);
+        if new_tree.add_all_skipping_duplicates(collection).is_err() {
+            panic!("Jbst::from_collection: the values would make the tree deeper than its depth limit ({DEFAULT_DEPTH_LIMIT})");
+        }
         new_tree
     }
 
+    /// Create a new tree from a collection, like `from_collection`, but for input that shouldn't hold duplicates:
+    /// the first duplicate is returned in a TreeError::DuplicateAt (with its index in the collection) instead.
+    ///
+    ///     use jtree::Jbst;
+    ///     use jtree::errors::TreeError;
+    ///
+    ///     assert_eq!( vec!(1,2,3), Jbst::try_from_collection([3,1,2]).unwrap().as_vec() );
+    ///     assert_eq!( Err(TreeError::DuplicateAt { index: 3, value: 1 }), Jbst::try_from_collection([3,1,2,1]).map(|tree| tree.as_vec()) );
+    pub fn try_from_collection<U: IntoIterator<Item = T>>(collection: U) -> Result<Self,TreeError<T>> {
+        let mut new_tree = Self::new();
+        new_tree.add_all_strict(collection)?;
+        Ok(new_tree)
+    }
+
     /// Insert a value
-    pub fn add(&mut self, value: T) -> Result<(),TreeError> {
+    pub fn add(&mut self, value: T) -> Result<(),TreeError<T>> {
+        if self.size == u32::MAX {
+            return Err(TreeError::CapacityExceeded { value });
+        }
+        #[cfg(feature = "metrics")]
+        metrics::start_operation();
         match &mut self.root {
             None => self.root = Some(Box::new(Node::new(value))),
-            Some(branch) => branch.add(value)?, // TODO: handle errors if any are possible
+            Some(branch) => {
+                let revived = branch.add(value, 1, self.depth_limit)?;
+                if revived {
+                    self.tombstones -= 1;
+                }
+            },
         }
         self.size += 1;
+        #[cfg(feature = "paranoid")]
+        self.paranoid_check("add");
         Ok(())
     }
 
     /// Alias for add_all_skipping_duplicates. Adds all members of a collection (vector, array, or whatever) to the tree.
-    pub fn add_all<U: IntoIterator<Item = T>>(&mut self, collection: U) -> Result<(),TreeError> {
+    pub fn add_all<U: IntoIterator<Item = T>>(&mut self, collection: U) -> Result<AddReport,TreeError<T>> {
         self.add_all_skipping_duplicates(collection)
     }
 
     /// Adds all members of a collection (vector, array, or whatever) to the tree,
-    /// skipping over any that would be duplicates, so no error will stop the batch.
-    pub fn add_all_skipping_duplicates<U: IntoIterator<Item = T>>(&mut self, collection: U) -> Result<(),TreeError> {
+    /// skipping over any that would be duplicates, so no duplicate will stop the batch.
+    /// The returned report says how many values were inserted and how many were skipped.
+    /// Any other error (such as a TreeError::DepthLimitExceeded) stops the batch and is returned.
+    pub fn add_all_skipping_duplicates<U: IntoIterator<Item = T>>(&mut self, collection: U) -> Result<AddReport,TreeError<T>> {
+        let mut report = AddReport::default();
         for elem in collection.into_iter() {
-            let _ = self.add(elem);
+            match self.add(elem) {
+                Ok(()) => report.inserted += 1,
+                Err(TreeError::ValueAlreadyStored { .. }) => report.skipped += 1,
+                Err(other) => return Err(other),
+            }
+        }
+        Ok(report)
+    }
+
+    /// Adds all members of a collection (vector, array, or whatever) to the tree, stopping at the first
+    /// duplicate.  Unlike `try_add_all`, the values added before it stay in the tree; the TreeError::DuplicateAt
+    /// error holds the index and value of the offending element, and the rest of the collection isn't added.
+    pub fn add_all_strict<U: IntoIterator<Item = T>>(&mut self, collection: U) -> Result<(),TreeError<T>> {
+        for (index, elem) in collection.into_iter().enumerate() {
+            match self.add(elem) {
+                Ok(()) => {},
+                Err(TreeError::ValueAlreadyStored { value }) => return Err(TreeError::DuplicateAt { index, value }),
+                Err(other) => return Err(other),
+            }
         }
         Ok(())
     }
@@ -77,6 +210,471 @@ impl <T: PartialEq + PartialOrd + Clone> Jbst<T> {
         self.size
     }
 
+    /// Get the number of nodes marked as deleted but not yet removed; always 0 unless the tree
+    /// was created with `with_lazy_deletion`.
+    pub fn get_tombstone_count(&self) -> u32 {
+        self.tombstones
+    }
+
+    /// Get the most nodes a path down from the root may go through: values that would go deeper aren't added.
+    pub fn get_depth_limit(&self) -> usize {
+        self.depth_limit
+    }
+
+    /// Change the tree's depth limit (to at least 1).  Values already deeper than a lowered limit stay where
+    /// they are; only values added from now on are held to it.
+    pub fn set_depth_limit(&mut self, depth_limit: usize) {
+        self.depth_limit = depth_limit.max(1);
+    }
+
+    /// Returns true if the value is currently a member of the tree
+    pub fn contains(&self, value: &T) -> bool {
+        #[cfg(feature = "metrics")]
+        metrics::start_operation();
+        return match &self.root {
+            None => false,
+            Some(branch) => branch.contains(value), 
+        };
+    }
+
+    /// Returns the stored value equal to `value`, if there is one.  This is the tree's own copy, which
+    /// matters for values that compare equal without being identical.
+    pub fn get(&self, value: &T) -> Option<&T> {
+        return match &self.root {
+            None => None,
+            Some(branch) => branch.get(value),
+        };
+    }
+
+    /// Returns the stored value equal to `value`, adding `value` first if there's none, with a single search
+    /// of the tree (rather than a `contains` followed by an `add`).  Handy for interning values.  Panics if
+    /// the value is new and the tree already holds as many values as it can count (`u32::MAX`), or the value
+    /// would go deeper than the tree's depth limit.
+    ///
+    ///     use jtree::Jbst;
+    ///
+    ///     let mut names = Jbst::new();
+    ///     assert_eq!( "ada", names.get_or_insert(String::from("ada")) );
+    ///     assert_eq!( "ada", names.get_or_insert(String::from("ada")) );
+    ///     assert_eq!( 1, names.get_size() );
+    pub fn get_or_insert(&mut self, value: T) -> &T {
+        let (slot, depth) = Node::slot_for(&mut self.root, &value, 1);
+        Self::check_depth(slot, depth, self.depth_limit);
+        Self::fill_slot(slot, &mut self.size, &mut self.tombstones, || value)
+    }
+
+    /// Like `get_or_insert`, but looks for a value equal to `probe`, and only calls `make` to create the value
+    /// to add if there's none.  Panics if `make` returns a value that doesn't equal the probe.
+    pub fn get_or_insert_with<F: FnOnce() -> T>(&mut self, probe: &T, make: F) -> &T {
+        let (slot, depth) = Node::slot_for(&mut self.root, probe, 1);
+        Self::check_depth(slot, depth, self.depth_limit);
+        Self::fill_slot(slot, &mut self.size, &mut self.tombstones, || {
+            let value = make();
+            assert!( value == *probe, "get_or_insert_with: the value made must equal the probe" );
+            value
+        })
+    }
+
+    /// Insert a value and return the tree's copy of it, unless an equal value is already stored, in which case
+    /// an OccupiedError holds both the stored value and the one given back.  It takes a single search of the
+    /// tree, like `get_or_insert`, and panics in the same cases: if the value is new and the tree already holds
+    /// as many values as it can count (`u32::MAX`), or would go deeper than its depth limit.
+    ///
+    ///     use jtree::Jbst;
+    ///
+    ///     let mut names = Jbst::new();
+    ///     assert_eq!( "ada", names.try_insert(String::from("ada")).unwrap() );
+    ///     let occupied = names.try_insert(String::from("ada")).unwrap_err();
+    ///     assert_eq!( ("ada", String::from("ada")), (occupied.existing.as_str(), occupied.value) );
+    pub fn try_insert(&mut self, value: T) -> Result<&T,OccupiedError<'_, T>> {
+        let (slot, depth) = Node::slot_for(&mut self.root, &value, 1);
+        Self::check_depth(slot, depth, self.depth_limit);
+        if slot.as_ref().is_some_and(|node| !node.deleted) {
+            return Err(OccupiedError { existing: &slot.as_ref().unwrap().value, value });
+        }
+        Ok(Self::fill_slot(slot, &mut self.size, &mut self.tombstones, || value))
+    }
+
+    /// Insert a value, replacing any equal value already stored, like `BTreeMap::insert` does for keys.
+    /// Returns the value that was replaced, or None if the value is new to the tree (or revives a tombstone).
+    /// Unlike `add`, this doesn't return errors; it panics only if the value is new and the tree already holds
+    /// as many values as it can count (`u32::MAX`), or would go deeper than its depth limit.
+    ///
+    ///     use jtree::Jbst;
+    ///
+    ///     let mut tree = Jbst::from_collection([1,2,3]);
+    ///     assert_eq!( Some(2), tree.insert(2) );
+    ///     assert_eq!( None, tree.insert(4) );
+    ///     assert_eq!( 4, tree.get_size() );
+    pub fn insert(&mut self, value: T) -> Option<T> {
+        let (slot, depth) = Node::slot_for(&mut self.root, &value, 1);
+        Self::check_depth(slot, depth, self.depth_limit);
+        let displaced = match slot {
+            None => {
+                self.size = self.size.checked_add(1).expect("the tree already holds as many values as it can count");
+                *slot = Some(Box::new(Node::new(value)));
+                None
+            },
+            Some(node) if node.deleted => {
+                self.size = self.size.checked_add(1).expect("the tree already holds as many values as it can count");
+                node.value = value;
+                node.deleted = false;
+                self.tombstones -= 1;
+                None
+            },
+            Some(node) => Some(std::mem::replace(&mut node.value, value)),
+        };
+        #[cfg(feature = "paranoid")]
+        self.paranoid_check("insert");
+        displaced
+    }
+
+    /// Panics if the slot found by `Node::slot_for`, at the given depth, is empty and too deep for a new node.
+    fn check_depth(slot: &Option<Box<Node<T>>>, depth: usize, depth_limit: usize) {
+        assert!( slot.is_some() || depth <= depth_limit, "the value would be deeper in the tree than its depth limit ({depth_limit})" );
+    }
+
+    /// Fill the slot found by `Node::slot_for` with a new node, if it's empty, or revive its node with a new
+    /// value, if it's a tombstone, and return the value there.  The `paranoid` check can't run here, since the returned
+    /// value borrows the tree; but nothing else in the tree moves.
+    fn fill_slot<'a, F: FnOnce() -> T>(slot: &'a mut Option<Box<Node<T>>>, size: &mut u32, tombstones: &mut u32, make: F) -> &'a T {
+        match slot {
+            None => {
+                *size = size.checked_add(1).expect("the tree already holds as many values as it can count");
+                *slot = Some(Box::new(Node::new(make())));
+            },
+            Some(node) if node.deleted => {
+                *size = size.checked_add(1).expect("the tree already holds as many values as it can count");
+                node.value = make();
+                node.deleted = false;
+                *tombstones -= 1;
+            },
+            Some(_) => {},
+        }
+        &slot.as_ref().unwrap().value
+    }
+
+    /// Returns references to all the values in the tree as an ordered Vec from least to greatest, for when
+    /// cloning them with `as_vec` would be wasteful.  Tombstones are skipped.
+    ///
+    ///     use jtree::Jbst;
+    ///
+    ///     let my_tree = Jbst::from_collection([2,1,3]);
+    ///     assert_eq!( vec!(&1,&2,&3), my_tree.values() );
+    pub fn values(&self) -> Vec<&T> {
+        let mut vals = Vec::with_capacity(self.size as usize);
+        vals.extend(self.iter());
+        vals
+    }
+
+    /// Consume the tree, moving its values into a Vec from least to greatest.  Unlike `as_vec`, nothing is cloned;
+    /// tombstones are simply dropped.
+    pub fn into_sorted_vec(self) -> Vec<T> {
+        self.into_iter().collect()
+    }
+
+    /// Call `f` on each value in order, from least to greatest, without copying the values into a Vec.  Tombstones are skipped.
+    ///
+    ///     use jtree::Jbst;
+    ///
+    ///     let my_tree = Jbst::from_collection([2,1,3]);
+    ///     let mut total = 0;
+    ///     my_tree.visit_in_order(|value| total += value);
+    ///     assert_eq!( 6, total );
+    pub fn visit_in_order<F: FnMut(&T)>(&self, mut f: F) {
+        let _ = self.try_visit(Traversal::In, |value| {
+            f(value);
+            ControlFlow::<()>::Continue(())
+        });
+    }
+
+    /// Call `f` on each value in pre-order: each node's value before the values in its left subtree, then its right subtree.
+    pub fn visit_pre_order<F: FnMut(&T)>(&self, mut f: F) {
+        let _ = self.try_visit(Traversal::Pre, |value| {
+            f(value);
+            ControlFlow::<()>::Continue(())
+        });
+    }
+
+    /// Call `f` on each value in post-order: the values in each node's left subtree, then its right subtree, then its own value.
+    pub fn visit_post_order<F: FnMut(&T)>(&self, mut f: F) {
+        let _ = self.try_visit(Traversal::Post, |value| {
+            f(value);
+            ControlFlow::<()>::Continue(())
+        });
+    }
+
+    /// Like `visit_in_order`, but `f` can stop the visit early by returning `ControlFlow::Break`, which is passed
+    /// back to the caller.
+    ///
+    ///     use std::ops::ControlFlow;
+    ///     use jtree::Jbst;
+    ///
+    ///     let my_tree = Jbst::from_collection([5,1,8,3]);
+    ///     let first_over_2 = my_tree.try_visit_in_order(|value| match *value > 2 {
+    ///         true => ControlFlow::Break(*value),
+    ///         false => ControlFlow::Continue(()),
+    ///     });
+    ///     assert_eq!( ControlFlow::Break(3), first_over_2 );
+    pub fn try_visit_in_order<B, F: FnMut(&T) -> ControlFlow<B>>(&self, f: F) -> ControlFlow<B> {
+        self.try_visit(Traversal::In, f)
+    }
+
+    /// Like `visit_pre_order`, but `f` can stop the visit early by returning `ControlFlow::Break`.
+    pub fn try_visit_pre_order<B, F: FnMut(&T) -> ControlFlow<B>>(&self, f: F) -> ControlFlow<B> {
+        self.try_visit(Traversal::Pre, f)
+    }
+
+    /// Like `visit_post_order`, but `f` can stop the visit early by returning `ControlFlow::Break`.
+    pub fn try_visit_post_order<B, F: FnMut(&T) -> ControlFlow<B>>(&self, f: F) -> ControlFlow<B> {
+        self.try_visit(Traversal::Post, f)
+    }
+
+    fn try_visit<B, F: FnMut(&T) -> ControlFlow<B>>(&self, order: Traversal, mut f: F) -> ControlFlow<B> {
+        return match &self.root {
+            None => ControlFlow::Continue(()),
+            Some(branch) => branch.try_visit(order, &mut f),
+        };
+    }
+
+    /// Returns an iterator over the values in order, from least to greatest, which borrows them rather than
+    /// cloning them into a Vec as `as_vec` does.  Tombstones are skipped.
+    ///
+    ///     use jtree::Jbst;
+    ///
+    ///     let my_tree = Jbst::from_collection([5,1,8,3]);
+    ///     assert_eq!( vec!(&1,&3,&5,&8), my_tree.iter().collect::<Vec<_>>() );
+    ///     assert_eq!( vec!(&8,&5), my_tree.iter().rev().take(2).collect::<Vec<_>>() );
+    ///     for value in &my_tree {
+    ///         assert!( my_tree.contains(value) );
+    ///     }
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { nodes: Nodes::new(&self.root) }
+    }
+
+    /// Returns an iterator over the values within a range (such as `2..5`, `..=7` or `(Bound::Excluded(1),
+    /// Bound::Unbounded)`), in order, from either end.  Only the paths down to the range's bounds are searched
+    /// to find them, so on a well-shaped tree it takes O(log(n)) time, plus O(1) for each value yielded.
+    ///
+    ///     use jtree::Jbst;
+    ///
+    ///     let my_tree = Jbst::from_collection([50,20,80,10,30,60,90]);
+    ///     assert_eq!( vec!(&20,&30,&50), my_tree.range(15..60).collect::<Vec<_>>() );
+    ///     assert_eq!( vec!(&90,&80), my_tree.range(80..).rev().collect::<Vec<_>>() );
+    pub fn range<R: RangeBounds<T>>(&self, range: R) -> Iter<'_, T> {
+        Iter { nodes: Nodes::within(&self.root, &range) }
+    }
+
+    /// Returns the values stored in leaf nodes (those with no children), in order from least to greatest.
+    /// A tombstone is still a node, so a value whose only children are tombstones isn't a leaf, and tombstoned
+    /// leaves are skipped.
+    ///
+    ///     use jtree::Jbst;
+    ///
+    ///     let my_tree = Jbst::from_collection([4,2,6,1,3]);
+    ///     assert_eq!( vec!(&1,&3,&6), my_tree.leaves().collect::<Vec<_>>() );
+    pub fn leaves(&self) -> impl Iterator<Item = &T> {
+        Nodes::new(&self.root).filter(|(node, _)| node.is_leaf() && !node.deleted).map(|(node, _)| &node.value)
+    }
+
+    /// Returns each value with its depth in the tree (the root is at depth 0), in order from least to greatest.
+    /// Tombstones are skipped, but still count towards the depths of the nodes below them.
+    ///
+    ///     use jtree::Jbst;
+    ///
+    ///     let my_tree = Jbst::from_collection([2,1,3]);
+    ///     assert_eq!( vec!((&1,1), (&2,0), (&3,1)), my_tree.iter_with_depth().collect::<Vec<_>>() );
+    pub fn iter_with_depth(&self) -> impl Iterator<Item = (&T, usize)> {
+        Nodes::new(&self.root).filter(|(node, _)| !node.deleted).map(|(node, depth)| (&node.value, depth))
+    }
+
+    /// Returns the values of this tree and another together, in order from least to greatest, without copying
+    /// them or sorting them again.  `duplicates` says whether a value in both trees is yielded twice or once.
+    ///
+    ///     use jtree::Jbst;
+    ///     use jtree::merge::Duplicates;
+    ///
+    ///     let tree_a = Jbst::from_collection([1,3,5]);
+    ///     let tree_b = Jbst::from_collection([2,3,4]);
+    ///     assert_eq!( vec!(&1,&2,&3,&4,&5), tree_a.merge_iter(&tree_b, Duplicates::Union).collect::<Vec<_>>() );
+    pub fn merge_iter<'a>(&'a self, other: &'a Self, duplicates: Duplicates) -> impl Iterator<Item = &'a T> {
+        MergeIter::new(self.iter(), other.iter(), duplicates)
+    }
+
+    /// Returns a cursor positioned at the value, or if it's not in the tree, at the least value greater than it
+    /// (or the ghost position past the end, if there's none).  From there, the cursor can walk to neighbouring values
+    /// in either direction.  Tombstones are skipped.
+    ///
+    ///     use jtree::Jbst;
+    ///
+    ///     let my_tree = Jbst::from_collection([10,20,30,40]);
+    ///     let mut cursor = my_tree.cursor_at(&25);
+    ///     assert_eq!( Some(&30), cursor.peek() );
+    ///     assert_eq!( Some(&40), cursor.next() );
+    ///     assert_eq!( None, cursor.next() ); // past the end
+    ///     assert_eq!( Some(&40), cursor.prev() );
+    pub fn cursor_at(&self, value: &T) -> Cursor<'_, T> {
+        let mut cursor = Cursor::new(&self.root);
+        cursor.seek(value);
+        cursor.settle_forward();
+        cursor
+    }
+
+    /// Returns a cursor positioned at the least value (or the ghost position, if the tree is empty).
+    pub fn cursor_front(&self) -> Cursor<'_, T> {
+        Cursor::front(&self.root)
+    }
+
+    /// Returns a cursor positioned at the greatest value (or the ghost position, if the tree is empty).
+    pub fn cursor_back(&self) -> Cursor<'_, T> {
+        let mut cursor = Cursor::new(&self.root);
+        cursor.prev();
+        cursor
+    }
+
+    /// Take all of the values out of the tree, in order from least to greatest, leaving it empty (and ready
+    /// to be reused) without copying the values.  Tombstones are discarded.  The tree is emptied straight away, so any values
+    /// the iterator isn't asked for are dropped with it.
+    ///
+    ///     use jtree::Jbst;
+    ///
+    ///     let mut my_tree = Jbst::from_collection([2,1,3]);
+    ///     assert_eq!( vec!(1,2,3), my_tree.drain().collect::<Vec<_>>() );
+    ///     assert_eq!( 0, my_tree.get_size() );
+    pub fn drain(&mut self) -> impl DoubleEndedIterator<Item = T> + use<T> {
+        self.take_nodes()
+    }
+
+    /// Empty the tree, handing its nodes to a `Drain` to take the values out of them, for `drain` and `into_iter`.
+    fn take_nodes(&mut self) -> Drain<T> {
+        #[cfg(feature = "metrics")]
+        metrics::record(|metrics| metrics.frees += u64::from(self.size) + u64::from(self.tombstones));
+        self.size = 0;
+        self.tombstones = 0;
+        Drain::new(self.root.take())
+    }
+
+    /// If the value is in the tree, delete it.  Otherwise a TreeError::ValueNotFound will be returned.
+    /// 
+    /// In lazy-deletion mode the value's node is only marked as deleted, and the tree is purged if
+    /// that pushes the number of tombstones over the limit.
+    pub fn drop_value(&mut self, value: T) -> Result<(),TreeError<T>> {
+        #[cfg(feature = "metrics")]
+        metrics::start_operation();
+        if let Some(tombstone_limit) = self.tombstone_limit {
+            let marked = match &mut self.root {
+                None => false,
+                Some(branch) => branch.mark_deleted(&value),
+            };
+            if !marked {
+                return Err(TreeError::ValueNotFound { value });
+            }
+            self.size -= 1;
+            self.tombstones += 1;
+            if self.tombstones > tombstone_limit {
+                self.purge();
+            }
+            #[cfg(feature = "paranoid")]
+            self.paranoid_check("drop_value");
+            return Ok(());
+        }
+        self.remove_node(value)?;
+        #[cfg(feature = "paranoid")]
+        self.paranoid_check("drop_value");
+        Ok(())
+    }
+
+    /// Physically remove all nodes that have been marked as deleted.  Does nothing unless the tree
+    /// was created with `with_lazy_deletion`.
+    pub fn purge(&mut self) {
+        if self.tombstones == 0 {
+            return;
+        }
+        if let Some(branch) = self.root.take() {
+            let (new_root, removed) = branch.purge(1);
+            self.root = new_root;
+            self.tombstones -= removed;
+            #[cfg(feature = "metrics")]
+            metrics::record(|metrics| metrics.frees += removed as u64);
+        }
+        #[cfg(feature = "paranoid")]
+        self.paranoid_check("purge");
+    }
+
+    /// Restructure the tree to remove the node holding this value, whether it is live or a tombstone.
+    fn remove_node(&mut self, value: T) -> Result<(),TreeError<T>> {
+        #[cfg(feature = "metrics")]
+        metrics::start_operation();
+        match self.root.take() {
+            None => {
+                self.root = None;
+                return Err(TreeError::ValueNotFound { value });
+            },
+            Some(child) => {
+                match child.drop_value(value) {
+                    (Err(error), new_node) => {
+                        self.root = new_node;
+                        return Err(error);
+                    },
+                    (Ok(was_tombstone), new_node) => {
+                        self.root = new_node;
+                        #[cfg(feature = "metrics")]
+                        metrics::record(|metrics| metrics.frees += 1);
+                        if was_tombstone {
+                            self.tombstones -= 1;
+                        } else {
+                            self.size -= 1;
+                        }
+                        return Ok(());
+                    }
+                }
+            },
+        }
+    }
+
+    /// Measure the tree's shape: node count, height, and leaf depths.  Tombstones left by lazy
+    /// deletion are still nodes, so they are counted too.
+    pub fn stats(&self) -> TreeStats {
+        let mut stats = TreeStats::default();
+        match &self.root {
+            None => (),
+            Some(branch) => branch.collect_stats(0, &mut stats),
+        }
+        stats
+    }
+
+    /// Verify the tree's internal consistency: that values are in order and the size and tombstone
+    /// counts match the nodes in the tree.  Returns a TreeError::InvariantViolation describing the
+    /// first problem found, if any.
+    pub fn check_invariants(&self) -> Result<(),TreeError<T>> {
+        let (live, dead) = match &self.root {
+            None => (0, 0),
+            Some(branch) => branch.check_invariants(&mut String::from("root"), None, None)?,
+        };
+        if live != self.size {
+            return Err(TreeError::InvariantViolation(format!("size is {} but the tree holds {} values", self.size, live)));
+        }
+        if dead != self.tombstones {
+            return Err(TreeError::InvariantViolation(format!("tombstone count is {} but the tree holds {} tombstones", self.tombstones, dead)));
+        }
+        Ok(())
+    }
+
+    /// With the `paranoid` feature, every change to the tree is followed by this check, which panics
+    /// with a description of the problem and the tree's shape if the change broke an invariant.
+    #[cfg(feature = "paranoid")]
+    fn paranoid_check(&self, operation: &str) {
+        if let Err(TreeError::InvariantViolation(problem)) = self.check_invariants() {
+            let shape = self.root.as_ref().map_or(String::from("."), |branch| branch.outline());
+            panic!("Jbst::{operation} broke the tree: {problem} (size {}, tombstones {}, shape {shape})", self.size, self.tombstones);
+        }
+    }
+
+}
+
+// The methods below copy values out of the tree (or, for `try_add_all` and `apply`, keep copies to undo with).
+impl <T: PartialEq + PartialOrd + Clone> Jbst<T> {
+
     /// Returns the 'value' field of the root node; used for automated tests only
     #[cfg(test)]
     fn get_root_value(&self) -> Option<T> {
@@ -86,12 +684,29 @@ impl <T: PartialEq + PartialOrd + Clone> Jbst<T> {
         }
     }
 
-    /// Returns true if the value is currently a member of the tree
-    pub fn contains(&self, value: &T) -> bool {
-        return match &self.root {
-            None => false,
-            Some(branch) => branch.contains(value), 
-        };
+    /// Adds all members of a collection (vector, array, or whatever) to the tree as a single transaction:
+    /// if any value is a duplicate, or can't be added for another reason (such as the depth limit), the values
+    /// already added from this batch are removed again so the tree holds the same values as before.  A duplicate
+    /// is reported as a TreeError::DuplicateAt, holding the index and value of the offending element; any other
+    /// error is returned as `add` gave it.
+    pub fn try_add_all<U: IntoIterator<Item = T>>(&mut self, collection: U) -> Result<(),TreeError<T>> {
+        let mut added = Vec::new();
+        for (index, elem) in collection.into_iter().enumerate() {
+            let copy = elem.clone();
+            let error = match self.add(elem) {
+                Ok(()) => {
+                    added.push(copy);
+                    continue;
+                },
+                Err(TreeError::ValueAlreadyStored { value }) => TreeError::DuplicateAt { index, value },
+                Err(other) => other,
+            };
+            for value in added.into_iter().rev() {
+                let _ = self.remove_node(value);
+            }
+            return Err(error);
+        }
+        Ok(())
     }
 
     /// Short for `as_vec_l_to_r`, this method returns all the values in the tree as an ordered Vec
@@ -105,30 +720,131 @@ impl <T: PartialEq + PartialOrd + Clone> Jbst<T> {
         return match &self.root {
             None => Vec::new(),
             Some(branch) => {
-                let mut vals = Vec::new();
+                let mut vals = Vec::with_capacity(self.size as usize);
                 branch.collect_values_l_to_r(&mut vals);
                 vals 
             }
         };
     }
 
-    /// Returns all the values in the tree as an ordered Vec from greatest to least  (right to left).
+    /// Returns the `n` least values in the tree (or all of them, if there are fewer), from least to greatest.
+    /// Only the part of the tree holding them is traversed, so this is much cheaper than slicing `as_vec`
+    /// when `n` is small.  Tombstones are skipped.
+    ///
+    ///     use jtree::Jbst;
+    ///
+    ///     let my_tree = Jbst::from_collection([5,1,8,3,9]);
+    ///     assert_eq!( vec!(1,3), my_tree.first_n(2) );
+    ///     assert_eq!( vec!(8,9), my_tree.last_n(2) );
+    pub fn first_n(&self, n: usize) -> Vec<T> {
+        let mut vals = Vec::with_capacity(min(n, self.size as usize));
+        match &self.root {
+            None => (),
+            Some(branch) => branch.collect_first_n(n, &mut vals),
+        }
+        vals
+    }
+
+    /// Returns the `n` greatest values in the tree (or all of them, if there are fewer), from least to greatest,
+    /// like the end of `as_vec`; but only the part of the tree holding them is traversed.
+    pub fn last_n(&self, n: usize) -> Vec<T> {
+        let mut vals = Vec::with_capacity(min(n, self.size as usize));
+        match &self.root {
+            None => (),
+            Some(branch) => branch.collect_last_n(n, &mut vals),
+        }
+        vals.reverse();
+        vals
+    }
+
+    /// Returns all the values in the tree as an ordered Vec from greatest to least  (right to left).  To walk them
+    /// in that order without cloning them, use `iter().rev()`.
     pub fn as_vec_r_to_l(&self) -> Vec<T> {
         return match &self.root {
             None => Vec::new(),
             Some(branch) => {
-                let mut vals = Vec::new();
+                let mut vals = Vec::with_capacity(self.size as usize);
                 branch.collect_values_r_to_l(&mut vals);
                 vals 
             }
         };
     }
 
+    /// Returns a cursor that can remove values as it walks the tree, positioned like `cursor_at`.
+    pub fn cursor_mut_at(&mut self, value: &T) -> CursorMut<'_, T> {
+        let current = self.cursor_at(value).peek().cloned();
+        CursorMut::new(self, current)
+    }
+
+    /// Returns a cursor that can remove values as it walks the tree, positioned at the least value.
+    pub fn cursor_mut_front(&mut self) -> CursorMut<'_, T> {
+        let current = self.cursor_front().peek().cloned();
+        CursorMut::new(self, current)
+    }
+
+    /// Returns a cursor that can remove values as it walks the tree, positioned at the greatest value.
+    pub fn cursor_mut_back(&mut self) -> CursorMut<'_, T> {
+        let current = self.cursor_back().peek().cloned();
+        CursorMut::new(self, current)
+    }
+
+    /// Copy the values into a read-only `FrozenSet`, which is faster to search.
+    pub fn freeze(&self) -> FrozenSet<T> {
+        FrozenSet::from_sorted_vec(self.as_vec())
+    }
+
+    /// Compare the values in this tree with another's, in a single pass over both in order, listing the values
+    /// found in only one of them.
+    ///
+    ///     use jtree::Jbst;
+    ///
+    ///     let old_tree = Jbst::from_collection([1,2,3]);
+    ///     let new_tree = Jbst::from_collection([2,3,4]);
+    ///     let diff = old_tree.diff(&new_tree);
+    ///     assert_eq!( vec!(1), diff.only_in_self );
+    ///     assert_eq!( vec!(4), diff.only_in_other );
+    pub fn diff(&self, other: &Self) -> TreeDiff<T> {
+        TreeDiff::between(self.iter(), other.iter())
+    }
+
+    /// Patch this tree with a diff, removing its `only_in_self` values and adding its `only_in_other` values,
+    /// so that after `tree_a.apply(&tree_a.diff(&tree_b))`, `tree_a` holds the same values as `tree_b`.  The
+    /// patch is all-or-nothing: if a value to remove isn't in the tree, or a value to add already is (or would go
+    /// deeper than the depth limit), the changes already made are undone and the error is returned.
+    pub fn apply(&mut self, diff: &TreeDiff<T>) -> Result<(),TreeError<T>> {
+        for (index, value) in diff.only_in_self.iter().enumerate() {
+            if let Err(error) = self.drop_value(value.clone()) {
+                self.restore(&diff.only_in_self[..index]);
+                return Err(error);
+            }
+        }
+        for (index, value) in diff.only_in_other.iter().enumerate() {
+            if let Err(error) = self.add(value.clone()) {
+                for added in diff.only_in_other[..index].iter() {
+                    let _ = self.drop_value(added.clone());
+                }
+                self.restore(&diff.only_in_self);
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    /// Add back the values a change being undone has removed.  They were in the tree a moment ago, so they go
+    /// back even where the change has left them deeper than the depth limit.
+    fn restore(&mut self, values: &[T]) {
+        let depth_limit = std::mem::replace(&mut self.depth_limit, usize::MAX);
+        for value in values {
+            let _ = self.add(value.clone());
+        }
+        self.depth_limit = depth_limit;
+    }
+
     /// Returns the smallest/lowest value in the tree, if any.
     pub fn least_value(&self) -> Option<T> {
         return match &self.root {
             None => None,
-            Some(subtree) => Some(subtree.least_value()),
+            Some(subtree) => subtree.least_live_value(),
         }
     }
 
@@ -136,235 +852,1143 @@ impl <T: PartialEq + PartialOrd + Clone> Jbst<T> {
     pub fn greatest_value(&self) -> Option<T> {
         return match &self.root {
             None => None,
-            Some(subtree) => Some(subtree.greatest_value()),
+            Some(subtree) => subtree.greatest_live_value(),
         }
     }
 
-    /// If the value is in the tree, delete it.  Otherwise a TreeError::ValueNotFound will be returned.
-    pub fn drop_value(&mut self, value: T) -> Result<(),TreeError> {
-        match self.root.take() {
-            None => {
-                self.root = None;
-                return Err(TreeError::ValueNotFound);
+    /// Export the tree's structure as a petgraph `DiGraph` (see the `graph` module).  Tombstones left by lazy deletion are still nodes, so they're included.
+    #[cfg(feature = "petgraph")]
+    pub fn to_petgraph(&self) -> DiGraph<T, Branch> {
+        let mut graph = DiGraph::new();
+        match &self.root {
+            None => (),
+            Some(branch) => {
+                branch.add_to_graph(&mut graph);
             },
-            Some(child) => {
-                match child.drop_value(value) {
-                    (Err(_), new_node) => {
-                        self.root = new_node;
-                        return Err(TreeError::ValueNotFound);
-                    },
-                    (Ok(_), new_node) => {
-                        self.root = new_node;
-                        self.size -= 1;
-                        return Ok(());
-                    }
-                }
+        }
+        graph
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd> Default for Jbst<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <T: PartialEq + PartialOrd + std::fmt::Debug> fmt::Debug for Jbst<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("Jbst")
+            .field("size", &self.get_size())
+            .field("values", &self.values())
+            .finish()
+    }
+}
+
+impl <T: PartialEq + PartialOrd + std::fmt::Debug> Jbst<T> {
+
+    /// Draw the tree sideways as ASCII art, one node per line, with the root at the left margin and
+    /// each right subtree drawn above its parent.
+    /// Tombstones left by lazy deletion are marked `(deleted)`.  An empty tree is drawn as an empty string.
+    ///
+    ///     use jtree::Jbst;
+    ///
+    ///     let my_tree = Jbst::from_collection([3,1,5,4,6]);
+    ///     assert_eq!( "    /-- 6\n/-- 5\n|   \\-- 4\n3\n\\-- 1\n", my_tree.to_ascii() );
+    ///
+    /// where the drawing looks like:
+    ///
+    /// ```text
+    ///     /-- 6
+    /// /-- 5
+    /// |   \-- 4
+    /// 3
+    /// \-- 1
+    /// ```
+    pub fn to_ascii(&self) -> String {
+        let mut drawing = String::new();
+        match &self.root {
+            None => (),
+            Some(branch) => branch.draw(&mut drawing, "", ""),
+        }
+        drawing
+    }
+
+    /// Print the tree's structure to stdout, as drawn by `to_ascii`.
+    pub fn print_tree(&self) {
+        print!("{}", self.to_ascii());
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd + Codec> Jbst<T> {
+
+    /// Encode the tree in jtree's binary format (see the `codec` module), keeping its exact shape.  Lazy deletion settings and tombstones are saved too.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        codec::write_header(&mut bytes, b'S');
+        self.size.encode(&mut bytes);
+        self.tombstones.encode(&mut bytes);
+        match self.tombstone_limit {
+            None => false.encode(&mut bytes),
+            Some(limit) => {
+                true.encode(&mut bytes);
+                limit.encode(&mut bytes);
             },
         }
+        match &self.root {
+            None => (),
+            Some(branch) => branch.encode(&mut bytes),
+        }
+        bytes
+    }
+
+    /// Decode a tree encoded by `to_bytes`.  Returns a TreeError::DeserializationCorrupt if the bytes
+    /// aren't a valid Jbst, or if the tree is deeper than `DECODE_DEPTH_LIMIT`.  Like a new tree, the
+    /// decoded one has the default depth limit.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self,TreeError<T>> {
+        let mut tree = Self::from_bytes_with_depth_limit(bytes, DECODE_DEPTH_LIMIT)?;
+        tree.depth_limit = DEFAULT_DEPTH_LIMIT;
+        Ok(tree)
+    }
+
+    /// Decode a tree encoded by `to_bytes`, like `from_bytes`, but refusing trees deeper than the given depth
+    /// limit (at least 1) rather than `DECODE_DEPTH_LIMIT`, and giving the decoded tree that limit.  (The
+    /// limit isn't saved.)
+    pub fn from_bytes_with_depth_limit(bytes: &[u8], depth_limit: usize) -> Result<Self,TreeError<T>> {
+        let depth_limit = depth_limit.max(1);
+        let mut reader = Reader::new(bytes);
+        reader.read_header(b'S', "Jbst")?;
+        let size: u32 = reader.read()?;
+        let tombstones: u32 = reader.read()?;
+        let tombstone_limit = match reader.read()? {
+            false => None,
+            true => Some(reader.read()?),
+        };
+        let root = match size > 0 || tombstones > 0 {
+            false => None,
+            true => Some(Node::decode(&mut reader, 1, depth_limit)?),
+        };
+        reader.finish()?;
+        let tree = Self { root, size, tombstones, tombstone_limit, depth_limit };
+        reader.validate(tree.check_invariants())?;
+        Ok(tree)
+    }
+
+    /// Save the tree to a file, in the format written by `to_bytes`, replacing the file if it exists.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(),TreeError<T>> {
+        fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+
+    /// Load a tree from a file written by `save`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self,TreeError<T>> {
+        let bytes = fs::read(path)?;
+        Self::from_bytes(&bytes)
     }
 
 }
 
-impl <T: PartialEq + PartialOrd + Clone> Default for Jbst<T> {
-    fn default() -> Self {
-        Self::new()
-    }
-}
+impl <T: PartialEq + PartialOrd + Hash> Jbst<T> {
+
+    /// Hash the tree's contents: its size and its values in order, so two trees holding the same values
+    /// have the same digest however they're shaped.  Tombstones left by lazy deletion aren't part of the contents, so they don't change the digest.
+    /// The digest is only as stable as the hasher (for example, `DefaultHasher::new()` may change between
+    /// Rust releases), so use the same one on both sides when comparing trees in different places.
+    ///
+    ///     use std::hash::DefaultHasher;
+    ///     use jtree::Jbst;
+    ///
+    ///     let tree_a = Jbst::from_collection([1,2,3]);
+    ///     let tree_b = Jbst::from_collection([3,2,1]);
+    ///     assert_eq!( tree_a.digest::<DefaultHasher>(), tree_b.digest::<DefaultHasher>() );
+    pub fn digest<H: Hasher + Default>(&self) -> u64 {
+        let mut hasher = H::default();
+        self.size.hash(&mut hasher);
+        match &self.root {
+            None => (),
+            Some(branch) => branch.hash_values(&mut hasher),
+        }
+        hasher.finish()
+    }
+
+}
+
+/// What `Node::drop_value` hands back to its parent: the outcome, and the node that takes the dropped node's place.
+type DropOutcome<T> = (Result<bool,TreeError<T>>, Option<Box<Node<T>>>);
+
+struct Node<T: PartialEq + PartialOrd> {
+    value: T,
+    deleted: bool, // tombstone left behind by lazy deletion
+    left: Option<Box<Node<T>>>,
+    right: Option<Box<Node<T>>>,
+}
+
+impl <T: PartialEq + PartialOrd> SearchNode for Node<T> {
+    type Value = T;
+    fn value(&self) -> &T { &self.value }
+    fn left(&self) -> Option<&Self> { self.left.as_deref() }
+    fn right(&self) -> Option<&Self> { self.right.as_deref() }
+}
+
+impl <T:PartialEq + PartialOrd> Node<T> {
+
+    pub fn new(value: T) -> Self {
+        #[cfg(feature = "metrics")]
+        metrics::record(|metrics| metrics.allocations += 1);
+        Self {
+            value,
+            deleted: false,
+            left: None,
+            right: None,
+        }
+    }
+
+    /// Insert a value.  Returns true if the value was stored by reviving a tombstone rather than
+    /// by creating a new node.  `depth` is this node's (1 for the root), and no new node may go deeper
+    /// than `depth_limit`.
+    pub fn add(&mut self, value: T, depth: usize, depth_limit: usize) -> Result<bool,TreeError<T>> {
+        #[cfg(feature = "metrics")]
+        metrics::visit();
+        if value == self.value {
+            if self.deleted {
+                self.value = value;
+                self.deleted = false;
+                return Ok(true);
+            }
+            // no duplicates allowed in this kind of tree
+            return Err(TreeError::ValueAlreadyStored { value })
+        }
+        if value < self.value {
+            // add to the left branch
+            match &mut self.left {
+                None if depth == depth_limit => return Err(TreeError::DepthLimitExceeded { value, limit: depth_limit }),
+                None => self.left = Some(Box::new(Node::new(value))),
+                Some(branch) => return branch.add(value, depth + 1, depth_limit),
+            }
+            return Ok(false)
+        } else {
+            // add it to the right branch
+            match &mut self.right {
+                None if depth == depth_limit => return Err(TreeError::DepthLimitExceeded { value, limit: depth_limit }),
+                None => self.right = Some(Box::new(Node::new(value))),
+                Some(branch) => return branch.add(value, depth + 1, depth_limit),
+            }
+            return Ok(false)
+        }
+    }
+
+    /// Mark the node holding the value as deleted, leaving the structure of the (sub)tree as it is.
+    /// Returns false if there is no live node with the value.
+    pub fn mark_deleted(&mut self, value: &T) -> bool {
+        #[cfg(feature = "metrics")]
+        metrics::visit();
+        if *value == self.value {
+            if self.deleted {
+                return false;
+            }
+            self.deleted = true;
+            return true;
+        }
+        let child = if *value < self.value { &mut self.left } else { &mut self.right };
+        return match child {
+            None => false,
+            Some(node) => node.mark_deleted(value),
+        }
+    }
+
+    /// Returns the (live) stored value equal to `value`, if it's in the (sub)tree
+    pub fn get(&self, value: &T) -> Option<&T> {
+        if *value == self.value {
+            return if self.deleted { None } else { Some(&self.value) };
+        }
+        let child = if *value < self.value { &self.left } else { &self.right };
+        return match child {
+            None => None,
+            Some(node) => node.get(value),
+        }
+    }
+
+    /// Find the link that holds the node with the probe's value, or the empty link where such a node
+    /// would be added, along with that node's depth (`depth` being the given link's).  (It decides before
+    /// borrowing the node mutably, so that the link can be returned.)
+    pub fn slot_for<'a>(link: &'a mut Option<Box<Node<T>>>, probe: &T, depth: usize) -> (&'a mut Option<Box<Node<T>>>, usize) {
+        let arrived = match link {
+            None => true,
+            Some(node) => *probe == node.value,
+        };
+        if arrived {
+            return (link, depth);
+        }
+        let node = link.as_mut().unwrap();
+        let child = if *probe < node.value { &mut node.left } else { &mut node.right };
+        Self::slot_for(child, probe, depth + 1)
+    }
+
+    /// Returns true if the value is currently a member of the (sub)tree
+    pub fn contains(&self, value: &T) -> bool {
+        #[cfg(feature = "metrics")]
+        metrics::visit();
+        if *value == self.value {
+            return !self.deleted;
+        }
+        if *value < self.value {
+            match &self.left {
+                Some(node) => node.contains(value),
+                None => return false
+            }
+        } else {
+            match &self.right {
+                Some(node) => node.contains(value),
+                None => return false
+            }
+        }
+    }
+
+    /// Recursively verify that this (sub)tree's values are in order and strictly between the bounds
+    /// set by its ancestors.  Returns the number of live and tombstoned nodes in the (sub)tree.
+    /// The path (e.g. "root.left.right") is only used to describe where a problem was found.
+    pub fn check_invariants(&self, path: &mut String, lower: Option<&T>, upper: Option<&T>) -> Result<(u32,u32),TreeError<T>> {
+        if lower.is_some_and(|lower| self.value <= *lower) || upper.is_some_and(|upper| self.value >= *upper) {
+            return Err(TreeError::InvariantViolation(format!("value at {path} is out of order")));
+        }
+        let (mut live, mut dead) = if self.deleted { (0, 1) } else { (1, 0) };
+        let path_len = path.len();
+        if let Some(node) = &self.left {
+            path.push_str(".left");
+            let (left_live, left_dead) = node.check_invariants(path, lower, Some(&self.value))?;
+            path.truncate(path_len);
+            live += left_live;
+            dead += left_dead;
+        }
+        if let Some(node) = &self.right {
+            path.push_str(".right");
+            let (right_live, right_dead) = node.check_invariants(path, Some(&self.value), upper)?;
+            path.truncate(path_len);
+            live += right_live;
+            dead += right_dead;
+        }
+        Ok((live, dead))
+    }
+
+    /// Sketch the shape of this (sub)tree, for the `paranoid` feature's diagnostics, without needing to print the values.
+    /// Live nodes are shown as `o` and tombstones as `x`, e.g. `(. o (. x .))`.
+    #[cfg(feature = "paranoid")]
+    fn outline(&self) -> String {
+        let left = self.left.as_ref().map_or(String::from("."), |node| node.outline());
+        let right = self.right.as_ref().map_or(String::from("."), |node| node.outline());
+        format!("({left} {} {right})", if self.deleted { "x" } else { "o" })
+    }
+
+    /// Recursively tally this (sub)tree's nodes, at the given depth, into the stats.
+    pub fn collect_stats(&self, depth: usize, stats: &mut TreeStats) {
+        stats.record_node(depth, self.is_leaf());
+        match &self.left {
+            Some(node) => node.collect_stats(depth + 1, stats),
+            None => (),
+        }
+        match &self.right {
+            Some(node) => node.collect_stats(depth + 1, stats),
+            None => (),
+        }
+    }
+
+    /// Returns true if the node is a leaf or terminal node, with no child nodes of its own.
+    pub fn is_leaf(&self) -> bool {
+        self.left.is_none() && self.right.is_none()
+    }
+
+    /// Remove the node with the smallest value from this (sub)tree, even if it is a tombstone, returning
+    /// the node that replaces this one along with the removed node's value and tombstone flag.
+    fn take_least(mut self: Box<Self>) -> (Option<Box<Node<T>>>, T, bool) {
+        return match self.left.take() {
+            None => (self.right, self.value, self.deleted),
+            Some(left_child) => {
+                let (new_left, value, deleted) = left_child.take_least();
+                self.left = new_left;
+                (Some(self), value, deleted)
+            },
+        }
+    }
+
+    /// Recursively call `f` on this (sub)tree's values in the given order, stopping as soon as it returns `ControlFlow::Break`.
+    pub fn try_visit<B, F: FnMut(&T) -> ControlFlow<B>>(&self, order: Traversal, f: &mut F) -> ControlFlow<B> {
+        if order == Traversal::Pre {
+            self.visit_value(f)?;
+        }
+        if let Some(node) = &self.left {
+            node.try_visit(order, f)?;
+        }
+        if order == Traversal::In {
+            self.visit_value(f)?;
+        }
+        if let Some(node) = &self.right {
+            node.try_visit(order, f)?;
+        }
+        if order == Traversal::Post {
+            self.visit_value(f)?;
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Call `f` on this node's value, unless it's a tombstone.
+    fn visit_value<B, F: FnMut(&T) -> ControlFlow<B>>(&self, f: &mut F) -> ControlFlow<B> {
+        if self.deleted {
+            return ControlFlow::Continue(());
+        }
+        f(&self.value)
+    }
+
+    /// If the value exists in this sub-tree, drop it, returning to the parent
+    /// a pointer to the Node that replaces this one, or None if this node
+    /// is removed by the change.  Called recursively.
+    /// 
+    /// Because 'self' is consumed, we need to return a node to replace it
+    /// even in case of error, hence we're returning a tuple of Result (to be interpreted)
+    /// and Option<Box<Node>> to replace the current node in the parent.
+    /// On success, the Result says whether the removed node was a tombstone.
+    /// 
+    pub fn drop_value(mut self, value: T) -> DropOutcome<T> {
+        #[cfg(feature = "metrics")]
+        metrics::visit();
+
+        // if the value is less than this node's value, and we have a left child, call 'drop_value' on the left child
+        if value < self.value {
+            match self.left {
+                None => return (Err(TreeError::ValueNotFound { value }), Some(Box::new(self))),
+                Some(left_child) => {
+                    match left_child.drop_value(value) {
+                        (Err(error), new_node) => {
+                            self.left = new_node;
+                            return (Err(error), Some(Box::new(self)));
+                        },
+                        (Ok(was_tombstone), new_node) => {
+                            self.left = new_node;
+                            return (Ok(was_tombstone), Some(Box::new(self)));
+                        } 
+                    }
+                }
+            }
+        }
+        // if the value is greater than this node's value, and we have a right child, call 'drop_value' on the right child
+        else if value > self.value {
+            match self.right {
+                None => return (Err(TreeError::ValueNotFound { value }), Some(Box::new(self))),
+                Some(right_child) => {
+                    match right_child.drop_value(value) {
+                        (Err(error), new_node) => {
+                            self.right = new_node;
+                            return (Err(error), Some(Box::new(self)));
+                        },
+                        (Ok(was_tombstone), new_node) => {
+                            self.right = new_node;
+                            return (Ok(was_tombstone), Some(Box::new(self)));
+                        } 
+                    }
+                }
+            }
+        }
+        // if this node has the exact value, take it out of the tree
+        else {
+            let was_tombstone = self.deleted;
+            return (Ok(was_tombstone), self.unlink());
+        }
+
+    }
+
+    /// Remove this node from its (sub)tree, returning the node that replaces it.
+    fn unlink(mut self) -> Option<Box<Node<T>>> {
+        // - if it has no children, just replace it with None
+        if self.is_leaf() {
+            return None;
+        }
+        // - if it has no left branch, replace it with its right child (and subtree)
+        if self.left.is_none() {
+            return self.right;
+        }
+        // - if it has no right branch, replace it with its left child (and subtree)
+        if self.right.is_none() {
+            return self.left;
+        }
+        // - if the root's right child is a leaf, replace its value with its right leaf (and drop that leaf)
+        if self.right.as_ref().unwrap().is_leaf() {
+            let right_child = self.right.take().unwrap();
+            self.value = right_child.value;
+            self.deleted = right_child.deleted;
+            return Some(Box::new(self));
+        }
+        // - otherwise, if the root's left child is a leaf, replace its value with its left leaf (and drop that leaf)
+        if self.left.as_ref().unwrap().is_leaf() {
+            let left_child = self.left.take().unwrap();
+            self.value = left_child.value;
+            self.deleted = left_child.deleted;
+            return Some(Box::new(self));
+        }
+        // - if we get to this point, both children are branches. Move the node holding the immediate successor
+        //   out of the right branch, and take its value (and tombstone flag)
+        let (new_right, successor_value, successor_deleted) = self.right.take().unwrap().take_least();
+        self.value = successor_value;
+        self.deleted = successor_deleted;
+        self.right = new_right;
+        return Some(Box::new(self));
+    }
+
+    /// Remove all the tombstones from this (sub)tree, whose root is at the given depth, returning the node that
+    /// replaces it and the number removed.
+    #[cfg_attr(not(feature = "metrics"), allow(clippy::only_used_in_recursion))]
+    fn purge(mut self: Box<Self>, depth: usize) -> (Option<Box<Node<T>>>, u32) {
+        #[cfg(feature = "metrics")]
+        metrics::reach(depth);
+        let mut removed = 0;
+        if let Some(left_child) = self.left.take() {
+            let (new_left, count) = left_child.purge(depth + 1);
+            self.left = new_left;
+            removed += count;
+        }
+        if let Some(right_child) = self.right.take() {
+            let (new_right, count) = right_child.purge(depth + 1);
+            self.right = new_right;
+            removed += count;
+        }
+        if !self.deleted {
+            return (Some(self), removed);
+        }
+        // the subtrees below hold no tombstones now, so no tombstone can move up into this node's place
+        return ((*self).unlink(), removed + 1);
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd + Clone> Node<T> {
+
+    /// Recursively add this (sub)tree's nodes to the graph, with edges from each node to its children.
+    /// Returns the index of this node in the graph.
+    #[cfg(feature = "petgraph")]
+    pub fn add_to_graph(&self, graph: &mut DiGraph<T, Branch>) -> NodeIndex {
+        let index = graph.add_node(self.value.clone());
+        match &self.left {
+            Some(node) => {
+                let child = node.add_to_graph(graph);
+                graph.add_edge(index, child, Branch::Left);
+            },
+            None => (),
+        }
+        match &self.right {
+            Some(node) => {
+                let child = node.add_to_graph(graph);
+                graph.add_edge(index, child, Branch::Right);
+            },
+            None => (),
+        }
+        index
+    }
+
+    /// Returns the smallest/lowest value in this (sub)tree that hasn't been deleted, if any.
+    pub fn least_live_value(&self) -> Option<T> {
+        if let Some(value) = self.left.as_ref().and_then(|left_child| left_child.least_live_value()) {
+            return Some(value);
+        }
+        if !self.deleted {
+            return Some(self.value.clone());
+        }
+        return self.right.as_ref().and_then(|right_child| right_child.least_live_value());
+    }
+
+    /// Returns the largest/highest value in this (sub)tree that hasn't been deleted, if any.
+    pub fn greatest_live_value(&self) -> Option<T> {
+        if let Some(value) = self.right.as_ref().and_then(|right_child| right_child.greatest_live_value()) {
+            return Some(value);
+        }
+        if !self.deleted {
+            return Some(self.value.clone());
+        }
+        return self.left.as_ref().and_then(|left_child| left_child.greatest_live_value());
+    }
+
+    /// Recursively add values to the borrowed vector, traversing the tree from left to right.
+    pub fn collect_values_l_to_r(&self, value_vector: &mut Vec<T>) {
+        match &self.left {
+            Some(node) => node.collect_values_l_to_r(value_vector),
+            None => (),
+        }
+        if !self.deleted {
+            value_vector.push(self.value.clone());
+        }
+        match &self.right {
+            Some(node) => node.collect_values_l_to_r(value_vector),
+            None => (),
+        }
+    }
+
+    /// Recursively add values to the borrowed vector, traversing the tree from left to right, until it holds `n` values.
+    pub fn collect_first_n(&self, n: usize, value_vector: &mut Vec<T>) {
+        if value_vector.len() >= n {
+            return;
+        }
+        match &self.left {
+            Some(node) => node.collect_first_n(n, value_vector),
+            None => (),
+        }
+        if value_vector.len() >= n {
+            return;
+        }
+        if !self.deleted {
+            value_vector.push(self.value.clone());
+        }
+        match &self.right {
+            Some(node) => node.collect_first_n(n, value_vector),
+            None => (),
+        }
+    }
+
+    /// Recursively add values to the borrowed vector, traversing the tree from right to left, until it holds `n` values.
+    pub fn collect_last_n(&self, n: usize, value_vector: &mut Vec<T>) {
+        if value_vector.len() >= n {
+            return;
+        }
+        match &self.right {
+            Some(node) => node.collect_last_n(n, value_vector),
+            None => (),
+        }
+        if value_vector.len() >= n {
+            return;
+        }
+        if !self.deleted {
+            value_vector.push(self.value.clone());
+        }
+        match &self.left {
+            Some(node) => node.collect_last_n(n, value_vector),
+            None => (),
+        }
+    }
+
+    /// Recursively add values to the borrowed vector, traversing the tree from right to left.
+    pub fn collect_values_r_to_l(&self, value_vector: &mut Vec<T>) {
+        match &self.right {
+            Some(node) => node.collect_values_r_to_l(value_vector),
+            None => (),
+        }
+        if !self.deleted {
+            value_vector.push(self.value.clone());
+        }
+        match &self.left {
+            Some(node) => node.collect_values_r_to_l(value_vector),
+            None => (),
+        }
+    }
+
+}
+
+/// Visits the nodes of a tree in order, from least to greatest value, along with their depths, from either end.
+/// It keeps a queue of the nodes still to come, in order, so it only holds about one path's worth of nodes for
+/// each end at a time.  Each one queued stands for itself along with its left and right subtrees, unless the
+/// nodes of a subtree have been queued separately.
+struct Nodes<'a, T: PartialEq + PartialOrd> {
+    queue: VecDeque<(&'a Node<T>, usize, bool, bool)>, // a node, its depth, and whether its left and right subtrees come with it
+}
+
+impl <'a, T: PartialEq + PartialOrd> Nodes<'a, T> {
+
+    fn new(root: &'a Option<Box<Node<T>>>) -> Self {
+        Self { queue: root.iter().map(|node| (&**node, 0, true, true)).collect() }
+    }
+
+    /// Queue just the nodes whose values are within the range, for `range`.
+    fn within<R: RangeBounds<T>>(root: &'a Option<Box<Node<T>>>, range: &R) -> Self {
+        Self { queue: queue_within(root.as_deref(), range) }
+    }
+
+}
+
+impl <'a, T: PartialEq + PartialOrd> Iterator for Nodes<'a, T> {
+    type Item = (&'a Node<T>, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (node, depth, with_left, with_right) = self.queue.pop_front()?;
+            if let (true, Some(left)) = (with_left, &node.left) {
+                // the left subtree comes first
+                self.queue.push_front((node, depth, false, with_right));
+                self.queue.push_front((left, depth + 1, true, true));
+                continue;
+            }
+            if let (true, Some(right)) = (with_right, &node.right) {
+                self.queue.push_front((right, depth + 1, true, true));
+            }
+            return Some((node, depth));
+        }
+    }
+}
+
+impl <'a, T: PartialEq + PartialOrd> DoubleEndedIterator for Nodes<'a, T> {
+
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            let (node, depth, with_left, with_right) = self.queue.pop_back()?;
+            if let (true, Some(right)) = (with_right, &node.right) {
+                // the right subtree comes last
+                self.queue.push_back((node, depth, with_left, false));
+                self.queue.push_back((right, depth + 1, true, true));
+                continue;
+            }
+            if let (true, Some(left)) = (with_left, &node.left) {
+                self.queue.push_back((left, depth + 1, true, true));
+            }
+            return Some((node, depth));
+        }
+    }
+}
+
+/// An iterator over a Jbst's values in order, skipping tombstones, from least to greatest or (with `rev` or
+/// `next_back`) from greatest to least, or from both ends at once.  Get one with `iter`.
+pub struct Iter<'a, T: PartialEq + PartialOrd> {
+    nodes: Nodes<'a, T>,
+}
+
+impl <'a, T: PartialEq + PartialOrd> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.nodes.find(|(node, _)| !node.deleted).map(|(node, _)| &node.value)
+    }
+}
+
+impl <'a, T: PartialEq + PartialOrd> DoubleEndedIterator for Iter<'a, T> {
+
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.nodes.rfind(|(node, _)| !node.deleted).map(|(node, _)| &node.value)
+    }
+}
+
+impl <'a, T: PartialEq + PartialOrd> IntoIterator for &'a Jbst<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator that moves the values out of a Jbst in order (from either end), taking the tree apart as it
+/// goes (tombstones are discarded).  Made by `into_iter`, as in `for value in tree`.
+pub struct IntoIter<T: PartialEq + PartialOrd> {
+    drain: Drain<T>,
+}
+
+impl <T: PartialEq + PartialOrd> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.drain.next()
+    }
+}
+
+impl <T: PartialEq + PartialOrd> DoubleEndedIterator for IntoIter<T> {
+
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.drain.next_back()
+    }
+}
+
+impl <T: PartialEq + PartialOrd> IntoIterator for Jbst<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        IntoIter { drain: self.take_nodes() }
+    }
+}
+
+/// Takes the values out of a tree's nodes in order, from either end, for `drain` and `into_iter`.  Like `Nodes`, it
+/// keeps a queue of the nodes still to come, each standing for itself and the children it still holds; children
+/// are taken from their parent as they're queued.
+struct Drain<T: PartialEq + PartialOrd> {
+    queue: VecDeque<Box<Node<T>>>,
+}
+
+impl <T: PartialEq + PartialOrd> Drain<T> {
+
+    fn new(root: Option<Box<Node<T>>>) -> Self {
+        Self { queue: root.into_iter().collect() }
+    }
+
+    /// Take the least node still to come out of the queue.
+    fn pop_least(&mut self) -> Option<Box<Node<T>>> {
+        loop {
+            let mut node = self.queue.pop_front()?;
+            if let Some(left) = node.left.take() {
+                self.queue.push_front(node);
+                self.queue.push_front(left);
+                continue;
+            }
+            if let Some(right) = node.right.take() {
+                self.queue.push_front(right);
+            }
+            return Some(node);
+        }
+    }
+
+    /// Take the greatest node still to come out of the queue.
+    fn pop_greatest(&mut self) -> Option<Box<Node<T>>> {
+        loop {
+            let mut node = self.queue.pop_back()?;
+            if let Some(right) = node.right.take() {
+                self.queue.push_back(node);
+                self.queue.push_back(right);
+                continue;
+            }
+            if let Some(left) = node.left.take() {
+                self.queue.push_back(left);
+            }
+            return Some(node);
+        }
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd> Iterator for Drain<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let node = self.pop_least()?;
+            if !node.deleted {
+                return Some(node.value);
+            }
+        }
+    }
+}
+
+impl <T: PartialEq + PartialOrd> DoubleEndedIterator for Drain<T> {
+
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            let node = self.pop_greatest()?;
+            if !node.deleted {
+                return Some(node.value);
+            }
+        }
+    }
+}
+
+/// A position among a Jbst's values, which can step to the next or previous value in amortized O(1) time.
+/// Get one with `cursor_at`, `cursor_front` or `cursor_back`.  Besides the values, there's a "ghost" position
+/// past both ends, where `peek` returns None: stepping back from it goes to the greatest value.  As an `Iterator`,
+/// the cursor yields the values after its position, and stops for good at the ghost position.
+/// Tombstones are skipped.
+pub struct Cursor<'a, T: PartialEq + PartialOrd> {
+    root: &'a Option<Box<Node<T>>>,
+    path: Vec<&'a Node<T>>, // from the root down to the current node; empty at the ghost position
+}
+
+impl <'a, T: PartialEq + PartialOrd> Cursor<'a, T> {
+
+    fn new(root: &'a Option<Box<Node<T>>>) -> Self {
+        Self { root, path: Vec::new() }
+    }
+
+    /// A cursor positioned at the least value (or the ghost position, if there are none).
+    fn front(root: &'a Option<Box<Node<T>>>) -> Self {
+        let mut cursor = Self::new(root);
+        cursor.push_left_edge(root);
+        cursor.settle_forward();
+        cursor
+    }
+
+    /// Returns the value at the cursor's position, without moving.
+    pub fn peek(&self) -> Option<&'a T> {
+        return match self.path.last() {
+            None => None,
+            Some(node) => Some(&node.value),
+        };
+    }
+
+    /// Move to the previous value and return it, or None on reaching the ghost position.
+    pub fn prev(&mut self) -> Option<&'a T> {
+        self.step_back();
+        while self.path.last().is_some_and(|node| node.deleted) {
+            self.step_back();
+        }
+        self.peek()
+    }
+
+    /// If the cursor is on a tombstone, move on to the next live value.
+    fn settle_forward(&mut self) {
+        while self.path.last().is_some_and(|node| node.deleted) {
+            self.step_forward();
+        }
+    }
+
+    /// Position the cursor on the least node whose value is at least `value`, or the ghost position if there's none.
+    fn seek(&mut self, value: &T) {
+        let mut link = self.root;
+        let mut found = 0;
+        while let Some(node) = link {
+            self.path.push(node);
+            if *value == node.value {
+                found = self.path.len();
+                break;
+            }
+            if *value < node.value {
+                found = self.path.len();
+                link = &node.left;
+            } else {
+                link = &node.right;
+            }
+        }
+        self.path.truncate(found);
+    }
+
+    /// Move to the next node in order (whether or not it's a tombstone), or from the last node to the ghost
+    /// position, or from the ghost position to the first node.
+    fn step_forward(&mut self) {
+        match self.path.last().copied() {
+            None => self.push_left_edge(self.root),
+            Some(node) if node.right.is_some() => self.push_left_edge(&node.right),
+            Some(_) => {
+                // climb until we come up out of a left subtree; that parent is next
+                let mut child = self.path.pop();
+                while let (Some(parent), Some(from)) = (self.path.last(), child) {
+                    if parent.left.as_deref().is_some_and(|left| std::ptr::eq(left, from)) {
+                        return;
+                    }
+                    child = self.path.pop();
+                }
+            },
+        }
+    }
+
+    /// Move to the previous node in order, or from the first node to the ghost position, or from the ghost
+    /// position to the last node.
+    fn step_back(&mut self) {
+        match self.path.last().copied() {
+            None => self.push_right_edge(self.root),
+            Some(node) if node.left.is_some() => self.push_right_edge(&node.left),
+            Some(_) => {
+                // climb until we come up out of a right subtree; that parent is previous
+                let mut child = self.path.pop();
+                while let (Some(parent), Some(from)) = (self.path.last(), child) {
+                    if parent.right.as_deref().is_some_and(|right| std::ptr::eq(right, from)) {
+                        return;
+                    }
+                    child = self.path.pop();
+                }
+            },
+        }
+    }
+
+    /// Descend from the node to the least node in its subtree.
+    fn push_left_edge(&mut self, mut link: &'a Option<Box<Node<T>>>) {
+        while let Some(node) = link {
+            self.path.push(node);
+            link = &node.left;
+        }
+    }
+
+    /// Descend from the node to the greatest node in its subtree.
+    fn push_right_edge(&mut self, mut link: &'a Option<Box<Node<T>>>) {
+        while let Some(node) = link {
+            self.path.push(node);
+            link = &node.right;
+        }
+    }
+
+}
+
+impl <'a, T: PartialEq + PartialOrd> Iterator for Cursor<'a, T> {
+    type Item = &'a T;
+
+    /// Move to the next value and return it, or None on reaching (or being at) the ghost position.
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.path.is_empty() {
+            return None;
+        }
+        self.step_forward();
+        self.settle_forward();
+        self.peek()
+    }
+}
+
+impl <'a, T: PartialEq + PartialOrd> FusedIterator for Cursor<'a, T> {}
+
+/// A cursor like `Cursor` that can also remove the value at its position, for editing a Jbst in one pass.
+/// Get one with `cursor_mut_at`, `cursor_mut_front` or `cursor_mut_back`.  Because it borrows the tree mutably
+/// while changing its structure, it remembers its position by value, so each step is a search from the root.
+///
+///     use jtree::Jbst;
+///
+///     let mut my_tree = Jbst::from_collection(1..=10);
+///     let mut cursor = my_tree.cursor_mut_front();
+///     while let Some(value) = cursor.peek() {
+///         if value % 3 == 0 {
+///             cursor.remove_current();
+///         } else {
+///             cursor.move_next();
+///         }
+///     }
+///     assert_eq!( vec!(1,2,4,5,7,8,10), my_tree.as_vec() );
+pub struct CursorMut<'a, T: PartialEq + PartialOrd + Clone> {
+    tree: &'a mut Jbst<T>,
+    current: Option<T>, // the value at the cursor's position, or None at the ghost position
+}
+
+impl <'a, T: PartialEq + PartialOrd + Clone> CursorMut<'a, T> {
 
-impl <T: PartialEq + PartialOrd + Clone + std::fmt::Debug> fmt::Debug for Jbst<T> {
-    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
-        fmt.debug_struct("Jbst")
-            .field("size", &self.get_size())
-            .field("values", &self.as_vec())
-            .finish()
+    fn new(tree: &'a mut Jbst<T>, current: Option<T>) -> Self {
+        Self { tree, current }
     }
-}
 
-struct Node<T: PartialEq + PartialOrd + Clone> {
-    value: T,
-    left: Option<Box<Node<T>>>,
-    right: Option<Box<Node<T>>>,
-}
+    /// Returns the value at the cursor's position, without moving.
+    pub fn peek(&self) -> Option<&T> {
+        self.current.as_ref()
+    }
 
-impl <T:PartialEq + PartialOrd + Clone> Node<T> {
+    /// Move to the next value and return it, or None on reaching the ghost position.
+    pub fn move_next(&mut self) -> Option<&T> {
+        self.current = self.value_after().cloned();
+        self.current.as_ref()
+    }
 
-    pub fn new(value: T) -> Self {
-        Self {
-            value,
-            left: None,
-            right: None,
-        }
+    /// Move to the previous value and return it, or None on reaching the ghost position.
+    pub fn move_prev(&mut self) -> Option<&T> {
+        self.current = self.value_before().cloned();
+        self.current.as_ref()
     }
 
-    /// Insert a value
-    pub fn add(&mut self, value: T) -> Result<(),TreeError> {
-        if value == self.value {
-            // no duplicates allowed in this kind of tree
-            return Err(TreeError::ValueAlreadyStored)
-        }
-        if value < self.value {
-            // add to the left branch
-            match &mut self.left {
-                None => self.left = Some(Box::new(Node::new(value))),
-                Some(branch) => branch.add(value)?,
-            }
-            return Ok(())
-        } else {
-            // add it to the right branch
-            match &mut self.right {
-                None => self.right = Some(Box::new(Node::new(value))),
-                Some(branch) => branch.add(value)?,
-            }
-            return Ok(())
-        }
+    /// Remove the value at the cursor's position and return it, moving the cursor on to the next value.
+    /// At the ghost position, nothing is removed and None is returned.
+    pub fn remove_current(&mut self) -> Option<T> {
+        let removed = self.current.take()?;
+        self.current = self.successor_of(&removed).cloned();
+        let _ = self.tree.drop_value(removed.clone());
+        Some(removed)
     }
 
-    /// Returns true if the value is currently a member of the (sub)tree
-    pub fn contains(&self, value: &T) -> bool {
-        if *value == self.value {
-            return true;
-        }
-        if *value < self.value {
-            match &self.left {
-                Some(node) => node.contains(value),
-                None => return false
-            }
-        } else {
-            match &self.right {
-                Some(node) => node.contains(value),
-                None => return false
-            }
-        }
+    /// The value after the cursor's position.
+    fn value_after(&self) -> Option<&T> {
+        return match &self.current {
+            None => Cursor::front(&self.tree.root).peek(),
+            Some(value) => self.successor_of(value),
+        };
     }
 
-    /// Returns true if the node is a leaf or terminal node, with no child nodes of its own.
-    pub fn is_leaf(&self) -> bool {
-        self.left.is_none() && self.right.is_none()
+    /// The value after the given one, which is in the tree.
+    fn successor_of(&self, value: &T) -> Option<&T> {
+        let mut cursor = Cursor::new(&self.tree.root);
+        cursor.seek(value);
+        cursor.next()
     }
 
-    /// Returns the smallest/lowest value in this (sub)tree.
-    pub fn least_value(&self) -> T {
-        return match &self.left {
-            None => self.value.clone(),
-            Some(left_child) => left_child.least_value(),
+    /// The value before the cursor's position.
+    fn value_before(&self) -> Option<&T> {
+        let mut cursor = Cursor::new(&self.tree.root);
+        match &self.current {
+            None => (),
+            Some(value) => cursor.seek(value),
         }
+        cursor.prev()
     }
 
-    /// Returns the largest/highest value in this (sub)tree.
-    pub fn greatest_value(&self) -> T {
-        return match &self.right {
-            None => self.value.clone(),
-            Some(right_child) => right_child.greatest_value(),
+}
+
+impl <T: PartialEq + PartialOrd + Hash> Node<T> {
+
+    /// Recursively feed this (sub)tree's values to the hasher, from left to right.
+    fn hash_values<H: Hasher>(&self, hasher: &mut H) {
+        match &self.left {
+            Some(node) => node.hash_values(hasher),
+            None => (),
+        }
+        if !self.deleted {
+            self.value.hash(hasher);
+        }
+        match &self.right {
+            Some(node) => node.hash_values(hasher),
+            None => (),
         }
     }
 
-    /// Recursively add values to the borrowed vector, traversing the tree from left to right.
-    pub fn collect_values_l_to_r(&self, value_vector: &mut Vec<T>) {
+}
+
+impl <T: PartialEq + PartialOrd + Codec> Node<T> {
+
+    /// Recursively encode this (sub)tree's node records, in pre-order.
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        let mut flags = 0;
+        if self.deleted {
+            flags |= DELETED;
+        }
+        if self.left.is_some() {
+            flags |= HAS_LEFT;
+        }
+        if self.right.is_some() {
+            flags |= HAS_RIGHT;
+        }
+        flags.encode(bytes);
+        self.value.encode(bytes);
         match &self.left {
-            Some(node) => node.collect_values_l_to_r(value_vector),
+            Some(node) => node.encode(bytes),
             None => (),
         }
-        value_vector.push(self.value.clone());
         match &self.right {
-            Some(node) => node.collect_values_l_to_r(value_vector),
+            Some(node) => node.encode(bytes),
             None => (),
         }
     }
 
-    /// Recursively add values to the borrowed vector, traversing the tree from right to left.
-    pub fn collect_values_r_to_l(&self, value_vector: &mut Vec<T>) {
+    /// Recursively decode a (sub)tree encoded by `encode`, whose root is at the given depth, refusing to
+    /// go deeper than `depth_limit`.
+    fn decode(reader: &mut Reader, depth: usize, depth_limit: usize) -> Result<Box<Node<T>>,TreeError<T>> {
+        let offset = reader.offset();
+        if depth > depth_limit {
+            return Err(TreeError::DeserializationCorrupt { offset, reason: format!("the tree is deeper than its depth limit ({depth_limit})") });
+        }
+        let flags: u8 = reader.read()?;
+        if flags & !(HAS_LEFT | HAS_RIGHT | DELETED) != 0 {
+            return Err(TreeError::DeserializationCorrupt { offset, reason: format!("{flags:#x} isn't a valid node record") });
+        }
+        let mut node = Node::new(reader.read()?);
+        node.deleted = flags & DELETED != 0;
+        if flags & HAS_LEFT != 0 {
+            node.left = Some(Node::decode(reader, depth + 1, depth_limit)?);
+        }
+        if flags & HAS_RIGHT != 0 {
+            node.right = Some(Node::decode(reader, depth + 1, depth_limit)?);
+        }
+        Ok(Box::new(node))
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd + std::fmt::Debug> Node<T> {
+
+    /// Recursively draw this (sub)tree for `to_ascii`: first the right subtree, then this node, then the left subtree.
+    /// The `prefix` holds the indentation and `|` lines carried down from ancestors, and the `link` is the
+    /// branch leading up (`/-- `) or down (`\-- `) to this node from its parent, or empty for the root.
+    fn draw(&self, drawing: &mut String, prefix: &str, link: &str) {
+        let (above, below) = match link {
+            "" => (String::from(prefix), String::from(prefix)),
+            "/-- " => (format!("{prefix}    "), format!("{prefix}|   ")),
+            _ => (format!("{prefix}|   "), format!("{prefix}    ")),
+        };
         match &self.right {
-            Some(node) => node.collect_values_r_to_l(value_vector),
+            Some(node) => node.draw(drawing, &above, "/-- "),
             None => (),
         }
-        value_vector.push(self.value.clone());
+        drawing.push_str(&format!("{prefix}{link}{}\n", self.label()));
         match &self.left {
-            Some(node) => node.collect_values_r_to_l(value_vector),
+            Some(node) => node.draw(drawing, &below, "\\-- "),
             None => (),
         }
     }
 
-    /// If the value exists in this sub-tree, drop it, returning to the parent
-    /// a pointer to the Node that replaces this one, or None if this node
-    /// is removed by the change.  Called recursively.
-    /// 
-    /// Because 'self' is consumed, we need to return a node to replace it
-    /// even in case of error, hence we're returning a tuple of Result (to be interpreted)
-    /// and Option<Box<Node>> to replace the current node in the parent.
-    /// 
-    pub fn drop_value(mut self, value: T) -> (Result<(),TreeError>, Option<Box<Node<T>>>) {
-
-        // if the value is less than this node's value, and we have a left child, call 'drop_value' on the left child
-        if value < self.value {
-            match self.left {
-                None => return (Err(TreeError::ValueNotFound), Some(Box::new(self))),
-                Some(left_child) => {
-                    match left_child.drop_value(value) {
-                        (Err(_), new_node) => {
-                            self.left = new_node;
-                            return (Err(TreeError::ValueNotFound), Some(Box::new(self)));
-                        },
-                        (Ok(_), new_node) => {
-                            self.left = new_node;
-                            return (Ok(()), Some(Box::new(self)));
-                        } 
-                    }
-                }
-            }
-        }
-        // if the value is greater than this node's value, and we have a right child, call 'drop_value' on the right child
-        else if value > self.value {
-            match self.right {
-                None => return (Err(TreeError::ValueNotFound), Some(Box::new(self))),
-                Some(right_child) => {
-                    match right_child.drop_value(value) {
-                        (Err(_), new_node) => {
-                            self.right = new_node;
-                            return (Err(TreeError::ValueNotFound), Some(Box::new(self)));
-                        },
-                        (Ok(_), new_node) => {
-                            self.right = new_node;
-                            return (Ok(()), Some(Box::new(self)));
-                        } 
-                    }
-                }
-            }
-        }
-        // if this node has the exact value:
-        else {
-            // - if it has no children, just replace it with None
-            if self.is_leaf() {
-                return (Ok(()), None);
-            }
-            // - if it has no left branch, replace it with its right child (and subtree)
-            if self.left.is_none() {
-                return (Ok(()), self.right);
-            }
-            // - if it has no right branch, replace it with its left child (and subtree)
-            if self.right.is_none() {
-                return (Ok(()), self.left);
-            }
-            // - if the root's right child is a leaf, replace its value with its right leaf (and drop that leaf)
-            let right_child = self.right.as_ref().unwrap();
-            if right_child.is_leaf() {
-                self.value = right_child.value.clone();
-                self.right = None;
-                return (Ok(()), Some(Box::new(self)));
-            }
-            // - otherwise, if the root's left child is a leaf, replace its value with its left leaf (and drop that leaf)
-            let left_child = self.left.as_ref().unwrap();
-            if left_child.is_leaf() {
-                self.value = left_child.value.clone();
-                self.left = None;
-                return (Ok(()), Some(Box::new(self)));
-            }
-            // - if we get to this point, both children are branches. Replace the root's value with its immediate successor, 
-            //   then recursively tell its right branch to remove that successor
-            self.value = right_child.least_value();
-            self.right = self.right.unwrap().drop_value(self.value.clone()).1;
-            return (Ok(()), Some(Box::new(self)));
+    /// How this node is shown by `to_ascii`: its value, and whether it's a tombstone.
+    fn label(&self) -> String {
+        if self.deleted {
+            return format!("{:?} (deleted)", self.value);
         }
-
+        format!("{:?}", self.value)
     }
 
 }
@@ -384,7 +2008,7 @@ mod tests {
         assert_eq!( Ok(()), my_tree.add(7) );
         assert_eq!( 3, my_tree.get_size() );
         assert_eq!(
-            Err(TreeError::ValueAlreadyStored),
+            Err(TreeError::ValueAlreadyStored { value: 7 }),
             my_tree.add(7) // can't add duplicates
         );
     }
@@ -392,17 +2016,17 @@ mod tests {
     #[test]
     fn add_collection() {
         let mut my_tree = Jbst::new();
-        assert_eq!( Ok(()), my_tree.add_all_skipping_duplicates(vec!(1,2,3,4,5)));
-        assert_eq!( Ok(()), my_tree.add_all([6,7,8,9,10])); // alias for add_all_skipping_duplicates
+        assert_eq!( Ok(AddReport { inserted: 5, skipped: 0 }), my_tree.add_all_skipping_duplicates(vec!(1,2,3,4,5)));
+        assert_eq!( Ok(AddReport { inserted: 5, skipped: 0 }), my_tree.add_all([6,7,8,9,10])); // alias for add_all_skipping_duplicates
         assert_eq!( 10, my_tree.get_size() );
-        assert_eq!( Ok(()), my_tree.add_all_skipping_duplicates([5,10,15,20])); // duplicates should NOT cause a panic
+        assert_eq!( Ok(AddReport { inserted: 2, skipped: 2 }), my_tree.add_all_skipping_duplicates([5,10,15,20])); // duplicates should NOT cause a panic
         assert_eq!( 12, my_tree.get_size() ); // duplicates were skipped
     }
 
     #[test]
     fn test_contains() {
         let mut my_tree = Jbst::new();
-        assert_eq!( Ok(()), my_tree.add_all_skipping_duplicates(vec!(8,6,7,5,3,0,9)));
+        assert_eq!( Ok(AddReport { inserted: 7, skipped: 0 }), my_tree.add_all_skipping_duplicates(vec!(8,6,7,5,3,0,9)));
         assert_eq!( 7, my_tree.get_size() );
         assert!( my_tree.contains(&7) );
         assert!( my_tree.contains(&8) );
@@ -436,13 +2060,13 @@ mod tests {
         // an empty tree
         let mut my_tree = Jbst::new();
         assert_eq!( 0, my_tree.get_size() );
-        assert_eq!( Err(TreeError::ValueNotFound), my_tree.drop_value(1) );
+        assert_eq!( Err(TreeError::ValueNotFound { value: 1 }), my_tree.drop_value(1) );
 
         // a tree with only a root node
         let mut my_tree = Jbst::new();
         let _ = my_tree.add(1);
         assert_eq!( 1, my_tree.get_size() );
-        assert_eq!( Err(TreeError::ValueNotFound), my_tree.drop_value(4) );
+        assert_eq!( Err(TreeError::ValueNotFound { value: 4 }), my_tree.drop_value(4) );
         assert_eq!( Ok(()), my_tree.drop_value(1) );
         assert_eq!( 0, my_tree.get_size() );
 
@@ -451,7 +2075,7 @@ mod tests {
         let _ = my_tree.add_all_skipping_duplicates(['A','B','C']);
         assert_eq!( Some('A'), my_tree.get_root_value() ); // root is 1
         assert_eq!( 3, my_tree.get_size() );
-        assert_eq!( Err(TreeError::ValueNotFound), my_tree.drop_value('Z') );
+        assert_eq!( Err(TreeError::ValueNotFound { value: 'Z' }), my_tree.drop_value('Z') );
         assert_eq!( Ok(()), my_tree.drop_value('A') );
         assert_eq!( vec!('B','C'), my_tree.as_vec_l_to_r() );
         assert_eq!( 2, my_tree.get_size() );
@@ -461,7 +2085,7 @@ mod tests {
         let _ = my_tree.add_all_skipping_duplicates([3,1,2]);
         assert_eq!( Some(3), my_tree.get_root_value() ); // root is 3
         assert_eq!( 3, my_tree.get_size() );
-        assert_eq!( Err(TreeError::ValueNotFound), my_tree.drop_value(4) );
+        assert_eq!( Err(TreeError::ValueNotFound { value: 4 }), my_tree.drop_value(4) );
         assert_eq!( Ok(()), my_tree.drop_value(3) );
         assert_eq!( vec!(1,2), my_tree.as_vec_l_to_r() );
         assert_eq!( 2, my_tree.get_size() );
@@ -471,7 +2095,7 @@ mod tests {
         let _ = my_tree.add_all_skipping_duplicates([2,1,3]);
         assert_eq!( Some(2), my_tree.get_root_value() ); // root is 2
         assert_eq!( 3, my_tree.get_size() );
-        assert_eq!( Err(TreeError::ValueNotFound), my_tree.drop_value(4) );
+        assert_eq!( Err(TreeError::ValueNotFound { value: 4 }), my_tree.drop_value(4) );
         assert_eq!( Ok(()), my_tree.drop_value(2) );
         assert_eq!( vec!(1,3), my_tree.as_vec_l_to_r() );
         assert_eq!( 2, my_tree.get_size() );
@@ -481,7 +2105,7 @@ mod tests {
         let _ = my_tree.add_all_skipping_duplicates([2,1,5,3,7]);
         assert_eq!( Some(2), my_tree.get_root_value() ); // root is 2
         assert_eq!( 5, my_tree.get_size() );
-        assert_eq!( Err(TreeError::ValueNotFound), my_tree.drop_value(4) );
+        assert_eq!( Err(TreeError::ValueNotFound { value: 4 }), my_tree.drop_value(4) );
         assert_eq!( Ok(()), my_tree.drop_value(2) );
         assert_eq!( vec!(1,3,5,7), my_tree.as_vec_l_to_r() );
         assert_eq!( 4, my_tree.get_size() );
@@ -491,7 +2115,7 @@ mod tests {
         let _ = my_tree.add_all_skipping_duplicates([5,3,8,1,2,7,9]);
         assert_eq!( Some(5), my_tree.get_root_value() ); // root is 5
         assert_eq!( 7, my_tree.get_size() );
-        assert_eq!( Err(TreeError::ValueNotFound), my_tree.drop_value(4) );
+        assert_eq!( Err(TreeError::ValueNotFound { value: 4 }), my_tree.drop_value(4) );
         assert_eq!( Ok(()), my_tree.drop_value(5) );
         assert_eq!( Some(7), my_tree.get_root_value() ); // root is now 7
         assert_eq!( vec!(1,2,3,7,8,9), my_tree.as_vec_l_to_r() );
@@ -509,4 +2133,534 @@ mod tests {
         assert_eq!( Some(9), my_tree.greatest_value() );
     }
 
+    #[test]
+    fn test_lazy_deletion() {
+        let mut my_tree = Jbst::with_lazy_deletion(2);
+        let _ = my_tree.add_all_skipping_duplicates([5,3,8,1,4,7,9]);
+        assert_eq!( Ok(()), my_tree.drop_value(5) );
+        assert_eq!( Some(5), my_tree.get_root_value() ); // the node is still there...
+        assert!( !my_tree.contains(&5) ); // ...but its value is gone
+        assert_eq!( Err(TreeError::ValueNotFound { value: 5 }), my_tree.drop_value(5) );
+        assert_eq!( 6, my_tree.get_size() );
+        assert_eq!( 1, my_tree.get_tombstone_count() );
+
+        // the least and greatest values skip over tombstones
+        assert_eq!( Ok(()), my_tree.drop_value(1) );
+        assert_eq!( Some(3), my_tree.least_value() );
+        assert_eq!( vec!(3,4,7,8,9), my_tree.as_vec() );
+
+        // re-adding a deleted value revives its node
+        assert_eq!( Ok(()), my_tree.add(1) );
+        assert_eq!( 1, my_tree.get_tombstone_count() );
+        assert_eq!( 6, my_tree.get_size() );
+
+        // going over the limit triggers a purge
+        assert_eq!( Ok(()), my_tree.drop_value(9) );
+        assert_eq!( 2, my_tree.get_tombstone_count() );
+        assert_eq!( Ok(()), my_tree.drop_value(8) );
+        assert_eq!( 0, my_tree.get_tombstone_count() );
+        assert_eq!( Some(7), my_tree.get_root_value() ); // root was physically replaced by its successor
+        assert_eq!( vec!(1,3,4,7), my_tree.as_vec() );
+        assert_eq!( 4, my_tree.get_size() );
+    }
+
+    #[test]
+    fn test_try_add_all() {
+        let mut my_tree = Jbst::from_collection([5,3,8]);
+        assert_eq!( Ok(()), my_tree.try_add_all([1,9]) );
+        assert_eq!( vec!(1,3,5,8,9), my_tree.as_vec() );
+        assert_eq!( Err(TreeError::DuplicateAt { index: 2, value: 8 }), my_tree.try_add_all([4,7,8,2]) ); // 8 is already stored
+        assert_eq!( Err(TreeError::DuplicateAt { index: 2, value: 6 }), my_tree.try_add_all([6,0,6]) ); // duplicates within the batch count too
+        assert_eq!( vec!(1,3,5,8,9), my_tree.as_vec() ); // the failed batches were rolled back
+        assert_eq!( 5, my_tree.get_size() );
+        assert_eq!( Some(5), my_tree.get_root_value() );
+    }
+
+    #[test]
+    fn test_add_all_strict() {
+        let mut my_tree = Jbst::from_collection([5,3,8]);
+        assert_eq!( Ok(()), my_tree.add_all_strict([1,9]) );
+        assert_eq!( Err(TreeError::DuplicateAt { index: 2, value: 8 }), my_tree.add_all_strict([4,7,8,2]) ); // 8 is already stored
+        assert_eq!( vec!(1,3,4,5,7,8,9), my_tree.as_vec() ); // 4 and 7 stay, and 2 was never reached
+        assert_eq!( Err(TreeError::DuplicateAt { index: 2, value: 6 }), my_tree.add_all_strict([6,0,6]) ); // duplicates within the batch count too
+        assert_eq!( vec!(0,1,3,4,5,6,7,8,9), my_tree.as_vec() );
+        assert_eq!( 9, my_tree.get_size() );
+    }
+
+    #[test]
+    fn test_check_invariants() {
+        let mut my_tree = Jbst::new();
+        assert_eq!( Ok(()), my_tree.check_invariants() );
+        // a pseudo-random mix of adds and drops, checking the invariants after every change
+        let mut state: u32 = 12345;
+        for _ in 0..2000 {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let value = state % 64;
+            if state.is_multiple_of(3) {
+                let _ = my_tree.drop_value(value);
+            } else {
+                let _ = my_tree.add(value);
+            }
+            assert_eq!( Ok(()), my_tree.check_invariants() );
+        }
+
+        // break the ordering on purpose
+        let mut my_tree = Jbst::from_collection([2,1,3]);
+        my_tree.root.as_mut().unwrap().value = 0;
+        assert_eq!( Err(TreeError::InvariantViolation("value at root.left is out of order".to_string())), my_tree.check_invariants() );
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut my_tree = Jbst::<u32>::with_lazy_deletion(10);
+        assert_eq!( TreeStats::default(), my_tree.stats() );
+        let _ = my_tree.add_all([4,2,1,3,5,6]);
+        let _ = my_tree.drop_value(6);
+        // the tombstone for 6 is still a leaf, at depth 2 like 1 and 3
+        let stats = my_tree.stats();
+        assert_eq!( 6, stats.node_count );
+        assert_eq!( 3, stats.height );
+        assert_eq!( 3, stats.leaf_count );
+        assert_eq!( 3, stats.internal_count );
+        assert_eq!( 2, stats.min_leaf_depth );
+        assert_eq!( 2, stats.max_leaf_depth );
+        assert_eq!( None, stats.rotations );
+        my_tree.purge();
+        let stats = my_tree.stats();
+        assert_eq!( 5, stats.node_count );
+        assert_eq!( 1, stats.min_leaf_depth );
+        assert_eq!( 2, stats.max_leaf_depth );
+        assert_eq!( 5.0 / 3.0, stats.avg_leaf_depth );
+    }
+
+    #[test]
+    fn test_to_ascii() {
+        let mut my_tree = Jbst::<u32>::with_lazy_deletion(10);
+        assert_eq!( "", my_tree.to_ascii() );
+        let _ = my_tree.add_all([2,1,3]);
+        let _ = my_tree.drop_value(3);
+        assert_eq!( "/-- 3 (deleted)\n2\n\\-- 1\n", my_tree.to_ascii() );
+    }
+
+    #[test]
+    fn test_to_and_from_bytes() {
+        let mut my_tree = Jbst::<u32>::with_lazy_deletion(5);
+        let _ = my_tree.add_all([4,2,6,1,3]);
+        let _ = my_tree.drop_value(2);
+        let copy = Jbst::<u32>::from_bytes(&my_tree.to_bytes()).unwrap();
+        assert_eq!( my_tree.to_ascii(), copy.to_ascii() );
+        assert_eq!( 1, copy.get_tombstone_count() );
+        assert_eq!( Some(5), copy.tombstone_limit );
+        let empty = Jbst::<String>::from_bytes(&Jbst::<String>::new().to_bytes()).unwrap();
+        assert_eq!( 0, empty.get_size() );
+
+        // data that decodes to an out-of-order tree is rejected
+        let mut bytes = Jbst::from_collection([2u32,1]).to_bytes();
+        let last = bytes.len() - 4;
+        bytes[last] = 7;
+        assert!( matches!( Jbst::<u32>::from_bytes(&bytes), Err(TreeError::DeserializationCorrupt { offset: 7, .. }) ) );
+        // as is data for another kind of tree
+        assert_eq!( Err(TreeError::DeserializationCorrupt { offset: 6, reason: String::from("this is a 'A' tree, not a Jbst") }),
+            Jbst::<u32>::from_bytes(&crate::Javlt::from_collection([1u32]).to_bytes()).map(|tree| tree.get_size()) );
+    }
+
+    #[test]
+    fn test_digest() {
+        use std::hash::DefaultHasher;
+        let my_tree = Jbst::from_collection([2,1,3]);
+        let same_values = Jbst::from_collection([1,2,3]); // a different shape
+        assert_eq!( my_tree.digest::<DefaultHasher>(), same_values.digest::<DefaultHasher>() );
+        assert_ne!( my_tree.digest::<DefaultHasher>(), Jbst::from_collection([1,2]).digest::<DefaultHasher>() );
+        let mut lazy_tree = Jbst::with_lazy_deletion(5);
+        let _ = lazy_tree.add_all([2,1,3,4]);
+        let _ = lazy_tree.drop_value(4);
+        assert_eq!( my_tree.digest::<DefaultHasher>(), lazy_tree.digest::<DefaultHasher>() );
+    }
+
+    #[test]
+    fn test_diff() {
+        let before = Jbst::from_collection([1,2,3,5,8]);
+        let mut after = Jbst::with_lazy_deletion(5);
+        let _ = after.add_all([8,3,4,1,6]);
+        let _ = after.drop_value(6); // tombstones aren't values
+        let diff = before.diff(&after);
+        assert_eq!( vec!(2,5), diff.only_in_self );
+        assert_eq!( vec!(4), diff.only_in_other );
+        assert!( before.diff(&Jbst::from_collection([5,3,8,2,1])).is_empty() );
+        assert_eq!( vec!(1,2,3,5,8), Jbst::new().diff(&before).only_in_other );
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut replica = Jbst::from_collection([1,2,3]);
+        let target = Jbst::from_collection([2,3,4]);
+        assert_eq!( Ok(()), replica.apply(&replica.diff(&target)) );
+        assert_eq!( vec!(2,3,4), replica.as_vec() );
+        // a diff that doesn't fit the tree changes nothing
+        let stale = TreeDiff { only_in_self: vec!(2), only_in_other: vec!(4) };
+        assert_eq!( Err(TreeError::ValueAlreadyStored { value: 4 }), replica.apply(&stale) );
+        assert_eq!( vec!(2,3,4), replica.as_vec() );
+        let stale = TreeDiff { only_in_self: vec!(3,5), only_in_other: vec!() };
+        assert_eq!( Err(TreeError::ValueNotFound { value: 5 }), replica.apply(&stale) );
+        assert_eq!( vec!(2,3,4), replica.as_vec() );
+        assert_eq!( Ok(()), replica.check_invariants() );
+    }
+
+    #[test]
+    #[cfg(feature = "petgraph")]
+    fn test_to_petgraph() {
+        use petgraph::visit::EdgeRef;
+        let graph = Jbst::from_collection([2,1,3,4]).to_petgraph();
+        assert_eq!( 4, graph.node_count() );
+        assert_eq!( 3, graph.edge_count() );
+        let root = NodeIndex::new(0);
+        assert_eq!( 2, graph[root] );
+        let mut children: Vec<(u32, Branch)> = graph.edges(root).map(|edge| (graph[edge.target()], *edge.weight())).collect();
+        children.sort_by_key(|(value, _)| *value);
+        assert_eq!( vec!((1, Branch::Left), (3, Branch::Right)), children );
+        assert_eq!( 0, Jbst::<u32>::new().to_petgraph().node_count() );
+    }
+
+    #[test]
+    fn test_visits() {
+        let mut my_tree = Jbst::with_lazy_deletion(5);
+        let _ = my_tree.add_all([4,2,6,1,3,5,7]);
+        let _ = my_tree.drop_value(6);
+        let mut values = Vec::new();
+        my_tree.visit_pre_order(|value| values.push(*value));
+        assert_eq!( vec!(4,2,1,3,5,7), values ); // the tombstone for 6 is skipped
+    }
+
+    #[test]
+    fn test_leaves() {
+        let mut my_tree = Jbst::with_lazy_deletion(5);
+        assert_eq!( None, my_tree.leaves().next() );
+        let _ = my_tree.add_all([4,2,6,1,3,5]);
+        let _ = my_tree.drop_value(5);
+        let _ = my_tree.drop_value(2);
+        // 5 is a tombstoned leaf, and 6 still has 5's tombstone as a child
+        assert_eq!( vec!(&1,&3), my_tree.leaves().collect::<Vec<_>>() );
+    }
+
+    #[test]
+    fn test_iter_with_depth() {
+        let mut my_tree = Jbst::with_lazy_deletion(5);
+        let _ = my_tree.add_all([4,2,6,1,3]);
+        let _ = my_tree.drop_value(2);
+        assert_eq!( vec!((&1,2), (&3,2), (&4,0), (&6,1)), my_tree.iter_with_depth().collect::<Vec<_>>() );
+    }
+
+    #[test]
+    fn test_cursor() {
+        let mut my_tree = Jbst::with_lazy_deletion(10);
+        let _ = my_tree.add_all([50,30,70,20,40,60,80]);
+        let _ = my_tree.drop_value(40);
+        let _ = my_tree.drop_value(50);
+        // 40 and 50 are tombstones, which the cursor steps over
+        let mut cursor = my_tree.cursor_at(&35);
+        assert_eq!( Some(&60), cursor.peek() );
+        assert_eq!( Some(&30), cursor.prev() );
+        assert_eq!( Some(&20), cursor.prev() );
+        assert_eq!( None, cursor.prev() );
+        assert_eq!( None, cursor.peek() );
+        assert_eq!( None, cursor.next() ); // an iterator stops at the ghost position...
+        assert_eq!( Some(&80), cursor.prev() ); // ...though stepping back goes round to the greatest value
+        let mut cursor = my_tree.cursor_at(&25);
+        assert_eq!( vec!(&60,&70,&80), cursor.by_ref().collect::<Vec<_>>() );
+        assert_eq!( None, cursor.next() );
+        let mut cursor = my_tree.cursor_back();
+        assert_eq!( Some(&80), cursor.peek() );
+        assert_eq!( None, cursor.next() );
+        assert_eq!( None, cursor.next() );
+        assert_eq!( Some(&80), cursor.prev() );
+        assert_eq!( None, my_tree.cursor_at(&90).peek() );
+        assert_eq!( None, Jbst::<u32>::new().cursor_front().peek() );
+    }
+
+    #[test]
+    fn test_cursor_mut() {
+        let mut my_tree = Jbst::with_lazy_deletion(2);
+        let _ = my_tree.add_all([5,3,8,1,4,7,9]);
+        let mut cursor = my_tree.cursor_mut_at(&4);
+        assert_eq!( Some(4), cursor.remove_current() );
+        assert_eq!( Some(&5), cursor.peek() );
+        assert_eq!( Some(&3), cursor.move_prev() ); // steps over 4's tombstone
+        assert_eq!( Some(3), cursor.remove_current() );
+        assert_eq!( Some(5), cursor.remove_current() ); // this one exceeds the limit, so the tree is purged
+        assert_eq!( Some(&7), cursor.peek() );
+        assert_eq!( Some(&8), cursor.move_next() );
+        assert_eq!( Some(&9), cursor.move_next() );
+        assert_eq!( Some(9), cursor.remove_current() );
+        assert_eq!( None, cursor.peek() );
+        assert_eq!( None, cursor.remove_current() );
+        assert_eq!( Some(&8), cursor.move_prev() );
+        assert_eq!( vec!(1,7,8), my_tree.as_vec() );
+        assert_eq!( Ok(()), my_tree.check_invariants() );
+    }
+
+    #[test]
+    fn test_drain() {
+        let mut my_tree = Jbst::with_lazy_deletion(5);
+        let _ = my_tree.add_all([4,2,6,1,3]);
+        let _ = my_tree.drop_value(2);
+        assert_eq!( vec!(1,3,4,6), my_tree.drain().collect::<Vec<_>>() );
+        assert_eq!( 0, my_tree.get_size() );
+        assert_eq!( 0, my_tree.get_tombstone_count() );
+        assert_eq!( Ok(()), my_tree.check_invariants() );
+        // still in lazy-deletion mode, and reusable
+        let _ = my_tree.add_all([2,1]);
+        let _ = my_tree.drop_value(1);
+        assert_eq!( 1, my_tree.get_tombstone_count() );
+    }
+
+    #[test]
+    fn test_merge_iter() {
+        let mut tree_a = Jbst::with_lazy_deletion(5);
+        let _ = tree_a.add_all([1,3,5,7]);
+        let _ = tree_a.drop_value(7);
+        let tree_b = Jbst::from_collection([2,3,4]);
+        assert_eq!( vec!(&1,&2,&3,&4,&5), tree_a.merge_iter(&tree_b, Duplicates::Union).collect::<Vec<_>>() );
+        assert_eq!( vec!(&1,&2,&3,&3,&4,&5), tree_a.merge_iter(&tree_b, Duplicates::KeepBoth).collect::<Vec<_>>() );
+    }
+
+    #[test]
+    fn get_or_insert_searches_once() {
+        let mut tree = Jbst::with_lazy_deletion(10);
+        let _ = tree.add_all([4,2,6]);
+        assert_eq!( &6, tree.get_or_insert(6) );
+        assert_eq!( &5, tree.get_or_insert(5) );
+        assert_eq!( 4, tree.get_size() );
+        // a tombstone is revived rather than a second node added
+        tree.drop_value(2).unwrap();
+        assert_eq!( None, tree.get(&2) );
+        assert_eq!( &2, tree.get_or_insert_with(&2, || 2) );
+        assert_eq!( 0, tree.get_tombstone_count() );
+        assert_eq!( &9, tree.get_or_insert_with(&9, || 9) );
+        assert_eq!( &9, tree.get_or_insert_with(&9, || panic!("9 is already stored")) );
+        assert_eq!( vec!(2,4,5,6,9), tree.as_vec() );
+        assert_eq!( Ok(()), tree.check_invariants() );
+    }
+
+    #[test]
+    fn insert_returns_the_displaced_value() {
+        let mut tree = Jbst::with_lazy_deletion(10);
+        assert_eq!( None, tree.insert(5) );
+        assert_eq!( None, tree.insert(3) );
+        assert_eq!( Some(5), tree.insert(5) );
+        assert_eq!( 2, tree.get_size() );
+        // inserting over a tombstone isn't a replacement
+        tree.drop_value(3).unwrap();
+        assert_eq!( None, tree.insert(3) );
+        assert_eq!( 0, tree.get_tombstone_count() );
+        assert_eq!( vec!(3,5), tree.as_vec() );
+        assert_eq!( Ok(()), tree.check_invariants() );
+    }
+
+    #[test]
+    fn first_n_and_last_n() {
+        let mut my_tree = Jbst::with_lazy_deletion(10);
+        let _ = my_tree.add_all([50,20,80,10,30,70,90]);
+        my_tree.drop_value(10).unwrap();
+        my_tree.drop_value(90).unwrap();
+        assert_eq!( vec!(20,30,50), my_tree.first_n(3) );
+        assert_eq!( vec!(50,70,80), my_tree.last_n(3) );
+        assert_eq!( my_tree.as_vec(), my_tree.first_n(100) );
+        assert_eq!( my_tree.as_vec(), my_tree.last_n(100) );
+        assert!( my_tree.first_n(0).is_empty() );
+        assert!( Jbst::<u8>::new().last_n(3).is_empty() );
+    }
+
+    #[test]
+    fn into_sorted_vec_skips_tombstones() {
+        let mut my_tree = Jbst::with_lazy_deletion(10);
+        let _ = my_tree.add_all([5,3,8,1,4]);
+        my_tree.drop_value(3).unwrap();
+        assert_eq!( vec!(1,4,5,8), my_tree.into_sorted_vec() );
+    }
+
+    #[test]
+    fn values_skips_tombstones() {
+        let mut my_tree = Jbst::with_lazy_deletion(10);
+        let _ = my_tree.add_all([5,3,8]);
+        my_tree.drop_value(5).unwrap();
+        assert_eq!( vec!(&3,&8), my_tree.values() );
+        assert!( Jbst::<u8>::new().values().is_empty() );
+    }
+
+    #[test]
+    fn dropping_moves_the_successor_without_cloning() {
+        use std::cell::Cell;
+        thread_local! { static CLONES: Cell<u32> = const { Cell::new(0) }; }
+        #[derive(PartialEq, PartialOrd, Debug)]
+        struct Counted(u32);
+        impl Clone for Counted {
+            fn clone(&self) -> Self {
+                CLONES.with(|clones| clones.set(clones.get() + 1));
+                Counted(self.0)
+            }
+        }
+        let mut my_tree = Jbst::new();
+        for n in [50, 20, 80, 10, 30, 70, 90, 60, 75, 65] {
+            my_tree.add(Counted(n)).unwrap();
+        }
+        // the root has two branches, and its successor (60) is deep in the right one
+        assert_eq!( Ok(()), my_tree.drop_value(Counted(50)) );
+        assert_eq!( Ok(()), my_tree.drop_value(Counted(20)) );
+        assert_eq!( 0, CLONES.with(|clones| clones.get()) );
+        assert_eq!( Some(60), my_tree.get_root_value().map(|value| value.0) );
+        assert_eq!( Ok(()), my_tree.check_invariants() );
+    }
+
+    #[test]
+    #[should_panic(expected = "as many values as it can count")]
+    fn full_tree_refuses_values() {
+        let mut tree = Jbst::from_collection([1,2,3]);
+        tree.size = u32::MAX;
+        assert_eq!( Err(TreeError::CapacityExceeded { value: 4 }), tree.add(4) );
+        tree.insert(4);
+    }
+
+    #[test]
+    fn try_insert_reports_occupied_values() {
+        let mut my_tree = Jbst::with_lazy_deletion(10);
+        let _ = my_tree.add_all([4,2,6]);
+        assert_eq!( Ok(&5), my_tree.try_insert(5) );
+        let occupied = my_tree.try_insert(2).unwrap_err();
+        assert_eq!( (&2, 2), (occupied.existing, occupied.value) );
+        assert_eq!( "OccupiedError: 2 wasn't inserted, because the tree already holds 2.", occupied.to_string() );
+        // a tombstoned value is revived, not occupied
+        let _ = my_tree.drop_value(6);
+        assert_eq!( Ok(&6), my_tree.try_insert(6) );
+        assert_eq!( (4, 0), (my_tree.get_size(), my_tree.get_tombstone_count()) );
+        assert_eq!( Ok(()), my_tree.check_invariants() );
+    }
+
+    #[test]
+    fn reviving_a_tombstone_stores_the_new_value() {
+        // values compare by the first field only, so the stored copy can be told apart by the second
+        #[derive(Clone, Debug)]
+        struct Tagged(u32, &'static str);
+        impl PartialEq for Tagged {
+            fn eq(&self, other: &Self) -> bool { self.0 == other.0 }
+        }
+        impl PartialOrd for Tagged {
+            fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> { self.0.partial_cmp(&other.0) }
+        }
+        let mut my_tree = Jbst::with_lazy_deletion(10);
+        let _ = my_tree.add_all([4,2,6].map(|n| Tagged(n, "old")));
+        my_tree.drop_value(Tagged(2, "")).unwrap();
+        assert_eq!( "new", my_tree.try_insert(Tagged(2, "new")).unwrap().1 );
+        assert_eq!( "new", my_tree.get(&Tagged(2, "")).unwrap().1 );
+        my_tree.drop_value(Tagged(4, "")).unwrap();
+        assert_eq!( "new", my_tree.get_or_insert(Tagged(4, "new")).1 );
+        my_tree.drop_value(Tagged(6, "")).unwrap();
+        assert_eq!( "made", my_tree.get_or_insert_with(&Tagged(6, "probe"), || Tagged(6, "made")).1 );
+        my_tree.drop_value(Tagged(2, "")).unwrap();
+        my_tree.add(Tagged(2, "added")).unwrap();
+        assert_eq!( "added", my_tree.get(&Tagged(2, "")).unwrap().1 );
+    }
+
+    #[test]
+    fn values_need_not_be_clone() {
+        #[derive(PartialEq, PartialOrd, Debug)]
+        struct Handle(u32);
+        let mut my_tree = Jbst::new();
+        for n in [5, 2, 8, 1, 3, 9, 7, 4, 6] {
+            assert_eq!( Ok(()), my_tree.add(Handle(n)) );
+        }
+        assert_eq!( Err(TreeError::ValueAlreadyStored { value: Handle(4) }), my_tree.add(Handle(4)) );
+        assert_eq!( Ok(()), my_tree.drop_value(Handle(5)) );
+        assert_eq!( Ok(()), my_tree.drop_value(Handle(2)) );
+        assert_eq!( Ok(()), my_tree.check_invariants() );
+        assert_eq!( Some(&Handle(6)), my_tree.get(&Handle(6)) );
+        assert_eq!( Some(Handle(9)), my_tree.insert(Handle(9)) );
+        assert_eq!( vec!(1,3,4,6,7,8,9), my_tree.into_sorted_vec().into_iter().map(|handle| handle.0).collect::<Vec<_>>() );
+
+        // purging moves the live values into the tombstones' places too
+        let mut lazy_tree = Jbst::with_lazy_deletion(3);
+        for n in [5, 2, 8, 1, 3, 9, 7, 4, 6] {
+            assert_eq!( Ok(()), lazy_tree.add(Handle(n)) );
+        }
+        for n in [5, 2, 8, 1] {
+            assert_eq!( Ok(()), lazy_tree.drop_value(Handle(n)) );
+        }
+        assert_eq!( (5, 0), (lazy_tree.get_size(), lazy_tree.get_tombstone_count()) );
+        assert_eq!( Ok(()), lazy_tree.check_invariants() );
+        assert_eq!( vec!(3,4,6,7,9), lazy_tree.iter().map(|handle| handle.0).collect::<Vec<_>>() );
+    }
+
+    #[test]
+    fn depth_limit_stops_deep_values() {
+        let mut my_tree = Jbst::<u32>::with_depth_limit(3);
+        let _ = my_tree.add_all([1,2,3]);
+        assert_eq!( Err(TreeError::DepthLimitExceeded { value: 4, limit: 3 }), my_tree.add(4) );
+        assert_eq!( Ok(()), my_tree.add(0) ); // only 2 deep
+        assert_eq!( vec!(0,1,2,3), my_tree.as_vec() );
+
+        // batches that hit the limit are undone as a whole
+        let mut full = Jbst::with_depth_limit(3);
+        let _ = full.add_all([4,2,6]);
+        assert_eq!( Err(TreeError::DepthLimitExceeded { value: 8, limit: 3 }), full.try_add_all([1,5,7,8,3]) );
+        assert_eq!( vec!(2,4,6), full.as_vec() );
+        let _ = full.add_all([1,3,5,7]);
+        let diff = TreeDiff { only_in_self: vec!(4), only_in_other: vec!(8) };
+        assert_eq!( Err(TreeError::DepthLimitExceeded { value: 8, limit: 3 }), full.apply(&diff) );
+        assert_eq!( vec!(1,2,3,4,5,6,7), full.as_vec() ); // 4 is back, though deeper than before
+
+        // a long sorted run stops at the limit, well before the stack would run out
+        let mut deep = Jbst::with_depth_limit(DECODE_DEPTH_LIMIT);
+        let limit = DECODE_DEPTH_LIMIT as u32;
+        assert_eq!( Err(TreeError::DepthLimitExceeded { value: limit, limit: DECODE_DEPTH_LIMIT }), deep.add_all(0..10_000) );
+        assert_eq!( limit, deep.get_size() );
+        assert!( std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| deep.insert(limit))).is_err() );
+        assert_eq!( Ok(()), deep.drop_value(limit - 1) );
+        assert_eq!( None, deep.insert(limit) );
+
+        // trees deeper than the limit aren't decoded either
+        let bytes = my_tree.to_bytes();
+        assert!( matches!( Jbst::<u32>::from_bytes_with_depth_limit(&bytes, 2), Err(TreeError::DeserializationCorrupt { .. }) ) );
+        assert_eq!( 3, Jbst::<u32>::from_bytes_with_depth_limit(&bytes, 3).unwrap().get_depth_limit() );
+
+        // a new tree has the default limit, and so does a decoded one
+        let mut deep = Jbst::new();
+        assert_eq!( DEFAULT_DEPTH_LIMIT, deep.get_depth_limit() );
+        let limit = DEFAULT_DEPTH_LIMIT as u32;
+        assert_eq!( Err(TreeError::DepthLimitExceeded { value: limit, limit: DEFAULT_DEPTH_LIMIT }), deep.add_all(0..10_000) );
+        assert!( std::panic::catch_unwind(|| Jbst::from_collection(0..10_000)).is_err() );
+        assert_eq!( DEFAULT_DEPTH_LIMIT, Jbst::<u32>::from_bytes(&deep.to_bytes()).unwrap().get_depth_limit() );
+        let mut deeper = Jbst::with_depth_limit(600);
+        let _ = deeper.add_all(0..600);
+        assert!( matches!( Jbst::<u32>::from_bytes(&deeper.to_bytes()), Err(TreeError::DeserializationCorrupt { .. }) ) );
+        assert_eq!( Ok(600), Jbst::<u32>::from_bytes_with_depth_limit(&deeper.to_bytes(), 600).map(|tree| tree.get_size()) );
+    }
+
+    #[test]
+    fn iter_borrows_live_values_in_order() {
+        let mut my_tree = Jbst::with_lazy_deletion(5);
+        let _ = my_tree.add_all([String::from("m"), String::from("c"), String::from("x"), String::from("a")]);
+        let _ = my_tree.drop_value(String::from("c")); // a tombstone now
+        assert_eq!( vec!("a", "m", "x"), my_tree.iter().map(String::as_str).collect::<Vec<_>>() );
+        assert_eq!( vec!("x", "m", "a"), my_tree.iter().rev().map(String::as_str).collect::<Vec<_>>() );
+        let mut joined = String::new();
+        for value in &my_tree {
+            joined.push_str(value);
+        }
+        assert_eq!( "amx", joined );
+        assert_eq!( None, Jbst::<String>::new().iter().next() );
+    }
+
+    #[test]
+    fn range_skips_tombstones() {
+        use std::ops::Bound;
+        let mut my_tree = Jbst::with_lazy_deletion(10);
+        let _ = my_tree.add_all([50,20,80,10,30,60,90,25,35]);
+        let _ = my_tree.drop_value(30);
+        assert_eq!( vec!(&20,&25,&35,&50), my_tree.range(20..=50).collect::<Vec<_>>() );
+        assert_eq!( vec!(&35,&25), my_tree.range(21..40).rev().collect::<Vec<_>>() );
+        assert_eq!( 0, my_tree.range(30..=30).count() );
+        assert_eq!( 0, my_tree.range((Bound::Included(60), Bound::Excluded(50))).count() );
+    }
+
 }
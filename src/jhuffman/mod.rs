@@ -0,0 +1,252 @@
+use std::{cmp::Reverse, collections::BinaryHeap, fmt};
+
+use crate::errors::TreeError;
+
+
+
+/// # Joe's Huffman Tree
+///
+/// A Huffman code for bytes: frequent symbols get short codes and rare ones long codes, giving the shortest
+/// possible encoding of data with the given symbol frequencies.  The tree is built bottom-up by repeatedly
+/// joining the two lightest subtrees (taken from a priority queue) until one remains; a symbol's code is
+/// its path from the root, `false` for left and `true` for right.
+///
+///     use jtree::jhuffman::HuffmanTree;
+///
+///     let message = b"abracadabra";
+///     let tree = HuffmanTree::from_data(message);
+///     assert_eq!( Some(&[false][..]), tree.code_for(b'a') ); // the most frequent symbol has the shortest code
+///     let encoded = tree.encode(message).unwrap();
+///     assert_eq!( 23, encoded.bit_len );
+///     assert_eq!( message.to_vec(), tree.decode(&encoded).unwrap() );
+///
+/// Ties between equal weights are broken by the order the subtrees were made in, so the same frequencies
+/// always give the same codes.
+pub struct HuffmanTree {
+    nodes: Vec<Node>, // the leaves, then the joined subtrees, in the order they were made
+    root: Option<usize>,
+    codes: Vec<Option<Vec<bool>>>, // indexed by symbol
+}
+
+enum Node {
+    Leaf(u8),
+    Branch(usize, usize), // the indexes of the left and right subtrees
+}
+
+/// Data encoded with a `HuffmanTree`: the code bits packed into bytes, most significant bit first, with
+/// the last byte padded with zeros.
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct Encoded {
+    pub bytes: Vec<u8>,
+    /// The number of bits used, not counting the padding
+    pub bit_len: usize,
+}
+
+impl HuffmanTree {
+
+    /// Build the tree from each symbol's frequency (symbols with a frequency of zero are left out).  A symbol
+    /// given twice is refused with a TreeError::ValueAlreadyStored, and one whose weight takes the total past
+    /// `u64::MAX` (which the joined subtrees' weights couldn't be counted up to) with a
+    /// TreeError::CapacityExceeded.
+    ///
+    ///     use jtree::jhuffman::HuffmanTree;
+    ///     use jtree::errors::TreeError;
+    ///
+    ///     assert!( HuffmanTree::from_frequencies([(b'a', 3), (b'b', 1)]).is_ok() );
+    ///     assert!( matches!(HuffmanTree::from_frequencies([(b'a', 3), (b'a', 1)]), Err(TreeError::ValueAlreadyStored { value: b'a' })) );
+    pub fn from_frequencies<U: IntoIterator<Item = (u8, u64)>>(frequencies: U) -> Result<Self,TreeError<u8>> {
+        let mut nodes = Vec::new();
+        let mut queue = BinaryHeap::new();
+        let mut seen = [false; 256];
+        let mut total: u64 = 0;
+        for (symbol, weight) in frequencies {
+            if std::mem::replace(&mut seen[symbol as usize], true) {
+                return Err(TreeError::ValueAlreadyStored { value: symbol });
+            }
+            // every subtree weighs no more than the total, so checking it once here covers all the joins below
+            total = total.checked_add(weight).ok_or(TreeError::CapacityExceeded { value: symbol })?;
+            if weight > 0 {
+                queue.push(Reverse((weight, nodes.len())));
+                nodes.push(Node::Leaf(symbol));
+            }
+        }
+        while queue.len() > 1 {
+            let Reverse((left_weight, left)) = queue.pop().unwrap();
+            let Reverse((right_weight, right)) = queue.pop().unwrap();
+            queue.push(Reverse((left_weight + right_weight, nodes.len())));
+            nodes.push(Node::Branch(left, right));
+        }
+        let root = queue.pop().map(|Reverse((_, index))| index);
+        let mut tree = Self { nodes, root, codes: vec![None; 256] };
+        match root {
+            None => (),
+            // a lone symbol still needs a one-bit code, so that each copy of it takes up a bit
+            Some(index) if matches!(tree.nodes[index], Node::Leaf(_)) => tree.assign_codes(index, &mut vec![false]),
+            Some(index) => tree.assign_codes(index, &mut Vec::new()),
+        }
+        Ok(tree)
+    }
+
+    /// Build the tree from the symbol frequencies in some data.
+    pub fn from_data(data: &[u8]) -> Self {
+        let mut counts = [0u64; 256];
+        for byte in data {
+            counts[*byte as usize] += 1;
+        }
+        Self::from_frequencies((0..=255).zip(counts)).expect("byte counts are distinct and add up to the data's length")
+    }
+
+    /// Record the codes of the symbols in the subtree, whose path from the root is `path`.
+    fn assign_codes(&mut self, index: usize, path: &mut Vec<bool>) {
+        match self.nodes[index] {
+            Node::Leaf(symbol) => self.codes[symbol as usize] = Some(path.clone()),
+            Node::Branch(left, right) => {
+                path.push(false);
+                self.assign_codes(left, path);
+                path.pop();
+                path.push(true);
+                self.assign_codes(right, path);
+                path.pop();
+            },
+        }
+    }
+
+    /// The symbol's code, or None if it isn't in the tree.
+    pub fn code_for(&self, symbol: u8) -> Option<&[bool]> {
+        self.codes[symbol as usize].as_deref()
+    }
+
+    /// The code table: each symbol in the tree with its code, in order of the symbols.
+    pub fn codes(&self) -> impl Iterator<Item = (u8, &[bool])> {
+        (0..=255).zip(&self.codes).filter_map(|(symbol, code)| {
+            return match code {
+                Some(code) => Some((symbol, code.as_slice())),
+                None => None,
+            };
+        })
+    }
+
+    /// Encode the data, returning a TreeError::ValueNotFound for the first symbol that isn't in the tree.
+    pub fn encode(&self, data: &[u8]) -> Result<Encoded,TreeError<u8>> {
+        let mut encoded = Encoded::default();
+        for &symbol in data {
+            let code = match self.code_for(symbol) {
+                Some(code) => code,
+                None => return Err(TreeError::ValueNotFound { value: symbol }),
+            };
+            for &bit in code {
+                if encoded.bit_len % 8 == 0 {
+                    encoded.bytes.push(0);
+                }
+                if bit {
+                    *encoded.bytes.last_mut().unwrap() |= 0x80 >> (encoded.bit_len % 8);
+                }
+                encoded.bit_len += 1;
+            }
+        }
+        Ok(encoded)
+    }
+
+    /// Decode data encoded with this tree, returning a TreeError::DeserializationCorrupt if the bits don't
+    /// spell out whole codes, or a TreeError::EmptyTree if there are bits but the tree has no codes at all.
+    pub fn decode(&self, encoded: &Encoded) -> Result<Vec<u8>,TreeError<u8>> {
+        let corrupt = |offset: usize, reason: &str| TreeError::DeserializationCorrupt { offset, reason: String::from(reason) };
+        if encoded.bit_len > encoded.bytes.len() * 8 {
+            return Err(corrupt(encoded.bytes.len(), "bit length runs past the end of the bytes"));
+        }
+        let root = match self.root {
+            None if encoded.bit_len == 0 => return Ok(Vec::new()),
+            None => return Err(TreeError::EmptyTree),
+            Some(root) => root,
+        };
+        let mut decoded = Vec::new();
+        let mut index = root;
+        for bit_index in 0..encoded.bit_len {
+            let bit = encoded.bytes[bit_index / 8] & (0x80 >> (bit_index % 8)) != 0;
+            index = match self.nodes[index] {
+                Node::Branch(left, right) => if bit { right } else { left },
+                // a lone symbol's code is a single 0
+                Node::Leaf(_) if !bit => index,
+                Node::Leaf(_) => return Err(corrupt(bit_index / 8, "not a code in this tree")),
+            };
+            match self.nodes[index] {
+                Node::Leaf(symbol) => {
+                    decoded.push(symbol);
+                    index = root;
+                },
+                Node::Branch(_, _) => (),
+            }
+        }
+        if index != root {
+            return Err(corrupt(encoded.bytes.len(), "the bits end partway through a code"));
+        }
+        Ok(decoded)
+    }
+
+}
+
+impl fmt::Debug for HuffmanTree {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = fmt.debug_map();
+        for (symbol, code) in self.codes() {
+            let bits: String = code.iter().map(|&bit| if bit { '1' } else { '0' }).collect();
+            map.entry(&symbol, &bits);
+        }
+        map.finish()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_are_prefix_free_and_optimal() {
+        // the classic textbook frequencies, whose optimal encoding takes 224 bits per 100 symbols
+        let frequencies = [(b'a', 45), (b'b', 13), (b'c', 12), (b'd', 16), (b'e', 9), (b'f', 5)];
+        let tree = HuffmanTree::from_frequencies(frequencies).unwrap();
+        let total: u64 = frequencies.iter().map(|(symbol, weight)| weight * tree.code_for(*symbol).unwrap().len() as u64).sum();
+        assert_eq!( 224, total );
+        let codes: Vec<&[bool]> = tree.codes().map(|(_, code)| code).collect();
+        for a in &codes {
+            for b in &codes {
+                assert!( a == b || !b.starts_with(a) );
+            }
+        }
+        assert_eq!( None, tree.code_for(b'z') );
+    }
+
+    #[test]
+    fn round_trips() {
+        let data: Vec<u8> = (0..5000u32).map(|n| (n * n % 251) as u8).collect();
+        let tree = HuffmanTree::from_data(&data);
+        let encoded = tree.encode(&data).unwrap();
+        assert!( encoded.bytes.len() < data.len() );
+        assert_eq!( data, tree.decode(&encoded).unwrap() );
+
+        let single = HuffmanTree::from_data(b"zzz");
+        let encoded = single.encode(b"zzz").unwrap();
+        assert_eq!( 3, encoded.bit_len );
+        assert_eq!( b"zzz".to_vec(), single.decode(&encoded).unwrap() );
+
+        let empty = HuffmanTree::from_data(b"");
+        assert_eq!( Ok(Vec::new()), empty.decode(&empty.encode(b"").unwrap()) );
+    }
+
+    #[test]
+    fn bad_input_is_reported() {
+        let tree = HuffmanTree::from_data(b"aab");
+        assert_eq!( Err(TreeError::ValueNotFound { value: b'c' }), tree.encode(b"abc") );
+        // 'a' is 0 and 'b' is 1 (or the other way round), so a lone bit past the end can't be read
+        let bad = Encoded { bytes: vec![0], bit_len: 9 };
+        assert!( matches!(tree.decode(&bad), Err(TreeError::DeserializationCorrupt { .. })) );
+        let empty = HuffmanTree::from_data(b"");
+        assert_eq!( Err(TreeError::EmptyTree), empty.decode(&tree.encode(b"ab").unwrap()) );
+        // frequencies that repeat a symbol, or that add up past what a weight can hold
+        assert!( matches!(HuffmanTree::from_frequencies([(b'a', 1), (b'b', 2), (b'a', 3)]), Err(TreeError::ValueAlreadyStored { value: b'a' })) );
+        assert!( matches!(HuffmanTree::from_frequencies([(b'a', u64::MAX), (b'b', 0), (b'c', 1)]), Err(TreeError::CapacityExceeded { value: b'c' })) );
+        assert!( HuffmanTree::from_frequencies([(b'a', u64::MAX - 1), (b'b', 1)]).is_ok() );
+    }
+
+}
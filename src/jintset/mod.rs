@@ -0,0 +1,464 @@
+use std::fmt;
+
+use crate::errors::TreeError;
+use crate::reports::AddReport;
+
+
+
+/// Marks a child index that doesn't point to any node.
+const NIL: u32 = u32::MAX;
+
+/// The most values a set can hold: its nodes' indices run from 0 up to one less than this, stopping short of NIL.
+const MAX_SIZE: u32 = NIL - 1;
+
+/// Integer types that can be used as keys in an `IntSet`.
+pub trait IntKey: Copy + Ord {}
+
+impl IntKey for u32 {}
+impl IntKey for u64 {}
+impl IntKey for usize {}
+
+/// # Integer Set
+/// 
+/// An AVL tree specialized for integer keys (`u32`, `u64`, or `usize`), for workloads like sets of IDs
+/// where the generic trees' overheads add up.  Instead of a separate heap allocation per node, all
+/// the nodes live in one `Vec` and point to each other with `u32` indices.  The keys are stored inline
+/// and copied rather than cloned, rotations only rewire indices, and the descent during lookups picks
+/// a child by indexing with the comparison result rather than branching on it.  A set holds at most
+/// `u32::MAX - 1` values, since one index is set aside to mean "no node".
+///
+///     use jtree::IntSet;
+///     use jtree::errors::TreeError;
+/// 
+///     let mut my_set = IntSet::<u64>::new();
+///     let _ = my_set.add(2);
+///     let _ = my_set.add(1);
+///     let _ = my_set.add(3);
+///     assert_eq!( 3, my_set.get_size() );
+///     assert_eq!( vec!(1,2,3), my_set.as_vec() );
+///     assert_eq!( Err(TreeError::ValueAlreadyStored { value: 1 }), my_set.add(1) ); // unique values only!
+///     assert!( my_set.contains(2) );
+pub struct IntSet<K: IntKey> {
+    nodes: Vec<IntNode<K>>,
+    free_slots: Vec<u32>, // indices of nodes that were dropped and can be reused
+    root: u32,
+    size: u32,
+}
+
+#[derive(Clone, Copy)]
+struct IntNode<K: IntKey> {
+    key: K,
+    children: [u32; 2], // left, right
+    height: u32,
+}
+
+impl <K: IntKey> IntSet<K> {
+
+    /// Create a new set with no data
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            free_slots: Vec::new(),
+            root: NIL,
+            size: 0,
+        }
+    }
+
+    /// Create a new set with room for at least `capacity` values before its node storage has to grow.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            nodes: Vec::with_capacity(capacity),
+            free_slots: Vec::new(),
+            root: NIL,
+            size: 0,
+        }
+    }
+
+    /// Create a new set from a collection (vector, array, or whatever), skipping duplicates.
+    /// Storage is preallocated based on the collection's size hint.
+    pub fn from_collection<U: IntoIterator<Item = K>>(collection: U) -> Self {
+        let iter = collection.into_iter();
+        let mut new_set = Self::with_capacity(iter.size_hint().0);
+        let _ = new_set.add_all(iter);
+        new_set
+    }
+
+    /// Insert a value
+    pub fn add(&mut self, key: K) -> Result<(),TreeError<K>> {
+        if self.size >= MAX_SIZE {
+            return Err(TreeError::CapacityExceeded { value: key });
+        }
+        self.root = self.insert_into(self.root, key)?;
+        self.size += 1;
+        #[cfg(feature = "paranoid")]
+        self.paranoid_check("add");
+        Ok(())
+    }
+
+    /// Adds all members of a collection (vector, array, or whatever) to the set,
+    /// skipping over any that would be duplicates, so no error will stop the batch.
+    /// The returned report says how many values were inserted and how many were skipped.
+    pub fn add_all<U: IntoIterator<Item = K>>(&mut self, collection: U) -> Result<AddReport,TreeError<K>> {
+        let mut report = AddReport::default();
+        for key in collection.into_iter() {
+            match self.add(key) {
+                Ok(()) => report.inserted += 1,
+                Err(_) => report.skipped += 1,
+            }
+        }
+        Ok(report)
+    }
+
+    /// Get the number of values in the set
+    pub fn get_size(&self) -> u32 {
+        self.size
+    }
+
+    /// Get the number of values the set can hold without allocating more node storage.
+    pub fn capacity(&self) -> usize {
+        self.nodes.capacity() - self.nodes.len() + self.free_slots.len() + self.size as usize
+    }
+
+    /// Reserve node storage for at least `additional` more values than the set currently holds.
+    pub fn reserve(&mut self, additional: usize) {
+        self.nodes.reserve(additional.saturating_sub(self.free_slots.len()));
+    }
+
+    /// Returns true if the value is currently a member of the set
+    pub fn contains(&self, key: K) -> bool {
+        let mut idx = self.root;
+        while idx != NIL {
+            let node = &self.nodes[idx as usize];
+            if node.key == key {
+                return true;
+            }
+            idx = node.children[usize::from(key > node.key)];
+        }
+        false
+    }
+
+    /// Returns all the values in the set as an ordered Vec from least to greatest.
+    pub fn as_vec(&self) -> Vec<K> {
+        let mut keys = Vec::with_capacity(self.size as usize);
+        let mut stack = Vec::new();
+        let mut idx = self.root;
+        while idx != NIL || !stack.is_empty() {
+            while idx != NIL {
+                stack.push(idx);
+                idx = self.nodes[idx as usize].children[0];
+            }
+            let node = &self.nodes[stack.pop().unwrap() as usize];
+            keys.push(node.key);
+            idx = node.children[1];
+        }
+        keys
+    }
+
+    /// Returns the smallest/lowest value in the set, if any.
+    pub fn least_value(&self) -> Option<K> {
+        self.extreme_value(0)
+    }
+
+    /// Returns the largest/highest value in the set, if any.
+    pub fn greatest_value(&self) -> Option<K> {
+        self.extreme_value(1)
+    }
+
+    /// If the value is in the set, delete it.  Otherwise a TreeError::ValueNotFound will be returned.
+    pub fn drop_value(&mut self, key: K) -> Result<(),TreeError<K>> {
+        self.root = self.remove_from(self.root, key)?;
+        self.size -= 1;
+        #[cfg(feature = "paranoid")]
+        self.paranoid_check("drop_value");
+        Ok(())
+    }
+
+    /// Verify the set's internal consistency: that values are in order, every node's height is correct
+    /// and its subtrees are balanced, and the size matches the number of nodes.  Returns a
+    /// TreeError::InvariantViolation describing the first problem found, if any.
+    pub fn check_invariants(&self) -> Result<(),TreeError<K>> {
+        let (_, nodes) = self.check_subtree(self.root, &mut String::from("root"), None, None)?;
+        if nodes != self.size {
+            return Err(TreeError::InvariantViolation(format!("size is {} but the set holds {} values", self.size, nodes)));
+        }
+        if nodes as usize + self.free_slots.len() != self.nodes.len() {
+            return Err(TreeError::InvariantViolation(format!("{} nodes are neither in the tree nor free", self.nodes.len() - self.free_slots.len() - nodes as usize)));
+        }
+        Ok(())
+    }
+
+    /// Recursively verify the subtree at `idx`, returning its height and number of nodes.
+    /// The path (e.g. "root.left.right") is only used to describe where a problem was found.
+    fn check_subtree(&self, idx: u32, path: &mut String, lower: Option<K>, upper: Option<K>) -> Result<(u32,u32),TreeError<K>> {
+        if idx == NIL {
+            return Ok((0, 0));
+        }
+        let node = &self.nodes[idx as usize];
+        if lower.is_some_and(|lower| node.key <= lower) || upper.is_some_and(|upper| node.key >= upper) {
+            return Err(TreeError::InvariantViolation(format!("value at {path} is out of order")));
+        }
+        let path_len = path.len();
+        path.push_str(".left");
+        let (left_height, left_nodes) = self.check_subtree(node.children[0], path, lower, Some(node.key))?;
+        path.truncate(path_len);
+        path.push_str(".right");
+        let (right_height, right_nodes) = self.check_subtree(node.children[1], path, Some(node.key), upper)?;
+        path.truncate(path_len);
+        if node.height != left_height.max(right_height) + 1 {
+            return Err(TreeError::InvariantViolation(format!("node at {path} has height {} but should be {}", node.height, left_height.max(right_height) + 1)));
+        }
+        if left_height.abs_diff(right_height) > 1 {
+            return Err(TreeError::InvariantViolation(format!("node at {path} is unbalanced")));
+        }
+        Ok((node.height, left_nodes + right_nodes + 1))
+    }
+
+    /// With the `paranoid` feature, every change to the set is followed by this check, which panics
+    /// with a description of the problem and the tree's shape if the change broke an invariant.
+    #[cfg(feature = "paranoid")]
+    fn paranoid_check(&self, operation: &str) {
+        if let Err(TreeError::InvariantViolation(problem)) = self.check_invariants() {
+            panic!("IntSet::{operation} broke the tree: {problem} (size {}, shape {})", self.size, self.outline(self.root));
+        }
+    }
+
+    /// Sketch the shape of the subtree at `idx`, for the `paranoid` feature's diagnostics.
+    /// Nodes are shown by their heights, e.g. `(. 2 (. 1 .))`.
+    #[cfg(feature = "paranoid")]
+    fn outline(&self, idx: u32) -> String {
+        if idx == NIL {
+            return String::from(".");
+        }
+        let node = &self.nodes[idx as usize];
+        format!("({} {} {})", self.outline(node.children[0]), node.height, self.outline(node.children[1]))
+    }
+
+    /// Follow the left (side 0) or right (side 1) children all the way down.
+    fn extreme_value(&self, side: usize) -> Option<K> {
+        if self.root == NIL {
+            return None;
+        }
+        let mut node = &self.nodes[self.root as usize];
+        while node.children[side] != NIL {
+            node = &self.nodes[node.children[side] as usize];
+        }
+        Some(node.key)
+    }
+
+    /// Store a new leaf node, reusing a free slot if there is one, and return its index.
+    fn allocate(&mut self, key: K) -> u32 {
+        let node = IntNode { key, children: [NIL, NIL], height: 1 };
+        match self.free_slots.pop() {
+            Some(idx) => {
+                self.nodes[idx as usize] = node;
+                idx
+            },
+            None => {
+                self.nodes.push(node);
+                (self.nodes.len() - 1) as u32
+            },
+        }
+    }
+
+    fn height(&self, idx: u32) -> u32 {
+        if idx == NIL { 0 } else { self.nodes[idx as usize].height }
+    }
+
+    fn update_height(&mut self, idx: u32) {
+        let [left, right] = self.nodes[idx as usize].children;
+        self.nodes[idx as usize].height = self.height(left).max(self.height(right)).saturating_add(1);
+    }
+
+    /// Rotate the subtree at `idx` so that its child on side `up` becomes the root, returning the new root.
+    fn rotate(&mut self, idx: u32, up: usize) -> u32 {
+        let child = self.nodes[idx as usize].children[up];
+        self.nodes[idx as usize].children[up] = self.nodes[child as usize].children[1 - up];
+        self.nodes[child as usize].children[1 - up] = idx;
+        self.update_height(idx);
+        self.update_height(child);
+        child
+    }
+
+    /// Restore the AVL balance of the subtree at `idx` after one of its children changed, returning the new root.
+    fn rebalance(&mut self, idx: u32) -> u32 {
+        self.update_height(idx);
+        let [left, right] = self.nodes[idx as usize].children;
+        let balancing_factor = i64::from(self.height(right)) - i64::from(self.height(left));
+        if balancing_factor > 1 {
+            // right-heavy; if the right child leans left, that's a Right Left case needing a double rotation
+            let [right_left, right_right] = self.nodes[right as usize].children;
+            if self.height(right_left) > self.height(right_right) {
+                self.nodes[idx as usize].children[1] = self.rotate(right, 0);
+            }
+            let new_root = self.rotate(idx, 1);
+            #[cfg(feature = "paranoid")]
+            self.paranoid_check_rotation(new_root);
+            return new_root;
+        }
+        if balancing_factor < -1 {
+            // left-heavy; if the left child leans right, that's a Left Right case needing a double rotation
+            let [left_left, left_right] = self.nodes[left as usize].children;
+            if self.height(left_right) > self.height(left_left) {
+                self.nodes[idx as usize].children[0] = self.rotate(left, 1);
+            }
+            let new_root = self.rotate(idx, 0);
+            #[cfg(feature = "paranoid")]
+            self.paranoid_check_rotation(new_root);
+            return new_root;
+        }
+        idx
+    }
+
+    /// With the `paranoid` feature, every rotation is followed by a check of the rotated subtree.
+    #[cfg(feature = "paranoid")]
+    fn paranoid_check_rotation(&self, idx: u32) {
+        if let Err(TreeError::InvariantViolation(problem)) = self.check_subtree(idx, &mut String::from("rotated subtree"), None, None) {
+            panic!("IntSet rotation broke the tree: {problem} (shape {})", self.outline(idx));
+        }
+    }
+
+    /// Insert the key into the subtree at `idx`, returning the subtree's new root.
+    fn insert_into(&mut self, idx: u32, key: K) -> Result<u32,TreeError<K>> {
+        if idx == NIL {
+            return Ok(self.allocate(key));
+        }
+        let node_key = self.nodes[idx as usize].key;
+        if key == node_key {
+            // no duplicates allowed in this kind of tree
+            return Err(TreeError::ValueAlreadyStored { value: key });
+        }
+        let side = usize::from(key > node_key);
+        let new_child = self.insert_into(self.nodes[idx as usize].children[side], key)?;
+        self.nodes[idx as usize].children[side] = new_child;
+        Ok(self.rebalance(idx))
+    }
+
+    /// Remove the key from the subtree at `idx`, returning the subtree's new root.
+    fn remove_from(&mut self, idx: u32, key: K) -> Result<u32,TreeError<K>> {
+        if idx == NIL {
+            return Err(TreeError::ValueNotFound { value: key });
+        }
+        let node_key = self.nodes[idx as usize].key;
+        if key != node_key {
+            let side = usize::from(key > node_key);
+            let new_child = self.remove_from(self.nodes[idx as usize].children[side], key)?;
+            self.nodes[idx as usize].children[side] = new_child;
+            return Ok(self.rebalance(idx));
+        }
+        self.free_slots.push(idx);
+        let [left, right] = self.nodes[idx as usize].children;
+        if left == NIL {
+            return Ok(right);
+        }
+        if right == NIL {
+            return Ok(left);
+        }
+        // both children are branches: the successor node takes this node's place
+        let (new_right, successor) = self.remove_least(right);
+        self.nodes[successor as usize].children = [left, new_right];
+        Ok(self.rebalance(successor))
+    }
+
+    /// Unlink the node with the least key in the subtree at `idx`, returning the subtree's new root and the unlinked node.
+    fn remove_least(&mut self, idx: u32) -> (u32, u32) {
+        let [left, right] = self.nodes[idx as usize].children;
+        if left == NIL {
+            return (right, idx);
+        }
+        let (new_left, least) = self.remove_least(left);
+        self.nodes[idx as usize].children[0] = new_left;
+        (self.rebalance(idx), least)
+    }
+
+}
+
+impl <K: IntKey> Default for IntSet<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <K: IntKey + std::fmt::Debug> fmt::Debug for IntSet<K> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("IntSet")
+            .field("size", &self.get_size())
+            .field("values", &self.as_vec())
+            .finish()
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_and_contains() {
+        let mut my_set = IntSet::<u32>::new();
+        assert_eq!( None, my_set.least_value() );
+        assert_eq!( Ok(AddReport { inserted: 100, skipped: 0 }), my_set.add_all((0..100).rev()) );
+        assert_eq!( 100, my_set.get_size() );
+        assert_eq!( Err(TreeError::ValueAlreadyStored { value: 42 }), my_set.add(42) );
+        assert!( my_set.contains(0) );
+        assert!( my_set.contains(99) );
+        assert!( !my_set.contains(100) );
+        assert_eq!( Some(0), my_set.least_value() );
+        assert_eq!( Some(99), my_set.greatest_value() );
+        assert_eq!( (0..100).collect::<Vec<u32>>(), my_set.as_vec() );
+        assert_eq!( 7, my_set.nodes[my_set.root as usize].height );
+        assert_eq!( Ok(()), my_set.check_invariants() );
+    }
+
+    #[test]
+    fn drop_values_and_reuse_slots() {
+        let mut my_set = IntSet::<u64>::from_collection((0..64).map(|i| i * 7 % 64));
+        for key in (0..64).step_by(2) {
+            assert_eq!( Ok(()), my_set.drop_value(key) );
+            assert_eq!( Ok(()), my_set.check_invariants() );
+        }
+        assert_eq!( Err(TreeError::ValueNotFound { value: 0 }), my_set.drop_value(0) );
+        assert_eq!( 32, my_set.get_size() );
+        assert_eq!( (0..64).filter(|k| k % 2 == 1).collect::<Vec<u64>>(), my_set.as_vec() );
+
+        // dropped nodes' slots get reused rather than growing the storage
+        assert_eq!( Ok(AddReport { inserted: 32, skipped: 0 }), my_set.add_all((0..64).step_by(2)) );
+        assert_eq!( 64, my_set.nodes.len() );
+        assert_eq!( Ok(()), my_set.check_invariants() );
+    }
+
+    #[test]
+    fn detect_broken_invariants() {
+        let mut my_set = IntSet::<u32>::from_collection([2,1,3]);
+        my_set.nodes[my_set.root as usize].key = 0;
+        assert_eq!( Err(TreeError::InvariantViolation("value at root.left is out of order".to_string())), my_set.check_invariants() );
+        let mut my_set = IntSet::<u32>::from_collection([2,1,3]);
+        my_set.size = 4;
+        assert_eq!( Err(TreeError::InvariantViolation("size is 4 but the set holds 3 values".to_string())), my_set.check_invariants() );
+    }
+
+    #[test]
+    fn preallocate_storage() {
+        let mut my_set = IntSet::<u32>::with_capacity(10);
+        assert!( my_set.capacity() >= 10 );
+        let reserved = my_set.nodes.capacity();
+        let _ = my_set.add_all(0..10);
+        assert_eq!( reserved, my_set.nodes.capacity() ); // no growth was needed
+        let _ = my_set.drop_value(3);
+        assert!( my_set.capacity() >= 10 ); // the dropped node's slot still counts
+        my_set.reserve(100);
+        assert!( my_set.capacity() >= 109 );
+
+        let my_set = IntSet::<u64>::from_collection(0..1000);
+        assert!( my_set.nodes.capacity() >= 1000 ); // sized from the range's exact size hint
+    }
+
+    #[test]
+    fn indices_stop_short_of_nil() {
+        let mut my_set = IntSet::<u32>::from_collection([1,2,3]);
+        my_set.size = MAX_SIZE; // as if it held all it can
+        assert_eq!( Err(TreeError::CapacityExceeded { value: 4 }), my_set.add(4) );
+        assert!( !my_set.contains(4) );
+    }
+
+}
@@ -5,15 +5,143 @@
 //! - `Jbst` : "Joe's BST", a simple binary search tree storing unique values in order (i.e. an ordered set).
 //! - `Jblst` : "Joe's B(list-like)ST", a simple binary (list-like) search tree allowing duplicate entries (i.e. an ordered list).
 //! - `Javlt` : "Joe's AVL Tree", a **self-balancing** AVL tree storing unique values in order (i.e. an ordered set with guaranteed O(log(n)) lookups).
+//! 
+//! The `OrderedSet` trait (in `ordered`) covers what the three have in common, for code that should work
+//! with any of them; `OrderedMultiset` adds `Jblst`'s counting of duplicates.
+//! 
+//! Since `Jbst` and `Jblst` aren't balanced, they refuse values that would go deeper than a depth limit (see
+//! `jbst::DEFAULT_DEPTH_LIMIT`), returning an error rather than risking a stack overflow.
+//! 
+//! A `StableJblst` (in `stable`) is a `Jblst` that keeps every equal value it's given, in the order they were
+//! added, rather than counting copies of one.
+//! 
+//! A `BoundedJavlt` keeps only the k greatest (or least) values added to it, evicting from the far end.
+//! `Javlt::builder()` (see `builder`) makes either kind of tree from one set of options.
+//! 
+//! An `AdaptiveSet` (in `adaptive`) starts out as a sorted Vec, which is best while it's small, and turns
+//! itself into a `Javlt` once it grows or changes too much.
+//! 
+//! An `AggregateJavlt` keeps a summary (a sum, a count, or your own) of each subtree, to summarize any
+//! range of values in O(log(n)).
+//! 
+//! To share a `Javlt` between threads, wrap it in a `JavltSync`, which takes care of the locking.
+//! Where that one lock is too contended, `ConcurrentSet` is a skip list that locks only the few nodes
+//! each change touches, and whose lookups take no locks at all.  For read-mostly sharing, `SnapshotJavlt`
+//! hands readers unchanging snapshots (`PersistentJavlt`s, which share their nodes between versions) while
+//! a writer carries on.
+//! 
+//! `VersionedMap` (in `versioned`) is an ordered map whose commits each make a version that can still be
+//! read later, for point-in-time reads.
+//! 
+//! A `LinkedJavlt` keeps a link from each node to its parent, so its cursors step between values without
+//! going back to the root, and can remove values as they go.
+//! 
+//! Any of these can be frozen into a read-only `FrozenSet`, which trades away mutability for faster lookups.
+//! Where most lookups are misses, a `FilteredJavlt` (in `bloom`) turns them away with a Bloom filter before
+//! searching the tree.
+//! 
+//! For sets of integer keys, `IntSet` is a leaner AVL tree that skips the generic trees' per-node allocations.
+//! For `u64` keys spread over their whole range, such as random IDs, a `RadixSet` (in `radix`) splits them
+//! by their top byte among 256 small trees.  A `ZOrderIndex` (in `zorder`) stores 2D points in one as
+//! interleaved-bit keys, to find the points in a rectangle.
+//! 
+//! `JavltMap` (in `javlt_map`) is an ordered map of keys to values, kept in a `Javlt` by key.
+//! `ExpiringMap` is an ordered map whose entries expire, for small caches that need ordered key scans.
+//! A `TimeIndex` (in `time_index`) keeps timestamped values in time order, to look them up by time.
+//! 
+//! An `Interner` (in `interner`) keeps one copy of each string it's given and hands out a `Symbol` for it.
+//! For trees of strings that ignore case, `Javlt::case_insensitive` stores them as `Folded` strings (see `folded`).
+//! To order strings by other rules, such as a language's alphabetical order, wrap them with a `Collator` (see `collation`).
+//! 
+//! `QuantileTracker` answers quantile queries (medians, percentiles) over a stream of values.
+//! 
+//! `LineIndex` (in `lines`) maps line numbers to byte offsets and back in a text buffer being edited.
+//! 
+//! The `jhuffman` module builds Huffman trees from symbol frequencies, to encode and decode bytes.
+//! 
+//! Trees of tuples are ordered field by field; the `composite` module adds range queries by a leading part
+//! of the tuple (a prefix), e.g. every `(priority, timestamp, id)` with a given priority.
+//! 
+//! `Jbst`, `Jblst` and `Javlt` can be saved to a file (or a byte vector) and loaded again; see the `codec` module for the format.
+//! 
+//! A `DurableJavlt` (in `durable`) keeps a `Javlt` in a file with a write-ahead log, so that it survives crashes.
+//! 
+//! With the `oracle` feature, the `oracle` module checks the set-like trees against `BTreeSet`, step by step,
+//! over random sequences of changes.  With the `testing` feature, `assert_tree_eq!` (in `testing`) compares
+//! two trees' values and describes any differences, with both trees' shapes.
+//! 
+//! With the `wasm` feature, the `wasm` module wraps `Javlt`s of numbers and strings for use from JavaScript.
+//! With the `ffi` feature, the `ffi` module exports C functions over a `Javlt<u64>`.
+//! With the `python` feature, the `python` module makes `Javlt` and `Jblst` into Python classes.
+//! With the `tracing` feature, `Javlt` reports its rotations, rebalancing, splits and merges through the `tracing` crate.
+//! With the `metrics` feature, the `metrics` module counts the work `Jbst` and `Javlt` do (comparisons,
+//! rotations, nodes allocated and freed, depths reached), to compare them on a workload.
 
 pub mod jbst;
 pub mod jblst;
+pub mod stable;
 pub mod javlt;
+pub mod ordered;
+pub mod bounded;
+pub mod builder;
+pub mod adaptive;
+pub mod aggregate;
+pub mod javlt_sync;
+pub mod javlt_map;
+pub mod concurrent;
+pub mod persistent;
+pub mod linked;
+pub mod snapshot;
+pub mod versioned;
+pub mod frozen;
+pub mod bloom;
+pub mod jintset;
+pub mod radix;
+pub mod zorder;
+pub mod expiring;
+pub mod time_index;
+pub mod interner;
+pub mod folded;
+pub mod collation;
+pub mod jhuffman;
+pub mod quantile;
+pub mod lines;
+pub mod merge;
+pub mod composite;
+pub mod codec;
+pub mod durable;
+#[cfg(feature = "petgraph")]
+pub mod graph;
+#[cfg(feature = "oracle")]
+pub mod oracle;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "python")]
+pub mod python;
 
 pub mod errors;
+pub mod reports;
+mod visit;
+mod bounds;
 
 pub use jbst::Jbst;
 pub use jblst::Jblst;
 pub use javlt::Javlt;
+pub use ordered::{OrderedSet, OrderedMultiset};
+pub use bounded::BoundedJavlt;
+pub use javlt_sync::JavltSync;
+pub use javlt_map::JavltMap;
+pub use concurrent::ConcurrentSet;
+pub use snapshot::SnapshotJavlt;
+pub use frozen::FrozenSet;
+pub use jintset::IntSet;
+pub use expiring::ExpiringMap;
+pub use quantile::QuantileTracker;
 
 
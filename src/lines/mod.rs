@@ -0,0 +1,505 @@
+use std::{cmp::max, fmt, ops::Range};
+
+use crate::errors::TreeError;
+
+
+
+/// # Line Index
+///
+/// Maps between line numbers and byte offsets in a text buffer, for editors and the like.  The text itself
+/// isn't stored, only the length of each line (including its `\n`), kept in order in an AVL tree whose nodes
+/// also count the lines and bytes in their subtrees.  Looking up a line's offset, or the line holding an
+/// offset, is a descent guided by those counts, so it takes O(log(n)) time; and an edit only changes the
+/// lines it touches, so the lines after it shift along without being visited.
+///
+/// Lines and offsets count from 0.  There's always at least one line: the last one, which has no `\n` and
+/// may be empty.
+///
+///     use jtree::lines::LineIndex;
+///
+///     let mut index = LineIndex::from_text("fn main() {\n}\n");
+///     assert_eq!( 3, index.line_count() );
+///     assert_eq!( Ok(12), index.offset_of_line(1) );
+///     index.insert_bytes(12, b"    println!();\n").unwrap();
+///     assert_eq!( Ok(28), index.offset_of_line(2) );
+///     assert_eq!( Ok(1), index.line_of_offset(20) );
+///     index.delete_range(11..28).unwrap(); // back to "fn main() {}\n"
+///     assert_eq!( 2, index.line_count() );
+pub struct LineIndex {
+    root: Link, // never empty
+}
+
+type Link = Option<Box<Node>>;
+
+impl LineIndex {
+
+    /// Create an index of an empty text: a single line with no bytes.
+    pub fn new() -> Self {
+        Self::from_text("")
+    }
+
+    /// Create an index of the lines in the text.
+    pub fn from_text<B: AsRef<[u8]>>(text: B) -> Self {
+        Self { root: Node::build(&line_lengths(text.as_ref())) }
+    }
+
+    /// Get the number of lines.
+    pub fn line_count(&self) -> usize {
+        Node::lines_of(&self.root)
+    }
+
+    /// Get the length of the text, in bytes.
+    pub fn len(&self) -> usize {
+        Node::bytes_of(&self.root)
+    }
+
+    /// Returns true if the text has no bytes (so it's a single, empty line).
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The offset of the first byte of the line.  Returns a TreeError::IndexOutOfRange if there's no such line.
+    pub fn offset_of_line(&self, line: usize) -> Result<usize,TreeError<usize>> {
+        Ok(self.line_range(line)?.start)
+    }
+
+    /// The range of offsets the line covers, including its `\n`.  Returns a TreeError::IndexOutOfRange if
+    /// there's no such line.
+    pub fn line_range(&self, line: usize) -> Result<Range<usize>,TreeError<usize>> {
+        if line >= self.line_count() {
+            return Err(TreeError::IndexOutOfRange { index: line, len: self.line_count() });
+        }
+        let (start, len) = Node::find_line(&self.root, line);
+        Ok(start..start + len)
+    }
+
+    /// The line holding the byte at the offset.  The offset just past the end of the text is on the last
+    /// line; beyond that, a TreeError::IndexOutOfRange is returned.
+    pub fn line_of_offset(&self, offset: usize) -> Result<usize,TreeError<usize>> {
+        let (line, _, _) = self.locate(offset)?;
+        Ok(line)
+    }
+
+    /// Record the insertion of the bytes at the offset, splitting the line there at each `\n` they hold.
+    /// Returns a TreeError::IndexOutOfRange if the offset is beyond the end of the text.
+    pub fn insert_bytes(&mut self, offset: usize, bytes: &[u8]) -> Result<(),TreeError<usize>> {
+        let (line, start, len) = self.locate(offset)?;
+        let column = offset - start;
+        let pieces = line_lengths(bytes);
+        match pieces.split_first() {
+            Some((only, [])) => Node::set_len(&mut self.root, line, len + only),
+            Some((first, rest)) => {
+                // the line ends after the first piece, and the rest of it follows the last piece
+                Node::set_len(&mut self.root, line, column + first);
+                for (i, piece) in rest.iter().enumerate() {
+                    let piece_len = if i == rest.len() - 1 { piece + len - column } else { *piece };
+                    Node::insert_at(&mut self.root, line + 1 + i, piece_len);
+                }
+            },
+            None => (),
+        }
+        #[cfg(feature = "paranoid")]
+        self.paranoid_check("insert_bytes");
+        Ok(())
+    }
+
+    /// Record the deletion of the bytes in the range, joining the lines at either end of it.  Returns a
+    /// TreeError::InvalidRange if the range's start is after its end, or a TreeError::IndexOutOfRange if
+    /// it goes beyond the end of the text.
+    pub fn delete_range(&mut self, range: Range<usize>) -> Result<(),TreeError<usize>> {
+        if range.start > range.end {
+            return Err(TreeError::InvalidRange);
+        }
+        let (last, last_start, last_len) = self.locate(range.end)?;
+        let (first, first_start, first_len) = self.locate(range.start)?;
+        if first == last {
+            Node::set_len(&mut self.root, first, first_len - range.len());
+        } else {
+            // what's left of the first line is joined by what's left of the last
+            Node::set_len(&mut self.root, first, range.start - first_start + last_start + last_len - range.end);
+            for _ in first..last {
+                Node::remove_at(&mut self.root, first + 1);
+            }
+        }
+        #[cfg(feature = "paranoid")]
+        self.paranoid_check("delete_range");
+        Ok(())
+    }
+
+    /// Verify the index's internal consistency: that every node's height, line count and byte count are
+    /// correct, its subtrees are balanced, and every line but the last has room for its `\n`.  Returns a
+    /// TreeError::InvariantViolation describing the first problem found, if any.
+    pub fn check_invariants(&self) -> Result<(),TreeError<usize>> {
+        return match &self.root {
+            None => Err(TreeError::InvariantViolation(String::from("there are no lines"))),
+            Some(branch) => branch.check_invariants(&mut String::from("root"), true),
+        };
+    }
+
+    /// With the `paranoid` feature, every edit is followed by this check, which panics with a description
+    /// of the problem if the edit broke an invariant.
+    #[cfg(feature = "paranoid")]
+    fn paranoid_check(&self, operation: &str) {
+        if let Err(TreeError::InvariantViolation(problem)) = self.check_invariants() {
+            panic!("LineIndex::{operation} broke the tree: {problem} ({} lines)", self.line_count());
+        }
+    }
+
+    /// The line holding the offset, with the line's start and length.
+    fn locate(&self, offset: usize) -> Result<(usize, usize, usize),TreeError<usize>> {
+        if offset > self.len() {
+            return Err(TreeError::IndexOutOfRange { index: offset, len: self.len() });
+        }
+        Ok(Node::find_offset(&self.root, offset))
+    }
+
+}
+
+impl Default for LineIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for LineIndex {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("LineIndex")
+            .field("lines", &self.line_count())
+            .field("bytes", &self.len())
+            .finish()
+    }
+}
+
+/// The length of each line of the text, including its `\n`; the last line has none.
+fn line_lengths(text: &[u8]) -> Vec<usize> {
+    let mut lengths: Vec<usize> = text.split(|byte| *byte == b'\n').map(|line| line.len() + 1).collect();
+    *lengths.last_mut().unwrap() -= 1;
+    lengths
+}
+
+
+struct Node {
+    len: usize, // of this line, in bytes
+    height: u32,
+    lines: usize, // in this node's whole subtree
+    bytes: usize, // in this node's whole subtree
+    left: Link,
+    right: Link,
+}
+
+// As in `AggregateJavlt`, the changing functions take the link that owns a node, so that a rotation can put a
+// different node in its place, and every node whose children change is brought up to date by `update`.  Lines
+// are found by position: a node's line comes after all of the lines in its left subtree.
+impl Node {
+
+    fn new(len: usize) -> Self {
+        Self {
+            len,
+            height: 1,
+            lines: 1,
+            bytes: len,
+            left: None,
+            right: None,
+        }
+    }
+
+    /// Recursively build a perfectly balanced (sub)tree of the line lengths.
+    fn build(lengths: &[usize]) -> Link {
+        if lengths.is_empty() {
+            return None;
+        }
+        let middle = lengths.len() / 2;
+        let mut node = Node::new(lengths[middle]);
+        node.left = Self::build(&lengths[..middle]);
+        node.right = Self::build(&lengths[middle + 1..]);
+        node.update();
+        Some(Box::new(node))
+    }
+
+    /// The start and length of the line at the position, which must be in the (sub)tree.
+    fn find_line(mut link: &Link, mut line: usize) -> (usize, usize) {
+        let mut start = 0;
+        while let Some(node) = link {
+            let left_lines = Self::lines_of(&node.left);
+            if line < left_lines {
+                link = &node.left;
+            } else if line == left_lines {
+                return (start + Self::bytes_of(&node.left), node.len);
+            } else {
+                start += Self::bytes_of(&node.left) + node.len;
+                line -= left_lines + 1;
+                link = &node.right;
+            }
+        }
+        panic!("LineIndex lost track of a line");
+    }
+
+    /// The position, start and length of the line holding the offset, which is at most the (sub)tree's length.
+    /// The offset at the very end is on the last line.
+    fn find_offset(mut link: &Link, mut offset: usize) -> (usize, usize, usize) {
+        let (mut line, mut start) = (0, 0);
+        while let Some(node) = link {
+            let left_bytes = Self::bytes_of(&node.left);
+            if offset < left_bytes {
+                link = &node.left;
+            } else if offset < left_bytes + node.len || node.right.is_none() {
+                return (line + Self::lines_of(&node.left), start + left_bytes, node.len);
+            } else {
+                line += Self::lines_of(&node.left) + 1;
+                start += left_bytes + node.len;
+                offset -= left_bytes + node.len;
+                link = &node.right;
+            }
+        }
+        panic!("LineIndex lost track of an offset");
+    }
+
+    /// Change the length of the line at the position, which must be in the (sub)tree.
+    fn set_len(link: &mut Link, line: usize, len: usize) {
+        let node = link.as_mut().unwrap();
+        let left_lines = Self::lines_of(&node.left);
+        if line < left_lines {
+            Self::set_len(&mut node.left, line, len);
+        } else if line == left_lines {
+            node.len = len;
+        } else {
+            Self::set_len(&mut node.right, line - left_lines - 1, len);
+        }
+        node.update();
+    }
+
+    /// Insert a line so that it's at the position, then rebalance the (sub)tree.
+    fn insert_at(link: &mut Link, line: usize, len: usize) {
+        let node = match link {
+            None => {
+                *link = Some(Box::new(Node::new(len)));
+                return;
+            },
+            Some(node) => node,
+        };
+        let left_lines = Self::lines_of(&node.left);
+        if line <= left_lines {
+            Self::insert_at(&mut node.left, line, len);
+        } else {
+            Self::insert_at(&mut node.right, line - left_lines - 1, len);
+        }
+        Self::rebalance(link);
+    }
+
+    /// Delete the line at the position, which must be in the (sub)tree, then rebalance it.
+    fn remove_at(link: &mut Link, line: usize) {
+        let node = link.as_mut().unwrap();
+        let left_lines = Self::lines_of(&node.left);
+        if line < left_lines {
+            Self::remove_at(&mut node.left, line);
+        } else if line > left_lines {
+            Self::remove_at(&mut node.right, line - left_lines - 1);
+        } else if node.left.is_none() {
+            // replace the node with its only child (or nothing)
+            let right = node.right.take();
+            *link = right;
+            return;
+        } else if node.right.is_none() {
+            let left = node.left.take();
+            *link = left;
+            return;
+        } else {
+            // both children are branches, so the next line moves up into this node
+            node.len = Self::take_first(&mut node.right);
+        }
+        Self::rebalance(link);
+    }
+
+    /// Delete the first line in a non-empty (sub)tree and return its length.
+    fn take_first(link: &mut Link) -> usize {
+        let node = link.as_mut().unwrap();
+        if node.left.is_some() {
+            let first = Self::take_first(&mut node.left);
+            Self::rebalance(link);
+            return first;
+        }
+        let mut node = link.take().unwrap();
+        *link = node.right.take();
+        node.len
+    }
+
+    /// Bring the node up to date, and rotate the (sub)tree if it has become unbalanced.
+    fn rebalance(link: &mut Link) {
+        let node = match link {
+            Some(node) => node,
+            None => return,
+        };
+        node.update();
+        let bf = node.compute_balancing_factor();
+        if bf > 1 {
+            // right-heavy; if the right child is left-heavy, this is a Right Left situation needing two rotations
+            if node.right.as_ref().unwrap().compute_balancing_factor() < 0 {
+                Self::rotate_right(&mut node.right);
+            }
+            Self::rotate_left(link);
+        } else if bf < -1 {
+            // left-heavy; if the left child is right-heavy, this is a Left Right situation needing two rotations
+            if node.left.as_ref().unwrap().compute_balancing_factor() > 0 {
+                Self::rotate_left(&mut node.left);
+            }
+            Self::rotate_right(link);
+        }
+    }
+
+    /// Make the node's right child the root of its (sub)tree.
+    fn rotate_left(link: &mut Link) {
+        let mut old_root = link.take().unwrap();
+        let mut new_root = old_root.right.take().unwrap();
+        old_root.right = new_root.left.take();
+        old_root.update();
+        new_root.left = Some(old_root);
+        new_root.update();
+        *link = Some(new_root);
+    }
+
+    /// Make the node's left child the root of its (sub)tree.
+    fn rotate_right(link: &mut Link) {
+        let mut old_root = link.take().unwrap();
+        let mut new_root = old_root.left.take().unwrap();
+        old_root.left = new_root.right.take();
+        old_root.update();
+        new_root.right = Some(old_root);
+        new_root.update();
+        *link = Some(new_root);
+    }
+
+    /// Recompute the node's height, line count and byte count from its children's.
+    fn update(&mut self) {
+        self.height = self.compute_height();
+        self.lines = Self::lines_of(&self.left) + 1 + Self::lines_of(&self.right);
+        self.bytes = Self::bytes_of(&self.left) + self.len + Self::bytes_of(&self.right);
+    }
+
+    /// Height of a subtree is the height of its largest child subtree, plus 1.
+    fn compute_height(&self) -> u32 {
+        let left_height = self.left.as_ref().map_or(0, |node| node.height);
+        let right_height = self.right.as_ref().map_or(0, |node| node.height);
+        max(left_height, right_height).saturating_add(1)
+    }
+
+    /// Balancing factor is the height of the right subtree minus the height of the left subtree.
+    fn compute_balancing_factor(&self) -> i64 {
+        let left_height = self.left.as_ref().map_or(0, |node| node.height);
+        let right_height = self.right.as_ref().map_or(0, |node| node.height);
+        i64::from(right_height) - i64::from(left_height)
+    }
+
+    fn lines_of(link: &Link) -> usize {
+        link.as_ref().map_or(0, |node| node.lines)
+    }
+
+    fn bytes_of(link: &Link) -> usize {
+        link.as_ref().map_or(0, |node| node.bytes)
+    }
+
+    /// Recursively verify this (sub)tree's heights, balance and counts.  `is_last` says whether the subtree
+    /// holds the text's last line, the only one that can be empty.
+    /// The path (e.g. "root.left.right") is only used to describe where a problem was found.
+    fn check_invariants(&self, path: &mut String, is_last: bool) -> Result<(),TreeError<usize>> {
+        let path_len = path.len();
+        if let Some(node) = &self.left {
+            path.push_str(".left");
+            node.check_invariants(path, false)?;
+            path.truncate(path_len);
+        }
+        if let Some(node) = &self.right {
+            path.push_str(".right");
+            node.check_invariants(path, is_last)?;
+            path.truncate(path_len);
+        }
+        if self.len == 0 && !(is_last && self.right.is_none()) {
+            return Err(TreeError::InvariantViolation(format!("line at {path} is empty, but isn't the last line")));
+        }
+        if self.height != self.compute_height() {
+            return Err(TreeError::InvariantViolation(format!("node at {path} has height {} but should be {}", self.height, self.compute_height())));
+        }
+        let balancing_factor = self.compute_balancing_factor();
+        if !(-1..=1).contains(&balancing_factor) {
+            return Err(TreeError::InvariantViolation(format!("node at {path} is unbalanced, with a balancing factor of {balancing_factor}")));
+        }
+        let lines = Self::lines_of(&self.left) + 1 + Self::lines_of(&self.right);
+        let bytes = Self::bytes_of(&self.left) + self.len + Self::bytes_of(&self.right);
+        if self.lines != lines || self.bytes != bytes {
+            return Err(TreeError::InvariantViolation(format!("node at {path} counts {} lines and {} bytes but should count {lines} and {bytes}", self.lines, self.bytes)));
+        }
+        Ok(())
+    }
+
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check every line and offset of the index against the text it should describe.
+    fn assert_matches(index: &LineIndex, text: &[u8]) {
+        assert_eq!( Ok(()), index.check_invariants() );
+        assert_eq!( text.len(), index.len() );
+        let starts: Vec<usize> = std::iter::once(0).chain(text.iter().enumerate().filter(|(_, byte)| **byte == b'\n').map(|(i, _)| i + 1)).collect();
+        assert_eq!( starts.len(), index.line_count() );
+        for (line, start) in starts.iter().enumerate() {
+            assert_eq!( Ok(*start), index.offset_of_line(line) );
+        }
+        for offset in 0..=text.len() {
+            let expected = starts.iter().rposition(|start| *start <= offset).unwrap();
+            assert_eq!( Ok(expected), index.line_of_offset(offset) );
+        }
+    }
+
+    #[test]
+    fn lookups() {
+        let index = LineIndex::from_text("ab\n\ncd\nef");
+        assert_matches(&index, b"ab\n\ncd\nef");
+        assert_eq!( Ok(4..7), index.line_range(2) );
+        assert_eq!( Err(TreeError::IndexOutOfRange { index: 4, len: 4 }), index.offset_of_line(4) );
+        assert_eq!( Err(TreeError::IndexOutOfRange { index: 10, len: 9 }), index.line_of_offset(10) );
+        assert_matches(&LineIndex::new(), b"");
+        assert_matches(&LineIndex::from_text("\n"), b"\n");
+    }
+
+    #[test]
+    fn edits_match_the_text() {
+        let mut text = b"one\ntwo\nthree".to_vec();
+        let mut index = LineIndex::from_text(&text);
+        let mut state: u64 = 0x2545_F491_4F6C_DD1D;
+        let mut random = |bound: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % bound as u64) as usize
+        };
+        let snippets: [&[u8]; 5] = [b"x", b"\n", b"yz\n\nw", b"\n\n\n", b"long line of text"];
+        for _ in 0..300 {
+            if random(4) == 0 && !text.is_empty() {
+                let start = random(text.len() + 1);
+                let end = start + random(text.len() - start + 1).min(8);
+                text.drain(start..end);
+                index.delete_range(start..end).unwrap();
+            } else {
+                let offset = random(text.len() + 1);
+                let snippet = snippets[random(snippets.len())];
+                text.splice(offset..offset, snippet.iter().copied());
+                index.insert_bytes(offset, snippet).unwrap();
+            }
+            assert_matches(&index, &text);
+        }
+        assert!( index.line_count() > 20 );
+    }
+
+    #[test]
+    fn bad_edits_are_refused() {
+        let mut index = LineIndex::from_text("abc\ndef");
+        assert_eq!( Err(TreeError::IndexOutOfRange { index: 8, len: 7 }), index.insert_bytes(8, b"x") );
+        assert_eq!( Err(TreeError::InvalidRange), index.delete_range(Range { start: 5, end: 2 }) );
+        assert_eq!( Err(TreeError::IndexOutOfRange { index: 9, len: 7 }), index.delete_range(2..9) );
+        assert_matches(&index, b"abc\ndef");
+    }
+
+}
@@ -0,0 +1,664 @@
+use std::{fmt, iter::FusedIterator};
+
+use crate::errors::TreeError;
+use crate::reports::AddReport;
+
+
+
+/// Marks a link that doesn't point to any node.
+const NIL: u32 = u32::MAX;
+
+/// # Linked AVL Tree
+///
+/// An AVL tree (like `Javlt`) whose nodes also link to their parents and are threaded: each one links to the
+/// nodes before and after it in order, too.  So a cursor steps to the next or previous value in O(1) time,
+/// holding nothing but the current node's index, rather than keeping (or rebuilding) the path down from the
+/// root, and iterating over the whole tree needs no stack.  That makes it the better backbone for cursors
+/// on very deep trees, and for `CursorMut`, which can remove values without searching for them again.
+///
+/// Like `IntSet`, all the nodes live in one `Vec` and link to each other with `u32` indices; a dropped node's
+/// slot is filled by moving the last node into it.
+///
+///     use jtree::linked::LinkedJavlt;
+///
+///     let mut tree = LinkedJavlt::from_collection([40,10,30,20]);
+///     let mut cursor = tree.cursor_mut_at(&20);
+///     assert_eq!( Some(20), cursor.remove_current() );
+///     assert_eq!( Some(&30), cursor.peek() );
+///     assert_eq!( Some(&10), cursor.move_prev() );
+///     assert_eq!( vec!(10,30,40), tree.as_vec() );
+pub struct LinkedJavlt<T: PartialEq + PartialOrd + Clone> {
+    nodes: Vec<LinkedNode<T>>,
+    root: u32,
+}
+
+struct LinkedNode<T> {
+    value: T,
+    children: [u32; 2], // left, right
+    parent: u32,
+    threads: [u32; 2], // the previous and next nodes in order (NIL at the ends)
+    height: u32,
+}
+
+impl <T: PartialEq + PartialOrd + Clone> LinkedJavlt<T> {
+
+    /// Create a new tree with no data
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            root: NIL,
+        }
+    }
+
+    /// Create a new tree from a collection (vector, array, or whatever), skipping duplicates.
+    pub fn from_collection<U: IntoIterator<Item = T>>(collection: U) -> Self {
+        let mut new_tree = Self::new();
+        let _ = new_tree.add_all(collection);
+        new_tree
+    }
+
+    /// Insert a value
+    pub fn add(&mut self, value: T) -> Result<(),TreeError<T>> {
+        // NIL is the one index a node can't have
+        if self.nodes.len() == NIL as usize {
+            return Err(TreeError::CapacityExceeded { value });
+        }
+        let mut parent = NIL;
+        let mut side = 0;
+        let mut idx = self.root;
+        while idx != NIL {
+            let node = &self.nodes[idx as usize];
+            if value == node.value {
+                // no duplicates allowed in this kind of tree
+                return Err(TreeError::ValueAlreadyStored { value });
+            }
+            parent = idx;
+            side = usize::from(value > node.value);
+            idx = node.children[side];
+        }
+        // a new left child comes just before its parent in order, and a new right child just after it
+        let mut threads = [NIL, NIL];
+        if parent != NIL {
+            threads[1 - side] = parent;
+            threads[side] = self.nodes[parent as usize].threads[side];
+        }
+        let new_idx = self.nodes.len() as u32;
+        self.nodes.push(LinkedNode { value, children: [NIL, NIL], parent, threads, height: 1 });
+        match parent {
+            NIL => self.root = new_idx,
+            _ => self.nodes[parent as usize].children[side] = new_idx,
+        }
+        for side in [0, 1] {
+            if threads[side] != NIL {
+                self.nodes[threads[side] as usize].threads[1 - side] = new_idx;
+            }
+        }
+        self.rebalance_upwards(parent);
+        #[cfg(feature = "paranoid")]
+        self.paranoid_check("add");
+        Ok(())
+    }
+
+    /// Adds all members of a collection (vector, array, or whatever) to the tree,
+    /// skipping over any that would be duplicates, so no error will stop the batch.
+    /// The returned report says how many values were inserted and how many were skipped.
+    pub fn add_all<U: IntoIterator<Item = T>>(&mut self, collection: U) -> Result<AddReport,TreeError<T>> {
+        let mut report = AddReport::default();
+        for elem in collection.into_iter() {
+            match self.add(elem) {
+                Ok(()) => report.inserted += 1,
+                Err(_) => report.skipped += 1,
+            }
+        }
+        Ok(report)
+    }
+
+    /// If the value is in the tree, delete it.  Otherwise a TreeError::ValueNotFound will be returned.
+    pub fn drop_value(&mut self, value: T) -> Result<(),TreeError<T>> {
+        let idx = self.find(&value);
+        if idx == NIL {
+            return Err(TreeError::ValueNotFound { value });
+        }
+        self.remove(idx);
+        #[cfg(feature = "paranoid")]
+        self.paranoid_check("drop_value");
+        Ok(())
+    }
+
+    /// Get the number of values in the tree
+    pub fn get_size(&self) -> u32 {
+        self.nodes.len() as u32
+    }
+
+    /// Returns true if the value is currently a member of the tree
+    pub fn contains(&self, value: &T) -> bool {
+        self.find(value) != NIL
+    }
+
+    /// Returns all the values in the tree as an ordered Vec from least to greatest.
+    pub fn as_vec(&self) -> Vec<T> {
+        let mut vals = Vec::with_capacity(self.nodes.len());
+        vals.extend(self.iter().cloned());
+        vals
+    }
+
+    /// Returns the smallest/lowest value in the tree, if any.
+    pub fn least_value(&self) -> Option<T> {
+        self.cursor_front().peek().cloned()
+    }
+
+    /// Returns the largest/highest value in the tree, if any.
+    pub fn greatest_value(&self) -> Option<T> {
+        self.cursor_back().peek().cloned()
+    }
+
+    /// Iterate over the values in order, from least to greatest.  This is the value at `cursor_front` and then
+    /// the cursor itself, so it needs no memory beyond its current node's index.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        let front = self.cursor_front();
+        front.peek().into_iter().chain(front)
+    }
+
+    /// Returns a cursor positioned at the value, or if it's not in the tree, at the least value greater than it
+    /// (or the ghost position past the end, if there's none).  From there, the cursor can walk to neighbouring values
+    /// in either direction.
+    ///
+    ///     use jtree::linked::LinkedJavlt;
+    ///
+    ///     let my_tree = LinkedJavlt::from_collection([10,20,30,40]);
+    ///     let mut cursor = my_tree.cursor_at(&25);
+    ///     assert_eq!( Some(&30), cursor.peek() );
+    ///     assert_eq!( Some(&40), cursor.next() );
+    ///     assert_eq!( None, cursor.next() ); // past the end
+    ///     assert_eq!( Some(&40), cursor.prev() );
+    pub fn cursor_at(&self, value: &T) -> Cursor<'_, T> {
+        Cursor { tree: self, idx: self.seek(value) }
+    }
+
+    /// Returns a cursor positioned at the least value (or the ghost position, if the tree is empty).
+    pub fn cursor_front(&self) -> Cursor<'_, T> {
+        Cursor { tree: self, idx: self.step(NIL, 1) }
+    }
+
+    /// Returns a cursor positioned at the greatest value (or the ghost position, if the tree is empty).
+    pub fn cursor_back(&self) -> Cursor<'_, T> {
+        Cursor { tree: self, idx: self.step(NIL, 0) }
+    }
+
+    /// Returns a cursor that can remove values as it walks the tree, positioned like `cursor_at`.
+    pub fn cursor_mut_at(&mut self, value: &T) -> CursorMut<'_, T> {
+        let idx = self.seek(value);
+        CursorMut { tree: self, idx }
+    }
+
+    /// Returns a cursor that can remove values as it walks the tree, positioned at the least value.
+    pub fn cursor_mut_front(&mut self) -> CursorMut<'_, T> {
+        let idx = self.step(NIL, 1);
+        CursorMut { tree: self, idx }
+    }
+
+    /// Returns a cursor that can remove values as it walks the tree, positioned at the greatest value.
+    pub fn cursor_mut_back(&mut self) -> CursorMut<'_, T> {
+        let idx = self.step(NIL, 0);
+        CursorMut { tree: self, idx }
+    }
+
+    /// Verify the tree's internal consistency: that values are in order, every node's height is correct and
+    /// its subtrees are balanced, every node links back to its parent, the threads link the nodes in order,
+    /// and every node is in the tree.
+    /// Returns a TreeError::InvariantViolation describing the first problem found, if any.
+    pub fn check_invariants(&self) -> Result<(),TreeError<T>> {
+        if self.root != NIL && self.nodes[self.root as usize].parent != NIL {
+            return Err(TreeError::InvariantViolation(String::from("the root has a parent")));
+        }
+        let mut previous = NIL;
+        let (_, nodes) = self.check_subtree(self.root, &mut String::from("root"), None, None, &mut previous)?;
+        if previous != NIL && self.nodes[previous as usize].threads[1] != NIL {
+            return Err(TreeError::InvariantViolation(String::from("the last node is threaded to a next one")));
+        }
+        if nodes as usize != self.nodes.len() {
+            return Err(TreeError::InvariantViolation(format!("{} nodes aren't in the tree", self.nodes.len() - nodes as usize)));
+        }
+        Ok(())
+    }
+
+    /// Recursively verify the subtree at `idx`, returning its height and number of nodes.  `previous` is the
+    /// last node visited in order, whose thread should lead to the next one visited.
+    /// The path (e.g. "root.left.right") is only used to describe where a problem was found.
+    fn check_subtree(&self, idx: u32, path: &mut String, lower: Option<&T>, upper: Option<&T>, previous: &mut u32) -> Result<(u32,u32),TreeError<T>> {
+        if idx == NIL {
+            return Ok((0, 0));
+        }
+        let node = &self.nodes[idx as usize];
+        if lower.is_some_and(|lower| node.value <= *lower) || upper.is_some_and(|upper| node.value >= *upper) {
+            return Err(TreeError::InvariantViolation(format!("value at {path} is out of order")));
+        }
+        for child in node.children {
+            if child != NIL && self.nodes[child as usize].parent != idx {
+                return Err(TreeError::InvariantViolation(format!("a child of the node at {path} doesn't link back to it")));
+            }
+        }
+        let path_len = path.len();
+        path.push_str(".left");
+        let (left_height, left_nodes) = self.check_subtree(node.children[0], path, lower, Some(&node.value), previous)?;
+        path.truncate(path_len);
+        if node.threads[0] != *previous || (*previous != NIL && self.nodes[*previous as usize].threads[1] != idx) {
+            return Err(TreeError::InvariantViolation(format!("node at {path} isn't threaded to the node before it")));
+        }
+        *previous = idx;
+        path.push_str(".right");
+        let (right_height, right_nodes) = self.check_subtree(node.children[1], path, Some(&node.value), upper, previous)?;
+        path.truncate(path_len);
+        if node.height != left_height.max(right_height) + 1 {
+            return Err(TreeError::InvariantViolation(format!("node at {path} has height {} but should be {}", node.height, left_height.max(right_height) + 1)));
+        }
+        if left_height.abs_diff(right_height) > 1 {
+            return Err(TreeError::InvariantViolation(format!("node at {path} is unbalanced")));
+        }
+        Ok((node.height, left_nodes + right_nodes + 1))
+    }
+
+    /// With the `paranoid` feature, every change to the tree is followed by this check, which panics
+    /// with a description of the problem and the tree's shape if the change broke an invariant.
+    #[cfg(feature = "paranoid")]
+    fn paranoid_check(&self, operation: &str) {
+        if let Err(TreeError::InvariantViolation(problem)) = self.check_invariants() {
+            panic!("LinkedJavlt::{operation} broke the tree: {problem} (size {}, shape {})", self.nodes.len(), self.outline(self.root));
+        }
+    }
+
+    /// Sketch the shape of the subtree at `idx`, for the `paranoid` feature's diagnostics.
+    /// Nodes are shown by their heights, e.g. `(. 2 (. 1 .))`.
+    #[cfg(feature = "paranoid")]
+    fn outline(&self, idx: u32) -> String {
+        if idx == NIL {
+            return String::from(".");
+        }
+        let node = &self.nodes[idx as usize];
+        format!("({} {} {})", self.outline(node.children[0]), node.height, self.outline(node.children[1]))
+    }
+
+    /// The index of the node holding the value, or NIL if there's none.
+    fn find(&self, value: &T) -> u32 {
+        let mut idx = self.root;
+        while idx != NIL {
+            let node = &self.nodes[idx as usize];
+            if *value == node.value {
+                return idx;
+            }
+            idx = node.children[usize::from(*value > node.value)];
+        }
+        NIL
+    }
+
+    /// The index of the least node whose value is at least `value`, or NIL if there's none.
+    fn seek(&self, value: &T) -> u32 {
+        let mut found = NIL;
+        let mut idx = self.root;
+        while idx != NIL {
+            let node = &self.nodes[idx as usize];
+            if *value == node.value {
+                return idx;
+            }
+            if *value < node.value {
+                found = idx;
+                idx = node.children[0];
+            } else {
+                idx = node.children[1];
+            }
+        }
+        found
+    }
+
+    /// The node after (side 1) or before (side 0) the one at `idx` in order, or NIL past the end.  From NIL
+    /// (the ghost position), this is the first or last node.
+    fn step(&self, idx: u32, side: usize) -> u32 {
+        return match idx {
+            NIL => self.extreme(self.root, 1 - side),
+            _ => self.nodes[idx as usize].threads[side],
+        };
+    }
+
+    /// Follow the left (side 0) or right (side 1) children down from `idx` as far as they go.
+    fn extreme(&self, mut idx: u32, side: usize) -> u32 {
+        if idx == NIL {
+            return NIL;
+        }
+        while self.nodes[idx as usize].children[side] != NIL {
+            idx = self.nodes[idx as usize].children[side];
+        }
+        idx
+    }
+
+    fn height(&self, idx: u32) -> u32 {
+        if idx == NIL { 0 } else { self.nodes[idx as usize].height }
+    }
+
+    fn update_height(&mut self, idx: u32) {
+        let [left, right] = self.nodes[idx as usize].children;
+        self.nodes[idx as usize].height = self.height(left).max(self.height(right)).saturating_add(1);
+    }
+
+    /// Point the parent's link to `old` (or the root, if there's no parent) at `new` instead.
+    fn replace_child(&mut self, parent: u32, old: u32, new: u32) {
+        if parent == NIL {
+            self.root = new;
+            return;
+        }
+        let children = &mut self.nodes[parent as usize].children;
+        let side = usize::from(children[1] == old);
+        children[side] = new;
+    }
+
+    /// Rotate the subtree at `idx` so that its child on side `up` becomes the root, returning the new root.
+    fn rotate(&mut self, idx: u32, up: usize) -> u32 {
+        let child = self.nodes[idx as usize].children[up];
+        let inner = self.nodes[child as usize].children[1 - up];
+        let parent = self.nodes[idx as usize].parent;
+        self.nodes[idx as usize].children[up] = inner;
+        if inner != NIL {
+            self.nodes[inner as usize].parent = idx;
+        }
+        self.nodes[child as usize].children[1 - up] = idx;
+        self.nodes[idx as usize].parent = child;
+        self.nodes[child as usize].parent = parent;
+        self.replace_child(parent, idx, child);
+        self.update_height(idx);
+        self.update_height(child);
+        child
+    }
+
+    /// Restore the AVL balance of the subtree at `idx` after one of its children changed.
+    fn rebalance(&mut self, idx: u32) {
+        self.update_height(idx);
+        let [left, right] = self.nodes[idx as usize].children;
+        let balancing_factor = i64::from(self.height(right)) - i64::from(self.height(left));
+        if balancing_factor > 1 {
+            // right-heavy; if the right child leans left, that's a Right Left case needing a double rotation
+            let [right_left, right_right] = self.nodes[right as usize].children;
+            if self.height(right_left) > self.height(right_right) {
+                self.rotate(right, 0);
+            }
+            self.rotate(idx, 1);
+        } else if balancing_factor < -1 {
+            // left-heavy; if the left child leans right, that's a Left Right case needing a double rotation
+            let [left_left, left_right] = self.nodes[left as usize].children;
+            if self.height(left_right) > self.height(left_left) {
+                self.rotate(left, 1);
+            }
+            self.rotate(idx, 0);
+        }
+    }
+
+    /// Rebalance the node at `idx` and each of its ancestors in turn, up to the root.
+    fn rebalance_upwards(&mut self, mut idx: u32) {
+        while idx != NIL {
+            let parent = self.nodes[idx as usize].parent;
+            self.rebalance(idx);
+            idx = parent;
+        }
+    }
+
+    /// Unlink the node at `idx` from the tree, rebalance, and free its slot, returning its value.
+    fn remove(&mut self, idx: u32) -> T {
+        let [left, right] = self.nodes[idx as usize].children;
+        let parent = self.nodes[idx as usize].parent;
+        let [previous, next] = self.nodes[idx as usize].threads;
+        if previous != NIL {
+            self.nodes[previous as usize].threads[1] = next;
+        }
+        if next != NIL {
+            self.nodes[next as usize].threads[0] = previous;
+        }
+        let rebalance_from;
+        if left == NIL || right == NIL {
+            let child = if left == NIL { right } else { left };
+            if child != NIL {
+                self.nodes[child as usize].parent = parent;
+            }
+            self.replace_child(parent, idx, child);
+            rebalance_from = parent;
+        } else {
+            // both children are branches: the successor node takes this node's place
+            let successor = next;
+            if successor == right {
+                rebalance_from = successor;
+            } else {
+                let successor_parent = self.nodes[successor as usize].parent;
+                let successor_right = self.nodes[successor as usize].children[1];
+                self.nodes[successor_parent as usize].children[0] = successor_right;
+                if successor_right != NIL {
+                    self.nodes[successor_right as usize].parent = successor_parent;
+                }
+                self.nodes[successor as usize].children[1] = right;
+                self.nodes[right as usize].parent = successor;
+                rebalance_from = successor_parent;
+            }
+            self.nodes[successor as usize].children[0] = left;
+            self.nodes[left as usize].parent = successor;
+            self.nodes[successor as usize].parent = parent;
+            self.replace_child(parent, idx, successor);
+        }
+        self.rebalance_upwards(rebalance_from);
+        self.release(idx)
+    }
+
+    /// Free the slot of the (already unlinked) node at `idx`, moving the last node into it, and return its value.
+    fn release(&mut self, idx: u32) -> T {
+        let last = (self.nodes.len() - 1) as u32;
+        if idx != last {
+            let [left, right] = self.nodes[last as usize].children;
+            let parent = self.nodes[last as usize].parent;
+            for child in [left, right] {
+                if child != NIL {
+                    self.nodes[child as usize].parent = idx;
+                }
+            }
+            self.replace_child(parent, last, idx);
+            for side in [0, 1] {
+                let neighbour = self.nodes[last as usize].threads[side];
+                if neighbour != NIL {
+                    self.nodes[neighbour as usize].threads[1 - side] = idx;
+                }
+            }
+        }
+        self.nodes.swap_remove(idx as usize).value
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd + Clone> Default for LinkedJavlt<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <T: PartialEq + PartialOrd + Clone + fmt::Debug> fmt::Debug for LinkedJavlt<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("LinkedJavlt")
+            .field("size", &self.get_size())
+            .field("values", &self.as_vec())
+            .finish()
+    }
+}
+
+/// A position among a LinkedJavlt's values, which can step to the next or previous value in O(1) time by
+/// following the nodes' threads.  Get one with `cursor_at`, `cursor_front` or `cursor_back`.  Besides the values,
+/// there's a "ghost" position past both ends, where `peek` returns None: stepping back from it goes to the greatest
+/// value.  As an `Iterator`, the cursor yields the values after its position, and stops for good at the ghost
+/// position.
+pub struct Cursor<'a, T: PartialEq + PartialOrd + Clone> {
+    tree: &'a LinkedJavlt<T>,
+    idx: u32, // the current node; NIL at the ghost position
+}
+
+impl <'a, T: PartialEq + PartialOrd + Clone> Cursor<'a, T> {
+
+    /// Returns the value at the cursor's position, without moving.
+    pub fn peek(&self) -> Option<&'a T> {
+        return match self.idx {
+            NIL => None,
+            idx => Some(&self.tree.nodes[idx as usize].value),
+        };
+    }
+
+    /// Move to the previous value and return it, or None on reaching the ghost position.
+    pub fn prev(&mut self) -> Option<&'a T> {
+        self.idx = self.tree.step(self.idx, 0);
+        self.peek()
+    }
+
+}
+
+impl <'a, T: PartialEq + PartialOrd + Clone> Iterator for Cursor<'a, T> {
+    type Item = &'a T;
+
+    /// Move to the next value and return it, or None on reaching (or being at) the ghost position.
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.idx == NIL {
+            return None;
+        }
+        self.idx = self.tree.step(self.idx, 1);
+        self.peek()
+    }
+}
+
+impl <'a, T: PartialEq + PartialOrd + Clone> FusedIterator for Cursor<'a, T> {}
+
+/// A cursor like `Cursor` that can also remove the value at its position, for editing a LinkedJavlt in one pass.
+/// Get one with `cursor_mut_at`, `cursor_mut_front` or `cursor_mut_back`.  Unlike `Javlt`'s `CursorMut`, it
+/// keeps its place by node rather than by value, so neither stepping nor removing searches from the root.
+pub struct CursorMut<'a, T: PartialEq + PartialOrd + Clone> {
+    tree: &'a mut LinkedJavlt<T>,
+    idx: u32, // the current node; NIL at the ghost position
+}
+
+impl <'a, T: PartialEq + PartialOrd + Clone> CursorMut<'a, T> {
+
+    /// Returns the value at the cursor's position, without moving.
+    pub fn peek(&self) -> Option<&T> {
+        return match self.idx {
+            NIL => None,
+            idx => Some(&self.tree.nodes[idx as usize].value),
+        };
+    }
+
+    /// Move to the next value and return it, or None on reaching the ghost position.
+    pub fn move_next(&mut self) -> Option<&T> {
+        self.idx = self.tree.step(self.idx, 1);
+        self.peek()
+    }
+
+    /// Move to the previous value and return it, or None on reaching the ghost position.
+    pub fn move_prev(&mut self) -> Option<&T> {
+        self.idx = self.tree.step(self.idx, 0);
+        self.peek()
+    }
+
+    /// Remove the value at the cursor's position and return it, moving the cursor on to the next value.
+    /// At the ghost position, nothing is removed and None is returned.
+    pub fn remove_current(&mut self) -> Option<T> {
+        if self.idx == NIL {
+            return None;
+        }
+        let removed = self.idx;
+        let mut next = self.tree.step(removed, 1);
+        // the last node moves into the removed node's slot, so if that was the next one, follow it
+        if next == (self.tree.nodes.len() - 1) as u32 {
+            next = removed;
+        }
+        let value = self.tree.remove(removed);
+        self.idx = next;
+        #[cfg(feature = "paranoid")]
+        self.tree.paranoid_check("CursorMut::remove_current");
+        Some(value)
+    }
+
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    /// A deterministic shuffle of 0..n, so the tests exercise all the rebalancing cases.
+    fn scrambled(n: u32) -> Vec<u32> {
+        (0..n).map(|i| i * 7919 % n).collect()
+    }
+
+    #[test]
+    fn add_and_drop_keep_the_links() {
+        let mut my_tree = LinkedJavlt::new();
+        let mut expected = BTreeSet::new();
+        for value in scrambled(500) {
+            assert_eq!( Ok(()), my_tree.add(value) );
+            expected.insert(value);
+        }
+        assert_eq!( Err(TreeError::ValueAlreadyStored { value: 42 }), my_tree.add(42) );
+        assert_eq!( Ok(()), my_tree.check_invariants() );
+        assert!( my_tree.nodes[my_tree.root as usize].height <= 11 );
+        for value in scrambled(500).into_iter().filter(|value| value % 3 != 0) {
+            assert_eq!( Ok(()), my_tree.drop_value(value) );
+            expected.remove(&value);
+            assert_eq!( Ok(()), my_tree.check_invariants() );
+        }
+        assert_eq!( Err(TreeError::ValueNotFound { value: 1 }), my_tree.drop_value(1) );
+        assert_eq!( expected.into_iter().collect::<Vec<_>>(), my_tree.as_vec() );
+        assert_eq!( Some(0), my_tree.least_value() );
+        assert_eq!( Some(498), my_tree.greatest_value() );
+    }
+
+    #[test]
+    fn cursors_step_both_ways() {
+        let my_tree = LinkedJavlt::from_collection(scrambled(100));
+        assert_eq!( (0..100).collect::<Vec<_>>(), my_tree.iter().copied().collect::<Vec<_>>() );
+        let mut cursor = my_tree.cursor_back();
+        let mut backwards = vec!(*cursor.peek().unwrap());
+        while let Some(value) = cursor.prev() {
+            backwards.push(*value);
+        }
+        assert_eq!( (0..100).rev().collect::<Vec<_>>(), backwards );
+        assert_eq!( None, cursor.next() ); // an iterator stops at the ghost position
+        assert_eq!( Some(&99), cursor.prev() );
+        assert_eq!( 0, my_tree.cursor_at(&100).count() );
+        assert_eq!( None, my_tree.cursor_at(&100).peek() );
+        assert_eq!( None, LinkedJavlt::<u32>::new().cursor_front().peek() );
+    }
+
+    #[test]
+    fn cursor_mut_removes_in_one_pass() {
+        let mut my_tree = LinkedJavlt::from_collection(scrambled(200));
+        let mut cursor = my_tree.cursor_mut_front();
+        while let Some(value) = cursor.peek() {
+            if value % 2 == 0 {
+                cursor.remove_current();
+            } else {
+                cursor.move_next();
+            }
+        }
+        assert_eq!( None, cursor.remove_current() );
+        assert_eq!( (0..200).filter(|value| value % 2 == 1).collect::<Vec<_>>(), my_tree.as_vec() );
+        assert_eq!( Ok(()), my_tree.check_invariants() );
+
+        // removing the node just before the last one in storage
+        let mut my_tree = LinkedJavlt::from_collection([1,2]);
+        let mut cursor = my_tree.cursor_mut_at(&1);
+        assert_eq!( Some(1), cursor.remove_current() );
+        assert_eq!( Some(&2), cursor.peek() );
+    }
+
+    #[test]
+    fn detect_broken_invariants() {
+        let mut my_tree = LinkedJavlt::from_collection([2,1,3]);
+        let left = my_tree.nodes[my_tree.root as usize].children[0];
+        my_tree.nodes[left as usize].parent = left;
+        assert_eq!( Err(TreeError::InvariantViolation("a child of the node at root doesn't link back to it".to_string())), my_tree.check_invariants() );
+        let mut my_tree = LinkedJavlt::from_collection([2,1,3]);
+        let right = my_tree.nodes[my_tree.root as usize].children[1];
+        my_tree.nodes[right as usize].threads[0] = NIL;
+        assert_eq!( Err(TreeError::InvariantViolation("node at root.right isn't threaded to the node before it".to_string())), my_tree.check_invariants() );
+    }
+
+}
@@ -0,0 +1,80 @@
+use std::iter::Peekable;
+
+/// What `merge_iter` does with a value that's in both trees.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Duplicates {
+    /// Yield the value from both trees (this tree's first), so nothing is lost.
+    KeepBoth,
+    /// Yield the value once, as in a set union.  With `Jblst`, each copy in one tree is paired off with a
+    /// copy in the other, so a value stored twice in one and three times in the other is yielded three times.
+    Union,
+}
+
+/// Merges two trees' values, each already in order, into one ordered sequence, for `merge_iter`.
+pub(crate) struct MergeIter<'a, T: 'a, A: Iterator<Item = &'a T>, B: Iterator<Item = &'a T>> {
+    ours: Peekable<A>,
+    theirs: Peekable<B>,
+    duplicates: Duplicates,
+}
+
+impl <'a, T: PartialEq + PartialOrd + 'a, A: Iterator<Item = &'a T>, B: Iterator<Item = &'a T>> MergeIter<'a, T, A, B> {
+
+    pub(crate) fn new(ours: A, theirs: B, duplicates: Duplicates) -> Self {
+        Self { ours: ours.peekable(), theirs: theirs.peekable(), duplicates }
+    }
+
+}
+
+impl <'a, T: PartialEq + PartialOrd + 'a, A: Iterator<Item = &'a T>, B: Iterator<Item = &'a T>> Iterator for MergeIter<'a, T, A, B> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        return match (self.ours.peek(), self.theirs.peek()) {
+            (None, None) => None,
+            (Some(_), None) => self.ours.next(),
+            (None, Some(_)) => self.theirs.next(),
+            (Some(ours), Some(theirs)) => {
+                if theirs < ours {
+                    return self.theirs.next();
+                }
+                if ours == theirs && self.duplicates == Duplicates::Union {
+                    self.theirs.next();
+                }
+                self.ours.next()
+            },
+        };
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::folded::Folded;
+
+    use super::*;
+
+    fn merged<'a, T: PartialEq + PartialOrd>(ours: &'a [T], theirs: &'a [T], duplicates: Duplicates) -> Vec<&'a T> {
+        MergeIter::new(ours.iter(), theirs.iter(), duplicates).collect()
+    }
+
+    #[test]
+    fn values_interleave_in_order() {
+        let (odd, even) = ([1, 3, 5, 7], [0, 2, 4]);
+        assert_eq!( vec!(&0, &1, &2, &3, &4, &5, &7), merged(&odd, &even, Duplicates::KeepBoth) );
+        assert_eq!( vec!(&1, &3), merged(&[1, 3], &[], Duplicates::Union) );
+        assert_eq!( vec!(&2), merged(&[], &[2], Duplicates::Union) );
+        assert!( merged::<u8>(&[], &[], Duplicates::KeepBoth).is_empty() );
+    }
+
+    #[test]
+    fn equal_values_come_from_this_tree_first() {
+        let ours = [Folded::new("Apple"), Folded::new("cherry")];
+        let theirs = [Folded::new("APPLE"), Folded::new("banana"), Folded::new("Cherry")];
+        let spellings = |duplicates| merged(&ours, &theirs, duplicates).into_iter().map(|word| *word.value()).collect::<Vec<_>>();
+        assert_eq!( vec!("Apple", "APPLE", "banana", "cherry", "Cherry"), spellings(Duplicates::KeepBoth) );
+        assert_eq!( vec!("Apple", "banana", "cherry"), spellings(Duplicates::Union) );
+        // repeated values pair off one for one
+        assert_eq!( vec!(&1, &1, &1, &2), merged(&[1, 1], &[1, 1, 1, 2], Duplicates::Union) );
+    }
+
+}
@@ -0,0 +1,181 @@
+//! With the `metrics` feature, this module counts the work `Jbst` and `Javlt` do, to compare them on a
+//! workload with numbers rather than guesses.  `measure` runs a closure and returns, with its result, the
+//! `Metrics` of the tree operations made in it (on the same thread):
+//!
+//!     use jtree::{Javlt, Jbst};
+//!     use jtree::metrics::{measure, Counted};
+//!
+//!     let (_, avl) = measure(|| Javlt::from_collection((0..100).map(Counted)));
+//!     let (_, bst) = measure(|| Jbst::from_collection((0..100).map(Counted)));
+//!     assert_eq!( (100, 100), (avl.allocations, bst.allocations) );
+//!     assert!( avl.rotations > 0 && bst.rotations == 0 );
+//!     // values added in order make the plain BST a list: adding the last goes through the other 99
+//!     assert!( avl.max_depth <= 7 && bst.max_depth == 99 );
+//!
+//! Rotations, allocations and frees of nodes, and depths are counted by the trees.  Comparisons are counted
+//! by the values themselves, when they're wrapped in `Counted`, so every comparison is caught, whichever
+//! method makes it (including the checks made with the `paranoid` feature, which should be off when measuring).
+
+use std::{cell::Cell, cmp::{max, Ordering}};
+
+/// What the tree operations made during a `measure` did.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Metrics {
+    /// Comparisons between `Counted` values.
+    pub comparisons: u64,
+    /// Rotations made to rebalance a `Javlt`.
+    pub rotations: u64,
+    /// Nodes created.
+    pub allocations: u64,
+    /// Nodes removed by `drop_value`, `drain`, or (for `Jbst`) `purge`.
+    pub frees: u64,
+    /// The most nodes (counting the root) that any one `add`, `contains` or `drop_value` went through on its
+    /// way down the tree.
+    pub max_depth: usize,
+}
+
+thread_local! {
+    static MEASURING: Cell<Option<Metrics>> = const { Cell::new(None) };
+    static DEPTH: Cell<usize> = const { Cell::new(0) }; // nodes reached by the operation in progress
+}
+
+/// Run `f`, returning its result along with the metrics of the tree operations it made.  Measurements can
+/// be nested; the outer one counts everything the inner one does, too.  If `f` panics, the measurement
+/// ends all the same, so nothing more is counted toward it.
+pub fn measure<R, F: FnOnce() -> R>(f: F) -> (R, Metrics) {
+    let ending = Ending { outer: MEASURING.replace(Some(Metrics::default())) };
+    let result = f();
+    let metrics = MEASURING.get().unwrap_or_default();
+    drop(ending);
+    (result, metrics)
+}
+
+/// The end of a `measure`, when dropped (on unwinding, too): the measurement around it, if any, takes over
+/// again, adding what was counted in this one.
+struct Ending {
+    outer: Option<Metrics>,
+}
+
+impl Drop for Ending {
+    fn drop(&mut self) {
+        let metrics = MEASURING.replace(self.outer).unwrap_or_default();
+        if let Some(outer) = self.outer {
+            MEASURING.set(Some(outer.plus(&metrics)));
+        }
+    }
+}
+
+impl Metrics {
+
+    /// The sum of two measurements, taking the greater of their depths.
+    fn plus(&self, other: &Metrics) -> Metrics {
+        Metrics {
+            comparisons: self.comparisons + other.comparisons,
+            rotations: self.rotations + other.rotations,
+            allocations: self.allocations + other.allocations,
+            frees: self.frees + other.frees,
+            max_depth: max(self.max_depth, other.max_depth),
+        }
+    }
+
+}
+
+/// Update the metrics being measured, if any.
+pub(crate) fn record<F: FnOnce(&mut Metrics)>(f: F) {
+    if let Some(mut metrics) = MEASURING.get() {
+        f(&mut metrics);
+        MEASURING.set(Some(metrics));
+    }
+}
+
+/// Note the start of an operation that goes down the tree, for `visit` to measure its depth.
+pub(crate) fn start_operation() {
+    DEPTH.set(0);
+}
+
+/// Note that the operation in progress has reached one more node on its way down.
+pub(crate) fn visit() {
+    let depth = DEPTH.get() + 1;
+    DEPTH.set(depth);
+    reach(depth);
+}
+
+/// Note that an operation that walks the whole tree, rather than one path down it, has reached a node at
+/// the given depth (counting the root as 1).
+pub(crate) fn reach(depth: usize) {
+    record(|metrics| metrics.max_depth = max(metrics.max_depth, depth));
+}
+
+/// A value that counts its comparisons with others (toward the `Metrics` being measured), and otherwise
+/// compares as the value it wraps does.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Counted<T>(pub T);
+
+impl <T: PartialEq> PartialEq for Counted<T> {
+    fn eq(&self, other: &Self) -> bool {
+        record(|metrics| metrics.comparisons += 1);
+        self.0 == other.0
+    }
+}
+
+impl <T: PartialOrd> PartialOrd for Counted<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        record(|metrics| metrics.comparisons += 1);
+        self.0.partial_cmp(&other.0)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::{Javlt, Jbst};
+
+    use super::*;
+
+    #[test]
+    fn operations_are_counted() {
+        let (mut lazy_tree, built) = measure(|| {
+            let mut tree = Jbst::with_lazy_deletion(10);
+            let _ = tree.add_all([4,2,6,1,3].map(Counted));
+            tree
+        });
+        assert_eq!( Metrics { comparisons: built.comparisons, rotations: 0, allocations: 5, frees: 0, max_depth: 2 }, built );
+        let ((), dropped) = measure(|| {
+            let _ = lazy_tree.drop_value(Counted(3)); // only marked as deleted...
+            lazy_tree.purge(); // ...until now
+        });
+        assert_eq!( (1, 3), (dropped.frees, dropped.max_depth) );
+
+        let mut tree = Javlt::from_collection((0..7).map(Counted));
+        let ((), outer) = measure(|| {
+            let ((), inner) = measure(|| assert!( tree.contains(&Counted(6)) ));
+            assert_eq!( Metrics { comparisons: 5, rotations: 0, allocations: 0, frees: 0, max_depth: 3 }, inner );
+            let _ = tree.drop_value(Counted(0));
+            let _ = tree.drop_value(Counted(1));
+            let _ = tree.drop_value(Counted(2)); // leaving 3's left side empty, so the tree rotates
+        });
+        assert_eq!( (1, 3, 3), (outer.rotations, outer.frees, outer.max_depth) );
+        assert!( outer.comparisons > 5 );
+        // nothing is counted outside a measurement
+        let _ = tree.add(Counted(9));
+        assert_eq!( 0, measure(|| ()).1.comparisons );
+    }
+
+    #[test]
+    fn measurements_end_when_their_closure_panics() {
+        let tree = Javlt::from_collection((0..7).map(Counted));
+        let ((), outer) = measure(|| {
+            let panicked = std::panic::catch_unwind(|| measure(|| {
+                assert!( tree.contains(&Counted(6)) );
+                panic!("measured code failed");
+            }));
+            assert!( panicked.is_err() );
+        });
+        assert_eq!( 3, outer.max_depth );
+        assert_eq!( None, MEASURING.get() );
+        let panicked = std::panic::catch_unwind(|| measure(|| panic!("measured code failed")));
+        assert!( panicked.is_err() );
+        assert_eq!( None, MEASURING.get() );
+    }
+
+}
@@ -0,0 +1,235 @@
+//! With the `oracle` feature, this module tests the set-like trees against `std::collections::BTreeSet`:
+//! it makes up a random sequence of adds, removes and lookups, applies it to both, and checks after every
+//! step that they answered alike and hold the same values in the same order.  The sequences come from a
+//! seed, so a failure can be replayed.
+//!
+//!     use jtree::Javlt;
+//!     use jtree::oracle;
+//!
+//!     for seed in 1..=5 {
+//!         oracle::assert_matches_btreeset::<Javlt<u64>>(seed, 500, 100);
+//!     }
+//!
+//! Any other set can be tested too, by implementing `SetUnderTest` for it.
+
+use std::{collections::BTreeSet, fmt, hash::Hash};
+
+use crate::bloom::FilteredJavlt;
+use crate::concurrent::ConcurrentSet;
+use crate::javlt::Javlt;
+use crate::jbst::Jbst;
+use crate::jintset::{IntKey, IntSet};
+use crate::linked::LinkedJavlt;
+use crate::persistent::PersistentJavlt;
+use crate::radix::RadixSet;
+
+/// The operations the oracle makes on a set, answering as `BTreeSet` does.
+pub trait SetUnderTest<T> {
+    /// Insert a value, returning false if it was already there.
+    fn add(&mut self, value: T) -> bool;
+    /// Delete a value, returning false if it wasn't there.
+    fn remove(&mut self, value: &T) -> bool;
+    /// Returns true if the value is in the set.
+    fn contains(&self, value: &T) -> bool;
+    /// The number of values in the set.
+    fn get_size(&self) -> usize;
+    /// The values in order from least to greatest.
+    fn to_vec(&self) -> Vec<T>;
+}
+
+/// One step of a test sequence.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Op<T> {
+    Add(T),
+    Remove(T),
+    Contains(T),
+}
+
+/// Where a set under test first behaved differently from the `BTreeSet`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    pub step: usize, // counting from 0
+    pub op: String,
+    pub expected: String,
+    pub found: String,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "at step {} ({}), expected {} but found {}", self.step, self.op, self.expected, self.found)
+    }
+}
+
+/// Make up `count` operations on values below `key_range`, about half of them adds, from the seed.  A small
+/// key range makes for more duplicate adds and successful removes.
+pub fn random_ops(seed: u64, count: usize, key_range: u64) -> Vec<Op<u64>> {
+    let mut state = seed.wrapping_add(0x9e37_79b9_7f4a_7c15).max(1); // xorshift gets stuck at zero
+    let mut next_random = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    (0..count).map(|_| {
+        let kind = next_random() % 10;
+        let value = next_random() % key_range.max(1);
+        return match kind {
+            0..=4 => Op::Add(value),
+            5..=7 => Op::Remove(value),
+            _ => Op::Contains(value),
+        };
+    }).collect()
+}
+
+/// Apply the operations to the set and to a `BTreeSet`, checking after each one that they answered alike
+/// and hold the same values in order.  Returns the first difference found, if any.
+pub fn compare<T: Ord + Clone + fmt::Debug, S: SetUnderTest<T>>(set: &mut S, ops: &[Op<T>]) -> Result<(),Mismatch> {
+    let mut expected_set = BTreeSet::new();
+    for (step, op) in ops.iter().enumerate() {
+        let mismatch = |expected: String, found: String| Mismatch { step, op: format!("{op:?}"), expected, found };
+        let (expected, found) = match op {
+            Op::Add(value) => (expected_set.insert(value.clone()), set.add(value.clone())),
+            Op::Remove(value) => (expected_set.remove(value), set.remove(value)),
+            Op::Contains(value) => (expected_set.contains(value), set.contains(value)),
+        };
+        if expected != found {
+            return Err(mismatch(format!("{expected}"), format!("{found}")));
+        }
+        if expected_set.len() != set.get_size() {
+            return Err(mismatch(format!("size {}", expected_set.len()), format!("size {}", set.get_size())));
+        }
+        let values = set.to_vec();
+        if !expected_set.iter().eq(values.iter()) {
+            return Err(mismatch(format!("{expected_set:?}"), format!("{values:?}")));
+        }
+    }
+    Ok(())
+}
+
+/// Test a new set against a `BTreeSet` with `count` random operations on values below `key_range`, made
+/// from the seed.  Panics, naming the seed and the first difference, if they behave differently.
+pub fn assert_matches_btreeset<S: SetUnderTest<u64> + Default>(seed: u64, count: usize, key_range: u64) {
+    let ops = random_ops(seed, count, key_range);
+    match compare(&mut S::default(), &ops) {
+        Ok(()) => (),
+        Err(mismatch) => panic!("seed {seed}: {mismatch}"),
+    }
+}
+
+impl <T: PartialEq + PartialOrd + Clone> SetUnderTest<T> for Jbst<T> {
+    fn add(&mut self, value: T) -> bool { Jbst::add(self, value).is_ok() }
+    fn remove(&mut self, value: &T) -> bool { self.drop_value(value.clone()).is_ok() }
+    fn contains(&self, value: &T) -> bool { Jbst::contains(self, value) }
+    fn get_size(&self) -> usize { Jbst::get_size(self) as usize }
+    fn to_vec(&self) -> Vec<T> { self.as_vec() }
+}
+
+impl <T: PartialEq + PartialOrd + Clone> SetUnderTest<T> for Javlt<T> {
+    fn add(&mut self, value: T) -> bool { Javlt::add(self, value).is_ok() }
+    fn remove(&mut self, value: &T) -> bool { self.drop_value(value.clone()).is_ok() }
+    fn contains(&self, value: &T) -> bool { Javlt::contains(self, value) }
+    fn get_size(&self) -> usize { Javlt::get_size(self) as usize }
+    fn to_vec(&self) -> Vec<T> { self.as_vec() }
+}
+
+impl <T: PartialEq + PartialOrd + Clone> SetUnderTest<T> for LinkedJavlt<T> {
+    fn add(&mut self, value: T) -> bool { LinkedJavlt::add(self, value).is_ok() }
+    fn remove(&mut self, value: &T) -> bool { self.drop_value(value.clone()).is_ok() }
+    fn contains(&self, value: &T) -> bool { LinkedJavlt::contains(self, value) }
+    fn get_size(&self) -> usize { LinkedJavlt::get_size(self) as usize }
+    fn to_vec(&self) -> Vec<T> { self.as_vec() }
+}
+
+impl <T: PartialEq + PartialOrd + Clone> SetUnderTest<T> for PersistentJavlt<T> {
+    fn add(&mut self, value: T) -> bool { PersistentJavlt::add(self, value).is_ok() }
+    fn remove(&mut self, value: &T) -> bool { self.drop_value(value.clone()).is_ok() }
+    fn contains(&self, value: &T) -> bool { PersistentJavlt::contains(self, value) }
+    fn get_size(&self) -> usize { PersistentJavlt::get_size(self) as usize }
+    fn to_vec(&self) -> Vec<T> { self.as_vec() }
+}
+
+impl <T: PartialEq + PartialOrd + Clone + Hash> SetUnderTest<T> for FilteredJavlt<T> {
+    fn add(&mut self, value: T) -> bool { FilteredJavlt::add(self, value).is_ok() }
+    fn remove(&mut self, value: &T) -> bool { self.drop_value(value.clone()).is_ok() }
+    fn contains(&self, value: &T) -> bool { FilteredJavlt::contains(self, value) }
+    fn get_size(&self) -> usize { FilteredJavlt::get_size(self) as usize }
+    fn to_vec(&self) -> Vec<T> { self.as_vec() }
+}
+
+impl <T: PartialEq + PartialOrd + Clone> SetUnderTest<T> for ConcurrentSet<T> {
+    fn add(&mut self, value: T) -> bool { self.insert(value).is_ok() }
+    fn remove(&mut self, value: &T) -> bool { ConcurrentSet::remove(self, value.clone()).is_ok() }
+    fn contains(&self, value: &T) -> bool { ConcurrentSet::contains(self, value) }
+    fn get_size(&self) -> usize { ConcurrentSet::get_size(self) }
+    fn to_vec(&self) -> Vec<T> { self.snapshot_vec() }
+}
+
+impl <K: IntKey> SetUnderTest<K> for IntSet<K> {
+    fn add(&mut self, value: K) -> bool { IntSet::add(self, value).is_ok() }
+    fn remove(&mut self, value: &K) -> bool { self.drop_value(*value).is_ok() }
+    fn contains(&self, value: &K) -> bool { IntSet::contains(self, *value) }
+    fn get_size(&self) -> usize { IntSet::get_size(self) as usize }
+    fn to_vec(&self) -> Vec<K> { self.as_vec() }
+}
+
+impl SetUnderTest<u64> for RadixSet {
+    fn add(&mut self, value: u64) -> bool { RadixSet::add(self, value).is_ok() }
+    fn remove(&mut self, value: &u64) -> bool { self.drop_value(*value).is_ok() }
+    fn contains(&self, value: &u64) -> bool { RadixSet::contains(self, *value) }
+    fn get_size(&self) -> usize { RadixSet::get_size(self) as usize }
+    fn to_vec(&self) -> Vec<u64> { self.as_vec() }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_sets_match_btreeset() {
+        for seed in 1..=4 {
+            assert_matches_btreeset::<Jbst<u64>>(seed, 1000, 150);
+            assert_matches_btreeset::<Javlt<u64>>(seed, 1000, 150);
+            assert_matches_btreeset::<LinkedJavlt<u64>>(seed, 1000, 150);
+            assert_matches_btreeset::<PersistentJavlt<u64>>(seed, 1000, 150);
+            assert_matches_btreeset::<FilteredJavlt<u64>>(seed, 1000, 150);
+            assert_matches_btreeset::<ConcurrentSet<u64>>(seed, 1000, 150);
+            assert_matches_btreeset::<IntSet<u64>>(seed, 1000, 150);
+            assert_matches_btreeset::<RadixSet>(seed, 1000, 150);
+        }
+    }
+
+    #[test]
+    fn a_difference_is_reported() {
+        // a set that forgets to remove its greatest value
+        #[derive(Default)]
+        struct Sticky(Javlt<u64>);
+        impl SetUnderTest<u64> for Sticky {
+            fn add(&mut self, value: u64) -> bool { self.0.add(value).is_ok() }
+            fn remove(&mut self, value: &u64) -> bool {
+                if self.0.greatest_value() == Some(*value) {
+                    return true;
+                }
+                self.0.drop_value(*value).is_ok()
+            }
+            fn contains(&self, value: &u64) -> bool { self.0.contains(value) }
+            fn get_size(&self) -> usize { self.0.get_size() as usize }
+            fn to_vec(&self) -> Vec<u64> { self.0.as_vec() }
+        }
+        let ops = vec!(Op::Add(2), Op::Add(7), Op::Contains(7), Op::Remove(7), Op::Add(5));
+        let mismatch = compare(&mut Sticky::default(), &ops).unwrap_err();
+        assert_eq!( 3, mismatch.step );
+        assert_eq!( "Remove(7)", mismatch.op );
+        assert_eq!( "at step 3 (Remove(7)), expected size 1 but found size 2", mismatch.to_string() );
+    }
+
+    #[test]
+    fn sequences_replay_from_the_seed() {
+        assert_eq!( random_ops(42, 100, 10), random_ops(42, 100, 10) );
+        assert_ne!( random_ops(42, 100, 10), random_ops(43, 100, 10) );
+        assert!( random_ops(0, 100, 10).iter().all(|op| match op {
+            Op::Add(value) | Op::Remove(value) | Op::Contains(value) => *value < 10,
+        }) );
+    }
+
+}
@@ -0,0 +1,249 @@
+//! # Ordered set traits
+//!
+//! `OrderedSet` is what `Jbst`, `Jblst` and `Javlt` have in common, so that code (a benchmark, say) can be
+//! written once and run on any of them:
+//!
+//!     use jtree::{Javlt, Jbst, OrderedSet};
+//!
+//!     fn middle_values<S: OrderedSet<u32>>(set: &mut S) -> Vec<u32> {
+//!         for value in [50, 10, 40, 20, 30] {
+//!             let _ = set.add(value);
+//!         }
+//!         set.range(15..45).cloned().collect()
+//!     }
+//!
+//!     assert_eq!( vec!(20,30,40), middle_values(&mut Jbst::new()) );
+//!     assert_eq!( vec!(20,30,40), middle_values(&mut Javlt::new()) );
+//!
+//! `Jblst` is a multiset, which keeps every copy of a value that's added; it's an `OrderedSet` whose `add`
+//! never turns a value away, and `OrderedMultiset` adds what's particular to counting copies.
+//!
+//! A `Box<dyn OrderedSet<T>>` can do everything but iterate, since `iter` and `range` return iterators whose
+//! types depend on the structure.  Every `OrderedSet` is also a `DynOrderedSet`, which adds boxed versions of
+//! them; with `SetKind`, the structure can be chosen at runtime, e.g. from a configuration file:
+//!
+//!     use std::ops::Bound;
+//!     use jtree::ordered::{DynOrderedSet, OrderedSet, SetKind};
+//!
+//!     let kind = SetKind::from_name("javlt").unwrap();
+//!     let mut set: Box<dyn DynOrderedSet<u64>> = kind.new_set();
+//!     set.add(3).unwrap();
+//!     set.add(1).unwrap();
+//!     assert!( set.contains(&3) );
+//!     assert_eq!( vec!(&1,&3), set.boxed_iter().collect::<Vec<_>>() );
+//!     assert_eq!( vec!(&3), set.boxed_range(Bound::Excluded(&1), Bound::Unbounded).collect::<Vec<_>>() );
+
+use std::ops::{Bound, RangeBounds};
+
+use crate::errors::TreeError;
+use crate::javlt::Javlt;
+use crate::jblst::Jblst;
+use crate::jbst::Jbst;
+
+/// The operations shared by the set-like trees.  Values are visited from least to greatest.
+pub trait OrderedSet<T> {
+    /// Insert a value.  A set holding unique values returns a TreeError::ValueAlreadyStored if it's already there.
+    fn add(&mut self, value: T) -> Result<(),TreeError<T>>;
+    /// Delete a value (one copy of it, in a multiset), returning a TreeError::ValueNotFound if it isn't there.
+    fn remove(&mut self, value: T) -> Result<(),TreeError<T>>;
+    /// Returns true if the value is in the set.
+    fn contains(&self, value: &T) -> bool;
+    /// The number of values in the set, counting every copy in a multiset.
+    fn len(&self) -> usize;
+    /// Returns true if the set holds no values.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// The values in order, without copying them.  (Not available through a trait object; see `DynOrderedSet`.)
+    fn iter<'a>(&'a self) -> impl Iterator<Item = &'a T> where T: 'a, Self: Sized;
+    /// The values within the range, in order.  (Not available through a trait object; see `DynOrderedSet`.)
+    fn range<'a, R: RangeBounds<T>>(&'a self, range: R) -> impl Iterator<Item = &'a T> where T: 'a, Self: Sized;
+    /// The least value, or None if the set is empty.
+    fn least(&self) -> Option<&T>;
+    /// The greatest value, or None if the set is empty.
+    fn greatest(&self) -> Option<&T>;
+}
+
+/// The operations particular to a set that keeps copies of equal values.
+pub trait OrderedMultiset<T>: OrderedSet<T> {
+    /// The number of copies of the value in the set (0 if there are none).
+    fn count(&self, value: &T) -> usize;
+    /// Each distinct value once, in order.
+    fn iter_distinct<'a>(&'a self) -> impl Iterator<Item = &'a T> where T: 'a;
+    /// Each distinct value with its number of copies, in order.
+    fn iter_counts<'a>(&'a self) -> impl Iterator<Item = (&'a T, usize)> where T: 'a;
+}
+
+/// An `OrderedSet` that can iterate as a trait object, for holding a set as a `Box<dyn DynOrderedSet<T>>`.
+/// Its iterators are boxed, and `boxed_range` takes its bounds as a pair.  It's implemented for every
+/// `OrderedSet`.
+pub trait DynOrderedSet<T>: OrderedSet<T> {
+    /// The values in order, without copying them.
+    fn boxed_iter(&self) -> Box<dyn Iterator<Item = &T> + '_>;
+    /// The values between the bounds, in order.
+    fn boxed_range<'a>(&'a self, lower: Bound<&'a T>, upper: Bound<&'a T>) -> Box<dyn Iterator<Item = &'a T> + 'a>;
+}
+
+impl <T, S: OrderedSet<T>> DynOrderedSet<T> for S {
+    fn boxed_iter(&self) -> Box<dyn Iterator<Item = &T> + '_> { Box::new(self.iter()) }
+    fn boxed_range<'a>(&'a self, lower: Bound<&'a T>, upper: Bound<&'a T>) -> Box<dyn Iterator<Item = &'a T> + 'a> {
+        Box::new(self.range((lower, upper)))
+    }
+}
+
+/// Which of the set-like trees to make, for choosing one at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetKind {
+    Jbst,
+    Jblst,
+    Javlt,
+}
+
+impl SetKind {
+
+    /// Look up a kind by its name, ignoring case: "jbst", "jblst" or "javlt".
+    pub fn from_name(name: &str) -> Option<Self> {
+        return match name.to_ascii_lowercase().as_str() {
+            "jbst" => Some(SetKind::Jbst),
+            "jblst" => Some(SetKind::Jblst),
+            "javlt" => Some(SetKind::Javlt),
+            _ => None,
+        };
+    }
+
+    /// Make a new, empty set of this kind.
+    pub fn new_set<T: PartialEq + PartialOrd + Clone + 'static>(self) -> Box<dyn DynOrderedSet<T>> {
+        return match self {
+            SetKind::Jbst => Box::new(Jbst::new()),
+            SetKind::Jblst => Box::new(Jblst::new()),
+            SetKind::Javlt => Box::new(Javlt::new()),
+        };
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd + Clone> OrderedSet<T> for Jbst<T> {
+    fn add(&mut self, value: T) -> Result<(),TreeError<T>> { Jbst::add(self, value) }
+    fn remove(&mut self, value: T) -> Result<(),TreeError<T>> { self.drop_value(value) }
+    fn contains(&self, value: &T) -> bool { Jbst::contains(self, value) }
+    fn len(&self) -> usize { self.get_size() as usize }
+    fn iter<'a>(&'a self) -> impl Iterator<Item = &'a T> where T: 'a { Jbst::iter(self) }
+    fn range<'a, R: RangeBounds<T>>(&'a self, range: R) -> impl Iterator<Item = &'a T> where T: 'a { Jbst::range(self, range) }
+    fn least(&self) -> Option<&T> { self.cursor_front().peek() }
+    fn greatest(&self) -> Option<&T> { self.cursor_back().peek() }
+}
+
+impl <T: PartialEq + PartialOrd + Clone> OrderedSet<T> for Jblst<T> {
+    fn add(&mut self, value: T) -> Result<(),TreeError<T>> { Jblst::add(self, value) }
+    fn remove(&mut self, value: T) -> Result<(),TreeError<T>> { self.drop_value(value) }
+    fn contains(&self, value: &T) -> bool { Jblst::contains(self, value) }
+    fn len(&self) -> usize { self.get_size() as usize }
+    fn iter<'a>(&'a self) -> impl Iterator<Item = &'a T> where T: 'a { Jblst::iter(self) }
+    fn range<'a, R: RangeBounds<T>>(&'a self, range: R) -> impl Iterator<Item = &'a T> where T: 'a { Jblst::range(self, range) }
+    fn least(&self) -> Option<&T> { self.cursor_front().peek() }
+    fn greatest(&self) -> Option<&T> { self.cursor_back().peek() }
+}
+
+impl <T: PartialEq + PartialOrd + Clone> OrderedMultiset<T> for Jblst<T> {
+    fn count(&self, value: &T) -> usize { Jblst::count(self, value) }
+    fn iter_distinct<'a>(&'a self) -> impl Iterator<Item = &'a T> where T: 'a { self.iter_unique() }
+    fn iter_counts<'a>(&'a self) -> impl Iterator<Item = (&'a T, usize)> where T: 'a { Jblst::iter_counts(self) }
+}
+
+impl <T: PartialEq + PartialOrd> OrderedSet<T> for Javlt<T> {
+    fn add(&mut self, value: T) -> Result<(),TreeError<T>> { Javlt::add(self, value) }
+    fn remove(&mut self, value: T) -> Result<(),TreeError<T>> { self.drop_value(value) }
+    fn contains(&self, value: &T) -> bool { Javlt::contains(self, value) }
+    fn len(&self) -> usize { self.get_size() as usize }
+    fn iter<'a>(&'a self) -> impl Iterator<Item = &'a T> where T: 'a { self.iter_values() }
+    fn range<'a, R: RangeBounds<T>>(&'a self, range: R) -> impl Iterator<Item = &'a T> where T: 'a { Javlt::range(self, range) }
+    fn least(&self) -> Option<&T> { Javlt::least(self) }
+    fn greatest(&self) -> Option<&T> { Javlt::greatest(self) }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run the same checks on any set holding unique values.
+    fn check_set<S: OrderedSet<u32> + Default>() {
+        let mut set = S::default();
+        assert!( set.is_empty() );
+        assert_eq!( None, set.least() );
+        assert_eq!( 0, set.range(..).count() );
+        for value in (0..100).map(|n| n * 37 % 100) {
+            set.add(value).unwrap();
+        }
+        assert_eq!( Err(TreeError::ValueAlreadyStored { value: 5 }), set.add(5) );
+        set.remove(50).unwrap();
+        assert_eq!( Err(TreeError::ValueNotFound { value: 50 }), set.remove(50) );
+        assert_eq!( 99, set.len() );
+        assert!( set.iter().cloned().eq((0..100).filter(|n| *n != 50)) );
+        assert_eq!( vec!(&48,&49,&51), set.range(48..52).collect::<Vec<_>>() );
+        assert_eq!( vec!(&49,&51), set.range((Bound::Excluded(48), Bound::Included(51))).collect::<Vec<_>>() );
+        assert_eq!( vec!(&98,&99), set.range(98..).collect::<Vec<_>>() );
+        assert_eq!( 0, set.range(100..).count() );
+        assert_eq!( 3, set.range(..=2).count() );
+        assert_eq!( (Some(&0), Some(&99)), (set.least(), set.greatest()) );
+    }
+
+    #[test]
+    fn sets_behave_alike() {
+        check_set::<Jbst<u32>>();
+        check_set::<Javlt<u32>>();
+        let mut lazy = Jbst::with_lazy_deletion(10);
+        OrderedSet::add(&mut lazy, 1).unwrap();
+        OrderedSet::add(&mut lazy, 2).unwrap();
+        OrderedSet::remove(&mut lazy, 1).unwrap();
+        // the tombstoned value is skipped
+        assert_eq!( Some(&2), lazy.least() );
+        assert_eq!( vec!(&2), lazy.range(..).collect::<Vec<_>>() );
+    }
+
+    #[test]
+    fn multiset_keeps_copies() {
+        let mut bag = Jblst::from_collection([3,1,3,2,3]);
+        assert_eq!( 5, OrderedSet::len(&bag) );
+        assert_eq!( 3, OrderedMultiset::count(&bag, &3) );
+        assert_eq!( vec!(&2,&3,&3,&3), bag.range(2..).collect::<Vec<_>>() );
+        OrderedSet::remove(&mut bag, 3).unwrap();
+        assert_eq!( vec!((&1,1),(&2,1),(&3,2)), OrderedMultiset::iter_counts(&bag).collect::<Vec<_>>() );
+        assert_eq!( vec!(&1,&2,&3), bag.iter_distinct().collect::<Vec<_>>() );
+        assert_eq!( Some(&3), bag.greatest() );
+    }
+
+    #[test]
+    fn sets_work_as_trait_objects() {
+        let mut sets: Vec<Box<dyn OrderedSet<u32>>> = vec!(Box::new(Jbst::new()), Box::new(Javlt::new()));
+        for set in sets.iter_mut() {
+            set.add(2).unwrap();
+            set.add(1).unwrap();
+            assert_eq!( Err(TreeError::ValueAlreadyStored { value: 2 }), set.add(2) );
+            assert_eq!( (2, Some(&2)), (set.len(), set.greatest()) );
+        }
+    }
+
+    #[test]
+    fn sets_chosen_at_runtime() {
+        assert_eq!( None, SetKind::from_name("btree") );
+        for name in ["Jbst", "jblst", "JAVLT"] {
+            let mut set = SetKind::from_name(name).unwrap().new_set::<u32>();
+            assert!( set.is_empty() );
+            for value in [5, 3, 8, 1, 3] {
+                let _ = set.add(value);
+            }
+            assert!( set.contains(&8) );
+            set.remove(8).unwrap();
+            assert_eq!( (Some(&1), Some(&5)), (set.least(), set.greatest()) );
+            let expected = match name {
+                "jblst" => vec!(&3,&3,&5),
+                _ => vec!(&3,&5),
+            };
+            assert_eq!( expected, set.boxed_range(Bound::Excluded(&1), Bound::Unbounded).collect::<Vec<_>>() );
+            assert_eq!( expected.len() + 1, set.boxed_iter().count() );
+            assert_eq!( expected.len() + 1, set.len() );
+        }
+    }
+
+}
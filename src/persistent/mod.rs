@@ -0,0 +1,509 @@
+use std::{cmp::max, fmt, ops::{Bound, RangeBounds}, sync::Arc};
+
+use crate::bounds::{is_above, is_below};
+use crate::errors::TreeError;
+
+
+
+/// # Persistent AVL Tree
+///
+/// An AVL tree (like `Javlt`) whose nodes are shared between copies of the tree.  Cloning it is O(1),
+/// and a change copies only the nodes on the path from the root to the changed value (O(log(n)) of them),
+/// so every other copy keeps the values it had.  That makes it cheap to hold on to old versions.
+///
+///     use jtree::persistent::PersistentJavlt;
+///
+///     let mut tree = PersistentJavlt::from_collection([1,2,3]);
+///     let before = tree.clone();
+///     tree.add(4).unwrap();
+///     tree.drop_value(1).unwrap();
+///     assert_eq!( vec!(2,3,4), tree.as_vec() );
+///     assert_eq!( vec!(1,2,3), before.as_vec() );
+///
+/// The nodes are reference counted with `Arc`, so copies can be sent to (and read by) other threads.
+pub struct PersistentJavlt<T: PartialEq + PartialOrd + Clone> {
+    size: u32,
+    root: Link<T>,
+}
+
+type Link<T> = Option<Arc<Node<T>>>;
+
+impl <T: PartialEq + PartialOrd + Clone> PersistentJavlt<T> {
+
+    /// Create a new tree with no data
+    pub fn new() -> Self {
+        Self {
+            size: 0,
+            root: None,
+        }
+    }
+
+    /// Create a new tree from a collection (vector, array, or whatever), skipping duplicates.
+    pub fn from_collection<U: IntoIterator<Item = T>>(collection: U) -> Self {
+        let mut new_tree = Self::new();
+        for value in collection {
+            let _ = new_tree.add(value);
+        }
+        new_tree
+    }
+
+    /// Insert a value, returning a TreeError::ValueAlreadyStored if it's already in the tree.
+    pub fn add(&mut self, value: T) -> Result<(),TreeError<T>> {
+        // check first, so that a failed add doesn't copy nodes it won't change
+        if self.contains(&value) {
+            return Err(TreeError::ValueAlreadyStored { value });
+        }
+        if self.size == u32::MAX {
+            return Err(TreeError::CapacityExceeded { value });
+        }
+        Node::add(&mut self.root, value);
+        self.size += 1;
+        #[cfg(feature = "paranoid")]
+        self.paranoid_check("add");
+        Ok(())
+    }
+
+    /// If the value is in the tree, delete it.  Otherwise a TreeError::ValueNotFound will be returned.
+    pub fn drop_value(&mut self, value: T) -> Result<(),TreeError<T>> {
+        if !self.contains(&value) {
+            return Err(TreeError::ValueNotFound { value });
+        }
+        Node::drop_value(&mut self.root, &value);
+        self.size -= 1;
+        #[cfg(feature = "paranoid")]
+        self.paranoid_check("drop_value");
+        Ok(())
+    }
+
+    /// Get the number of values in the tree
+    pub fn get_size(&self) -> u32 {
+        self.size
+    }
+
+    /// Returns true if the value is currently a member of the tree
+    pub fn contains(&self, value: &T) -> bool {
+        let mut link = &self.root;
+        while let Some(node) = link {
+            if *value == node.value {
+                return true;
+            }
+            link = if *value < node.value { &node.left } else { &node.right };
+        }
+        false
+    }
+
+    /// Returns the stored value equal to `value`, if there is one.  This is the tree's own copy, which
+    /// matters for values that compare equal without being identical.
+    pub fn get(&self, value: &T) -> Option<&T> {
+        let mut link = &self.root;
+        while let Some(node) = link {
+            if *value == node.value {
+                return Some(&node.value);
+            }
+            link = if *value < node.value { &node.left } else { &node.right };
+        }
+        None
+    }
+
+    /// Returns references to the values within the range, in order from least to greatest.  Only the
+    /// subtrees that overlap the range are visited.
+    ///
+    ///     use jtree::persistent::PersistentJavlt;
+    ///
+    ///     let tree = PersistentJavlt::from_collection(1..=10);
+    ///     assert_eq!( vec!(&4,&5,&6), tree.range(4..7) );
+    pub fn range<R: RangeBounds<T>>(&self, range: R) -> Vec<&T> {
+        let mut vals = Vec::new();
+        match &self.root {
+            None => (),
+            Some(branch) => branch.collect_range(range.start_bound(), range.end_bound(), &mut vals),
+        }
+        vals
+    }
+
+    /// Returns all the values in the tree as an ordered Vec from least to greatest.
+    pub fn as_vec(&self) -> Vec<T> {
+        let mut vals = Vec::with_capacity(self.size as usize);
+        self.visit_in_order(|value| vals.push(value.clone()));
+        vals
+    }
+
+    /// Call `f` on each value in order, from least to greatest, without copying the values into a Vec.
+    pub fn visit_in_order<F: FnMut(&T)>(&self, mut f: F) {
+        match &self.root {
+            None => (),
+            Some(branch) => branch.visit_in_order(&mut f),
+        }
+    }
+
+    /// Build a perfectly balanced tree from values that are already sorted from least to greatest, with no
+    /// duplicates, in O(n).
+    pub(crate) fn from_sorted_vec(values: Vec<T>) -> Self {
+        let size = values.len();
+        let tree = Self {
+            size: u32::try_from(size).expect("more values than a tree can count"),
+            root: Node::from_sorted(&mut values.into_iter(), size),
+        };
+        #[cfg(feature = "paranoid")]
+        tree.paranoid_check("from_sorted_vec");
+        tree
+    }
+
+    /// Call `f` on a mutable reference to each value, from least to greatest, copying any node another tree
+    /// shares first.  Only for values whose ordering `f` leaves alone, so it isn't public.
+    pub(crate) fn visit_in_order_mut<F: FnMut(&mut T)>(&mut self, mut f: F) {
+        Node::visit_in_order_mut(&mut self.root, &mut f);
+    }
+
+    /// Returns the least value in the tree, or None if the tree is empty
+    pub fn least_value(&self) -> Option<T> {
+        let mut node = self.root.as_ref()?;
+        while let Some(left) = &node.left {
+            node = left;
+        }
+        Some(node.value.clone())
+    }
+
+    /// Returns the greatest value in the tree, or None if the tree is empty
+    pub fn greatest_value(&self) -> Option<T> {
+        let mut node = self.root.as_ref()?;
+        while let Some(right) = &node.right {
+            node = right;
+        }
+        Some(node.value.clone())
+    }
+
+    /// Verify the tree's internal consistency: that values are in order, every node's height is correct
+    /// and its subtrees are balanced, and the size matches the number of nodes.  Returns a
+    /// TreeError::InvariantViolation describing the first problem found, if any.
+    pub fn check_invariants(&self) -> Result<(),TreeError<T>> {
+        let nodes = match &self.root {
+            None => 0,
+            Some(branch) => branch.check_invariants(&mut String::from("root"), None, None)?,
+        };
+        if nodes != self.size {
+            return Err(TreeError::InvariantViolation(format!("size is {} but the tree holds {} values", self.size, nodes)));
+        }
+        Ok(())
+    }
+
+    /// With the `paranoid` feature, every change to the tree is followed by this check, which panics
+    /// with a description of the problem if the change broke an invariant.
+    #[cfg(feature = "paranoid")]
+    fn paranoid_check(&self, operation: &str) {
+        if let Err(TreeError::InvariantViolation(problem)) = self.check_invariants() {
+            panic!("PersistentJavlt::{operation} broke the tree: {problem} (size {})", self.size);
+        }
+    }
+
+}
+
+// a clone shares all of the nodes; they're copied later, as and when either tree changes them
+impl <T: PartialEq + PartialOrd + Clone> Clone for PersistentJavlt<T> {
+    fn clone(&self) -> Self {
+        Self {
+            size: self.size,
+            root: self.root.clone(),
+        }
+    }
+}
+
+impl <T: PartialEq + PartialOrd + Clone> Default for PersistentJavlt<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <T: PartialEq + PartialOrd + Clone + std::fmt::Debug> fmt::Debug for PersistentJavlt<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("PersistentJavlt")
+            .field("size", &self.get_size())
+            .field("values", &self.as_vec())
+            .finish()
+    }
+}
+
+
+#[derive(Clone)]
+struct Node<T: PartialEq + PartialOrd + Clone> {
+    value: T,
+    height: u32,
+    left: Link<T>,
+    right: Link<T>,
+}
+
+// The changing functions take the link that owns a node, rather than the node itself, so that a rotation
+// can put a different node in its place.  `Arc::make_mut` copies a node before changing it if any other
+// tree still shares it.
+impl <T: PartialEq + PartialOrd + Clone> Node<T> {
+
+    fn new(value: T) -> Self {
+        Self {
+            value,
+            height: 1,
+            left: None,
+            right: None,
+        }
+    }
+
+    /// Insert a value that isn't already in the (sub)tree, then rebalance it.
+    fn add(link: &mut Link<T>, value: T) {
+        match link {
+            None => *link = Some(Arc::new(Node::new(value))),
+            Some(node) => {
+                let node = Arc::make_mut(node);
+                if value < node.value {
+                    Self::add(&mut node.left, value);
+                } else {
+                    Self::add(&mut node.right, value);
+                }
+                Self::rebalance(link);
+            },
+        }
+    }
+
+    /// Delete a value that is in the (sub)tree, then rebalance it.
+    fn drop_value(link: &mut Link<T>, value: &T) {
+        let node = match link {
+            Some(node) => Arc::make_mut(node),
+            None => return,
+        };
+        if *value < node.value {
+            Self::drop_value(&mut node.left, value);
+        } else if *value > node.value {
+            Self::drop_value(&mut node.right, value);
+        } else if node.left.is_none() {
+            // replace the node with its only child (or nothing)
+            let right = node.right.take();
+            *link = right;
+            return;
+        } else if node.right.is_none() {
+            let left = node.left.take();
+            *link = left;
+            return;
+        } else {
+            // both children are branches, so take the value's immediate successor out of the right branch
+            node.value = Self::take_least(&mut node.right);
+        }
+        Self::rebalance(link);
+    }
+
+    /// Delete the least value in a non-empty (sub)tree and return it.
+    fn take_least(link: &mut Link<T>) -> T {
+        let node = Arc::make_mut(link.as_mut().unwrap());
+        if node.left.is_some() {
+            let least = Self::take_least(&mut node.left);
+            Self::rebalance(link);
+            return least;
+        }
+        let least = node.value.clone();
+        let right = node.right.take();
+        *link = right;
+        least
+    }
+
+    /// Update the node's height, and rotate the (sub)tree if it has become unbalanced.
+    fn rebalance(link: &mut Link<T>) {
+        let node = match link {
+            Some(node) => Arc::make_mut(node),
+            None => return,
+        };
+        node.height = node.compute_height();
+        let bf = node.compute_balancing_factor();
+        if bf > 1 {
+            // right-heavy; if the right child is left-heavy, this is a Right Left situation needing two rotations
+            if node.right.as_ref().unwrap().compute_balancing_factor() < 0 {
+                Self::rotate_right(&mut node.right);
+            }
+            Self::rotate_left(link);
+        } else if bf < -1 {
+            // left-heavy; if the left child is right-heavy, this is a Left Right situation needing two rotations
+            if node.left.as_ref().unwrap().compute_balancing_factor() > 0 {
+                Self::rotate_left(&mut node.left);
+            }
+            Self::rotate_right(link);
+        }
+    }
+
+    /// Make the node's right child the root of its (sub)tree.
+    fn rotate_left(link: &mut Link<T>) {
+        let mut old_root = link.take().unwrap();
+        let node = Arc::make_mut(&mut old_root);
+        let mut new_root = node.right.take().unwrap();
+        let child = Arc::make_mut(&mut new_root);
+        node.right = child.left.take();
+        node.height = node.compute_height();
+        child.left = Some(old_root);
+        child.height = child.compute_height();
+        *link = Some(new_root);
+    }
+
+    /// Make the node's left child the root of its (sub)tree.
+    fn rotate_right(link: &mut Link<T>) {
+        let mut old_root = link.take().unwrap();
+        let node = Arc::make_mut(&mut old_root);
+        let mut new_root = node.left.take().unwrap();
+        let child = Arc::make_mut(&mut new_root);
+        node.left = child.right.take();
+        node.height = node.compute_height();
+        child.right = Some(old_root);
+        child.height = child.compute_height();
+        *link = Some(new_root);
+    }
+
+    /// Height of a subtree is the height of its largest child subtree, plus 1.
+    fn compute_height(&self) -> u32 {
+        let left_height = self.left.as_ref().map_or(0, |node| node.height);
+        let right_height = self.right.as_ref().map_or(0, |node| node.height);
+        max(left_height, right_height).saturating_add(1)
+    }
+
+    /// Balancing factor is the height of the right subtree minus the height of the left subtree.
+    fn compute_balancing_factor(&self) -> i64 {
+        let left_height = self.left.as_ref().map_or(0, |node| node.height);
+        let right_height = self.right.as_ref().map_or(0, |node| node.height);
+        i64::from(right_height) - i64::from(left_height)
+    }
+
+    /// Recursively call `f` on this (sub)tree's values, from least to greatest.
+    fn visit_in_order<F: FnMut(&T)>(&self, f: &mut F) {
+        if let Some(node) = &self.left {
+            node.visit_in_order(f);
+        }
+        f(&self.value);
+        if let Some(node) = &self.right {
+            node.visit_in_order(f);
+        }
+    }
+
+    /// Recursively call `f` on a mutable reference to each of this (sub)tree's values, from least to greatest.
+    fn visit_in_order_mut<F: FnMut(&mut T)>(link: &mut Link<T>, f: &mut F) {
+        if let Some(node) = link {
+            let node = Arc::make_mut(node);
+            Self::visit_in_order_mut(&mut node.left, f);
+            f(&mut node.value);
+            Self::visit_in_order_mut(&mut node.right, f);
+        }
+    }
+
+    /// Recursively build a perfectly balanced (sub)tree from the next `len` of the sorted, unique values.
+    fn from_sorted<I: Iterator<Item = T>>(values: &mut I, len: usize) -> Link<T> {
+        if len == 0 {
+            return None;
+        }
+        let left = Self::from_sorted(values, len / 2);
+        let mut node = Node::new(values.next().unwrap());
+        node.left = left;
+        node.right = Self::from_sorted(values, len - len / 2 - 1);
+        node.height = node.compute_height();
+        Some(Arc::new(node))
+    }
+
+    /// Recursively add references to this (sub)tree's values between the bounds to the vector, from least to greatest.
+    fn collect_range<'a>(&'a self, lower: Bound<&T>, upper: Bound<&T>, values: &mut Vec<&'a T>) {
+        let below = is_below(&self.value, lower);
+        let above = is_above(&self.value, upper);
+        if let (false, Some(node)) = (below, &self.left) {
+            node.collect_range(lower, upper, values);
+        }
+        if !below && !above {
+            values.push(&self.value);
+        }
+        if let (false, Some(node)) = (above, &self.right) {
+            node.collect_range(lower, upper, values);
+        }
+    }
+
+    /// Recursively verify that this (sub)tree's values are in order and strictly between the bounds
+    /// set by its ancestors, and that its heights and balance are right.  Returns the number of nodes.
+    /// The path (e.g. "root.left.right") is only used to describe where a problem was found.
+    fn check_invariants(&self, path: &mut String, lower: Option<&T>, upper: Option<&T>) -> Result<u32,TreeError<T>> {
+        if lower.is_some_and(|lower| self.value <= *lower) || upper.is_some_and(|upper| self.value >= *upper) {
+            return Err(TreeError::InvariantViolation(format!("value at {path} is out of order")));
+        }
+        let mut nodes = 1;
+        let path_len = path.len();
+        if let Some(node) = &self.left {
+            path.push_str(".left");
+            nodes += node.check_invariants(path, lower, Some(&self.value))?;
+            path.truncate(path_len);
+        }
+        if let Some(node) = &self.right {
+            path.push_str(".right");
+            nodes += node.check_invariants(path, Some(&self.value), upper)?;
+            path.truncate(path_len);
+        }
+        if self.height != self.compute_height() {
+            return Err(TreeError::InvariantViolation(format!("node at {path} has height {} but should be {}", self.height, self.compute_height())));
+        }
+        let balancing_factor = self.compute_balancing_factor();
+        if !(-1..=1).contains(&balancing_factor) {
+            return Err(TreeError::InvariantViolation(format!("node at {path} is unbalanced, with a balancing factor of {balancing_factor}")));
+        }
+        Ok(nodes)
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_and_drop() {
+        let mut tree = PersistentJavlt::from_collection([5,3,8,1,4,7,9,2,6]);
+        assert_eq!( 9, tree.get_size() );
+        assert_eq!( Err(TreeError::ValueAlreadyStored { value: 4 }), tree.add(4) );
+        assert_eq!( Ok(()), tree.drop_value(3) );
+        assert_eq!( Err(TreeError::ValueNotFound { value: 3 }), tree.drop_value(3) );
+        assert_eq!( vec!(1,2,4,5,6,7,8,9), tree.as_vec() );
+        assert_eq!( Some(1), tree.least_value() );
+        assert_eq!( Some(9), tree.greatest_value() );
+        assert!( tree.contains(&6) );
+        assert!( !tree.contains(&3) );
+        assert_eq!( Some(&6), tree.get(&6) );
+        assert_eq!( None, tree.get(&3) );
+        assert_eq!( vec!(&1,&2,&4), tree.range(..=4) );
+        assert_eq!( vec!(&8,&9), tree.range(8..) );
+        assert!( tree.range(10..).is_empty() );
+        assert_eq!( Ok(()), tree.check_invariants() );
+    }
+
+    #[test]
+    fn clones_keep_their_values() {
+        // every version is kept while a pseudo-random mix of adds and drops goes on
+        let mut tree = PersistentJavlt::new();
+        let mut versions = Vec::new();
+        let mut state: u64 = 42;
+        for _ in 0..500 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let value = state % 100;
+            if state & 128 == 0 {
+                let _ = tree.add(value);
+            } else {
+                let _ = tree.drop_value(value);
+            }
+            assert_eq!( Ok(()), tree.check_invariants() );
+            versions.push((tree.clone(), tree.as_vec()));
+        }
+        for (version, values) in &versions {
+            assert_eq!( Ok(()), version.check_invariants() );
+            assert_eq!( *values, version.as_vec() );
+        }
+    }
+
+    #[test]
+    fn changes_copy_only_one_path() {
+        let tree = PersistentJavlt::from_collection(0..1000);
+        let mut changed = tree.clone();
+        changed.add(1000).unwrap();
+        // the new value goes on the right edge, so the left subtree is still shared
+        let (original_root, changed_root) = (tree.root.as_ref().unwrap(), changed.root.as_ref().unwrap());
+        assert!( !Arc::ptr_eq(original_root, changed_root) );
+        assert!( Arc::ptr_eq(original_root.left.as_ref().unwrap(), changed_root.left.as_ref().unwrap()) );
+    }
+
+}
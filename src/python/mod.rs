@@ -0,0 +1,290 @@
+//! With the `python` feature, `Javlt` and `Jblst` are exposed to Python as classes of the same names, holding
+//! any Python objects that can be compared with each other (numbers, strings, tuples, ...).  They're ordered
+//! with Python's own `<` and `==`.  To build the extension module, use maturin with
+//! `--features python,pyo3/extension-module`; then, from Python:
+//!
+//! ```text
+//! from jtree import Javlt, Jblst
+//!
+//! tree = Javlt([5, 1, 4])
+//! tree.insert(3)        # True
+//! tree.insert(3)        # False: already there
+//! 4 in tree             # True
+//! list(tree)            # [1, 3, 4, 5]
+//! tree.range(2, 5)      # [3, 4]: from the first bound up to (but not including) the second
+//! tree.remove(4)        # raises KeyError if it isn't there
+//!
+//! counts = Jblst(["b", "a", "b"])
+//! list(counts)          # ['a', 'b', 'b']
+//! ```
+//!
+//! A value that can't be compared with the values already in the tree (say, a string added to a tree of
+//! numbers) raises Python's TypeError, and leaves the tree as it was.
+
+use std::{cell::Cell, cmp::Ordering};
+
+use pyo3::exceptions::PyKeyError;
+use pyo3::prelude::*;
+use pyo3::types::PyList;
+
+use crate::javlt::Javlt;
+use crate::jblst::Jblst;
+
+thread_local! {
+    /// The error raised by the latest failed comparison, if it hasn't been reported yet.
+    static COMPARE_ERROR: Cell<Option<PyErr>> = const { Cell::new(None) };
+}
+
+/// A Python object, compared with Python's operators.  A comparison that raises an error is treated as
+/// incomparable, and the error is kept to be raised by the method that made it.
+struct PyValue(Py<PyAny>);
+
+impl PyValue {
+
+    /// Compare with Python's operators, keeping any error for `checked` to raise.
+    fn compare<R>(&self, other: &Self, f: fn(&Bound<'_, PyAny>, &Bound<'_, PyAny>) -> PyResult<R>) -> Option<R> {
+        Python::attach(|py| match f(self.0.bind(py), other.0.bind(py)) {
+            Ok(result) => Some(result),
+            Err(error) => {
+                COMPARE_ERROR.set(Some(error));
+                None
+            },
+        })
+    }
+
+}
+
+impl PartialEq for PyValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.compare(other, |a, b| a.eq(b)).unwrap_or(false)
+    }
+}
+
+impl PartialOrd for PyValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.compare(other, |a, b| a.compare(b))
+    }
+}
+
+impl Clone for PyValue {
+    fn clone(&self) -> Self {
+        Python::attach(|py| PyValue(self.0.clone_ref(py)))
+    }
+}
+
+/// Run `f`, then raise the error from any comparison it made that failed.
+fn checked<R>(f: impl FnOnce() -> R) -> PyResult<R> {
+    COMPARE_ERROR.set(None);
+    let result = f();
+    return match COMPARE_ERROR.take() {
+        Some(error) => Err(error),
+        None => Ok(result),
+    };
+}
+
+/// The values from the cursor's first one, up to (but not including) `upper`.
+fn values_below<'a, I: Iterator<Item = &'a PyValue>>(first: Option<&'a PyValue>, rest: I, upper: &PyValue) -> Vec<Py<PyAny>> {
+    first.into_iter().chain(rest)
+        .take_while(|value| *value < upper)
+        .map(|value| value.clone().0)
+        .collect()
+}
+
+/// An AVL tree of unique values, in order: an ordered set.
+#[pyclass(name = "Javlt")]
+pub struct PyJavlt {
+    tree: Javlt<PyValue>,
+}
+
+#[pymethods]
+impl PyJavlt {
+
+    /// Create a tree, optionally from the values of an iterable, skipping duplicates.
+    #[new]
+    #[pyo3(signature = (values=None))]
+    fn new(values: Option<&Bound<'_, PyAny>>) -> PyResult<Self> {
+        let mut new_tree = Self { tree: Javlt::new() };
+        if let Some(values) = values {
+            for value in values.try_iter()? {
+                new_tree.insert(value?.unbind())?;
+            }
+        }
+        Ok(new_tree)
+    }
+
+    /// Insert a value, returning False if it was already in the tree.
+    fn insert(&mut self, value: Py<PyAny>) -> PyResult<bool> {
+        let value = PyValue(value);
+        // search first, so that a value that can't be compared is turned away before the tree changes
+        if checked(|| self.tree.contains(&value))? {
+            return Ok(false);
+        }
+        Ok(self.tree.add(value).is_ok())
+    }
+
+    /// Delete a value, raising KeyError if it isn't in the tree.
+    fn remove(&mut self, value: Py<PyAny>) -> PyResult<()> {
+        let value = PyValue(value);
+        if !checked(|| self.tree.contains(&value))? {
+            return Err(PyKeyError::new_err(value.0));
+        }
+        let _ = self.tree.drop_value(value);
+        Ok(())
+    }
+
+    /// The values from `lower` up to (but not including) `upper`, in order.
+    fn range(&self, lower: Py<PyAny>, upper: Py<PyAny>) -> PyResult<Vec<Py<PyAny>>> {
+        let (lower, upper) = (PyValue(lower), PyValue(upper));
+        checked(|| {
+            let cursor = self.tree.cursor_at(&lower);
+            values_below(cursor.peek(), cursor, &upper)
+        })
+    }
+
+    fn __contains__(&self, value: Py<PyAny>) -> PyResult<bool> {
+        checked(|| self.tree.contains(&PyValue(value)))
+    }
+
+    fn __len__(&self) -> usize {
+        self.tree.get_size() as usize
+    }
+
+    fn __iter__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let values = self.tree.values().into_iter().map(|value| value.0.clone_ref(py));
+        Ok(PyList::new(py, values)?.try_iter()?.into_any())
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        let values = PyList::new(py, self.tree.values().into_iter().map(|value| value.0.clone_ref(py)))?;
+        Ok(format!("Javlt({})", values.repr()?))
+    }
+
+}
+
+/// A binary search tree of values in order, keeping duplicates: an ordered list.
+#[pyclass(name = "Jblst")]
+pub struct PyJblst {
+    tree: Jblst<PyValue>,
+}
+
+#[pymethods]
+impl PyJblst {
+
+    /// Create a tree, optionally from the values of an iterable.
+    #[new]
+    #[pyo3(signature = (values=None))]
+    fn new(values: Option<&Bound<'_, PyAny>>) -> PyResult<Self> {
+        let mut new_tree = Self { tree: Jblst::new() };
+        if let Some(values) = values {
+            for value in values.try_iter()? {
+                new_tree.insert(value?.unbind())?;
+            }
+        }
+        Ok(new_tree)
+    }
+
+    /// Insert a value, after any equal values already in the tree.
+    fn insert(&mut self, value: Py<PyAny>) -> PyResult<()> {
+        let value = PyValue(value);
+        checked(|| self.tree.contains(&value))?;
+        let _ = self.tree.add(value);
+        Ok(())
+    }
+
+    /// Delete one copy of a value, raising KeyError if it isn't in the tree.
+    fn remove(&mut self, value: Py<PyAny>) -> PyResult<()> {
+        let value = PyValue(value);
+        if !checked(|| self.tree.contains(&value))? {
+            return Err(PyKeyError::new_err(value.0));
+        }
+        let _ = self.tree.drop_value(value);
+        Ok(())
+    }
+
+    /// The values from `lower` up to (but not including) `upper`, in order, with their duplicates.
+    fn range(&self, lower: Py<PyAny>, upper: Py<PyAny>) -> PyResult<Vec<Py<PyAny>>> {
+        let (lower, upper) = (PyValue(lower), PyValue(upper));
+        checked(|| {
+            let cursor = self.tree.cursor_at(&lower);
+            values_below(cursor.peek(), cursor, &upper)
+        })
+    }
+
+    fn __contains__(&self, value: Py<PyAny>) -> PyResult<bool> {
+        checked(|| self.tree.contains(&PyValue(value)))
+    }
+
+    fn __len__(&self) -> usize {
+        self.tree.get_size() as usize
+    }
+
+    fn __iter__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let values = self.tree.values().into_iter().map(|value| value.0.clone_ref(py));
+        Ok(PyList::new(py, values)?.try_iter()?.into_any())
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        let values = PyList::new(py, self.tree.values().into_iter().map(|value| value.0.clone_ref(py)))?;
+        Ok(format!("Jblst({})", values.repr()?))
+    }
+
+}
+
+/// The `jtree` Python module.
+#[pymodule]
+fn jtree(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyJavlt>()?;
+    module.add_class::<PyJblst>()?;
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use pyo3::types::PyDict;
+
+    use super::*;
+
+    fn run(code: &str) {
+        Python::initialize();
+        Python::attach(|py| {
+            let globals = PyDict::new(py);
+            globals.set_item("Javlt", py.get_type::<PyJavlt>()).unwrap();
+            globals.set_item("Jblst", py.get_type::<PyJblst>()).unwrap();
+            let code = std::ffi::CString::new(code).unwrap();
+            py.run(&code, Some(&globals), None).unwrap();
+        });
+    }
+
+    #[test]
+    fn python_can_use_the_trees() {
+        run(r#"
+tree = Javlt([5, 1, 4, 1])
+assert len(tree) == 3
+assert tree.insert(3) and not tree.insert(3)
+assert 4 in tree and 2 not in tree
+assert list(tree) == [1, 3, 4, 5]
+assert tree.range(2, 5) == [3, 4]
+tree.remove(4)
+try:
+    tree.remove(4)
+    assert False
+except KeyError:
+    pass
+try:
+    tree.insert("x")
+    assert False
+except TypeError:
+    pass
+assert repr(tree) == "Javlt([1, 3, 5])"
+
+words = Jblst(["b", "a", "b", "c"])
+assert list(words) == ["a", "b", "b", "c"]
+assert words.range("b", "c") == ["b", "b"]
+words.remove("b")
+assert list(words) == ["a", "b", "c"]
+assert len(words) == 3
+"#);
+    }
+
+}
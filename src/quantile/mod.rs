@@ -0,0 +1,187 @@
+use std::fmt;
+
+use crate::aggregate::{AggregateJavlt, Count};
+use crate::errors::TreeError;
+
+
+
+/// # Quantile Tracker
+///
+/// Takes in a stream of values and answers quantile queries (the median, the 95th percentile, ...) at any
+/// point along the way.  The values are kept in order in an `AggregateJavlt` that counts each subtree's values,
+/// so adding one is O(log(n)), and so is a query, which follows the counts down to the value at the requested rank.
+///
+///     use jtree::QuantileTracker;
+///
+///     let mut latencies = QuantileTracker::new();
+///     for ms in [12, 7, 30, 9, 15, 11, 250, 8, 10, 13] {
+///         latencies.push(ms).unwrap();
+///     }
+///     assert_eq!( Some(11), latencies.median() );
+///     assert_eq!( Some(30), latencies.quantile(0.9) );
+///     assert_eq!( Some(250), latencies.quantile(1.0) );
+///
+/// Quantiles use the nearest-rank method: the `q` quantile of `n` values is the `ceil(q * n)`-th least value
+/// (or the least value, for `q = 0`), so the answer is always one of the values seen.
+///
+/// For long streams, `with_capacity` bounds the memory used by keeping a uniform random sample (a
+/// "reservoir") of the values seen, and answers from the sample, which trades exactness for space.
+pub struct QuantileTracker<T: PartialEq + PartialOrd + Clone> {
+    samples: AggregateJavlt<Sample<T>, Count>,
+    capacity: Option<usize>,
+    reservoir: Vec<Sample<T>>, // with a capacity, the same samples in slot order, for random replacement
+    seen: u64,
+    seed: u64, // xorshift state for choosing which samples to replace
+}
+
+/// A value tagged with its position in the stream, so that equal values are kept as distinct samples.
+#[derive(PartialEq, PartialOrd, Clone)]
+struct Sample<T> {
+    value: T,
+    position: u64,
+}
+
+impl <T: PartialEq + PartialOrd + Clone> QuantileTracker<T> {
+
+    /// Create a tracker that keeps every value, for exact answers
+    pub fn new() -> Self {
+        Self {
+            samples: AggregateJavlt::new(),
+            capacity: None,
+            reservoir: Vec::new(),
+            seen: 0,
+            seed: 0x2545f4914f6cdd1d,
+        }
+    }
+
+    /// Create a tracker that keeps a uniform random sample of at most `capacity` values, for approximate
+    /// answers in bounded memory.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity: Some(capacity),
+            reservoir: Vec::with_capacity(capacity),
+            ..Self::new()
+        }
+    }
+
+    /// Take in a value.  Values that can't be ordered, even against themselves (such as a float NaN), are
+    /// refused with a TreeError::Incomparable.
+    pub fn push(&mut self, value: T) -> Result<(),TreeError<T>> {
+        if value.partial_cmp(&value).is_none() {
+            return Err(TreeError::Incomparable { value });
+        }
+        let sample = Sample { value, position: self.seen };
+        self.seen += 1;
+        match self.capacity {
+            None => {
+                let _ = self.samples.add(sample);
+            },
+            Some(capacity) if self.reservoir.len() < capacity => {
+                self.reservoir.push(sample.clone());
+                let _ = self.samples.add(sample);
+            },
+            Some(capacity) => {
+                // keep the new value with probability capacity / seen, in place of a random sample
+                let slot = (self.next_random() % self.seen) as usize;
+                if slot < capacity {
+                    let replaced = std::mem::replace(&mut self.reservoir[slot], sample.clone());
+                    let _ = self.samples.drop_value(replaced);
+                    let _ = self.samples.add(sample);
+                }
+            },
+        }
+        Ok(())
+    }
+
+    /// The `q` quantile (for `q` from 0 to 1) of the values seen, or None if no values have been seen or `q`
+    /// is out of range.
+    pub fn quantile(&self, q: f64) -> Option<T> {
+        let size = self.samples.get_size() as usize;
+        if size == 0 || !(0.0..=1.0).contains(&q) {
+            return None;
+        }
+        let rank = ((q * size as f64).ceil() as usize).clamp(1, size);
+        self.samples.nth(rank - 1).map(|sample| sample.value.clone())
+    }
+
+    /// The median (0.5 quantile) of the values seen
+    pub fn median(&self) -> Option<T> {
+        self.quantile(0.5)
+    }
+
+    /// The number of values seen, including any that weren't sampled
+    pub fn count(&self) -> u64 {
+        self.seen
+    }
+
+    /// The number of values being kept to answer queries
+    pub fn sample_size(&self) -> u32 {
+        self.samples.get_size()
+    }
+
+    /// Step the xorshift state and return it
+    fn next_random(&mut self) -> u64 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        self.seed
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd + Clone> Default for QuantileTracker<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <T: PartialEq + PartialOrd + Clone + fmt::Debug> fmt::Debug for QuantileTracker<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("QuantileTracker")
+            .field("count", &self.seen)
+            .field("sample_size", &self.sample_size())
+            .field("capacity", &self.capacity)
+            .field("median", &self.median())
+            .finish()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exact_quantiles_with_duplicates() {
+        let mut tracker = QuantileTracker::new();
+        assert_eq!( None, tracker.median() );
+        for value in [3, 1, 3, 3, 2] {
+            tracker.push(value).unwrap();
+        }
+        assert_eq!( 5, tracker.sample_size() );
+        assert_eq!( Some(1), tracker.quantile(0.0) );
+        assert_eq!( Some(1), tracker.quantile(0.2) );
+        assert_eq!( Some(2), tracker.quantile(0.4) );
+        assert_eq!( Some(3), tracker.median() );
+        assert_eq!( Some(3), tracker.quantile(1.0) );
+        assert_eq!( None, tracker.quantile(1.5) );
+        assert_eq!( None, tracker.quantile(f64::NAN) );
+        assert!( matches!(QuantileTracker::new().push(f64::NAN), Err(TreeError::Incomparable { .. })) );
+    }
+
+    #[test]
+    fn bounded_sample_stays_representative() {
+        let mut tracker = QuantileTracker::with_capacity(1000);
+        // a sorted stream is the worst case for a naive sample, which would keep only the first values
+        for value in 0..100_000 {
+            tracker.push(value).unwrap();
+        }
+        assert_eq!( 100_000, tracker.count() );
+        assert_eq!( 1000, tracker.sample_size() );
+        let median = tracker.median().unwrap();
+        assert!( (40_000..60_000).contains(&median), "median of the sample was {median}" );
+        let p90 = tracker.quantile(0.9).unwrap();
+        assert!( (85_000..95_000).contains(&p90), "90th percentile of the sample was {p90}" );
+    }
+
+}
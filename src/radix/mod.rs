@@ -0,0 +1,216 @@
+use std::{fmt, ops::{Bound, RangeBounds}};
+
+use crate::errors::TreeError;
+use crate::javlt::Javlt;
+use crate::ordered::OrderedSet;
+
+
+
+/// The number of buckets, one for each value of a key's top byte.
+const BUCKETS: usize = 256;
+
+/// # Radix Set
+///
+/// A set of `u64` keys split by their top byte among 256 small `Javlt`s.  Finding a key's bucket is a shift,
+/// and each bucket is a balanced tree of about 1/256th of the keys, so for keys spread evenly over the whole
+/// `u64` range (such as random IDs or hashes) every search is about 8 levels shorter than in one big tree.
+/// Keys that share their top byte (small counters, say) all land in one bucket, and get no benefit.  Since the
+/// buckets are in order of the top byte, the keys are still visited in order.
+///
+///     use jtree::radix::RadixSet;
+///
+///     let mut ids = RadixSet::new();
+///     ids.add(0xff00_0000_0000_0001).unwrap();
+///     ids.add(42).unwrap();
+///     ids.add(0x8000_0000_0000_0000).unwrap();
+///     assert!( ids.contains(42) );
+///     assert_eq!( vec!(42, 0x8000_0000_0000_0000, 0xff00_0000_0000_0001), ids.as_vec() );
+pub struct RadixSet {
+    buckets: Vec<Javlt<u64>>,
+    size: u32,
+}
+
+/// The bucket a key belongs in.
+fn bucket_of(key: u64) -> usize {
+    (key >> 56) as usize
+}
+
+impl RadixSet {
+
+    /// Create a new set with no data
+    pub fn new() -> Self {
+        Self {
+            buckets: (0..BUCKETS).map(|_| Javlt::new()).collect(),
+            size: 0,
+        }
+    }
+
+    /// Create a new set from a collection (vector, array, or whatever), skipping duplicates.
+    pub fn from_collection<U: IntoIterator<Item = u64>>(collection: U) -> Self {
+        let mut new_set = Self::new();
+        for key in collection {
+            let _ = new_set.add(key);
+        }
+        new_set
+    }
+
+    /// Insert a key, returning a TreeError::ValueAlreadyStored if it's already in the set.
+    pub fn add(&mut self, key: u64) -> Result<(),TreeError<u64>> {
+        if self.size == u32::MAX {
+            return Err(TreeError::CapacityExceeded { value: key });
+        }
+        self.buckets[bucket_of(key)].add(key)?;
+        self.size += 1;
+        Ok(())
+    }
+
+    /// If the key is in the set, delete it.  Otherwise a TreeError::ValueNotFound will be returned.
+    pub fn drop_value(&mut self, key: u64) -> Result<(),TreeError<u64>> {
+        self.buckets[bucket_of(key)].drop_value(key)?;
+        self.size -= 1;
+        Ok(())
+    }
+
+    /// Returns true if the key is in the set
+    pub fn contains(&self, key: u64) -> bool {
+        self.buckets[bucket_of(key)].contains(&key)
+    }
+
+    /// Get the number of keys in the set
+    pub fn get_size(&self) -> u32 {
+        self.size
+    }
+
+    /// Returns all the keys in the set as an ordered Vec from least to greatest.
+    pub fn as_vec(&self) -> Vec<u64> {
+        let mut keys = Vec::with_capacity(self.size as usize);
+        keys.extend(OrderedSet::iter(self).copied());
+        keys
+    }
+
+    /// Returns the number of keys in each of the 256 buckets, in order of their top byte: a quick way to see
+    /// whether the keys are spread out enough to benefit.
+    pub fn bucket_sizes(&self) -> Vec<u32> {
+        self.buckets.iter().map(|bucket| bucket.get_size()).collect()
+    }
+
+    /// Verify each bucket's internal consistency, that each holds only keys with its top byte, and that the
+    /// size matches the number of keys.  Returns a TreeError::InvariantViolation describing the first
+    /// problem found, if any.
+    pub fn check_invariants(&self) -> Result<(),TreeError<u64>> {
+        let mut keys: u32 = 0;
+        for (index, bucket) in self.buckets.iter().enumerate() {
+            bucket.check_invariants()?;
+            if let Some(key) = bucket.iter().find(|key| bucket_of(**key) != index) {
+                return Err(TreeError::InvariantViolation(format!("key {key:#x} is in bucket {index}")));
+            }
+            keys += bucket.get_size();
+        }
+        if keys != self.size {
+            return Err(TreeError::InvariantViolation(format!("size is {} but the buckets hold {} keys", self.size, keys)));
+        }
+        Ok(())
+    }
+
+}
+
+impl OrderedSet<u64> for RadixSet {
+    fn add(&mut self, value: u64) -> Result<(),TreeError<u64>> { RadixSet::add(self, value) }
+    fn remove(&mut self, value: u64) -> Result<(),TreeError<u64>> { self.drop_value(value) }
+    fn contains(&self, value: &u64) -> bool { RadixSet::contains(self, *value) }
+    fn len(&self) -> usize { self.size as usize }
+    fn iter<'a>(&'a self) -> impl Iterator<Item = &'a u64> where u64: 'a {
+        self.buckets.iter().flat_map(|bucket| bucket.iter())
+    }
+    fn range<'a, R: RangeBounds<u64>>(&'a self, range: R) -> impl Iterator<Item = &'a u64> where u64: 'a {
+        let bounds = (range.start_bound().cloned(), range.end_bound().cloned());
+        let first = match bounds.0 {
+            Bound::Unbounded => 0,
+            Bound::Included(key) | Bound::Excluded(key) => bucket_of(key),
+        };
+        let last = match bounds.1 {
+            Bound::Unbounded => BUCKETS - 1,
+            Bound::Included(key) | Bound::Excluded(key) => bucket_of(key),
+        };
+        self.buckets.get(first..=last).unwrap_or_default().iter()
+            .flat_map(move |bucket| OrderedSet::range(bucket, bounds))
+    }
+    fn least(&self) -> Option<&u64> { OrderedSet::iter(self).next() }
+    fn greatest(&self) -> Option<&u64> {
+        self.buckets.iter().rev().find_map(|bucket| OrderedSet::greatest(bucket))
+    }
+}
+
+impl Default for RadixSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for RadixSet {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_set().entries(OrderedSet::iter(self)).finish()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    #[test]
+    fn keys_spread_over_the_buckets_stay_in_order() {
+        // a multiplicative hash spreads the keys out over the whole range
+        let keys: Vec<u64> = (0..2000u64).map(|n| n.wrapping_mul(0x9e37_79b9_7f4a_7c15)).collect();
+        let mut set = RadixSet::from_collection(keys.iter().copied());
+        let mut expected: BTreeSet<u64> = keys.iter().copied().collect();
+        assert!( set.bucket_sizes().iter().all(|size| *size > 0) );
+        for key in keys.iter().step_by(3) {
+            assert_eq!( Ok(()), set.drop_value(*key) );
+            expected.remove(key);
+        }
+        assert_eq!( Err(TreeError::ValueNotFound { value: keys[0] }), set.drop_value(keys[0]) );
+        assert_eq!( Err(TreeError::ValueAlreadyStored { value: keys[1] }), set.add(keys[1]) );
+        assert!( set.check_invariants().is_ok() );
+        assert_eq!( expected.len() as u32, set.get_size() );
+        assert_eq!( expected.iter().copied().collect::<Vec<_>>(), set.as_vec() );
+
+        let (lower, upper) = (0x3000_0000_0000_0000, 0xa0ff_0000_0000_0000);
+        assert!( expected.range(lower..=upper).eq(OrderedSet::range(&set, lower..=upper)) );
+        assert!( expected.range(..upper).eq(OrderedSet::range(&set, ..upper)) );
+        assert_eq!( 0, OrderedSet::range(&set, (Bound::Excluded(upper), Bound::Excluded(lower))).count() );
+        assert_eq!( expected.first(), set.least() );
+        assert_eq!( expected.last(), set.greatest() );
+    }
+
+    #[test]
+    fn clustered_keys_share_a_bucket() {
+        let mut set = RadixSet::from_collection([5, 3, 9, 1, 3]);
+        assert_eq!( 4, set.get_size() );
+        assert_eq!( 4, set.bucket_sizes()[0] );
+        assert_eq!( 4, set.bucket_sizes().iter().sum::<u32>() );
+        assert_eq!( vec!(1, 3, 5, 9), OrderedSet::range(&set, (Bound::Excluded(0), Bound::Excluded(10))).copied().collect::<Vec<_>>() );
+        assert_eq!( vec!(3, 5), OrderedSet::range(&set, 2..=5).copied().collect::<Vec<_>>() );
+        assert_eq!( "{1, 3, 5, 9}", format!("{set:?}") );
+        for key in [1, 3, 5, 9] {
+            set.drop_value(key).unwrap();
+        }
+        assert_eq!( (None, None), (set.least(), set.greatest()) );
+        assert!( set.as_vec().is_empty() );
+    }
+
+    #[test]
+    fn misplaced_keys_are_found() {
+        let mut set = RadixSet::from_collection([1, 2]);
+        assert!( set.buckets[7].add(3).is_ok() );
+        let expected = TreeError::InvariantViolation(String::from("key 0x3 is in bucket 7"));
+        assert_eq!( Err(expected), set.check_invariants() );
+        set.buckets[7] = Javlt::new();
+        set.size = 3;
+        let expected = TreeError::InvariantViolation(String::from("size is 3 but the buckets hold 2 keys"));
+        assert_eq!( Err(expected), set.check_invariants() );
+    }
+
+}
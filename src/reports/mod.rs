@@ -0,0 +1,152 @@
+/// The outcome of adding a batch of values to a tree.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct AddReport {
+    /// Number of values that were stored in the tree.
+    pub inserted: usize,
+    /// Number of values that were skipped because they were already in the tree.
+    pub skipped: usize,
+}
+
+/// Shape and balance metrics for a tree, as returned by `stats()`.  Depths count edges from the root
+/// (so the root is at depth 0), while height counts levels (so a lone root has height 1).
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct TreeStats {
+    /// Number of nodes in the tree (for a `Jblst`, duplicates share a node; for a lazily-deleting `Jbst`, tombstones are included).
+    pub node_count: usize,
+    /// Number of levels in the tree, or 0 if it's empty.
+    pub height: usize,
+    /// Number of nodes with no children.
+    pub leaf_count: usize,
+    /// Number of nodes with at least one child.
+    pub internal_count: usize,
+    /// Depth of the shallowest leaf.
+    pub min_leaf_depth: usize,
+    /// Depth of the deepest leaf.
+    pub max_leaf_depth: usize,
+    /// Mean depth of the leaves.
+    pub avg_leaf_depth: f64,
+    /// Rotations performed to rebalance the tree since it was created; only self-balancing trees have these.
+    pub rotations: Option<u64>,
+}
+
+impl TreeStats {
+
+    /// Tally one node found at the given depth while walking a tree.
+    pub(crate) fn record_node(&mut self, depth: usize, is_leaf: bool) {
+        self.node_count += 1;
+        self.height = self.height.max(depth + 1);
+        if !is_leaf {
+            self.internal_count += 1;
+            return;
+        }
+        if self.leaf_count == 0 || depth < self.min_leaf_depth {
+            self.min_leaf_depth = depth;
+        }
+        self.max_leaf_depth = self.max_leaf_depth.max(depth);
+        self.leaf_count += 1;
+        self.avg_leaf_depth += (depth as f64 - self.avg_leaf_depth) / self.leaf_count as f64;
+    }
+
+}
+
+/// The differences between two trees' contents, as returned by `diff()`.  Both lists are in order.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct TreeDiff<T> {
+    /// Values in the tree `diff()` was called on, but not in the other tree.
+    pub only_in_self: Vec<T>,
+    /// Values in the other tree, but not in the tree `diff()` was called on.
+    pub only_in_other: Vec<T>,
+}
+
+impl <T> TreeDiff<T> {
+
+    /// Returns true if the two trees held the same values.
+    pub fn is_empty(&self) -> bool {
+        self.only_in_self.is_empty() && self.only_in_other.is_empty()
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd + Clone> TreeDiff<T> {
+
+    /// Compare two ordered sequences of values in a single pass, like a merge.
+    pub(crate) fn between<'a, A, B>(mut ours: A, mut theirs: B) -> Self
+    where
+        T: 'a,
+        A: Iterator<Item = &'a T>,
+        B: Iterator<Item = &'a T>,
+    {
+        let mut diff = TreeDiff { only_in_self: Vec::new(), only_in_other: Vec::new() };
+        let mut our_next = ours.next();
+        let mut their_next = theirs.next();
+        loop {
+            match (our_next, their_next) {
+                (None, None) => return diff,
+                (Some(ours_value), None) => {
+                    diff.only_in_self.push(ours_value.clone());
+                    our_next = ours.next();
+                },
+                (None, Some(theirs_value)) => {
+                    diff.only_in_other.push(theirs_value.clone());
+                    their_next = theirs.next();
+                },
+                (Some(ours_value), Some(theirs_value)) => {
+                    if ours_value == theirs_value {
+                        our_next = ours.next();
+                        their_next = theirs.next();
+                    } else if ours_value < theirs_value {
+                        diff.only_in_self.push(ours_value.clone());
+                        our_next = ours.next();
+                    } else {
+                        diff.only_in_other.push(theirs_value.clone());
+                        their_next = theirs.next();
+                    }
+                },
+            }
+        }
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_tally_the_nodes_recorded() {
+        let mut stats = TreeStats::default();
+        assert_eq!( (0, 0), (stats.node_count, stats.height) );
+        // the shape of 4 under 2, with 1 and 3 under it: ((1) 2 (3)) 4
+        for (depth, is_leaf) in [(0, false), (1, false), (2, true), (2, true)] {
+            stats.record_node(depth, is_leaf);
+        }
+        assert_eq!( 4, stats.node_count );
+        assert_eq!( 3, stats.height );
+        assert_eq!( (2, 2), (stats.leaf_count, stats.internal_count) );
+        assert_eq!( (2, 2), (stats.min_leaf_depth, stats.max_leaf_depth) );
+        assert_eq!( 2.0, stats.avg_leaf_depth );
+        // leaves at any depth, in any order
+        stats.record_node(5, true);
+        stats.record_node(1, true);
+        assert_eq!( (1, 5), (stats.min_leaf_depth, stats.max_leaf_depth) );
+        assert_eq!( 6, stats.height );
+        assert_eq!( 2.5, stats.avg_leaf_depth );
+        assert_eq!( None, stats.rotations );
+    }
+
+    #[test]
+    fn diffs_find_what_each_side_lacks() {
+        let ours = [1, 3, 4, 8, 9];
+        let theirs = [2, 3, 8, 10, 11];
+        let diff = TreeDiff::between(ours.iter(), theirs.iter());
+        assert_eq!( vec!(1, 4, 9), diff.only_in_self );
+        assert_eq!( vec!(2, 10, 11), diff.only_in_other );
+        assert!( !diff.is_empty() );
+        assert!( TreeDiff::between(ours.iter(), ours.iter()).is_empty() );
+        let none: [i32; 0] = [];
+        let diff = TreeDiff::between(none.iter(), theirs.iter());
+        assert_eq!( (Vec::new(), theirs.to_vec()), (diff.only_in_self, diff.only_in_other) );
+    }
+
+}
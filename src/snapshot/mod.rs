@@ -0,0 +1,150 @@
+use std::{fmt, sync::{Arc, Mutex, PoisonError}};
+
+use arc_swap::ArcSwap;
+
+use crate::errors::TreeError;
+use crate::persistent::PersistentJavlt;
+
+
+
+/// # Snapshot AVL Tree
+///
+/// An ordered set for read-mostly sharing between threads.  Readers take a `snapshot()`, an `Arc` of a
+/// `PersistentJavlt` that never changes, and read it for as long as they like without any locking, while
+/// a writer goes on adding and deleting values.  Each change copies the O(log(n)) nodes on its path and
+/// publishes a new version; a snapshot taken before it keeps the values it had (snapshot isolation).
+///
+///     use jtree::SnapshotJavlt;
+///
+///     let shared = SnapshotJavlt::from_collection([1,2,3]);
+///     let before = shared.snapshot();
+///     shared.insert(4).unwrap();
+///     shared.remove(1).unwrap();
+///     assert_eq!( vec!(1,2,3), before.as_vec() );
+///     assert_eq!( vec!(2,3,4), shared.snapshot().as_vec() );
+///
+/// Writers take turns, one at a time.  Readers take no lock at all: the latest version is swapped in
+/// atomically (through `arc-swap`), so taking a snapshot never waits for a writer, nor a writer for readers.
+pub struct SnapshotJavlt<T: PartialEq + PartialOrd + Clone> {
+    latest: ArcSwap<PersistentJavlt<T>>, // the version readers see
+    writer: Mutex<PersistentJavlt<T>>, // the writer's working copy, which shares its nodes with `latest`
+}
+
+impl <T: PartialEq + PartialOrd + Clone> SnapshotJavlt<T> {
+
+    /// Create a new set with no data
+    pub fn new() -> Self {
+        Self::from(PersistentJavlt::new())
+    }
+
+    /// Create a new set from a collection (vector, array, or whatever), skipping duplicates.
+    pub fn from_collection<U: IntoIterator<Item = T>>(collection: U) -> Self {
+        Self::from(PersistentJavlt::from_collection(collection))
+    }
+
+    /// Get a consistent, unchanging view of the set as it is now.
+    pub fn snapshot(&self) -> Arc<PersistentJavlt<T>> {
+        self.latest.load_full()
+    }
+
+    /// Insert a value, returning a TreeError::ValueAlreadyStored if it's already in the set.
+    pub fn insert(&self, value: T) -> Result<(),TreeError<T>> {
+        self.write(|tree| tree.add(value))
+    }
+
+    /// If the value is in the set, delete it.  Otherwise a TreeError::ValueNotFound will be returned.
+    pub fn remove(&self, value: T) -> Result<(),TreeError<T>> {
+        self.write(|tree| tree.drop_value(value))
+    }
+
+    /// Returns true if the value is in the latest version of the set
+    pub fn contains(&self, value: &T) -> bool {
+        self.latest.load().contains(value)
+    }
+
+    /// Get the number of values in the latest version of the set
+    pub fn get_size(&self) -> u32 {
+        self.latest.load().get_size()
+    }
+
+    /// Make a change to the writer's copy and, if it worked, publish the result as the latest version.
+    fn write<R, F: FnOnce(&mut PersistentJavlt<T>) -> Result<R,TreeError<T>>>(&self, change: F) -> Result<R,TreeError<T>> {
+        let mut tree = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let result = change(&mut tree)?;
+        self.latest.store(Arc::new(tree.clone()));
+        Ok(result)
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd + Clone> From<PersistentJavlt<T>> for SnapshotJavlt<T> {
+    fn from(tree: PersistentJavlt<T>) -> Self {
+        Self {
+            latest: ArcSwap::from_pointee(tree.clone()),
+            writer: Mutex::new(tree),
+        }
+    }
+}
+
+impl <T: PartialEq + PartialOrd + Clone> Default for SnapshotJavlt<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <T: PartialEq + PartialOrd + Clone + std::fmt::Debug> fmt::Debug for SnapshotJavlt<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let latest = self.snapshot();
+        fmt.debug_struct("SnapshotJavlt")
+            .field("size", &latest.get_size())
+            .field("values", &latest.as_vec())
+            .finish()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn readers_see_consistent_snapshots() {
+        // the writer keeps the set as a run of consecutive values, moving it up one value at a time;
+        // every snapshot the readers take must be such a run, however the threads interleave
+        let shared = SnapshotJavlt::from_collection(0..100);
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                for n in 0..1000 {
+                    shared.insert(n + 100).unwrap();
+                    shared.remove(n).unwrap();
+                }
+            });
+            for _ in 0..3 {
+                scope.spawn(|| {
+                    for _ in 0..200 {
+                        let snapshot = shared.snapshot();
+                        let values = snapshot.as_vec();
+                        assert!( values.len() == 100 || values.len() == 101 );
+                        assert_eq!( values.len() as u32, snapshot.get_size() );
+                        assert!( values.windows(2).all(|pair| pair[1] == pair[0] + 1) );
+                        assert_eq!( values, snapshot.as_vec() );
+                    }
+                });
+            }
+        });
+        assert_eq!( (1000..1100).collect::<Vec<_>>(), shared.snapshot().as_vec() );
+        assert_eq!( Ok(()), shared.snapshot().check_invariants() );
+    }
+
+    #[test]
+    fn failed_changes_publish_nothing() {
+        let shared = SnapshotJavlt::from_collection([1,2]);
+        let before = shared.snapshot();
+        assert_eq!( Err(TreeError::ValueAlreadyStored { value: 1 }), shared.insert(1) );
+        assert_eq!( Err(TreeError::ValueNotFound { value: 3 }), shared.remove(3) );
+        assert!( Arc::ptr_eq(&before, &shared.snapshot()) );
+    }
+
+}
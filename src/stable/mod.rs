@@ -0,0 +1,538 @@
+use std::{collections::VecDeque, fmt};
+
+use crate::errors::TreeError;
+use crate::jbst::DEFAULT_DEPTH_LIMIT;
+use crate::reports::AddReport;
+
+
+
+/// # Stable Binary (List-like) Search Tree
+///
+/// A variant of `Jblst` that keeps every value it's given.  A `Jblst` stores one copy of a value and counts
+/// how many times it was added, which is fine for numbers but loses records that compare equal without being
+/// identical (say, log entries ordered by timestamp).  A `StableJblst` keeps each of them as a distinct entry,
+/// and keeps equal values in the order they were added, so it serves as a stable ordered list of records.
+///
+///     use jtree::stable::StableJblst;
+///
+///     #[derive(Debug)]
+///     struct Entry { time: u32, message: &'static str }
+///     impl PartialEq for Entry {
+///         fn eq(&self, other: &Self) -> bool { self.time == other.time }
+///     }
+///     impl PartialOrd for Entry {
+///         fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> { self.time.partial_cmp(&other.time) }
+///     }
+///
+///     let mut log = StableJblst::new();
+///     log.add(Entry { time: 2, message: "second" }).unwrap();
+///     log.add(Entry { time: 1, message: "first" }).unwrap();
+///     log.add(Entry { time: 2, message: "also second" }).unwrap();
+///     let messages: Vec<_> = log.iter().map(|entry| entry.message).collect();
+///     assert_eq!( vec!("first", "second", "also second"), messages );
+///
+/// Like `Jblst`, it's an unbalanced binary search tree; equal values share a node, which holds them in a
+/// queue.  `drop_value` removes the earliest-added of the equal values, first in, first out.  It can be given
+/// a depth limit, as a `Jblst` can, so that values which would go deeper are refused with an error.
+pub struct StableJblst<T: PartialEq + PartialOrd> {
+    root: Link<T>,
+    size: u32,
+    depth_limit: usize, // the most nodes allowed on a path down from the root
+}
+
+type Link<T> = Option<Box<Node<T>>>;
+
+impl <T: PartialEq + PartialOrd> StableJblst<T> {
+
+    /// Create a new tree with no data
+    pub fn new() -> Self {
+        Self {
+            root: None,
+            size: 0,
+            depth_limit: DEFAULT_DEPTH_LIMIT,
+        }
+    }
+
+    /// Create a new tree with no data, whose values may go no deeper than `depth_limit` nodes (at least 1)
+    /// down from the root.  A value that would go deeper isn't added, and a TreeError::DepthLimitExceeded is
+    /// returned instead.
+    pub fn with_depth_limit(depth_limit: usize) -> Self {
+        let mut new_tree = Self::new();
+        new_tree.set_depth_limit(depth_limit);
+        new_tree
+    }
+
+    /// Create a new tree from a collection (vector, array, or whatever), keeping equal values in the
+    /// collection's order.  Panics if the values would make the tree deeper than `DEFAULT_DEPTH_LIMIT`; such
+    /// a collection can be added to a tree made with `with_depth_limit`.
+    pub fn from_collection<U: IntoIterator<Item = T>>(collection: U) -> Self {
+        let mut new_tree = Self::new();
+        if new_tree.add_all(collection).is_err() {
+            panic!("StableJblst::from_collection: the values would make the tree deeper than its depth limit ({DEFAULT_DEPTH_LIMIT})");
+        }
+        new_tree
+    }
+
+    /// Insert a value, after any equal values already in the tree.  Returns a TreeError::DepthLimitExceeded if
+    /// the value would go deeper than the tree's depth limit.
+    pub fn add(&mut self, value: T) -> Result<(),TreeError<T>> {
+        if self.size == u32::MAX {
+            return Err(TreeError::CapacityExceeded { value });
+        }
+        Node::add(&mut self.root, value, 1, self.depth_limit)?;
+        self.size += 1;
+        #[cfg(feature = "paranoid")]
+        self.paranoid_check("add");
+        Ok(())
+    }
+
+    /// Adds all members of a collection (vector, array, or whatever) to the tree, in order.
+    /// Since duplicates are allowed, the returned report never counts any values as skipped.
+    pub fn add_all<U: IntoIterator<Item = T>>(&mut self, collection: U) -> Result<AddReport,TreeError<T>> {
+        let mut report = AddReport::default();
+        for elem in collection.into_iter() {
+            self.add(elem)?;
+            report.inserted += 1;
+        }
+        Ok(report)
+    }
+
+    /// Get the number of values in the tree
+    pub fn get_size(&self) -> u32 {
+        self.size
+    }
+
+    /// Get the most nodes a path down from the root may go through: values that would go deeper aren't added.
+    pub fn get_depth_limit(&self) -> usize {
+        self.depth_limit
+    }
+
+    /// Change the tree's depth limit (to at least 1).  Values already deeper than a lowered limit stay where
+    /// they are; only values added from now on are held to it.
+    pub fn set_depth_limit(&mut self, depth_limit: usize) {
+        self.depth_limit = depth_limit.max(1);
+    }
+
+    /// Returns true if the value is currently a member of the tree
+    pub fn contains(&self, value: &T) -> bool {
+        Node::find(&self.root, value).is_some()
+    }
+
+    /// Delete the earliest-added value equal to `value`.  If there's none, a TreeError::ValueNotFound will be returned.
+    pub fn drop_value(&mut self, value: T) -> Result<(),TreeError<T>> {
+        return match self.take_first(&value) {
+            Some(_) => Ok(()),
+            None => Err(TreeError::ValueNotFound { value }),
+        };
+    }
+
+    /// Remove the earliest-added value equal to `value` and return it, or None if there's none.
+    pub fn take_first(&mut self, value: &T) -> Option<T> {
+        let removed = Node::remove(&mut self.root, value, |_| true)?;
+        self.size -= 1;
+        #[cfg(feature = "paranoid")]
+        self.paranoid_check("take_first");
+        Some(removed)
+    }
+
+    /// Returns the values equal to `key`, in the order they were added.  With values compared by a key, as
+    /// below, these are all of the distinct items stored under it.
+    ///
+    ///     use jtree::stable::StableJblst;
+    ///
+    ///     #[derive(Debug)]
+    ///     struct Order { customer: u32, item: &'static str }
+    ///     impl PartialEq for Order {
+    ///         fn eq(&self, other: &Self) -> bool { self.customer == other.customer }
+    ///     }
+    ///     impl PartialOrd for Order {
+    ///         fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> { self.customer.partial_cmp(&other.customer) }
+    ///     }
+    ///
+    ///     let mut orders = StableJblst::from_collection([
+    ///         Order { customer: 7, item: "tea" }, Order { customer: 3, item: "jam" }, Order { customer: 7, item: "cake" },
+    ///     ]);
+    ///     let key = Order { customer: 7, item: "" };
+    ///     let items: Vec<_> = orders.items_equal_to(&key).map(|order| order.item).collect();
+    ///     assert_eq!( vec!("tea", "cake"), items );
+    ///     let cake = orders.remove_item(&key, |order| order.item == "cake").unwrap();
+    ///     assert_eq!( "cake", cake.item );
+    ///     assert_eq!( 1, orders.items_equal_to(&key).count() );
+    pub fn items_equal_to(&self, key: &T) -> impl Iterator<Item = &T> {
+        Node::find(&self.root, key).into_iter().flat_map(|node| node.items.iter())
+    }
+
+    /// Remove the earliest-added value equal to `key` that `is_item` picks out, and return it; or None if
+    /// there's no such value.  Since the values equal to `key` can't be told apart by comparing them, `is_item`
+    /// is how to say which one is meant, e.g. by comparing the fields that the ordering leaves out.
+    pub fn remove_item<F: FnMut(&T) -> bool>(&mut self, key: &T, is_item: F) -> Option<T> {
+        let removed = Node::remove(&mut self.root, key, is_item)?;
+        self.size -= 1;
+        #[cfg(feature = "paranoid")]
+        self.paranoid_check("remove_item");
+        Some(removed)
+    }
+
+    /// Remove the least value (the earliest-added, if it has equals) and return it, or None if the tree is empty.
+    pub fn pop_first(&mut self) -> Option<T> {
+        let removed = Node::pop_least(&mut self.root)?;
+        self.size -= 1;
+        #[cfg(feature = "paranoid")]
+        self.paranoid_check("pop_first");
+        Some(removed)
+    }
+
+    /// The values in order from least to greatest, with equal values in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        Nodes::new(&self.root).flat_map(|node| node.items.iter())
+    }
+
+    /// Returns references to all the values in the tree as an ordered Vec from least to greatest, with
+    /// equal values in the order they were added.
+    pub fn values(&self) -> Vec<&T> {
+        let mut vals = Vec::with_capacity(self.size as usize);
+        vals.extend(self.iter());
+        vals
+    }
+
+    /// Consume the tree, moving its values into a Vec from least to greatest, with equal values in the order
+    /// they were added.
+    pub fn into_sorted_vec(self) -> Vec<T> {
+        let mut vals = Vec::with_capacity(self.size as usize);
+        let mut stack = Vec::new();
+        let mut link = self.root;
+        loop {
+            while let Some(mut node) = link {
+                link = node.left.take();
+                stack.push(node);
+            }
+            match stack.pop() {
+                None => return vals,
+                Some(mut node) => {
+                    link = node.right.take();
+                    vals.extend(node.items);
+                },
+            }
+        }
+    }
+
+    /// Verify the tree's internal consistency: that values are in order, each node holds at least one value
+    /// and only values equal to each other, and the size matches the number of values.  Returns a
+    /// TreeError::InvariantViolation describing the first problem found, if any.
+    pub fn check_invariants(&self) -> Result<(),TreeError<T>> {
+        let total = match &self.root {
+            None => 0,
+            Some(branch) => branch.check_invariants(&mut String::from("root"), None, None)?,
+        };
+        if total != self.size as usize {
+            return Err(TreeError::InvariantViolation(format!("size is {} but the tree holds {} values", self.size, total)));
+        }
+        Ok(())
+    }
+
+    /// With the `paranoid` feature, every change to the tree is followed by this check, which panics
+    /// with a description of the problem if the change broke an invariant.
+    #[cfg(feature = "paranoid")]
+    fn paranoid_check(&self, operation: &str) {
+        if let Err(TreeError::InvariantViolation(problem)) = self.check_invariants() {
+            panic!("StableJblst::{operation} broke the tree: {problem} (size {})", self.size);
+        }
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd + Clone> StableJblst<T> {
+
+    /// Returns all the values in the tree as an ordered Vec from least to greatest, with equal values in the
+    /// order they were added.
+    pub fn as_vec(&self) -> Vec<T> {
+        self.iter().cloned().collect()
+    }
+
+}
+
+impl <T: PartialEq + PartialOrd> Default for StableJblst<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <T: PartialEq + PartialOrd + fmt::Debug> fmt::Debug for StableJblst<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("StableJblst")
+            .field("size", &self.get_size())
+            .field("values", &self.values())
+            .finish()
+    }
+}
+
+
+struct Node<T: PartialEq + PartialOrd> {
+    items: VecDeque<T>, // equal values, in the order they were added; never empty
+    left: Link<T>,
+    right: Link<T>,
+}
+
+// Like `AggregateJavlt`'s nodes, the changing functions take the link that owns a node, so that a node can be
+// spliced out of its place.
+impl <T: PartialEq + PartialOrd> Node<T> {
+
+    fn new(value: T) -> Self {
+        Self {
+            items: VecDeque::from([value]),
+            left: None,
+            right: None,
+        }
+    }
+
+    /// The value all of this node's items are equal to.
+    fn key(&self) -> &T {
+        &self.items[0]
+    }
+
+    /// Insert a value after any equal ones, where `depth` is the depth of the (sub)tree at `link`.  A value
+    /// that needs a new node deeper than `depth_limit` is handed back in a TreeError::DepthLimitExceeded.
+    fn add(link: &mut Link<T>, value: T, depth: usize, depth_limit: usize) -> Result<(),TreeError<T>> {
+        let node = match link {
+            None if depth > depth_limit => return Err(TreeError::DepthLimitExceeded { value, limit: depth_limit }),
+            None => {
+                *link = Some(Box::new(Node::new(value)));
+                return Ok(());
+            },
+            Some(node) => node,
+        };
+        if value == *node.key() {
+            node.items.push_back(value);
+            Ok(())
+        } else if value < *node.key() {
+            Self::add(&mut node.left, value, depth + 1, depth_limit)
+        } else {
+            Self::add(&mut node.right, value, depth + 1, depth_limit)
+        }
+    }
+
+    /// Returns the node holding values equal to `value`, if there is one.
+    fn find<'a>(mut link: &'a Link<T>, value: &T) -> Option<&'a Node<T>> {
+        while let Some(node) = link {
+            if *value == *node.key() {
+                return Some(node);
+            }
+            link = if *value < *node.key() { &node.left } else { &node.right };
+        }
+        None
+    }
+
+    /// Remove the earliest-added of the values equal to `value` for which `is_item` is true, splicing out its
+    /// node if that was its last value.
+    fn remove<F: FnMut(&T) -> bool>(link: &mut Link<T>, value: &T, is_item: F) -> Option<T> {
+        let node = link.as_mut()?;
+        if *value == *node.key() {
+            let index = node.items.iter().position(is_item)?;
+            let removed = node.items.remove(index);
+            if node.items.is_empty() {
+                Self::unlink(link);
+            }
+            return removed;
+        }
+        if *value < *node.key() {
+            return Self::remove(&mut node.left, value, is_item);
+        }
+        Self::remove(&mut node.right, value, is_item)
+    }
+
+    /// Remove the earliest-added of the least values in the (sub)tree.
+    fn pop_least(link: &mut Link<T>) -> Option<T> {
+        let node = link.as_mut()?;
+        if node.left.is_some() {
+            return Self::pop_least(&mut node.left);
+        }
+        let removed = node.items.pop_front();
+        if node.items.is_empty() {
+            Self::unlink(link);
+        }
+        removed
+    }
+
+    /// Splice the (emptied) node out of its place.
+    fn unlink(link: &mut Link<T>) {
+        let mut node = link.take().unwrap();
+        *link = match (node.left.take(), node.right.take()) {
+            (None, right) => right,
+            (left, None) => left,
+            (left, Some(right)) => {
+                // both children are branches, so move the node holding the immediate successors into its place
+                let (new_right, mut successor) = Self::take_least(right);
+                successor.left = left;
+                successor.right = new_right;
+                Some(successor)
+            },
+        };
+    }
+
+    /// Detach the node with the least values from a (sub)tree, returning the node that replaces the subtree's
+    /// root along with the detached node.
+    fn take_least(mut root: Box<Node<T>>) -> (Link<T>, Box<Node<T>>) {
+        return match root.left.take() {
+            None => {
+                let right = root.right.take();
+                (right, root)
+            },
+            Some(left_child) => {
+                let (new_left, least) = Self::take_least(left_child);
+                root.left = new_left;
+                (Some(root), least)
+            },
+        };
+    }
+
+    /// Recursively verify that this (sub)tree's values are in order and strictly between the bounds set by its
+    /// ancestors, and that each node's values are equal.  Returns the number of values.
+    fn check_invariants(&self, path: &mut String, lower: Option<&T>, upper: Option<&T>) -> Result<usize,TreeError<T>> {
+        if self.items.is_empty() {
+            return Err(TreeError::InvariantViolation(format!("node at {path} holds no values")));
+        }
+        if self.items.iter().any(|item| *item != *self.key()) {
+            return Err(TreeError::InvariantViolation(format!("node at {path} holds values that aren't equal")));
+        }
+        if lower.is_some_and(|lower| *self.key() <= *lower) || upper.is_some_and(|upper| *self.key() >= *upper) {
+            return Err(TreeError::InvariantViolation(format!("value at {path} is out of order")));
+        }
+        let mut total = self.items.len();
+        let path_len = path.len();
+        if let Some(node) = &self.left {
+            path.push_str(".left");
+            total += node.check_invariants(path, lower, Some(self.key()))?;
+            path.truncate(path_len);
+        }
+        if let Some(node) = &self.right {
+            path.push_str(".right");
+            total += node.check_invariants(path, Some(self.key()), upper)?;
+            path.truncate(path_len);
+        }
+        Ok(total)
+    }
+
+}
+
+/// Visits the nodes of a tree in order, from least to greatest value, keeping a stack of the nodes still to come.
+struct Nodes<'a, T: PartialEq + PartialOrd> {
+    stack: Vec<&'a Node<T>>,
+}
+
+impl <'a, T: PartialEq + PartialOrd> Nodes<'a, T> {
+
+    fn new(root: &'a Link<T>) -> Self {
+        let mut nodes = Self { stack: Vec::new() };
+        nodes.push_left_edge(root);
+        nodes
+    }
+
+    /// Stack the node and its chain of left children, so the least of them is on top.
+    fn push_left_edge(&mut self, mut link: &'a Link<T>) {
+        while let Some(node) = link {
+            self.stack.push(node);
+            link = &node.left;
+        }
+    }
+
+}
+
+impl <'a, T: PartialEq + PartialOrd> Iterator for Nodes<'a, T> {
+    type Item = &'a Node<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.push_left_edge(&node.right);
+        Some(node)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A record ordered by its key alone, with a tag telling equal records apart
+    #[derive(Debug, Clone)]
+    struct Record { key: u32, tag: u32 }
+
+    impl PartialEq for Record {
+        fn eq(&self, other: &Self) -> bool {
+            self.key == other.key
+        }
+    }
+
+    impl PartialOrd for Record {
+        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+            self.key.partial_cmp(&other.key)
+        }
+    }
+
+    fn tags(records: Vec<Record>) -> Vec<(u32, u32)> {
+        records.into_iter().map(|record| (record.key, record.tag)).collect()
+    }
+
+    #[test]
+    fn equal_values_keep_their_order() {
+        // the same as a stable sort of the records by key
+        let records: Vec<Record> = (0..60).map(|tag| Record { key: (tag * 7) % 10, tag }).collect();
+        let mut tree = StableJblst::from_collection(records.clone());
+        assert_eq!( Ok(()), tree.check_invariants() );
+        let mut sorted = records.clone();
+        sorted.sort_by_key(|record| record.key);
+        assert_eq!( tags(sorted.clone()), tags(tree.as_vec()) );
+        // dropping takes the earliest of the equal values
+        assert_eq!( Ok(()), tree.drop_value(Record { key: 3, tag: 999 }) );
+        assert_eq!( Some(19), tree.take_first(&Record { key: 3, tag: 0 }).map(|record| record.tag) );
+        assert_eq!( Some(0), tree.pop_first().map(|record| record.tag) );
+        assert_eq!( Err(TreeError::ValueNotFound { value: Record { key: 10, tag: 0 } }), tree.drop_value(Record { key: 10, tag: 0 }) );
+        assert_eq!( 57, tree.get_size() );
+        sorted.retain(|record| ![9, 19, 0].contains(&record.tag));
+        assert_eq!( tags(sorted), tags(tree.into_sorted_vec()) );
+    }
+
+    #[test]
+    fn emptied_nodes_are_spliced_out() {
+        let mut tree = StableJblst::from_collection([50, 20, 80, 10, 30, 70, 90, 60, 75, 50, 80]);
+        for value in [50, 50, 20, 80, 10] {
+            assert_eq!( Ok(()), tree.drop_value(value) );
+            assert_eq!( Ok(()), tree.check_invariants() );
+        }
+        assert!( tree.contains(&80) );
+        assert!( !tree.contains(&50) );
+        assert_eq!( vec!(&30,&60,&70,&75,&80,&90), tree.values() );
+        while tree.pop_first().is_some() {
+            assert_eq!( Ok(()), tree.check_invariants() );
+        }
+        assert_eq!( 0, tree.get_size() );
+    }
+
+    #[test]
+    fn items_under_a_key_stay_retrievable() {
+        let records: Vec<Record> = (0..40).map(|tag| Record { key: tag % 4, tag }).collect();
+        let mut tree = StableJblst::from_collection(records);
+        let key = Record { key: 2, tag: 0 };
+        let expected: Vec<u32> = (0..40).filter(|tag| tag % 4 == 2).collect();
+        assert_eq!( expected, tree.items_equal_to(&key).map(|record| record.tag).collect::<Vec<_>>() );
+        assert_eq!( Some(18), tree.remove_item(&key, |record| record.tag == 18).map(|record| record.tag) );
+        assert_eq!( None, tree.remove_item(&key, |record| record.tag == 18).map(|record| record.tag) );
+        assert_eq!( None, tree.remove_item(&Record { key: 9, tag: 0 }, |_| true).map(|record| record.tag) );
+        // removing every item under a key takes its node out of the tree
+        while tree.remove_item(&key, |record| record.tag > 0).is_some() {}
+        assert_eq!( 0, tree.items_equal_to(&key).count() );
+        assert!( !tree.contains(&key) );
+        assert_eq!( 30, tree.get_size() );
+        assert_eq!( Ok(()), tree.check_invariants() );
+    }
+
+    #[test]
+    fn depth_limit_stops_deep_values() {
+        let mut tree = StableJblst::with_depth_limit(2);
+        let _ = tree.add_all([1, 2, 2]);
+        assert_eq!( Err(TreeError::DepthLimitExceeded { value: 3, limit: 2 }), tree.add(3) );
+        assert_eq!( Ok(()), tree.add(2) ); // equal values share a node, so they go no deeper
+        assert_eq!( vec!(1, 2, 2, 2), tree.as_vec() );
+        assert_eq!( DEFAULT_DEPTH_LIMIT, StableJblst::<u32>::new().get_depth_limit() );
+    }
+
+}
@@ -0,0 +1,140 @@
+//! With the `testing` feature, this module helps tests that compare trees.  `assert_tree_eq!` checks that two
+//! trees hold the same values (the same number of times each), and when they don't, rather than dumping both
+//! trees' `Debug` output, it lists just the values that differ, followed by both trees' shapes as drawn by
+//! `to_ascii`.
+//!
+//!     use jtree::{assert_tree_eq, Javlt, Jbst};
+//!
+//!     let balanced = Javlt::from_collection([1,2,3,4]);
+//!     let unbalanced = Jbst::from_collection([1,2,3,4]);
+//!     assert_tree_eq!( balanced, unbalanced ); // different shapes, same values
+//!
+//! A failure reads like this:
+//!
+//! ```text
+//! trees differ:
+//!   missing from right: [3]
+//!   extra in right: [5]
+//!   counts differ: 2 (left 2, right 1)
+//! left shape:
+//!     /-- 3
+//! 2 x2
+//! \-- 1
+//! right shape:
+//! ...
+//! ```
+
+use std::fmt::Debug;
+
+use crate::javlt::Javlt;
+use crate::jblst::Jblst;
+use crate::jbst::Jbst;
+use crate::ordered::OrderedSet;
+
+/// Asserts that two trees (of any of the kinds that implement `Shape`) hold the same values, the same number
+/// of times each, whatever their shapes.  On failure it panics with a list of the differences and both shapes.
+#[macro_export]
+macro_rules! assert_tree_eq {
+    ($left:expr, $right:expr $(,)?) => {
+        if let Some(report) = $crate::testing::differences(&$left, &$right) {
+            panic!("{}", report);
+        }
+    };
+}
+
+/// A tree that can draw its shape, for `assert_tree_eq!` to show.
+pub trait Shape {
+    /// The tree drawn as text, one value per line.
+    fn shape(&self) -> String;
+}
+
+impl <T: PartialEq + PartialOrd + Clone + Debug> Shape for Jbst<T> {
+    fn shape(&self) -> String { self.to_ascii() }
+}
+
+impl <T: PartialEq + PartialOrd + Clone + Debug> Shape for Jblst<T> {
+    fn shape(&self) -> String { self.to_ascii() }
+}
+
+impl <T: PartialEq + PartialOrd + Debug> Shape for Javlt<T> {
+    fn shape(&self) -> String { self.to_ascii() }
+}
+
+/// Describe how the values of two trees differ: the values only in `left`, those only in `right`, and those
+/// in both but a different number of times, followed by both trees' shapes.  Returns None if they hold the
+/// same values.
+///
+///     use jtree::Jblst;
+///     use jtree::testing::differences;
+///
+///     let report = differences(&Jblst::from_collection([1,2,2]), &Jblst::from_collection([2,3])).unwrap();
+///     assert!( report.contains("missing from right: [1]\n  extra in right: [3]\n  counts differ: 2 (left 2, right 1)\n") );
+///     assert_eq!( None, differences(&Jblst::from_collection([2,1]), &Jblst::from_collection([1,2])) );
+pub fn differences<T, A, B>(left: &A, right: &B) -> Option<String>
+where T: PartialEq + PartialOrd + Debug, A: OrderedSet<T> + Shape, B: OrderedSet<T> + Shape {
+    let (mut missing, mut extra, mut counts): (Vec<&T>, Vec<&T>, Vec<String>) = (Vec::new(), Vec::new(), Vec::new());
+    let (left_counts, right_counts) = (count_runs(left.iter()), count_runs(right.iter()));
+    let (mut left_runs, mut right_runs) = (left_counts.iter().peekable(), right_counts.iter().peekable());
+    loop {
+        match (left_runs.peek(), right_runs.peek()) {
+            (None, None) => break,
+            (Some(_), None) => missing.extend(left_runs.next().map(|(value, _)| value)),
+            (None, Some(_)) => extra.extend(right_runs.next().map(|(value, _)| value)),
+            (Some((a, a_count)), Some((b, b_count))) => {
+                if a < b {
+                    missing.extend(left_runs.next().map(|(value, _)| value));
+                } else if b < a {
+                    extra.extend(right_runs.next().map(|(value, _)| value));
+                } else {
+                    if a_count != b_count {
+                        counts.push(format!("{a:?} (left {a_count}, right {b_count})"));
+                    }
+                    left_runs.next();
+                    right_runs.next();
+                }
+            },
+        }
+    }
+    if missing.is_empty() && extra.is_empty() && counts.is_empty() {
+        return None;
+    }
+    let mut report = String::from("trees differ:\n");
+    if !missing.is_empty() {
+        report.push_str(&format!("  missing from right: {missing:?}\n"));
+    }
+    if !extra.is_empty() {
+        report.push_str(&format!("  extra in right: {extra:?}\n"));
+    }
+    if !counts.is_empty() {
+        report.push_str(&format!("  counts differ: {}\n", counts.join(", ")));
+    }
+    report.push_str(&format!("left shape:\n{}right shape:\n{}", left.shape(), right.shape()));
+    Some(report)
+}
+
+/// Each distinct value of an ordered sequence, with the number of times it appears.
+fn count_runs<'a, T: PartialEq>(values: impl Iterator<Item = &'a T>) -> Vec<(&'a T, usize)> {
+    let mut runs: Vec<(&T, usize)> = Vec::new();
+    for value in values {
+        match runs.last_mut() {
+            Some((last, count)) if *last == value => *count += 1,
+            _ => runs.push((value, 1)),
+        }
+    }
+    runs
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[should_panic(expected = "trees differ:\n  extra in right: [\"d\"]\nleft shape:\n")]
+    fn mismatches_are_described() {
+        assert_tree_eq!( Javlt::from_collection(["a", "b", "c"]), Jbst::from_collection(["c", "b", "a"]) );
+        assert_tree_eq!( Jblst::from_collection(["b", "a", "a"]), Jblst::from_collection(["a", "b", "a"]) );
+        assert_tree_eq!( Javlt::from_collection(["a", "b", "c"]), Jbst::from_collection(["c", "b", "a", "d"]) );
+    }
+
+}
@@ -0,0 +1,191 @@
+use std::{cmp::Ordering, fmt, mem, time::SystemTime};
+
+use crate::javlt::{Cursor, Javlt};
+
+
+
+/// # Time Index
+///
+/// Values stamped with the time they happened (or any other ordered, copyable point on a timeline: an
+/// `Instant`, or a `Duration` since some start), kept in time order so they can be looked up by time.
+/// Several values can share a timestamp; they're kept in the order they were inserted.
+///
+///     use std::time::{Duration, SystemTime};
+///     use jtree::time_index::TimeIndex;
+///
+///     let start = SystemTime::UNIX_EPOCH;
+///     let at = |secs| start + Duration::from_secs(secs);
+///     let mut readings = TimeIndex::new();
+///     readings.insert(at(10), 20.5);
+///     readings.insert(at(20), 21.0);
+///     readings.insert(at(30), 19.5);
+///     assert_eq!( vec!((at(10), &20.5), (at(20), &21.0)), readings.between(at(0), at(30)).collect::<Vec<_>>() );
+///     assert_eq!( Some((at(20), &21.0)), readings.latest_before(at(25)) );
+///     assert_eq!( Some((at(30), &19.5)), readings.earliest_after(at(20)) );
+pub struct TimeIndex<V, T: PartialOrd + Copy = SystemTime> {
+    entries: Javlt<Entry<T, V>>,
+    next_seq: u64, // orders values with the same timestamp by when they were inserted
+}
+
+/// A value and its timestamp, compared by the timestamp, then the order of insertion.
+struct Entry<T, V> {
+    at: T,
+    seq: u64,
+    value: Option<V>, // None only in a lookup
+}
+
+impl <T: PartialOrd, V> PartialEq for Entry<T, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.at == other.at && self.seq == other.seq
+    }
+}
+
+impl <T: PartialOrd, V> PartialOrd for Entry<T, V> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        return match self.at.partial_cmp(&other.at)? {
+            Ordering::Equal => self.seq.partial_cmp(&other.seq),
+            ordering => Some(ordering),
+        };
+    }
+}
+
+impl <V, T: PartialOrd + Copy> TimeIndex<V, T> {
+
+    /// Create a new index with no values
+    pub fn new() -> Self {
+        Self {
+            entries: Javlt::new(),
+            next_seq: 0,
+        }
+    }
+
+    /// Store a value at the given time, after any values already stored at the same time.
+    pub fn insert(&mut self, at: T, value: V) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        let _ = self.entries.add(Entry { at, seq, value: Some(value) });
+    }
+
+    /// Iterate over the values from `from` (inclusive) up to `until` (exclusive), in time order.
+    pub fn between(&self, from: T, until: T) -> impl Iterator<Item = (T, &V)> {
+        Self::values(self.entries.cursor_at(&Self::lookup(from, 0))).take_while(move |(at, _)| *at < until)
+    }
+
+    /// The latest value from strictly before `at`, if there is one (of several at that time, the last inserted).
+    pub fn latest_before(&self, at: T) -> Option<(T, &V)> {
+        let mut cursor = self.entries.cursor_at(&Self::lookup(at, 0));
+        cursor.prev().and_then(Self::pair)
+    }
+
+    /// The earliest value from strictly after `at`, if there is one (of several at that time, the first inserted).
+    pub fn earliest_after(&self, at: T) -> Option<(T, &V)> {
+        self.entries.cursor_at(&Self::lookup(at, u64::MAX)).peek().and_then(Self::pair)
+    }
+
+    /// Iterate over all the values, in time order.
+    pub fn iter(&self) -> impl Iterator<Item = (T, &V)> {
+        Self::values(self.entries.cursor_front())
+    }
+
+    /// Delete the values from before `until`, returning them with their timestamps in time order.  Takes O(n)
+    /// time, however few values go.
+    pub fn remove_before(&mut self, until: T) -> Vec<(T, V)> {
+        let (removed, kept) = mem::take(&mut self.entries).partition(|entry| entry.at < until);
+        self.entries = kept;
+        removed.into_sorted_vec().into_iter().filter_map(|entry| entry.value.map(|value| (entry.at, value))).collect()
+    }
+
+    /// Get the number of values held
+    pub fn get_size(&self) -> u32 {
+        self.entries.get_size()
+    }
+
+    /// An entry with no value, to find a position in time order.
+    fn lookup(at: T, seq: u64) -> Entry<T, V> {
+        Entry { at, seq, value: None }
+    }
+
+    fn pair(entry: &Entry<T, V>) -> Option<(T, &V)> {
+        entry.value.as_ref().map(|value| (entry.at, value))
+    }
+
+    /// The values from the cursor's position onwards.
+    fn values(cursor: Cursor<'_, Entry<T, V>>) -> impl Iterator<Item = (T, &V)> {
+        cursor.peek().into_iter().chain(cursor).filter_map(Self::pair)
+    }
+
+}
+
+impl <V, T: PartialOrd + Copy> Default for TimeIndex<V, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <V: fmt::Debug, T: PartialOrd + Copy + fmt::Debug> fmt::Debug for TimeIndex<V, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_list().entries(self.iter()).finish()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn lookups_by_time() {
+        // offsets from the start of a run, rather than wall clock times
+        let secs = Duration::from_secs;
+        let mut events: TimeIndex<&str, Duration> = TimeIndex::new();
+        for (at, event) in [(5, "b"), (1, "a"), (5, "c"), (9, "d"), (5, "e")] {
+            events.insert(secs(at), event);
+        }
+        let between = |from, until| events.between(secs(from), secs(until)).map(|(_, event)| *event).collect::<Vec<_>>();
+        assert_eq!( vec!("b", "c", "e"), between(5, 9) );
+        assert_eq!( vec!("a", "b", "c", "e", "d"), between(0, 10) );
+        assert!( between(6, 9).is_empty() && between(9, 5).is_empty() );
+        assert_eq!( Some((secs(5), &"e")), events.latest_before(secs(9)) );
+        assert_eq!( Some((secs(1), &"a")), events.latest_before(secs(5)) );
+        assert_eq!( None, events.latest_before(secs(1)) );
+        assert_eq!( Some((secs(5), &"b")), events.earliest_after(secs(1)) );
+        assert_eq!( Some((secs(9), &"d")), events.earliest_after(secs(5)) );
+        assert_eq!( None, events.earliest_after(secs(9)) );
+
+        assert_eq!( vec!((secs(1), "a"), (secs(5), "b"), (secs(5), "c"), (secs(5), "e")), events.remove_before(secs(9)) );
+        assert_eq!( 1, events.get_size() );
+        events.insert(secs(9), "f");
+        assert_eq!( vec!("d", "f"), events.iter().map(|(_, event)| *event).collect::<Vec<_>>() );
+    }
+
+    #[test]
+    fn empty_and_exhausted_indexes() {
+        let mut readings: TimeIndex<u32, u32> = TimeIndex::default();
+        assert_eq!( None, readings.latest_before(10) );
+        assert_eq!( None, readings.earliest_after(0) );
+        assert!( readings.remove_before(10).is_empty() );
+        assert_eq!( "[]", format!("{readings:?}") );
+        readings.insert(3, 30);
+        readings.insert(7, 70);
+        readings.insert(3, 31);
+        assert_eq!( "[(3, 30), (3, 31), (7, 70)]", format!("{readings:?}") );
+        // nothing is before the earliest time, and everything is before one past the latest
+        assert!( readings.remove_before(3).is_empty() );
+        assert_eq!( vec!((3, 30), (3, 31), (7, 70)), readings.remove_before(8) );
+        assert_eq!( 0, readings.get_size() );
+        assert_eq!( None, readings.iter().next() );
+    }
+
+    #[test]
+    fn wall_clock_times_by_default() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut log = TimeIndex::new();
+        log.insert(start + Duration::from_millis(250), "second");
+        log.insert(start, "first");
+        assert_eq!( Some((start, &"first")), log.latest_before(start + Duration::from_millis(1)) );
+        assert_eq!( vec!(&"first", &"second"), log.iter().map(|(_, event)| event).collect::<Vec<_>>() );
+    }
+
+}
@@ -0,0 +1,309 @@
+use std::{cmp::Ordering, fmt, ops::{Bound, RangeBounds}};
+
+use crate::errors::TreeError;
+use crate::persistent::PersistentJavlt;
+
+
+
+/// # Versioned Map
+///
+/// An ordered map that keeps its past.  Changes go into a working copy, and each `commit()` records the
+/// working copy as a new version with an id (counting up from 1).  Reads can target the working copy or
+/// any version still retained, so a reader can keep looking at the map as it was at one commit while
+/// later changes are made (point-in-time reads, as in multi-version concurrency control).
+///
+///     use jtree::versioned::VersionedMap;
+///
+///     let mut map = VersionedMap::new();
+///     map.insert("apple", 1);
+///     map.insert("pear", 2);
+///     let first = map.commit();
+///     map.insert("apple", 10);
+///     map.remove(&"pear");
+///     let second = map.commit();
+///     assert_eq!( Ok(Some(&1)), map.get_at(first, &"apple") );
+///     assert_eq!( Ok(Some(&10)), map.get_at(second, &"apple") );
+///     assert_eq!( Ok(None), map.get_at(second, &"pear") );
+///
+/// The versions are `PersistentJavlt`s, which share all the nodes a commit didn't change, so each version
+/// costs only the O(log(n)) nodes copied by each change since the one before.  Call
+/// `forget_versions_before` to let the oldest versions go.
+pub struct VersionedMap<K: PartialEq + PartialOrd + Clone, V: Clone> {
+    working: PersistentJavlt<Entry<K, V>>, // the uncommitted working copy
+    versions: Vec<(u64, PersistentJavlt<Entry<K, V>>)>, // the retained versions, oldest first
+    next_version: u64,
+}
+
+#[derive(Clone)]
+struct Entry<K, V> {
+    key: K,
+    value: Option<V>, // None only in a lookup
+}
+
+impl <K: PartialEq, V> PartialEq for Entry<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl <K: PartialOrd, V> PartialOrd for Entry<K, V> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.key.partial_cmp(&other.key)
+    }
+}
+
+impl <K: PartialEq + PartialOrd + Clone, V: Clone> VersionedMap<K, V> {
+
+    /// Create a new map with no entries and no versions
+    pub fn new() -> Self {
+        Self {
+            working: PersistentJavlt::new(),
+            versions: Vec::new(),
+            next_version: 1,
+        }
+    }
+
+    /// Insert a value for the key in the working copy, returning the value it replaced, if any.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let old = self.remove(&key);
+        let _ = self.working.add(Entry { key, value: Some(value) });
+        return old;
+    }
+
+    /// Remove the key from the working copy, returning its value if it was there.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let lookup = Self::lookup(key);
+        let old = match self.working.get(&lookup) {
+            Some(entry) => entry.value.clone(),
+            None => return None,
+        };
+        let _ = self.working.drop_value(lookup);
+        return old;
+    }
+
+    /// Get the value for the key in the working copy, including any uncommitted changes.
+    pub fn get(&self, key: &K) -> Option<&V> {
+        Self::value_in(&self.working, key)
+    }
+
+    /// Get the number of entries in the working copy
+    pub fn get_size(&self) -> u32 {
+        self.working.get_size()
+    }
+
+    /// Call `f` on each key and a mutable reference to its value in the working copy, in key order, to change
+    /// the values in place in one traversal.  Committed versions keep the values they had: any node a version
+    /// still shares is copied before it's changed.
+    pub fn for_each_mut<F: FnMut(&K, &mut V)>(&mut self, mut f: F) {
+        self.working.visit_in_order_mut(|entry| {
+            match &mut entry.value {
+                Some(value) => f(&entry.key, value),
+                None => {},
+            }
+        });
+    }
+
+    /// Build a new map from the working copy, with each value replaced by what `f` makes of it.  The new map
+    /// has no versions yet.  If `f` fails for any entry, its error is returned and no map is built.  The keys
+    /// are already in order, so the new map is built in O(n) rather than by inserting each entry.
+    ///
+    ///     use jtree::versioned::VersionedMap;
+    ///
+    ///     let mut map = VersionedMap::new();
+    ///     map.insert("a", 1.5f64);
+    ///     map.insert("b", -2.0);
+    ///     let checked = map.map_values(|_, value| if *value >= 0.0 { Ok(value.sqrt()) } else { Err("negative") });
+    ///     assert_eq!( Err("negative"), checked.map(|roots| roots.get_size()) );
+    ///     map.remove(&"b");
+    ///     let roots = map.map_values(|_, value| Ok::<f64, ()>(value.sqrt())).unwrap();
+    ///     assert_eq!( Some(&1.5f64.sqrt()), roots.get(&"a") );
+    pub fn map_values<U: Clone, E, F: FnMut(&K, &V) -> Result<U, E>>(&self, mut f: F) -> Result<VersionedMap<K, U>, E> {
+        let mut entries = Vec::with_capacity(self.working.get_size() as usize);
+        let mut failure = None;
+        self.working.visit_in_order(|entry| {
+            match (&failure, &entry.value) {
+                (None, Some(value)) => match f(&entry.key, value) {
+                    Ok(mapped) => entries.push(Entry { key: entry.key.clone(), value: Some(mapped) }),
+                    Err(error) => failure = Some(error),
+                },
+                _ => {},
+            }
+        });
+        if let Some(error) = failure {
+            return Err(error);
+        }
+        Ok(VersionedMap {
+            working: PersistentJavlt::from_sorted_vec(entries),
+            versions: Vec::new(),
+            next_version: 1,
+        })
+    }
+
+    /// Record the working copy as a new version, returning its id.  Committing costs O(1): the version
+    /// shares every node with the working copy until the next change.
+    pub fn commit(&mut self) -> u64 {
+        let version = self.next_version;
+        self.next_version += 1;
+        self.versions.push((version, self.working.clone()));
+        return version;
+    }
+
+    /// Get the value the key had in the given version.  A TreeError::ValueNotFound is returned if that
+    /// version was never committed or has been forgotten.
+    pub fn get_at(&self, version: u64, key: &K) -> Result<Option<&V>,TreeError<u64>> {
+        let tree = self.version(version)?;
+        Ok(Self::value_in(tree, key))
+    }
+
+    /// Get the entries whose keys are within the range in the given version, in key order.  A
+    /// TreeError::ValueNotFound is returned if that version was never committed or has been forgotten.
+    ///
+    ///     use jtree::versioned::VersionedMap;
+    ///
+    ///     let mut map = VersionedMap::new();
+    ///     for n in 1..=5 {
+    ///         map.insert(n, n * 10);
+    ///     }
+    ///     let version = map.commit();
+    ///     map.remove(&3);
+    ///     assert_eq!( Ok(vec!((&2,&20),(&3,&30),(&4,&40))), map.range_at(version, 2..5) );
+    pub fn range_at<R: RangeBounds<K>>(&self, version: u64, range: R) -> Result<Vec<(&K,&V)>,TreeError<u64>> {
+        let tree = self.version(version)?;
+        let lower = Self::lookup_bound(range.start_bound());
+        let upper = Self::lookup_bound(range.end_bound());
+        let entries = tree.range((lower, upper)).into_iter()
+            .filter_map(|entry| entry.value.as_ref().map(|value| (&entry.key, value)))
+            .collect();
+        Ok(entries)
+    }
+
+    /// Returns the id of the most recent version, or None if nothing has been committed.
+    pub fn latest_version(&self) -> Option<u64> {
+        self.versions.last().map(|(version, _)| *version)
+    }
+
+    /// Returns the ids of the retained versions, oldest first.
+    pub fn versions(&self) -> Vec<u64> {
+        self.versions.iter().map(|(version, _)| *version).collect()
+    }
+
+    /// Forget every version older than the given one.  Their nodes are freed once no other version
+    /// shares them.
+    pub fn forget_versions_before(&mut self, version: u64) {
+        self.versions.retain(|(retained, _)| *retained >= version);
+    }
+
+    /// Find a retained version by its id.
+    fn version(&self, version: u64) -> Result<&PersistentJavlt<Entry<K, V>>,TreeError<u64>> {
+        match self.versions.binary_search_by_key(&version, |(id, _)| *id) {
+            Ok(index) => Ok(&self.versions[index].1),
+            Err(_) => Err(TreeError::ValueNotFound { value: version }),
+        }
+    }
+
+    fn value_in<'a>(tree: &'a PersistentJavlt<Entry<K, V>>, key: &K) -> Option<&'a V> {
+        match tree.get(&Self::lookup(key)) {
+            Some(entry) => entry.value.as_ref(),
+            None => None,
+        }
+    }
+
+    fn lookup(key: &K) -> Entry<K, V> {
+        Entry { key: key.clone(), value: None }
+    }
+
+    fn lookup_bound(bound: Bound<&K>) -> Bound<Entry<K, V>> {
+        match bound {
+            Bound::Included(key) => Bound::Included(Self::lookup(key)),
+            Bound::Excluded(key) => Bound::Excluded(Self::lookup(key)),
+            Bound::Unbounded => Bound::Unbounded,
+        }
+    }
+
+}
+
+impl <K: PartialEq + PartialOrd + Clone, V: Clone> Default for VersionedMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <K: PartialEq + PartialOrd + Clone + fmt::Debug, V: Clone + fmt::Debug> fmt::Debug for VersionedMap<K, V> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut entries = Vec::new();
+        self.working.visit_in_order(|entry| {
+            match &entry.value {
+                Some(value) => entries.push((entry.key.clone(), value.clone())),
+                None => {},
+            }
+        });
+        fmt.debug_struct("VersionedMap")
+            .field("versions", &self.versions())
+            .field("working", &entries)
+            .finish()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_target_each_version() {
+        let mut map = VersionedMap::new();
+        let mut versions = Vec::new();
+        for n in 0..50 {
+            map.insert(n, n);
+            if n % 2 == 0 && n >= 2 {
+                assert_eq!( Some(n - 2), map.insert(n - 2, n) );
+            }
+            versions.push(map.commit());
+        }
+        map.remove(&10);
+        for (n, version) in versions.iter().enumerate() {
+            let n = n as i32;
+            assert_eq!( Ok(Some(&n)), map.get_at(*version, &n) );
+            assert_eq!( Ok(None), map.get_at(*version, &(n + 1)) );
+            let range = map.range_at(*version, ..).unwrap();
+            assert_eq!( n as usize + 1, range.len() );
+        }
+        assert_eq!( Ok(Some(&12)), map.get_at(versions[12], &10) );
+        assert_eq!( None, map.get(&10) );
+        assert_eq!( 49, map.get_size() );
+    }
+
+    #[test]
+    fn forgotten_versions_are_not_found() {
+        let mut map = VersionedMap::new();
+        assert_eq!( None, map.latest_version() );
+        map.insert('a', 1);
+        let first = map.commit();
+        map.insert('b', 2);
+        let second = map.commit();
+        assert_eq!( vec!(first, second), map.versions() );
+        map.forget_versions_before(second);
+        assert_eq!( Err(TreeError::ValueNotFound { value: first }), map.get_at(first, &'a') );
+        assert_eq!( Err(TreeError::ValueNotFound { value: 99 }), map.range_at(99, 'a'..) );
+        assert_eq!( Ok(vec!((&'b',&2))), map.range_at(second, 'b'..='z') );
+        assert_eq!( Some(second), map.latest_version() );
+    }
+
+    #[test]
+    fn changing_values_in_place_leaves_versions_alone() {
+        let mut map = VersionedMap::new();
+        for n in 0..100 {
+            map.insert(n, n);
+        }
+        let version = map.commit();
+        map.for_each_mut(|key, value| *value += key);
+        assert_eq!( Some(&84), map.get(&42) );
+        assert_eq!( Ok(Some(&42)), map.get_at(version, &42) );
+        let halves = map.map_values(|_, value| Ok::<i32, ()>(value / 2)).unwrap();
+        assert_eq!( 100, halves.get_size() );
+        assert_eq!( Some(&42), halves.get(&42) );
+        assert_eq!( None, halves.latest_version() );
+        assert!( halves.working.check_invariants().is_ok() );
+    }
+
+}
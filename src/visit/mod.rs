@@ -0,0 +1,10 @@
+/// The order in which the trees' `visit_*` methods visit a node's value relative to its subtrees.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub(crate) enum Traversal {
+    /// The node's value, then its left subtree, then its right subtree.
+    Pre,
+    /// The left subtree, then the node's value, then the right subtree; i.e. least to greatest.
+    In,
+    /// The left subtree, then the right subtree, then the node's value.
+    Post,
+}
@@ -0,0 +1,145 @@
+//! With the `wasm` feature, `JavltF64` and `JavltString` wrap a `Javlt` of numbers or strings for use from
+//! JavaScript through wasm-bindgen, for visualizations and browser tools.  They're deliberately thin: add,
+//! contains, remove, the size, and the values in order as an array.  From JavaScript:
+//!
+//! ```text
+//! const tree = new JavltF64();
+//! tree.add(2.5);
+//! tree.add(1.0);
+//! tree.contains(2.5);   // true
+//! tree.as_array();      // Float64Array [1, 2.5]
+//! ```
+//!
+//! The same methods can be called from Rust, too:
+//!
+//!     use jtree::wasm::JavltF64;
+//!
+//!     let mut tree = JavltF64::new();
+//!     assert!( tree.add(2.5) );
+//!     assert!( tree.add(1.0) );
+//!     assert!( !tree.add(2.5) );
+//!     assert_eq!( vec!(1.0, 2.5), tree.as_array() );
+
+use wasm_bindgen::prelude::*;
+
+use crate::javlt::Javlt;
+
+/// A `Javlt` of numbers, for JavaScript.  NaN can't be ordered, so it's never added.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct JavltF64 {
+    tree: Javlt<f64>,
+}
+
+#[wasm_bindgen]
+impl JavltF64 {
+
+    /// Create a new tree with no data
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self { tree: Javlt::new() }
+    }
+
+    /// Insert a value, returning false if it was already in the tree (or is NaN).
+    pub fn add(&mut self, value: f64) -> bool {
+        if value.is_nan() {
+            return false;
+        }
+        self.tree.add(value).is_ok()
+    }
+
+    /// Returns true if the value is in the tree
+    pub fn contains(&self, value: f64) -> bool {
+        self.tree.contains(&value)
+    }
+
+    /// Delete the value, returning false if it wasn't in the tree.
+    pub fn remove(&mut self, value: f64) -> bool {
+        self.tree.drop_value(value).is_ok()
+    }
+
+    /// The number of values in the tree
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> u32 {
+        self.tree.get_size()
+    }
+
+    /// The values in order from least to greatest (a `Float64Array` in JavaScript)
+    pub fn as_array(&self) -> Vec<f64> {
+        self.tree.as_vec()
+    }
+
+}
+
+/// A `Javlt` of strings, for JavaScript.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct JavltString {
+    tree: Javlt<String>,
+}
+
+#[wasm_bindgen]
+impl JavltString {
+
+    /// Create a new tree with no data
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self { tree: Javlt::new() }
+    }
+
+    /// Insert a value, returning false if it was already in the tree.
+    pub fn add(&mut self, value: String) -> bool {
+        self.tree.add(value).is_ok()
+    }
+
+    /// Returns true if the value is in the tree
+    pub fn contains(&self, value: &str) -> bool {
+        self.tree.contains(&value.to_string())
+    }
+
+    /// Delete the value, returning false if it wasn't in the tree.
+    pub fn remove(&mut self, value: String) -> bool {
+        self.tree.drop_value(value).is_ok()
+    }
+
+    /// The number of values in the tree
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> u32 {
+        self.tree.get_size()
+    }
+
+    /// The values in order from least to greatest (an array of strings in JavaScript)
+    pub fn as_array(&self) -> Vec<String> {
+        self.tree.as_vec()
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrappers_add_and_remove() {
+        let mut numbers = JavltF64::new();
+        assert!( !numbers.add(f64::NAN) );
+        for n in [3.0, -1.5, 2.0, 3.0] {
+            numbers.add(n);
+        }
+        assert_eq!( 3, numbers.size() );
+        assert!( numbers.remove(2.0) );
+        assert!( !numbers.remove(2.0) );
+        assert_eq!( vec!(-1.5, 3.0), numbers.as_array() );
+
+        let mut words = JavltString::new();
+        for word in ["pear", "fig", "apple", "fig"] {
+            words.add(word.to_string());
+        }
+        assert!( words.contains("fig") );
+        assert!( words.remove(String::from("fig")) );
+        assert!( !words.contains("fig") );
+        assert_eq!( vec!("apple", "pear"), words.as_array() );
+    }
+
+}
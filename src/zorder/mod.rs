@@ -0,0 +1,331 @@
+//! # Z-order spatial index
+//!
+//! A point `(x, y)` on a 2^32 by 2^32 grid becomes a single `u64` key by interleaving the bits of its
+//! coordinates (its Morton code, or Z-order key): points near each other on the grid mostly get keys near
+//! each other, and every square of the grid aligned to a power of two is one unbroken range of keys.  So a
+//! `ZOrderIndex` keeps its points as keys in a `RadixSet`, and finds the points in a rectangle by scanning
+//! the keys from its least corner's to its greatest corner's, jumping straight to the next key inside the
+//! rectangle whenever the scan strays out of it (the BIGMIN step of Tropf and Herzog): a lightweight index
+//! for point data.
+//!
+//!     use jtree::zorder::ZOrderIndex;
+//!
+//!     let mut shops = ZOrderIndex::new();
+//!     for point in [(10, 10), (12, 40), (35, 12), (500, 500)] {
+//!         shops.insert(point).unwrap();
+//!     }
+//!     let mut nearby = shops.query_rect((0, 0), (40, 40));
+//!     nearby.sort();
+//!     assert_eq!( vec!((10, 10), (12, 40), (35, 12)), nearby );
+//!
+//! Other coordinates (such as latitude and longitude) can be scaled onto the grid first.
+
+use std::ops::RangeInclusive;
+
+use crate::errors::TreeError;
+use crate::ordered::OrderedSet;
+use crate::radix::RadixSet;
+
+/// Interleave the bits of the coordinates into a Z-order key: bit i of `x` becomes bit 2i, and bit i of `y`
+/// becomes bit 2i+1.
+pub fn interleave(x: u32, y: u32) -> u64 {
+    spread(x) | (spread(y) << 1)
+}
+
+/// Split a Z-order key back into its coordinates.
+pub fn deinterleave(key: u64) -> (u32, u32) {
+    (gather(key), gather(key >> 1))
+}
+
+/// Move bit i of the value to bit 2i.
+fn spread(value: u32) -> u64 {
+    let mut bits = u64::from(value);
+    bits = (bits | (bits << 16)) & 0x0000_ffff_0000_ffff;
+    bits = (bits | (bits << 8)) & 0x00ff_00ff_00ff_00ff;
+    bits = (bits | (bits << 4)) & 0x0f0f_0f0f_0f0f_0f0f;
+    bits = (bits | (bits << 2)) & 0x3333_3333_3333_3333;
+    (bits | (bits << 1)) & 0x5555_5555_5555_5555
+}
+
+/// Move bit 2i of the key to bit i, the reverse of `spread`.
+fn gather(key: u64) -> u32 {
+    let mut bits = key & 0x5555_5555_5555_5555;
+    bits = (bits | (bits >> 1)) & 0x3333_3333_3333_3333;
+    bits = (bits | (bits >> 2)) & 0x0f0f_0f0f_0f0f_0f0f;
+    bits = (bits | (bits >> 4)) & 0x00ff_00ff_00ff_00ff;
+    bits = (bits | (bits >> 8)) & 0x0000_ffff_0000_ffff;
+    ((bits | (bits >> 16)) & 0x0000_0000_ffff_ffff) as u32
+}
+
+/// A square of the grid, `2^level` cells on a side, with its corner at (`x`, `y`) (multiples of the side).
+#[derive(Clone, Copy)]
+struct Square {
+    x: u32,
+    y: u32,
+    level: u32,
+}
+
+impl Square {
+
+    /// The last cell along each side.
+    fn far_corner(&self) -> (u64, u64) {
+        let side = 1u64 << self.level;
+        (u64::from(self.x) + side - 1, u64::from(self.y) + side - 1)
+    }
+
+    /// The square's cells' keys, which are consecutive.
+    fn keys(&self) -> RangeInclusive<u64> {
+        let first = interleave(self.x, self.y);
+        return match self.level {
+            32 => first..=u64::MAX,
+            level => first..=first + ((1u64 << (2 * level)) - 1),
+        };
+    }
+
+    /// The four quarters of the square, in Z order.
+    fn quarters(&self) -> [Square; 4] {
+        let (level, half) = (self.level - 1, 1u32 << (self.level - 1));
+        [
+            Square { x: self.x, y: self.y, level },
+            Square { x: self.x + half, y: self.y, level },
+            Square { x: self.x, y: self.y + half, level },
+            Square { x: self.x + half, y: self.y + half, level },
+        ]
+    }
+
+}
+
+/// Split the rectangle from `min` to `max` (inclusive, as `(x, y)` corners) into at most about `max_ranges`
+/// ranges of Z-order keys, in order, that hold every key in the rectangle.  Unless the rectangle happens to
+/// be made of few enough aligned squares, the ranges also hold some keys from outside it, which a search
+/// will have to skip.
+///
+/// The splitting stops a level short of where the ranges would run out, so a rectangle that few squares
+/// fit can be covered very loosely: at worst (a thin strip across the centre of the grid, say) the ranges
+/// hold nearly every key on the grid.  `ZOrderIndex::query_rect` doesn't rely on them for that reason.
+///
+///     use jtree::zorder::{interleave, z_ranges};
+///
+///     // a 2x2 square aligned on the grid is exactly one range
+///     assert_eq!( vec!(interleave(2, 2)..=interleave(3, 3)), z_ranges((2, 2), (3, 3), 4) );
+pub fn z_ranges(min: (u32, u32), max: (u32, u32), max_ranges: usize) -> Vec<RangeInclusive<u64>> {
+    if min.0 > max.0 || min.1 > max.1 {
+        return Vec::new();
+    }
+    let (min, max) = ((u64::from(min.0), u64::from(min.1)), (u64::from(max.0), u64::from(max.1)));
+    let mut inside = Vec::new(); // squares wholly in the rectangle
+    let mut straddling = vec!(Square { x: 0, y: 0, level: 32 }); // squares partly in it
+    // split the straddling squares into quarters, a level at a time, while there's room for more ranges
+    while !straddling.is_empty() && inside.len() + straddling.len() * 4 <= max_ranges.max(1) {
+        let mut next = Vec::new();
+        for quarter in straddling.iter().flat_map(Square::quarters) {
+            let (far_x, far_y) = quarter.far_corner();
+            let (x, y) = (u64::from(quarter.x), u64::from(quarter.y));
+            if far_x < min.0 || x > max.0 || far_y < min.1 || y > max.1 {
+                continue;
+            }
+            if x >= min.0 && far_x <= max.0 && y >= min.1 && far_y <= max.1 {
+                inside.push(quarter);
+            } else {
+                next.push(quarter);
+            }
+        }
+        straddling = next;
+    }
+    let mut squares = inside;
+    squares.extend(straddling);
+    squares.sort_by_key(|square| interleave(square.x, square.y));
+    // join ranges that meet end to end
+    let mut ranges: Vec<RangeInclusive<u64>> = Vec::with_capacity(squares.len());
+    for keys in squares.iter().map(Square::keys) {
+        match ranges.last_mut() {
+            Some(last) if last.end().checked_add(1) == Some(*keys.start()) => *last = *last.start()..=*keys.end(),
+            _ => ranges.push(keys),
+        }
+    }
+    ranges
+}
+
+/// The least key greater than `key` whose point is in the rectangle with corners at keys `min` and `max`,
+/// for a `key` between them whose point isn't (Tropf and Herzog's BIGMIN), or None if there's no such key.
+fn next_inside(key: u64, min: u64, max: u64) -> Option<u64> {
+    let (mut min, mut max) = (min, max);
+    let mut next = None;
+    for bit in (0..64).rev() {
+        let mask = 1u64 << bit;
+        // the bits below this one that belong to the same coordinate
+        let below = (0x5555_5555_5555_5555u64 << (bit % 2)) & (mask - 1);
+        match (key & mask != 0, min & mask != 0, max & mask != 0) {
+            (false, false, true) => {
+                next = Some((min | mask) & !below);
+                max = (max & !mask) | below;
+            },
+            (false, true, true) => return Some(min),
+            (true, false, false) => return next,
+            (true, false, true) => min = (min | mask) & !below,
+            _ => {},
+        }
+    }
+    next
+}
+
+/// A set of points on a 2^32 by 2^32 grid, kept in Z order, that can be searched by rectangle.
+#[derive(Default, Debug)]
+pub struct ZOrderIndex {
+    keys: RadixSet,
+}
+
+impl ZOrderIndex {
+
+    /// Create a new index with no points
+    pub fn new() -> Self {
+        Self {
+            keys: RadixSet::new(),
+        }
+    }
+
+    /// Insert a point, returning a TreeError::ValueAlreadyStored if it's already in the index.
+    pub fn insert(&mut self, point: (u32, u32)) -> Result<(),TreeError<(u32, u32)>> {
+        self.keys.add(interleave(point.0, point.1)).map_err(|error| Self::error_at(error, point))
+    }
+
+    /// If the point is in the index, delete it.  Otherwise a TreeError::ValueNotFound will be returned.
+    pub fn remove(&mut self, point: (u32, u32)) -> Result<(),TreeError<(u32, u32)>> {
+        self.keys.drop_value(interleave(point.0, point.1)).map_err(|error| Self::error_at(error, point))
+    }
+
+    /// Returns true if the point is in the index
+    pub fn contains(&self, point: (u32, u32)) -> bool {
+        self.keys.contains(interleave(point.0, point.1))
+    }
+
+    /// Get the number of points in the index
+    pub fn get_size(&self) -> u32 {
+        self.keys.get_size()
+    }
+
+    /// Returns the points in the rectangle from `min` to `max` (inclusive, as `(x, y)` corners), in Z order.
+    ///
+    /// Stored keys between the corners' but outside the rectangle are skipped over a run at a time, so the
+    /// search takes about O(log(n)) time for each such run, rather than for each key.
+    pub fn query_rect(&self, min: (u32, u32), max: (u32, u32)) -> Vec<(u32, u32)> {
+        if min.0 > max.0 || min.1 > max.1 {
+            return Vec::new();
+        }
+        let (first, last) = (interleave(min.0, min.1), interleave(max.0, max.1));
+        let mut found = Vec::new();
+        let mut from = Some(first);
+        while let Some(start) = from.take() {
+            for key in OrderedSet::range(&self.keys, start..=last) {
+                let (x, y) = deinterleave(*key);
+                if (min.0..=max.0).contains(&x) && (min.1..=max.1).contains(&y) {
+                    found.push((x, y));
+                } else {
+                    from = next_inside(*key, first, last);
+                    break;
+                }
+            }
+        }
+        found
+    }
+
+    /// Iterate over all the points, in Z order.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, u32)> {
+        OrderedSet::iter(&self.keys).map(|key| deinterleave(*key))
+    }
+
+    /// The error from the key set, about the point rather than its key.
+    fn error_at(error: TreeError<u64>, point: (u32, u32)) -> TreeError<(u32, u32)> {
+        return match error {
+            TreeError::ValueAlreadyStored { .. } => TreeError::ValueAlreadyStored { value: point },
+            TreeError::ValueNotFound { .. } => TreeError::ValueNotFound { value: point },
+            TreeError::CapacityExceeded { .. } => TreeError::CapacityExceeded { value: point },
+            other => TreeError::InvariantViolation(format!("unexpected error for point {point:?}: {other}")),
+        };
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_round_trip() {
+        for point in [(0, 0), (1, 0), (0, 1), (u32::MAX, 0), (0x1234_5678, 0x9abc_def0), (u32::MAX, u32::MAX)] {
+            assert_eq!( point, deinterleave(interleave(point.0, point.1)) );
+        }
+        assert_eq!( 0b10, interleave(0, 1) );
+        assert_eq!( 0b0110, interleave(0b10, 0b01) );
+    }
+
+    #[test]
+    fn rectangles_find_their_points() {
+        // points scattered with a simple generator, some clustered near the origin
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next_random = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let mut index = ZOrderIndex::new();
+        let mut points = Vec::new();
+        for n in 0..1500u64 {
+            let range = if n % 3 == 0 { 1000 } else { u64::from(u32::MAX) + 1 };
+            let point = ((next_random() % range) as u32, (next_random() % range) as u32);
+            if index.insert(point).is_ok() {
+                points.push(point);
+            }
+        }
+        assert_eq!( Err(TreeError::ValueAlreadyStored { value: points[0] }), index.insert(points[0]) );
+        let rectangles = [((0, 0), (999, 999)), ((17, 250), (600, 260)), ((0, 0), (u32::MAX, u32::MAX)), ((1 << 31, 5), (u32::MAX - 3, 1 << 31)), ((5, 5), (4, 9))];
+        for (min, max) in rectangles {
+            let mut found = index.query_rect(min, max);
+            let mut expected: Vec<(u32, u32)> = points.iter().copied()
+                .filter(|(x, y)| (min.0..=max.0).contains(x) && (min.1..=max.1).contains(y))
+                .collect();
+            found.sort();
+            expected.sort();
+            assert_eq!( expected, found );
+        }
+        // ranges come in order, don't overlap, and cover every cell of the rectangle
+        let ranges = z_ranges((3, 5), (20, 9), 16);
+        assert!( ranges.len() <= 16 );
+        assert!( ranges.windows(2).all(|pair| pair[0].end() < pair[1].start()) );
+        for x in 3..=20 {
+            for y in 5..=9 {
+                assert!( ranges.iter().any(|keys| keys.contains(&interleave(x, y))) );
+            }
+        }
+        assert_eq!( Ok(()), index.remove(points[1]) );
+        assert!( !index.contains(points[1]) );
+        assert_eq!( points.len() as u32 - 1, index.get_size() );
+    }
+
+    #[test]
+    fn scans_jump_to_the_next_key_inside() {
+        let (min, max) = ((3, 2), (9, 6));
+        let (first, last) = (interleave(min.0, min.1), interleave(max.0, max.1));
+        let inside = |key: u64| {
+            let (x, y) = deinterleave(key);
+            (min.0..=max.0).contains(&x) && (min.1..=max.1).contains(&y)
+        };
+        for key in (first..=last).filter(|key| !inside(*key)) {
+            assert_eq!( (key + 1..=last).find(|key| inside(*key)), next_inside(key, first, last) );
+        }
+        // a thin strip across the centre of the grid, which no few squares cover
+        let mut index = ZOrderIndex::new();
+        for x in (0..u32::MAX).step_by(1 << 24) {
+            for y in [0, (1 << 31) - 2, (1 << 31) - 1, 1 << 31, (1 << 31) + 1, u32::MAX] {
+                index.insert((x, y)).unwrap();
+            }
+        }
+        let found = index.query_rect((0, (1 << 31) - 1), (u32::MAX, 1 << 31));
+        assert_eq!( 512, found.len() );
+        assert!( found.iter().all(|(_, y)| *y == (1 << 31) - 1 || *y == 1 << 31) );
+        assert!( found.windows(2).all(|pair| interleave(pair[0].0, pair[0].1) < interleave(pair[1].0, pair[1].1)) );
+    }
+
+}